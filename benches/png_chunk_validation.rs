@@ -0,0 +1,119 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+// Measures how long reading and writing metadata takes for a large PNG, which
+// is dominated by validating the CRC of every chunk (unless skipped) and
+// (de)compressing the zTXt chunk. Compare the default backends with the accelerated ones:
+//
+//     cargo bench --bench png_chunk_validation
+//     cargo bench --bench png_chunk_validation --features crc32fast,zlib-ng
+
+use std::path::Path;
+use std::time::Duration;
+use std::time::Instant;
+
+use crc::Crc;
+use crc::CRC_32_ISO_HDLC;
+
+use little_exif::exif_tag::ExifTag;
+use little_exif::metadata::Metadata;
+use little_exif::read_options::ReadOptions;
+use little_exif::write_options::WriteOptions;
+
+const IDAT_CHUNK_SIZE:  usize = 8 * 1024 * 1024;
+const IDAT_CHUNK_COUNT: usize = 8;
+const ITERATIONS:       u32   = 10;
+
+fn
+push_chunk
+(
+	png:        &mut Vec<u8>,
+	chunk_type: &[u8; 4],
+	data:       &[u8]
+)
+{
+	let crc_struct = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+	let mut digest = crc_struct.digest();
+	digest.update(chunk_type);
+	digest.update(data);
+
+	png.extend((data.len() as u32).to_be_bytes());
+	png.extend(chunk_type);
+	png.extend(data);
+	png.extend(digest.finalize().to_be_bytes());
+}
+
+/// Builds a PNG with 64 MiB of (not actually decodable) image data
+fn
+build_large_png()
+-> Vec<u8>
+{
+	let mut png = vec![0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a];
+	push_chunk(&mut png, b"IHDR", &[0, 0, 16, 0, 0, 0, 16, 0, 8, 2, 0, 0, 0]);
+
+	// Cheap pseudo random bytes so that nothing can take shortcuts
+	let mut state = 0x2545f491u32;
+	let idat_data: Vec<u8> = (0..IDAT_CHUNK_SIZE).map(|_| {
+		state ^= state << 13;
+		state ^= state >> 17;
+		state ^= state << 5;
+		state as u8
+	}).collect();
+
+	for _ in 0..IDAT_CHUNK_COUNT
+	{
+		push_chunk(&mut png, b"IDAT", &idat_data);
+	}
+	push_chunk(&mut png, b"IEND", &[]);
+	return png;
+}
+
+fn
+measure
+(
+	name:   &str,
+	mut f:  impl FnMut()
+)
+{
+	let mut total = Duration::ZERO;
+	for _ in 0..ITERATIONS
+	{
+		let start = Instant::now();
+		f();
+		total += start.elapsed();
+	}
+
+	let per_iteration = total / ITERATIONS;
+	let throughput = (IDAT_CHUNK_SIZE * IDAT_CHUNK_COUNT) as f64 / per_iteration.as_secs_f64() / (1024.0 * 1024.0);
+	println!("{:<24} {:>10.2?} per iteration ({:>8.1} MiB/s)", name, per_iteration, throughput);
+}
+
+fn
+main()
+{
+	let path_buf = std::env::temp_dir().join("little_exif_png_chunk_validation.png");
+	let path = Path::new(&path_buf);
+	std::fs::write(path, build_large_png()).unwrap();
+
+	println!(
+		"crc32fast: {}, zlib-ng: {}",
+		cfg!(feature = "crc32fast"),
+		cfg!(feature = "zlib-ng")
+	);
+
+	let mut metadata = Metadata::new();
+	metadata.set_tag(ExifTag::ImageDescription("Benchmark".repeat(1000)));
+	metadata.set_tag(ExifTag::ISO(vec![100]));
+
+	let validating_write = WriteOptions::new();
+	let skipping_write   = WriteOptions::new().validate_crc(false);
+	let validating_read  = ReadOptions::new().validate_crc(true);
+	let skipping_read    = ReadOptions::new();
+
+	measure("write, validating CRCs", || metadata.write_to_file_with_options(path, &validating_write).unwrap());
+	measure("write, skipping CRCs",   || metadata.write_to_file_with_options(path, &skipping_write).unwrap());
+	measure("read, validating CRCs",  || { Metadata::new_from_path_with_options(path, &validating_read).unwrap(); });
+	measure("read, skipping CRCs",    || { Metadata::new_from_path_with_options(path, &skipping_read).unwrap(); });
+
+	std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,8 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+fn
+main()
+{
+	napi_build::setup();
+}
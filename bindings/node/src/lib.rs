@@ -0,0 +1,364 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Node.js bindings for little_exif. Build the native module using the
+//! [napi-rs CLI](https://napi.rs), e.g. `npx @napi-rs/cli build` in this
+//! folder.
+//!
+//! ```js
+//! const littleExif = require('./little_exif.node');
+//!
+//! const tags = littleExif.read('image.jpg');
+//! littleExif.write('image.jpg', { Artist: 'Jane Doe', ISO: [400] });
+//! const stripped = littleExif.stripBuffer(upload, 'jpg');
+//! ```
+//!
+//! Tags are identified by their names. Their values are represented as
+//! - `string` for strings
+//! - `Buffer` for undefined data
+//! - `number[]` for numbers, with rationals stored as consecutive
+//!   numerator/denominator pairs (e.g. `[1, 250]`)
+//!
+//! When writing, a single number may be given instead of an array.
+//! Functions with the suffix `Buffer` work on image data in memory and
+//! require the type of the image (`'jpg'`, `'png'`, `'webp'`, `'tif'`, `'heic'`, `'avif'`, `'gif'`, `'cr2'`, `'cr3'`, `'orf'`, `'rw2'`, `'raf'` or `'avi'`).
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use napi::bindgen_prelude::Buffer;
+use napi::bindgen_prelude::Either3;
+use napi::bindgen_prelude::Either4;
+use napi::Error;
+use napi::Result;
+use napi_derive::napi;
+
+use little_exif::endian::Endian;
+use little_exif::exif_tag::ExifTag;
+use little_exif::exif_tag::ExifTagGroup;
+use little_exif::exif_tag_format::ExifTagFormat;
+use little_exif::filetype::FileExtension;
+use little_exif::metadata::Metadata;
+
+type TagValue      = Either3<String, Buffer, Vec<f64>>;
+type InputTagValue = Either4<String, Buffer, Vec<f64>, f64>;
+
+/// Reads the tags of the image at the given path
+#[napi]
+pub fn
+read
+(
+	path: String
+)
+-> Result<HashMap<String, TagValue>>
+{
+	Ok(tags_to_map(&Metadata::new_from_path(Path::new(&path)).map_err(to_napi_error)?))
+}
+
+/// Reads the tags of the given image data
+#[napi]
+pub fn
+read_buffer
+(
+	data:      Buffer,
+	file_type: String
+)
+-> Result<HashMap<String, TagValue>>
+{
+	let metadata = Metadata::new_from_vec(&data.to_vec(), parse_file_type(&file_type)?).map_err(to_napi_error)?;
+	Ok(tags_to_map(&metadata))
+}
+
+/// Sets the given tags in the image at the given path. Other tags that are
+/// already stored in the image are kept
+#[napi]
+pub fn
+write
+(
+	path: String,
+	tags: HashMap<String, InputTagValue>
+)
+-> Result<()>
+{
+	let path = Path::new(&path);
+	let mut metadata = Metadata::new_from_path(path).map_err(to_napi_error)?;
+	set_tags(&mut metadata, tags)?;
+	metadata.write_to_file(path).map_err(to_napi_error)
+}
+
+/// Sets the given tags in the given image data and returns the new data.
+/// Other tags that are already stored in the image are kept
+#[napi]
+pub fn
+write_buffer
+(
+	data:      Buffer,
+	file_type: String,
+	tags:      HashMap<String, InputTagValue>
+)
+-> Result<Buffer>
+{
+	let file_type = parse_file_type(&file_type)?;
+	let mut data = data.to_vec();
+	let mut metadata = Metadata::new_from_vec(&data, file_type).map_err(to_napi_error)?;
+	set_tags(&mut metadata, tags)?;
+	metadata.write_to_vec(&mut data, file_type).map_err(to_napi_error)?;
+	Ok(data.into())
+}
+
+/// Removes all EXIF data from the image at the given path
+#[napi]
+pub fn
+strip
+(
+	path: String
+)
+-> Result<()>
+{
+	Metadata::file_clear_metadata(Path::new(&path)).map_err(to_napi_error)
+}
+
+/// Removes all EXIF data from the given image data and returns the new data
+#[napi]
+pub fn
+strip_buffer
+(
+	data:      Buffer,
+	file_type: String
+)
+-> Result<Buffer>
+{
+	let mut data = data.to_vec();
+	Metadata::clear_metadata(&mut data, parse_file_type(&file_type)?).map_err(to_napi_error)?;
+	Ok(data.into())
+}
+
+fn
+to_napi_error
+(
+	error: std::io::Error
+)
+-> Error
+{
+	Error::from_reason(error.to_string())
+}
+
+fn
+parse_file_type
+(
+	file_type: &str
+)
+-> Result<FileExtension>
+{
+	FileExtension::from_str(&file_type.to_lowercase())
+		.map_err(|_| Error::from_reason(format!("Unsupported file type {}!", file_type)))
+}
+
+fn
+tags_to_map
+(
+	metadata: &Metadata
+)
+-> HashMap<String, TagValue>
+{
+	metadata.data().iter()
+		.map(|tag| (tag.name(), tag_value_to_js(tag)))
+		.collect()
+}
+
+/// Converts the value of the tag to the representation described in the
+/// module documentation
+fn
+tag_value_to_js
+(
+	tag: &ExifTag
+)
+-> TagValue
+{
+	let raw_data = tag.value_as_u8_vec(&Endian::Little);
+
+	match tag.format()
+	{
+		ExifTagFormat::STRING
+			=> Either3::A(String::from_utf8_lossy(&raw_data).trim_end_matches('\0').to_string()),
+		ExifTagFormat::UNDEF
+			=> Either3::B(raw_data.into()),
+		format
+			=> Either3::C(decode_numbers(&raw_data, &format)),
+	}
+}
+
+/// Decodes the little endian raw data of a numeric tag
+fn
+decode_numbers
+(
+	raw_data: &[u8],
+	format:   &ExifTagFormat
+)
+-> Vec<f64>
+{
+	let values = |bytes_per_component: usize, convert: &dyn Fn(&[u8]) -> f64| -> Vec<f64> {
+		raw_data.chunks_exact(bytes_per_component).map(convert).collect()
+	};
+
+	match format
+	{
+		ExifTagFormat::INT8U                               => values(1, &|bytes| bytes[0] as f64),
+		ExifTagFormat::INT8S                               => values(1, &|bytes| bytes[0] as i8 as f64),
+		ExifTagFormat::INT16U                              => values(2, &|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as f64),
+		ExifTagFormat::INT16S                              => values(2, &|bytes| i16::from_le_bytes([bytes[0], bytes[1]]) as f64),
+		ExifTagFormat::INT32U | ExifTagFormat::RATIONAL64U => values(4, &|bytes| u32::from_le_bytes(bytes.try_into().unwrap()) as f64),
+		ExifTagFormat::INT32S | ExifTagFormat::RATIONAL64S => values(4, &|bytes| i32::from_le_bytes(bytes.try_into().unwrap()) as f64),
+		ExifTagFormat::FLOAT                               => values(4, &|bytes| f32::from_le_bytes(bytes.try_into().unwrap()) as f64),
+		ExifTagFormat::DOUBLE                              => values(8, &|bytes| f64::from_le_bytes(bytes.try_into().unwrap())),
+		_                                                  => Vec::new(),
+	}
+}
+
+/// Encodes the numbers as little endian raw data of the given format
+fn
+encode_numbers
+(
+	numbers: &[f64],
+	format:  &ExifTagFormat
+)
+-> Vec<u8>
+{
+	numbers.iter()
+		.flat_map(|number| match format
+		{
+			ExifTagFormat::INT8U                               => vec![*number as u8],
+			ExifTagFormat::INT8S                               => vec![*number as i8 as u8],
+			ExifTagFormat::INT16U                              => (*number as u16).to_le_bytes().to_vec(),
+			ExifTagFormat::INT16S                              => (*number as i16).to_le_bytes().to_vec(),
+			ExifTagFormat::INT32U | ExifTagFormat::RATIONAL64U => (*number as u32).to_le_bytes().to_vec(),
+			ExifTagFormat::INT32S | ExifTagFormat::RATIONAL64S => (*number as i32).to_le_bytes().to_vec(),
+			ExifTagFormat::FLOAT                               => (*number as f32).to_le_bytes().to_vec(),
+			ExifTagFormat::DOUBLE                              => number.to_le_bytes().to_vec(),
+			_                                                  => Vec::new(),
+		})
+		.collect()
+}
+
+fn
+set_tags
+(
+	metadata: &mut Metadata,
+	tags:     HashMap<String, InputTagValue>
+)
+-> Result<()>
+{
+	for (name, value) in tags
+	{
+		metadata.set_tag(tag_from_js(&name, value)?);
+	}
+	Ok(())
+}
+
+/// Looks up the tag with the given name, searching the tag IDs of all IFDs
+/// (the GPS and the Interop IFD have their own ones)
+fn
+get_tag_by_name
+(
+	name: &str
+)
+-> Option<ExifTag>
+{
+	static TAGS: OnceLock<HashMap<String, ExifTag>> = OnceLock::new();
+
+	TAGS.get_or_init(|| {
+		[ExifTagGroup::IFD0, ExifTagGroup::GPSIFD, ExifTagGroup::InteropIFD].iter()
+			.flat_map(|group| (0..=u16::MAX).filter_map(|hex_value| ExifTag::from_u16_in_group(hex_value, group).ok()))
+			.map(|tag| (tag.name(), tag))
+			.collect()
+	}).get(name).cloned()
+}
+
+/// Constructs the tag with the given name from its JavaScript representation
+fn
+tag_from_js
+(
+	name:  &str,
+	value: InputTagValue
+)
+-> Result<ExifTag>
+{
+	let tag = get_tag_by_name(name)
+		.ok_or_else(|| Error::from_reason(format!("Unknown tag {}!", name)))?;
+
+	if !tag.is_writable()
+	{
+		return Err(Error::from_reason(format!("Tag {} can't be written!", name)));
+	}
+
+	let format = tag.format();
+	let raw_data = match (&format, value)
+	{
+		// EXIF strings are ASCII
+		(ExifTagFormat::STRING, Either4::A(string)) if string.is_ascii()
+			=> string.into_bytes(),
+		(ExifTagFormat::UNDEF, Either4::B(buffer))
+			=> buffer.to_vec(),
+		(ExifTagFormat::STRING | ExifTagFormat::UNDEF, _)
+			=> return Err(Error::from_reason(format!("Invalid value for tag {}!", name))),
+		(_, Either4::C(numbers))
+			=> encode_numbers(&numbers, &format),
+		(_, Either4::D(number))
+			=> encode_numbers(&[number], &format),
+		_
+			=> return Err(Error::from_reason(format!("Tag {} requires numbers!", name))),
+	};
+
+	ExifTag::from_u16_with_data(tag.as_u16(), &format, &raw_data, &Endian::Little, &tag.get_group())
+		.map_err(Error::from_reason)
+}
+
+#[cfg(test)]
+mod tests
+{
+	use napi::bindgen_prelude::Either4;
+
+	use little_exif::exif_tag::ExifTag;
+	use little_exif::exif_tag_format::ExifTagFormat;
+
+	#[test]
+	fn
+	numbers_round_trip()
+	{
+		for (format, numbers) in [
+			(ExifTagFormat::INT16U,      vec![1.0, 65535.0]),
+			(ExifTagFormat::INT32S,      vec![-5.0]),
+			(ExifTagFormat::RATIONAL64S, vec![-1.0, 3.0]),
+			(ExifTagFormat::DOUBLE,      vec![0.25]),
+		]
+		{
+			let raw_data = crate::encode_numbers(&numbers, &format);
+			assert_eq!(crate::decode_numbers(&raw_data, &format), numbers);
+		}
+	}
+
+	#[test]
+	fn
+	tags_from_js_values()
+	{
+		assert_eq!(
+			crate::tag_from_js("Artist", Either4::A("Jane Doe".to_string())).unwrap(),
+			ExifTag::Artist("Jane Doe".to_string())
+		);
+		assert_eq!(
+			crate::tag_from_js("ExposureTime", Either4::C(vec![1.0, 250.0])).unwrap(),
+			ExifTag::ExposureTime(vec![1, 250])
+		);
+		assert_eq!(
+			crate::tag_from_js("ISO", Either4::D(400.0)).unwrap(),
+			ExifTag::ISO(vec![400])
+		);
+
+		assert!(crate::tag_from_js("NoSuchTag",  Either4::D(1.0)).is_err());
+		assert!(crate::tag_from_js("ExifOffset", Either4::D(1.0)).is_err());
+		assert!(crate::tag_from_js("Artist",     Either4::D(1.0)).is_err());
+		assert!(crate::tag_from_js("Artist",     Either4::A("©".to_string())).is_err());
+	}
+}
@@ -0,0 +1,383 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Python bindings for little_exif. Build and install the module using
+//! [maturin](https://www.maturin.rs), e.g. `maturin develop` in this folder.
+//!
+//! ```python
+//! import little_exif
+//!
+//! tags = little_exif.read("image.jpg")
+//! little_exif.write("image.jpg", {"Artist": "Jane Doe", "ISO": [400]})
+//! little_exif.strip("image.png")
+//! ```
+//!
+//! Tags are identified by their names. Their values are represented as
+//! - `str` for strings
+//! - `bytes` for undefined data
+//! - `list` of `int` or `float` for numbers, with the exception of rationals,
+//!   which are `list` of `(numerator, denominator)` tuples
+//!
+//! When writing, a single number (or tuple) may be given instead of a list.
+//! Functions with the suffix `_bytes` work on image data in memory and
+//! require the type of the image (`"jpg"`, `"png"`, `"webp"`, `"tif"`, `"heic"`, `"avif"`, `"gif"`, `"cr2"`, `"cr3"`, `"orf"`, `"rw2"`, `"raf"` or `"avi"`).
+
+// False positive caused by the code generated for `#[pyfunction]`
+#![allow(clippy::useless_conversion)]
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use pyo3::exceptions::PyKeyError;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use pyo3::types::PyDict;
+
+use little_exif::endian::Endian;
+use little_exif::exif_tag::ExifTag;
+use little_exif::exif_tag::ExifTagGroup;
+use little_exif::exif_tag_format::ExifTagFormat;
+use little_exif::filetype::FileExtension;
+use little_exif::metadata::Metadata;
+
+/// Reads the tags of the image at the given path as dict
+#[pyfunction]
+fn
+read
+<'py>
+(
+	py:   Python<'py>,
+	path: PathBuf
+)
+-> PyResult<Bound<'py, PyDict>>
+{
+	tags_to_dict(py, &Metadata::new_from_path(&path)?)
+}
+
+/// Reads the tags of the given image data as dict
+#[pyfunction]
+fn
+read_bytes
+<'py>
+(
+	py:        Python<'py>,
+	data:      Vec<u8>,
+	file_type: &str
+)
+-> PyResult<Bound<'py, PyDict>>
+{
+	tags_to_dict(py, &Metadata::new_from_vec(&data, parse_file_type(file_type)?)?)
+}
+
+/// Sets the given tags in the image at the given path. Other tags that are
+/// already stored in the image are kept
+#[pyfunction]
+fn
+write
+(
+	path: PathBuf,
+	tags: &Bound<'_, PyDict>
+)
+-> PyResult<()>
+{
+	let mut metadata = Metadata::new_from_path(&path)?;
+	set_tags(&mut metadata, tags)?;
+	metadata.write_to_file(&path)?;
+	Ok(())
+}
+
+/// Sets the given tags in the given image data and returns the new data.
+/// Other tags that are already stored in the image are kept
+#[pyfunction]
+fn
+write_bytes
+<'py>
+(
+	py:        Python<'py>,
+	data:      Vec<u8>,
+	file_type: &str,
+	tags:      &Bound<'py, PyDict>
+)
+-> PyResult<Bound<'py, PyBytes>>
+{
+	let file_type = parse_file_type(file_type)?;
+	let mut data = data;
+	let mut metadata = Metadata::new_from_vec(&data, file_type)?;
+	set_tags(&mut metadata, tags)?;
+	metadata.write_to_vec(&mut data, file_type)?;
+	Ok(PyBytes::new_bound(py, &data))
+}
+
+/// Removes all EXIF data from the image at the given path
+#[pyfunction]
+fn
+strip
+(
+	path: PathBuf
+)
+-> PyResult<()>
+{
+	Metadata::file_clear_metadata(&path)?;
+	Ok(())
+}
+
+/// Removes all EXIF data from the given image data and returns the new data
+#[pyfunction]
+fn
+strip_bytes
+<'py>
+(
+	py:        Python<'py>,
+	data:      Vec<u8>,
+	file_type: &str
+)
+-> PyResult<Bound<'py, PyBytes>>
+{
+	let mut data = data;
+	Metadata::clear_metadata(&mut data, parse_file_type(file_type)?)?;
+	Ok(PyBytes::new_bound(py, &data))
+}
+
+#[pymodule]
+#[pyo3(name = "little_exif")]
+fn
+little_exif_python
+(
+	module: &Bound<'_, PyModule>
+)
+-> PyResult<()>
+{
+	module.add_function(wrap_pyfunction!(read,        module)?)?;
+	module.add_function(wrap_pyfunction!(read_bytes,  module)?)?;
+	module.add_function(wrap_pyfunction!(write,       module)?)?;
+	module.add_function(wrap_pyfunction!(write_bytes, module)?)?;
+	module.add_function(wrap_pyfunction!(strip,       module)?)?;
+	module.add_function(wrap_pyfunction!(strip_bytes, module)?)?;
+	Ok(())
+}
+
+fn
+parse_file_type
+(
+	file_type: &str
+)
+-> PyResult<FileExtension>
+{
+	FileExtension::from_str(&file_type.to_lowercase())
+		.map_err(|_| PyValueError::new_err(format!("Unsupported file type {}!", file_type)))
+}
+
+fn
+tags_to_dict
+<'py>
+(
+	py:       Python<'py>,
+	metadata: &Metadata
+)
+-> PyResult<Bound<'py, PyDict>>
+{
+	let dict = PyDict::new_bound(py);
+	for tag in metadata.data()
+	{
+		dict.set_item(tag.name(), tag_value_to_object(py, tag))?;
+	}
+	Ok(dict)
+}
+
+/// Converts the value of the tag to the Python representation described in
+/// the module documentation
+fn
+tag_value_to_object
+(
+	py:  Python<'_>,
+	tag: &ExifTag
+)
+-> PyObject
+{
+	let raw_data = tag.value_as_u8_vec(&Endian::Little);
+
+	match tag.format()
+	{
+		ExifTagFormat::STRING
+			=> String::from_utf8_lossy(&raw_data).trim_end_matches('\0').into_py(py),
+		ExifTagFormat::UNDEF
+			=> PyBytes::new_bound(py, &raw_data).into_py(py),
+		ExifTagFormat::INT8U
+			=> raw_data.into_py(py),
+		ExifTagFormat::INT8S
+			=> raw_data.iter().map(|byte| *byte as i8).collect::<Vec<i8>>().into_py(py),
+		ExifTagFormat::INT16U
+			=> raw_data.chunks_exact(2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]])).collect::<Vec<u16>>().into_py(py),
+		ExifTagFormat::INT16S
+			=> raw_data.chunks_exact(2).map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]])).collect::<Vec<i16>>().into_py(py),
+		ExifTagFormat::INT32U
+			=> u32_values(&raw_data).into_py(py),
+		ExifTagFormat::INT32S
+			=> u32_values(&raw_data).into_iter().map(|value| value as i32).collect::<Vec<i32>>().into_py(py),
+		ExifTagFormat::RATIONAL64U
+			=> u32_values(&raw_data).chunks_exact(2).map(|pair| (pair[0], pair[1])).collect::<Vec<(u32, u32)>>().into_py(py),
+		ExifTagFormat::RATIONAL64S
+			=> u32_values(&raw_data).chunks_exact(2).map(|pair| (pair[0] as i32, pair[1] as i32)).collect::<Vec<(i32, i32)>>().into_py(py),
+		ExifTagFormat::FLOAT
+			=> u32_values(&raw_data).into_iter().map(f32::from_bits).collect::<Vec<f32>>().into_py(py),
+		ExifTagFormat::DOUBLE
+			=> raw_data.chunks_exact(8).map(|bytes| f64::from_le_bytes(bytes.try_into().unwrap())).collect::<Vec<f64>>().into_py(py),
+	}
+}
+
+fn
+u32_values
+(
+	raw_data: &[u8]
+)
+-> Vec<u32>
+{
+	raw_data.chunks_exact(4).map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap())).collect()
+}
+
+fn
+set_tags
+(
+	metadata: &mut Metadata,
+	tags:     &Bound<'_, PyDict>
+)
+-> PyResult<()>
+{
+	for (name, value) in tags.iter()
+	{
+		let name: String = name.extract()?;
+		metadata.set_tag(tag_from_object(&name, &value)?);
+	}
+	Ok(())
+}
+
+/// Looks up the tag with the given name, searching the tag IDs of all IFDs
+/// (the GPS and the Interop IFD have their own ones)
+fn
+get_tag_by_name
+(
+	name: &str
+)
+-> Option<ExifTag>
+{
+	static TAGS: OnceLock<HashMap<String, ExifTag>> = OnceLock::new();
+
+	TAGS.get_or_init(|| {
+		[ExifTagGroup::IFD0, ExifTagGroup::GPSIFD, ExifTagGroup::InteropIFD].iter()
+			.flat_map(|group| (0..=u16::MAX).filter_map(|hex_value| ExifTag::from_u16_in_group(hex_value, group).ok()))
+			.map(|tag| (tag.name(), tag))
+			.collect()
+	}).get(name).cloned()
+}
+
+/// Constructs the tag with the given name from its Python representation
+fn
+tag_from_object
+(
+	name:  &str,
+	value: &Bound<'_, PyAny>
+)
+-> PyResult<ExifTag>
+{
+	let tag = get_tag_by_name(name)
+		.ok_or_else(|| PyKeyError::new_err(format!("Unknown tag {}!", name)))?;
+
+	if !tag.is_writable()
+	{
+		return Err(PyValueError::new_err(format!("Tag {} can't be written!", name)));
+	}
+
+	let raw_data: Vec<u8> = match tag.format()
+	{
+		ExifTagFormat::STRING => {
+			// EXIF strings are ASCII
+			let string: String = value.extract()?;
+			if !string.is_ascii()
+			{
+				return Err(PyValueError::new_err(format!("Value of tag {} is not ASCII!", name)));
+			}
+			string.into_bytes()
+		},
+		ExifTagFormat::UNDEF
+			=> value.extract()?,
+		ExifTagFormat::INT8U
+			=> extract_values::<u8>(value)?,
+		ExifTagFormat::INT8S
+			=> extract_values::<i8>(value)?.into_iter().map(|value| value as u8).collect(),
+		ExifTagFormat::INT16U
+			=> extract_values::<u16>(value)?.into_iter().flat_map(u16::to_le_bytes).collect(),
+		ExifTagFormat::INT16S
+			=> extract_values::<i16>(value)?.into_iter().flat_map(i16::to_le_bytes).collect(),
+		ExifTagFormat::INT32U
+			=> extract_values::<u32>(value)?.into_iter().flat_map(u32::to_le_bytes).collect(),
+		ExifTagFormat::INT32S
+			=> extract_values::<i32>(value)?.into_iter().flat_map(i32::to_le_bytes).collect(),
+		ExifTagFormat::RATIONAL64U
+			=> extract_values::<(u32, u32)>(value)?.into_iter().flat_map(|(n, d)| [n.to_le_bytes(), d.to_le_bytes()].concat()).collect(),
+		ExifTagFormat::RATIONAL64S
+			=> extract_values::<(i32, i32)>(value)?.into_iter().flat_map(|(n, d)| [n.to_le_bytes(), d.to_le_bytes()].concat()).collect(),
+		ExifTagFormat::FLOAT
+			=> extract_values::<f32>(value)?.into_iter().flat_map(f32::to_le_bytes).collect(),
+		ExifTagFormat::DOUBLE
+			=> extract_values::<f64>(value)?.into_iter().flat_map(f64::to_le_bytes).collect(),
+	};
+
+	ExifTag::from_u16_with_data(tag.as_u16(), &tag.format(), &raw_data, &Endian::Little, &tag.get_group())
+		.map_err(PyValueError::new_err)
+}
+
+/// Extracts either a list of values or a single value
+fn
+extract_values
+<'py, T: FromPyObject<'py>>
+(
+	value: &Bound<'py, PyAny>
+)
+-> PyResult<Vec<T>>
+{
+	if let Ok(values) = value.extract::<Vec<T>>()
+	{
+		return Ok(values);
+	}
+	Ok(vec![value.extract::<T>()?])
+}
+
+#[cfg(test)]
+mod tests
+{
+	use pyo3::prelude::*;
+	use pyo3::types::PyDict;
+
+	#[test]
+	fn
+	write_read_strip_bytes()
+	{
+		pyo3::prepare_freethreaded_python();
+		Python::with_gil(|py| {
+			let module = PyModule::new_bound(py, "little_exif").unwrap();
+			crate::little_exif_python(&module).unwrap();
+
+			let image = std::fs::read("../../tests/sample2.jpg").unwrap();
+			let tags = PyDict::new_bound(py);
+			tags.set_item("Artist", "Jane Doe").unwrap();
+			tags.set_item("ISO", 400).unwrap();
+
+			let written = module.call_method1("write_bytes", (image, "jpg", tags)).unwrap();
+			let read_tags = module.call_method1("read_bytes", (written.clone(), "jpg")).unwrap();
+			assert_eq!(read_tags.get_item("Artist").unwrap().extract::<String>().unwrap(), "Jane Doe");
+
+			let stripped = module.call_method1("strip_bytes", (written, "JPG")).unwrap();
+			let read_tags = module.call_method1("read_bytes", (stripped, "jpg")).unwrap();
+			assert!(read_tags.downcast::<PyDict>().unwrap().is_empty());
+
+			// Errors are raised as exceptions
+			let unknown = PyDict::new_bound(py);
+			unknown.set_item("NoSuchTag", 1).unwrap();
+			assert!(module.call_method1("write_bytes", (vec![0u8], "jpg", unknown)).is_err());
+			assert!(module.call_method1("read_bytes", (vec![0u8], "gif")).is_err());
+		});
+	}
+}
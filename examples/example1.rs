@@ -26,13 +26,13 @@ main()
 
 	// Create metadata structs & fill them
 	let mut png_data = Metadata::new();
-	let mut jpg_data = Metadata::new_from_path(&jpg_path).unwrap();
+	let mut jpg_data = Metadata::new_from_path(jpg_path).unwrap();
 	fill_metadata(&mut png_data);
 	fill_metadata(&mut jpg_data);
 
 	// Write the metadata to the copies
-	png_data.write_to_file(&png_path)?;
-	jpg_data.write_to_file(&jpg_path)?;
+	png_data.write_to_file(png_path)?;
+	jpg_data.write_to_file(jpg_path)?;
 	
 	// Read in the metadata again & print it
 	println!("PNG read result:");
@@ -0,0 +1,108 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Fuzzes the parsers deterministically and stores the minimized inputs that
+//! make them panic as regression fixtures in `tests/regressions`, see
+//! `tests/fuzz/mod.rs`. Commit the new fixtures together with the fix.
+//!
+//! `cargo run --release --example fuzz -- [iterations] [seed]`
+//!
+//! Only debug builds (without `--release`) catch integer overflows, which
+//! makes them slower but more thorough.
+//!
+//! Crashes that abort the process (e.g. a stack overflow) can't be caught,
+//! so the input that is currently exercised is kept in `LAST_INPUT_PATH`.
+//! Such an input (or any other crashing one, e.g. from a bug report) gets
+//! minimized and stored by running every candidate in a child process:
+//!
+//! `cargo run --release --example fuzz -- --add <path>`
+
+#[path = "../tests/fuzz/mod.rs"]
+mod fuzz;
+
+const LAST_INPUT_PATH: &str = "target/fuzz_last_input.bin";
+
+/// Checks whether exercising the input crashes a child process, either by
+/// a panic or by aborting
+fn
+crashes_child
+(
+	input: &[u8]
+)
+-> Result<bool, std::io::Error>
+{
+	let candidate_path = std::env::temp_dir().join(format!("little_exif_fuzz_candidate_{}.bin", std::process::id()));
+	std::fs::write(&candidate_path, input)?;
+	let status = std::process::Command::new(std::env::current_exe()?)
+		.arg("--exercise")
+		.arg(&candidate_path)
+		.stdout(std::process::Stdio::null())
+		.stderr(std::process::Stdio::null())
+		.status()?;
+	std::fs::remove_file(&candidate_path)?;
+	return Ok(!status.success());
+}
+
+/// Stores the input as fixture, unless it is already stored
+fn
+store_fixture
+(
+	input: &[u8],
+	panic: &str
+)
+-> Result<(), std::io::Error>
+{
+	let path = fuzz::fixture_path(input);
+	if !path.exists()
+	{
+		std::fs::create_dir_all(fuzz::REGRESSIONS_DIRECTORY)?;
+		std::fs::write(&path, input)?;
+	}
+	println!("{} ({} bytes): {}", path.display(), input.len(), panic);
+	return Ok(());
+}
+
+fn
+main()
+-> Result<(), std::io::Error>
+{
+	let arguments = std::env::args().skip(1).collect::<Vec<String>>();
+	match arguments.iter().map(String::as_str).collect::<Vec<&str>>()[..]
+	{
+		["--exercise", path] => {
+			fuzz::exercise(&std::fs::read(path)?);
+			return Ok(());
+		},
+		["--add", path] => {
+			let input = std::fs::read(path)?;
+			if !crashes_child(&input)?
+			{
+				return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("{} doesn't crash little_exif!", path)));
+			}
+			let minimized = fuzz::minimize(&input, |candidate| crashes_child(candidate).unwrap_or(false));
+			return store_fixture(&minimized, "crashes the process");
+		},
+		_ => (),
+	}
+
+	let parse = |index: usize, default: u64| arguments.get(index)
+		.map(|argument| argument.parse::<u64>().map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidInput, error)))
+		.unwrap_or(Ok(default));
+	let iterations = parse(0, 100_000)?;
+	let seed       = parse(1, 1)?;
+
+	let mut last_input = std::fs::File::create(LAST_INPUT_PATH)?;
+	let crashes = fuzz::fuzz(iterations as usize, seed, &mut |input| {
+		use std::io::Seek;
+		use std::io::Write;
+		let _ = last_input.set_len(0).and_then(|_| last_input.rewind()).and_then(|_| last_input.write_all(input));
+	});
+
+	for (panic, input) in &crashes
+	{
+		store_fixture(input, panic)?;
+	}
+	println!("{} iterations with seed {}: {} distinct panics", iterations, seed, crashes.len());
+
+	Ok(())
+}
@@ -0,0 +1,363 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Recording the acquisition parameters of astrophotography (e.g. the
+//! sensor temperature, gain and number of stacked frames) in ordinary image
+//! files, using the keywords of FITS headers that astro software already
+//! knows, e.g. `CCD-TEMP` or `GAIN`.
+//!
+//! The keywords are encoded as FITS header cards (`KEYWORD = value`, one per
+//! line) and stored either
+//! - in the `UserComment` tag (ASCII character code), see
+//!   `Metadata::acquisition_keywords` and `Metadata::set_acquisition_keywords`,
+//!   replacing any previous comment, or
+//! - in the XMP packet (see the `xmp` module, JPEG, PNG and GIF only) as property
+//!   `astro:FitsHeader` of the namespace
+//!   `https://github.com/TechnikTobi/little_exif/ns/astro/1.0/`, see
+//!   `write_keywords_to_vec` and `read_keywords_from_vec`.
+//!
+//! The temperature that cameras store in their maker notes is available via
+//! `MakerNote::sensor_temperature`.
+//!
+//! # Examples
+//! ```no_run
+//! use little_exif::astro::AcquisitionKeywords;
+//! use little_exif::astro::KeywordValue;
+//! use little_exif::astro::FRAME_COUNT;
+//! use little_exif::astro::GAIN;
+//! use little_exif::astro::SENSOR_TEMPERATURE;
+//! use little_exif::metadata::Metadata;
+//!
+//! let path = std::path::Path::new("m31_stacked.jpg");
+//! let mut metadata = Metadata::new_from_path(path).unwrap();
+//! metadata.set_acquisition_keywords(&AcquisitionKeywords::new()
+//!     .keyword(SENSOR_TEMPERATURE, KeywordValue::Float(-10.0))
+//!     .keyword(GAIN,               KeywordValue::Integer(120))
+//!     .keyword(FRAME_COUNT,        KeywordValue::Integer(64))
+//! ).unwrap();
+//! metadata.write_to_file(path).unwrap();
+//! ```
+
+use crate::general_file_io::io_error;
+use crate::xmp;
+use crate::xmp::Namespace;
+
+const ASTRO_NAMESPACE: Namespace = ("astro", "https://github.com/TechnikTobi/little_exif/ns/astro/1.0/");
+
+const KEYWORD_LENGTH: usize = 8;
+const CARD_LENGTH:    usize = 80;
+const VALUE_END:      usize = 30;                                               // Column that fixed format values end at
+
+/// Exposure time of a single frame in seconds
+pub const EXPOSURE_TIME:      &str = "EXPTIME";
+/// Sensor temperature in degrees Celsius
+pub const SENSOR_TEMPERATURE: &str = "CCD-TEMP";
+/// Target temperature of the sensor cooling in degrees Celsius
+pub const SET_TEMPERATURE:    &str = "SET-TEMP";
+/// Gain of the sensor, in units of the capture software
+pub const GAIN:               &str = "GAIN";
+/// Offset (bias level) of the sensor, in units of the capture software
+pub const OFFSET:             &str = "OFFSET";
+/// Number of frames that were stacked
+pub const FRAME_COUNT:        &str = "NCOMBINE";
+/// Name of the filter
+pub const FILTER:             &str = "FILTER";
+/// Name of the observed object
+pub const OBJECT:             &str = "OBJECT";
+
+/// The value of a keyword
+#[derive(Debug, Clone, PartialEq)]
+pub enum
+KeywordValue
+{
+	Logical(bool),
+	Integer(i64),
+	Float(f64),
+	Text(String),                                                               // Printable ASCII characters only
+}
+
+impl
+KeywordValue
+{
+	/// Gets the value as floating point number if it is numeric
+	pub fn
+	as_f64
+	(
+		&self
+	)
+	-> Option<f64>
+	{
+		match self
+		{
+			KeywordValue::Integer(value) => Some(*value as f64),
+			KeywordValue::Float(value)   => Some(*value),
+			_                            => None,
+		}
+	}
+
+	/// Formats the value as in a fixed format header card
+	fn
+	format
+	(
+		&self
+	)
+	-> Result<String, std::io::Error>
+	{
+		let right_aligned = |value: String| format!("{:>width$}", value, width = VALUE_END - KEYWORD_LENGTH - 2);
+
+		match self
+		{
+			KeywordValue::Logical(value) => Ok(right_aligned(String::from(if *value { "T" } else { "F" }))),
+			KeywordValue::Integer(value) => Ok(right_aligned(value.to_string())),
+			KeywordValue::Float(value)   => {
+				if !value.is_finite()
+				{
+					return io_error!(InvalidInput, "FITS keyword values must be finite!");
+				}
+				// Always includes a decimal point or an exponent, so that it is
+				// read as float
+				Ok(right_aligned(format!("{:?}", value).to_uppercase()))
+			},
+			KeywordValue::Text(value)    => {
+				if !value.chars().all(|character| (' '..='~').contains(&character))
+				{
+					return io_error!(InvalidInput, "FITS keyword values may only contain printable ASCII characters!");
+				}
+				// Quotes are escaped by doubling them, the string is padded
+				// to at least 8 characters
+				Ok(format!("'{:<8}'", value.replace('\'', "''")))
+			},
+		}
+	}
+
+	/// Parses the value of a header card, i.e. everything after `= `
+	fn
+	parse
+	(
+		value: &str
+	)
+	-> Option<KeywordValue>
+	{
+		let value = value.trim_start();
+
+		if let Some(quoted) = value.strip_prefix('\'')
+		{
+			let mut text = String::new();
+			let mut characters = quoted.chars().peekable();
+			while let Some(character) = characters.next()
+			{
+				match (character, characters.peek())
+				{
+					('\'', Some('\'')) => {
+						text.push('\'');
+						characters.next();
+					},
+					('\'', _)          => return Some(KeywordValue::Text(text.trim_end().to_string())),
+					(character, _)     => text.push(character),
+				}
+			}
+			return None;
+		}
+
+		// Anything after a slash is a comment
+		let value = value.split('/').next()?.trim();
+		match value
+		{
+			"T" => Some(KeywordValue::Logical(true)),
+			"F" => Some(KeywordValue::Logical(false)),
+			_   => value.parse::<i64>().map(KeywordValue::Integer).ok()
+				.or_else(|| value.replace(['D', 'd'], "E").parse::<f64>().map(KeywordValue::Float).ok()),
+		}
+	}
+}
+
+/// A set of FITS keywords describing the acquisition of an image, see the
+/// module documentation. Set using chained calls, starting with an empty
+/// set constructed by `AcquisitionKeywords::new`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct
+AcquisitionKeywords
+{
+	keywords: Vec<(String, KeywordValue)>,
+}
+
+impl
+AcquisitionKeywords
+{
+	/// Constructs an empty set of keywords
+	pub fn
+	new()
+	-> AcquisitionKeywords
+	{
+		AcquisitionKeywords::default()
+	}
+
+	/// Sets the value of the keyword, replacing a previous value. Keywords
+	/// consist of up to 8 upper case letters, digits, hyphens and
+	/// underscores, lower case letters are converted. Invalid keywords are
+	/// reported when writing.
+	pub fn
+	keyword
+	(
+		mut self,
+		name:  &str,
+		value: KeywordValue
+	)
+	-> AcquisitionKeywords
+	{
+		let name = name.to_ascii_uppercase();
+		self.keywords.retain(|(existing, _)| *existing != name);
+		self.keywords.push((name, value));
+		self
+	}
+
+	/// Gets the value of the keyword
+	pub fn
+	get
+	(
+		&self,
+		name: &str
+	)
+	-> Option<&KeywordValue>
+	{
+		let name = name.to_ascii_uppercase();
+		self.keywords.iter().find(|(existing, _)| *existing == name).map(|(_, value)| value)
+	}
+
+	/// Gets all keywords and their values, in the order they were set
+	pub fn
+	keywords
+	(
+		&self
+	)
+	-> &[(String, KeywordValue)]
+	{
+		&self.keywords
+	}
+
+	/// Encodes the keywords as header cards, separated by line breaks
+	/// - If a keyword or value is invalid, an error of kind `InvalidInput`
+	///   gets returned.
+	pub(crate) fn
+	to_cards
+	(
+		&self
+	)
+	-> Result<String, std::io::Error>
+	{
+		let mut cards = Vec::new();
+		for (name, value) in &self.keywords
+		{
+			if name.is_empty() || name.len() > KEYWORD_LENGTH
+				|| !name.chars().all(|character| character.is_ascii_uppercase() || character.is_ascii_digit() || character == '-' || character == '_')
+			{
+				return io_error!(InvalidInput, format!("Invalid FITS keyword '{}'!", name));
+			}
+
+			let card = format!("{:<width$}= {}", name, value.format()?, width = KEYWORD_LENGTH);
+			if card.len() > CARD_LENGTH
+			{
+				return io_error!(InvalidInput, format!("Value of FITS keyword '{}' is too long!", name));
+			}
+			cards.push(card);
+		}
+		return Ok(cards.join("\n"));
+	}
+
+	/// Decodes header cards, separated by line breaks. Lines that are not
+	/// valued keywords (e.g. `COMMENT` or `END`) are skipped. Returns `None`
+	/// if there are no valued keywords at all.
+	pub(crate) fn
+	from_cards
+	(
+		cards: &str
+	)
+	-> Option<AcquisitionKeywords>
+	{
+		let mut keywords = AcquisitionKeywords::new();
+		for card in cards.lines()
+		{
+			let value = match card.get(KEYWORD_LENGTH..)
+			{
+				Some(value) if value.starts_with("= ") => &value[2..],
+				_                                      => continue,
+			};
+			if let Some(value) = KeywordValue::parse(value)
+			{
+				keywords = keywords.keyword(card[..KEYWORD_LENGTH].trim_end(), value);
+			}
+		}
+		if keywords.keywords.is_empty() { None } else { Some(keywords) }
+	}
+}
+
+/// Writes the keywords to the XMP packet of the image, replacing
+/// previous ones (see module documentation)
+/// - If a keyword or value is invalid, an error of kind `InvalidInput` gets
+///   returned and the image is not changed.
+pub fn
+write_keywords_to_vec
+(
+	file_buffer: &mut Vec<u8>,
+	keywords:    &AcquisitionKeywords
+)
+-> Result<(), std::io::Error>
+{
+	let cards = keywords.to_cards()?;
+	let packet = xmp::read_xmp_from_vec(file_buffer)?;
+	let packet = xmp::set_properties(packet.as_deref(), ASTRO_NAMESPACE, &[("FitsHeader", Some(cards))])?;
+	return xmp::write_xmp_to_vec(file_buffer, &packet);
+}
+
+/// Reads the keywords from the XMP packet of the image. Returns `None`
+/// if there are none.
+pub fn
+read_keywords_from_vec
+(
+	file_buffer: &[u8]
+)
+-> Result<Option<AcquisitionKeywords>, std::io::Error>
+{
+	let keywords = xmp::read_xmp_from_vec(file_buffer)?
+		.and_then(|packet| xmp::get_property(&packet, ASTRO_NAMESPACE, "FitsHeader"))
+		.and_then(|cards| AcquisitionKeywords::from_cards(&cards));
+	return Ok(keywords);
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::astro::*;
+
+	#[test]
+	fn
+	encode_and_decode_cards()
+	-> Result<(), std::io::Error>
+	{
+		let keywords = AcquisitionKeywords::new()
+			.keyword(SENSOR_TEMPERATURE, KeywordValue::Float(-10.0))
+			.keyword("gain",             KeywordValue::Integer(120))
+			.keyword(OBJECT,             KeywordValue::Text("Andromeda's core".to_string()))
+			.keyword("COOLED",           KeywordValue::Logical(true))
+			.keyword(GAIN,               KeywordValue::Integer(139));
+
+		let cards = keywords.to_cards()?;
+		assert_eq!(cards.lines().next(), Some("CCD-TEMP=                -10.0"));
+		assert!(cards.contains("OBJECT  = 'Andromeda''s core'"));
+		assert!(cards.contains("GAIN    =                  139"));
+		assert!(AcquisitionKeywords::new().keyword(EXPOSURE_TIME, KeywordValue::Float(1e-7)).to_cards()?.ends_with(" 1E-7"));
+		assert_eq!(AcquisitionKeywords::from_cards(&cards), Some(keywords));
+
+		// Cards written by other software
+		let decoded = AcquisitionKeywords::from_cards("EXPTIME =              3.0D+02 / [s]\nCOMMENT = not a value\nFILTER  = 'Ha      '\nEND").unwrap();
+		assert_eq!(decoded.get(EXPOSURE_TIME), Some(&KeywordValue::Float(300.0)));
+		assert_eq!(decoded.get("filter"),      Some(&KeywordValue::Text("Ha".to_string())));
+		assert_eq!(decoded.keywords().len(), 2);
+		assert_eq!(AcquisitionKeywords::from_cards("Shot with my phone"), None);
+
+		assert!(AcquisitionKeywords::new().keyword("EXPOSURETIME", KeywordValue::Integer(1)).to_cards().is_err());
+		assert!(AcquisitionKeywords::new().keyword(OBJECT, KeywordValue::Text("M31 ☆".to_string())).to_cards().is_err());
+		assert!(AcquisitionKeywords::new().keyword(GAIN, KeywordValue::Float(f64::NAN)).to_cards().is_err());
+		Ok(())
+	}
+}
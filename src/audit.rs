@@ -0,0 +1,214 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Auditing which tags are present in a set of files, e.g. for finding the
+//! photos of an archive that lack a capture date or GPS data in one call.
+//! The result is a matrix with a row for each file and a column for each
+//! audited tag. Tags without an actual value (see `ExifTag::is_empty_value`)
+//! count as missing.
+//!
+//! ```no_run
+//! use little_exif::audit::KEY_TAGS;
+//! use little_exif::audit::presence_matrix;
+//! use little_exif::exif_tag::ExifTag;
+//!
+//! let paths = [std::path::Path::new("image.jpg"), std::path::Path::new("image.png")];
+//! let matrix = presence_matrix(&paths, &KEY_TAGS);
+//! for path in matrix.missing(&ExifTag::DateTimeOriginal(String::new()))
+//! {
+//!     println!("{} lacks a capture date", path.display());
+//! }
+//! ```
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::exif_tag::ExifTag;
+use crate::metadata::Metadata;
+
+/// The tags most audits are interested in: When, where and with what a
+/// photo was taken, how it is displayed and who owns it
+pub const KEY_TAGS: [ExifTag; 8] = [
+	ExifTag::DateTimeOriginal(String::new()),
+	ExifTag::GPSLatitude(Vec::new()),
+	ExifTag::GPSLongitude(Vec::new()),
+	ExifTag::Make(String::new()),
+	ExifTag::Model(String::new()),
+	ExifTag::Orientation(Vec::new()),
+	ExifTag::Artist(String::new()),
+	ExifTag::Copyright(String::new()),
+];
+
+/// The row of a file in a `PresenceMatrix`
+#[derive(Debug)]
+pub struct
+PresenceRow
+{
+	/// The path of the file, as given to `presence_matrix`
+	pub path:    PathBuf,
+
+	/// For each audited tag whether it is present in the file, or the error
+	/// that occurred while reading the file
+	pub present: Result<Vec<bool>, std::io::Error>,
+}
+
+/// Which of the audited tags are present in which file, see
+/// `presence_matrix`
+#[derive(Debug)]
+pub struct
+PresenceMatrix
+{
+	tags: Vec<ExifTag>,
+	rows: Vec<PresenceRow>,
+}
+
+impl
+PresenceMatrix
+{
+	/// Gets the audited tags, i.e. the columns of the matrix
+	pub fn
+	tags
+	(
+		&self
+	)
+	-> &[ExifTag]
+	{
+		&self.tags
+	}
+
+	/// Gets the rows of the matrix, in the order the files have been given
+	pub fn
+	rows
+	(
+		&self
+	)
+	-> &[PresenceRow]
+	{
+		&self.rows
+	}
+
+	/// Gets the column of the given tag, matched by its type
+	fn
+	column
+	(
+		&self,
+		tag: &ExifTag
+	)
+	-> Option<usize>
+	{
+		self.tags.iter().position(|audited| audited.as_u16() == tag.as_u16() && audited.get_group() == tag.get_group())
+	}
+
+	/// Gets the files that could be read but lack the given tag. Returns no
+	/// files if the tag has not been audited.
+	pub fn
+	missing
+	(
+		&self,
+		tag: &ExifTag
+	)
+	-> Vec<&Path>
+	{
+		let Some(column) = self.column(tag) else { return Vec::new(); };
+		return self.rows.iter()
+			.filter(|row| row.present.as_ref().is_ok_and(|present| !present[column]))
+			.map(|row| row.path.as_path())
+			.collect();
+	}
+
+	/// Gets the number of files the given tag is present in
+	pub fn
+	count_present
+	(
+		&self,
+		tag: &ExifTag
+	)
+	-> usize
+	{
+		let Some(column) = self.column(tag) else { return 0; };
+		return self.rows.iter()
+			.filter(|row| row.present.as_ref().is_ok_and(|present| present[column]))
+			.count();
+	}
+
+	/// Gets the files that could not be read, together with the error
+	pub fn
+	unreadable
+	(
+		&self
+	)
+	-> Vec<(&Path, &std::io::Error)>
+	{
+		self.rows.iter()
+			.filter_map(|row| row.present.as_ref().err().map(|error| (row.path.as_path(), error)))
+			.collect()
+	}
+}
+
+/// Reads the files and checks which of the given tags are present in each of
+/// them. The tags are selected using their type, i.e. the value of the given
+/// tags is ignored. Unlike `export::export_csv`, a file that can't be read
+/// doesn't stop the audit but is recorded in its row.
+pub fn
+presence_matrix
+(
+	paths: &[&Path],
+	tags:  &[ExifTag]
+)
+-> PresenceMatrix
+{
+	let rows = paths.iter()
+		.map(|path| PresenceRow
+		{
+			path:    path.to_path_buf(),
+			present: Metadata::new_from_path(path).map(|metadata| tags.iter()
+				.map(|tag| metadata.get_tag(tag).is_some_and(|found| !found.is_empty_value()))
+				.collect()
+			),
+		})
+		.collect();
+
+	return PresenceMatrix { tags: tags.to_vec(), rows };
+}
+
+#[cfg(test)]
+mod tests
+{
+	use std::path::Path;
+
+	use crate::audit::KEY_TAGS;
+	use crate::audit::presence_matrix;
+	use crate::exif_tag::ExifTag;
+	use crate::metadata::Metadata;
+
+	#[test]
+	fn
+	matrix()
+	-> Result<(), std::io::Error>
+	{
+		let path = Path::new("tests/tmp_audit.png");
+		std::fs::copy("tests/png_parse_test_image.png", path)?;
+		let mut metadata = Metadata::new();
+		metadata.set_tag(ExifTag::DateTimeOriginal("2024:05:06 07:08:09".to_string()));
+		metadata.set_tag(ExifTag::Artist("  ".to_string()));
+		metadata.write_to_file(path)?;
+
+		let matrix = presence_matrix(
+			&[path, Path::new("tests/sample2.png"), Path::new("tests/no_such_file.jpg")],
+			&KEY_TAGS
+		);
+		std::fs::remove_file(path)?;
+
+		let capture_date = ExifTag::DateTimeOriginal(String::new());
+		assert_eq!(matrix.tags().len(), KEY_TAGS.len());
+		assert_eq!(matrix.rows().len(), 3);
+		assert_eq!(matrix.rows()[0].present.as_ref().unwrap()[0..2], [true, false]);
+		assert_eq!(matrix.count_present(&capture_date), 1);
+		assert_eq!(matrix.missing(&capture_date), [Path::new("tests/sample2.png")]);
+		assert_eq!(matrix.missing(&ExifTag::Artist(String::new())).len(), 2);
+		assert_eq!(matrix.missing(&ExifTag::ISO(Vec::new())).len(), 0);
+		assert_eq!(matrix.unreadable().len(), 1);
+		assert_eq!(matrix.unreadable()[0].0, Path::new("tests/no_such_file.jpg"));
+		Ok(())
+	}
+}
@@ -0,0 +1,777 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! AVI files are RIFF files with the form type `AVI `. They don't contain
+//! TIFF encoded EXIF data, but cameras store information about their clips
+//! in chunks whose sub-chunks are mapped to EXIF tags:
+//! - `LIST exif`, defined by the EXIF standard for RIFF files: `ever`
+//!   (ExifVersion), `ecor` (Make), `emdl` (Model), `etim`
+//!   (DateTimeOriginal) and `eucm` (UserComment)
+//! - `LIST INFO`: `IART` (Artist), `ICOP` (Copyright), `ICMT`
+//!   (ImageDescription), `ISFT` (Software) and `ICRD` (CreateDate)
+//! - `IDIT` in the `hdrl` list, the recording time written by e.g. Canon and
+//!   Fujifilm cameras. It is read as DateTimeOriginal if there is no `etim`
+//!
+//! Timestamps are read in the EXIF format as well as in the `asctime` format
+//! used by `IDIT` (e.g. `THU OCT 26 16:46:04 2006`), `ICRD` may also be a
+//! date only (e.g. `2006-10-26`). When writing, `etim` gets the EXIF format,
+//! `ICRD` the date only and `IDIT` the format it had before.
+//!
+//! Writing replaces the mapped sub-chunks, keeping all others (e.g. `INAM`
+//! or `emnt`). Tags without a counterpart are not stored. The lists are put
+//! right in front of the `movi` list holding the movie data, while `IDIT` is
+//! overwritten in place. As the indices of some files (OpenDML indices and
+//! some `idx1` indices) point to absolute positions in the file, the movie
+//! data of such files is never moved: The size difference is compensated
+//! with the `JUNK` chunk in front of the movie data, and writing fails with
+//! an error of kind `Unsupported` if it isn't big enough.
+
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::path::Path;
+
+use crate::chunk_types::RiffChunkType;
+use crate::date_inference::parse_exif_date_millis;
+use crate::endian::Endian;
+use crate::errors::MissingExif;
+use crate::errors::ParseErrorContext;
+use crate::exif_date::DateCoercion;
+use crate::exif_date::ExifDate;
+use crate::exif_tag::ExifTag;
+use crate::general_file_io::*;
+use crate::generated_image::decode_user_comment;
+use crate::generated_image::encode_user_comment;
+use crate::generated_image::ASCII_CHARACTER_CODE;
+use crate::generated_image::UNICODE_CHARACTER_CODE;
+use crate::metadata::Metadata;
+use crate::riff_chunk::*;
+use crate::vfs::FileSystem;
+use crate::vfs::ReadSeek;
+
+pub(crate) const AVI_SIGNATURE: [u8; 4] = *b"AVI ";
+
+const HEADER_LIST: [u8; 4] = *b"hdrl";
+const STREAM_LIST: [u8; 4] = *b"strl";
+const ODML_LIST:   [u8; 4] = *b"odml";
+const MOVIE_LIST:  [u8; 4] = *b"movi";
+const EXIF_LIST:   [u8; 4] = *b"exif";
+const INFO_LIST:   [u8; 4] = *b"INFO";
+
+const MONTHS:   [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+const WEEKDAYS: [&str; 7]  = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];                 // Starting with 1970-01-01
+
+/// How the value of a sub-chunk is converted from and to the tag
+#[derive(Clone, Copy, PartialEq)]
+enum
+ValueKind
+{
+	Text,
+	Timestamp,
+	Date,
+	Version,
+	Comment,
+}
+
+/// A sub-chunk of a metadata list and the tag it is mapped to
+struct
+Field
+{
+	list: [u8; 4],
+	id:   [u8; 4],
+	tag:  ExifTag,
+	kind: ValueKind,
+}
+
+/// Gets the mapped sub-chunks, see the module documentation
+fn
+fields
+()
+-> Vec<Field>
+{
+	vec![
+		Field { list: EXIF_LIST, id: *b"ever", tag: ExifTag::ExifVersion(Vec::new()),         kind: ValueKind::Version   },
+		Field { list: EXIF_LIST, id: *b"ecor", tag: ExifTag::Make(String::new()),             kind: ValueKind::Text      },
+		Field { list: EXIF_LIST, id: *b"emdl", tag: ExifTag::Model(String::new()),            kind: ValueKind::Text      },
+		Field { list: EXIF_LIST, id: *b"etim", tag: ExifTag::DateTimeOriginal(String::new()), kind: ValueKind::Timestamp },
+		Field { list: EXIF_LIST, id: *b"eucm", tag: ExifTag::UserComment(Vec::new()),         kind: ValueKind::Comment   },
+		Field { list: INFO_LIST, id: *b"IART", tag: ExifTag::Artist(String::new()),           kind: ValueKind::Text      },
+		Field { list: INFO_LIST, id: *b"ICOP", tag: ExifTag::Copyright(String::new()),        kind: ValueKind::Text      },
+		Field { list: INFO_LIST, id: *b"ICMT", tag: ExifTag::ImageDescription(String::new()), kind: ValueKind::Text      },
+		Field { list: INFO_LIST, id: *b"ISFT", tag: ExifTag::Software(String::new()),         kind: ValueKind::Text      },
+		Field { list: INFO_LIST, id: *b"ICRD", tag: ExifTag::CreateDate(String::new()),       kind: ValueKind::Date      },
+	]
+}
+
+/// Opens the AVI file at the given path for reading
+fn
+open_file
+(
+	file_system: &dyn FileSystem,
+	path:        &Path
+)
+-> Result<Box<dyn ReadSeek>, std::io::Error>
+{
+	if !file_system.exists(path)
+	{
+		return io_error!(NotFound, "Can't open AVI file - File does not exist!");
+	}
+
+	return file_system.open(path);
+}
+
+/// Checks whether the data starts with the RIFF header of an AVI file
+pub(crate) fn
+is_avi
+(
+	file_buffer: &[u8]
+)
+-> bool
+{
+	file_buffer.starts_with(&RiffChunkType::RIFF.as_bytes()) && file_buffer.get(8..12) == Some(&AVI_SIGNATURE)
+}
+
+/// Decodes the text of a sub-chunk, which is usually NUL terminated and
+/// sometimes ends with a line break. Text that isn't valid UTF-8 is decoded
+/// as Latin-1.
+fn
+decode_text
+(
+	payload: &[u8]
+)
+-> String
+{
+	let text = match std::str::from_utf8(payload)
+	{
+		Ok(text) => text.to_string(),
+		Err(_)   => payload.iter().map(|byte| *byte as char).collect(),
+	};
+	return text.trim_end_matches(['\0', '\n', '\r', ' ']).to_string();
+}
+
+/// Parses a timestamp in the EXIF format or the `asctime` format, e.g.
+/// `THU OCT 26 16:46:04 2006`
+fn
+parse_timestamp
+(
+	text: &str
+)
+-> Option<ExifDate>
+{
+	if let Some(date) = ExifDate::parse(text, DateCoercion::Lenient)
+	{
+		return Some(date);
+	}
+
+	let [_, month, day, time, year] = text.split_whitespace().collect::<Vec<&str>>()[..]
+	else
+	{
+		return None;
+	};
+	let month = MONTHS.iter().position(|name| name.eq_ignore_ascii_case(month))? + 1;
+	return ExifDate::parse(&format!("{}:{:02}:{:0>2} {}", year, month, day, time), DateCoercion::Strict);
+}
+
+/// Formats the timestamp in the `asctime` format, e.g.
+/// `Thu Oct 26 16:46:04 2006`
+fn
+format_asctime
+(
+	date: &ExifDate
+)
+-> String
+{
+	let days = parse_exif_date_millis(&date.to_exif_string(), None).unwrap_or(0).div_euclid(86_400_000);
+	format!(
+		"{} {} {:02} {:02}:{:02}:{:02} {:04}",
+		WEEKDAYS[days.rem_euclid(7) as usize],
+		MONTHS[(date.month as usize).clamp(1, 12) - 1],
+		date.day,
+		date.hour,
+		date.minute,
+		date.second,
+		date.year
+	)
+}
+
+/// Converts the payload of a mapped sub-chunk into its tag. Returns `None`
+/// if the payload is not a valid value, e.g. a timestamp in an unknown format
+fn
+payload_to_tag
+(
+	field:   &Field,
+	payload: &[u8],
+	endian:  &Endian
+)
+-> Option<ExifTag>
+{
+	match field.kind
+	{
+		ValueKind::Text
+			=> field.tag.with_string_value(&decode_text(payload)),
+		ValueKind::Timestamp | ValueKind::Date
+			=> field.tag.with_string_value(&parse_timestamp(&decode_text(payload))?.to_exif_string()),
+		ValueKind::Version if payload.len() >= 4
+			=> Some(ExifTag::ExifVersion(payload[..4].to_vec())),
+		ValueKind::Version
+			=> None,
+		ValueKind::Comment
+			=> {
+				// Comments are usually prefixed with their character code
+				// like the tag, but Unicode comments have to use the byte
+				// order of the metadata
+				let text = match payload.starts_with(&UNICODE_CHARACTER_CODE) || payload.starts_with(&ASCII_CHARACTER_CODE)
+				{
+					true  => decode_user_comment(payload, &Endian::Little),
+					false => decode_text(payload),
+				};
+				Some(ExifTag::UserComment(encode_user_comment(&text, endian)))
+			},
+	}
+}
+
+/// Converts the value of the tag in the metadata into the payload of the
+/// mapped sub-chunk. Returns `None` if the metadata doesn't contain the tag
+fn
+tag_to_payload
+(
+	field:    &Field,
+	metadata: &Metadata
+)
+-> Option<Vec<u8>>
+{
+	let tag = metadata.get_tag(&field.tag)?;
+	let mut payload = match (field.kind, tag)
+	{
+		(ValueKind::Version, ExifTag::ExifVersion(version)) => return Some(version.clone()),
+		(ValueKind::Comment, ExifTag::UserComment(comment)) => {
+			let text = decode_user_comment(comment, metadata.endian());
+			return Some(encode_user_comment(&text, &Endian::Little));
+		},
+		(ValueKind::Text,      _) => metadata.get_string_value(&field.tag)?,
+		(ValueKind::Timestamp, _) => {
+			let text = metadata.get_string_value(&field.tag)?;
+			parse_timestamp(&text).map(|date| date.to_exif_string()).unwrap_or(text)
+		},
+		(ValueKind::Date,      _) => {
+			let text = metadata.get_string_value(&field.tag)?;
+			parse_timestamp(&text).map(|date| format!("{:04}-{:02}-{:02}", date.year, date.month, date.day)).unwrap_or(text)
+		},
+		_ => return None,
+	}.into_bytes();
+
+	payload.push(0x00);
+	return Some(payload);
+}
+
+/// Gets the tags stored in the sub-chunks of a metadata list, given by its
+/// payload following the list type
+fn
+read_list
+(
+	list_type: [u8; 4],
+	data:      &[u8],
+	endian:    &Endian,
+	tags:      &mut Vec<ExifTag>,
+	warnings:  &mut Vec<ParseErrorContext>
+)
+-> Result<(), std::io::Error>
+{
+	let fields = fields();
+	for (position, descriptor) in sub_chunks(data)?
+	{
+		let id = *descriptor.header().as_bytes();
+		let field = match fields.iter().find(|field| field.list == list_type && field.id == id)
+		{
+			Some(field) => field,
+			None        => continue,
+		};
+
+		let payload = &data[position + 8..position + 8 + descriptor.payload_size()];
+		match payload_to_tag(field, payload, endian)
+		{
+			Some(tag) => tags.push(tag),
+			None      => warnings.push(ParseErrorContext::new().chunk(descriptor.header().as_str()).warning(
+				&format!("Ignoring invalid value '{}' of AVI metadata!", decode_text(payload))
+			)),
+		}
+	}
+	return Ok(());
+}
+
+/// Reads the metadata from the AVI data and encodes it as TIFF data, see the
+/// module documentation. Only the metadata chunks are read, the payload of
+/// all others (e.g. the movie data) gets skipped.
+/// - If there is no metadata, an error gets returned
+pub(crate) fn
+read_metadata
+<T: Read + Seek>
+(
+	cursor:   &mut T,
+	warnings: &mut Vec<ParseErrorContext>
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	let _span = span!("parse", format = "AVI");
+	let riff_end = check_header(cursor, AVI_SIGNATURE, "AVI", false)?;
+
+	let mut metadata = Metadata::new();
+	let mut tags     = Vec::new();
+	let mut idit     = None;
+	let mut position = 12u64;
+	let mut header_buffer = [0u8; 12];
+	while position + 8 <= riff_end
+	{
+		perform_file_action!(cursor.seek(SeekFrom::Start(position)));
+		if !try_read_exact(cursor, &mut header_buffer[..8])?
+		{
+			break;
+		}
+		let fourcc = FourCC::new(&header_buffer[0..4])?;
+		let size   = u32::from_le_bytes([header_buffer[4], header_buffer[5], header_buffer[6], header_buffer[7]]) as u64;
+		let next   = position + 8 + size + size % 2;
+
+		// Only the lists that may hold metadata get read
+		let list_type = match fourcc.chunk_type() == RiffChunkType::LIST && size >= 4 && try_read_exact(cursor, &mut header_buffer[8..12])?
+		{
+			true  => Some([header_buffer[8], header_buffer[9], header_buffer[10], header_buffer[11]]),
+			false => None,
+		};
+		let is_metadata = fourcc.chunk_type() == RiffChunkType::IDIT
+			|| list_type.is_some_and(|list_type| [HEADER_LIST, EXIF_LIST, INFO_LIST].contains(&list_type));
+		if is_metadata
+		{
+			let payload_size = size - list_type.map_or(0, |_| 4);
+			let mut payload = vec![0u8; payload_size as usize];
+			if !try_read_exact(cursor, &mut payload)?
+			{
+				return io_error!(UnexpectedEof, format!("Could not read AVI chunk '{}'!", fourcc));
+			}
+
+			match list_type
+			{
+				Some(HEADER_LIST) => {
+					idit = idit.or(sub_chunks(&payload)?.into_iter()
+						.find(|(_, descriptor)| descriptor.header().chunk_type() == RiffChunkType::IDIT)
+						.map(|(position, descriptor)| payload[position + 8..position + 8 + descriptor.payload_size()].to_vec()));
+				},
+				Some(list_type) => read_list(list_type, &payload, metadata.endian(), &mut tags, warnings)?,
+				None            => idit = idit.or(Some(payload)),
+			}
+		}
+
+		position = next;
+	}
+
+	// The recording time is only used if there is no timestamp in the EXIF list
+	if let Some(idit) = idit
+	{
+		match parse_timestamp(&decode_text(&idit))
+		{
+			Some(date) => tags.insert(0, ExifTag::DateTimeOriginal(date.to_exif_string())),
+			None       => warnings.push(ParseErrorContext::new().chunk("IDIT").warning(
+				&format!("Ignoring invalid recording time '{}'!", decode_text(&idit))
+			)),
+		}
+	}
+
+	if tags.is_empty()
+	{
+		return MissingExif::error("No metadata found in AVI file!");
+	}
+	for tag in tags
+	{
+		metadata.set_tag(tag);
+	}
+	return Ok(metadata.encode());
+}
+
+pub(crate) fn
+file_read_metadata
+(
+	file_system: &dyn FileSystem,
+	path:        &Path,
+	warnings:    &mut Vec<ParseErrorContext>
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	read_metadata(&mut open_file(file_system, path)?, warnings)
+}
+
+/// Gets the list type of the chunk at the given position, if it is a list
+fn
+list_type
+(
+	file_buffer: &[u8],
+	position:    usize,
+	descriptor:  &RiffChunkDescriptor
+)
+-> Option<[u8; 4]>
+{
+	match descriptor.header().chunk_type() == RiffChunkType::LIST && descriptor.payload_size() >= 4
+	{
+		true  => file_buffer[position + 8..position + 12].try_into().ok(),
+		false => None,
+	}
+}
+
+/// Builds the metadata lists from the given metadata. The sub-chunks of the
+/// previous lists are kept in their order, with the mapped ones replaced by
+/// the new values, which are appended if they didn't exist before. Lists
+/// without sub-chunks are left out.
+fn
+build_lists
+(
+	metadata: &Metadata,
+	previous: &[Vec<u8>]
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	let fields = fields();
+	let mut lists = Vec::new();
+	for list_type in [EXIF_LIST, INFO_LIST]
+	{
+		let mut new_values = fields.iter()
+			.filter(|field| field.list == list_type)
+			.map(|field| (field, tag_to_payload(field, metadata)))
+			.collect::<Vec<_>>();
+
+		// The EXIF version alone isn't worth a list
+		if list_type == EXIF_LIST && new_values.iter().all(|(field, value)| field.kind == ValueKind::Version || value.is_none())
+		{
+			new_values.iter_mut().for_each(|(_, value)| *value = None);
+		}
+
+		let mut payload = list_type.to_vec();
+		for list in previous.iter().filter(|list| list.starts_with(&list_type))
+		{
+			let sub_chunk_data = &list[4..];
+			for (position, descriptor) in sub_chunks(sub_chunk_data)?
+			{
+				let id = *descriptor.header().as_bytes();
+				let value = match new_values.iter_mut().find(|(field, _)| field.id == id)
+				{
+					Some((_, value)) => value.take(),
+					None             => Some(sub_chunk_data[position + 8..position + 8 + descriptor.payload_size()].to_vec()),
+				};
+				if let Some(value) = value
+				{
+					payload.extend(RiffChunk::new(descriptor.header(), value).serialize());
+				}
+			}
+		}
+		for (field, value) in new_values
+		{
+			if let Some(value) = value
+			{
+				payload.extend(RiffChunk::new(FourCC::new(&field.id)?, value).serialize());
+			}
+		}
+
+		if payload.len() > 4
+		{
+			lists.extend(RiffChunk::new(RiffChunkType::LIST.into(), payload).serialize());
+		}
+	}
+	return Ok(lists);
+}
+
+/// Overwrites the `IDIT` chunks with the recording time in the format they
+/// had before, or turns them into `JUNK` chunks if there is no recording time
+/// or it doesn't fit. This way, their size never changes.
+fn
+update_recording_time
+(
+	file_buffer: &mut [u8],
+	chunks:      &[(usize, RiffChunkDescriptor)],
+	metadata:    &Metadata
+)
+-> Result<(), std::io::Error>
+{
+	let recording_time = metadata.get_string_value(&ExifTag::DateTimeOriginal(String::new()))
+		.and_then(|text| parse_timestamp(&text));
+
+	// The IDIT chunk is either in the header list or at the top level
+	let mut positions = Vec::new();
+	for (position, descriptor) in chunks
+	{
+		if descriptor.header().chunk_type() == RiffChunkType::IDIT
+		{
+			positions.push((*position, descriptor.payload_size()));
+		}
+		if list_type(file_buffer, *position, descriptor) == Some(HEADER_LIST)
+		{
+			let start = position + 12;
+			for (sub_position, sub_descriptor) in sub_chunks(&file_buffer[start..start + descriptor.payload_size() - 4])?
+			{
+				if sub_descriptor.header().chunk_type() == RiffChunkType::IDIT
+				{
+					positions.push((start + sub_position, sub_descriptor.payload_size()));
+				}
+			}
+		}
+	}
+
+	for (position, size) in positions
+	{
+		let payload = &mut file_buffer[position + 8..position + 8 + size];
+		let previous = decode_text(payload);
+		let text = recording_time.map(|date| match ExifDate::parse(&previous, DateCoercion::Lenient)
+		{
+			Some(_)                                                    => date.to_exif_string(),
+			None if previous.chars().any(|c| c.is_ascii_lowercase())   => format_asctime(&date) + "\n",
+			None                                                       => format_asctime(&date).to_uppercase() + "\n",
+		});
+
+		match text
+		{
+			Some(text) if text.len() <= size => {
+				payload.fill(0x00);
+				payload[..text.len()].copy_from_slice(text.as_bytes());
+			},
+			_ => file_buffer[position..position + 4].copy_from_slice(&RiffChunkType::JUNK.as_bytes()),
+		}
+	}
+	return Ok(());
+}
+
+/// Checks whether the movie data can be moved, i.e. there are neither
+/// OpenDML indices nor an `idx1` index with absolute offsets
+fn
+is_movable
+(
+	file_buffer: &[u8],
+	chunks:      &[(usize, RiffChunkDescriptor)],
+	movie_list:  usize,
+	riff_end:    usize
+)
+-> Result<bool, std::io::Error>
+{
+	// Extension chunks following the RIFF chunk are only used by OpenDML
+	if riff_end != file_buffer.len()
+	{
+		return Ok(false);
+	}
+
+	for (position, descriptor) in chunks
+	{
+		if list_type(file_buffer, *position, descriptor) == Some(HEADER_LIST)
+		{
+			let header = &file_buffer[position + 12..position + 8 + descriptor.payload_size()];
+			for (sub_position, sub_descriptor) in sub_chunks(header)?
+			{
+				match list_type(header, sub_position, &sub_descriptor)
+				{
+					Some(ODML_LIST)   => return Ok(false),
+					Some(STREAM_LIST) => {
+						let stream = &header[sub_position + 12..sub_position + 8 + sub_descriptor.payload_size()];
+						if sub_chunks(stream)?.iter().any(|(_, chunk)| chunk.header().chunk_type() == RiffChunkType::indx)
+						{
+							return Ok(false);
+						}
+					},
+					_ => (),
+				}
+			}
+		}
+
+		// Relative offsets start at the list type of the movie list, so the
+		// first one is 4, while absolute ones point behind it
+		if descriptor.header().chunk_type() == RiffChunkType::idx1 && descriptor.payload_size() >= 16
+		{
+			let first_offset = u32::from_le_bytes(file_buffer[position + 16..position + 20].try_into().unwrap()) as usize;
+			if first_offset > movie_list
+			{
+				return Ok(false);
+			}
+		}
+	}
+	return Ok(true);
+}
+
+/// Writes the given metadata (the TIFF data, see `Metadata::encode`) to the
+/// AVI data, see the module documentation
+pub(crate) fn
+write_metadata
+(
+	file_buffer:              &mut Vec<u8>,
+	general_encoded_metadata: &Vec<u8>
+)
+-> Result<(), std::io::Error>
+{
+	let metadata = Metadata::decode(general_encoded_metadata)?;
+	return write_decoded_metadata(file_buffer, &metadata);
+}
+
+/// Writes the metadata to the AVI data, see `write_metadata`
+fn
+write_decoded_metadata
+(
+	file_buffer: &mut Vec<u8>,
+	metadata:    &Metadata
+)
+-> Result<(), std::io::Error>
+{
+	let riff_end = check_header(&mut std::io::Cursor::new(&file_buffer), AVI_SIGNATURE, "AVI", false)? as usize;
+	let trailing_bytes = file_buffer.len() - riff_end;
+	let chunks = sub_chunks(&file_buffer[12..riff_end])?
+		.into_iter()
+		.map(|(position, descriptor)| (position + 12, descriptor))
+		.collect::<Vec<_>>();
+
+	let movie_list = match chunks.iter().find(|(position, descriptor)| list_type(file_buffer, *position, descriptor) == Some(MOVIE_LIST))
+	{
+		Some((position, _)) => *position,
+		None                => return io_error!(InvalidData, "AVI file lacks the movi list with the movie data!"),
+	};
+	let movable = is_movable(file_buffer, &chunks, movie_list, riff_end)?;
+
+	update_recording_time(file_buffer, &chunks, metadata)?;
+
+	// Take the previous metadata lists out, the ones behind the movie data
+	// are turned into JUNK chunks so that the movie data stays in place
+	let mut previous_lists = Vec::new();
+	let mut kept_chunks    = Vec::new();
+	for (position, descriptor) in &chunks
+	{
+		let range = *position..position + descriptor.byte_count().min(riff_end - position);
+		let is_metadata_list = list_type(file_buffer, *position, descriptor).is_some_and(|list_type| list_type == EXIF_LIST || list_type == INFO_LIST);
+		if is_metadata_list
+		{
+			previous_lists.push(file_buffer[position + 8..position + 8 + descriptor.payload_size()].to_vec());
+			if *position > movie_list
+			{
+				file_buffer[range.start..range.start + 4].copy_from_slice(&RiffChunkType::JUNK.as_bytes());
+			}
+		}
+		else if *position < movie_list
+		{
+			kept_chunks.push(range);
+		}
+	}
+
+	// The padding in front of the movie data gets replaced by a new one
+	if kept_chunks.last().is_some_and(|range| file_buffer[range.start..range.start + 4] == RiffChunkType::JUNK.as_bytes())
+	{
+		kept_chunks.pop();
+	}
+
+	let mut region = kept_chunks.into_iter().flat_map(|range| file_buffer[range].to_vec()).collect::<Vec<u8>>();
+	region.extend(build_lists(metadata, &previous_lists)?);
+
+	let available = movie_list - 12;
+	match available.checked_sub(region.len())
+	{
+		Some(0)                   => (),
+		Some(slack) if slack >= 8 => region.extend(RiffChunk::new(RiffChunkType::JUNK.into(), vec![0x00; slack - 8]).serialize()),
+		_ if movable              => (),
+		_                         => return io_error!(
+			Unsupported,
+			"Not enough space in front of the movie data of the AVI file for the metadata, and the movie data can't be moved due to its index!"
+		),
+	}
+
+	file_buffer.splice(12..movie_list, region);
+	update_riff_size(file_buffer, trailing_bytes);
+	return Ok(());
+}
+
+/// Writes the given metadata to the AVI file at the specified path (see
+/// `write_metadata`)
+pub(crate) fn
+file_write_metadata
+(
+	file_system:              &dyn FileSystem,
+	path:                     &Path,
+	general_encoded_metadata: &Vec<u8>
+)
+-> Result<(), std::io::Error>
+{
+	let mut file_buffer = read_file_to_vec(open_file(file_system, path)?)?;
+	write_metadata(&mut file_buffer, general_encoded_metadata)?;
+	file_system.write(path, &file_buffer)?;
+	return Ok(());
+}
+
+/// Removes the mapped sub-chunks and the recording time from the AVI data,
+/// see the module documentation
+pub(crate) fn
+clear_metadata
+(
+	file_buffer: &mut Vec<u8>
+)
+-> Result<(), std::io::Error>
+{
+	write_decoded_metadata(file_buffer, &Metadata::new())
+}
+
+pub(crate) fn
+file_clear_metadata
+(
+	file_system: &dyn FileSystem,
+	path:        &Path
+)
+-> Result<(), std::io::Error>
+{
+	let mut file_buffer = read_file_to_vec(open_file(file_system, path)?)?;
+	let original_buffer = file_buffer.clone();
+	clear_metadata(&mut file_buffer)?;
+	if file_buffer != original_buffer
+	{
+		file_system.write(path, &file_buffer)?;
+	}
+	return Ok(());
+}
+
+/// Provides the `LIST exif` and `LIST INFO` chunks holding the metadata, to
+/// be used by the user (e.g. in combination with another library)
+pub(crate) fn
+as_u8_vec
+(
+	general_encoded_metadata: &Vec<u8>
+)
+-> Vec<u8>
+{
+	Metadata::decode(general_encoded_metadata)
+		.and_then(|metadata| build_lists(&metadata, &[]))
+		.unwrap_or_default()
+}
+
+/// Gets the frame size given by the main AVI header
+pub(crate) fn
+image_dimensions
+(
+	file_buffer: &[u8]
+)
+-> Option<(u32, u32)>
+{
+	// The header list comes first, starting with the main header
+	if !is_avi(file_buffer) || file_buffer.get(20..24)? != HEADER_LIST || file_buffer.get(24..28)? != RiffChunkType::avih.as_bytes()
+	{
+		return None;
+	}
+	let main_header = file_buffer.get(32..32 + 40)?;
+	let width  = u32::from_le_bytes(main_header[32..36].try_into().ok()?);
+	let height = u32::from_le_bytes(main_header[36..40].try_into().ok()?);
+	return Some((width, height));
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::avi::*;
+
+	#[test]
+	fn
+	timestamps()
+	{
+		let date = parse_timestamp("THU OCT 26 16:46:04 2006").unwrap();
+		assert_eq!(date.to_exif_string(), "2006:10:26 16:46:04");
+		assert_eq!(format_asctime(&date), "Thu Oct 26 16:46:04 2006");
+		assert_eq!(parse_timestamp("Mon Jan  2 03:04:05 2017").unwrap().to_exif_string(), "2017:01:02 03:04:05");
+		assert_eq!(format_asctime(&parse_timestamp("2017-01-02").unwrap()), "Mon Jan 02 00:00:00 2017");
+		assert_eq!(parse_timestamp("2006:10:26 16:46:04").unwrap(), date);
+		assert!(parse_timestamp("Someday").is_none());
+		assert_eq!(decode_text(b"Caf\xe9\0"), "Café");
+	}
+}
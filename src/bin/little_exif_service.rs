@@ -0,0 +1,401 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! A small HTTP service for stripping, reading and writing EXIF metadata of
+//! images that are sent as request body. Everything happens in memory using
+//! the `*_vec` functions of `Metadata`, no temporary files are created.
+//! Only available with the `service` feature:
+//!
+//! `cargo run --features service --bin little_exif_service -- 127.0.0.1:8080`
+//!
+//! Endpoints (all `POST`, the image type is given either via the `type` query
+//! parameter - `jpg`, `png` or `webp` - or the `Content-Type` header):
+//! - `/strip`: Responds with the image without any EXIF data
+//! - `/read`:  Responds with the tags of the image as JSON object
+//! - `/write?Artist=...&Copyright=...`: Responds with the image with the
+//!   given string tags set
+//!
+//! Request bodies are read as stream up to `MAX_BODY_SIZE` bytes, responses
+//! are sent using chunked transfer encoding.
+
+use std::io::Cursor;
+use std::io::Read;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+
+use tiny_http::Header;
+use tiny_http::Method;
+use tiny_http::Request;
+use tiny_http::Response;
+use tiny_http::Server;
+
+use little_exif::endian::Endian;
+use little_exif::exif_tag::ExifTag;
+use little_exif::exif_tag_format::ExifTagFormat;
+use little_exif::export::format_value;
+use little_exif::filetype::FileExtension;
+use little_exif::metadata::Metadata;
+
+const DEFAULT_ADDRESS: &str   = "127.0.0.1:8080";
+const MAX_BODY_SIZE:   u64    = 64 * 1024 * 1024;
+const WORKER_COUNT:    usize  = 4;
+
+type ServiceResponse = Response<Cursor<Vec<u8>>>;
+
+fn
+main()
+{
+	let address = std::env::args().nth(1).unwrap_or(DEFAULT_ADDRESS.to_string());
+	let server = match Server::http(&address)
+	{
+		Ok(server) => Arc::new(server),
+		Err(error) => {
+			eprintln!("Could not listen on {}: {}", address, error);
+			std::process::exit(1);
+		},
+	};
+	println!("Listening on http://{}", address);
+
+	let workers = (0..WORKER_COUNT)
+		.map(|_| {
+			let server = server.clone();
+			thread::spawn(move || {
+				for mut request in server.incoming_requests()
+				{
+					// Don't let a panic while processing an image take down
+					// the worker, report it to the client instead
+					let response = panic::catch_unwind(AssertUnwindSafe(|| handle_request(&mut request)))
+						.unwrap_or_else(|_| text_response(500, "Processing the image failed!"));
+					if let Err(error) = request.respond(response)
+					{
+						eprintln!("Could not send response: {}", error);
+					}
+				}
+			})
+		})
+		.collect::<Vec<_>>();
+
+	for worker in workers
+	{
+		let _ = worker.join();
+	}
+}
+
+fn
+handle_request
+(
+	request: &mut Request
+)
+-> ServiceResponse
+{
+	let url = request.url().to_string();
+	let (route, query) = url.split_once('?').unwrap_or((&url, ""));
+	let query_parameters = parse_query(query);
+
+	if !["/strip", "/read", "/write"].contains(&route)
+	{
+		return text_response(404, "Unknown endpoint!");
+	}
+
+	if *request.method() != Method::Post
+	{
+		return text_response(405, "Only POST requests are supported!");
+	}
+
+	let file_type = match get_file_type(request, &query_parameters)
+	{
+		Some(file_type) => file_type,
+		None            => return text_response(415, "Unknown or unsupported image type!"),
+	};
+
+	let mut file_buffer = match read_body(request)
+	{
+		Ok(file_buffer) => file_buffer,
+		Err(response)   => return response,
+	};
+
+	let result = match route
+	{
+		"/strip" => Metadata::clear_metadata(&mut file_buffer, file_type)
+			.map(|_| image_response(file_buffer, file_type)),
+		"/read"  => Metadata::new_from_vec(&file_buffer, file_type)
+			.map(|metadata| json_response(&metadata)),
+		_        => write_tags(&mut file_buffer, file_type, &query_parameters)
+			.map(|_| image_response(file_buffer, file_type)),
+	};
+
+	return result.unwrap_or_else(|error| text_response(400, &error.to_string()));
+}
+
+/// Reads the request body, rejecting it if it exceeds `MAX_BODY_SIZE`
+fn
+read_body
+(
+	request: &mut Request
+)
+-> Result<Vec<u8>, ServiceResponse>
+{
+	if request.body_length().is_some_and(|length| length as u64 > MAX_BODY_SIZE)
+	{
+		return Err(text_response(413, "Request body too large!"));
+	}
+
+	// The length may be unknown (chunked transfer encoding), so only read up
+	// to one byte more than allowed for detecting bodies that are too large
+	let mut file_buffer = Vec::new();
+	if let Err(error) = request.as_reader().take(MAX_BODY_SIZE + 1).read_to_end(&mut file_buffer)
+	{
+		return Err(text_response(400, &error.to_string()));
+	}
+	if file_buffer.len() as u64 > MAX_BODY_SIZE
+	{
+		return Err(text_response(413, "Request body too large!"));
+	}
+
+	return Ok(file_buffer);
+}
+
+/// Sets the string tags given as query parameters (e.g. `Artist=...`) on the
+/// metadata of the image, keeping all other tags
+fn
+write_tags
+(
+	file_buffer:      &mut Vec<u8>,
+	file_type:        FileExtension,
+	query_parameters: &[(String, String)]
+)
+-> Result<(), std::io::Error>
+{
+	let mut metadata = Metadata::new_from_vec(file_buffer, file_type)?;
+
+	for (name, value) in query_parameters
+	{
+		if name == "type"
+		{
+			continue;
+		}
+
+		// EXIF strings are ASCII
+		if !value.is_ascii()
+		{
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidInput,
+				format!("Value of tag {} is not ASCII!", name)
+			));
+		}
+
+		let tag = match get_string_tag_by_name(name, value)
+		{
+			Some(tag) => tag,
+			None      => return Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidInput,
+				format!("Unknown or non-string tag {}!", name)
+			)),
+		};
+		metadata.set_tag(tag);
+	}
+
+	return metadata.write_to_vec(file_buffer, file_type);
+}
+
+/// Constructs the writable string tag with the given name and value
+fn
+get_string_tag_by_name
+(
+	name:  &str,
+	value: &str
+)
+-> Option<ExifTag>
+{
+	let tag = ExifTag::from_name(name).ok()
+		.filter(|tag| tag.name() == name && tag.is_string() && tag.is_writable())?;
+
+	return ExifTag::from_u16_with_data(
+		tag.as_u16(),
+		&ExifTagFormat::STRING,
+		&value.as_bytes().to_vec(),
+		&Endian::Little,
+		&tag.get_group()
+	).ok();
+}
+
+/// Determines the image type via the `type` query parameter or - if that is
+/// not given - the `Content-Type` header
+fn
+get_file_type
+(
+	request:          &Request,
+	query_parameters: &[(String, String)]
+)
+-> Option<FileExtension>
+{
+	if let Some((_, file_type)) = query_parameters.iter().find(|(name, _)| name == "type")
+	{
+		return FileExtension::from_str(&file_type.to_lowercase()).ok();
+	}
+
+	let content_type = request.headers().iter()
+		.find(|header| header.field.equiv("Content-Type"))?
+		.value
+		.as_str()
+		.to_lowercase();
+
+	match content_type.split(';').next().unwrap_or("").trim()
+	{
+		"image/jpeg"            => Some(FileExtension::JPEG),
+		"image/png"             => Some(FileExtension::PNG { as_zTXt_chunk: true }),
+		"image/webp"            => Some(FileExtension::WEBP),
+		"image/tiff"            => Some(FileExtension::TIFF),
+		"image/heic"            => Some(FileExtension::HEIF),
+		"image/heif"            => Some(FileExtension::HEIF),
+		"image/avif"            => Some(FileExtension::AVIF),
+		"image/gif"             => Some(FileExtension::GIF),
+		"image/x-canon-cr2"     => Some(FileExtension::CR2),
+		"image/x-canon-cr3"     => Some(FileExtension::CR3),
+		"image/x-olympus-orf"   => Some(FileExtension::ORF),
+		"image/x-panasonic-rw2" => Some(FileExtension::RW2),
+		"image/x-fuji-raf"      => Some(FileExtension::RAF),
+		"video/x-msvideo"       => Some(FileExtension::AVI),
+		"video/avi"             => Some(FileExtension::AVI),
+		_                       => None,
+	}
+}
+
+/// Splits the query into its name/value pairs and decodes them
+fn
+parse_query
+(
+	query: &str
+)
+-> Vec<(String, String)>
+{
+	query.split('&')
+		.filter(|parameter| !parameter.is_empty())
+		.map(|parameter| {
+			let (name, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+			(percent_decode(name), percent_decode(value))
+		})
+		.collect()
+}
+
+fn
+percent_decode
+(
+	input: &str
+)
+-> String
+{
+	let bytes = input.as_bytes();
+	let mut decoded = Vec::new();
+	let mut index = 0;
+
+	while index < bytes.len()
+	{
+		let hex_digits = bytes.get(index+1..index+3)
+			.and_then(|digits| std::str::from_utf8(digits).ok())
+			.and_then(|digits| u8::from_str_radix(digits, 16).ok());
+
+		match (bytes[index], hex_digits)
+		{
+			(b'%', Some(byte)) => { decoded.push(byte); index += 3; },
+			(b'+', _)          => { decoded.push(b' ');  index += 1; },
+			(byte, _)          => { decoded.push(byte);  index += 1; },
+		}
+	}
+
+	return String::from_utf8_lossy(&decoded).to_string();
+}
+
+fn
+image_response
+(
+	file_buffer: Vec<u8>,
+	file_type:   FileExtension
+)
+-> ServiceResponse
+{
+	let content_type = match file_type
+	{
+		FileExtension::JPEG                     => "image/jpeg",
+		FileExtension::PNG { as_zTXt_chunk: _ } => "image/png",
+		FileExtension::WEBP                     => "image/webp",
+		FileExtension::TIFF                     => "image/tiff",
+		FileExtension::HEIF                     => "image/heif",
+		FileExtension::AVIF                     => "image/avif",
+		FileExtension::GIF                      => "image/gif",
+		FileExtension::CR2                      => "image/x-canon-cr2",
+		FileExtension::CR3                      => "image/x-canon-cr3",
+		FileExtension::ORF                      => "image/x-olympus-orf",
+		FileExtension::RW2                      => "image/x-panasonic-rw2",
+		FileExtension::RAF                      => "image/x-fuji-raf",
+		FileExtension::AVI                      => "video/x-msvideo",
+	};
+
+	// Without a data length, the body gets streamed using chunked encoding
+	return Response::new(200.into(), vec![content_type_header(content_type)], Cursor::new(file_buffer), None, None);
+}
+
+/// Responds with a JSON object mapping the names of the tags to their
+/// (formatted) values
+fn
+json_response
+(
+	metadata: &Metadata
+)
+-> ServiceResponse
+{
+	let fields = metadata.data().iter()
+		.map(|tag| format!("{}:{}", json_string(&tag.name()), json_string(&format_value(tag))))
+		.collect::<Vec<String>>();
+
+	let body = format!("{{{}}}", fields.join(","));
+	return Response::from_string(body).with_header(content_type_header("application/json"));
+}
+
+fn
+json_string
+(
+	value: &str
+)
+-> String
+{
+	let mut escaped = String::from("\"");
+	for character in value.chars()
+	{
+		match character
+		{
+			'"'                          => escaped.push_str("\\\""),
+			'\\'                         => escaped.push_str("\\\\"),
+			character if character < ' ' => escaped.push_str(&format!("\\u{:04x}", character as u32)),
+			character                    => escaped.push(character),
+		}
+	}
+	escaped.push('"');
+	return escaped;
+}
+
+fn
+text_response
+(
+	status_code: u16,
+	message:     &str
+)
+-> ServiceResponse
+{
+	return Response::from_string(message)
+		.with_status_code(status_code)
+		.with_header(content_type_header("text/plain; charset=utf-8"));
+}
+
+fn
+content_type_header
+(
+	content_type: &str
+)
+-> Header
+{
+	Header::from_bytes("Content-Type", content_type).unwrap()
+}
@@ -0,0 +1,368 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! A read-only decoder for binary property lists (`bplist00`), which Apple
+//! devices embed in their maker notes, e.g. the `RunTime` entry of iPhone
+//! photos. See `MakerNote::apple_run_time`.
+//!
+//! Only what is needed for reading is implemented: All object types of
+//! version 00 are decoded, sets are returned as arrays and dictionaries keep
+//! the order of their entries.
+
+use crate::endian::*;
+use crate::general_file_io::io_error;
+
+const BPLIST_SIGNATURE: [u8; 8] = *b"bplist00";
+const TRAILER_LENGTH:   usize   = 32;
+
+/// Limits the nesting of arrays and dictionaries, as objects may (invalidly)
+/// reference themselves
+const MAX_DEPTH:        usize   = 32;
+
+/// A value of a property list
+#[derive(Debug, Clone, PartialEq)]
+pub enum
+PlistValue
+{
+	Null,
+	Boolean(bool),
+	Integer(i128),
+	Real(f64),
+	Date(f64),                                                                  // Seconds since 2001-01-01 00:00:00 UTC
+	Data(Vec<u8>),
+	String(String),
+	Uid(u64),
+	Array(Vec<PlistValue>),
+	Dictionary(Vec<(String, PlistValue)>),
+}
+
+impl
+PlistValue
+{
+	/// Gets the value of the given key, if this is a dictionary containing it
+	pub fn
+	get
+	(
+		&self,
+		key: &str
+	)
+	-> Option<&PlistValue>
+	{
+		match self
+		{
+			PlistValue::Dictionary(entries) => entries.iter().find(|(entry_key, _)| entry_key == key).map(|(_, value)| value),
+			_                               => None,
+		}
+	}
+
+	/// Gets the value as integer, if it is one
+	pub fn
+	as_integer
+	(
+		&self
+	)
+	-> Option<i128>
+	{
+		match self
+		{
+			PlistValue::Integer(value) => Some(*value),
+			_                          => None,
+		}
+	}
+}
+
+/// The information of the trailer at the end of a binary property list
+struct
+Trailer
+{
+	offset_size:    usize,                                                      // Size of the entries of the offset table
+	reference_size: usize,                                                      // Size of object references in arrays and dictionaries
+	object_count:   usize,
+	top_object:     usize,
+	offset_table:   usize,                                                      // Position of the offset table
+}
+
+/// Reads a big endian unsigned integer of the given size (1 to 8 bytes)
+fn
+read_uint
+(
+	data:     &[u8],
+	position: usize,
+	size:     usize
+)
+-> Result<u64, std::io::Error>
+{
+	if size == 0 || size > 8
+	{
+		return io_error!(InvalidData, format!("Invalid integer size {} in binary plist!", size));
+	}
+	match data.get(position..position.saturating_add(size))
+	{
+		Some(bytes) => Ok(bytes.iter().fold(0u64, |value, byte| value << 8 | *byte as u64)),
+		None        => io_error!(UnexpectedEof, "Binary plist is truncated!"),
+	}
+}
+
+fn
+read_bytes
+(
+	data:     &[u8],
+	position: usize,
+	length:   usize
+)
+-> Result<&[u8], std::io::Error>
+{
+	match data.get(position..position.saturating_add(length))
+	{
+		Some(bytes) => Ok(bytes),
+		None        => io_error!(UnexpectedEof, "Binary plist is truncated!"),
+	}
+}
+
+/// Decodes a binary property list
+/// - If the data is not a valid binary property list, an error gets returned.
+///
+/// # Examples
+/// ```
+/// use little_exif::bplist::PlistValue;
+/// use little_exif::bplist::decode;
+///
+/// // A property list consisting of the integer 42
+/// let mut data = b"bplist00".to_vec();
+/// data.extend([0x10, 0x2a, 0x08]);
+/// data.extend([0u8; 6]);
+/// data.extend([1, 1]);
+/// data.extend(1u64.to_be_bytes());
+/// data.extend(0u64.to_be_bytes());
+/// data.extend(10u64.to_be_bytes());
+///
+/// assert_eq!(decode(&data).unwrap(), PlistValue::Integer(42));
+/// ```
+pub fn
+decode
+(
+	data: &[u8]
+)
+-> Result<PlistValue, std::io::Error>
+{
+	if !data.starts_with(&BPLIST_SIGNATURE) || data.len() < BPLIST_SIGNATURE.len() + TRAILER_LENGTH
+	{
+		return io_error!(InvalidData, "Not a binary plist!");
+	}
+
+	let trailer_start = data.len() - TRAILER_LENGTH;
+	let trailer = Trailer
+	{
+		offset_size:    data[trailer_start + 6] as usize,
+		reference_size: data[trailer_start + 7] as usize,
+		object_count:   Endian::Big.read::<u64>(data, trailer_start +  8).unwrap() as usize,
+		top_object:     Endian::Big.read::<u64>(data, trailer_start + 16).unwrap() as usize,
+		offset_table:   Endian::Big.read::<u64>(data, trailer_start + 24).unwrap() as usize,
+	};
+
+	return decode_object(data, &trailer, trailer.top_object, 0);
+}
+
+fn
+decode_object
+(
+	data:    &[u8],
+	trailer: &Trailer,
+	object:  usize,
+	depth:   usize
+)
+-> Result<PlistValue, std::io::Error>
+{
+	if object >= trailer.object_count
+	{
+		return io_error!(InvalidData, "Invalid object reference in binary plist!");
+	}
+	if depth > MAX_DEPTH
+	{
+		return io_error!(InvalidData, "Binary plist is nested too deeply!");
+	}
+
+	let offset_position = trailer.offset_table.saturating_add(object.saturating_mul(trailer.offset_size));
+	let position = read_uint(data, offset_position, trailer.offset_size)? as usize;
+	let marker   = *read_bytes(data, position, 1)?.first().unwrap();
+	let info     = (marker & 0x0f) as usize;
+
+	// The length of data, strings and collections is either stored in the
+	// lower 4 bits of the marker or as integer object following the marker
+	let read_length = || -> Result<(usize, usize), std::io::Error> {
+		if info != 0x0f
+		{
+			return Ok((info, position + 1));
+		}
+		let size_marker = *read_bytes(data, position + 1, 1)?.first().unwrap();
+		if size_marker & 0xf0 != 0x10
+		{
+			return io_error!(InvalidData, "Invalid length in binary plist!");
+		}
+		let size = 1usize << (size_marker & 0x0f);
+		Ok((read_uint(data, position + 2, size)? as usize, position + 2 + size))
+	};
+
+	let decode_references = |start: usize, count: usize| -> Result<Vec<usize>, std::io::Error> {
+		(0..count)
+			.map(|index| read_uint(data, start.saturating_add(index.saturating_mul(trailer.reference_size)), trailer.reference_size).map(|reference| reference as usize))
+			.collect()
+	};
+
+	match marker >> 4
+	{
+		0x0 => match marker
+		{
+			0x00 => Ok(PlistValue::Null),
+			0x08 => Ok(PlistValue::Boolean(false)),
+			0x09 => Ok(PlistValue::Boolean(true)),
+			_    => io_error!(InvalidData, format!("Unknown object type 0x{:02x} in binary plist!", marker)),
+		},
+		0x1 => {
+			let size  = 1usize << info;
+			let bytes = read_bytes(data, position + 1, size)?;
+			let value = match size
+			{
+				// 8 and 16 byte integers are signed, smaller ones unsigned
+				1 | 2 | 4 => bytes.iter().fold(0i128, |value, byte| value << 8 | *byte as i128),
+				8         => i64::from_be_bytes(bytes.try_into().unwrap()) as i128,
+				16        => i128::from_be_bytes(bytes.try_into().unwrap()),
+				_         => return io_error!(InvalidData, "Invalid integer size in binary plist!"),
+			};
+			Ok(PlistValue::Integer(value))
+		},
+		0x2 | 0x3 => {
+			let value = match info
+			{
+				2 => Endian::Big.read::<f32>(data, position + 1).map(|value| value as f64),
+				3 => Endian::Big.read::<f64>(data, position + 1),
+				_ => return io_error!(InvalidData, "Invalid real size in binary plist!"),
+			};
+			match (marker >> 4, value)
+			{
+				(_,   None)        => io_error!(UnexpectedEof, "Binary plist is truncated!"),
+				(0x2, Some(value)) => Ok(PlistValue::Real(value)),
+				(_,   Some(value)) => Ok(PlistValue::Date(value)),
+			}
+		},
+		0x4 => {
+			let (length, start) = read_length()?;
+			Ok(PlistValue::Data(read_bytes(data, start, length)?.to_vec()))
+		},
+		0x5 => {
+			let (length, start) = read_length()?;
+			Ok(PlistValue::String(read_bytes(data, start, length)?.iter().map(|byte| *byte as char).collect()))
+		},
+		0x6 => {
+			let (length, start) = read_length()?;
+			let units = read_bytes(data, start, length.saturating_mul(2))?
+				.chunks(2)
+				.map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+				.collect::<Vec<u16>>();
+			Ok(PlistValue::String(String::from_utf16_lossy(&units)))
+		},
+		0x8 => Ok(PlistValue::Uid(read_uint(data, position + 1, info + 1)?)),
+		0xa | 0xc => {
+			let (count, start) = read_length()?;
+			let values = decode_references(start, count)?
+				.into_iter()
+				.map(|reference| decode_object(data, trailer, reference, depth + 1))
+				.collect::<Result<Vec<PlistValue>, std::io::Error>>()?;
+			Ok(PlistValue::Array(values))
+		},
+		0xd => {
+			let (count, start) = read_length()?;
+			let keys   = decode_references(start, count)?;
+			let values = decode_references(start.saturating_add(count.saturating_mul(trailer.reference_size)), count)?;
+
+			let mut entries = Vec::new();
+			for (key, value) in keys.into_iter().zip(values)
+			{
+				let key = match decode_object(data, trailer, key, depth + 1)?
+				{
+					PlistValue::String(key) => key,
+					_                       => return io_error!(InvalidData, "Dictionary key in binary plist is not a string!"),
+				};
+				entries.push((key, decode_object(data, trailer, value, depth + 1)?));
+			}
+			Ok(PlistValue::Dictionary(entries))
+		},
+		_ => io_error!(InvalidData, format!("Unknown object type 0x{:02x} in binary plist!", marker)),
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::bplist::PlistValue;
+	use crate::bplist::decode;
+
+	/// Builds a binary plist from the given objects, using 1 byte offsets
+	/// and references. The first object is the top object.
+	fn
+	build_bplist
+	(
+		objects: &[Vec<u8>]
+	)
+	-> Vec<u8>
+	{
+		let mut data = b"bplist00".to_vec();
+		let mut offsets = Vec::new();
+		for object in objects
+		{
+			offsets.push(data.len() as u8);
+			data.extend(object);
+		}
+		let offset_table = data.len() as u64;
+		data.extend(offsets);
+		data.extend([0u8; 6]);
+		data.extend([1, 1]);
+		data.extend((objects.len() as u64).to_be_bytes());
+		data.extend(0u64.to_be_bytes());
+		data.extend(offset_table.to_be_bytes());
+		return data;
+	}
+
+	#[test]
+	fn
+	decode_apple_run_time()
+	{
+		// {"flags": 1, "value": 123456789, "timescale": 1000000000, "epoch": 0}
+		// with the keys and values referencing objects 1 to 8
+		let data = build_bplist(&[
+			vec![0xd4, 1, 2, 3, 4, 5, 6, 7, 8],
+			[vec![0x55], b"flags".to_vec()].concat(),
+			[vec![0x55], b"value".to_vec()].concat(),
+			[vec![0x59], b"timescale".to_vec()].concat(),
+			[vec![0x55], b"epoch".to_vec()].concat(),
+			vec![0x10, 0x01],
+			[vec![0x13], 123456789i64.to_be_bytes().to_vec()].concat(),
+			[vec![0x12], 1000000000u32.to_be_bytes().to_vec()].concat(),
+			vec![0x10, 0x00],
+		]);
+
+		let plist = decode(&data).unwrap();
+		assert_eq!(plist.get("value").and_then(PlistValue::as_integer), Some(123456789));
+		assert_eq!(plist.get("timescale").and_then(PlistValue::as_integer), Some(1000000000));
+		assert_eq!(plist.get("missing"), None);
+
+		// Other types and a self referencing array
+		let data = build_bplist(&[
+			vec![0xa4, 1, 2, 3, 4],
+			vec![0x09],
+			[vec![0x23], 0.5f64.to_be_bytes().to_vec()].concat(),
+			vec![0x62, 0x00, 0xe4, 0x00, 0x21],
+			vec![0x42, 0xca, 0xfe],
+		]);
+		assert_eq!(decode(&data).unwrap(), PlistValue::Array(vec![
+			PlistValue::Boolean(true),
+			PlistValue::Real(0.5),
+			PlistValue::String("ä!".to_string()),
+			PlistValue::Data(vec![0xca, 0xfe]),
+		]));
+
+		assert!(decode(&build_bplist(&[vec![0xa1, 0]])).is_err());
+		assert!(decode(&data[..data.len()-1]).is_err());
+		assert!(decode(b"bplist").is_err());
+	}
+}
@@ -0,0 +1,223 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Grouping images into bursts (series of shots taken in quick succession),
+//! e.g. for culling tools that only show the best shot of each burst.
+//!
+//! Two images belong to the same burst if they were taken by the same camera
+//! (same `Make`, `Model` and `SerialNumber`) and the time between them -
+//! based on `DateTimeOriginal` and `SubSecTimeOriginal`, or `CreateDate` and
+//! `SubSecTimeDigitized` - doesn't exceed the given maximum interval. Without
+//! the fractional seconds, shots within the same second can't be ordered, so
+//! the interval should be at least one second in that case.
+//!
+//! Sequence numbers stored in maker notes are vendor specific and only
+//! decoded for some vendors (see `MakerNote::sequence_number`), so they are
+//! not taken into account.
+
+use std::path::Path;
+use std::time::Duration;
+
+use crate::date_inference::parse_exif_date_millis;
+use crate::exif_tag::ExifTag;
+use crate::metadata::Metadata;
+
+/// Gets the time the image was taken in milliseconds (see the module
+/// documentation for the tags used). Returns `None` if it is unknown.
+pub(crate) fn
+capture_time_millis
+(
+	metadata: &Metadata
+)
+-> Option<i64>
+{
+	[
+		(ExifTag::DateTimeOriginal(String::new()), ExifTag::SubSecTimeOriginal(String::new())),
+		(ExifTag::CreateDate(String::new()),       ExifTag::SubSecTimeDigitized(String::new())),
+	]
+		.into_iter()
+		.find_map(|(date_tag, sub_seconds_tag)| parse_exif_date_millis(
+			&metadata.get_string_value(&date_tag)?,
+			metadata.get_string_value(&sub_seconds_tag).as_deref()
+		))
+}
+
+/// Gets the values identifying the camera that took the image, i.e. its
+/// `Make`, `Model` and `SerialNumber` (empty if not set)
+pub(crate) fn
+camera_identity
+(
+	metadata: &Metadata
+)
+-> Vec<String>
+{
+	[
+		ExifTag::Make(String::new()),
+		ExifTag::Model(String::new()),
+		ExifTag::SerialNumber(String::new()),
+	]
+		.into_iter()
+		.map(|tag| metadata.get_string_value(&tag).unwrap_or_default())
+		.collect()
+}
+
+/// The information about a shot that is relevant for grouping it
+#[derive(Debug, Clone, PartialEq)]
+struct
+Shot
+{
+	camera:    Vec<String>,
+	timestamp: i64,                                                             // In milliseconds
+}
+
+impl
+Shot
+{
+	/// Gets the information from the metadata. Returns `None` if it doesn't
+	/// tell when the image was taken
+	fn
+	from_metadata
+	(
+		metadata: &Metadata
+	)
+	-> Option<Shot>
+	{
+		let timestamp = capture_time_millis(metadata)?;
+
+		return Some(Shot { camera: camera_identity(metadata), timestamp });
+	}
+}
+
+/// Assigns a group ID to every shot, see `group_bursts`
+fn
+group_shots
+(
+	shots:        &[Option<Shot>],
+	max_interval: Duration
+)
+-> Vec<usize>
+{
+	// Shots without a timestamp go last, each one in its own group
+	let mut order = (0..shots.len()).collect::<Vec<usize>>();
+	order.sort_by_key(|index| match &shots[*index]
+	{
+		Some(shot) => (0, shot.timestamp, *index),
+		None       => (1, 0,              *index),
+	});
+
+	let max_interval = max_interval.as_millis() as i64;
+	let mut group_ids = vec![0; shots.len()];
+	let mut group_count = 0;
+
+	// The last shot of each camera's current burst and the burst's ID
+	let mut open_bursts: Vec<(&Shot, usize)> = Vec::new();
+
+	for index in order
+	{
+		let shot = match &shots[index]
+		{
+			Some(shot) => shot,
+			None       => {
+				group_ids[index] = group_count;
+				group_count += 1;
+				continue;
+			},
+		};
+
+		match open_bursts.iter_mut().find(|(last_shot, _)| last_shot.camera == shot.camera)
+		{
+			Some(open_burst) if shot.timestamp - open_burst.0.timestamp <= max_interval => {
+				group_ids[index] = open_burst.1;
+				open_burst.0 = shot;
+			},
+			Some(open_burst) => {
+				*open_burst = (shot, group_count);
+				group_ids[index] = group_count;
+				group_count += 1;
+			},
+			None => {
+				open_bursts.push((shot, group_count));
+				group_ids[index] = group_count;
+				group_count += 1;
+			},
+		}
+	}
+
+	return group_ids;
+}
+
+/// Groups the images at the given paths into bursts (see module
+/// documentation). Returns a group ID for each path - images with the same
+/// ID belong to the same burst. IDs are assigned in chronological order of
+/// the bursts, starting with 0. Images without a timestamp get a group of
+/// their own, with IDs after all the others.
+/// - If one of the files can't be read (e.g. because it doesn't exist or has
+///   an unsupported type), an error gets returned.
+///
+/// # Examples
+/// ```no_run
+/// use std::path::Path;
+/// use std::time::Duration;
+/// use little_exif::burst::group_bursts;
+///
+/// let paths = [Path::new("IMG_0001.jpg"), Path::new("IMG_0002.jpg")];
+/// let group_ids = group_bursts(&paths, Duration::from_millis(500)).unwrap();
+/// if group_ids[0] == group_ids[1]
+/// {
+///     println!("Same burst");
+/// }
+/// ```
+pub fn
+group_bursts
+(
+	paths:        &[&Path],
+	max_interval: Duration
+)
+-> Result<Vec<usize>, std::io::Error>
+{
+	let mut shots = Vec::new();
+	for path in paths
+	{
+		shots.push(Shot::from_metadata(&Metadata::new_from_path(path)?));
+	}
+	return Ok(group_shots(&shots, max_interval));
+}
+
+#[cfg(test)]
+mod tests
+{
+	use std::time::Duration;
+
+	use crate::burst::Shot;
+	use crate::burst::group_shots;
+
+	fn
+	shot
+	(
+		model:     &str,
+		timestamp: i64
+	)
+	-> Option<Shot>
+	{
+		Some(Shot { camera: vec!["Cam".to_string(), model.to_string(), String::new()], timestamp })
+	}
+
+	#[test]
+	fn
+	group_shots_into_bursts()
+	{
+		let shots = [
+			shot("A", 1_000),
+			None,
+			shot("A", 1_400),
+			shot("B", 1_200),                                                   // Other camera, interleaved
+			shot("A", 1_800),
+			shot("A", 5_000),                                                   // Gap too large
+			shot("B", 1_300),
+		];
+
+		assert_eq!(group_shots(&shots, Duration::from_millis(500)), vec![0, 3, 0, 1, 0, 2, 1]);
+		assert_eq!(group_shots(&shots, Duration::from_millis(100)), vec![0, 5, 2, 1, 3, 4, 1]);
+		assert_eq!(group_shots(&[], Duration::from_secs(1)), Vec::<usize>::new());
+	}
+}
@@ -0,0 +1,420 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! A catalog that indexes the metadata of image files into an SQLite
+//! database, e.g. as storage layer for a lightweight photo manager. Files are
+//! only (re-)read if their modification time or size changed since they were
+//! last indexed. Only available with the `sqlite` feature.
+//!
+//! The database consists of two tables, which may also be queried directly:
+//! - `files`: `id`, `path` (absolute), `modified` (nanoseconds since the UNIX
+//!   epoch) and `size` of every indexed file
+//! - `tags`: `file_id`, `tag` (hex value), `name` and `value` (formatted as
+//!   described in the `export` module) of every tag of every file
+
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+use rusqlite::params;
+use rusqlite::Connection;
+use rusqlite::OptionalExtension;
+
+use crate::exif_tag::ExifTag;
+use crate::export::format_value;
+use crate::filetype::FileExtension;
+use crate::general_file_io::canonicalize;
+use crate::metadata::Metadata;
+
+/// The statements bringing the schema from one version to the next, where
+/// the version is stored as `user_version` of the database. Append new
+/// migrations, never change existing ones!
+const MIGRATIONS: [&str; 1] = [
+	// Version 0 -> 1
+	"CREATE TABLE files (
+		id       INTEGER PRIMARY KEY,
+		path     TEXT    NOT NULL UNIQUE,
+		modified INTEGER NOT NULL,
+		size     INTEGER NOT NULL
+	);
+	CREATE TABLE tags (
+		file_id  INTEGER NOT NULL REFERENCES files(id) ON DELETE CASCADE,
+		tag      INTEGER NOT NULL,
+		name     TEXT    NOT NULL,
+		value    TEXT    NOT NULL,
+		PRIMARY KEY (file_id, tag)
+	);
+	CREATE INDEX tags_by_value ON tags (tag, value);",
+];
+
+fn
+to_io_error
+(
+	error: rusqlite::Error
+)
+-> std::io::Error
+{
+	std::io::Error::other(error.to_string())
+}
+
+/// What happened during indexing
+#[derive(Debug, Default, PartialEq)]
+pub struct
+IndexSummary
+{
+	pub indexed:   usize, // Files that were new or changed
+	pub unchanged: usize, // Files that were skipped
+	pub removed:   usize, // Entries of files that no longer exist
+}
+
+pub struct
+Catalog
+{
+	connection: Connection,
+}
+
+impl
+Catalog
+{
+	/// Opens the catalog database at the given path, creating it if it does
+	/// not exist yet and updating its schema if necessary
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::catalog::Catalog;
+	/// use little_exif::exif_tag::ExifTag;
+	///
+	/// let mut catalog = Catalog::open(std::path::Path::new("catalog.db")).unwrap();
+	/// catalog.index_directory(std::path::Path::new("photos")).unwrap();
+	/// let files = catalog.find_files(&ExifTag::Model("Testcam".to_string())).unwrap();
+	/// ```
+	pub fn
+	open
+	(
+		path: &Path
+	)
+	-> Result<Catalog, std::io::Error>
+	{
+		Self::from_connection(Connection::open(path).map_err(to_io_error)?)
+	}
+
+	/// Opens a catalog that only exists in memory
+	pub fn
+	open_in_memory
+	()
+	-> Result<Catalog, std::io::Error>
+	{
+		Self::from_connection(Connection::open_in_memory().map_err(to_io_error)?)
+	}
+
+	fn
+	from_connection
+	(
+		mut connection: Connection
+	)
+	-> Result<Catalog, std::io::Error>
+	{
+		connection.pragma_update(None, "foreign_keys", true).map_err(to_io_error)?;
+
+		let version: usize = connection
+			.pragma_query_value(None, "user_version", |row| row.get(0))
+			.map_err(to_io_error)?;
+		if version > MIGRATIONS.len()
+		{
+			return Err(std::io::Error::other(format!(
+				"Catalog schema version {} is newer than the supported version {}!",
+				version,
+				MIGRATIONS.len()
+			)));
+		}
+
+		let transaction = connection.transaction().map_err(to_io_error)?;
+		for (index, migration) in MIGRATIONS.iter().enumerate().skip(version)
+		{
+			transaction.execute_batch(migration).map_err(to_io_error)?;
+			transaction.pragma_update(None, "user_version", index + 1).map_err(to_io_error)?;
+		}
+		transaction.commit().map_err(to_io_error)?;
+
+		return Ok(Catalog { connection });
+	}
+
+	/// Indexes the file at the given path, unless it is already indexed and
+	/// unchanged. Returns whether the file was (re-)indexed.
+	pub fn
+	index_file
+	(
+		&mut self,
+		path: &Path
+	)
+	-> Result<bool, std::io::Error>
+	{
+		let path = canonicalize(path)?;
+		let path_str = path.to_string_lossy().to_string();
+		let (modified, size) = file_stamp(&path)?;
+
+		let stored_stamp: Option<(i64, i64)> = self.connection
+			.query_row(
+				"SELECT modified, size FROM files WHERE path = ?1",
+				params![path_str],
+				|row| Ok((row.get(0)?, row.get(1)?))
+			)
+			.optional()
+			.map_err(to_io_error)?;
+		if stored_stamp == Some((modified, size))
+		{
+			return Ok(false);
+		}
+
+		let metadata = Metadata::new_from_path(&path)?;
+
+		// Replace the previous entry (and its tags) in one go
+		let transaction = self.connection.transaction().map_err(to_io_error)?;
+		transaction.execute("DELETE FROM files WHERE path = ?1", params![path_str]).map_err(to_io_error)?;
+		transaction.execute(
+			"INSERT INTO files (path, modified, size) VALUES (?1, ?2, ?3)",
+			params![path_str, modified, size]
+		).map_err(to_io_error)?;
+		let file_id = transaction.last_insert_rowid();
+
+		for tag in metadata.data()
+		{
+			transaction.execute(
+				"INSERT OR REPLACE INTO tags (file_id, tag, name, value) VALUES (?1, ?2, ?3, ?4)",
+				params![file_id, tag.as_u16(), tag.name(), format_value(tag)]
+			).map_err(to_io_error)?;
+		}
+		transaction.commit().map_err(to_io_error)?;
+
+		return Ok(true);
+	}
+
+	/// Indexes all supported files in the given directory and its
+	/// subdirectories and removes the entries of files in there that no
+	/// longer exist
+	pub fn
+	index_directory
+	(
+		&mut self,
+		path: &Path
+	)
+	-> Result<IndexSummary, std::io::Error>
+	{
+		let directory = canonicalize(path)?;
+		let mut summary = IndexSummary::default();
+
+		let mut files = Vec::new();
+		collect_supported_files(&directory, &mut files)?;
+		for file in &files
+		{
+			if self.index_file(file)?
+			{
+				summary.indexed += 1;
+			}
+			else
+			{
+				summary.unchanged += 1;
+			}
+		}
+
+		for indexed_path in self.indexed_files()?
+		{
+			if indexed_path.starts_with(&directory) && !indexed_path.exists()
+			{
+				self.remove_file(&indexed_path)?;
+				summary.removed += 1;
+			}
+		}
+
+		return Ok(summary);
+	}
+
+	/// Removes the entry of the given file. Returns whether there was one.
+	pub fn
+	remove_file
+	(
+		&mut self,
+		path: &Path
+	)
+	-> Result<bool, std::io::Error>
+	{
+		// The file itself may already be gone, so it can't be canonicalized
+		let path = canonicalize(path).unwrap_or(path.to_path_buf());
+		let removed_count = self.connection
+			.execute("DELETE FROM files WHERE path = ?1", params![path.to_string_lossy()])
+			.map_err(to_io_error)?;
+		return Ok(removed_count > 0);
+	}
+
+	/// Gets the paths of all indexed files
+	pub fn
+	indexed_files
+	(
+		&self
+	)
+	-> Result<Vec<PathBuf>, std::io::Error>
+	{
+		let mut statement = self.connection
+			.prepare("SELECT path FROM files ORDER BY path")
+			.map_err(to_io_error)?;
+		let paths = statement
+			.query_map([], |row| row.get::<_, String>(0))
+			.map_err(to_io_error)?
+			.map(|path| path.map(PathBuf::from))
+			.collect::<Result<Vec<PathBuf>, rusqlite::Error>>()
+			.map_err(to_io_error)?;
+		return Ok(paths);
+	}
+
+	/// Gets the names and formatted values of the tags of the given file
+	pub fn
+	get_tags
+	(
+		&self,
+		path: &Path
+	)
+	-> Result<Vec<(String, String)>, std::io::Error>
+	{
+		let path = canonicalize(path)?;
+		let mut statement = self.connection
+			.prepare(
+				"SELECT tags.name, tags.value FROM tags
+				JOIN files ON files.id = tags.file_id
+				WHERE files.path = ?1 ORDER BY tags.tag"
+			)
+			.map_err(to_io_error)?;
+		let tags = statement
+			.query_map(params![path.to_string_lossy()], |row| Ok((row.get(0)?, row.get(1)?)))
+			.map_err(to_io_error)?
+			.collect::<Result<Vec<(String, String)>, rusqlite::Error>>()
+			.map_err(to_io_error)?;
+		return Ok(tags);
+	}
+
+	/// Finds the files that have the given tag with the given value
+	pub fn
+	find_files
+	(
+		&self,
+		tag: &ExifTag
+	)
+	-> Result<Vec<PathBuf>, std::io::Error>
+	{
+		let mut statement = self.connection
+			.prepare(
+				"SELECT files.path FROM files
+				JOIN tags ON files.id = tags.file_id
+				WHERE tags.tag = ?1 AND tags.value = ?2 ORDER BY files.path"
+			)
+			.map_err(to_io_error)?;
+		let paths = statement
+			.query_map(params![tag.as_u16(), format_value(tag)], |row| row.get::<_, String>(0))
+			.map_err(to_io_error)?
+			.map(|path| path.map(PathBuf::from))
+			.collect::<Result<Vec<PathBuf>, rusqlite::Error>>()
+			.map_err(to_io_error)?;
+		return Ok(paths);
+	}
+}
+
+/// The modification time (in nanoseconds since the UNIX epoch) and the size
+/// of the file, which are used for detecting changes
+fn
+file_stamp
+(
+	path: &Path
+)
+-> Result<(i64, i64), std::io::Error>
+{
+	let file_metadata = std::fs::metadata(path)?;
+	let modified = file_metadata.modified()?
+		.duration_since(UNIX_EPOCH)
+		.map(|duration| duration.as_nanos() as i64)
+		.unwrap_or(0);
+	return Ok((modified, file_metadata.len() as i64));
+}
+
+fn
+collect_supported_files
+(
+	path:  &Path,
+	files: &mut Vec<PathBuf>
+)
+-> Result<(), std::io::Error>
+{
+	if path.is_dir()
+	{
+		for entry in std::fs::read_dir(path)?
+		{
+			collect_supported_files(&entry?.path(), files)?;
+		}
+	}
+	else if FileExtension::from_path(path).is_some()
+	{
+		files.push(path.to_path_buf());
+	}
+
+	return Ok(());
+}
+
+#[cfg(test)]
+mod tests
+{
+	use std::path::Path;
+
+	use crate::catalog::Catalog;
+	use crate::catalog::IndexSummary;
+	use crate::exif_tag::ExifTag;
+	use crate::general_file_io::canonicalize;
+	use crate::metadata::Metadata;
+
+	#[test]
+	fn
+	incremental_indexing()
+	-> Result<(), std::io::Error>
+	{
+		let directory = Path::new("tests/tmp_catalog");
+		let _ = std::fs::remove_dir_all(directory);
+		std::fs::create_dir(directory)?;
+		let path_a = directory.join("a.png");
+		let path_b = directory.join("b.png");
+		std::fs::copy("tests/png_parse_test_image.png", &path_a)?;
+		std::fs::copy("tests/png_parse_test_image.png", &path_b)?;
+
+		let mut catalog = Catalog::open_in_memory()?;
+		assert_eq!(catalog.index_directory(directory)?, IndexSummary { indexed: 2, unchanged: 0, removed: 0 });
+		assert_eq!(catalog.index_directory(directory)?, IndexSummary { indexed: 0, unchanged: 2, removed: 0 });
+
+		// Change one file, remove the other
+		let mut metadata = Metadata::new();
+		metadata.set_tag(ExifTag::Model("Testcam".to_string()));
+		metadata.set_tag(ExifTag::ExposureProgram(vec![2]));
+		metadata.write_to_file(&path_a)?;
+		std::fs::remove_file(&path_b)?;
+
+		assert_eq!(catalog.index_directory(directory)?, IndexSummary { indexed: 1, unchanged: 0, removed: 1 });
+		assert_eq!(catalog.indexed_files()?, vec![canonicalize(&path_a)?]);
+		assert!(catalog.get_tags(&path_a)?.contains(&("Model".to_string(), "Testcam".to_string())));
+		assert_eq!(catalog.find_files(&ExifTag::Model("Testcam".to_string()))?.len(), 1);
+		assert_eq!(catalog.find_files(&ExifTag::Model("Othercam".to_string()))?.len(), 0);
+
+		std::fs::remove_dir_all(directory)?;
+		Ok(())
+	}
+
+	#[test]
+	fn
+	schema_migration()
+	-> Result<(), std::io::Error>
+	{
+		let path = Path::new("tests/tmp_catalog.db");
+		let _ = std::fs::remove_file(path);
+
+		// Opening an existing catalog must not try to create the tables again
+		Catalog::open(path)?;
+		Catalog::open(path)?;
+
+		std::fs::remove_file(path)?;
+		Ok(())
+	}
+}
@@ -0,0 +1,182 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! The rules where the writers place the metadata within a file, given as a
+//! table per format:
+//! - JPEG: The APP1 segment comes right after the SOI marker
+//! - PNG: The eXIf/zTXt chunk comes right after IHDR, which also satisfies
+//!   the requirement of eXIf coming before the first IDAT chunk
+//! - WebP: VP8X comes first, followed by ICCP, ANIM and the image data. The
+//!   EXIF chunk is placed after those, but before XMP and unknown chunks
+//! - TIFF: Not chunk based, IFD0 usually follows the header. The writer
+//!   appends the IFDs instead, as the image data can't be moved (see `tiff`).
+//!   The same applies to CR2, ORF and RW2, which are read-only so far
+//! - HEIF and AVIF: Not chunk based, the EXIF data is an item described by the `meta`
+//!   box. The writer appends its data in an `mdat` box (see `heif`). CR3
+//!   uses the same container, but is read-only so far
+//! - RAF: The EXIF data is stored in the embedded JPEG preview, so the JPEG
+//!   rules apply. RAF is read-only so far
+//! - AVI: RIFF like WebP, but the metadata lists are told apart from the
+//!   other LIST chunks by their list type, so the writer places them right
+//!   in front of the movie data itself (see `avi`)
+//! - GIF: Blocks without four byte types. The application extensions come
+//!   after the extensions in front of the first image, the global color
+//!   table following the logical screen descriptor (see `gif`)
+//!
+//! Adding a new format only requires a new table entry in `chunk_order`.
+
+use crate::chunk_types::PngChunkType;
+use crate::chunk_types::RiffChunkType;
+use crate::filetype::FileExtension;
+
+/// Describes where the metadata of a chunk based format has to be placed
+pub(crate) struct
+ChunkOrder
+{
+	/// Number of bytes at the start of the file before the first chunk,
+	/// e.g. the signature
+	pub(crate) start:           usize,
+
+	/// Types of chunks that have to be placed before the metadata. The
+	/// metadata is placed in front of the first chunk not in this list
+	pub(crate) before_metadata: &'static [[u8; 4]],
+}
+
+const JPEG_ORDER: ChunkOrder = ChunkOrder
+{
+	start:           2,                                                         // SOI marker
+	before_metadata: &[],
+};
+
+const PNG_ORDER: ChunkOrder = ChunkOrder
+{
+	start:           8,                                                         // PNG signature
+	before_metadata: &[PngChunkType::IHDR.as_bytes()],
+};
+
+const WEBP_ORDER: ChunkOrder = ChunkOrder
+{
+	start:           12,                                                        // RIFF header incl. file size and WEBP
+	before_metadata: &[
+		RiffChunkType::VP8X.as_bytes(),
+		RiffChunkType::ICCP.as_bytes(),
+		RiffChunkType::ANIM.as_bytes(),
+		RiffChunkType::VP8.as_bytes(),
+		RiffChunkType::VP8L.as_bytes(),
+	],
+};
+
+const TIFF_ORDER: ChunkOrder = ChunkOrder
+{
+	start:           8,                                                         // TIFF header
+	before_metadata: &[],
+};
+
+const HEIF_ORDER: ChunkOrder = ChunkOrder
+{
+	start:           0,                                                         // The ftyp box is the first box
+	before_metadata: &[],
+};
+
+const AVI_ORDER: ChunkOrder = ChunkOrder
+{
+	start:           12,                                                        // RIFF header incl. file size and AVI
+	before_metadata: &[],
+};
+
+const GIF_ORDER: ChunkOrder = ChunkOrder
+{
+	start:           13,                                                        // Header and logical screen descriptor
+	before_metadata: &[],
+};
+
+/// Gets the ordering rules of the given file type
+pub(crate) fn
+chunk_order
+(
+	file_type: FileExtension
+)
+-> &'static ChunkOrder
+{
+	match file_type
+	{
+		FileExtension::JPEG    => &JPEG_ORDER,
+		FileExtension::PNG{..} => &PNG_ORDER,
+		FileExtension::WEBP    => &WEBP_ORDER,
+		FileExtension::TIFF    => &TIFF_ORDER,
+		FileExtension::HEIF    => &HEIF_ORDER,
+		FileExtension::AVIF    => &HEIF_ORDER,
+		FileExtension::GIF     => &GIF_ORDER,
+		FileExtension::CR2     => &TIFF_ORDER,
+		FileExtension::CR3     => &HEIF_ORDER,
+		FileExtension::ORF     => &TIFF_ORDER,
+		FileExtension::RW2     => &TIFF_ORDER,
+		FileExtension::RAF     => &JPEG_ORDER,
+		FileExtension::AVI     => &AVI_ORDER,
+	}
+}
+
+impl
+ChunkOrder
+{
+	/// Checks if the chunk type has to be placed before the metadata. Chunk
+	/// types are case sensitive in all formats
+	fn
+	precedes_metadata
+	(
+		&self,
+		chunk_type: &[u8; 4]
+	)
+	-> bool
+	{
+		self.before_metadata.contains(chunk_type)
+	}
+
+	/// Determines the position where the metadata gets inserted, given the
+	/// types and byte counts of the chunks of the file (in file order), after
+	/// any previously stored metadata has been removed
+	pub(crate) fn
+	metadata_insert_position
+	(
+		&self,
+		chunks: impl IntoIterator<Item = ([u8; 4], usize)>
+	)
+	-> usize
+	{
+		let mut insert_position = self.start;
+		for (chunk_type, byte_count) in chunks
+		{
+			if !self.precedes_metadata(&chunk_type)
+			{
+				break;
+			}
+			insert_position += byte_count;
+		}
+		return insert_position;
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::chunk_order::chunk_order;
+	use crate::filetype::FileExtension;
+
+	#[test]
+	fn
+	metadata_insert_positions()
+	{
+		let webp = chunk_order(FileExtension::WEBP);
+		assert_eq!(webp.metadata_insert_position(vec![(*b"VP8X", 18), (*b"ICCP", 100), (*b"VP8 ", 500), (*b"XMP ", 50)]), 630);
+		assert_eq!(webp.metadata_insert_position(vec![(*b"VP8X", 18), (*b"VP8 ", 500), (*b"xmp ", 50)]), 530);
+		assert_eq!(webp.metadata_insert_position(vec![(*b"vp8x", 18)]), 12);
+		assert_eq!(webp.metadata_insert_position(vec![(*b"VP8X", 18), (*b"ALPH", 20), (*b"VP8 ", 500)]), 30);
+
+		let png = chunk_order(FileExtension::PNG { as_zTXt_chunk: false });
+		assert_eq!(png.metadata_insert_position(vec![(*b"IHDR", 25), (*b"iCCP", 100), (*b"IDAT", 500)]), 33);
+		assert_eq!(png.metadata_insert_position(vec![(*b"ihdr", 25)]), 8);
+
+		let jpeg = chunk_order(FileExtension::JPEG);
+		assert_eq!(jpeg.metadata_insert_position(Vec::new()), 2);
+	}
+}
@@ -0,0 +1,442 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Typed identifiers of the parts the supported file formats consist of:
+//! PNG chunk types, RIFF chunk fourCCs (used by WebP), ISOBMFF box types
+//! (used by HEIF), JPEG markers and GIF block types. They
+//! are used by little_exif itself and may be used by callers working with
+//! the raw data, so that a typo (e.g. "VP8" instead of "VP8 ") is caught by
+//! the compiler instead of silently never matching.
+//!
+//! ```
+//! use little_exif::chunk_types::GifBlockType;
+//! use little_exif::chunk_types::IsoBoxType;
+//! use little_exif::chunk_types::JpegMarker;
+//! use little_exif::chunk_types::PngChunkType;
+//! use little_exif::chunk_types::RiffChunkType;
+//!
+//! assert_eq!(PngChunkType::from_bytes(*b"eXIf"), PngChunkType::eXIf);
+//! assert_eq!(PngChunkType::from_bytes(*b"prVt"), PngChunkType::Unknown(*b"prVt"));
+//! assert_eq!(RiffChunkType::VP8.as_bytes(), *b"VP8 ");
+//! assert_eq!(IsoBoxType::from_bytes(*b"iloc"), IsoBoxType::iloc);
+//! assert_eq!(JpegMarker::from_byte(0xe1), JpegMarker::APP(1));
+//! assert_eq!(JpegMarker::APP(1).to_string(), "APP1");
+//! assert_eq!(GifBlockType::from_bytes(0x21, 0xff), Some(GifBlockType::Application));
+//! ```
+
+/// This macro builds the enums for the chunk types identified by four bytes
+macro_rules! build_chunk_type_enum {
+	(
+		$( #[$attribute:meta] )*
+		$enum_name:ident,
+		$( (
+			$variant:ident,
+			$bytes:literal,
+			$description:literal
+		) ),*
+	)
+	=>
+	{
+		$( #[$attribute] )*
+		#[allow(non_camel_case_types)]
+		#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+		pub enum
+		$enum_name
+		{
+			$(
+				#[doc = $description]
+				$variant,
+			)*
+			/// Any other type, given by its four bytes
+			Unknown([u8; 4]),
+		}
+
+		impl
+		$enum_name
+		{
+			/// Gets the type identified by the given bytes. Note that the
+			/// comparison is case sensitive
+			pub fn
+			from_bytes
+			(
+				bytes: [u8; 4]
+			)
+			-> $enum_name
+			{
+				match &bytes
+				{
+					$(
+						$bytes => $enum_name::$variant,
+					)*
+					_ => $enum_name::Unknown(bytes),
+				}
+			}
+
+			/// Gets the four bytes identifying the type, as found in a file
+			pub const fn
+			as_bytes
+			(
+				&self
+			)
+			-> [u8; 4]
+			{
+				match self
+				{
+					$(
+						$enum_name::$variant => *$bytes,
+					)*
+					$enum_name::Unknown(bytes) => *bytes,
+				}
+			}
+		}
+
+		impl
+		std::fmt::Display
+		for
+		$enum_name
+		{
+			fn
+			fmt
+			(
+				&self,
+				f: &mut std::fmt::Formatter<'_>
+			)
+			-> std::fmt::Result
+			{
+				write!(f, "{}", String::from_utf8_lossy(&self.as_bytes()))
+			}
+		}
+	}
+}
+
+build_chunk_type_enum![
+	/// The types of PNG chunks. Only the ones of the PNG specification (3rd
+	/// edition) and its extensions are known, private chunks are `Unknown`.
+	PngChunkType,
+	// Variant  Bytes       Description
+	(IHDR,      b"IHDR",    "Image header, always the first chunk"),
+	(PLTE,      b"PLTE",    "Palette"),
+	(IDAT,      b"IDAT",    "Image data, possibly split across several chunks"),
+	(IEND,      b"IEND",    "Image trailer, always the last chunk"),
+	(acTL,      b"acTL",    "Animation control (APNG)"),
+	(bKGD,      b"bKGD",    "Background color"),
+	(cHRM,      b"cHRM",    "Primary chromaticities and white point"),
+	(cICP,      b"cICP",    "Coding-independent code points"),
+	(eXIf,      b"eXIf",    "EXIF data"),
+	(fcTL,      b"fcTL",    "Frame control (APNG)"),
+	(fdAT,      b"fdAT",    "Frame data (APNG)"),
+	(gAMA,      b"gAMA",    "Image gamma"),
+	(hIST,      b"hIST",    "Image histogram"),
+	(iCCP,      b"iCCP",    "Embedded ICC profile"),
+	(iTXt,      b"iTXt",    "International textual data, e.g. XMP"),
+	(pHYs,      b"pHYs",    "Physical pixel dimensions"),
+	(sBIT,      b"sBIT",    "Significant bits"),
+	(sPLT,      b"sPLT",    "Suggested palette"),
+	(sRGB,      b"sRGB",    "Standard RGB color space"),
+	(tEXt,      b"tEXt",    "Textual data, e.g. EXIF data as raw profile"),
+	(tIME,      b"tIME",    "Image last-modification time"),
+	(tRNS,      b"tRNS",    "Transparency"),
+	(zTXt,      b"zTXt",    "Compressed textual data, e.g. EXIF data as raw profile")
+];
+
+build_chunk_type_enum![
+	/// The fourCCs identifying the types of RIFF chunks in WebP and AVI
+	/// files. Note that some of them end with a space, e.g. `VP8` is "VP8 ".
+	RiffChunkType,
+	// Variant  Bytes       Description
+	(RIFF,      b"RIFF",    "The container of the entire file"),
+	(LIST,      b"LIST",    "A list of chunks, whose type follows the size"),
+	(JUNK,      b"JUNK",    "Padding without meaning"),
+	(VP8,       b"VP8 ",    "Lossy image data"),
+	(VP8L,      b"VP8L",    "Lossless image data"),
+	(VP8X,      b"VP8X",    "Extended format header with feature flags and canvas size"),
+	(ALPH,      b"ALPH",    "Alpha channel of lossy image data"),
+	(ANIM,      b"ANIM",    "Animation parameters"),
+	(ANMF,      b"ANMF",    "Animation frame"),
+	(ICCP,      b"ICCP",    "Embedded ICC profile"),
+	(EXIF,      b"EXIF",    "EXIF data"),
+	(XMP,       b"XMP ",    "XMP data"),
+	(avih,      b"avih",    "Main header of an AVI file, e.g. the frame size"),
+	(IDIT,      b"IDIT",    "Recording time of an AVI file"),
+	(indx,      b"indx",    "OpenDML index of an AVI stream, with absolute offsets"),
+	(idx1,      b"idx1",    "Index of the movie data of an AVI file")
+];
+
+build_chunk_type_enum![
+	/// The types of ISOBMFF boxes in HEIF and CR3 files. Only the ones
+	/// little_exif deals with are known, all others are `Unknown`.
+	IsoBoxType,
+	// Variant  Bytes       Description
+	(ftyp,      b"ftyp",    "File type and compatible brands, always the first box"),
+	(meta,      b"meta",    "Untimed metadata, i.e. the items of a HEIF file"),
+	(hdlr,      b"hdlr",    "Handler type of the meta box"),
+	(pitm,      b"pitm",    "Primary item"),
+	(iloc,      b"iloc",    "Locations of the data of the items"),
+	(iinf,      b"iinf",    "Item information, containing an infe box per item"),
+	(infe,      b"infe",    "Item information entry, e.g. the type of an item"),
+	(iref,      b"iref",    "References between items, e.g. Exif describing an image"),
+	(iprp,      b"iprp",    "Item properties, containing ipco and ipma"),
+	(ipco,      b"ipco",    "Item property container"),
+	(ipma,      b"ipma",    "Item property associations"),
+	(ispe,      b"ispe",    "Image spatial extents, i.e. width and height"),
+	(idat,      b"idat",    "Item data stored within the meta box"),
+	(mdat,      b"mdat",    "Media data, e.g. the coded image and the EXIF data"),
+	(moov,      b"moov",    "Movie, i.e. the tracks of an image sequence"),
+	(uuid,      b"uuid",    "User extension, identified by a UUID following the type"),
+	(CMT1,      b"CMT1",    "IFD0 of a Canon CR3 file, as TIFF data"),
+	(CMT2,      b"CMT2",    "ExifIFD of a Canon CR3 file, as TIFF data"),
+	(CMT3,      b"CMT3",    "Maker notes of a Canon CR3 file, as TIFF data"),
+	(CMT4,      b"CMT4",    "GPSIFD of a Canon CR3 file, as TIFF data")
+];
+
+/// The markers of the segments of a JPEG file, given by the byte following
+/// the 0xff prefix
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum
+JpegMarker
+{
+	/// Start of image (0xd8)
+	SOI,
+	/// End of image (0xd9)
+	EOI,
+	/// Start of scan (0xda), after which the entropy coded image data follows
+	SOS,
+	/// Define quantization tables (0xdb)
+	DQT,
+	/// Define Huffman tables (0xc4)
+	DHT,
+	/// Define restart interval (0xdd)
+	DRI,
+	/// Comment (0xfe)
+	COM,
+	/// Temporary private use in arithmetic coding (0x01)
+	TEM,
+	/// Start of frame n (0xc0 to 0xcf, except for 0xc4, 0xc8 and 0xcc), e.g.
+	/// 0 for baseline and 2 for progressive DCT
+	SOF(u8),
+	/// Application segment n (0xe0 to 0xef), e.g. 1 for EXIF and XMP
+	APP(u8),
+	/// Restart marker n (0xd0 to 0xd7)
+	RST(u8),
+	/// Any other marker, given by its byte
+	Unknown(u8),
+}
+
+impl
+JpegMarker
+{
+	/// Gets the marker given by the byte following the 0xff prefix
+	pub fn
+	from_byte
+	(
+		byte: u8
+	)
+	-> JpegMarker
+	{
+		match byte
+		{
+			0xd8                          => JpegMarker::SOI,
+			0xd9                          => JpegMarker::EOI,
+			0xda                          => JpegMarker::SOS,
+			0xdb                          => JpegMarker::DQT,
+			0xc4                          => JpegMarker::DHT,
+			0xdd                          => JpegMarker::DRI,
+			0xfe                          => JpegMarker::COM,
+			0x01                          => JpegMarker::TEM,
+			0xc8 | 0xcc                   => JpegMarker::Unknown(byte),         // JPG extension and DAC
+			0xc0..=0xcf                   => JpegMarker::SOF(byte - 0xc0),
+			0xe0..=0xef                   => JpegMarker::APP(byte - 0xe0),
+			0xd0..=0xd7                   => JpegMarker::RST(byte - 0xd0),
+			_                             => JpegMarker::Unknown(byte),
+		}
+	}
+
+	/// Gets the byte following the 0xff prefix. Numbers of `SOF`, `APP` and
+	/// `RST` markers out of their range wrap around
+	pub const fn
+	as_byte
+	(
+		&self
+	)
+	-> u8
+	{
+		match self
+		{
+			JpegMarker::SOI           => 0xd8,
+			JpegMarker::EOI           => 0xd9,
+			JpegMarker::SOS           => 0xda,
+			JpegMarker::DQT           => 0xdb,
+			JpegMarker::DHT           => 0xc4,
+			JpegMarker::DRI           => 0xdd,
+			JpegMarker::COM           => 0xfe,
+			JpegMarker::TEM           => 0x01,
+			JpegMarker::SOF(n)        => 0xc0 | (*n & 0x0f),
+			JpegMarker::APP(n)        => 0xe0 | (*n & 0x0f),
+			JpegMarker::RST(n)        => 0xd0 | (*n & 0x07),
+			JpegMarker::Unknown(byte) => *byte,
+		}
+	}
+
+	/// Checks if the marker stands on its own, i.e. is not followed by a
+	/// length field and payload
+	pub fn
+	is_standalone
+	(
+		&self
+	)
+	-> bool
+	{
+		matches!(self, JpegMarker::SOI | JpegMarker::EOI | JpegMarker::TEM | JpegMarker::RST(_))
+	}
+}
+
+impl
+std::fmt::Display
+for
+JpegMarker
+{
+	fn
+	fmt
+	(
+		&self,
+		f: &mut std::fmt::Formatter<'_>
+	)
+	-> std::fmt::Result
+	{
+		match self
+		{
+			JpegMarker::SOF(n)        => write!(f, "SOF{}", n),
+			JpegMarker::APP(n)        => write!(f, "APP{}", n),
+			JpegMarker::RST(n)        => write!(f, "RST{}", n),
+			JpegMarker::Unknown(byte) => write!(f, "0xff{:02x}", byte),
+			_                         => write!(f, "{:?}", self),
+		}
+	}
+}
+
+/// The types of the blocks of a GIF data stream following the logical screen
+/// descriptor (and the global color table), given by their introducer and -
+/// for extensions - the label following it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum
+GifBlockType
+{
+	/// Image descriptor (0x2c), followed by the image data
+	Image,
+	/// Graphic control extension (0x21 0xf9), applying to the following image
+	GraphicControl,
+	/// Plain text extension (0x21 0x01)
+	PlainText,
+	/// Comment extension (0x21 0xfe)
+	Comment,
+	/// Application extension (0x21 0xff), e.g. for looping and XMP packets
+	Application,
+	/// Any other extension (0x21), given by its label
+	Extension(u8),
+	/// Trailer (0x3b), ending the data stream
+	Trailer,
+}
+
+impl
+GifBlockType
+{
+	const EXTENSION_INTRODUCER: u8 = 0x21;
+
+	/// Gets the type of the block starting with the given introducer and
+	/// label (which is ignored for blocks other than extensions). Returns
+	/// `None` if the introducer is invalid
+	pub fn
+	from_bytes
+	(
+		introducer: u8,
+		label:      u8
+	)
+	-> Option<GifBlockType>
+	{
+		match (introducer, label)
+		{
+			(0x2c, _)    => Some(GifBlockType::Image),
+			(0x3b, _)    => Some(GifBlockType::Trailer),
+			(0x21, 0xf9) => Some(GifBlockType::GraphicControl),
+			(0x21, 0x01) => Some(GifBlockType::PlainText),
+			(0x21, 0xfe) => Some(GifBlockType::Comment),
+			(0x21, 0xff) => Some(GifBlockType::Application),
+			(0x21, _)    => Some(GifBlockType::Extension(label)),
+			_            => None,
+		}
+	}
+
+	/// Gets the introducer of the block and - for extensions - the label
+	pub const fn
+	as_bytes
+	(
+		&self
+	)
+	-> (u8, Option<u8>)
+	{
+		match self
+		{
+			GifBlockType::Image            => (0x2c, None),
+			GifBlockType::Trailer          => (0x3b, None),
+			GifBlockType::GraphicControl   => (Self::EXTENSION_INTRODUCER, Some(0xf9)),
+			GifBlockType::PlainText        => (Self::EXTENSION_INTRODUCER, Some(0x01)),
+			GifBlockType::Comment          => (Self::EXTENSION_INTRODUCER, Some(0xfe)),
+			GifBlockType::Application      => (Self::EXTENSION_INTRODUCER, Some(0xff)),
+			GifBlockType::Extension(label) => (Self::EXTENSION_INTRODUCER, Some(*label)),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::chunk_types::GifBlockType;
+	use crate::chunk_types::IsoBoxType;
+	use crate::chunk_types::JpegMarker;
+	use crate::chunk_types::PngChunkType;
+	use crate::chunk_types::RiffChunkType;
+
+	#[test]
+	fn
+	round_trip()
+	{
+		for byte in 0..=u8::MAX
+		{
+			assert_eq!(JpegMarker::from_byte(byte).as_byte(), byte);
+		}
+		for chunk_type in [PngChunkType::IHDR, PngChunkType::zTXt, PngChunkType::Unknown(*b"prVt")]
+		{
+			assert_eq!(PngChunkType::from_bytes(chunk_type.as_bytes()), chunk_type);
+		}
+		for chunk_type in [RiffChunkType::VP8, RiffChunkType::XMP, RiffChunkType::JUNK, RiffChunkType::Unknown(*b"strh")]
+		{
+			assert_eq!(RiffChunkType::from_bytes(chunk_type.as_bytes()), chunk_type);
+		}
+		for box_type in [IsoBoxType::ftyp, IsoBoxType::uuid, IsoBoxType::Unknown(*b"hvcC")]
+		{
+			assert_eq!(IsoBoxType::from_bytes(box_type.as_bytes()), box_type);
+		}
+		for block_type in [GifBlockType::Image, GifBlockType::Application, GifBlockType::Extension(0x02), GifBlockType::Trailer]
+		{
+			let (introducer, label) = block_type.as_bytes();
+			assert_eq!(GifBlockType::from_bytes(introducer, label.unwrap_or(0)), Some(block_type));
+		}
+		assert_eq!(GifBlockType::from_bytes(0x00, 0xff), None);
+	}
+
+	#[test]
+	fn
+	names()
+	{
+		assert_eq!(JpegMarker::from_byte(0xc2).to_string(), "SOF2");
+		assert_eq!(JpegMarker::from_byte(0xc8).to_string(), "0xffc8");
+		assert_eq!(JpegMarker::DQT.to_string(), "DQT");
+		assert_eq!(RiffChunkType::VP8.to_string(), "VP8 ");
+		assert_eq!(PngChunkType::Unknown(*b"prVt").to_string(), "prVt");
+
+		// Case matters
+		assert_eq!(PngChunkType::from_bytes(*b"iDAT"), PngChunkType::Unknown(*b"iDAT"));
+		assert_eq!(RiffChunkType::from_bytes(*b"vp8l"), RiffChunkType::Unknown(*b"vp8l"));
+	}
+}
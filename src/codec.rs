@@ -0,0 +1,168 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Checksum and (de)compression routines used for PNG chunks.
+//!
+//! By default everything is done by pure Rust crates. Throughput sensitive
+//! users can swap in faster, hardware accelerated implementations:
+//! - feature `crc32fast`: CRC computation using `crc32fast` (SSE4.2/PCLMULQDQ
+//!   on x86, CRC instructions on aarch64)
+//! - feature `zlib-ng`: zlib (de)compression using `flate2` backed by zlib-ng
+//!   (requires a C compiler and CMake for building)
+
+#[cfg(not(feature = "zlib-ng"))]
+use miniz_oxide::deflate::compress_to_vec_zlib;
+#[cfg(not(feature = "zlib-ng"))]
+use miniz_oxide::inflate::decompress_to_vec_zlib_with_limit;
+#[cfg(not(feature = "zlib-ng"))]
+use miniz_oxide::inflate::TINFLStatus;
+
+use crate::general_file_io::io_error;
+
+#[cfg(not(feature = "crc32fast"))]
+const CRC_32: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
+
+/// Computes the CRC-32 (as used by PNG) over the concatenation of the given
+/// slices, without having to copy them into one buffer first
+#[cfg(not(feature = "crc32fast"))]
+pub(crate) fn
+crc32
+(
+	parts: &[&[u8]]
+)
+-> u32
+{
+	let mut digest = CRC_32.digest();
+	for part in parts
+	{
+		digest.update(part);
+	}
+	return digest.finalize();
+}
+
+/// Computes the CRC-32 (as used by PNG) over the concatenation of the given
+/// slices, without having to copy them into one buffer first
+#[cfg(feature = "crc32fast")]
+pub(crate) fn
+crc32
+(
+	parts: &[&[u8]]
+)
+-> u32
+{
+	let mut hasher = crc32fast::Hasher::new();
+	for part in parts
+	{
+		hasher.update(part);
+	}
+	return hasher.finalize();
+}
+
+/// Compresses the data using zlib with the given level (0 to 10)
+#[cfg(not(feature = "zlib-ng"))]
+pub(crate) fn
+deflate
+(
+	data:  &[u8],
+	level: u8
+)
+-> Vec<u8>
+{
+	return compress_to_vec_zlib(data, level);
+}
+
+/// Compresses the data using zlib with the given level (0 to 10)
+#[cfg(feature = "zlib-ng")]
+pub(crate) fn
+deflate
+(
+	data:  &[u8],
+	level: u8
+)
+-> Vec<u8>
+{
+	use std::io::Write;
+
+	// zlib only knows levels up to 9
+	let compression = flate2::Compression::new(std::cmp::min(level, 9) as u32);
+	let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), compression);
+	encoder.write_all(data).expect("Writing into a Vec can't fail");
+	return encoder.finish().expect("Writing into a Vec can't fail");
+}
+
+/// Inflates zlib compressed data, failing as soon as the output would grow
+/// larger than the given limit instead of allocating all of it
+#[cfg(not(feature = "zlib-ng"))]
+pub(crate) fn
+inflate_with_limit
+(
+	compressed_data: &[u8],
+	limit:           usize
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	match decompress_to_vec_zlib_with_limit(compressed_data, limit)
+	{
+		Ok(inflated_data)
+			=> Ok(inflated_data),
+		Err(error) if error.status == TINFLStatus::HasMoreOutput
+			=> io_error!(InvalidData, format!("Inflated chunk data exceeds the limit of {} bytes!", limit)),
+		Err(_)
+			=> io_error!(InvalidData, "Could not inflate compressed chunk data!"),
+	}
+}
+
+/// Inflates zlib compressed data, failing as soon as the output would grow
+/// larger than the given limit instead of allocating all of it
+#[cfg(feature = "zlib-ng")]
+pub(crate) fn
+inflate_with_limit
+(
+	compressed_data: &[u8],
+	limit:           usize
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	use std::io::Read;
+
+	// Read at most one byte more than allowed to detect exceeding the limit
+	let mut inflated_data = Vec::new();
+	let decoder = flate2::read::ZlibDecoder::new(compressed_data);
+	if decoder.take(limit as u64 + 1).read_to_end(&mut inflated_data).is_err()
+	{
+		return io_error!(InvalidData, "Could not inflate compressed chunk data!");
+	}
+
+	if inflated_data.len() > limit
+	{
+		return io_error!(InvalidData, format!("Inflated chunk data exceeds the limit of {} bytes!", limit));
+	}
+
+	return Ok(inflated_data);
+}
+
+#[cfg(test)]
+mod tests
+{
+	#[test]
+	fn
+	crc32_over_parts()
+	{
+		// Check value of CRC-32/ISO-HDLC, split up at arbitrary positions
+		assert_eq!(crate::codec::crc32(&[b"123456789"]),          0xcbf43926);
+		assert_eq!(crate::codec::crc32(&[b"1234", b"", b"56789"]), 0xcbf43926);
+	}
+
+	#[test]
+	fn
+	deflate_inflate_round_trip()
+	{
+		let data = b"exif exif exif exif exif exif exif exif".repeat(100);
+		for level in [0, 1, 8, 10]
+		{
+			let compressed = crate::codec::deflate(&data, level);
+			assert_eq!(crate::codec::inflate_with_limit(&compressed, data.len()).unwrap(), data);
+			assert!(crate::codec::inflate_with_limit(&compressed, data.len() - 1).is_err());
+		}
+	}
+}
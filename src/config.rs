@@ -0,0 +1,202 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Defaults for the options of all calls, so that applications don't have to
+//! pass the same options to every call site.
+//!
+//! A `Config` holds the byte order of new metadata, whether reading is
+//! lenient, whether writes keep a backup and how writes get stamped. It can
+//! be set for the whole process (`Config::set_global`) or for the current
+//! thread (`Config::set_thread`), which takes precedence over the global one.
+//! `Metadata::new`, `ReadOptions::new` and `WriteOptions::new` start with the
+//! values of the current config (see `Config::current`), so individual
+//! options can still be changed per call. To use a config for a single call
+//! only, pass it using `Metadata::new_with_config`,
+//! `ReadOptions::from_config` or `WriteOptions::from_config`.
+//!
+//! # Examples
+//! ```no_run
+//! use little_exif::config::Config;
+//! use little_exif::endian::Endian;
+//! use little_exif::metadata::Metadata;
+//! use little_exif::write_options::SoftwareStamp;
+//!
+//! Config::set_global(Config::new()
+//!     .endian(Endian::Big)
+//!     .backup(true)
+//!     .software_stamp(SoftwareStamp::Custom("PhotoSorter 2.1".to_string()))
+//! );
+//!
+//! // Keeps a backup and stamps the write, without passing any options
+//! let path = std::path::Path::new("image.jpg");
+//! Metadata::new_from_path(path).unwrap().write_to_file(path).unwrap();
+//! ```
+
+use std::cell::RefCell;
+use std::sync::RwLock;
+
+use crate::endian::Endian;
+use crate::write_options::SoftwareStamp;
+
+static GLOBAL_CONFIG: RwLock<Option<Config>> = RwLock::new(None);
+
+thread_local!
+{
+	static THREAD_CONFIG: RefCell<Option<Config>> = const { RefCell::new(None) };
+}
+
+/// Defaults for the options of all calls, see the module documentation
+#[derive(Debug, Clone, PartialEq)]
+pub struct
+Config
+{
+	pub(crate) endian:         Endian,
+	pub(crate) lenient:        bool,
+	pub(crate) backup:         bool,
+	pub(crate) software_stamp: SoftwareStamp,
+}
+
+impl
+Config
+{
+	/// Constructs the default config, which is also used as long as no other
+	/// one has been set:
+	/// - New metadata is encoded in little endian byte order
+	/// - Reading is lenient, issues are collected as warnings
+	/// - Files are overwritten without keeping a backup
+	/// - No stamping of `Software` and `ModifyDate`
+	pub fn
+	new
+	()
+	-> Config
+	{
+		Config
+		{
+			endian:         Endian::Little,
+			lenient:        true,
+			backup:         false,
+			software_stamp: SoftwareStamp::Off,
+		}
+	}
+
+	/// Sets the byte order of metadata created using `Metadata::new`.
+	/// Metadata read from a file keeps the byte order of the file.
+	pub fn
+	endian
+	(
+		mut self,
+		endian: Endian
+	)
+	-> Config
+	{
+		self.endian = endian;
+		self
+	}
+
+	/// Sets the default of `ReadOptions::lenient`
+	pub fn
+	lenient
+	(
+		mut self,
+		lenient: bool
+	)
+	-> Config
+	{
+		self.lenient = lenient;
+		self
+	}
+
+	/// Sets the default of `WriteOptions::backup`
+	pub fn
+	backup
+	(
+		mut self,
+		backup: bool
+	)
+	-> Config
+	{
+		self.backup = backup;
+		self
+	}
+
+	/// Sets the default of `WriteOptions::auto_stamp_software`
+	pub fn
+	software_stamp
+	(
+		mut self,
+		software_stamp: SoftwareStamp
+	)
+	-> Config
+	{
+		self.software_stamp = software_stamp;
+		self
+	}
+
+	/// Sets the config for all threads that haven't set their own one
+	pub fn
+	set_global
+	(
+		config: Config
+	)
+	{
+		*GLOBAL_CONFIG.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(config);
+	}
+
+	/// Gets the config set using `set_global`, or the default config if
+	/// there is none
+	pub fn
+	global
+	()
+	-> Config
+	{
+		GLOBAL_CONFIG.read()
+			.unwrap_or_else(|poisoned| poisoned.into_inner())
+			.clone()
+			.unwrap_or_default()
+	}
+
+	/// Sets the config for the current thread, taking precedence over the
+	/// global one
+	pub fn
+	set_thread
+	(
+		config: Config
+	)
+	{
+		THREAD_CONFIG.with(|thread_config| *thread_config.borrow_mut() = Some(config));
+	}
+
+	/// Removes the config of the current thread, so that the global one
+	/// applies again
+	pub fn
+	clear_thread
+	()
+	{
+		THREAD_CONFIG.with(|thread_config| *thread_config.borrow_mut() = None);
+	}
+
+	/// Gets the config that applies to the current thread: The one set using
+	/// `set_thread` if there is one, otherwise the global one
+	pub fn
+	current
+	()
+	-> Config
+	{
+		THREAD_CONFIG.with(|thread_config| thread_config.borrow().clone())
+			.unwrap_or_else(Config::global)
+	}
+}
+
+impl
+Default
+for
+Config
+{
+	fn
+	default
+	()
+	-> Config
+	{
+		Config::new()
+	}
+}
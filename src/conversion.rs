@@ -0,0 +1,423 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Carrying metadata over from an image to a converted version of it, e.g.
+//! after batch-converting JPEGs to WebP with a tool that drops the metadata.
+//! The formats of both files are detected by their signatures and the EXIF
+//! data gets stored in the way the target format expects it - an APP1
+//! segment for JPEG, a zTXt chunk for PNG or an EXIF chunk for WebP.
+//!
+//! Tags that describe how the image data of the source is encoded (e.g.
+//! `Compression` or `YCbCrSubSampling`) don't apply to the re-encoded image
+//! and are not carried over. The same goes for the image dimensions, as the
+//! conversion may have resized the image, and for the DNG opcode lists, which
+//! only apply to the raw image data.
+//!
+//! The XMP packet and the IPTC datasets of the source are carried over as
+//! they are if the target format supports them (see
+//! `FileExtension::capabilities`). Fields that can't be represented in the
+//! target, e.g. IPTC keywords when converting a JPEG to WebP or EXIF tags
+//! without counterpart in an AVI file, are not dropped silently but listed
+//! in the returned `CarryReport`.
+
+use std::path::Path;
+
+use crate::exif_tag::ExifTag;
+use crate::filetype::FileExtension;
+use crate::general_file_io::io_error;
+use crate::general_file_io::write_vec_to_file;
+use crate::iptc;
+use crate::jpg;
+use crate::metadata::Metadata;
+use crate::provenance::MetadataSource;
+use crate::xmp;
+
+/// Why a field of the source is missing in the destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum
+DropReason
+{
+	/// The tag only applies to the image data of the source, see the module
+	/// documentation
+	DependsOnImageData,
+
+	/// The value of the tag gets computed when writing, e.g. `StripOffsets`
+	NotWritable,
+
+	/// The destination format doesn't support the kind of metadata at all,
+	/// e.g. IPTC data in PNG files
+	UnsupportedKind,
+
+	/// The destination format supports the kind of metadata, but not the
+	/// field, e.g. most EXIF tags in AVI files
+	UnsupportedField,
+}
+
+/// A field of the source that has not been carried over
+#[derive(Debug, Clone, PartialEq)]
+pub struct
+DroppedField
+{
+	/// The kind of metadata the field belongs to
+	pub kind:   MetadataSource,
+
+	/// The name of the field: The tag name for EXIF (e.g. `Artist`), the
+	/// qualified name for XMP (e.g. `dc:subject`) and the dataset name for
+	/// IPTC (e.g. `Keywords`)
+	pub name:   String,
+
+	pub reason: DropReason,
+}
+
+/// The fields `carry_metadata` could not carry over, in the order EXIF, XMP
+/// and IPTC
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct
+CarryReport
+{
+	dropped: Vec<DroppedField>,
+}
+
+impl
+CarryReport
+{
+	/// Gets all fields that have not been carried over, including the ones
+	/// left out on purpose because they only apply to the source
+	pub fn
+	dropped
+	(
+		&self
+	)
+	-> &[DroppedField]
+	{
+		&self.dropped
+	}
+
+	/// Checks if every field got carried over, apart from the ones that only
+	/// apply to the image data of the source
+	pub fn
+	is_lossless
+	(
+		&self
+	)
+	-> bool
+	{
+		self.dropped.iter().all(|field| field.reason == DropReason::DependsOnImageData)
+	}
+
+	fn
+	drop
+	(
+		&mut self,
+		kind:   MetadataSource,
+		name:   String,
+		reason: DropReason
+	)
+	{
+		if !self.dropped.iter().any(|field| field.kind == kind && field.name == name)
+		{
+			self.dropped.push(DroppedField { kind, name, reason });
+		}
+	}
+}
+
+/// Checks if the tag describes the encoding or dimensions of the image data,
+/// making it invalid after converting the image to another format
+fn
+depends_on_encoding
+(
+	tag: &ExifTag
+)
+-> bool
+{
+	matches!(tag,
+		ExifTag::ImageWidth(_)                |
+		ExifTag::ImageHeight(_)               |
+		ExifTag::BitsPerSample(_)             |
+		ExifTag::Compression(_)               |
+		ExifTag::PhotometricInterpretation(_) |
+		ExifTag::SamplesPerPixel(_)           |
+		ExifTag::RowsPerStrip(_)              |
+		ExifTag::PlanarConfiguration(_)       |
+		ExifTag::ThumbnailOffset(_)           |
+		ExifTag::ThumbnailLength(_)           |
+		ExifTag::YCbCrCoefficients(_)         |
+		ExifTag::YCbCrSubSampling(_)          |
+		ExifTag::YCbCrPositioning(_)          |
+		ExifTag::ReferenceBlackWhite(_)       |
+		ExifTag::ComponentsConfiguration(_)   |
+		ExifTag::CompressedBitsPerPixel(_)    |
+		ExifTag::ExifImageWidth(_)            |
+		ExifTag::ExifImageHeight(_)           |
+		ExifTag::OpcodeList1(_)               |
+		ExifTag::OpcodeList2(_)               |
+		ExifTag::OpcodeList3(_)
+	)
+}
+
+/// Reads the file and determines its type by its signature
+fn
+read_image
+(
+	path: &Path
+)
+-> Result<(Vec<u8>, FileExtension), std::io::Error>
+{
+	if !path.exists()
+	{
+		return io_error!(NotFound, format!("Can't carry metadata - File {} does not exist!", path.display()));
+	}
+
+	let file_buffer = std::fs::read(path)?;
+	match FileExtension::from_signature(&file_buffer)
+	{
+		Some(file_type) => Ok((file_buffer, file_type)),
+		None            => io_error!(InvalidData, format!("Can't carry metadata - Unsupported file type of {}!", path.display())),
+	}
+}
+
+/// Gets the resource blocks of the JPEG image holding the IIM data and its
+/// digest, which is empty if there are none
+fn
+read_iptc_resources
+(
+	file_buffer: &[u8]
+)
+-> Result<Vec<iptc::Resource>, std::io::Error>
+{
+	let mut resources = match jpg::read_photoshop_resources(file_buffer)?
+	{
+		Some(resources) => iptc::decode_resources(&resources)?,
+		None            => return Ok(Vec::new()),
+	};
+	resources.retain(|resource| resource.id == iptc::IPTC_RESOURCE_ID || resource.id == iptc::IPTC_DIGEST_RESOURCE_ID);
+	return Ok(resources);
+}
+
+/// Replaces the IIM data and its digest in the resource blocks of the JPEG
+/// image, keeping all other resources of it
+fn
+write_iptc_resources
+(
+	file_buffer:    &mut Vec<u8>,
+	iptc_resources: Vec<iptc::Resource>
+)
+-> Result<(), std::io::Error>
+{
+	let mut resources = match jpg::read_photoshop_resources(file_buffer)?
+	{
+		Some(resources) => iptc::decode_resources(&resources)?,
+		None            => Vec::new(),
+	};
+	resources.retain(|resource| resource.id != iptc::IPTC_RESOURCE_ID && resource.id != iptc::IPTC_DIGEST_RESOURCE_ID);
+	resources.extend(iptc_resources);
+	return jpg::write_photoshop_resources(file_buffer, &iptc::encode_resources(&resources));
+}
+
+/// Replaces the metadata of the image at `destination_path` with the one of
+/// the image at `source_path`, excluding tags that only apply to the image
+/// data of the source (see module documentation). If the source has no EXIF
+/// data, the one of the destination gets cleared, while its XMP packet and
+/// IPTC data are only replaced if the source has some. The source is not
+/// modified. Returns the fields that could not be carried over.
+/// - If one of the files doesn't exist, has an unsupported format or the
+///   metadata can't be stored in the destination (e.g. because it's too
+///   large for a JPEG APP1 segment), an error gets returned and the
+///   destination is left unchanged.
+///
+/// # Examples
+/// ```no_run
+/// use little_exif::conversion::carry_metadata;
+///
+/// let report = carry_metadata(
+///     std::path::Path::new("original.jpg"),
+///     std::path::Path::new("converted.webp")
+/// ).unwrap();
+///
+/// for field in report.dropped()
+/// {
+///     println!("{:?} field {} not carried over: {:?}", field.kind, field.name, field.reason);
+/// }
+/// ```
+pub fn
+carry_metadata
+(
+	source_path:      &Path,
+	destination_path: &Path
+)
+-> Result<CarryReport, std::io::Error>
+{
+	let (source_buffer, source_type) = read_image(source_path)?;
+	let (mut destination_buffer, destination_type) = read_image(destination_path)?;
+	let capabilities = destination_type.capabilities();
+
+	let source_metadata = Metadata::new_from_vec(&source_buffer, source_type)?;
+	let mut report = CarryReport::default();
+
+	let mut metadata = Metadata::new();
+	for tag in source_metadata.data()
+	{
+		if depends_on_encoding(tag)
+		{
+			report.drop(MetadataSource::Exif, tag.name(), DropReason::DependsOnImageData);
+		}
+		else if !tag.is_writable()
+		{
+			// Offset tags get recreated when writing
+			if tag.is_offset_tag().is_none()
+			{
+				report.drop(MetadataSource::Exif, tag.name(), DropReason::NotWritable);
+			}
+		}
+		else
+		{
+			metadata.set_tag(tag.clone());
+		}
+	}
+
+	if metadata.data().is_empty()
+	{
+		Metadata::clear_metadata(&mut destination_buffer, destination_type)?;
+	}
+	else
+	{
+		metadata.write_to_vec(&mut destination_buffer, destination_type)?;
+
+		// Formats like AVI only store some of the tags
+		let written = Metadata::new_from_vec(&destination_buffer, destination_type)?;
+		for tag in metadata.data()
+		{
+			if written.get_tag(tag).is_none()
+			{
+				report.drop(MetadataSource::Exif, tag.name(), DropReason::UnsupportedField);
+			}
+		}
+	}
+
+	let packet = match source_type.capabilities().xmp
+	{
+		true  => xmp::read_xmp_from_vec(&source_buffer)?,
+		false => None,
+	};
+	if let Some(packet) = packet
+	{
+		if capabilities.xmp
+		{
+			xmp::write_xmp_to_vec(&mut destination_buffer, &packet)?;
+		}
+		else
+		{
+			for name in xmp::property_names(&packet)
+			{
+				report.drop(MetadataSource::Xmp, name, DropReason::UnsupportedKind);
+			}
+		}
+	}
+
+	let iptc_resources = match source_type.capabilities().iptc
+	{
+		true  => read_iptc_resources(&source_buffer)?,
+		false => Vec::new(),
+	};
+	if !iptc_resources.is_empty()
+	{
+		if capabilities.iptc
+		{
+			write_iptc_resources(&mut destination_buffer, iptc_resources)?;
+		}
+		else if let Some(iim) = iptc_resources.iter().find(|resource| resource.id == iptc::IPTC_RESOURCE_ID)
+		{
+			// Only the application record holds actual fields, its first
+			// dataset being the record version
+			for dataset in iptc::decode_iim(&iim.data)?
+			{
+				if dataset.record == 2 && dataset.number != 0
+				{
+					report.drop(MetadataSource::Iptc, dataset.name(), DropReason::UnsupportedKind);
+				}
+			}
+		}
+	}
+
+	write_vec_to_file(destination_path, &destination_buffer)?;
+	return Ok(report);
+}
+
+#[cfg(test)]
+mod tests
+{
+	use std::path::Path;
+
+	use crate::conversion::*;
+	use crate::iptc::Dataset;
+	use crate::iptc::Resource;
+
+	#[test]
+	fn
+	carry_jpg_to_other_formats()
+	-> Result<(), std::io::Error>
+	{
+		let source = Path::new("tests/tmp_carry_source.jpg");
+		let mut file_buffer = std::fs::read("tests/sample2.jpg")?;
+		let mut metadata = Metadata::new();
+		metadata.set_tag(ExifTag::Artist("Jane Doe".to_string()));
+		metadata.set_tag(ExifTag::Compression(vec![6]));
+		metadata.set_tag(ExifTag::ISO(vec![400]));
+		metadata.write_to_vec(&mut file_buffer, FileExtension::JPEG)?;
+
+		let packet = xmp::set_properties(None, ("photoshop", "http://ns.adobe.com/photoshop/1.0/"), &[("City", Some("Graz".to_string()))])?;
+		xmp::write_xmp_to_vec(&mut file_buffer, &packet)?;
+		let iim = iptc::encode_iim(&[
+			Dataset { record: 2, number: 0,  data: vec![0, 4] },
+			Dataset { record: 2, number: 25, data: b"Sea".to_vec() },
+			Dataset { record: 2, number: 25, data: b"Lighthouse".to_vec() },
+			Dataset { record: 2, number: 90, data: b"Graz".to_vec() },
+		]);
+		write_iptc_resources(&mut file_buffer, vec![Resource { id: iptc::IPTC_RESOURCE_ID, name: Vec::new(), data: iim.clone() }])?;
+		std::fs::write(source, &file_buffer)?;
+
+		let dropped = |kind: MetadataSource, name: &str, reason: DropReason| DroppedField { kind, name: name.to_string(), reason };
+		let compression = dropped(MetadataSource::Exif, "Compression", DropReason::DependsOnImageData);
+		let city_xmp = dropped(MetadataSource::Xmp, "photoshop:City", DropReason::UnsupportedKind);
+		let keywords = dropped(MetadataSource::Iptc, "Keywords", DropReason::UnsupportedKind);
+		let city_iptc = dropped(MetadataSource::Iptc, "City", DropReason::UnsupportedKind);
+		let iso = dropped(MetadataSource::Exif, "ISO", DropReason::UnsupportedField);
+
+		for (original, destination, expected) in [
+			("tests/sample2_simple_lossy.webp", "tests/tmp_carry_destination.webp", vec![&compression, &city_xmp, &keywords, &city_iptc]),
+			("tests/sample2.png",               "tests/tmp_carry_destination.png",  vec![&compression, &keywords, &city_iptc]),
+			("tests/sample2.jpg",               "tests/tmp_carry_destination.jpg",  vec![&compression]),
+			("tests/sample.avi",                "tests/tmp_carry_destination.avi",  vec![&compression, &iso, &city_xmp, &keywords, &city_iptc]),
+		]
+		{
+			let destination = Path::new(destination);
+			std::fs::copy(original, destination)?;
+			let report = carry_metadata(source, destination);
+			let carried = std::fs::read(destination);
+			std::fs::remove_file(destination)?;
+
+			let report = report?;
+			assert_eq!(report.dropped().iter().collect::<Vec<_>>(), expected);
+			assert_eq!(report.is_lossless(), expected.len() == 1);
+
+			let carried = carried?;
+			let file_type = FileExtension::from_signature(&carried).unwrap();
+			let metadata = Metadata::new_from_vec(&carried, file_type)?;
+			assert_eq!(metadata.get_tag(&ExifTag::Artist(String::new())), Some(&ExifTag::Artist("Jane Doe".to_string())));
+			assert!(metadata.get_tag(&ExifTag::Compression(Vec::new())).is_none());
+			if file_type.capabilities().xmp
+			{
+				assert_eq!(xmp::read_xmp_from_vec(&carried)?, Some(packet.clone()));
+			}
+			if file_type.capabilities().iptc
+			{
+				assert!(read_iptc_resources(&carried)?.iter().any(|resource| resource.data == iim));
+			}
+		}
+
+		let unsupported = carry_metadata(source, Path::new("tests/sample1.txt"));
+		std::fs::remove_file(source)?;
+		assert!(unsupported.is_err());
+		Ok(())
+	}
+}
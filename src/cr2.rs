@@ -0,0 +1,36 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Canon CR2 raw files are TIFF files with four IFDs: IFD0 describes the
+//! full size JPEG preview and holds the metadata (including the ExifIFD with
+//! the exposure settings, the serial number and Canon's maker notes), IFD1
+//! the thumbnail, IFD2 a small RGB preview and IFD3 the raw data. The TIFF
+//! header is followed by `CR`, the version (2.0) and the offset of IFD3.
+//!
+//! Only reading is supported, see `raw_file`.
+
+use crate::filetype::FileExtension;
+use crate::raw_file::TiffRawFormat;
+use crate::tiff;
+
+const CR2_MAGIC: [u8; 4] = [b'C', b'R', 0x02, 0x00];                           // "CR", major and minor version
+
+pub(crate) const CR2: TiffRawFormat = TiffRawFormat
+{
+	file_type:        FileExtension::CR2,
+	signature_length: 12,
+	is_format:        is_cr2,
+};
+
+/// Checks whether the data starts with the TIFF header and magic of a CR2
+/// file
+pub(crate) fn
+is_cr2
+(
+	file_buffer: &[u8]
+)
+-> bool
+{
+	return file_buffer.starts_with(&tiff::TIFF_SIGNATURE_LITTLE_ENDIAN)
+		&& file_buffer.get(8..12) == Some(&CR2_MAGIC);
+}
@@ -0,0 +1,147 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Canon CR3 raw files are ISOBMFF files (like HEIF, see `heif`) of the
+//! brand `crx `. The metadata is stored in a `uuid` box within the `moov`
+//! box, identified by Canon's UUID, which contains one box per IFD, each of
+//! them holding complete TIFF data with the IFD as its IFD0:
+//! - `CMT1`: IFD0
+//! - `CMT2`: ExifIFD
+//! - `CMT3`: Canon's maker notes
+//! - `CMT4`: GPSIFD
+//!
+//! When reading, IFD0, ExifIFD and GPSIFD are combined into a single block of
+//! TIFF data (see `tiff::combine_ifds`), so that they are decoded like the
+//! EXIF data of any other file. The maker notes are not included, as the
+//! offsets of Canon's maker notes depend on their position.
+//!
+//! Only reading is supported, see `raw_file`.
+
+use std::io::Read;
+use std::io::Seek;
+use std::path::Path;
+
+use crate::chunk_types::IsoBoxType;
+use crate::errors::MissingExif;
+use crate::general_file_io::*;
+use crate::heif;
+use crate::tiff;
+use crate::vfs::FileSystem;
+use crate::vfs::ReadSeek;
+
+/// The brand of CR3 files, given by the `ftyp` box
+pub(crate) const CR3_BRANDS: [[u8; 4]; 1] = [*b"crx "];
+
+/// The UUID of the `uuid` box holding the metadata
+const CANON_UUID: [u8; 16] = [
+	0x85, 0xc0, 0xb6, 0x87, 0x82, 0x0f, 0x11, 0xe0,
+	0x81, 0x11, 0xf4, 0xce, 0x46, 0x2b, 0x6a, 0x48,
+];
+
+const EXIF_OFFSET_TAG: u16 = 0x8769;
+const GPS_INFO_TAG:    u16 = 0x8825;
+
+/// Opens the CR3 file at the given path for reading
+fn
+open_file
+(
+	file_system: &dyn FileSystem,
+	path:        &Path
+)
+-> Result<Box<dyn ReadSeek>, std::io::Error>
+{
+	if !file_system.exists(path)
+	{
+		return io_error!(NotFound, "Can't open CR3 file - File does not exist!");
+	}
+
+	return file_system.open(path);
+}
+
+/// Gets the boxes within Canon's `uuid` box in the `moov` box
+fn
+metadata_boxes
+(
+	file_buffer: &[u8]
+)
+-> Result<Vec<heif::IsoBox>, std::io::Error>
+{
+	let boxes = heif::read_boxes(file_buffer, 0, file_buffer.len())?;
+	let Some(moov) = boxes.iter().find(|iso_box| iso_box.box_type == IsoBoxType::moov) else
+	{
+		return io_error!(InvalidData, "Can't read CR3 file - No moov box found!");
+	};
+
+	for iso_box in heif::read_boxes(file_buffer, moov.payload_start, moov.end)?
+	{
+		if iso_box.box_type == IsoBoxType::uuid && file_buffer[iso_box.payload_start - 16..iso_box.payload_start] == CANON_UUID
+		{
+			return heif::read_boxes(file_buffer, iso_box.payload_start, iso_box.end);
+		}
+	}
+	return MissingExif::error("No EXIF data found!");
+}
+
+/// Reads the raw EXIF data from the CR3 file, i.e. the combined IFDs of the
+/// `CMT1`, `CMT2` and `CMT4` boxes
+pub(crate) fn
+read_metadata
+<T: Read + Seek>
+(
+	cursor: &mut T
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	let _span = span!("parse", format = "CR3");
+
+	let mut file_buffer = Vec::new();
+	cursor.read_to_end(&mut file_buffer)?;
+	if !heif::has_brand(&file_buffer, &CR3_BRANDS)
+	{
+		return io_error!(InvalidData, "Can't open CR3 file - Wrong brand!");
+	}
+
+	let boxes = metadata_boxes(&file_buffer)?;
+	let payload = |box_type: IsoBoxType| boxes.iter()
+		.find(|iso_box| iso_box.box_type == box_type)
+		.map(|iso_box| &file_buffer[iso_box.payload_start..iso_box.end]);
+
+	let Some(ifd0) = payload(IsoBoxType::CMT1) else
+	{
+		return MissingExif::error("No EXIF data found!");
+	};
+	let sub_ifds = [(EXIF_OFFSET_TAG, IsoBoxType::CMT2), (GPS_INFO_TAG, IsoBoxType::CMT4)].into_iter()
+		.filter_map(|(pointer_tag, box_type)| payload(box_type).map(|sub_ifd| (pointer_tag, sub_ifd)))
+		.collect::<Vec<_>>();
+
+	let mut raw_exif_data = EXIF_HEADER.to_vec();
+	raw_exif_data.extend(tiff::combine_ifds(ifd0, &sub_ifds)?);
+	return Ok(raw_exif_data);
+}
+
+
+
+pub(crate) fn
+file_read_metadata
+(
+	file_system: &dyn FileSystem,
+	path:        &Path
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	read_metadata(&mut open_file(file_system, path)?)
+}
+
+
+
+/// Provides the CR3 specific encoding result as vector of bytes to be used
+/// by the user, which is TIFF data like the content of the `CMT` boxes
+pub(crate) fn
+as_u8_vec
+(
+	general_encoded_metadata: &Vec<u8>
+)
+-> Vec<u8>
+{
+	tiff::as_u8_vec(general_encoded_metadata)
+}
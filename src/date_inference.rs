@@ -0,0 +1,280 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Inferring the date an image was taken from other sources than its EXIF
+//! data, for images that lost it along the way (e.g. when sent via WhatsApp)
+//! or never had it (e.g. screenshots). See
+//! `Metadata::infer_date_from_filename` and
+//! `Metadata::infer_date_from_modification_time`.
+//!
+//! Patterns for file names consist of literal characters and the following
+//! placeholders, each matching a fixed number of digits:
+//! - `%Y`: Year (4 digits)
+//! - `%m`, `%d`: Month and day (2 digits each)
+//! - `%H`, `%M`, `%S`: Hour, minute and second (2 digits each)
+//!
+//! The pattern may match anywhere in the file name. If it doesn't contain a
+//! time, midnight is used.
+
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+/// Patterns for the file names used by common phones, messengers and
+/// screenshot tools
+pub const DEFAULT_FILENAME_DATE_PATTERNS: [&str; 8] = [
+	"IMG_%Y%m%d_%H%M%S",                                                        // Android cameras
+	"PXL_%Y%m%d_%H%M%S",                                                        // Google Pixel
+	"IMG-%Y%m%d-WA",                                                            // WhatsApp
+	"Screenshot_%Y%m%d-%H%M%S",                                                 // Android
+	"Screenshot_%Y-%m-%d-%H-%M-%S",                                             // Android (older versions)
+	"Screenshot %Y-%m-%d at %H.%M.%S",                                          // macOS
+	"%Y-%m-%d %H.%M.%S",                                                        // Dropbox camera uploads
+	"%Y%m%d_%H%M%S",                                                            // Generic
+];
+
+/// Tries to match the pattern at the start of the given name, returning the
+/// date and time components (year, month, day, hour, minute, second)
+fn
+match_pattern_at
+(
+	name:    &[char],
+	pattern: &str
+)
+-> Option<[u32; 6]>
+{
+	let mut components = [0u32, 1, 1, 0, 0, 0];
+	let mut pattern_chars = pattern.chars();
+	let mut position = 0;
+
+	while let Some(pattern_char) = pattern_chars.next()
+	{
+		if pattern_char != '%'
+		{
+			if name.get(position) != Some(&pattern_char)
+			{
+				return None;
+			}
+			position += 1;
+			continue;
+		}
+
+		let (index, digit_count) = match pattern_chars.next()?
+		{
+			'Y' => (0, 4),
+			'm' => (1, 2),
+			'd' => (2, 2),
+			'H' => (3, 2),
+			'M' => (4, 2),
+			'S' => (5, 2),
+			_   => return None,
+		};
+
+		let digits = name.get(position..position+digit_count)?;
+		let mut value = 0;
+		for digit in digits
+		{
+			value = value * 10 + digit.to_digit(10)?;
+		}
+		components[index] = value;
+		position += digit_count;
+	}
+
+	return Some(components);
+}
+
+/// Checks if the components form a plausible date and time, rejecting e.g.
+/// random numbers in a file name that happen to match a pattern
+fn
+is_plausible
+(
+	components: &[u32; 6]
+)
+-> bool
+{
+	let [year, month, day, hour, minute, second] = *components;
+	return (1900..=2100).contains(&year)
+		&& (1..=12).contains(&month)
+		&& (1..=days_in_month(year, month)).contains(&day)
+		&& hour   < 24
+		&& minute < 60
+		&& second < 60;
+}
+
+/// The number of days of the month in the given year
+pub(crate) fn
+days_in_month
+(
+	year:  u32,
+	month: u32
+)
+-> u32
+{
+	match month
+	{
+		2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => 29,
+		2                                                          => 28,
+		4 | 6 | 9 | 11                                             => 30,
+		_                                                          => 31,
+	}
+}
+
+/// Formats the components as EXIF date, e.g. `2024:01:31 18:05:00`
+fn
+format_exif_date
+(
+	components: &[u32; 6]
+)
+-> String
+{
+	let [year, month, day, hour, minute, second] = *components;
+	format!("{:04}:{:02}:{:02} {:02}:{:02}:{:02}", year, month, day, hour, minute, second)
+}
+
+/// Finds the first pattern that matches the name and returns the date as
+/// EXIF date string
+pub(crate) fn
+parse_date_from_name
+(
+	name:     &str,
+	patterns: &[&str]
+)
+-> Option<String>
+{
+	let name = name.chars().collect::<Vec<char>>();
+
+	for pattern in patterns
+	{
+		for start in 0..name.len()
+		{
+			if let Some(components) = match_pattern_at(&name[start..], pattern)
+			{
+				if is_plausible(&components)
+				{
+					return Some(format_exif_date(&components));
+				}
+			}
+		}
+	}
+
+	return None;
+}
+
+/// Formats the point in time as EXIF date string in UTC
+pub(crate) fn
+format_system_time_utc
+(
+	time: SystemTime
+)
+-> Option<String>
+{
+	let seconds = time.duration_since(UNIX_EPOCH).ok()?.as_secs();
+	let days = seconds / 86400;
+	let seconds_of_day = seconds % 86400;
+
+	// Convert the days since 1970-01-01 to a date in the proleptic Gregorian
+	// calendar, see http://howardhinnant.github.io/date_algorithms.html
+	let shifted_days = days + 719468;
+	let era = shifted_days / 146097;
+	let day_of_era = shifted_days % 146097;
+	let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+	let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+	let month_index = (5 * day_of_year + 2) / 153;
+	let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+	let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+	let year = year_of_era + era * 400 + (month <= 2) as u64;
+
+	return Some(format_exif_date(&[
+		year as u32,
+		month as u32,
+		day as u32,
+		(seconds_of_day / 3600) as u32,
+		(seconds_of_day % 3600 / 60) as u32,
+		(seconds_of_day % 60) as u32,
+	]));
+}
+
+/// Parses an EXIF date string (e.g. `2024:01:31 18:05:00`) and the optional
+/// fractional seconds (the digits stored in the `SubSecTime*` tags) into
+/// milliseconds since 1970-01-01 00:00:00, ignoring any time zone
+pub(crate) fn
+parse_exif_date_millis
+(
+	date:        &str,
+	sub_seconds: Option<&str>
+)
+-> Option<i64>
+{
+	let date = date.trim_end_matches('\0');
+	let pattern_components = match_pattern_at(&date.chars().collect::<Vec<char>>(), "%Y:%m:%d %H:%M:%S")?;
+	if !is_plausible(&pattern_components)
+	{
+		return None;
+	}
+	let [year, month, day, hour, minute, second] = pattern_components.map(|component| component as i64);
+
+	// Convert the date to days since 1970-01-01 (see format_system_time_utc)
+	let shifted_year = if month <= 2 { year - 1 } else { year };
+	let era = shifted_year / 400;
+	let year_of_era = shifted_year - era * 400;
+	let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+	let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+	let days = era * 146097 + day_of_era - 719468;
+
+	// Fractional seconds are given as digits after the decimal point
+	let milliseconds = sub_seconds
+		.map(|digits| digits.trim_end_matches('\0').trim())
+		.filter(|digits| !digits.is_empty() && digits.chars().all(|digit| digit.is_ascii_digit()))
+		.map(|digits| format!("{:0<3}", &digits[..digits.len().min(3)]).parse::<i64>().unwrap_or(0))
+		.unwrap_or(0);
+
+	return Some(((days * 86400 + hour * 3600 + minute * 60 + second) * 1000) + milliseconds);
+}
+
+#[cfg(test)]
+mod tests
+{
+	use std::time::Duration;
+	use std::time::UNIX_EPOCH;
+
+	use crate::date_inference::DEFAULT_FILENAME_DATE_PATTERNS;
+	use crate::date_inference::format_system_time_utc;
+	use crate::date_inference::parse_date_from_name;
+	use crate::date_inference::parse_exif_date_millis;
+
+	#[test]
+	fn
+	parse_dates_from_names()
+	{
+		let parse = |name: &str| parse_date_from_name(name, &DEFAULT_FILENAME_DATE_PATTERNS);
+
+		assert_eq!(parse("IMG-20240131-WA0003.jpg"),                    Some("2024:01:31 00:00:00".to_string()));
+		assert_eq!(parse("PXL_20230704_183015123.jpg"),                 Some("2023:07:04 18:30:15".to_string()));
+		assert_eq!(parse("Screenshot 2022-12-24 at 09.41.00.png"),      Some("2022:12:24 09:41:00".to_string()));
+		assert_eq!(parse("Urlaub_Screenshot_20210228-235959.png"),      Some("2021:02:28 23:59:59".to_string()));
+
+		// Implausible dates and names without dates
+		assert_eq!(parse("IMG-20230229-WA0001.jpg"),                    None);
+		assert_eq!(parse("IMG_99999999_999999.jpg"),                    None);
+		assert_eq!(parse("holiday.jpg"),                                None);
+
+		assert_eq!(parse_date_from_name("scan 31.01.2024.png", &["%d.%m.%Y"]), Some("2024:01:31 00:00:00".to_string()));
+	}
+
+	#[test]
+	fn
+	format_system_times()
+	{
+		assert_eq!(format_system_time_utc(UNIX_EPOCH),                                    Some("1970:01:01 00:00:00".to_string()));
+		assert_eq!(format_system_time_utc(UNIX_EPOCH + Duration::from_secs(951782400)),   Some("2000:02:29 00:00:00".to_string()));
+		assert_eq!(format_system_time_utc(UNIX_EPOCH + Duration::from_secs(1706724300)),  Some("2024:01:31 18:05:00".to_string()));
+	}
+	#[test]
+	fn
+	parse_exif_dates()
+	{
+		assert_eq!(parse_exif_date_millis("1970:01:01 00:00:00\0", None),        Some(0));
+		assert_eq!(parse_exif_date_millis("2024:01:31 18:05:00",   Some("5")),   Some(1706724300500));
+		assert_eq!(parse_exif_date_millis("2024:01:31 18:05:00",   Some("0421")), Some(1706724300042));
+		assert_eq!(parse_exif_date_millis("    :  :     :  :  ",   None),        None);
+	}
+}
@@ -0,0 +1,255 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Access to the opcode lists of DNG files (`OpcodeList1` to `OpcodeList3`),
+//! which describe processing steps a raw converter has to apply, most
+//! notably lens corrections like distortion (`WarpRectilinear`) and
+//! vignetting (`FixVignetteRadial`, `GainMap`) correction.
+//!
+//! The opcode lists are stored as opaque `UNDEF` tags, so they are written
+//! back unchanged when other metadata gets edited. This module splits them
+//! into their opcodes, whose parameters are kept as raw bytes. Opcode lists
+//! are always big endian, regardless of the byte order of the file.
+//!
+//! Note that DNG files store the opcode lists in the IFD of the raw image,
+//! which is usually a SubIFD and not IFD0.
+
+use crate::endian::*;
+use crate::general_file_io::io_error;
+
+/// The opcodes defined by the DNG specification (version 1.6)
+const OPCODE_NAMES: [(u32, &str); 14] = [
+	( 1, "WarpRectilinear"),
+	( 2, "WarpFisheye"),
+	( 3, "FixVignetteRadial"),
+	( 4, "FixBadPixelsConstant"),
+	( 5, "FixBadPixelsList"),
+	( 6, "TrimBounds"),
+	( 7, "MapTable"),
+	( 8, "MapPolynomial"),
+	( 9, "GainMap"),
+	(10, "DeltaPerRow"),
+	(11, "DeltaPerColumn"),
+	(12, "ScalePerRow"),
+	(13, "ScalePerColumn"),
+	(14, "WarpRectilinear2"),
+];
+
+/// Flag that allows skipping the opcode if the reader doesn't support it
+const FLAG_OPTIONAL:     u32 = 0x00000001;
+
+/// Flag that allows skipping the opcode when creating a preview
+const FLAG_PREVIEW_SKIP: u32 = 0x00000002;
+
+/// A single opcode of an opcode list
+#[derive(Debug, Clone, PartialEq)]
+pub struct
+Opcode
+{
+	pub id:          u32,
+	pub dng_version: [u8; 4],                                                   // Minimum DNG version required for the opcode
+	pub flags:       u32,
+	pub parameters:  Vec<u8>,
+}
+
+impl
+Opcode
+{
+	/// Gets the name of the opcode as given in the DNG specification, e.g.
+	/// "WarpRectilinear". Returns `None` for unknown opcodes.
+	pub fn
+	name
+	(
+		&self
+	)
+	-> Option<&'static str>
+	{
+		OPCODE_NAMES.iter().find(|(id, _)| *id == self.id).map(|(_, name)| *name)
+	}
+
+	/// Checks if readers that don't support the opcode may skip it
+	pub fn
+	is_optional
+	(
+		&self
+	)
+	-> bool
+	{
+		self.flags & FLAG_OPTIONAL != 0
+	}
+
+	/// Checks if the opcode may be skipped when rendering a preview
+	pub fn
+	is_preview_skippable
+	(
+		&self
+	)
+	-> bool
+	{
+		self.flags & FLAG_PREVIEW_SKIP != 0
+	}
+
+	/// Checks if the opcode corrects lens distortion or vignetting
+	pub fn
+	is_lens_correction
+	(
+		&self
+	)
+	-> bool
+	{
+		matches!(self.name(), Some("WarpRectilinear" | "WarpFisheye" | "FixVignetteRadial" | "GainMap" | "WarpRectilinear2"))
+	}
+}
+
+/// The decoded content of one of the `OpcodeList` tags
+#[derive(Debug, Clone, PartialEq)]
+pub struct
+OpcodeList
+{
+	opcodes: Vec<Opcode>,
+}
+
+impl
+OpcodeList
+{
+	pub fn
+	new
+	(
+		opcodes: Vec<Opcode>
+	)
+	-> OpcodeList
+	{
+		OpcodeList { opcodes }
+	}
+
+	/// Decodes the raw data of an `OpcodeList` tag
+	/// - If the data is truncated, an error gets returned.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::dng_opcode::OpcodeList;
+	/// use little_exif::exif_tag::ExifTag;
+	/// use little_exif::metadata::Metadata;
+	///
+	/// let metadata = Metadata::new_from_path(std::path::Path::new("image.jpg")).unwrap();
+	/// if let Some(ExifTag::OpcodeList3(raw_data)) = metadata.get_tag(&ExifTag::OpcodeList3(Vec::new()))
+	/// {
+	///     for opcode in OpcodeList::decode(raw_data).unwrap().lens_corrections()
+	///     {
+	///         println!("{:?}", opcode.name());
+	///     }
+	/// }
+	/// ```
+	pub fn
+	decode
+	(
+		raw_data: &[u8]
+	)
+	-> Result<OpcodeList, std::io::Error>
+	{
+		let read_u32 = |position: usize| -> Result<u32, std::io::Error> {
+			match Endian::Big.read::<u32>(raw_data, position)
+			{
+				Some(value) => Ok(value),
+				None        => io_error!(UnexpectedEof, "Opcode list is truncated!"),
+			}
+		};
+
+		let opcode_count = read_u32(0)?;
+		let mut opcodes = Vec::new();
+		let mut position = 4;
+
+		for _ in 0..opcode_count
+		{
+			let id          = read_u32(position)?;
+			let dng_version = read_u32(position + 4)?.to_be_bytes();
+			let flags       = read_u32(position + 8)?;
+			let length      = read_u32(position + 12)? as usize;
+			position += 16;
+
+			let parameters = match raw_data.get(position..position.saturating_add(length))
+			{
+				Some(parameters) => parameters.to_vec(),
+				None             => return io_error!(UnexpectedEof, "Opcode list is truncated!"),
+			};
+			position += length;
+
+			opcodes.push(Opcode { id, dng_version, flags, parameters });
+		}
+
+		return Ok(OpcodeList { opcodes });
+	}
+
+	/// Encodes the opcode list as raw data for an `OpcodeList` tag
+	pub fn
+	encode
+	(
+		&self
+	)
+	-> Vec<u8>
+	{
+		let mut raw_data = Vec::new();
+		Endian::Big.write(&mut raw_data, self.opcodes.len() as u32);
+		for opcode in &self.opcodes
+		{
+			Endian::Big.write(&mut raw_data, opcode.id);
+			raw_data.extend(opcode.dng_version);
+			Endian::Big.write(&mut raw_data, opcode.flags);
+			Endian::Big.write(&mut raw_data, opcode.parameters.len() as u32);
+			raw_data.extend(&opcode.parameters);
+		}
+		return raw_data;
+	}
+
+	pub fn
+	opcodes
+	(
+		&self
+	)
+	-> &Vec<Opcode>
+	{
+		&self.opcodes
+	}
+
+	/// Gets the opcodes that correct lens distortion or vignetting
+	pub fn
+	lens_corrections
+	(
+		&self
+	)
+	-> Vec<&Opcode>
+	{
+		self.opcodes.iter().filter(|opcode| opcode.is_lens_correction()).collect()
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::dng_opcode::Opcode;
+	use crate::dng_opcode::OpcodeList;
+
+	#[test]
+	fn
+	decode_and_encode_opcode_lists()
+	{
+		let opcode_list = OpcodeList::new(vec![
+			Opcode { id: 1, dng_version: [1, 3, 0, 0], flags: 0, parameters: vec![0x00, 0x00, 0x00, 0x01, 0x3f, 0xf0] },
+			Opcode { id: 4, dng_version: [1, 3, 0, 0], flags: 3, parameters: Vec::new() },
+			Opcode { id: 3, dng_version: [1, 3, 0, 0], flags: 1, parameters: vec![0x12; 56] },
+			Opcode { id: 99, dng_version: [1, 7, 0, 0], flags: 1, parameters: vec![0xff] },
+		]);
+
+		let raw_data = opcode_list.encode();
+		assert_eq!(&raw_data[0..8], &[0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01]);
+		assert_eq!(OpcodeList::decode(&raw_data).unwrap(), opcode_list);
+
+		let names = opcode_list.lens_corrections().iter().map(|opcode| opcode.name()).collect::<Vec<_>>();
+		assert_eq!(names, vec![Some("WarpRectilinear"), Some("FixVignetteRadial")]);
+		assert!(opcode_list.opcodes()[1].is_preview_skippable());
+		assert_eq!(opcode_list.opcodes()[3].name(), None);
+
+		assert!(OpcodeList::decode(&raw_data[..raw_data.len()-1]).is_err());
+		assert!(OpcodeList::decode(&[]).is_err());
+	}
+}
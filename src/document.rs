@@ -0,0 +1,343 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Metadata of captured document pages, so that document scanning and OCR
+//! pipelines can store the title of the document and the order of its pages
+//! in the captured images themselves. A `DocumentPage` gets written to these
+//! EXIF tags and properties of the PRISM namespace
+//! `http://prismstandard.org/namespaces/basic/2.0/` (prefix `prism`) in the
+//! XMP packet (see the `xmp` module):
+//!
+//! | Field           | EXIF tag       | XMP property            |
+//! |-----------------|----------------|-------------------------|
+//! | `document_name` | `DocumentName` | `prism:publicationName` |
+//! | `page_name`     | `PageName`     |                         |
+//! | `page_number`   | `PageNumber`   | `prism:pageCount`       |
+//! | `page_label`    |                | `prism:startingPage`    |
+//! | `volume`        |                | `prism:volume`          |
+//! | `issue`         |                | `prism:number`          |
+//!
+//! The page number starts at 0 as defined by TIFF, the page label is the
+//! number printed on the page (e.g. "iv"). XMP is only supported for JPEG
+//! and PNG files, TIFF files are not supported by little_exif so far.
+//!
+//! For scans of film, see the `scan` module.
+//!
+//! # Examples
+//! ```no_run
+//! use little_exif::document::DocumentPage;
+//!
+//! let pages = ["page_1.png", "page_2.png", "page_3.png"];
+//! for (index, path) in pages.iter().enumerate()
+//! {
+//!     DocumentPage::new()
+//!         .document_name("Annual Report 1987")
+//!         .page_number(index as u16, pages.len() as u16)
+//!         .page_label(&(index + 1).to_string())
+//!         .write_to_file(std::path::Path::new(path))
+//!         .unwrap();
+//! }
+//! ```
+
+use std::path::Path;
+
+use crate::exif_tag::ExifTag;
+use crate::filetype::FileExtension;
+use crate::general_file_io::io_error;
+use crate::general_file_io::write_vec_to_file;
+use crate::metadata::Metadata;
+use crate::xmp;
+use crate::xmp::Namespace;
+
+const PRISM_NAMESPACE: Namespace = ("prism", "http://prismstandard.org/namespaces/basic/2.0/");
+
+/// The metadata of a captured document page, see the module documentation.
+/// Fields that are `None` don't change the image when writing the page.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct
+DocumentPage
+{
+	pub document_name: Option<String>,
+	pub page_name:     Option<String>,
+	pub page_number:   Option<(u16, u16)>,                                      // Page starting at 0, total number of pages (0 if unknown)
+	pub page_label:    Option<String>,                                          // E.g. "iv"
+	pub volume:        Option<String>,
+	pub issue:         Option<String>,
+}
+
+impl
+DocumentPage
+{
+	/// Constructs a page without any fields
+	pub fn
+	new()
+	-> DocumentPage
+	{
+		DocumentPage::default()
+	}
+
+	/// Sets the name (title) of the document
+	pub fn
+	document_name
+	(
+		mut self,
+		document_name: &str
+	)
+	-> DocumentPage
+	{
+		self.document_name = Some(document_name.to_string());
+		self
+	}
+
+	/// Sets the name of the page, e.g. "Table of contents"
+	pub fn
+	page_name
+	(
+		mut self,
+		page_name: &str
+	)
+	-> DocumentPage
+	{
+		self.page_name = Some(page_name.to_string());
+		self
+	}
+
+	/// Sets the position of the page within the document, starting at 0,
+	/// and the total number of pages, or 0 if unknown
+	pub fn
+	page_number
+	(
+		mut self,
+		page:  u16,
+		total: u16
+	)
+	-> DocumentPage
+	{
+		self.page_number = Some((page, total));
+		self
+	}
+
+	/// Sets the number printed on the page
+	pub fn
+	page_label
+	(
+		mut self,
+		page_label: &str
+	)
+	-> DocumentPage
+	{
+		self.page_label = Some(page_label.to_string());
+		self
+	}
+
+	/// Sets the volume of the document, e.g. of a journal
+	pub fn
+	volume
+	(
+		mut self,
+		volume: &str
+	)
+	-> DocumentPage
+	{
+		self.volume = Some(volume.to_string());
+		self
+	}
+
+	/// Sets the issue of the document, e.g. of a journal
+	pub fn
+	issue
+	(
+		mut self,
+		issue: &str
+	)
+	-> DocumentPage
+	{
+		self.issue = Some(issue.to_string());
+		self
+	}
+
+	/// Checks that the page number doesn't exceed the total number of pages
+	fn
+	check
+	(
+		&self
+	)
+	-> Result<(), std::io::Error>
+	{
+		if let Some((page, total)) = self.page_number
+		{
+			if total != 0 && page >= total
+			{
+				return io_error!(InvalidInput, format!("Page {} exceeds the total of {} pages!", page, total));
+			}
+		}
+		return Ok(());
+	}
+
+	/// Converts the fields that are set into PRISM properties
+	fn
+	to_properties
+	(
+		&self
+	)
+	-> Vec<(&'static str, Option<String>)>
+	{
+		[
+			("publicationName", self.document_name.clone()),
+			("pageCount",       self.page_number.filter(|(_, total)| *total != 0).map(|(_, total)| total.to_string())),
+			("startingPage",    self.page_label.clone()),
+			("volume",          self.volume.clone()),
+			("number",          self.issue.clone()),
+		]
+		.into_iter()
+		.filter(|(_, value)| value.is_some())
+		.collect()
+	}
+
+	/// Sets the EXIF tags of the fields that are set. The fields only stored
+	/// in XMP are not included.
+	/// - If the page number exceeds the total number of pages, an error of
+	///   kind `InvalidInput` gets returned and the metadata is not changed.
+	pub fn
+	apply
+	(
+		&self,
+		metadata: &mut Metadata
+	)
+	-> Result<(), std::io::Error>
+	{
+		self.check()?;
+
+		if let Some(document_name) = &self.document_name
+		{
+			metadata.set_tag(ExifTag::DocumentName(document_name.clone()));
+		}
+		if let Some(page_name) = &self.page_name
+		{
+			metadata.set_tag(ExifTag::PageName(page_name.clone()));
+		}
+		if let Some((page, total)) = self.page_number
+		{
+			metadata.set_tag(ExifTag::PageNumber(vec![page, total]));
+		}
+		return Ok(());
+	}
+
+	/// Reads the page from the EXIF data and the XMP packet of the JPEG or
+	/// PNG image. Fields that are stored in both are taken from EXIF.
+	pub fn
+	read_from_vec
+	(
+		file_buffer: &Vec<u8>
+	)
+	-> Result<DocumentPage, std::io::Error>
+	{
+		let file_type = match FileExtension::from_signature(file_buffer)
+		{
+			Some(file_type) => file_type,
+			None            => return io_error!(Unsupported, "Unsupported file type!"),
+		};
+		let metadata = Metadata::new_from_vec(file_buffer, file_type)?;
+		let packet = xmp::read_xmp_from_vec(file_buffer)?.unwrap_or_default();
+
+		let get_string = |tag: ExifTag| metadata.get_string_value(&tag)
+			.map(|value| value.trim_end_matches('\0').to_string());
+		let get_property = |name: &str| xmp::get_property(&packet, PRISM_NAMESPACE, name);
+
+		let page_number = match metadata.get_tag(&ExifTag::PageNumber(Vec::new()))
+		{
+			Some(ExifTag::PageNumber(values)) if values.len() == 2 => Some((values[0], values[1])),
+			_                                                       => None,
+		};
+
+		return Ok(DocumentPage
+		{
+			document_name: get_string(ExifTag::DocumentName(String::new())).or_else(|| get_property("publicationName")),
+			page_name:     get_string(ExifTag::PageName(String::new())),
+			page_number,
+			page_label:    get_property("startingPage"),
+			volume:        get_property("volume"),
+			issue:         get_property("number"),
+		});
+	}
+
+	/// Writes the page to the EXIF data and the XMP packet of the JPEG or
+	/// PNG image, see the module documentation
+	/// - If the page number exceeds the total number of pages, an error of
+	///   kind `InvalidInput` gets returned.
+	/// - If the image is neither a JPEG nor a PNG file, an error of kind
+	///   `Unsupported` gets returned.
+	/// - In case of an error, the image is not changed.
+	pub fn
+	write_to_vec
+	(
+		&self,
+		file_buffer: &mut Vec<u8>
+	)
+	-> Result<(), std::io::Error>
+	{
+		self.check()?;
+
+		let file_type = match FileExtension::from_signature(file_buffer)
+		{
+			Some(file_type @ (FileExtension::JPEG | FileExtension::PNG { .. })) => file_type,
+			_ => return io_error!(Unsupported, "Document pages are only supported for JPEG and PNG files!"),
+		};
+
+		let mut metadata = Metadata::new_from_vec(file_buffer, file_type)?;
+		self.apply(&mut metadata)?;
+
+		let mut new_buffer = file_buffer.clone();
+		let packet = xmp::read_xmp_from_vec(&new_buffer)?;
+		let packet = xmp::set_properties(packet.as_deref(), PRISM_NAMESPACE, &self.to_properties())?;
+		xmp::write_xmp_to_vec(&mut new_buffer, &packet)?;
+		metadata.write_to_vec(&mut new_buffer, file_type)?;
+
+		*file_buffer = new_buffer;
+		return Ok(());
+	}
+
+	/// Writes the page to the image at the given path, see `write_to_vec`
+	pub fn
+	write_to_file
+	(
+		&self,
+		path: &Path
+	)
+	-> Result<(), std::io::Error>
+	{
+		let mut file_buffer = std::fs::read(path)?;
+		self.write_to_vec(&mut file_buffer)?;
+		return write_vec_to_file(path, &file_buffer);
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::document::DocumentPage;
+	use crate::exif_tag::ExifTag;
+	use crate::metadata::Metadata;
+
+	#[test]
+	fn
+	apply_page()
+	-> Result<(), std::io::Error>
+	{
+		let mut metadata = Metadata::new();
+		metadata.set_tag(ExifTag::PageName("Cover".to_string()));
+
+		DocumentPage::new().document_name("Report").page_number(2, 0).apply(&mut metadata)?;
+		assert_eq!(metadata.get_tag(&ExifTag::DocumentName(String::new())), Some(&ExifTag::DocumentName("Report".to_string())));
+		assert_eq!(metadata.get_tag(&ExifTag::PageNumber(Vec::new())),      Some(&ExifTag::PageNumber(vec![2, 0])));
+		assert_eq!(metadata.get_tag(&ExifTag::PageName(String::new())),     Some(&ExifTag::PageName("Cover".to_string())));
+
+		assert!(DocumentPage::new().page_number(2, 0).to_properties().is_empty());
+		let properties = DocumentPage::new().page_number(2, 10).page_label("iii").to_properties();
+		assert_eq!(properties, vec![("pageCount", Some("10".to_string())), ("startingPage", Some("iii".to_string()))]);
+
+		assert!(DocumentPage::new().page_number(10, 10).apply(&mut metadata).is_err());
+		assert_eq!(metadata.get_tag(&ExifTag::PageNumber(Vec::new())), Some(&ExifTag::PageNumber(vec![2, 0])));
+		Ok(())
+	}
+}
@@ -1,9 +1,7 @@
 // Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
 // See https://github.com/TechnikTobi/little_exif#license for licensing details
 
-use paste::paste;
-
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum
 Endian
 {
@@ -28,6 +26,108 @@ impl Endian
 	}
 }
 
+/// Numbers with a fixed size that can be converted from and to bytes in
+/// either byte order, see `ByteOrder`
+pub(crate) trait
+EndianNumber
+: Sized + Copy
+{
+	const SIZE: usize;
+
+	/// Converts exactly `SIZE` bytes into the number
+	fn
+	from_bytes
+	(
+		bytes:  &[u8],
+		endian: &Endian
+	)
+	-> Self;
+
+	/// Appends the bytes of the number to the buffer
+	fn
+	extend_bytes
+	(
+		self,
+		buffer: &mut Vec<u8>,
+		endian: &Endian
+	);
+}
+
+/// Bounds-checked reading and writing of numbers in a byte order, without
+/// intermediate allocations
+pub(crate) trait
+ByteOrder
+{
+	/// Reads the number starting at the given position of the data.
+	/// Returns `None` if the data is too short.
+	fn
+	read
+	<N: EndianNumber>
+	(
+		&self,
+		data:     &[u8],
+		position: usize
+	)
+	-> Option<N>;
+
+	/// Appends the number to the buffer
+	fn
+	write
+	<N: EndianNumber>
+	(
+		&self,
+		buffer: &mut Vec<u8>,
+		value:  N
+	);
+
+	/// Gets the bytes of the number, e.g. for replacing existing ones
+	fn
+	bytes
+	<N: EndianNumber>
+	(
+		&self,
+		value: N
+	)
+	-> Vec<u8>
+	{
+		let mut buffer = Vec::with_capacity(N::SIZE);
+		self.write(&mut buffer, value);
+		return buffer;
+	}
+}
+
+impl
+ByteOrder
+for
+Endian
+{
+	fn
+	read
+	<N: EndianNumber>
+	(
+		&self,
+		data:     &[u8],
+		position: usize
+	)
+	-> Option<N>
+	{
+		let bytes = data.get(position..position.checked_add(N::SIZE)?)?;
+		return Some(N::from_bytes(bytes, self));
+	}
+
+	fn
+	write
+	<N: EndianNumber>
+	(
+		&self,
+		buffer: &mut Vec<u8>,
+		value:  N
+	)
+	{
+		value.extend_bytes(buffer, self);
+	}
+}
+
 pub(crate) trait
 U8conversion<T>
 {
@@ -56,6 +156,42 @@ macro_rules! build_u8conversion
 	)
 	=>
 	{
+		impl EndianNumber for $type
+		{
+			const SIZE: usize = $number_of_bytes;
+
+			fn
+			from_bytes
+			(
+				bytes:  &[u8],
+				endian: &Endian
+			)
+			-> $type
+			{
+				let bytes: [u8; $number_of_bytes] = bytes.try_into().unwrap();
+				match *endian
+				{
+					Endian::Little => <$type>::from_le_bytes(bytes),
+					Endian::Big    => <$type>::from_be_bytes(bytes),
+				}
+			}
+
+			fn
+			extend_bytes
+			(
+				self,
+				buffer: &mut Vec<u8>,
+				endian: &Endian
+			)
+			{
+				match *endian
+				{
+					Endian::Little => buffer.extend(self.to_le_bytes()),
+					Endian::Big    => buffer.extend(self.to_be_bytes()),
+				}
+			}
+		}
+
 		impl U8conversion<$type> for $type
 		{
 			fn
@@ -66,11 +202,7 @@ macro_rules! build_u8conversion
 			)
 			-> Vec<u8>
 			{
-				match *endian
-				{
-					Endian::Little => self.to_le_bytes().to_vec(),
-					Endian::Big => self.to_be_bytes().to_vec(),
-				}
+				endian.bytes(*self)
 			}
 
 			fn
@@ -82,11 +214,7 @@ macro_rules! build_u8conversion
 			-> $type
 			{
 				assert!(u8_vec.len() == $number_of_bytes);
-				match *endian
-				{
-					Endian::Little => <paste!{[<$type>]}>::from_le_bytes(u8_vec[0..$number_of_bytes].try_into().unwrap()),
-					Endian::Big    => <paste!{[<$type>]}>::from_be_bytes(u8_vec[0..$number_of_bytes].try_into().unwrap()),
-				}
+				<$type as EndianNumber>::from_bytes(u8_vec, endian)
 			}
 		}
 
@@ -100,11 +228,10 @@ macro_rules! build_u8conversion
 			)
 			-> Vec<u8>
 			{
-				let mut u8_vec = Vec::new();
+				let mut u8_vec = Vec::with_capacity(self.len() * $number_of_bytes);
 				for value in self
 				{
-					// u8_vec.extend(value.to_u8_vec(endian).iter());
-					u8_vec.extend(<$type as U8conversion<$type>>::to_u8_vec(value, endian).iter());
+					endian.write(&mut u8_vec, *value);
 				}
 				return u8_vec;
 			}
@@ -117,20 +244,12 @@ macro_rules! build_u8conversion
 			)
 			-> Vec<$type>
 			{
-				assert!(u8_vec.len() % $number_of_bytes == 0);
+				assert!(u8_vec.len().is_multiple_of($number_of_bytes));
 
-				let mut result: Vec<$type> = Vec::new();
-
-				for i in 0..(u8_vec.len() / $number_of_bytes)
-				{
-					result.push(
-						<$type>::from_u8_vec(
-							&u8_vec[(0 + i*$number_of_bytes)..((i+1)*$number_of_bytes)].to_vec(), 
-							endian
-					) as $type);
-				}
-
-				return result;
+				return u8_vec
+					.chunks_exact($number_of_bytes)
+					.map(|bytes| <$type as EndianNumber>::from_bytes(bytes, endian))
+					.collect();
 			}
 		}
 	}
@@ -158,7 +277,7 @@ impl U8conversion<String> for String
 	-> Vec<u8>
 	{
 		let mut u8_vec = self.as_bytes().to_vec();
-		u8_vec.push(0x00 as u8);
+		u8_vec.push(0x00);
 		return u8_vec;
 	}
 
@@ -170,8 +289,6 @@ impl U8conversion<String> for String
 	)
 	-> String
 	{
-		assert!(u8_vec.len() % 1 == 0);
-
 		let mut result = String::new();
 
 		for byte in u8_vec
@@ -186,21 +303,26 @@ impl U8conversion<String> for String
 	}
 }
 
-macro_rules! to_u8_vec_macro {
-	($type:ty, $value:expr, $endian:expr)
-	=>
-	{
-		<$type as U8conversion<$type>>::to_u8_vec($value, $endian)
-	};
-}
+#[cfg(test)]
+mod tests
+{
+	use crate::endian::*;
 
-macro_rules! from_u8_vec_macro {
-	($type:ty, $value:expr, $endian:expr)
-	=>
+	#[test]
+	fn
+	read_and_write_numbers()
 	{
-		<$type as U8conversion<$type>>::from_u8_vec($value, $endian)
+		let data = [0x12, 0x34, 0x56, 0x78, 0x9a];
+
+		assert_eq!(Endian::Big.read::<u16>(&data, 0),    Some(0x1234));
+		assert_eq!(Endian::Little.read::<u32>(&data, 1), Some(0x9a785634));
+		assert_eq!(Endian::Big.read::<u32>(&data, 2),    None);
+		assert_eq!(Endian::Big.read::<u8>(&data, usize::MAX), None);
+
+		let mut buffer = Vec::new();
+		Endian::Big.write(&mut buffer, 0x1234u16);
+		Endian::Little.write(&mut buffer, -2i32);
+		assert_eq!(buffer, vec![0x12, 0x34, 0xfe, 0xff, 0xff, 0xff]);
+		assert_eq!(Endian::Little.bytes(1.0f32), vec![0x00, 0x00, 0x80, 0x3f]);
 	}
 }
-
-pub(crate) use to_u8_vec_macro;
-pub(crate) use from_u8_vec_macro;
\ No newline at end of file
@@ -0,0 +1,233 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Typed access to the tags describing the environment an image was taken
+//! in, which were added by EXIF 2.31 for scientific and underwater
+//! photography. See e.g. `Metadata::ambient_temperature` and
+//! `Metadata::set_water_depth`.
+//!
+//! The tags store fixed units, the accessors convert from and to the unit
+//! passed by the caller:
+//!
+//! | Tag                    | Stored unit          | Precision  | Accessor unit      |
+//! |------------------------|----------------------|------------|--------------------|
+//! | `AmbientTemperature`   | °C                   | 0.1        | `TemperatureUnit`  |
+//! | `Humidity`             | % relative humidity  | 0.1        | -                  |
+//! | `Pressure`             | hPa (= mbar)         | 0.1        | `PressureUnit`     |
+//! | `WaterDepth`           | m, negative above    | 0.001      | `LengthUnit`       |
+//! | `Acceleration`         | mGal (10^-5 m/s²)    | 1          | `AccelerationUnit` |
+//! | `CameraElevationAngle` | ° above the horizon  | 0.01       | -                  |
+//!
+//! Some devices store `0xFFFFFFFF/0xFFFFFFFF` (or a denominator of zero) for
+//! values they couldn't measure, which the accessors report as `None`.
+
+/// The unit of a temperature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum
+TemperatureUnit
+{
+	Celsius,
+	Fahrenheit,
+	Kelvin,
+}
+
+impl
+TemperatureUnit
+{
+	/// Converts a temperature in this unit into degrees Celsius
+	pub(crate) fn
+	to_celsius
+	(
+		self,
+		value: f64
+	)
+	-> f64
+	{
+		match self
+		{
+			TemperatureUnit::Celsius    => value,
+			TemperatureUnit::Fahrenheit => (value - 32.0) * 5.0 / 9.0,
+			TemperatureUnit::Kelvin     => value - 273.15,
+		}
+	}
+
+	/// Converts a temperature in degrees Celsius into this unit
+	pub(crate) fn
+	celsius_to_unit
+	(
+		self,
+		celsius: f64
+	)
+	-> f64
+	{
+		match self
+		{
+			TemperatureUnit::Celsius    => celsius,
+			TemperatureUnit::Fahrenheit => celsius * 9.0 / 5.0 + 32.0,
+			TemperatureUnit::Kelvin     => celsius + 273.15,
+		}
+	}
+}
+
+/// The unit of a pressure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum
+PressureUnit
+{
+	Hectopascal,                                                                // Same as millibar
+	Kilopascal,
+	InchesOfMercury,
+}
+
+impl
+PressureUnit
+{
+	/// Gets the number of hectopascal per unit
+	pub(crate) fn
+	hectopascal
+	(
+		&self
+	)
+	-> f64
+	{
+		match self
+		{
+			PressureUnit::Hectopascal     => 1.0,
+			PressureUnit::Kilopascal      => 10.0,
+			PressureUnit::InchesOfMercury => 33.8638866667,
+		}
+	}
+}
+
+/// The unit of a length, e.g. the water depth
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum
+LengthUnit
+{
+	Meters,
+	Feet,
+}
+
+impl
+LengthUnit
+{
+	/// Gets the number of meters per unit
+	pub(crate) fn
+	meters
+	(
+		&self
+	)
+	-> f64
+	{
+		match self
+		{
+			LengthUnit::Meters => 1.0,
+			LengthUnit::Feet   => 0.3048,
+		}
+	}
+}
+
+/// The unit of an acceleration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum
+AccelerationUnit
+{
+	Milligal,                                                                   // 10^-5 m/s²
+	MetersPerSecondSquared,
+	StandardGravity,                                                            // 9.80665 m/s²
+}
+
+impl
+AccelerationUnit
+{
+	/// Gets the number of milligal per unit
+	pub(crate) fn
+	milligal
+	(
+		&self
+	)
+	-> f64
+	{
+		match self
+		{
+			AccelerationUnit::Milligal               => 1.0,
+			AccelerationUnit::MetersPerSecondSquared => 100_000.0,
+			AccelerationUnit::StandardGravity        => 980_665.0,
+		}
+	}
+}
+
+/// Converts a value into a signed rational with the given denominator.
+/// Returns `None` if it is not a finite number or out of range.
+pub(crate) fn
+f64_to_signed_rational
+(
+	value:       f64,
+	denominator: i32
+)
+-> Option<Vec<i32>>
+{
+	let numerator = (value * denominator as f64).round();
+	if !numerator.is_finite() || numerator.abs() > i32::MAX as f64
+	{
+		return None;
+	}
+	return Some(vec![numerator as i32, denominator]);
+}
+
+/// Converts a signed rational into a decimal number. Returns `None` if the
+/// value is malformed or unknown.
+pub(crate) fn
+signed_rational_to_f64
+(
+	rational: &[i32]
+)
+-> Option<f64>
+{
+	match rational
+	{
+		[-1, -1]                                      => None,
+		[numerator, denominator] if *denominator != 0 => Some(*numerator as f64 / *denominator as f64),
+		_                                             => None,
+	}
+}
+
+/// Converts an unsigned rational into a decimal number. Returns `None` if
+/// the value is malformed or unknown.
+pub(crate) fn
+unsigned_rational_to_f64
+(
+	rational: &[u32]
+)
+-> Option<f64>
+{
+	match rational
+	{
+		[numerator, denominator] if *denominator != 0 && *numerator != u32::MAX => Some(*numerator as f64 / *denominator as f64),
+		_                                                                       => None,
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::environment::*;
+
+	#[test]
+	fn
+	convert_units()
+	{
+		assert_eq!(TemperatureUnit::Fahrenheit.to_celsius(212.0), 100.0);
+		assert_eq!(TemperatureUnit::Fahrenheit.celsius_to_unit(-40.0), -40.0);
+		assert!((TemperatureUnit::Kelvin.celsius_to_unit(TemperatureUnit::Kelvin.to_celsius(300.0)) - 300.0).abs() < 1e-9);
+
+		assert_eq!(f64_to_signed_rational(-12.34, 10), Some(vec![-123, 10]));
+		assert_eq!(f64_to_signed_rational(f64::NAN, 10), None);
+		assert_eq!(f64_to_signed_rational(1e9, 10), None);
+		assert_eq!(signed_rational_to_f64(&[-123, 10]), Some(-12.3));
+		assert_eq!(signed_rational_to_f64(&[-1, 10]), Some(-0.1));
+		assert_eq!(signed_rational_to_f64(&[-1, -1]), None);
+		assert_eq!(unsigned_rational_to_f64(&[u32::MAX, 1]), None);
+		assert_eq!(unsigned_rational_to_f64(&[5, 0]), None);
+	}
+}
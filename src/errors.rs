@@ -0,0 +1,818 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Errors that callers may want to handle specifically. All functions of the
+//! crate return `std::io::Error`s, these types are stored inside of them and
+//! can be accessed using their `from_io_error` functions.
+
+use std::path::Path;
+use std::path::PathBuf;
+
+/// The file that should be written to is read-only, e.g. because it's on a
+/// read-only file system (like a write-protected SD card) or because of its
+/// permissions. The file has not been modified.
+///
+/// # Examples
+/// ```no_run
+/// use little_exif::errors::ReadOnlyTarget;
+/// use little_exif::metadata::Metadata;
+///
+/// let path = std::path::Path::new("image.jpg");
+/// if let Err(error) = Metadata::new().write_to_file(path)
+/// {
+///     if let Some(read_only_target) = ReadOnlyTarget::from_io_error(&error)
+///     {
+///         println!("{} is read-only", read_only_target.path().display());
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct
+ReadOnlyTarget
+{
+	path: PathBuf,
+}
+
+impl
+ReadOnlyTarget
+{
+	pub(crate) fn
+	new
+	(
+		path: &Path
+	)
+	-> ReadOnlyTarget
+	{
+		ReadOnlyTarget { path: path.to_path_buf() }
+	}
+
+	/// The path of the read-only file
+	pub fn
+	path
+	(
+		&self
+	)
+	-> &Path
+	{
+		&self.path
+	}
+
+	/// Gets the `ReadOnlyTarget` stored in the given error, if there is one
+	pub fn
+	from_io_error
+	(
+		error: &std::io::Error
+	)
+	-> Option<&ReadOnlyTarget>
+	{
+		error.get_ref()?.downcast_ref::<ReadOnlyTarget>()
+	}
+}
+
+impl
+std::fmt::Display
+for
+ReadOnlyTarget
+{
+	fn
+	fmt
+	(
+		&self,
+		formatter: &mut std::fmt::Formatter<'_>
+	)
+	-> std::fmt::Result
+	{
+		write!(formatter, "Can't write to {} - File is read-only!", self.path.display())
+	}
+}
+
+impl
+std::error::Error
+for
+ReadOnlyTarget
+{}
+
+/// There is not enough free space for writing the file, detected by
+/// `WriteOptions::safe_write` before the file gets replaced. The file has
+/// not been modified.
+///
+/// # Examples
+/// ```no_run
+/// use little_exif::errors::InsufficientSpace;
+/// use little_exif::metadata::Metadata;
+/// use little_exif::write_options::WriteOptions;
+///
+/// let path = std::path::Path::new("image.jpg");
+/// if let Err(error) = Metadata::new().write_to_file_with_options(path, &WriteOptions::new().safe_write(true))
+/// {
+///     if let Some(insufficient_space) = InsufficientSpace::from_io_error(&error)
+///     {
+///         println!("{} bytes required", insufficient_space.required_bytes());
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct
+InsufficientSpace
+{
+	path:     PathBuf,
+	required: u64,
+}
+
+impl
+InsufficientSpace
+{
+	pub(crate) fn
+	new
+	(
+		path:     &Path,
+		required: u64
+	)
+	-> InsufficientSpace
+	{
+		InsufficientSpace { path: path.to_path_buf(), required }
+	}
+
+	/// The path of the file that should have been written
+	pub fn
+	path
+	(
+		&self
+	)
+	-> &Path
+	{
+		&self.path
+	}
+
+	/// The size of the file that should have been written, in bytes
+	pub fn
+	required_bytes
+	(
+		&self
+	)
+	-> u64
+	{
+		self.required
+	}
+
+	/// Gets the `InsufficientSpace` stored in the given error, if there is
+	/// one
+	pub fn
+	from_io_error
+	(
+		error: &std::io::Error
+	)
+	-> Option<&InsufficientSpace>
+	{
+		error.get_ref()?.downcast_ref::<InsufficientSpace>()
+	}
+}
+
+impl
+std::fmt::Display
+for
+InsufficientSpace
+{
+	fn
+	fmt
+	(
+		&self,
+		formatter: &mut std::fmt::Formatter<'_>
+	)
+	-> std::fmt::Result
+	{
+		write!(formatter, "Can't write to {} - Not enough space for {} bytes, file has not been modified!", self.path.display(), self.required)
+	}
+}
+
+impl
+std::error::Error
+for
+InsufficientSpace
+{}
+
+/// The file is in use by another process that doesn't allow writing to it,
+/// e.g. an image viewer on Windows (`ERROR_SHARING_VIOLATION` or
+/// `ERROR_LOCK_VIOLATION`). Writing has been retried with increasing delays
+/// before giving up, the file has not been modified. As the file usually
+/// can still be read, `Metadata::write_to_file_or_sidecar` treats it like a
+/// read-only file.
+///
+/// # Examples
+/// ```no_run
+/// use little_exif::errors::SharingViolation;
+/// use little_exif::metadata::Metadata;
+///
+/// let path = std::path::Path::new("image.jpg");
+/// if let Err(error) = Metadata::new().write_to_file(path)
+/// {
+///     if let Some(sharing_violation) = SharingViolation::from_io_error(&error)
+///     {
+///         println!("Close {} in other programs and try again", sharing_violation.path().display());
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct
+SharingViolation
+{
+	path:     PathBuf,
+	attempts: u32,
+}
+
+impl
+SharingViolation
+{
+	pub(crate) fn
+	new
+	(
+		path:     &Path,
+		attempts: u32
+	)
+	-> SharingViolation
+	{
+		SharingViolation { path: path.to_path_buf(), attempts }
+	}
+
+	/// The path of the file that is in use
+	pub fn
+	path
+	(
+		&self
+	)
+	-> &Path
+	{
+		&self.path
+	}
+
+	/// The number of times accessing the file has been tried
+	pub fn
+	attempts
+	(
+		&self
+	)
+	-> u32
+	{
+		self.attempts
+	}
+
+	/// Gets the `SharingViolation` stored in the given error, if there is
+	/// one
+	pub fn
+	from_io_error
+	(
+		error: &std::io::Error
+	)
+	-> Option<&SharingViolation>
+	{
+		error.get_ref()?.downcast_ref::<SharingViolation>()
+	}
+}
+
+impl
+std::fmt::Display
+for
+SharingViolation
+{
+	fn
+	fmt
+	(
+		&self,
+		formatter: &mut std::fmt::Formatter<'_>
+	)
+	-> std::fmt::Result
+	{
+		write!(formatter, "Can't write to {} - File is in use by another process ({} attempts), file has not been modified!", self.path.display(), self.attempts)
+	}
+}
+
+impl
+std::error::Error
+for
+SharingViolation
+{}
+
+/// The file is of a type little_exif recognizes, but that can't hold embedded
+/// EXIF data, e.g. a BMP image. Nothing has been read or written. As
+/// pipelines processing mixed folders usually want to keep the metadata
+/// anyway, `Metadata::write_to_file_or_sidecar` writes it to a sidecar file
+/// instead.
+///
+/// # Examples
+/// ```no_run
+/// use little_exif::errors::NoEmbeddedMetadata;
+/// use little_exif::metadata::Metadata;
+///
+/// let path = std::path::Path::new("scan.bmp");
+/// if let Err(error) = Metadata::new_from_path(path)
+/// {
+///     if let Some(no_embedded_metadata) = NoEmbeddedMetadata::from_io_error(&error)
+///     {
+///         println!("Skipping {} file {}", no_embedded_metadata.format(), no_embedded_metadata.path().display());
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct
+NoEmbeddedMetadata
+{
+	path:   PathBuf,
+	format: &'static str,
+}
+
+impl
+NoEmbeddedMetadata
+{
+	pub(crate) fn
+	new
+	(
+		path:   &Path,
+		format: &'static str
+	)
+	-> NoEmbeddedMetadata
+	{
+		NoEmbeddedMetadata { path: path.to_path_buf(), format }
+	}
+
+	/// The path of the file
+	pub fn
+	path
+	(
+		&self
+	)
+	-> &Path
+	{
+		&self.path
+	}
+
+	/// The name of the file type, e.g. "BMP"
+	pub fn
+	format
+	(
+		&self
+	)
+	-> &'static str
+	{
+		self.format
+	}
+
+	/// Gets the `NoEmbeddedMetadata` stored in the given error, if there is
+	/// one
+	pub fn
+	from_io_error
+	(
+		error: &std::io::Error
+	)
+	-> Option<&NoEmbeddedMetadata>
+	{
+		error.get_ref()?.downcast_ref::<NoEmbeddedMetadata>()
+	}
+}
+
+impl
+std::fmt::Display
+for
+NoEmbeddedMetadata
+{
+	fn
+	fmt
+	(
+		&self,
+		formatter: &mut std::fmt::Formatter<'_>
+	)
+	-> std::fmt::Result
+	{
+		write!(formatter, "Can't access metadata of {} - {} files don't support embedded EXIF data!", self.path.display(), self.format)
+	}
+}
+
+impl
+std::error::Error
+for
+NoEmbeddedMetadata
+{}
+
+/// The file has no EXIF data at all, as opposed to EXIF data that can't be
+/// read. Lets `Metadata::has_exif` and `Metadata::has_gps` tell the two
+/// apart, as a file that can't be parsed must not pass as one without
+/// metadata.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct
+MissingExif
+{
+	message: String,
+}
+
+impl
+MissingExif
+{
+	/// Gets an error of kind `Other` with the given message, marked as
+	/// missing EXIF data
+	pub(crate) fn
+	error<T>
+	(
+		message: impl Into<String>
+	)
+	-> Result<T, std::io::Error>
+	{
+		Err(std::io::Error::other(MissingExif { message: message.into() }))
+	}
+
+	/// Checks if the error is one returned by `MissingExif::error`
+	pub(crate) fn
+	is
+	(
+		error: &std::io::Error
+	)
+	-> bool
+	{
+		error.get_ref().is_some_and(|inner| inner.is::<MissingExif>())
+	}
+}
+
+impl
+std::fmt::Display
+for
+MissingExif
+{
+	fn
+	fmt
+	(
+		&self,
+		formatter: &mut std::fmt::Formatter<'_>
+	)
+	-> std::fmt::Result
+	{
+		write!(formatter, "{}", self.message)
+	}
+}
+
+impl
+std::error::Error
+for
+MissingExif
+{}
+
+/// The encoded metadata exceeds the budget set by
+/// `WriteOptions::max_metadata_bytes`, either because trimming is disabled or
+/// because even the remaining tags don't fit. Nothing has been written.
+///
+/// # Examples
+/// ```no_run
+/// use little_exif::errors::MetadataTooLarge;
+/// use little_exif::metadata::Metadata;
+/// use little_exif::write_options::WriteOptions;
+///
+/// let metadata = Metadata::new_from_path(std::path::Path::new("image.jpg")).unwrap();
+/// if let Err(error) = metadata.encode_with_options(&WriteOptions::new().max_metadata_bytes(4096))
+/// {
+///     if let Some(too_large) = MetadataTooLarge::from_io_error(&error)
+///     {
+///         println!("{} bytes exceed the budget of {} bytes", too_large.size(), too_large.limit());
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct
+MetadataTooLarge
+{
+	size:  usize,
+	limit: usize,
+}
+
+impl
+MetadataTooLarge
+{
+	pub(crate) fn
+	new
+	(
+		size:  usize,
+		limit: usize
+	)
+	-> MetadataTooLarge
+	{
+		MetadataTooLarge { size, limit }
+	}
+
+	/// The size of the encoded metadata in bytes
+	pub fn
+	size
+	(
+		&self
+	)
+	-> usize
+	{
+		self.size
+	}
+
+	/// The budget in bytes
+	pub fn
+	limit
+	(
+		&self
+	)
+	-> usize
+	{
+		self.limit
+	}
+
+	/// Gets the `MetadataTooLarge` stored in the given error, if there is one
+	pub fn
+	from_io_error
+	(
+		error: &std::io::Error
+	)
+	-> Option<&MetadataTooLarge>
+	{
+		error.get_ref()?.downcast_ref::<MetadataTooLarge>()
+	}
+}
+
+impl
+std::fmt::Display
+for
+MetadataTooLarge
+{
+	fn
+	fmt
+	(
+		&self,
+		formatter: &mut std::fmt::Formatter<'_>
+	)
+	-> std::fmt::Result
+	{
+		write!(formatter, "Encoded metadata of {} bytes exceeds the budget of {} bytes!", self.size, self.limit)
+	}
+}
+
+impl
+std::error::Error
+for
+MetadataTooLarge
+{}
+
+/// Where in the data parsing failed: The offset within the file, the name of
+/// the chunk (PNG, WebP) or segment (JPEG), and for errors in the EXIF data
+/// the IFD and the number of its entry (starting at 0). Depending on where
+/// the error occurred, only some of these are known.
+/// The same type describes the warnings collected while reading metadata,
+/// see `Metadata::warnings`.
+///
+/// # Examples
+/// ```no_run
+/// use little_exif::errors::ParseErrorContext;
+/// use little_exif::filetype::FileExtension;
+/// use little_exif::metadata::Metadata;
+///
+/// let file_buffer = std::fs::read("image.png").unwrap();
+/// if let Err(error) = Metadata::clear_metadata(&mut file_buffer.clone(), FileExtension::PNG { as_zTXt_chunk: true })
+/// {
+///     if let Some(context) = ParseErrorContext::from_io_error(&error)
+///     {
+///         println!("{} at offset {:?} in chunk {:?}", context.message(), context.offset_in_file(), context.chunk_name());
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct
+ParseErrorContext
+{
+	message: String,
+	offset:  Option<u64>,
+	chunk:   Option<String>,
+	ifd:     Option<String>,
+	entry:   Option<usize>,
+}
+
+impl
+ParseErrorContext
+{
+	pub(crate) fn
+	new()
+	-> ParseErrorContext
+	{
+		ParseErrorContext::default()
+	}
+
+	pub(crate) fn
+	offset
+	(
+		mut self,
+		offset: u64
+	)
+	-> ParseErrorContext
+	{
+		self.offset = Some(offset);
+		self
+	}
+
+	pub(crate) fn
+	chunk
+	(
+		mut self,
+		chunk: &str
+	)
+	-> ParseErrorContext
+	{
+		self.chunk = Some(chunk.to_string());
+		self
+	}
+
+	pub(crate) fn
+	ifd
+	(
+		mut self,
+		ifd: &str
+	)
+	-> ParseErrorContext
+	{
+		self.ifd = Some(ifd.to_string());
+		self
+	}
+
+	pub(crate) fn
+	entry
+	(
+		mut self,
+		entry: usize
+	)
+	-> ParseErrorContext
+	{
+		self.entry = Some(entry);
+		self
+	}
+
+	/// Turns the context into a warning with the given message, for issues
+	/// that don't stop the parsing, see `Metadata::warnings`
+	pub(crate) fn
+	warning
+	(
+		mut self,
+		message: &str
+	)
+	-> ParseErrorContext
+	{
+		self.message = message.to_string();
+		self
+	}
+
+	/// Stores the context in the error, keeping its kind. If the error
+	/// already has a context, the more specific information of the existing
+	/// one is kept and only the missing one gets filled in.
+	pub(crate) fn
+	attach
+	(
+		self,
+		error: std::io::Error
+	)
+	-> std::io::Error
+	{
+		let merged = match ParseErrorContext::from_io_error(&error)
+		{
+			Some(existing) => ParseErrorContext
+			{
+				message: existing.message.clone(),
+				offset:  existing.offset.or(self.offset),
+				chunk:   existing.chunk.clone().or(self.chunk),
+				ifd:     existing.ifd.clone().or(self.ifd),
+				entry:   existing.entry.or(self.entry),
+			},
+			None => ParseErrorContext
+			{
+				message: error.to_string(),
+				..self
+			},
+		};
+		return std::io::Error::new(error.kind(), merged);
+	}
+
+	/// The message of the original error, without the context
+	pub fn
+	message
+	(
+		&self
+	)
+	-> &str
+	{
+		&self.message
+	}
+
+	/// The offset of the chunk or segment within the file
+	pub fn
+	offset_in_file
+	(
+		&self
+	)
+	-> Option<u64>
+	{
+		self.offset
+	}
+
+	/// The type of the chunk (e.g. "zTXt", "EXIF") or name of the segment
+	/// (e.g. "APP1")
+	pub fn
+	chunk_name
+	(
+		&self
+	)
+	-> Option<&str>
+	{
+		self.chunk.as_deref()
+	}
+
+	/// The name of the IFD, e.g. "IFD0" or "ExifIFD"
+	pub fn
+	ifd_name
+	(
+		&self
+	)
+	-> Option<&str>
+	{
+		self.ifd.as_deref()
+	}
+
+	/// The number of the IFD entry, starting at 0
+	pub fn
+	entry_number
+	(
+		&self
+	)
+	-> Option<usize>
+	{
+		self.entry
+	}
+
+	/// Gets the `ParseErrorContext` stored in the given error, if there is one
+	pub fn
+	from_io_error
+	(
+		error: &std::io::Error
+	)
+	-> Option<&ParseErrorContext>
+	{
+		error.get_ref()?.downcast_ref::<ParseErrorContext>()
+	}
+}
+
+impl
+std::fmt::Display
+for
+ParseErrorContext
+{
+	fn
+	fmt
+	(
+		&self,
+		formatter: &mut std::fmt::Formatter<'_>
+	)
+	-> std::fmt::Result
+	{
+		let mut location = Vec::new();
+		if let Some(offset) = self.offset { location.push(format!("offset 0x{:x}", offset)); }
+		if let Some(chunk)  = &self.chunk { location.push(format!("chunk {}",      chunk));  }
+		if let Some(ifd)    = &self.ifd   { location.push(format!("IFD {}",        ifd));    }
+		if let Some(entry)  = self.entry  { location.push(format!("entry {}",      entry));  }
+
+		if location.is_empty()
+		{
+			return write!(formatter, "{}", self.message);
+		}
+		write!(formatter, "{} ({})", self.message, location.join(", "))
+	}
+}
+
+impl
+std::error::Error
+for
+ParseErrorContext
+{}
+
+/// Attaches a `ParseErrorContext` to the error of a result
+pub(crate) trait
+WithParseContext<T>
+{
+	fn
+	with_parse_context
+	(
+		self,
+		context: impl FnOnce() -> ParseErrorContext
+	)
+	-> Result<T, std::io::Error>;
+}
+
+impl<T>
+WithParseContext<T>
+for
+Result<T, std::io::Error>
+{
+	fn
+	with_parse_context
+	(
+		self,
+		context: impl FnOnce() -> ParseErrorContext
+	)
+	-> Result<T, std::io::Error>
+	{
+		self.map_err(|error| context().attach(error))
+	}
+}
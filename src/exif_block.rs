@@ -0,0 +1,135 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! The containers store the EXIF data (the TIFF header followed by the IFDs)
+//! with different prefixes:
+//! - JPEG APP1 segments and PNG raw profiles: `Exif\0\0`
+//! - WebP EXIF chunks and PNG eXIf chunks: None, the data starts with the
+//!   TIFF header
+//! - HEIF Exif items: A 4 byte big endian offset to the TIFF header, usually
+//!   followed by `Exif\0\0`
+//!
+//! The format modules use `add_prefix` when writing and `normalize` when
+//! reading, which brings the data of any of these forms into the form the
+//! generic decoder expects, i.e. `Exif\0\0` followed by the TIFF data.
+
+use crate::general_file_io::io_error;
+use crate::general_file_io::EXIF_HEADER;
+
+const TIFF_HEADER_LITTLE_ENDIAN: [u8; 4] = [0x49, 0x49, 0x2a, 0x00];             // "II*\0"
+const TIFF_HEADER_BIG_ENDIAN:    [u8; 4] = [0x4d, 0x4d, 0x00, 0x2a];             // "MM\0*"
+
+/// The prefix a container puts in front of the TIFF data
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum
+ExifPrefix
+{
+	None,
+	ExifHeader,
+	HeifOffset,
+}
+
+fn
+starts_with_tiff_header
+(
+	data: &[u8]
+)
+-> bool
+{
+	data.starts_with(&TIFF_HEADER_LITTLE_ENDIAN) || data.starts_with(&TIFF_HEADER_BIG_ENDIAN)
+}
+
+/// Puts the prefix used by the container in front of the TIFF data
+pub(crate) fn
+add_prefix
+(
+	tiff_data: &[u8],
+	prefix:    ExifPrefix
+)
+-> Vec<u8>
+{
+	let mut prefixed = Vec::with_capacity(4 + EXIF_HEADER.len() + tiff_data.len());
+	if prefix == ExifPrefix::HeifOffset
+	{
+		prefixed.extend((EXIF_HEADER.len() as u32).to_be_bytes());
+	}
+	if prefix != ExifPrefix::None
+	{
+		prefixed.extend(EXIF_HEADER);
+	}
+	prefixed.extend(tiff_data);
+	return prefixed;
+}
+
+/// Brings EXIF data with any of the prefixes into the form expected by the
+/// generic decoder: `Exif\0\0` followed by the TIFF data.
+/// - If the data doesn't start with a TIFF header after removing the
+///   prefix, an error gets returned.
+pub(crate) fn
+normalize
+(
+	data: &[u8]
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	// Check for the prefixes from the most to the least specific one, as a
+	// HEIF offset of 0 could otherwise be mistaken for part of the data
+	let tiff_data = if data.starts_with(&EXIF_HEADER) && starts_with_tiff_header(&data[EXIF_HEADER.len()..])
+	{
+		&data[EXIF_HEADER.len()..]
+	}
+	else if starts_with_tiff_header(data)
+	{
+		data
+	}
+	else
+	{
+		let heif_offset = data.get(0..4)
+			.map(|offset| u32::from_be_bytes(offset.try_into().unwrap()) as usize)
+			.and_then(|offset| data.get(4usize.saturating_add(offset)..));
+		match heif_offset
+		{
+			Some(tiff_data) if starts_with_tiff_header(tiff_data) => tiff_data,
+			_ => return io_error!(Other, "Data does not contain EXIF data!"),
+		}
+	};
+
+	return Ok(add_prefix(tiff_data, ExifPrefix::ExifHeader));
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::exif_block::ExifPrefix;
+	use crate::exif_block::add_prefix;
+	use crate::exif_block::normalize;
+
+	#[test]
+	fn
+	normalize_all_prefixes()
+	{
+		let little_endian = vec![0x49, 0x49, 0x2a, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00];
+		let big_endian    = vec![0x4d, 0x4d, 0x00, 0x2a, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00];
+
+		for tiff_data in [little_endian, big_endian]
+		{
+			let expected = [b"Exif\0\0".to_vec(), tiff_data.clone()].concat();
+
+			for prefix in [ExifPrefix::None, ExifPrefix::ExifHeader, ExifPrefix::HeifOffset]
+			{
+				assert_eq!(normalize(&add_prefix(&tiff_data, prefix)).unwrap(), expected, "{:?}", prefix);
+			}
+
+			// HEIF offsets of 0, i.e. without the EXIF header
+			let heif_without_header = [vec![0x00, 0x00, 0x00, 0x00], tiff_data.clone()].concat();
+			assert_eq!(normalize(&heif_without_header).unwrap(), expected);
+
+			assert_eq!(&add_prefix(&tiff_data, ExifPrefix::HeifOffset)[..4], &[0x00, 0x00, 0x00, 0x06]);
+		}
+
+		assert!(normalize(b"Exif\0\0XX*\0").is_err());
+		assert!(normalize(&[0x00, 0x00, 0x00, 0x10, 0x49, 0x49]).is_err());
+		assert!(normalize(&[0xff, 0xff, 0xff, 0xff]).is_err());
+		assert!(normalize(&[]).is_err());
+	}
+}
@@ -0,0 +1,341 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Parsing the date strings of the EXIF date tags (e.g. `DateTimeOriginal`)
+//! and formatting them for display. EXIF defines them as
+//! `YYYY:MM:DD HH:MM:SS`, however cameras, phones and editing software
+//! produce all kinds of variations. `ExifDate::parse` handles them depending
+//! on the `DateCoercion`:
+//!
+//! | Value                       | `Strict` | `Lenient`             | `Coerce`              |
+//! |-----------------------------|----------|-----------------------|-----------------------|
+//! | `2023:07:04 18:30:15`       | ✓        | ✓                     | ✓                     |
+//! | `2023/07/04 18:30:15`       |          | ✓                     | ✓                     |
+//! | `2023-07-04T18:30:15+02:00` |          | ✓ (offset is ignored) | ✓ (offset is ignored) |
+//! | `2023:07:04 18:30`          |          | ✓ (0 seconds)         | ✓ (0 seconds)         |
+//! | `2023:07:04`                |          | ✓ (midnight)          | ✓ (midnight)          |
+//! | `2023:13:40 24:00:00`       |          |                       | `2023:12:31 23:00:00` |
+//! | `0000:00:00 00:00:00`       |          |                       |                       |
+//!
+//! Placeholders for unknown dates (zeros or spaces) are never accepted. Dates
+//! always start with the year, as there is no telling whether `01/02` is
+//! meant as January 2nd or February 1st.
+//!
+//! # Examples
+//! ```
+//! use little_exif::exif_date::DateCoercion;
+//! use little_exif::exif_date::DateStyle;
+//! use little_exif::exif_date::ExifDate;
+//!
+//! let date = ExifDate::parse("2023/07/04 18:30", DateCoercion::Lenient).unwrap();
+//! assert_eq!(date.to_exif_string(), "2023:07:04 18:30:00");
+//! assert_eq!(date.format(DateStyle::from_locale("en-US")), "7/4/2023 6:30:00 PM");
+//! assert_eq!(date.format(DateStyle::from_locale("de-DE")), "04.07.2023 18:30:00");
+//! ```
+
+use crate::date_inference::days_in_month;
+
+/// How strictly `ExifDate::parse` checks the date string
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum
+DateCoercion
+{
+	/// Only accept dates of the form defined by EXIF
+	Strict,
+
+	/// Accept other separators, a `T` between date and time, a time zone
+	/// offset after the time and missing seconds or a missing time, as long
+	/// as the values are valid
+	Lenient,
+
+	/// Like `Lenient`, but bring values that are out of range into it, e.g.
+	/// month 13 becomes December and day 40 the last day of the month
+	Coerce,
+}
+
+/// The conventions for displaying dates, see `ExifDate::format`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum
+DateStyle
+{
+	/// `2024-01-31 18:05:00`
+	Iso,
+
+	/// `1/31/2024 6:05:00 PM`
+	Us,
+
+	/// `31/01/2024 18:05:00`
+	Uk,
+
+	/// `31.01.2024 18:05:00`
+	German,
+
+	/// `2024/01/31 18:05:00`
+	Japanese,
+}
+
+impl
+DateStyle
+{
+	/// Gets the style commonly used for the given locale, given as BCP 47
+	/// language tag (e.g. `de-AT`) or POSIX locale (e.g. `de_AT.UTF-8`).
+	/// Unknown locales use `Iso`.
+	pub fn
+	from_locale
+	(
+		locale: &str
+	)
+	-> DateStyle
+	{
+		let mut parts = locale.split(['.', '@']).next().unwrap_or("").split(['-', '_']);
+		let language = parts.next().unwrap_or("").to_ascii_lowercase();
+		let region = parts.next_back().unwrap_or("").to_ascii_uppercase();
+
+		if region == "US" || region == "PH"
+		{
+			return DateStyle::Us;
+		}
+		if language == "en" && region == "CA"
+		{
+			return DateStyle::Iso;
+		}
+		match language.as_str()
+		{
+			"en" | "fr" | "es" | "it" | "pt" | "el" | "ga" | "vi"            => DateStyle::Uk,
+			"de" | "da" | "fi" | "nb" | "nn" | "no" | "pl" | "ru" | "uk" |
+			"cs" | "sk" | "tr" | "ro" | "hr" | "sl"                          => DateStyle::German,
+			"ja" | "zh"                                                      => DateStyle::Japanese,
+			_                                                                => DateStyle::Iso,
+		}
+	}
+}
+
+/// A date and time as stored in the EXIF date tags, without a time zone
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct
+ExifDate
+{
+	pub year:   u16,
+	pub month:  u8,
+	pub day:    u8,
+	pub hour:   u8,
+	pub minute: u8,
+	pub second: u8,
+}
+
+/// Parses the numbers of the date or time, separated by any of the given
+/// separators. Returns `None` if there are too many numbers, too few or
+/// any of them is not a number.
+fn
+parse_numbers
+(
+	text:       &str,
+	separators: &[char],
+	min_count:  usize,
+	max_count:  usize
+)
+-> Option<Vec<u32>>
+{
+	let numbers = text.split(separators)
+		.map(|number| match !number.is_empty() && number.len() <= 4 && number.chars().all(|digit| digit.is_ascii_digit())
+		{
+			true  => number.parse::<u32>().ok(),
+			false => None,
+		})
+		.collect::<Option<Vec<u32>>>()?;
+
+	if !(min_count..=max_count).contains(&numbers.len())
+	{
+		return None;
+	}
+	return Some(numbers);
+}
+
+impl
+ExifDate
+{
+	/// Parses the value of an EXIF date tag, see the module documentation.
+	/// Trailing NUL characters and whitespace are ignored. Returns `None` if
+	/// the value is not a date or a placeholder for an unknown date.
+	pub fn
+	parse
+	(
+		text:     &str,
+		coercion: DateCoercion
+	)
+	-> Option<ExifDate>
+	{
+		let text = text.trim_end_matches('\0').trim();
+
+		let (date, time) = match text.split_once([' ', 'T'])
+		{
+			Some((date, time)) => (date, Some(time.trim_start())),
+			None               => (text, None),
+		};
+
+		// Drop a time zone offset (e.g. "+02:00" or "Z") and fractional
+		// seconds following the time
+		let time = time.map(|time| match time.find(['+', '-', 'Z', 'z'])
+		{
+			Some(offset_start) => &time[..offset_start],
+			None               => time,
+		});
+		let time = time.map(|time| match time.rfind('.')
+		{
+			Some(fraction_start) if time.matches(':').count() == 2 => &time[..fraction_start],
+			_                                                      => time,
+		});
+
+		// The year has to come first, with all of its four digits
+		if !date.get(..4).is_some_and(|year| year.chars().all(|digit| digit.is_ascii_digit()))
+		{
+			return None;
+		}
+		let date = parse_numbers(date, &[':', '/', '-', '.'], 3, 3)?;
+		let time = match time
+		{
+			Some(time) => parse_numbers(time, &[':', '.'], 2, 3)?,
+			None       => Vec::new(),
+		};
+		let mut components = [date[0], date[1], date[2], 0, 0, 0];
+		components[3..3 + time.len()].copy_from_slice(&time);
+
+		// Placeholders and values that are far off
+		if components[0] == 0 || components[1..].iter().all(|component| *component == 0) || components[0] > 9999 || components[1..].iter().any(|component| *component > 99)
+		{
+			return None;
+		}
+
+		if coercion == DateCoercion::Coerce
+		{
+			components[1] = components[1].clamp(1, 12);
+			components[2] = components[2].clamp(1, days_in_month(components[0], components[1]));
+			components[3] = components[3].min(23);
+			components[4] = components[4].min(59);
+			components[5] = components[5].min(59);
+		}
+
+		let [year, month, day, hour, minute, second] = components;
+		let valid = (1..=12).contains(&month)
+			&& (1..=days_in_month(year, month)).contains(&day)
+			&& hour   < 24
+			&& minute < 60
+			&& second < 60;
+		if !valid
+		{
+			return None;
+		}
+
+		let date = ExifDate
+		{
+			year:   year   as u16,
+			month:  month  as u8,
+			day:    day    as u8,
+			hour:   hour   as u8,
+			minute: minute as u8,
+			second: second as u8,
+		};
+		if coercion == DateCoercion::Strict && date.to_exif_string() != text
+		{
+			return None;
+		}
+		return Some(date);
+	}
+
+	/// Formats the date as defined by EXIF, e.g. `2024:01:31 18:05:00`
+	pub fn
+	to_exif_string
+	(
+		&self
+	)
+	-> String
+	{
+		format!("{:04}:{:02}:{:02} {:02}:{:02}:{:02}", self.year, self.month, self.day, self.hour, self.minute, self.second)
+	}
+
+	/// Formats the date for display in the given style
+	pub fn
+	format
+	(
+		&self,
+		style: DateStyle
+	)
+	-> String
+	{
+		let time = format!("{:02}:{:02}:{:02}", self.hour, self.minute, self.second);
+		match style
+		{
+			DateStyle::Iso      => format!("{:04}-{:02}-{:02} {}", self.year, self.month, self.day, time),
+			DateStyle::Uk       => format!("{:02}/{:02}/{:04} {}", self.day, self.month, self.year, time),
+			DateStyle::German   => format!("{:02}.{:02}.{:04} {}", self.day, self.month, self.year, time),
+			DateStyle::Japanese => format!("{:04}/{:02}/{:02} {}", self.year, self.month, self.day, time),
+			DateStyle::Us       => format!(
+				"{}/{}/{:04} {}:{:02}:{:02} {}",
+				self.month,
+				self.day,
+				self.year,
+				match self.hour % 12 { 0 => 12, hour => hour },
+				self.minute,
+				self.second,
+				if self.hour < 12 { "AM" } else { "PM" }
+			),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::exif_date::DateCoercion;
+	use crate::exif_date::DateStyle;
+	use crate::exif_date::ExifDate;
+
+	#[test]
+	fn
+	parse_malformed_dates()
+	{
+		let parse = |text: &str, coercion: DateCoercion| ExifDate::parse(text, coercion).map(|date| date.to_exif_string());
+
+		for text in ["2023:07:04 18:30:15", "2023:07:04 18:30:15\0", "2023:07:04 18:30:15  "]
+		{
+			assert_eq!(parse(text, DateCoercion::Strict), Some("2023:07:04 18:30:15".to_string()));
+		}
+		for text in ["2023/07/04 18:30:15", "2023-07-04T18:30:15+02:00", "2023.7.4 18:30:15.120", "2023:07:04  18:30:15Z"]
+		{
+			assert_eq!(parse(text, DateCoercion::Strict), None, "{}", text);
+			assert_eq!(parse(text, DateCoercion::Lenient), Some("2023:07:04 18:30:15".to_string()), "{}", text);
+		}
+		assert_eq!(parse("2023:07:04 18:30", DateCoercion::Lenient), Some("2023:07:04 18:30:00".to_string()));
+		assert_eq!(parse("2023:07:04",       DateCoercion::Lenient), Some("2023:07:04 00:00:00".to_string()));
+
+		// Out of range values are only coerced on request
+		assert_eq!(parse("2023:13:40 24:00:00", DateCoercion::Lenient), None);
+		assert_eq!(parse("2023:13:40 24:00:00", DateCoercion::Coerce),  Some("2023:12:31 23:00:00".to_string()));
+		assert_eq!(parse("2024:02:30 12:61:00", DateCoercion::Coerce),  Some("2024:02:29 12:59:00".to_string()));
+
+		// Placeholders and things that aren't dates
+		for text in ["0000:00:00 00:00:00", "    :  :     :  :  ", "", "07/04/23", "2023:07:04 18:30:15:00", "yesterday"]
+		{
+			assert_eq!(parse(text, DateCoercion::Coerce), None, "{}", text);
+		}
+	}
+
+	#[test]
+	fn
+	format_dates()
+	{
+		let date = ExifDate::parse("2024:01:31 00:05:09", DateCoercion::Strict).unwrap();
+		assert_eq!(date.format(DateStyle::Iso),      "2024-01-31 00:05:09");
+		assert_eq!(date.format(DateStyle::Us),       "1/31/2024 12:05:09 AM");
+		assert_eq!(date.format(DateStyle::Uk),       "31/01/2024 00:05:09");
+		assert_eq!(date.format(DateStyle::German),   "31.01.2024 00:05:09");
+		assert_eq!(date.format(DateStyle::Japanese), "2024/01/31 00:05:09");
+
+		assert_eq!(DateStyle::from_locale("en-US"),       DateStyle::Us);
+		assert_eq!(DateStyle::from_locale("en_GB.UTF-8"), DateStyle::Uk);
+		assert_eq!(DateStyle::from_locale("de_AT"),       DateStyle::German);
+		assert_eq!(DateStyle::from_locale("zh-Hant-TW"),  DateStyle::Japanese);
+		assert_eq!(DateStyle::from_locale("sv-SE"),       DateStyle::Iso);
+		assert_eq!(DateStyle::from_locale("C"),           DateStyle::Iso);
+	}
+}
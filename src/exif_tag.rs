@@ -20,6 +20,69 @@ ExifTagGroup
 	IFD1,
 }
 
+impl
+ExifTagGroup
+{
+	/// Gets the group whose tag IDs are shared by this group: The GPS and
+	/// the Interop IFD have their own IDs (e.g. `0x000b` is `GPSDOP` in the
+	/// GPS IFD but `ProcessingSoftware` in IFD0), all other groups share the
+	/// IDs of IFD0
+	pub(crate) fn
+	id_space
+	(
+		&self
+	)
+	-> ExifTagGroup
+	{
+		match self
+		{
+			ExifTagGroup::GPSIFD     => ExifTagGroup::GPSIFD,
+			ExifTagGroup::InteropIFD => ExifTagGroup::InteropIFD,
+			_                        => ExifTagGroup::IFD0,
+		}
+	}
+}
+
+/// Constructs the value of a tag from text, which is only possible for the
+/// value type of `STRING` tags
+trait
+FromText
+: Sized
+{
+	fn
+	from_text
+	(
+		text: &str
+	)
+	-> Option<Self>;
+}
+
+impl FromText for String
+{
+	fn
+	from_text
+	(
+		text: &str
+	)
+	-> Option<String>
+	{
+		Some(text.to_string())
+	}
+}
+
+impl<T> FromText for Vec<T>
+{
+	fn
+	from_text
+	(
+		_text: &str
+	)
+	-> Option<Vec<T>>
+	{
+		None
+	}
+}
+
 macro_rules! build_tag_enum {
 	( 
 		$( (
@@ -36,7 +99,7 @@ macro_rules! build_tag_enum {
 		/// These are the currently supported tags by little_exif. 
 		/// Note that for tags that are unknown at the moment a fallback
 		/// solution is provided using the `Unknown...` variants. 
-		#[derive(PartialEq, Debug)]
+		#[derive(PartialEq, Debug, Clone)]
 		pub enum 
 		ExifTag
 		{
@@ -87,9 +150,68 @@ macro_rules! build_tag_enum {
 				}
 			}
 
+			/// Gets the name of an EXIF tag, e.g. "ImageDescription". Unknown
+			/// tags are named after their hex value, e.g. "Unknown0x0113".
+			pub fn
+			name
+			(
+				&self
+			)
+			-> String
+			{
+				match *self
+				{
+					$(
+						ExifTag::$tag(_) => String::from(stringify!($tag)),
+					)*
+					_ => format!("Unknown0x{:04x}", self.as_u16()),
+				}
+			}
+
+			/// Gets the tag for a given name, e.g. entered by a user. The case
+			/// as well as spaces, dashes and underscores are ignored, so
+			/// "f-number" resolves to `FNumber`. Common alternative names like
+			/// "Aperture" are accepted as well, see `TAG_NAME_ALIASES`.
+			/// The tag is initialized with new, empty data.
+			/// If the name is unknown, an error is returned.
+			///
+			/// # Examples
+			/// ```
+			/// use little_exif::exif_tag::ExifTag;
+			///
+			/// assert_eq!(ExifTag::from_name("F-Number").unwrap().as_u16(), 0x829d);
+			/// assert_eq!(ExifTag::from_name("aperture").unwrap().as_u16(), 0x829d);
+			/// ```
+			pub fn
+			from_name
+			(
+				name: &str
+			)
+			-> Result<ExifTag, String>
+			{
+				let mut normalized = normalize_tag_name(name);
+				if let Some((_, tag_name)) = TAG_NAME_ALIASES.iter().find(|(alias, _)| normalize_tag_name(alias) == normalized)
+				{
+					normalized = normalize_tag_name(tag_name);
+				}
+
+				$(
+					if normalize_tag_name(stringify!($tag)) == normalized
+					{
+						return Ok(ExifTag::$tag(<paste!{[<$format_enum>]}>::new()));
+					}
+				)*
+				return Err(format!("Unknown EXIF tag name '{}'", name));
+			}
+
 			/// Gets the tag for a given hex value. 
 			/// The tag is initialized with new, empty data.
 			/// If the hex value is unknown, an error is returned.
+			/// As the GPS and the Interop IFD have their own tag IDs, a hex
+			/// value may belong to several tags (e.g. `0x0001` is
+			/// `InteroperabilityIndex` as well as `GPSLatitudeRef`), in which
+			/// case the first one is returned. Use `from_u16_in_group` to
+			/// resolve such hex values.
 			/// 
 			/// # Examples
 			/// ```no_run
@@ -104,20 +226,51 @@ macro_rules! build_tag_enum {
 			)
 			-> Result<ExifTag, String>
 			{
-				
-				match hex_value
-				{
-					$(
-						$hex_value => Ok(ExifTag::$tag(<paste!{[<$format_enum>]}>::new())),
-					)*
-					_ => Err(String::from("Invalid hex value for EXIF tag - Use 'Unknown...' instead")),
-				}
+				$(
+					if $hex_value == hex_value
+					{
+						return Ok(ExifTag::$tag(<paste!{[<$format_enum>]}>::new()));
+					}
+				)*
+				return Err(String::from("Invalid hex value for EXIF tag - Use 'Unknown...' instead"));
+			}
+
+			/// Gets the tag for a given hex value within the given group, as the
+			/// GPS and the Interop IFD have their own tag IDs (e.g. `0x000b` is
+			/// `GPSDOP` in the GPS IFD but unknown in the others).
+			/// The tag is initialized with new, empty data.
+			/// If the hex value is unknown in the group, an error is returned.
+			///
+			/// # Examples
+			/// ```
+			/// use little_exif::exif_tag::ExifTag;
+			/// use little_exif::exif_tag::ExifTagGroup;
+			///
+			/// assert!(ExifTag::from_u16_in_group(0x000b, &ExifTagGroup::GPSIFD).is_ok());
+			/// assert!(ExifTag::from_u16_in_group(0x000b, &ExifTagGroup::IFD0).is_err());
+			/// ```
+			pub fn
+			from_u16_in_group
+			(
+				hex_value: u16,
+				group:     &ExifTagGroup
+			)
+			-> Result<ExifTag, String>
+			{
+				$(
+					if $hex_value == hex_value && ExifTagGroup::$group.id_space() == group.id_space()
+					{
+						return Ok(ExifTag::$tag(<paste!{[<$format_enum>]}>::new()));
+					}
+				)*
+				return Err(String::from("Invalid hex value for EXIF tag in this group - Use 'Unknown...' instead"));
 			}
 
 			/// Gets the tag for a given hex value. 
 			/// The tag is initalized using the given raw data by converting it
 			/// to the appropriate format.
-			/// If the hex value is unknown, the other parameters are used to
+			/// If the hex value is unknown in the given group (see
+			/// `from_u16_in_group`), the other parameters are used to
 			/// generate an appropriate unkown tag for the specified format.
 			/// 
 			/// # Examples
@@ -146,32 +299,29 @@ macro_rules! build_tag_enum {
 			)
 			-> Result<ExifTag, String>
 			{
-				match hex_value
-				{
-					$(
-						$hex_value => Ok(ExifTag::$tag(
+				$(
+					if $hex_value == hex_value && ExifTagGroup::$group.id_space() == group.id_space()
+					{
+						return Ok(ExifTag::$tag(
 							<paste!{[<$format_enum>]} as U8conversion<paste!{[<$format_enum>]}>>::from_u8_vec(raw_data, endian)
-						)),
-					)*
-					_ => {
-						// In this case, the given hex_value represents a tag that is unknown
-						match *format
-						{
-							ExifTagFormat::INT8U        => Ok(ExifTag::UnknownINT8U(        <INT8U          as U8conversion<INT8U>>::from_u8_vec(raw_data, endian),         hex_value, *group)),
-							ExifTagFormat::STRING       => Ok(ExifTag::UnknownSTRING(       <STRING         as U8conversion<STRING>>::from_u8_vec(raw_data, endian),        hex_value, *group)),
-							ExifTagFormat::INT16U       => Ok(ExifTag::UnknownINT16U(       <INT16U         as U8conversion<INT16U>>::from_u8_vec(raw_data, endian),        hex_value, *group)),
-							ExifTagFormat::INT32U       => Ok(ExifTag::UnknownINT32U(       <INT32U         as U8conversion<INT32U>>::from_u8_vec(raw_data, endian),        hex_value, *group)),
-							ExifTagFormat::RATIONAL64U  => Ok(ExifTag::UnknownRATIONAL64U(  <RATIONAL64U    as U8conversion<RATIONAL64U>>::from_u8_vec(raw_data, endian),   hex_value, *group)),
-							ExifTagFormat::INT8S        => Ok(ExifTag::UnknownINT8S(        <INT8S          as U8conversion<INT8S>>::from_u8_vec(raw_data, endian),         hex_value, *group)),
-							ExifTagFormat::UNDEF        => Ok(ExifTag::UnknownUNDEF(        <UNDEF          as U8conversion<UNDEF>>::from_u8_vec(raw_data, endian),         hex_value, *group)),
-							ExifTagFormat::INT16S       => Ok(ExifTag::UnknownINT16S(       <INT16S         as U8conversion<INT16S>>::from_u8_vec(raw_data, endian),        hex_value, *group)),
-							ExifTagFormat::INT32S       => Ok(ExifTag::UnknownINT32S(       <INT32S         as U8conversion<INT32S>>::from_u8_vec(raw_data, endian),        hex_value, *group)),
-							ExifTagFormat::RATIONAL64S	=> Ok(ExifTag::UnknownRATIONAL64S(	<RATIONAL64S    as U8conversion<RATIONAL64S>>::from_u8_vec(raw_data, endian),   hex_value, *group)),
-							ExifTagFormat::FLOAT        => Ok(ExifTag::UnknownFLOAT(        <FLOAT          as U8conversion<FLOAT>>::from_u8_vec(raw_data, endian),         hex_value, *group)),
-							ExifTagFormat::DOUBLE       => Ok(ExifTag::UnknownDOUBLE(       <DOUBLE         as U8conversion<DOUBLE>>::from_u8_vec(raw_data, endian),        hex_value, *group)),
-							
-						}
-					},
+						));
+					}
+				)*
+				// In this case, the given hex_value represents a tag that is unknown
+				match *format
+				{
+					ExifTagFormat::INT8U        => Ok(ExifTag::UnknownINT8U(        <INT8U          as U8conversion<INT8U>>::from_u8_vec(raw_data, endian),         hex_value, *group)),
+					ExifTagFormat::STRING       => Ok(ExifTag::UnknownSTRING(       <STRING         as U8conversion<STRING>>::from_u8_vec(raw_data, endian),        hex_value, *group)),
+					ExifTagFormat::INT16U       => Ok(ExifTag::UnknownINT16U(       <INT16U         as U8conversion<INT16U>>::from_u8_vec(raw_data, endian),        hex_value, *group)),
+					ExifTagFormat::INT32U       => Ok(ExifTag::UnknownINT32U(       <INT32U         as U8conversion<INT32U>>::from_u8_vec(raw_data, endian),        hex_value, *group)),
+					ExifTagFormat::RATIONAL64U  => Ok(ExifTag::UnknownRATIONAL64U(  <RATIONAL64U    as U8conversion<RATIONAL64U>>::from_u8_vec(raw_data, endian),   hex_value, *group)),
+					ExifTagFormat::INT8S        => Ok(ExifTag::UnknownINT8S(        <INT8S          as U8conversion<INT8S>>::from_u8_vec(raw_data, endian),         hex_value, *group)),
+					ExifTagFormat::UNDEF        => Ok(ExifTag::UnknownUNDEF(        <UNDEF          as U8conversion<UNDEF>>::from_u8_vec(raw_data, endian),         hex_value, *group)),
+					ExifTagFormat::INT16S       => Ok(ExifTag::UnknownINT16S(       <INT16S         as U8conversion<INT16S>>::from_u8_vec(raw_data, endian),        hex_value, *group)),
+					ExifTagFormat::INT32S       => Ok(ExifTag::UnknownINT32S(       <INT32S         as U8conversion<INT32S>>::from_u8_vec(raw_data, endian),        hex_value, *group)),
+					ExifTagFormat::RATIONAL64S	=> Ok(ExifTag::UnknownRATIONAL64S(	<RATIONAL64S    as U8conversion<RATIONAL64S>>::from_u8_vec(raw_data, endian),   hex_value, *group)),
+					ExifTagFormat::FLOAT        => Ok(ExifTag::UnknownFLOAT(        <FLOAT          as U8conversion<FLOAT>>::from_u8_vec(raw_data, endian),         hex_value, *group)),
+					ExifTagFormat::DOUBLE       => Ok(ExifTag::UnknownDOUBLE(       <DOUBLE         as U8conversion<DOUBLE>>::from_u8_vec(raw_data, endian),        hex_value, *group)),
 				}
 			}
 
@@ -246,7 +396,7 @@ macro_rules! build_tag_enum {
 			{
 				if self.is_unknown()
 				{
-					if let Ok(_) = Self::from_u16(self.as_u16())
+					if let Ok(_) = Self::from_u16_in_group(self.as_u16(), &self.get_group())
 					{
 						return false;
 					}
@@ -407,6 +557,26 @@ macro_rules! build_tag_enum {
 					ExifTag::UnknownDOUBLE(         value, _, _) => value.to_u8_vec(endian),
 				}
 			}
+
+			/// Gets a copy of the tag with the given text as value. Returns
+			/// `None` if the tag is not a `STRING` tag.
+			pub(crate) fn
+			with_string_value
+			(
+				&self,
+				text: &str
+			)
+			-> Option<ExifTag>
+			{
+				match self
+				{
+					$(
+						ExifTag::$tag(_) => <paste!{[<$format_enum>]} as FromText>::from_text(text).map(ExifTag::$tag),
+					)*
+					ExifTag::UnknownSTRING(_, hex_value, group) => Some(ExifTag::UnknownSTRING(text.to_string(), *hex_value, *group)),
+					_ => None,
+				}
+			}
 		}
 	};
 }
@@ -417,7 +587,6 @@ macro_rules! build_tag_enum {
 // Note regarding non-writable tags: Apart from
 // - StripOffsets
 // - StripByteCounts
-// - Opto-ElectricConvFactor (OECF)
 // - SpatialFrequencyResponse
 // - DeviceSettingDescription
 // none of them are part of the EXIF 2.32 specification
@@ -427,6 +596,31 @@ build_tag_enum![
 	// Tag                        Tag ID  Format         Nr. Components     Writable   Group
 	(InteroperabilityIndex,       0x0001, STRING,        Some::<u32>(4),    true,      InteropIFD),
 
+	(GPSVersionID,                0x0000, INT8U,         Some::<u32>(4),    true,      GPSIFD),
+	(GPSLatitudeRef,              0x0001, STRING,        Some::<u32>(2),    true,      GPSIFD),     // N or S
+	(GPSLatitude,                 0x0002, RATIONAL64U,   Some::<u32>(3),    true,      GPSIFD),
+	(GPSLongitudeRef,             0x0003, STRING,        Some::<u32>(2),    true,      GPSIFD),     // E or W
+	(GPSLongitude,                0x0004, RATIONAL64U,   Some::<u32>(3),    true,      GPSIFD),
+	(GPSAltitudeRef,              0x0005, INT8U,         Some::<u32>(1),    true,      GPSIFD),     // 0/1: above/below sea level, 2/3: above/below the ellipsoid (EXIF 3.0)
+	(GPSAltitude,                 0x0006, RATIONAL64U,   Some::<u32>(1),    true,      GPSIFD),
+
+	(GPSDOP,                      0x000b, RATIONAL64U,   Some::<u32>(1),    true,      GPSIFD),
+	(GPSSpeedRef,                 0x000c, STRING,        Some::<u32>(2),    true,      GPSIFD),     // K, M or N
+	(GPSSpeed,                    0x000d, RATIONAL64U,   Some::<u32>(1),    true,      GPSIFD),
+	(GPSTrackRef,                 0x000e, STRING,        Some::<u32>(2),    true,      GPSIFD),     // T or M
+	(GPSTrack,                    0x000f, RATIONAL64U,   Some::<u32>(1),    true,      GPSIFD),
+	(GPSImgDirectionRef,          0x0010, STRING,        Some::<u32>(2),    true,      GPSIFD),     // T or M
+	(GPSImgDirection,             0x0011, RATIONAL64U,   Some::<u32>(1),    true,      GPSIFD),
+
+	(GPSDestLatitudeRef,          0x0013, STRING,        Some::<u32>(2),    true,      GPSIFD),     // N or S
+	(GPSDestLatitude,             0x0014, RATIONAL64U,   Some::<u32>(3),    true,      GPSIFD),
+	(GPSDestLongitudeRef,         0x0015, STRING,        Some::<u32>(2),    true,      GPSIFD),     // E or W
+	(GPSDestLongitude,            0x0016, RATIONAL64U,   Some::<u32>(3),    true,      GPSIFD),
+	(GPSDestBearingRef,           0x0017, STRING,        Some::<u32>(2),    true,      GPSIFD),     // T or M
+	(GPSDestBearing,              0x0018, RATIONAL64U,   Some::<u32>(1),    true,      GPSIFD),
+	(GPSDestDistanceRef,          0x0019, STRING,        Some::<u32>(2),    true,      GPSIFD),     // K, M or N
+	(GPSDestDistance,             0x001a, RATIONAL64U,   Some::<u32>(1),    true,      GPSIFD),
+
 	(ImageWidth,                  0x0100, INT32U,        Some::<u32>(1),    true,      IFD0),       // IFD1?
 	(ImageHeight,                 0x0101, INT32U,        Some::<u32>(1),    true,      IFD0),       // IFD1?
 	(BitsPerSample,               0x0102, INT16U,        Some::<u32>(3),    true,      IFD0),       // IFD1?
@@ -434,6 +628,7 @@ build_tag_enum![
 
 	(PhotometricInterpretation,   0x0106, INT16U,        Some::<u32>(1),    true,      IFD0),       // IFD1?
 
+	(DocumentName,                0x010d, STRING,        None::<u32>,       true,      IFD0),
 	(ImageDescription,            0x010e, STRING,        None::<u32>,       true,      IFD0),
 	(Make,                        0x010f, STRING,        None::<u32>,       true,      IFD0),
 	(Model,                       0x0110, STRING,        None::<u32>,       true,      IFD0),
@@ -447,8 +642,10 @@ build_tag_enum![
 	(XResolution,                 0x011a, RATIONAL64U,   Some::<u32>(1),    true,      IFD0),
 	(YResolution,                 0x011b, RATIONAL64U,   Some::<u32>(1),    true,      IFD0),
 	(PlanarConfiguration,         0x011c, INT16U,        Some::<u32>(1),    true,      IFD0),       // IFD1?
+	(PageName,                    0x011d, STRING,        None::<u32>,       true,      IFD0),
 
 	(ResolutionUnit,              0x0128, INT16U,        Some::<u32>(1),    true,      IFD0),       // IFD1?
+	(PageNumber,                  0x0129, INT16U,        Some::<u32>(2),    true,      IFD0),       // Page (starting at 0), total number of pages (0 if unknown)
 
 	(TransferFunction,            0x012d, INT16U,        Some::<u32>(3),    true,      IFD0),
 
@@ -468,6 +665,9 @@ build_tag_enum![
 	(YCbCrPositioning,            0x0213, INT16U,        Some::<u32>(1),    true,      IFD0),       // IFD1?
 	(ReferenceBlackWhite,         0x0214, RATIONAL64U,   Some::<u32>(6),    true,      IFD0),       // IFD1?
 
+	(Rating,                      0x4746, INT16U,        Some::<u32>(1),    true,      IFD0),       // 0 to 5 stars, used by Windows
+	(RatingPercent,               0x4749, INT16U,        Some::<u32>(1),    true,      IFD0),       // Windows: 1, 25, 50, 75 or 99 for 1 to 5 stars
+
 	(Copyright,                   0x8298, STRING,        None::<u32>,       true,      IFD0),
 	(ExposureTime,                0x829a, RATIONAL64U,   Some::<u32>(1),    true,      ExifIFD),
 	(FNumber,                     0x829d, RATIONAL64U,   Some::<u32>(1),    true,      ExifIFD),
@@ -476,9 +676,9 @@ build_tag_enum![
 
 	(ExposureProgram,             0x8822, INT16U,        Some::<u32>(1),    true,      ExifIFD),
 	(SpectralSensitivity,         0x8824, STRING,        None::<u32>,       true,      ExifIFD),
-	(GPSInfo,                     0x8825, INT32U,        Some::<u32>(1),    true,      IFD0),       // -> GPS Tags: https://exiftool.org/TagNames/GPS.html
+	(GPSInfo,                     0x8825, INT32U,        Some::<u32>(1),    false,     IFD0),       // -> GPS Tags: https://exiftool.org/TagNames/GPS.html
 	(ISO,                         0x8827, INT16U,        None::<u32>,       true,      ExifIFD),
-	(OECF,                        0x8828, UNDEF,         None::<u32>,       false,     NO_GROUP),
+	(OECF,                        0x8828, UNDEF,         None::<u32>,       true,      ExifIFD),    // See scan::Oecf
 	(SensitivityType,             0x8830, INT16U,        Some::<u32>(1),    true,      ExifIFD),
 	(StandardOutputSensitivity,   0x8831, INT32U,        Some::<u32>(1),    true,      ExifIFD),
 	(RecommendedExposureIndex,    0x8832, INT32U,        Some::<u32>(1),    true,      ExifIFD),
@@ -506,6 +706,7 @@ build_tag_enum![
 	(LightSource,                 0x9208, INT16U,        Some::<u32>(1),    true,      ExifIFD),    // -> EXIF LightSource Values: https://exiftool.org/TagNames/EXIF.html#LightSource
 	(Flash,                       0x9209, INT16U,        Some::<u32>(1),    true,      ExifIFD),    // -> EXIF Flash Values: https://exiftool.org/TagNames/EXIF.html#Flash
 	(FocalLength,                 0x920a, RATIONAL64U,   Some::<u32>(1),    true,      ExifIFD),
+	(ImageNumber,                 0x9211, INT32U,        Some::<u32>(1),    true,      ExifIFD),
 
 	(SubjectArea,                 0x9214, INT16U,        Some::<u32>(4),    true,      ExifIFD),
 
@@ -522,6 +723,12 @@ build_tag_enum![
 	(Acceleration,                0x9404, RATIONAL64U,   Some::<u32>(1),    true,      ExifIFD),
 	(CameraElevationAngle,        0x9405, RATIONAL64S,   Some::<u32>(1),    true,      ExifIFD),
 
+	(XPTitle,                     0x9c9b, INT8U,         None::<u32>,       true,      IFD0),       // Windows: UTF-16LE, see shell_properties
+	(XPComment,                   0x9c9c, INT8U,         None::<u32>,       true,      IFD0),       // Windows
+	(XPAuthor,                    0x9c9d, INT8U,         None::<u32>,       true,      IFD0),       // Windows
+	(XPKeywords,                  0x9c9e, INT8U,         None::<u32>,       true,      IFD0),       // Windows, separated by semicolons
+	(XPSubject,                   0x9c9f, INT8U,         None::<u32>,       true,      IFD0),       // Windows
+
 	(FlashpixVersion,             0xa000, UNDEF,         Some::<u32>(4),    true,      ExifIFD),
 	(ColorSpace,                  0xa001, INT16U,        Some::<u32>(1),    true,      ExifIFD),
 	(ExifImageWidth,              0xa002, INT32U,        Some::<u32>(1),    true,      ExifIFD),
@@ -570,9 +777,52 @@ build_tag_enum![
 	(CompositeImageCount,         0xa461, INT16U,        Some::<u32>(2),    true,      ExifIFD),
 	(CompositeImageExposureTimes, 0xa462, UNDEF,         None::<u32>,       true,      ExifIFD),
 
-	(Gamma,                       0xa500, RATIONAL64U,   Some::<u32>(1),    true,      ExifIFD)
+	(Gamma,                       0xa500, RATIONAL64U,   Some::<u32>(1),    true,      ExifIFD),
+
+	(OpcodeList1,                 0xc740, UNDEF,         None::<u32>,       true,      IFD0),       // DNG, usually in the raw IFD -> see dng_opcode module
+	(OpcodeList2,                 0xc741, UNDEF,         None::<u32>,       true,      IFD0),       // DNG
+	(OpcodeList3,                 0xc74e, UNDEF,         None::<u32>,       true,      IFD0)        // DNG
 ];
 
+/// Alternative names accepted by `ExifTag::from_name` and the names of the
+/// tags they resolve to. Matching ignores case, spaces, dashes and underscores.
+pub const TAG_NAME_ALIASES: [(&str, &str); 19] = [
+	("Aperture",            "FNumber"),
+	("ShutterSpeed",        "ExposureTime"),
+	("Exposure",            "ExposureTime"),
+	("ISOSpeedRatings",     "ISO"),
+	("ExposureBias",        "ExposureCompensation"),
+	("DateTaken",           "DateTimeOriginal"),
+	("DateTime",            "ModifyDate"),
+	("DateTimeDigitized",   "CreateDate"),
+	("Description",         "ImageDescription"),
+	("Caption",             "ImageDescription"),
+	("Title",               "ImageDescription"),
+	("Author",              "Artist"),
+	("Creator",             "Artist"),
+	("Camera",              "Model"),
+	("CameraModel",         "Model"),
+	("CameraMake",          "Make"),
+	("Manufacturer",        "Make"),
+	("Lens",                "LensModel"),
+	("FocalLength35mm",     "FocalLengthIn35mmFormat"),
+];
+
+/// Brings a tag name into the form used for comparing names, i.e. lowercase
+/// without spaces, dashes and underscores
+fn
+normalize_tag_name
+(
+	name: &str
+)
+-> String
+{
+	name.chars()
+		.filter(|character| !matches!(character, ' ' | '-' | '_'))
+		.flat_map(|character| character.to_lowercase())
+		.collect()
+}
+
 impl ExifTag
 {
 	/// Checks if a tag is for representing the offset to a SubIFD (e.g. ExifIFD).
@@ -590,9 +840,70 @@ impl ExifTag
 		{
 			ExifTag::ExifOffset(_)		=> Some(ExifTagGroup::ExifIFD),
 			ExifTag::GPSInfo(_)			=> Some(ExifTagGroup::GPSIFD),
-			ExifTag::MakerNote(_)		=> Some(ExifTagGroup::MakerNotesIFD),
 			ExifTag::InteropOffset(_)	=> Some(ExifTagGroup::InteropIFD),
 			_ => None
 		}
 	}
+
+	/// Checks if the tag has no actual value: Tags without any components
+	/// and `STRING` tags consisting of nothing but whitespace and NUL
+	/// characters. Offset tags are never empty, as their value gets computed
+	/// during the writing process. See `read_options::EmptyValues` for how
+	/// such tags are handled.
+	pub fn
+	is_empty_value
+	(
+		&self
+	)
+	-> bool
+	{
+		if self.is_offset_tag().is_some()
+		{
+			return false;
+		}
+
+		let raw_data = self.value_as_u8_vec(&Endian::Little);
+		if self.is_string()
+		{
+			return raw_data.iter().all(|byte| *byte == 0 || byte.is_ascii_whitespace());
+		}
+		return raw_data.is_empty();
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::exif_tag::ExifTag;
+	use crate::exif_tag::ExifTagGroup;
+	use crate::exif_tag::TAG_NAME_ALIASES;
+	use crate::exif_tag::normalize_tag_name;
+
+	#[test]
+	fn
+	tag_names_are_unambiguous()
+	{
+		let names = [ExifTagGroup::IFD0, ExifTagGroup::GPSIFD, ExifTagGroup::InteropIFD].iter()
+			.flat_map(|group| (0..=u16::MAX).filter_map(|hex_value| ExifTag::from_u16_in_group(hex_value, group).ok()))
+			.map(|tag| normalize_tag_name(&tag.name()))
+			.collect::<Vec<_>>();
+
+		for (index, name) in names.iter().enumerate()
+		{
+			assert!(!names[index+1..].contains(name), "{}", name);
+		}
+
+		// Aliases must neither shadow tag names nor point to unknown ones
+		for (alias, tag_name) in TAG_NAME_ALIASES
+		{
+			assert!(!names.contains(&normalize_tag_name(alias)), "{}", alias);
+			assert_eq!(ExifTag::from_name(alias).unwrap().name(), tag_name);
+		}
+
+		assert_eq!(ExifTag::from_name("exif_offset").unwrap().name(), "ExifOffset");
+		assert!(ExifTag::from_name("NoSuchTag").is_err());
+
+		assert_eq!(ExifTag::from_u16(0x0001).unwrap().name(), "InteroperabilityIndex");
+		assert_eq!(ExifTag::from_u16_in_group(0x0001, &ExifTagGroup::GPSIFD).unwrap().name(), "GPSLatitudeRef");
+	}
 }
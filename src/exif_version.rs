@@ -0,0 +1,193 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Targeting a specific version of the EXIF standard when writing, for
+//! devices like printers and photo frames that reject data containing tags
+//! they don't know. See `WriteOptions::exif_version`.
+//!
+//! When writing for an older version, tags introduced by a later one are
+//! dropped, with the following exceptions:
+//! - Before 2.3, the `ISO` tag was the only way to store the sensitivity.
+//!   If it's missing, it gets set to the value of `ISOSpeed`,
+//!   `StandardOutputSensitivity` or `RecommendedExposureIndex` (in this
+//!   order), capped to 65535 as the tag only holds 16 bit values.
+//! - `ExifVersion` is set to the targeted version.
+
+use crate::exif_tag::ExifTag;
+
+/// The versions of the EXIF standard that can be targeted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum
+ExifVersion
+{
+	V220,
+	V221,
+	V230,
+	V231,
+	V232,
+	V300,
+}
+
+impl
+ExifVersion
+{
+	/// Gets the version as stored in the `ExifVersion` tag, e.g. `0232`
+	pub fn
+	as_bytes
+	(
+		&self
+	)
+	-> [u8; 4]
+	{
+		match self
+		{
+			ExifVersion::V220 => *b"0220",
+			ExifVersion::V221 => *b"0221",
+			ExifVersion::V230 => *b"0230",
+			ExifVersion::V231 => *b"0231",
+			ExifVersion::V232 => *b"0232",
+			ExifVersion::V300 => *b"0300",
+		}
+	}
+
+	/// Gets the version from the value of an `ExifVersion` tag. Returns
+	/// `None` for versions that can't be targeted.
+	pub fn
+	from_bytes
+	(
+		bytes: &[u8]
+	)
+	-> Option<ExifVersion>
+	{
+		[
+			ExifVersion::V220,
+			ExifVersion::V221,
+			ExifVersion::V230,
+			ExifVersion::V231,
+			ExifVersion::V232,
+			ExifVersion::V300,
+		].into_iter().find(|version| version.as_bytes() == bytes)
+	}
+}
+
+/// Gets the version of the standard that introduced the tag. Tags that were
+/// already part of 2.2 (or are not part of the standard at all) are treated
+/// as valid for every version.
+fn
+introduced_in
+(
+	tag: &ExifTag
+)
+-> ExifVersion
+{
+	match tag
+	{
+		ExifTag::Gamma(_)                       => ExifVersion::V221,
+
+		ExifTag::SensitivityType(_)             |
+		ExifTag::StandardOutputSensitivity(_)   |
+		ExifTag::RecommendedExposureIndex(_)    |
+		ExifTag::ISOSpeed(_)                    |
+		ExifTag::ISOSpeedLatitudeyyy(_)         |
+		ExifTag::ISOSpeedLatitudezzz(_)         |
+		ExifTag::OwnerName(_)                   |
+		ExifTag::SerialNumber(_)                |
+		ExifTag::LensInfo(_)                    |
+		ExifTag::LensMake(_)                    |
+		ExifTag::LensModel(_)                   |
+		ExifTag::LensSerialNumber(_)            => ExifVersion::V230,
+
+		ExifTag::OffsetTime(_)                  |
+		ExifTag::OffsetTimeOriginal(_)          |
+		ExifTag::OffsetTimeDigitized(_)         |
+		ExifTag::AmbientTemperature(_)          |
+		ExifTag::Humidity(_)                    |
+		ExifTag::Pressure(_)                    |
+		ExifTag::WaterDepth(_)                  |
+		ExifTag::Acceleration(_)                |
+		ExifTag::CameraElevationAngle(_)        => ExifVersion::V231,
+
+		ExifTag::CompositeImage(_)              |
+		ExifTag::CompositeImageCount(_)         |
+		ExifTag::CompositeImageExposureTimes(_) => ExifVersion::V232,
+
+		_                                       => ExifVersion::V220,
+	}
+}
+
+/// Converts the tags so that they are valid for the given version, see
+/// module documentation
+pub(crate) fn
+convert_tags_to_version
+(
+	tags:    Vec<ExifTag>,
+	version: ExifVersion
+)
+-> Vec<ExifTag>
+{
+	let mut converted = Vec::new();
+
+	if version < ExifVersion::V230 && !tags.iter().any(|tag| matches!(tag, ExifTag::ISO(_)))
+	{
+		let sensitivity = tags.iter().find_map(|tag| match tag
+		{
+			ExifTag::ISOSpeed(values) => values.first().copied(),
+			_                         => None,
+		}).or_else(|| tags.iter().find_map(|tag| match tag
+		{
+			ExifTag::StandardOutputSensitivity(values) => values.first().copied(),
+			_                                          => None,
+		})).or_else(|| tags.iter().find_map(|tag| match tag
+		{
+			ExifTag::RecommendedExposureIndex(values) => values.first().copied(),
+			_                                         => None,
+		}));
+
+		if let Some(sensitivity) = sensitivity
+		{
+			converted.push(ExifTag::ISO(vec![sensitivity.min(u16::MAX as u32) as u16]));
+		}
+	}
+
+	converted.extend(tags.into_iter().filter(|tag| !matches!(tag, ExifTag::ExifVersion(_)) && introduced_in(tag) <= version));
+	converted.push(ExifTag::ExifVersion(version.as_bytes().to_vec()));
+	return converted;
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::exif_tag::ExifTag;
+	use crate::exif_version::ExifVersion;
+	use crate::exif_version::convert_tags_to_version;
+
+	#[test]
+	fn
+	convert_to_older_versions()
+	{
+		let tags = vec![
+			ExifTag::ExifVersion(b"0232".to_vec()),
+			ExifTag::Artist("Jane Doe".to_string()),
+			ExifTag::StandardOutputSensitivity(vec![102400]),
+			ExifTag::OffsetTimeOriginal("+01:00".to_string()),
+			ExifTag::CompositeImage(vec![2]),
+		];
+
+		assert_eq!(convert_tags_to_version(tags.clone(), ExifVersion::V220), vec![
+			ExifTag::ISO(vec![65535]),
+			ExifTag::Artist("Jane Doe".to_string()),
+			ExifTag::ExifVersion(b"0220".to_vec()),
+		]);
+
+		assert_eq!(convert_tags_to_version(tags.clone(), ExifVersion::V231), vec![
+			ExifTag::Artist("Jane Doe".to_string()),
+			ExifTag::StandardOutputSensitivity(vec![102400]),
+			ExifTag::OffsetTimeOriginal("+01:00".to_string()),
+			ExifTag::ExifVersion(b"0231".to_vec()),
+		]);
+
+		assert_eq!(convert_tags_to_version(tags.clone(), ExifVersion::V300).len(), tags.len());
+		assert_eq!(ExifVersion::from_bytes(b"0221"), Some(ExifVersion::V221));
+		assert_eq!(ExifVersion::from_bytes(b"0210"), None);
+	}
+}
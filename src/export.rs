@@ -0,0 +1,313 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Exporting selected tags of a set of files as a table for analyzing them
+//! with other tools. Each file is a row, starting with a `path` column that
+//! is followed by a column for each selected tag. Tags that are not present
+//! in a file are left empty. Values are formatted as follows:
+//! - Strings as they are, without trailing NUL characters
+//! - Numbers separated by spaces, rationals as `numerator/denominator`
+//! - Undefined data as hex digits
+//!
+//! CSV is always available, Parquet requires the `parquet` feature.
+
+use std::io::Write;
+use std::path::Path;
+
+use crate::endian::*;
+use crate::exif_tag::ExifTag;
+use crate::exif_tag_format::ExifTagFormat;
+use crate::metadata::Metadata;
+
+/// Formats the value of a tag as text (see module documentation)
+pub fn
+format_value
+(
+	tag: &ExifTag
+)
+-> String
+{
+	let endian = Endian::Little;
+	let raw_data = tag.value_as_u8_vec(&endian);
+
+	let join = |bytes_per_component: usize, convert: &dyn Fn(&[u8]) -> String| -> String {
+		raw_data.chunks_exact(bytes_per_component)
+			.map(convert)
+			.collect::<Vec<String>>()
+			.join(" ")
+	};
+
+	match tag.format()
+	{
+		ExifTagFormat::STRING
+			=> String::from_u8_vec(&raw_data, &endian).trim_end_matches('\0').to_string(),
+		ExifTagFormat::UNDEF
+			=> raw_data.iter().map(|byte| format!("{:02x}", byte)).collect(),
+		ExifTagFormat::INT8U
+			=> join(1, &|bytes| bytes[0].to_string()),
+		ExifTagFormat::INT8S
+			=> join(1, &|bytes| (bytes[0] as i8).to_string()),
+		ExifTagFormat::INT16U
+			=> join(2, &|bytes| u16::from_bytes(bytes, &endian).to_string()),
+		ExifTagFormat::INT16S
+			=> join(2, &|bytes| i16::from_bytes(bytes, &endian).to_string()),
+		ExifTagFormat::INT32U
+			=> join(4, &|bytes| u32::from_bytes(bytes, &endian).to_string()),
+		ExifTagFormat::INT32S
+			=> join(4, &|bytes| i32::from_bytes(bytes, &endian).to_string()),
+		ExifTagFormat::RATIONAL64U
+			=> join(8, &|bytes| format!(
+				"{}/{}",
+				u32::from_bytes(&bytes[0..4], &endian),
+				u32::from_bytes(&bytes[4..8], &endian)
+			)),
+		ExifTagFormat::RATIONAL64S
+			=> join(8, &|bytes| format!(
+				"{}/{}",
+				i32::from_bytes(&bytes[0..4], &endian),
+				i32::from_bytes(&bytes[4..8], &endian)
+			)),
+		ExifTagFormat::FLOAT
+			=> join(4, &|bytes| f32::from_bytes(bytes, &endian).to_string()),
+		ExifTagFormat::DOUBLE
+			=> join(8, &|bytes| f64::from_bytes(bytes, &endian).to_string()),
+	}
+}
+
+/// Reads the files and builds the rows of the table, the first column of each
+/// row being the path of the file
+fn
+collect_rows
+(
+	paths: &[&Path],
+	tags:  &[ExifTag]
+)
+-> Result<Vec<Vec<Option<String>>>, std::io::Error>
+{
+	let mut rows = Vec::new();
+	for path in paths
+	{
+		let metadata = Metadata::new_from_path(path)?;
+
+		let mut row = vec![Some(path.to_string_lossy().to_string())];
+		for tag in tags
+		{
+			row.push(metadata.get_tag(tag).map(format_value));
+		}
+		rows.push(row);
+	}
+	return Ok(rows);
+}
+
+/// Quotes a CSV field if necessary, according to RFC 4180
+fn
+escape_csv_field
+(
+	field: &str
+)
+-> String
+{
+	if field.contains([',', '"', '\n', '\r'])
+	{
+		return format!("\"{}\"", field.replace('"', "\"\""));
+	}
+	return field.to_string();
+}
+
+/// Writes the given tags of the files as CSV, starting with a header line.
+/// The tags are selected using their type, i.e. the value of the given tags
+/// is ignored. Fails if any of the files can't be read.
+///
+/// # Examples
+/// ```no_run
+/// use little_exif::exif_tag::ExifTag;
+/// use little_exif::export::export_csv;
+///
+/// let mut csv = Vec::new();
+/// export_csv(
+///     &[std::path::Path::new("image.png"), std::path::Path::new("image.jpg")],
+///     &[ExifTag::Model(String::new()), ExifTag::ISO(vec![])],
+///     &mut csv
+/// ).unwrap();
+/// ```
+pub fn
+export_csv
+(
+	paths:  &[&Path],
+	tags:   &[ExifTag],
+	writer: &mut impl Write
+)
+-> Result<(), std::io::Error>
+{
+	let rows = collect_rows(paths, tags)?;
+
+	let mut header = vec![String::from("path")];
+	header.extend(tags.iter().map(|tag| tag.name()));
+	writeln!(writer, "{}", header.iter().map(|name| escape_csv_field(name)).collect::<Vec<String>>().join(","))?;
+
+	for row in rows
+	{
+		let fields = row.iter()
+			.map(|field| escape_csv_field(field.as_deref().unwrap_or("")))
+			.collect::<Vec<String>>();
+		writeln!(writer, "{}", fields.join(","))?;
+	}
+
+	return Ok(());
+}
+
+/// Writes the given tags of the files as Parquet file with one (optional)
+/// UTF8 column per tag. See `export_csv` for further details.
+#[cfg(feature = "parquet")]
+pub fn
+export_parquet
+(
+	paths:  &[&Path],
+	tags:   &[ExifTag],
+	writer: &mut (impl Write + Send)
+)
+-> Result<(), std::io::Error>
+{
+	use std::sync::Arc;
+
+	use parquet::basic::ConvertedType;
+	use parquet::basic::Repetition;
+	use parquet::basic::Type as PhysicalType;
+	use parquet::data_type::ByteArray;
+	use parquet::data_type::ByteArrayType;
+	use parquet::file::properties::WriterProperties;
+	use parquet::file::writer::SerializedFileWriter;
+	use parquet::schema::types::Type;
+
+	let to_io_error = |error: parquet::errors::ParquetError| std::io::Error::other(error.to_string());
+
+	let rows = collect_rows(paths, tags)?;
+
+	// Build the schema - the path is always present, the tags are optional
+	let mut column_names = vec![String::from("path")];
+	column_names.extend(tags.iter().map(|tag| tag.name()));
+
+	let mut fields = Vec::new();
+	for (index, column_name) in column_names.iter().enumerate()
+	{
+		let repetition = if index == 0 { Repetition::REQUIRED } else { Repetition::OPTIONAL };
+		let field = Type::primitive_type_builder(column_name, PhysicalType::BYTE_ARRAY)
+			.with_repetition(repetition)
+			.with_converted_type(ConvertedType::UTF8)
+			.build()
+			.map_err(to_io_error)?;
+		fields.push(Arc::new(field));
+	}
+	let schema = Type::group_type_builder("exif")
+		.with_fields(fields)
+		.build()
+		.map_err(to_io_error)?;
+
+	// Write all rows as one row group, column by column
+	let properties = Arc::new(WriterProperties::builder().build());
+	let mut file_writer = SerializedFileWriter::new(writer, Arc::new(schema), properties).map_err(to_io_error)?;
+	let mut row_group_writer = file_writer.next_row_group().map_err(to_io_error)?;
+
+	let mut column_index = 0;
+	while let Some(mut column_writer) = row_group_writer.next_column().map_err(to_io_error)?
+	{
+		let values = rows.iter()
+			.filter_map(|row| row[column_index].as_ref())
+			.map(|value| ByteArray::from(value.as_str()))
+			.collect::<Vec<ByteArray>>();
+		let definition_levels = rows.iter()
+			.map(|row| row[column_index].is_some() as i16)
+			.collect::<Vec<i16>>();
+
+		// The required path column has no definition levels
+		let definition_levels = if column_index == 0 { None } else { Some(definition_levels.as_slice()) };
+		column_writer.typed::<ByteArrayType>()
+			.write_batch(&values, definition_levels, None)
+			.map_err(to_io_error)?;
+		column_writer.close().map_err(to_io_error)?;
+
+		column_index += 1;
+	}
+
+	row_group_writer.close().map_err(to_io_error)?;
+	file_writer.close().map_err(to_io_error)?;
+
+	return Ok(());
+}
+
+#[cfg(test)]
+mod tests
+{
+	use std::path::Path;
+
+	use crate::exif_tag::ExifTag;
+	use crate::export::escape_csv_field;
+	use crate::export::format_value;
+	use crate::metadata::Metadata;
+
+	#[test]
+	fn
+	format_values()
+	{
+		assert_eq!(format_value(&ExifTag::Model("Cam\0".to_string())),                "Cam");
+		assert_eq!(format_value(&ExifTag::ISO(vec![100, 200])),                        "100 200");
+		assert_eq!(format_value(&ExifTag::ExposureTime(vec![1, 250])),                  "1/250");
+		assert_eq!(format_value(&ExifTag::ExposureCompensation(vec![-1, 3])),           "-1/3");
+		assert_eq!(format_value(&ExifTag::ExifVersion(vec![0x30, 0x32, 0x33, 0x32])),  "30323332");
+		assert_eq!(escape_csv_field("a \"b\", c"), "\"a \"\"b\"\", c\"");
+	}
+
+	#[test]
+	fn
+	csv()
+	-> Result<(), std::io::Error>
+	{
+		let path = Path::new("tests/tmp_export.png");
+		std::fs::copy("tests/png_parse_test_image.png", path)?;
+		let mut metadata = Metadata::new();
+		metadata.set_tag(ExifTag::Model("Cam, 1".to_string()));
+		metadata.set_tag(ExifTag::ExposureProgram(vec![2]));
+		metadata.write_to_file(path)?;
+
+		let mut csv = Vec::new();
+		let result = crate::export::export_csv(
+			&[path, Path::new("tests/png_parse_test_image.png")],
+			&[ExifTag::Model(String::new()), ExifTag::Make(String::new())],
+			&mut csv
+		);
+		std::fs::remove_file(path)?;
+		result?;
+
+		assert_eq!(
+			String::from_utf8(csv).unwrap(),
+			"path,Model,Make\ntests/tmp_export.png,\"Cam, 1\",\ntests/png_parse_test_image.png,,\n"
+		);
+		Ok(())
+	}
+	#[test]
+	#[cfg(feature = "parquet")]
+	fn
+	parquet()
+	-> Result<(), std::io::Error>
+	{
+		use parquet::file::reader::FileReader;
+		use parquet::file::reader::SerializedFileReader;
+
+		let path = Path::new("tests/tmp_export.parquet");
+		let mut file = std::fs::File::create(path)?;
+		crate::export::export_parquet(
+			&[Path::new("tests/png_parse_test_image.png")],
+			&[ExifTag::Model(String::new())],
+			&mut file
+		)?;
+
+		let reader = SerializedFileReader::new(std::fs::File::open(path)?).unwrap();
+		let rows = reader.get_row_iter(None).unwrap()
+			.map(|row| row.unwrap().to_string())
+			.collect::<Vec<String>>();
+		std::fs::remove_file(path)?;
+
+		assert_eq!(rows, vec!["{path: \"tests/png_parse_test_image.png\", Model: null}"]);
+		Ok(())
+	}
+}
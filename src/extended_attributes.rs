@@ -0,0 +1,505 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Mirroring key fields of the metadata into extended attributes of the file
+//! system, so that Finder/Spotlight and file managers can index them without
+//! parsing images. Only available with the `xattr` feature.
+//!
+//! The mirrored fields (see `MirroredFields`) are the capture date and time
+//! (`DateTimeOriginal` and `OffsetTimeOriginal`) and the star rating of the
+//! XMP packet (`xmp:Rating`, only for file types with XMP support). They are
+//! stored using the convention of the platform (see `XattrConvention`):
+//! - Freedesktop (Linux and other Unix systems): `user.dublincore.date` as
+//!   ISO 8601 date (e.g. `2024-05-06T07:08:09+02:00`) and
+//!   `user.baloo.rating` as number from 0 to 10 (half stars, as used by KDE)
+//! - Spotlight (macOS): `com.apple.metadata:kMDItemContentCreationDate` and
+//!   `com.apple.metadata:kMDItemStarRating`, each a binary property list
+//!   holding a date or an integer. The date is stored in UTC, so syncing it
+//!   back yields the UTC time with an offset of `+00:00`.
+//!
+//! `mirror_to_xattrs` writes the attributes, either called directly or after
+//! every write using `WriteOptions::mirror_xattrs`. In the other direction,
+//! `sync_back` writes fields that have been changed via the attributes (e.g.
+//! a rating set in a file manager) back into the metadata of the file.
+//!
+//! # Examples
+//! ```no_run
+//! use little_exif::extended_attributes;
+//!
+//! let path = std::path::Path::new("image.jpg");
+//! extended_attributes::mirror_to_xattrs(path).unwrap();
+//!
+//! // Later on, after the rating has been changed in a file manager
+//! if extended_attributes::sync_back(path).unwrap()
+//! {
+//!     println!("Updated the metadata of {}", path.display());
+//! }
+//! ```
+
+use std::path::Path;
+use std::time::Duration;
+use std::time::UNIX_EPOCH;
+
+use crate::bplist;
+use crate::bplist::PlistValue;
+use crate::date_inference;
+use crate::exif_tag::ExifTag;
+use crate::filetype::FileExtension;
+use crate::general_file_io::io_error;
+use crate::general_file_io::write_vec_to_file;
+use crate::metadata::Metadata;
+use crate::xmp;
+use crate::xmp::Namespace;
+
+const XMP_NAMESPACE: Namespace = ("xmp", "http://ns.adobe.com/xap/1.0/");
+
+/// Seconds between 1970-01-01 and 2001-01-01, the epoch of property list
+/// dates
+const PLIST_EPOCH_OFFSET: i64 = 978307200;
+
+/// The conventions for naming and encoding the extended attributes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum
+XattrConvention
+{
+	/// `user.dublincore.date` and `user.baloo.rating` as plain text
+	Freedesktop,
+
+	/// `com.apple.metadata:kMDItem*` attributes as binary property lists
+	Spotlight,
+}
+
+impl
+XattrConvention
+{
+	/// The convention of the platform little_exif has been compiled for
+	pub fn
+	native()
+	-> XattrConvention
+	{
+		match cfg!(target_os = "macos")
+		{
+			true  => XattrConvention::Spotlight,
+			false => XattrConvention::Freedesktop,
+		}
+	}
+
+	/// The names of the attributes for the date and the rating
+	fn
+	attribute_names
+	(
+		&self
+	)
+	-> (&'static str, &'static str)
+	{
+		match self
+		{
+			XattrConvention::Freedesktop => ("user.dublincore.date",                          "user.baloo.rating"),
+			XattrConvention::Spotlight   => ("com.apple.metadata:kMDItemContentCreationDate", "com.apple.metadata:kMDItemStarRating"),
+		}
+	}
+}
+
+/// The fields that get mirrored into extended attributes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct
+MirroredFields
+{
+	pub date_time_original:   Option<String>,                                  // EXIF date, e.g. "2024:05:06 07:08:09"
+	pub offset_time_original: Option<String>,                                  // e.g. "+02:00"
+	pub rating:               Option<u8>,                                      // 0 to 5 stars
+}
+
+impl
+MirroredFields
+{
+	/// Gets the fields from the given image data. Ratings outside of 0 to 5
+	/// (e.g. -1 for rejected images) are ignored.
+	/// - If the data is not a valid image of the given type, an error gets
+	///   returned.
+	pub fn
+	from_vec
+	(
+		file_buffer: &Vec<u8>,
+		file_type:   FileExtension
+	)
+	-> Result<MirroredFields, std::io::Error>
+	{
+		let metadata = Metadata::new_from_vec(file_buffer, file_type)?;
+		let rating = match file_type.capabilities().xmp
+		{
+			true  => xmp::read_xmp_from_vec(file_buffer)?
+				.and_then(|packet| xmp::get_property(&packet, XMP_NAMESPACE, "Rating"))
+				.and_then(|rating| rating.trim().parse::<u8>().ok())
+				.filter(|rating| *rating <= 5),
+			false => None,
+		};
+
+		return Ok(MirroredFields
+		{
+			date_time_original:   metadata.get_string_value(&ExifTag::DateTimeOriginal(String::new())).filter(|date| !date.is_empty()),
+			offset_time_original: metadata.get_string_value(&ExifTag::OffsetTimeOriginal(String::new())).filter(|offset| !offset.is_empty()),
+			rating,
+		});
+	}
+
+	/// Encodes the fields as values of the attributes for the date and the
+	/// rating. Dates that can't be parsed are not encoded.
+	fn
+	encode
+	(
+		&self,
+		convention: XattrConvention
+	)
+	-> (Option<Vec<u8>>, Option<Vec<u8>>)
+	{
+		let offset = self.offset_time_original.as_deref().and_then(parse_offset);
+		let date = self.date_time_original.as_deref()
+			.filter(|date| date_inference::parse_exif_date_millis(date, None).is_some());
+
+		match convention
+		{
+			XattrConvention::Freedesktop => {
+				let date = date.map(|date| {
+					let (day, time) = date.split_at(10);
+					let offset = self.offset_time_original.as_deref().filter(|_| offset.is_some()).unwrap_or("");
+					format!("{}T{}{}", day.replace(':', "-"), time.trim(), offset).into_bytes()
+				});
+				(date, self.rating.map(|rating| (rating * 2).to_string().into_bytes()))
+			},
+			XattrConvention::Spotlight   => {
+				let date = date
+					.and_then(|date| date_inference::parse_exif_date_millis(date, None))
+					.map(|millis| (millis / 1000 - offset.unwrap_or(0) - PLIST_EPOCH_OFFSET) as f64)
+					.map(|seconds| encode_plist_scalar(0x33, seconds.to_be_bytes()));
+				(date, self.rating.map(|rating| encode_plist_scalar(0x13, (rating as i64).to_be_bytes())))
+			},
+		}
+	}
+
+	/// Decodes the values of the attributes for the date and the rating.
+	/// Values that can't be decoded are ignored
+	fn
+	decode
+	(
+		convention: XattrConvention,
+		date:       Option<&[u8]>,
+		rating:     Option<&[u8]>
+	)
+	-> MirroredFields
+	{
+		let mut fields = MirroredFields::default();
+
+		match convention
+		{
+			XattrConvention::Freedesktop => {
+				let date = date.and_then(|date| std::str::from_utf8(date).ok()).map(str::trim);
+				if let Some(date) = date.filter(|date| date.len() >= 19 && date.is_char_boundary(19))
+				{
+					let (date_time, offset) = date.split_at(19);
+					let exif_date = date_time.replacen('-', ":", 2).replacen('T', " ", 1);
+					if date_inference::parse_exif_date_millis(&exif_date, None).is_some()
+					{
+						fields.offset_time_original = match offset
+						{
+							"Z"                                      => Some("+00:00".to_string()),
+							offset if parse_offset(offset).is_some() => Some(offset.to_string()),
+							_                                        => None,
+						};
+						fields.date_time_original = Some(exif_date);
+					}
+				}
+
+				fields.rating = rating
+					.and_then(|rating| std::str::from_utf8(rating).ok())
+					.and_then(|rating| rating.trim().parse::<u8>().ok())
+					.filter(|rating| *rating <= 10)
+					.map(|rating| rating.div_ceil(2));
+			},
+			XattrConvention::Spotlight   => {
+				if let Some(Ok(PlistValue::Date(seconds))) = date.map(bplist::decode)
+				{
+					let unix_seconds = seconds as i64 + PLIST_EPOCH_OFFSET;
+					if unix_seconds >= 0
+					{
+						fields.date_time_original = date_inference::format_system_time_utc(UNIX_EPOCH + Duration::from_secs(unix_seconds as u64));
+						fields.offset_time_original = Some("+00:00".to_string());
+					}
+				}
+
+				fields.rating = match rating.map(bplist::decode)
+				{
+					Some(Ok(PlistValue::Integer(rating))) if (0..=5).contains(&rating) => Some(rating as u8),
+					_                                                                  => None,
+				};
+			},
+		}
+
+		return fields;
+	}
+}
+
+/// Parses a time offset like `+02:00` into seconds
+fn
+parse_offset
+(
+	offset: &str
+)
+-> Option<i64>
+{
+	let offset = offset.trim_end_matches('\0');
+	let sign = match offset.get(0..1)?
+	{
+		"+" => 1,
+		"-" => -1,
+		_   => return None,
+	};
+	let (hours, minutes) = offset.get(1..)?.split_once(':')?;
+	if hours.len() != 2 || minutes.len() != 2
+	{
+		return None;
+	}
+	return Some(sign * (hours.parse::<i64>().ok()? * 3600 + minutes.parse::<i64>().ok()? * 60));
+}
+
+/// Encodes a single object with an 8 byte value (e.g. marker 0x13 for an
+/// integer, 0x33 for a date) as binary property list, which consists of the
+/// object, an offset table with one entry and the trailer
+fn
+encode_plist_scalar
+(
+	marker: u8,
+	value:  [u8; 8]
+)
+-> Vec<u8>
+{
+	let mut data = b"bplist00".to_vec();
+	data.push(marker);
+	data.extend(value);
+
+	let offset_table = data.len() as u64;
+	data.push(8);                                                               // Offset of the object
+	data.extend([0u8; 6]);
+	data.extend([1, 1]);                                                        // Offset and reference size
+	data.extend(1u64.to_be_bytes());                                            // Number of objects
+	data.extend(0u64.to_be_bytes());                                            // Top object
+	data.extend(offset_table.to_be_bytes());
+	return data;
+}
+
+/// Sets the attribute to the given value, or removes it if there is none
+fn
+set_attribute
+(
+	path:  &Path,
+	name:  &str,
+	value: Option<Vec<u8>>
+)
+-> Result<(), std::io::Error>
+{
+	match value
+	{
+		Some(value)                               => xattr::set(path, name, &value),
+		None if xattr::get(path, name)?.is_some() => xattr::remove(path, name),
+		None                                      => Ok(()),
+	}
+}
+
+/// Gets the type of the file at the given path from its extension
+fn
+file_type_of
+(
+	path: &Path
+)
+-> Result<FileExtension, std::io::Error>
+{
+	match FileExtension::from_path(path)
+	{
+		Some(file_type) => Ok(file_type),
+		None            => io_error!(Unsupported, format!("Can't mirror metadata - Unsupported file type of {}!", path.display())),
+	}
+}
+
+/// Writes the fields of the metadata of the image at the given path into
+/// its extended attributes, using the convention of the platform. Attributes
+/// of fields that are not present get removed. Returns the mirrored fields.
+/// - If the file system doesn't support extended attributes, an error of
+///   kind `Unsupported` gets returned.
+pub fn
+mirror_to_xattrs
+(
+	path: &Path
+)
+-> Result<MirroredFields, std::io::Error>
+{
+	let fields = MirroredFields::from_vec(&std::fs::read(path)?, file_type_of(path)?)?;
+	write_xattrs(path, &fields, XattrConvention::native())?;
+	return Ok(fields);
+}
+
+/// Writes the given fields into the extended attributes of the file at the
+/// given path using the given convention, see `mirror_to_xattrs`
+pub fn
+write_xattrs
+(
+	path:       &Path,
+	fields:     &MirroredFields,
+	convention: XattrConvention
+)
+-> Result<(), std::io::Error>
+{
+	let (date_name, rating_name) = convention.attribute_names();
+	let (date, rating) = fields.encode(convention);
+	set_attribute(path, date_name, date)?;
+	set_attribute(path, rating_name, rating)?;
+	return Ok(());
+}
+
+/// Reads the fields from the extended attributes of the file at the given
+/// path using the given convention. Attributes that are missing or can't be
+/// decoded result in fields of `None`.
+pub fn
+read_xattrs
+(
+	path:       &Path,
+	convention: XattrConvention
+)
+-> Result<MirroredFields, std::io::Error>
+{
+	let (date_name, rating_name) = convention.attribute_names();
+	let date = xattr::get(path, date_name)?;
+	let rating = xattr::get(path, rating_name)?;
+	return Ok(MirroredFields::decode(convention, date.as_deref(), rating.as_deref()));
+}
+
+/// Writes the fields stored in the extended attributes of the image at the
+/// given path (using the convention of the platform) back into its metadata,
+/// if they differ. Fields without an attribute are left as they are, and
+/// ratings are only written to file types with XMP support. Returns whether
+/// the file has been changed.
+pub fn
+sync_back
+(
+	path: &Path
+)
+-> Result<bool, std::io::Error>
+{
+	let file_type = file_type_of(path)?;
+	let mut file_buffer = std::fs::read(path)?;
+	let current = MirroredFields::from_vec(&file_buffer, file_type)?;
+	let mirrored = read_xattrs(path, XattrConvention::native())?;
+	let mut changed = false;
+
+	if mirrored.date_time_original.is_some() && (mirrored.date_time_original != current.date_time_original || (mirrored.offset_time_original.is_some() && mirrored.offset_time_original != current.offset_time_original))
+	{
+		let mut metadata = Metadata::new_from_vec(&file_buffer, file_type)?;
+		metadata.set_tag(ExifTag::DateTimeOriginal(mirrored.date_time_original.clone().unwrap()));
+		if let Some(offset) = &mirrored.offset_time_original
+		{
+			metadata.set_tag(ExifTag::OffsetTimeOriginal(offset.clone()));
+		}
+		metadata.write_to_vec(&mut file_buffer, file_type)?;
+		changed = true;
+	}
+
+	if mirrored.rating.is_some() && mirrored.rating != current.rating && file_type.capabilities().xmp
+	{
+		let packet = xmp::read_xmp_from_vec(&file_buffer)?;
+		let packet = xmp::set_properties(packet.as_deref(), XMP_NAMESPACE, &[("Rating", mirrored.rating.map(|rating| rating.to_string()))])?;
+		xmp::write_xmp_to_vec(&mut file_buffer, &packet)?;
+		changed = true;
+	}
+
+	if changed
+	{
+		write_vec_to_file(path, &file_buffer)?;
+	}
+	return Ok(changed);
+}
+
+#[cfg(test)]
+mod tests
+{
+	use std::path::Path;
+
+	use crate::bplist;
+	use crate::bplist::PlistValue;
+	use crate::extended_attributes::MirroredFields;
+	use crate::extended_attributes::XattrConvention;
+	use crate::extended_attributes::read_xattrs;
+	use crate::extended_attributes::sync_back;
+	use crate::extended_attributes::write_xattrs;
+	use crate::filetype::FileExtension;
+
+	fn
+	sample_fields()
+	-> MirroredFields
+	{
+		MirroredFields
+		{
+			date_time_original:   Some("2024:05:06 07:08:09".to_string()),
+			offset_time_original: Some("+02:00".to_string()),
+			rating:               Some(4),
+		}
+	}
+
+	#[test]
+	fn
+	freedesktop_encoding()
+	{
+		let (date, rating) = sample_fields().encode(XattrConvention::Freedesktop);
+		assert_eq!(date.as_deref(), Some(b"2024-05-06T07:08:09+02:00".as_slice()));
+		assert_eq!(rating.as_deref(), Some(b"8".as_slice()));
+		assert_eq!(MirroredFields::decode(XattrConvention::Freedesktop, date.as_deref(), rating.as_deref()), sample_fields());
+
+		// Half stars are rounded up, UTC is written as Z by some tools
+		let decoded = MirroredFields::decode(XattrConvention::Freedesktop, Some(b"2024-05-06T07:08:09Z"), Some(b"7"));
+		assert_eq!(decoded.offset_time_original.as_deref(), Some("+00:00"));
+		assert_eq!(decoded.rating, Some(4));
+		assert_eq!(MirroredFields::decode(XattrConvention::Freedesktop, Some(b"yesterday"), Some(b"11")), MirroredFields::default());
+	}
+
+	#[test]
+	fn
+	spotlight_encoding()
+	{
+		// 07:08:09 at +02:00 is 05:08:09 UTC
+		let (date, rating) = sample_fields().encode(XattrConvention::Spotlight);
+		assert_eq!(bplist::decode(date.as_deref().unwrap()).unwrap(), PlistValue::Date(736664889.0));
+		assert_eq!(bplist::decode(rating.as_deref().unwrap()).unwrap(), PlistValue::Integer(4));
+
+		let decoded = MirroredFields::decode(XattrConvention::Spotlight, date.as_deref(), rating.as_deref());
+		assert_eq!(decoded.date_time_original.as_deref(), Some("2024:05:06 05:08:09"));
+		assert_eq!(decoded.offset_time_original.as_deref(), Some("+00:00"));
+		assert_eq!(decoded.rating, Some(4));
+	}
+
+	#[test]
+	fn
+	mirror_and_sync_back()
+	-> Result<(), std::io::Error>
+	{
+		let path = Path::new("tests/sample2_xattr_copy.jpg");
+		std::fs::copy("tests/sample2.jpg", path)?;
+
+		// Skip file systems without support for extended attributes
+		let fields = sample_fields();
+		if let Err(error) = write_xattrs(path, &fields, XattrConvention::native())
+		{
+			std::fs::remove_file(path)?;
+			assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+			return Ok(());
+		}
+		let read_back = read_xattrs(path, XattrConvention::native());
+		let changed = sync_back(path);
+		let synced = MirroredFields::from_vec(&std::fs::read(path)?, FileExtension::JPEG);
+		let changed_again = sync_back(path);
+		std::fs::remove_file(path)?;
+
+		assert_eq!(read_back?.rating, Some(4));
+		assert!(changed?);
+		assert_eq!(synced?.rating, Some(4));
+		assert!(!changed_again?);
+		Ok(())
+	}
+}
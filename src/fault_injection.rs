@@ -0,0 +1,304 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Injecting faults into the IO of little_exif for testing its error
+//! handling: Short reads and writes, interruptions (`EINTR`) and failures
+//! after a certain number of bytes, e.g. a disk running full in the middle
+//! of rewriting a file.
+
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+
+use crate::general_file_io::SyncWrite;
+
+/// Wraps a reader and/or writer, injecting the configured faults
+pub(crate) struct
+FaultyIo<T>
+{
+	inner:           T,
+	max_chunk:       usize,                                                     // Maximum number of bytes per call
+	interrupt_every: usize,                                                     // Every n-th call gets interrupted, 0 for never
+	fail_after:      Option<(u64, ErrorKind)>,                                  // Number of bytes after which all calls fail
+	transferred:     u64,
+	calls:           usize,
+}
+
+impl<T>
+FaultyIo<T>
+{
+	pub(crate) fn
+	new
+	(
+		inner: T
+	)
+	-> FaultyIo<T>
+	{
+		FaultyIo { inner, max_chunk: usize::MAX, interrupt_every: 0, fail_after: None, transferred: 0, calls: 0 }
+	}
+
+	/// Transfers at most the given number of bytes per call
+	pub(crate) fn
+	short
+	(
+		mut self,
+		max_chunk: usize
+	)
+	-> FaultyIo<T>
+	{
+		self.max_chunk = max_chunk.max(1);
+		self
+	}
+
+	/// Interrupts every n-th call, like a signal arriving during a syscall
+	pub(crate) fn
+	interrupt_every
+	(
+		mut self,
+		interrupt_every: usize
+	)
+	-> FaultyIo<T>
+	{
+		self.interrupt_every = interrupt_every;
+		self
+	}
+
+	/// Fails with the given error kind once the given number of bytes has
+	/// been transferred
+	pub(crate) fn
+	fail_after
+	(
+		mut self,
+		byte_count: u64,
+		kind:       ErrorKind
+	)
+	-> FaultyIo<T>
+	{
+		self.fail_after = Some((byte_count, kind));
+		self
+	}
+
+	/// Decides whether the next call gets a fault injected, otherwise returns
+	/// the number of bytes it may transfer
+	fn
+	next_call
+	(
+		&mut self,
+		requested: usize
+	)
+	-> Result<usize, std::io::Error>
+	{
+		self.calls += 1;
+		if self.interrupt_every != 0 && self.calls.is_multiple_of(self.interrupt_every)
+		{
+			return Err(std::io::Error::new(ErrorKind::Interrupted, "Injected interruption"));
+		}
+
+		let mut allowed = requested.min(self.max_chunk);
+		if let Some((byte_count, kind)) = self.fail_after
+		{
+			if self.transferred >= byte_count && requested > 0
+			{
+				return Err(std::io::Error::new(kind, "Injected failure"));
+			}
+			allowed = allowed.min((byte_count - self.transferred) as usize);
+		}
+		return Ok(allowed);
+	}
+}
+
+impl<T: Read>
+Read
+for
+FaultyIo<T>
+{
+	fn
+	read
+	(
+		&mut self,
+		buffer: &mut [u8]
+	)
+	-> Result<usize, std::io::Error>
+	{
+		let allowed = self.next_call(buffer.len())?;
+		let read = self.inner.read(&mut buffer[..allowed])?;
+		self.transferred += read as u64;
+		return Ok(read);
+	}
+}
+
+impl<T: Seek>
+Seek
+for
+FaultyIo<T>
+{
+	fn
+	seek
+	(
+		&mut self,
+		position: SeekFrom
+	)
+	-> Result<u64, std::io::Error>
+	{
+		self.inner.seek(position)
+	}
+}
+
+impl<T: Write>
+Write
+for
+FaultyIo<T>
+{
+	fn
+	write
+	(
+		&mut self,
+		buffer: &[u8]
+	)
+	-> Result<usize, std::io::Error>
+	{
+		let allowed = self.next_call(buffer.len())?;
+		let written = self.inner.write(&buffer[..allowed])?;
+		self.transferred += written as u64;
+		return Ok(written);
+	}
+
+	fn
+	flush
+	(
+		&mut self
+	)
+	-> Result<(), std::io::Error>
+	{
+		self.inner.flush()
+	}
+}
+
+impl<T: SyncWrite>
+SyncWrite
+for
+FaultyIo<T>
+{
+	fn
+	sync_all
+	(
+		&self
+	)
+	-> Result<(), std::io::Error>
+	{
+		self.inner.sync_all()
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use std::io::Cursor;
+	use std::io::ErrorKind;
+	use std::path::Path;
+
+	use crate::errors::InsufficientSpace;
+	use crate::fault_injection::FaultyIo;
+	use crate::filetype::FileExtension;
+	use crate::general_file_io::safe_write_vec_to_file_through;
+	use crate::vfs::OsFileSystem;
+	use crate::write_options::TempFileLocation;
+	use crate::metadata::Metadata;
+	use crate::read_options::ReadOptions;
+
+	const SAMPLES: [&str; 4] = [
+		"tests/sample2.jpg",
+		"tests/sample2_extended.webp",
+		"tests/read_sample.webp",
+		"tests/png_parse_test_image.png",
+	];
+
+	fn
+	read_sample
+	(
+		path: &str
+	)
+	-> (Vec<u8>, FileExtension, Vec<u8>)
+	{
+		let file_buffer = std::fs::read(path).unwrap();
+		let file_type = FileExtension::from_signature(&file_buffer).unwrap();
+		let encoded = Metadata::new_from_vec(&file_buffer, file_type).unwrap().encode();
+		return (file_buffer, file_type, encoded);
+	}
+
+	#[test]
+	fn
+	short_reads_and_interruptions()
+	{
+		for path in SAMPLES
+		{
+			let (file_buffer, file_type, expected) = read_sample(path);
+			for (max_chunk, interrupt_every) in [(1, 0), (3, 2), (usize::MAX, 2)]
+			{
+				let mut reader = FaultyIo::new(Cursor::new(&file_buffer)).short(max_chunk).interrupt_every(interrupt_every);
+				let metadata = Metadata::new_from_seekable_reader(&mut reader, file_type, &ReadOptions::new()).unwrap();
+				assert_eq!(metadata.encode(), expected, "{} {} {}", path, max_chunk, interrupt_every);
+			}
+		}
+	}
+
+	#[test]
+	fn
+	read_failures_are_reported()
+	{
+		for path in SAMPLES
+		{
+			let (file_buffer, file_type, expected) = read_sample(path);
+			for byte_count in (0..file_buffer.len() as u64).step_by(file_buffer.len() / 97 + 1)
+			{
+				let mut reader = FaultyIo::new(Cursor::new(&file_buffer)).fail_after(byte_count, ErrorKind::ConnectionReset);
+
+				// Either the failure happened after the metadata has been read
+				// or it gets reported - but never as missing metadata
+				match Metadata::new_from_seekable_reader(&mut reader, file_type, &ReadOptions::new())
+				{
+					Ok(metadata) => assert_eq!(metadata.encode(), expected, "{} {}", path, byte_count),
+					Err(error)   => assert_eq!(error.kind(), ErrorKind::ConnectionReset, "{} {}: {}", path, byte_count, error),
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn
+	safe_write_failures_keep_file()
+	-> Result<(), std::io::Error>
+	{
+		let path = Path::new("tests/sample2_fault_injection_copy.jpg");
+		std::fs::copy("tests/sample2.jpg", path)?;
+		let original = std::fs::read(path)?;
+		let new_data = [&original[..], &[0u8; 1000][..]].concat();
+
+		let leftovers = || std::fs::read_dir("tests").unwrap()
+			.filter_map(|entry| entry.ok())
+			.filter(|entry| entry.file_name().to_string_lossy().starts_with(".sample2_fault_injection_copy.jpg.little_exif-"))
+			.count();
+
+		for kind in [ErrorKind::StorageFull, ErrorKind::BrokenPipe]
+		{
+			for byte_count in [0, 1, 4096, new_data.len() as u64 - 1]
+			{
+				let error = safe_write_vec_to_file_through(&OsFileSystem, path, &new_data, &TempFileLocation::SameDirectory, |file| FaultyIo::new(file).short(1000).fail_after(byte_count, kind)).err().unwrap();
+				assert_eq!(error.kind(), kind);
+				assert_eq!(error.get_ref().is_some_and(|inner| inner.is::<InsufficientSpace>()), kind == ErrorKind::StorageFull);
+				assert_eq!(std::fs::read(path)?, original);
+				assert_eq!(leftovers(), 0);
+			}
+		}
+
+		// Short writes and interruptions are retried
+		safe_write_vec_to_file_through(&OsFileSystem, path, &new_data, &TempFileLocation::SameDirectory, |file| FaultyIo::new(file).short(777).interrupt_every(3))?;
+		assert_eq!(std::fs::read(path)?, new_data);
+		assert_eq!(leftovers(), 0);
+
+		std::fs::remove_file(path)?;
+		Ok(())
+	}
+}
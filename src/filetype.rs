@@ -3,14 +3,30 @@
 
 use std::str::FromStr;
 
-#[derive(Debug, PartialEq)]
+/// File types that are recognized by their extensions, but can't hold
+/// embedded EXIF data, see `errors::NoEmbeddedMetadata`
+const WITHOUT_EMBEDDED_METADATA: [(&str, &[&str]); 1] = [
+	("BMP", &["bmp", "dib"]),
+];
+
+#[derive(Debug, PartialEq, Clone, Copy)]
 #[allow(non_snake_case)]
 pub enum
 FileExtension
 {
 	PNG  {as_zTXt_chunk: bool},
 	JPEG,
-	WEBP
+	WEBP,
+	TIFF,
+	HEIF,
+	AVIF,
+	GIF,
+	CR2,
+	CR3,
+	ORF,
+	RW2,
+	RAF,
+	AVI
 }
 
 impl 
@@ -33,7 +49,243 @@ FileExtension
 			"jpeg"  => Ok(FileExtension::JPEG),
 			"png"   => Ok(FileExtension::PNG{ as_zTXt_chunk: true}),
 			"webp"  => Ok(FileExtension::WEBP),
+			"tif"   => Ok(FileExtension::TIFF),
+			"tiff"  => Ok(FileExtension::TIFF),
+			"heic"  => Ok(FileExtension::HEIF),
+			"heif"  => Ok(FileExtension::HEIF),
+			"hif"   => Ok(FileExtension::HEIF),
+			"avif"  => Ok(FileExtension::AVIF),
+			"gif"   => Ok(FileExtension::GIF),
+			"cr2"   => Ok(FileExtension::CR2),
+			"cr3"   => Ok(FileExtension::CR3),
+			"orf"   => Ok(FileExtension::ORF),
+			"rw2"   => Ok(FileExtension::RW2),
+			"raf"   => Ok(FileExtension::RAF),
+			"avi"   => Ok(FileExtension::AVI),
 			_       => Err(()),
 		}
 	}
 }
+
+impl
+FileExtension
+{
+	/// Determines the file type based on the extension of the given path,
+	/// ignoring its case. Returns `None` for unsupported or missing extensions
+	pub(crate) fn
+	from_path
+	(
+		path: &std::path::Path
+	)
+	-> Option<FileExtension>
+	{
+		let extension = path.extension()?.to_str()?;
+		return FileExtension::from_str(&extension.to_lowercase()).ok();
+	}
+
+	/// Checks if the extension of the given path, ignoring its case, belongs
+	/// to a file type that can't hold embedded EXIF data. Returns the name of
+	/// the type (e.g. "BMP") if so.
+	pub(crate) fn
+	without_embedded_metadata
+	(
+		path: &std::path::Path
+	)
+	-> Option<&'static str>
+	{
+		let extension = path.extension()?.to_str()?.to_lowercase();
+		return WITHOUT_EMBEDDED_METADATA.iter()
+			.find(|(_, extensions)| extensions.contains(&extension.as_str()))
+			.map(|(name, _)| *name);
+	}
+
+	/// Determines the file type based on the signature at the start of the
+	/// given data. Returns `None` if it doesn't match any supported type
+	pub(crate) fn
+	from_signature
+	(
+		file_buffer: &[u8]
+	)
+	-> Option<FileExtension>
+	{
+		if file_buffer.starts_with(&[0xff, 0xd8])
+		{
+			return Some(FileExtension::JPEG);
+		}
+		if file_buffer.starts_with(&[0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a])
+		{
+			return Some(FileExtension::PNG { as_zTXt_chunk: true });
+		}
+		if file_buffer.starts_with(b"RIFF") && file_buffer.get(8..12) == Some(b"WEBP")
+		{
+			return Some(FileExtension::WEBP);
+		}
+		if crate::avi::is_avi(file_buffer)
+		{
+			return Some(FileExtension::AVI);
+		}
+		if crate::cr2::is_cr2(file_buffer)
+		{
+			return Some(FileExtension::CR2);
+		}
+		if crate::orf::is_orf(file_buffer)
+		{
+			return Some(FileExtension::ORF);
+		}
+		if crate::rw2::is_rw2(file_buffer)
+		{
+			return Some(FileExtension::RW2);
+		}
+		if crate::raf::is_raf(file_buffer)
+		{
+			return Some(FileExtension::RAF);
+		}
+		if file_buffer.starts_with(b"II*\0") || file_buffer.starts_with(b"MM\0*")
+		{
+			return Some(FileExtension::TIFF);
+		}
+		if crate::gif::GIF_SIGNATURES.iter().any(|signature| file_buffer.starts_with(signature))
+		{
+			return Some(FileExtension::GIF);
+		}
+		if crate::heif::has_brand(file_buffer, &crate::cr3::CR3_BRANDS)
+		{
+			return Some(FileExtension::CR3);
+		}
+		if crate::heif::has_brand(file_buffer, &crate::heif::AVIF_BRANDS)
+		{
+			return Some(FileExtension::AVIF);
+		}
+		if crate::heif::has_brand(file_buffer, &crate::heif::HEIF_BRANDS)
+		{
+			return Some(FileExtension::HEIF);
+		}
+		return None;
+	}
+
+	/// Gets the name of the file type, e.g. for messages and tracing
+	pub(crate) fn
+	name
+	(
+		&self
+	)
+	-> &'static str
+	{
+		match self
+		{
+			FileExtension::JPEG       => "JPEG",
+			FileExtension::PNG { .. } => "PNG",
+			FileExtension::WEBP       => "WebP",
+			FileExtension::TIFF       => "TIFF",
+			FileExtension::HEIF       => "HEIF",
+			FileExtension::AVIF       => "AVIF",
+			FileExtension::GIF        => "GIF",
+			FileExtension::CR2        => "CR2",
+			FileExtension::CR3        => "CR3",
+			FileExtension::ORF        => "ORF",
+			FileExtension::RW2        => "RW2",
+			FileExtension::RAF        => "RAF",
+			FileExtension::AVI        => "AVI",
+		}
+	}
+}
+
+/// What little_exif is able to do with files of a certain type, see
+/// `capabilities`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct
+FormatCapabilities
+{
+	pub file_type:  FileExtension,
+	pub extensions: &'static [&'static str],                                    // Lowercase file extensions of the type
+	pub read:       bool,                                                       // Reading EXIF data
+	pub write:      bool,                                                       // Writing EXIF data
+	pub clear:      bool,                                                       // Removing EXIF data
+	pub xmp:        bool,                                                       // Reading and writing XMP packets, see `xmp`
+	pub iptc:       bool,                                                       // Reading and writing IPTC location fields, see `geocoding`
+	pub icc:        bool,                                                       // Reading and writing ICC profiles
+	pub thumbnails: bool,                                                       // Reading and writing embedded thumbnails
+}
+
+impl
+FileExtension
+{
+	/// Gets the capabilities of little_exif for this file type
+	pub fn
+	capabilities
+	(
+		&self
+	)
+	-> FormatCapabilities
+	{
+		let extensions: &'static [&'static str] = match self
+		{
+			FileExtension::JPEG    => &["jpg", "jpeg"],
+			FileExtension::PNG{..} => &["png"],
+			FileExtension::WEBP    => &["webp"],
+			FileExtension::TIFF    => &["tif", "tiff"],
+			FileExtension::HEIF    => &["heic", "heif", "hif"],
+			FileExtension::AVIF    => &["avif"],
+			FileExtension::GIF     => &["gif"],
+			FileExtension::CR2     => &["cr2"],
+			FileExtension::CR3     => &["cr3"],
+			FileExtension::ORF     => &["orf"],
+			FileExtension::RW2     => &["rw2"],
+			FileExtension::RAF     => &["raf"],
+			FileExtension::AVI     => &["avi"],
+		};
+
+		// Currently, all supported types can be read, and all but the raw
+		// formats written and cleared, but only with regard to the EXIF data
+		// (and the IPTC location fields of JPEG files and XMP packets of
+		// JPEG, PNG and GIF files)
+		let raw = matches!(self, FileExtension::CR2 | FileExtension::CR3 | FileExtension::ORF | FileExtension::RW2 | FileExtension::RAF);
+		FormatCapabilities
+		{
+			file_type:  *self,
+			extensions,
+			read:       true,
+			write:      !raw,
+			clear:      !raw,
+			xmp:        matches!(self, FileExtension::JPEG | FileExtension::PNG{..} | FileExtension::GIF),
+			iptc:       *self == FileExtension::JPEG,
+			icc:        false,
+			thumbnails: false,
+		}
+	}
+}
+
+/// Gets the capabilities of little_exif for all supported file types, e.g.
+/// for enabling or disabling actions in a GUI depending on the file type.
+///
+/// # Examples
+/// ```
+/// use little_exif::filetype::capabilities;
+///
+/// let writable = capabilities()
+///     .iter()
+///     .filter(|capabilities| capabilities.write)
+///     .flat_map(|capabilities| capabilities.extensions.to_vec())
+///     .collect::<Vec<_>>();
+/// assert!(writable.contains(&"webp"));
+/// ```
+pub fn
+capabilities()
+-> Vec<FormatCapabilities>
+{
+	[
+		FileExtension::JPEG,
+		FileExtension::PNG { as_zTXt_chunk: true },
+		FileExtension::WEBP,
+		FileExtension::TIFF,
+		FileExtension::HEIF,
+		FileExtension::AVIF,
+		FileExtension::GIF,
+		FileExtension::CR2,
+		FileExtension::CR3,
+		FileExtension::ORF,
+		FileExtension::RW2,
+		FileExtension::RAF,
+		FileExtension::AVI,
+	].iter().map(|file_type| file_type.capabilities()).collect()
+}
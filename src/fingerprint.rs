@@ -0,0 +1,219 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+use crate::date_inference::parse_exif_date_millis;
+use crate::endian::*;
+use crate::exif_tag::ExifTag;
+use crate::metadata::Metadata;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME:        u64 = 0x00000100000001b3;
+
+/// 64 bit FNV-1a hash, which - in contrast to the hashers of the standard
+/// library - is guaranteed to stay the same across versions and platforms
+fn
+fnv1a
+(
+	data: &[u8]
+)
+-> u64
+{
+	data.iter().fold(FNV_OFFSET_BASIS, |hash, byte| (hash ^ *byte as u64).wrapping_mul(FNV_PRIME))
+}
+
+/// Parses a time zone offset like `+02:00` into minutes
+pub(crate) fn
+parse_offset_minutes
+(
+	offset: &str
+)
+-> Option<i64>
+{
+	let (sign, hours_and_minutes) = match offset.chars().next()?
+	{
+		'+' => (1, &offset[1..]),
+		'-' => (-1, &offset[1..]),
+		_   => return None,
+	};
+	let (hours, minutes) = hours_and_minutes.split_once(':')?;
+	return Some(sign * (hours.parse::<i64>().ok()? * 60 + minutes.parse::<i64>().ok()?));
+}
+
+/// Gets the first value of the stored numeric tag
+fn
+get_number
+(
+	metadata: &Metadata,
+	tag:      &ExifTag
+)
+-> Option<u32>
+{
+	match metadata.get_tag(tag)?
+	{
+		ExifTag::ImageWidth(values)      |
+		ExifTag::ImageHeight(values)     |
+		ExifTag::ExifImageWidth(values)  |
+		ExifTag::ExifImageHeight(values) => values.first().copied(),
+		_                                => None,
+	}
+}
+
+/// The capture time in milliseconds since 1970-01-01, in UTC if the time
+/// zone offset is known
+fn
+get_normalized_capture_time
+(
+	metadata: &Metadata
+)
+-> Option<i64>
+{
+	let sources = [
+		(ExifTag::DateTimeOriginal(String::new()), ExifTag::SubSecTimeOriginal(String::new()),  ExifTag::OffsetTimeOriginal(String::new())),
+		(ExifTag::CreateDate(String::new()),       ExifTag::SubSecTimeDigitized(String::new()), ExifTag::OffsetTimeDigitized(String::new())),
+	];
+
+	for (date_tag, sub_seconds_tag, offset_tag) in sources
+	{
+		let date = match metadata.get_string_value(&date_tag)
+		{
+			Some(date) => date,
+			None       => continue,
+		};
+		let sub_seconds = metadata.get_string_value(&sub_seconds_tag);
+
+		if let Some(timestamp) = parse_exif_date_millis(&date, sub_seconds.as_deref())
+		{
+			let offset_minutes = metadata.get_string_value(&offset_tag)
+				.and_then(|offset| parse_offset_minutes(&offset))
+				.unwrap_or(0);
+			return Some(timestamp - offset_minutes * 60 * 1000);
+		}
+	}
+
+	return None;
+}
+
+/// Computes the fingerprint, see `Metadata::fingerprint`
+pub(crate) fn
+compute_fingerprint
+(
+	metadata: &Metadata
+)
+-> Option<u64>
+{
+	let capture_time = get_normalized_capture_time(metadata)?;
+
+	// Prefer the dimensions of the ExifIFD, as the ones in IFD0 may be left
+	// over from a thumbnail or the original encoding
+	let width  = get_number(metadata, &ExifTag::ExifImageWidth(Vec::new()))
+		.or_else(|| get_number(metadata, &ExifTag::ImageWidth(Vec::new())))
+		.unwrap_or(0);
+	let height = get_number(metadata, &ExifTag::ExifImageHeight(Vec::new()))
+		.or_else(|| get_number(metadata, &ExifTag::ImageHeight(Vec::new())))
+		.unwrap_or(0);
+
+	// Rotating the image while re-encoding it swaps the dimensions
+	let (long_side, short_side) = (width.max(height), width.min(height));
+
+	let mut data = Vec::new();
+	Endian::Little.write(&mut data, capture_time);
+	Endian::Little.write(&mut data, long_side);
+	Endian::Little.write(&mut data, short_side);
+	for tag in [
+		ExifTag::Make(String::new()),
+		ExifTag::Model(String::new()),
+		ExifTag::SerialNumber(String::new()),
+	]
+	{
+		// Terminate every value so that e.g. "ab" + "c" differs from "a" + "bc"
+		data.extend(metadata.get_string_value(&tag).unwrap_or_default().to_lowercase().into_bytes());
+		data.push(0x00);
+	}
+
+	// Tells apart the shots of a burst taken within the same (sub)second
+	if let Some(shutter_count) = metadata.shutter_count()
+	{
+		Endian::Little.write(&mut data, shutter_count);
+	}
+
+	return Some(fnv1a(&data));
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::exif_tag::ExifTag;
+	use crate::fingerprint::fnv1a;
+	use crate::metadata::Metadata;
+
+	fn
+	get_test_metadata()
+	-> Metadata
+	{
+		let mut metadata = Metadata::new();
+		metadata.set_tag(ExifTag::Make("Testcam".to_string()));
+		metadata.set_tag(ExifTag::SerialNumber("0815".to_string()));
+		metadata.set_tag(ExifTag::DateTimeOriginal("2024:01:31 18:05:00".to_string()));
+		metadata.set_tag(ExifTag::OffsetTimeOriginal("+01:00".to_string()));
+		metadata.set_tag(ExifTag::ExifImageWidth(vec![6000]));
+		metadata.set_tag(ExifTag::ExifImageHeight(vec![4000]));
+		metadata
+	}
+
+	#[test]
+	fn
+	fnv1a_reference_values()
+	{
+		assert_eq!(fnv1a(b""),       0xcbf29ce484222325);
+		assert_eq!(fnv1a(b"foobar"), 0x85944171f73967e8);
+	}
+
+	#[test]
+	fn
+	fingerprints()
+	{
+		let original = get_test_metadata();
+		let fingerprint = original.fingerprint().unwrap();
+
+		// Same shot after re-encoding: Padded make, rotated, other software
+		// and the same capture time given in another time zone
+		let mut re_encoded = get_test_metadata();
+		re_encoded.set_tag(ExifTag::Make("TESTCAM   ".to_string()));
+		re_encoded.set_tag(ExifTag::ExifImageWidth(vec![4000]));
+		re_encoded.set_tag(ExifTag::ExifImageHeight(vec![6000]));
+		re_encoded.set_tag(ExifTag::Software("Converter".to_string()));
+		re_encoded.set_tag(ExifTag::DateTimeOriginal("2024:01:31 19:05:00".to_string()));
+		re_encoded.set_tag(ExifTag::OffsetTimeOriginal("+02:00".to_string()));
+		assert_eq!(re_encoded.fingerprint(), Some(fingerprint));
+
+		// Another shot or camera
+		let mut other_shot = get_test_metadata();
+		other_shot.set_tag(ExifTag::SubSecTimeOriginal("5".to_string()));
+		assert_ne!(other_shot.fingerprint(), Some(fingerprint));
+
+		let mut other_camera = get_test_metadata();
+		other_camera.set_tag(ExifTag::SerialNumber("0816".to_string()));
+		assert_ne!(other_camera.fingerprint(), Some(fingerprint));
+
+		// Shots of a burst, only told apart by the shutter counts in the
+		// (Nikon) maker notes
+		let nikon_maker_note = |shutter_count: u32| {
+			let mut raw_data = b"Nikon\0\x02\x10\0\0MM\0\x2a\0\0\0\x08".to_vec();
+			raw_data.extend([0x00, 0x01, 0x00, 0xa7, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01]);
+			raw_data.extend(shutter_count.to_be_bytes());
+			raw_data.extend([0x00; 4]);
+			raw_data
+		};
+		let mut first_shot = get_test_metadata();
+		first_shot.set_tag(ExifTag::Make("NIKON CORPORATION".to_string()));
+		first_shot.set_tag(ExifTag::MakerNote(nikon_maker_note(12345)));
+		let mut second_shot = get_test_metadata();
+		second_shot.set_tag(ExifTag::Make("NIKON CORPORATION".to_string()));
+		second_shot.set_tag(ExifTag::MakerNote(nikon_maker_note(12346)));
+		assert_eq!(first_shot.shutter_count(), Some(12345));
+		assert_ne!(first_shot.fingerprint(), second_shot.fingerprint());
+
+		// No capture time, no fingerprint
+		assert_eq!(Metadata::new().fingerprint(), None);
+	}
+}
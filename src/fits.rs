@@ -0,0 +1,294 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Mapping the header keywords of FITS files (the common format of
+//! astronomical data) to EXIF tags and back, e.g. when converting the data
+//! to PNG for publication. Requires the `fits` feature.
+//!
+//! | FITS keyword | EXIF tags                                                        |
+//! |--------------|------------------------------------------------------------------|
+//! | `DATE-OBS`   | `DateTimeOriginal`, `SubSecTimeOriginal`, `OffsetTimeOriginal`   |
+//! | `EXPTIME`    | `ExposureTime`                                                   |
+//! | `INSTRUME`   | `Model`                                                          |
+//! | `TELESCOP`   | `LensModel`                                                      |
+//! | `OBSERVER`   | `Artist`                                                         |
+//!
+//! `DATE-OBS` is expected to be in UTC, so the offset is set to `+00:00`.
+//!
+//! All other keywords - except for the ones describing the structure of the
+//! FITS data, like `NAXIS` - are kept as acquisition keywords in
+//! `UserComment` (see the `astro` module), which can also be written to XMP
+//! using `astro::write_keywords_to_vec`.
+//!
+//! # Examples
+//! ```no_run
+//! use little_exif::fits::fits_to_metadata;
+//! use little_exif::fits::parse_fits_header;
+//! use little_exif::metadata::Metadata;
+//!
+//! let keywords = parse_fits_header(&std::fs::read("m31.fits").unwrap()).unwrap();
+//! let mut metadata = Metadata::new();
+//! fits_to_metadata(&keywords, &mut metadata).unwrap();
+//! metadata.write_to_file(std::path::Path::new("m31.png")).unwrap();
+//! ```
+
+use std::time::Duration;
+use std::time::UNIX_EPOCH;
+
+use crate::astro::AcquisitionKeywords;
+use crate::astro::KeywordValue;
+use crate::astro::EXPOSURE_TIME;
+use crate::date_inference::format_system_time_utc;
+use crate::date_inference::parse_exif_date_millis;
+use crate::exif_tag::ExifTag;
+use crate::fingerprint::parse_offset_minutes;
+use crate::general_file_io::io_error;
+use crate::metadata::Metadata;
+
+const CARD_LENGTH: usize = 80;
+
+const DATE_OBS:   &str = "DATE-OBS";
+const INSTRUMENT: &str = "INSTRUME";
+const TELESCOPE:  &str = "TELESCOP";
+const OBSERVER:   &str = "OBSERVER";
+
+/// Keywords describing the structure of the FITS data, which don't apply to
+/// the converted image
+const STRUCTURAL_KEYWORDS: [&str; 9] = ["SIMPLE", "BITPIX", "NAXIS", "EXTEND", "BZERO", "BSCALE", "PCOUNT", "GCOUNT", "XTENSION"];
+
+/// Checks whether the keyword describes the structure of the FITS data,
+/// including the numbered ones like `NAXIS1`
+fn
+is_structural
+(
+	name: &str
+)
+-> bool
+{
+	STRUCTURAL_KEYWORDS.iter().any(|keyword| name.strip_prefix(keyword).is_some_and(|rest| rest.chars().all(|character| character.is_ascii_digit())))
+}
+
+/// Reads the keywords of the primary header of the FITS file, i.e. the
+/// 80 character cards up to the `END` card.
+/// - If the data doesn't start with a FITS header or it lacks the `END` card,
+///   an error of kind `InvalidData` gets returned.
+pub fn
+parse_fits_header
+(
+	file_buffer: &[u8]
+)
+-> Result<AcquisitionKeywords, std::io::Error>
+{
+	if !file_buffer.starts_with(b"SIMPLE  =")
+	{
+		return io_error!(InvalidData, "Not a FITS file!");
+	}
+
+	let mut cards = Vec::new();
+	for card in file_buffer.chunks_exact(CARD_LENGTH)
+	{
+		if card.starts_with(b"END ")
+		{
+			return Ok(AcquisitionKeywords::from_cards(&cards.join("\n")).unwrap_or_default());
+		}
+		cards.push(String::from_utf8_lossy(card).into_owned());
+	}
+
+	return io_error!(InvalidData, "FITS header lacks the END card!");
+}
+
+/// Converts a `DATE-OBS` value (e.g. `2024-01-31T18:05:00.25`) into an EXIF
+/// date string and the fractional seconds
+fn
+parse_date_obs
+(
+	value: &str
+)
+-> Option<(String, String)>
+{
+	let (date, time) = value.split_once('T').unwrap_or((value, "00:00:00"));
+	let (time, sub_seconds) = time.split_once('.').unwrap_or((time, ""));
+
+	let exif_date = format!("{} {}", date.replace('-', ":"), time);
+	parse_exif_date_millis(&exif_date, Some(sub_seconds))?;
+	if !sub_seconds.chars().all(|digit| digit.is_ascii_digit())
+	{
+		return None;
+	}
+	return Some((exif_date, sub_seconds.to_string()));
+}
+
+/// Converts an exposure time in seconds into a rational, preferring
+/// fractions of one second like `1/250`
+fn
+exposure_time_to_rational
+(
+	seconds: f64
+)
+-> Option<Vec<u32>>
+{
+	if !(seconds > 0.0 && seconds < u32::MAX as f64)
+	{
+		return None;
+	}
+	let inverse = (1.0 / seconds).round();
+	if seconds < 1.0 && (1.0 / inverse - seconds).abs() < 1e-9
+	{
+		return Some(vec![1, inverse as u32]);
+	}
+
+	// Otherwise with a precision of 1 ms, reduced by the greatest common
+	// divisor
+	let milliseconds = (seconds * 1000.0).round() as u64;
+	let (mut divisor, mut remainder) = (milliseconds, 1000);
+	while remainder != 0
+	{
+		(divisor, remainder) = (remainder, divisor % remainder);
+	}
+	let divisor = divisor.max(1);
+	return Some(vec![u32::try_from(milliseconds / divisor).ok()?, (1000 / divisor) as u32]);
+}
+
+/// Sets the EXIF tags corresponding to the FITS keywords and stores the
+/// remaining ones in `UserComment` (see module documentation). Keywords
+/// that are not present don't change the tags.
+/// - If a mapped keyword has an unexpected value (e.g. a `DATE-OBS` that is
+///   not a date) or a keyword can't be stored, an error of kind
+///   `InvalidData` or `InvalidInput` gets returned and the metadata is not
+///   changed.
+pub fn
+fits_to_metadata
+(
+	keywords: &AcquisitionKeywords,
+	metadata: &mut Metadata
+)
+-> Result<(), std::io::Error>
+{
+	let mut tags = Vec::new();
+	let mut remaining = AcquisitionKeywords::new();
+
+	for (name, value) in keywords.keywords()
+	{
+		match (name.as_str(), value)
+		{
+			(DATE_OBS, KeywordValue::Text(date)) => match parse_date_obs(date)
+			{
+				Some((date, sub_seconds)) => {
+					tags.push(ExifTag::DateTimeOriginal(date));
+					tags.push(ExifTag::SubSecTimeOriginal(sub_seconds));
+					tags.push(ExifTag::OffsetTimeOriginal(String::from("+00:00")));
+				},
+				None                      => return io_error!(InvalidData, format!("Invalid DATE-OBS '{}'!", date)),
+			},
+			(EXPOSURE_TIME, value) if value.as_f64().is_some() => match value.as_f64().and_then(exposure_time_to_rational)
+			{
+				Some(rational) => tags.push(ExifTag::ExposureTime(rational)),
+				None           => return io_error!(InvalidData, format!("Invalid EXPTIME {:?}!", value)),
+			},
+			(INSTRUMENT, KeywordValue::Text(text)) => tags.push(ExifTag::Model(text.clone())),
+			(TELESCOPE,  KeywordValue::Text(text)) => tags.push(ExifTag::LensModel(text.clone())),
+			(OBSERVER,   KeywordValue::Text(text)) => tags.push(ExifTag::Artist(text.clone())),
+			(DATE_OBS | EXPOSURE_TIME | INSTRUMENT | TELESCOPE | OBSERVER, _) => {
+				return io_error!(InvalidData, format!("Unexpected type of FITS keyword {}!", name));
+			},
+			(name, _) if is_structural(name) => (),
+			(name, value) => remaining = remaining.keyword(name, value.clone()),
+		}
+	}
+
+	if !remaining.keywords().is_empty()
+	{
+		metadata.set_acquisition_keywords(&remaining)?;
+	}
+	for tag in tags
+	{
+		metadata.set_tag(tag);
+	}
+	return Ok(());
+}
+
+/// Gets the FITS keywords corresponding to the EXIF tags, together with the
+/// acquisition keywords stored in `UserComment` (see module documentation).
+/// The capture time is converted to UTC if its offset is known, otherwise it
+/// is used as is. Text that can't be stored in FITS headers (e.g. because of
+/// non-ASCII characters) is skipped.
+pub fn
+metadata_to_fits
+(
+	metadata: &Metadata
+)
+-> AcquisitionKeywords
+{
+	let mut keywords = metadata.acquisition_keywords().unwrap_or_default();
+
+	let capture_time = metadata.get_string_value(&ExifTag::DateTimeOriginal(String::new()))
+		.and_then(|date| parse_exif_date_millis(&date, metadata.get_string_value(&ExifTag::SubSecTimeOriginal(String::new())).as_deref()))
+		.map(|milliseconds| milliseconds - metadata.get_string_value(&ExifTag::OffsetTimeOriginal(String::new()))
+			.and_then(|offset| parse_offset_minutes(&offset))
+			.unwrap_or(0) * 60_000
+		);
+	if let Some(milliseconds) = capture_time.and_then(|milliseconds| u64::try_from(milliseconds).ok())
+	{
+		let date = format_system_time_utc(UNIX_EPOCH + Duration::from_millis(milliseconds))
+			.map(|date| date.replacen(':', "-", 2).replacen(' ', "T", 1));
+		if let Some(mut date) = date
+		{
+			if milliseconds % 1000 != 0
+			{
+				date.push_str(&format!(".{:03}", milliseconds % 1000));
+			}
+			keywords = keywords.keyword(DATE_OBS, KeywordValue::Text(date));
+		}
+	}
+
+	if let Some(ExifTag::ExposureTime(rational)) = metadata.get_tag(&ExifTag::ExposureTime(Vec::new()))
+	{
+		if let [numerator, denominator] = rational[..]
+		{
+			if denominator != 0
+			{
+				keywords = keywords.keyword(EXPOSURE_TIME, KeywordValue::Float(numerator as f64 / denominator as f64));
+			}
+		}
+	}
+
+	for (name, tag) in [
+		(INSTRUMENT, ExifTag::Model(String::new())),
+		(TELESCOPE,  ExifTag::LensModel(String::new())),
+		(OBSERVER,   ExifTag::Artist(String::new())),
+	]
+	{
+		let text = metadata.get_string_value(&tag)
+			.map(|text| text.trim_end_matches('\0').to_string())
+			.filter(|text| !text.is_empty() && text.chars().all(|character| (' '..='~').contains(&character)));
+		if let Some(text) = text
+		{
+			keywords = keywords.keyword(name, KeywordValue::Text(text));
+		}
+	}
+
+	return keywords;
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::fits::*;
+
+	#[test]
+	fn
+	convert_values()
+	{
+		assert_eq!(parse_date_obs("2024-01-31T18:05:00.25"), Some(("2024:01:31 18:05:00".to_string(), "25".to_string())));
+		assert_eq!(parse_date_obs("2024-01-31"),             Some(("2024:01:31 00:00:00".to_string(), String::new())));
+		assert_eq!(parse_date_obs("31/01/24"),               None);
+
+		assert_eq!(exposure_time_to_rational(0.004), Some(vec![1, 250]));
+		assert_eq!(exposure_time_to_rational(300.0), Some(vec![300, 1]));
+		assert_eq!(exposure_time_to_rational(2.5),   Some(vec![5, 2]));
+		assert_eq!(exposure_time_to_rational(0.0),   None);
+
+		assert!(is_structural("NAXIS2") && is_structural("BITPIX"));
+		assert!(!is_structural("NAXISX") && !is_structural("GAIN"));
+	}
+}
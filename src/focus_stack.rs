@@ -0,0 +1,342 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Grouping the source frames of focus stacks, i.e. series of shots taken
+//! using the focus bracketing of the camera, and marking them with a common
+//! ID so that stacking software can pick up the groups without the frames
+//! having to be sorted by hand.
+//!
+//! Frames are detected using the bracketing stored in the maker notes (see
+//! `MakerNote::bracket`), which is only available for Olympus/OM System
+//! cameras so far. Frames taken by the same camera belong to the same stack
+//! as long as their shot numbers increase by one in chronological order.
+//!
+//! The groups are written to the XMP packet (see the `xmp` module, so only
+//! JPEG, PNG and GIF files are supported) using these properties of the namespace
+//! `https://github.com/TechnikTobi/little_exif/ns/stack/1.0/` (prefix
+//! `stack`):
+//! - `StackID`: The ID shared by all frames of the stack
+//! - `StackIndex`: The position of the frame within the stack, starting at 1
+//! - `StackCount`: The number of frames of the stack
+//!
+//! # Examples
+//! ```no_run
+//! use std::path::Path;
+//! use little_exif::focus_stack::stamp_focus_stacks;
+//!
+//! let paths = [Path::new("P1010001.jpg"), Path::new("P1010002.jpg")];
+//! for (path, membership) in paths.iter().zip(stamp_focus_stacks(&paths).unwrap())
+//! {
+//!     if let Some(membership) = membership
+//!     {
+//!         println!("{}: {}/{} of {}", path.display(), membership.index, membership.count, membership.id);
+//!     }
+//! }
+//! ```
+
+use std::path::Path;
+
+use crate::burst::camera_identity;
+use crate::burst::capture_time_millis;
+use crate::general_file_io::io_error;
+use crate::general_file_io::write_vec_to_file;
+use crate::maker_note::BracketKind;
+use crate::metadata::Metadata;
+use crate::xmp;
+use crate::xmp::Namespace;
+
+const STACK_NAMESPACE: Namespace = ("stack", "https://github.com/TechnikTobi/little_exif/ns/stack/1.0/");
+
+/// The ID of a stack and the index of a frame within it, see `group_frames`
+type Membership = (usize, u32);
+
+/// The membership of a frame in a focus stack
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct
+StackMembership
+{
+	pub id:    String,
+	pub index: u32,                                                             // Starting at 1
+	pub count: u32,
+}
+
+/// The information about a frame that is relevant for grouping it
+#[derive(Debug, Clone, PartialEq)]
+struct
+Frame
+{
+	camera:      Vec<String>,
+	timestamp:   i64,                                                           // In milliseconds
+	shot_number: u32,
+}
+
+impl
+Frame
+{
+	/// Gets the information from the metadata. Returns `None` if the image
+	/// was not taken using focus bracketing or it is unknown when.
+	fn
+	from_metadata
+	(
+		metadata: &Metadata
+	)
+	-> Option<Frame>
+	{
+		let bracket = metadata.maker_note()?.bracket()?;
+		if !bracket.kinds.contains(&BracketKind::Focus)
+		{
+			return None;
+		}
+
+		return Some(Frame
+		{
+			camera:      camera_identity(metadata),
+			timestamp:   capture_time_millis(metadata)?,
+			shot_number: bracket.shot_number,
+		});
+	}
+}
+
+/// Assigns each frame a stack ID and its index within the stack, see
+/// `group_focus_stacks`
+fn
+group_frames
+(
+	frames: &[Option<Frame>]
+)
+-> Vec<Option<Membership>>
+{
+	let mut order = (0..frames.len())
+		.filter(|index| frames[*index].is_some())
+		.collect::<Vec<usize>>();
+	order.sort_by_key(|index| (frames[*index].as_ref().map(|frame| frame.timestamp), *index));
+
+	let mut memberships = vec![None; frames.len()];
+	let mut stack_count = 0;
+
+	// The last frame of each camera's current stack, the stack's ID and size
+	let mut open_stacks: Vec<(&Frame, usize, u32)> = Vec::new();
+
+	for index in order
+	{
+		let frame = match &frames[index]
+		{
+			Some(frame) => frame,
+			None        => continue,
+		};
+
+		match open_stacks.iter_mut().find(|(last_frame, _, _)| last_frame.camera == frame.camera)
+		{
+			Some(open_stack) if open_stack.0.shot_number.checked_add(1) == Some(frame.shot_number) => {
+				open_stack.0 = frame;
+				open_stack.2 += 1;
+				memberships[index] = Some((open_stack.1, open_stack.2));
+			},
+			Some(open_stack) => {
+				*open_stack = (frame, stack_count, 1);
+				memberships[index] = Some((stack_count, 1));
+				stack_count += 1;
+			},
+			None => {
+				open_stacks.push((frame, stack_count, 1));
+				memberships[index] = Some((stack_count, 1));
+				stack_count += 1;
+			},
+		}
+	}
+
+	return memberships;
+}
+
+/// Reads the frames at the given paths and groups them into focus stacks
+fn
+read_and_group
+(
+	paths: &[&Path]
+)
+-> Result<(Vec<Metadata>, Vec<Option<Membership>>), std::io::Error>
+{
+	let mut metadata = Vec::new();
+	for path in paths
+	{
+		metadata.push(Metadata::new_from_path(path)?);
+	}
+	let frames = metadata.iter().map(Frame::from_metadata).collect::<Vec<Option<Frame>>>();
+	let memberships = group_frames(&frames);
+	return Ok((metadata, memberships));
+}
+
+/// Groups the images at the given paths into focus stacks (see module
+/// documentation). Returns a stack ID for each path, or `None` if the image
+/// was not taken using focus bracketing. IDs are assigned in chronological
+/// order of the stacks, starting with 0.
+/// - If one of the files can't be read, an error gets returned.
+pub fn
+group_focus_stacks
+(
+	paths: &[&Path]
+)
+-> Result<Vec<Option<usize>>, std::io::Error>
+{
+	let (_, memberships) = read_and_group(paths)?;
+	return Ok(memberships.into_iter().map(|membership| membership.map(|(stack, _)| stack)).collect());
+}
+
+/// Writes the membership of the image in a focus stack to its XMP packet,
+/// replacing previous values
+pub fn
+write_stack_to_vec
+(
+	file_buffer: &mut Vec<u8>,
+	membership:  &StackMembership
+)
+-> Result<(), std::io::Error>
+{
+	if membership.id.is_empty() || membership.index == 0 || membership.index > membership.count
+	{
+		return io_error!(InvalidInput, "Invalid focus stack membership!");
+	}
+
+	let packet = xmp::read_xmp_from_vec(file_buffer)?;
+	let packet = xmp::set_properties(packet.as_deref(), STACK_NAMESPACE, &[
+		("StackID",    Some(membership.id.clone())),
+		("StackIndex", Some(membership.index.to_string())),
+		("StackCount", Some(membership.count.to_string())),
+	])?;
+	return xmp::write_xmp_to_vec(file_buffer, &packet);
+}
+
+/// Reads the membership of the image in a focus stack from its XMP packet.
+/// Returns `None` if it is not marked as part of one.
+pub fn
+read_stack_from_vec
+(
+	file_buffer: &[u8]
+)
+-> Result<Option<StackMembership>, std::io::Error>
+{
+	let packet = match xmp::read_xmp_from_vec(file_buffer)?
+	{
+		Some(packet) => packet,
+		None         => return Ok(None),
+	};
+
+	let get = |name: &str| xmp::get_property(&packet, STACK_NAMESPACE, name);
+	let membership = (|| Some(StackMembership
+	{
+		id:    get("StackID")?,
+		index: get("StackIndex")?.parse().ok()?,
+		count: get("StackCount")?.parse().ok()?,
+	}))();
+	return Ok(membership);
+}
+
+/// Groups the images at the given paths into focus stacks and writes the
+/// membership to the frames (see module documentation). The ID of a stack is
+/// derived from the fingerprint of its first frame (see
+/// `Metadata::fingerprint`), so stamping the same frames again results in
+/// the same IDs. Returns the membership for each path, images that are not
+/// part of a stack are not changed.
+/// - If one of the files can't be read, an error gets returned before any
+///   file is changed.
+/// - If writing one of the files fails (e.g. because it is not a JPEG file),
+///   the error gets returned and the frames before it remain stamped.
+pub fn
+stamp_focus_stacks
+(
+	paths: &[&Path]
+)
+-> Result<Vec<Option<StackMembership>>, std::io::Error>
+{
+	let (metadata, memberships) = read_and_group(paths)?;
+
+	// The first frame and the size of each stack
+	let mut stacks: Vec<(usize, u32)> = Vec::new();
+	for (index, (stack, position)) in memberships.iter().enumerate().filter_map(|(index, membership)| Some((index, (*membership)?)))
+	{
+		if stacks.len() <= stack
+		{
+			stacks.resize(stack + 1, (index, 0));
+		}
+		if position == 1
+		{
+			stacks[stack].0 = index;
+		}
+		stacks[stack].1 = stacks[stack].1.max(position);
+	}
+
+	let ids = stacks.iter()
+		.map(|(first_frame, _)| {
+			let fingerprint = metadata[*first_frame].fingerprint()
+				.or_else(|| capture_time_millis(&metadata[*first_frame]).map(|timestamp| timestamp as u64))
+				.unwrap_or_default();
+			format!("{:016x}", fingerprint)
+		})
+		.collect::<Vec<String>>();
+
+	let mut results = Vec::new();
+	for (path, membership) in paths.iter().zip(memberships)
+	{
+		let membership = membership.map(|(stack, position)| StackMembership
+		{
+			id:    ids[stack].clone(),
+			index: position,
+			count: stacks[stack].1,
+		});
+
+		if let Some(membership) = &membership
+		{
+			let mut file_buffer = std::fs::read(path)?;
+			write_stack_to_vec(&mut file_buffer, membership)?;
+			write_vec_to_file(path, &file_buffer)?;
+		}
+		results.push(membership);
+	}
+
+	return Ok(results);
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::focus_stack::Frame;
+	use crate::focus_stack::group_frames;
+
+	fn
+	frame
+	(
+		model:       &str,
+		timestamp:   i64,
+		shot_number: u32
+	)
+	-> Option<Frame>
+	{
+		Some(Frame { camera: vec!["OM".to_string(), model.to_string(), String::new()], timestamp, shot_number })
+	}
+
+	#[test]
+	fn
+	group_frames_into_stacks()
+	{
+		let frames = [
+			frame("A", 1_000, 1),
+			None,
+			frame("A", 1_200, 2),
+			frame("B", 1_100, 1),                                               // Other camera, interleaved
+			frame("A", 1_400, 3),
+			frame("A", 9_000, 1),                                               // Next stack
+			frame("B", 1_300, 3),                                               // Missing frame
+		];
+
+		assert_eq!(group_frames(&frames), vec![
+			Some((0, 1)),
+			None,
+			Some((0, 2)),
+			Some((1, 1)),
+			Some((0, 3)),
+			Some((3, 1)),
+			Some((2, 1)),
+		]);
+		assert_eq!(group_frames(&[]), Vec::new());
+	}
+}
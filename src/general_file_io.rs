@@ -1,8 +1,14 @@
 // Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
 // See https://github.com/TechnikTobi/little_exif#license for licensing details
 
+use crate::errors::InsufficientSpace;
+use crate::errors::ReadOnlyTarget;
+use crate::errors::SharingViolation;
+use crate::vfs::FileSystem;
+use crate::vfs::OsFileSystem;
+use crate::write_options::TempFileLocation;
+
 pub(crate) const NEWLINE:                u8      = 0x0a;
-pub(crate) const SPACE:                  u8      = 0x20;
 pub(crate) const EXIF_HEADER:            [u8; 6] = [0x45, 0x78, 0x69, 0x66, 0x00, 0x00];
 
 macro_rules! perform_file_action {
@@ -30,5 +36,814 @@ macro_rules! io_error {
 	};
 }
 
+/// Enters a tracing span (requires the `tracing` feature) until the returned
+/// guard gets dropped, e.g. `let span = span!("parse", format = "PNG"; chunks);`
+/// The fields after the semicolon are recorded later on using `record!`.
+/// Without the feature, none of the field values get evaluated.
+#[cfg(feature = "tracing")]
+macro_rules! span {
+	($name:literal $(, $field:ident = $value:expr)* $(; $($empty_field:ident),*)?)
+	=>
+	{
+		tracing::info_span!(
+			$name
+			$(, $field = $value)*
+			$($(, $empty_field = tracing::field::Empty)*)?
+		).entered()
+	};
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! span {
+	($name:literal $(, $field:ident = $value:expr)* $(; $($empty_field:ident),*)?)
+	=>
+	{
+		crate::general_file_io::NoSpan
+	};
+}
+
+/// Records the value of a field of a span entered using `span!`
+#[cfg(feature = "tracing")]
+macro_rules! record {
+	($span:expr, $field:ident, $value:expr)
+	=>
+	{
+		$span.record(stringify!($field), $value);
+	};
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! record {
+	($span:expr, $field:ident, $value:expr)
+	=>
+	{
+		let _ = &$span;
+	};
+}
+
+/// Stands in for a span if the `tracing` feature is not enabled
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct NoSpan;
+
 pub(crate) use perform_file_action;
-pub(crate) use io_error;
\ No newline at end of file
+pub(crate) use io_error;
+pub(crate) use span;
+pub(crate) use record;
+
+/// Reads in the remaining content of the given file, e.g. for modifying it
+/// in memory before writing it back
+pub(crate) fn
+read_file_to_vec
+(
+	mut file: impl std::io::Read
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	let mut file_buffer = Vec::new();
+	perform_file_action!(std::io::Read::read_to_end(&mut file, &mut file_buffer));
+	return Ok(file_buffer);
+}
+
+/// Reads in the whole content of the given stream, which doesn't have to be
+/// seekable (e.g. `stdin`). Returns an error of kind `FileTooLarge` if the
+/// stream contains more than `max_size` bytes
+pub(crate) fn
+read_stream_to_vec
+(
+	reader:   impl std::io::Read,
+	max_size: u64
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	// Read one byte more than allowed for detecting streams that are too large
+	let mut file_buffer = Vec::new();
+	std::io::Read::read_to_end(&mut reader.take(max_size.saturating_add(1)), &mut file_buffer)?;
+	if file_buffer.len() as u64 > max_size
+	{
+		return io_error!(FileTooLarge, format!("Can't read stream - It exceeds the limit of {} bytes!", max_size));
+	}
+	return Ok(file_buffer);
+}
+
+/// Fills the buffer like `Read::read_exact`, but tells running out of data
+/// apart from failures of the reader: If the data ends too early, `Ok(false)`
+/// gets returned so that the caller can report this in terms of the file
+/// format. Other errors (e.g. of a failing disk) are returned as they are
+/// instead of being mistaken for truncated data
+pub(crate) fn
+try_read_exact
+(
+	reader: &mut impl std::io::Read,
+	buffer: &mut [u8]
+)
+-> Result<bool, std::io::Error>
+{
+	match reader.read_exact(buffer)
+	{
+		Ok(())                                                           => Ok(true),
+		Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+		Err(error)                                                       => Err(error),
+	}
+}
+
+/// Wraps a reader and remembers whether it failed, so that errors of the
+/// reader itself (e.g. of the disk or network) can be told apart from
+/// problems with the data read from it. Interruptions don't count as
+/// failures, as they are retried.
+pub(crate) struct
+TrackedReader<'a, T>
+{
+	inner:  &'a mut T,
+	failed: bool,
+}
+
+impl<'a, T>
+TrackedReader<'a, T>
+{
+	pub(crate) fn
+	new
+	(
+		inner: &'a mut T
+	)
+	-> TrackedReader<'a, T>
+	{
+		TrackedReader { inner, failed: false }
+	}
+
+	/// Whether the wrapped reader returned an error
+	pub(crate) fn
+	failed
+	(
+		&self
+	)
+	-> bool
+	{
+		self.failed
+	}
+
+	fn
+	track
+	<R>
+	(
+		&mut self,
+		result: Result<R, std::io::Error>
+	)
+	-> Result<R, std::io::Error>
+	{
+		if result.as_ref().is_err_and(|error| error.kind() != std::io::ErrorKind::Interrupted)
+		{
+			self.failed = true;
+		}
+		return result;
+	}
+}
+
+impl<T: std::io::Read>
+std::io::Read
+for
+TrackedReader<'_, T>
+{
+	fn
+	read
+	(
+		&mut self,
+		buffer: &mut [u8]
+	)
+	-> Result<usize, std::io::Error>
+	{
+		let result = self.inner.read(buffer);
+		self.track(result)
+	}
+}
+
+impl<T: std::io::Seek>
+std::io::Seek
+for
+TrackedReader<'_, T>
+{
+	fn
+	seek
+	(
+		&mut self,
+		position: std::io::SeekFrom
+	)
+	-> Result<u64, std::io::Error>
+	{
+		let result = self.inner.seek(position);
+		self.track(result)
+	}
+}
+
+/// A writer whose data can be flushed to the storage device, like a file.
+/// Writing files goes through this so that tests can inject faults
+pub(crate) trait
+SyncWrite
+: std::io::Write
+{
+	fn
+	sync_all
+	(
+		&self
+	)
+	-> Result<(), std::io::Error>;
+}
+
+impl
+SyncWrite
+for
+std::fs::File
+{
+	fn
+	sync_all
+	(
+		&self
+	)
+	-> Result<(), std::io::Error>
+	{
+		std::fs::File::sync_all(self)
+	}
+}
+
+/// Checks if the error was caused by trying to write to a read-only file
+fn
+is_read_only_error
+(
+	error: &std::io::Error
+)
+-> bool
+{
+	matches!(error.kind(), std::io::ErrorKind::PermissionDenied | std::io::ErrorKind::ReadOnlyFilesystem)
+}
+
+/// The delays between the attempts to access a file that is in use by
+/// another process, adding up to one and a half seconds
+const SHARING_VIOLATION_DELAYS: [u64; 5] = [50, 100, 200, 400, 800];            // Milliseconds
+
+/// Checks if the error was caused by another process using the file in a
+/// way that doesn't allow the access, which only happens on Windows
+fn
+is_sharing_violation
+(
+	error: &std::io::Error
+)
+-> bool
+{
+	// ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION
+	cfg!(windows) && matches!(error.raw_os_error(), Some(32) | Some(33))
+}
+
+/// Performs the access to the file at the given path, retrying it after the
+/// given delays as long as it fails with an error matching `is_transient`.
+/// If it fails after the last retry as well, an error containing a
+/// `SharingViolation` gets returned
+fn
+retry_with_delays
+<R>
+(
+	path:         &std::path::Path,
+	delays:       &[std::time::Duration],
+	is_transient: impl Fn(&std::io::Error) -> bool,
+	mut access:   impl FnMut() -> Result<R, std::io::Error>
+)
+-> Result<R, std::io::Error>
+{
+	let mut attempts = 0;
+	loop
+	{
+		match access()
+		{
+			Err(error) if is_transient(&error) => match delays.get(attempts)
+			{
+				Some(delay) => std::thread::sleep(*delay),
+				None        => return Err(std::io::Error::new(std::io::ErrorKind::ResourceBusy, SharingViolation::new(path, attempts as u32 + 1))),
+			},
+			result => return result,
+		}
+		attempts += 1;
+	}
+}
+
+/// Performs the access to the file at the given path, retrying it with
+/// increasing delays while the file is in use by another process (e.g. a
+/// viewer on Windows). Returns an error containing a `SharingViolation` if
+/// the file stays in use
+pub(crate) fn
+retry_if_shared
+<R>
+(
+	path:   &std::path::Path,
+	access: impl FnMut() -> Result<R, std::io::Error>
+)
+-> Result<R, std::io::Error>
+{
+	let delays = SHARING_VIOLATION_DELAYS.map(std::time::Duration::from_millis);
+	retry_with_delays(path, &delays, is_sharing_violation, access)
+}
+
+/// Checks up front whether the file at the given path can be written to,
+/// either due to its permissions or the file system it is stored on.
+/// Returns an error containing a `ReadOnlyTarget` if it can't, or one
+/// containing a `SharingViolation` if it is in use by another process
+pub(crate) fn
+check_writable
+(
+	path: &std::path::Path
+)
+-> Result<(), std::io::Error>
+{
+	if std::fs::metadata(path)?.permissions().readonly()
+	{
+		return Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, ReadOnlyTarget::new(path)));
+	}
+
+	// Opening without truncating doesn't modify the file
+	match retry_if_shared(path, || std::fs::OpenOptions::new().write(true).open(path))
+	{
+		Ok(_)                                    => Ok(()),
+		Err(error) if is_read_only_error(&error) => Err(std::io::Error::new(error.kind(), ReadOnlyTarget::new(path))),
+		Err(error)                               => Err(error),
+	}
+}
+
+/// Writes the given data to the file at the given path, replacing its
+/// content. Errors due to the file being read-only contain a `ReadOnlyTarget`,
+/// while the file being in use by another process is handled as described
+/// for `retry_if_shared`
+pub(crate) fn
+write_vec_to_file
+(
+	path:        &std::path::Path,
+	file_buffer: &[u8]
+)
+-> Result<(), std::io::Error>
+{
+	match retry_if_shared(path, || std::fs::write(path, file_buffer))
+	{
+		Err(error) if is_read_only_error(&error) => Err(std::io::Error::new(error.kind(), ReadOnlyTarget::new(path))),
+		result                                   => result,
+	}
+}
+
+/// Checks if the error was caused by the storage (or the quota of the user)
+/// being exhausted
+fn
+is_insufficient_space_error
+(
+	error: &std::io::Error
+)
+-> bool
+{
+	matches!(error.kind(), std::io::ErrorKind::StorageFull | std::io::ErrorKind::QuotaExceeded | std::io::ErrorKind::FileTooLarge)
+}
+
+/// Gets the directory the temporary file for the file at the given path gets
+/// created in
+fn
+temp_file_directory
+(
+	path:     &std::path::Path,
+	location: &TempFileLocation
+)
+-> std::path::PathBuf
+{
+	match location
+	{
+		TempFileLocation::SameDirectory        => path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(std::path::Path::new(".")).to_path_buf(),
+		TempFileLocation::SystemTemp           => std::env::temp_dir(),
+		TempFileLocation::Directory(directory) => directory.clone(),
+	}
+}
+
+/// Creates a new temporary file for the file at the given path in the given
+/// location, named `.<file name>.little_exif-<n>.tmp`
+fn
+create_temp_file
+(
+	path:     &std::path::Path,
+	location: &TempFileLocation
+)
+-> Result<(std::path::PathBuf, std::fs::File), std::io::Error>
+{
+	let directory = temp_file_directory(path, location);
+	let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+	for number in 0..100
+	{
+		let temp_path = directory.join(format!(".{}.little_exif-{}.tmp", file_name, number));
+		match std::fs::OpenOptions::new().write(true).create_new(true).open(&temp_path)
+		{
+			Ok(file)                                                         => return Ok((temp_path, file)),
+			Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => continue,
+			Err(error)                                                       => return Err(error),
+		}
+	}
+	return io_error!(AlreadyExists, "Can't create temporary file - Too many leftover temporary files!");
+}
+
+/// Replaces the file at the given path by the temporary file. If they are
+/// stored on different file systems, renaming is not possible, so the
+/// temporary file first gets copied to another one next to the file, which
+/// then replaces it. Copying over the file itself would leave a truncated
+/// file behind if it fails.
+fn
+replace_by_temp_file
+(
+	path:      &std::path::Path,
+	temp_path: &std::path::Path
+)
+-> Result<(), std::io::Error>
+{
+	match retry_if_shared(path, || std::fs::rename(temp_path, path))
+	{
+		Err(error) if error.kind() == std::io::ErrorKind::CrossesDevices => {
+			let permissions = std::fs::metadata(temp_path)?.permissions();
+			let (local_temp_path, mut local_temp_file) = create_temp_file(path, &TempFileLocation::SameDirectory)?;
+			let copy_result = std::fs::File::open(temp_path)
+				.and_then(|mut temp_file| std::io::copy(&mut temp_file, &mut local_temp_file))
+				.and_then(|_| local_temp_file.sync_all());
+			drop(local_temp_file);
+
+			let result = copy_result
+				.and_then(|_| std::fs::set_permissions(&local_temp_path, permissions))
+				.and_then(|_| retry_if_shared(path, || std::fs::rename(&local_temp_path, path)));
+			if result.is_err()
+			{
+				let _ = std::fs::remove_file(&local_temp_path);
+				return result;
+			}
+			return std::fs::remove_file(temp_path);
+		},
+		result => result,
+	}
+}
+
+/// Writes the given data to the file at the given path like
+/// `write_vec_to_file`, however without ever leaving a truncated file behind:
+/// The data is first written to a temporary file in the given location
+/// (see `WriteOptions::temp_file_location`), which replaces the original
+/// only after all data has been written. If the free space at the location
+/// is too small for the data, nothing gets written at all. Running out of
+/// space nonetheless (e.g. as other processes take up space in the meantime)
+/// is detected while writing the temporary file. Both result in an error
+/// containing an `InsufficientSpace` and an untouched original.
+/// The permissions of the original file are kept.
+pub(crate) fn
+safe_write_vec_to_file
+(
+	path:        &std::path::Path,
+	file_buffer: &[u8],
+	location:    &TempFileLocation
+)
+-> Result<(), std::io::Error>
+{
+	safe_write_vec_to_file_through(&OsFileSystem, path, file_buffer, location, |temp_file| temp_file)
+}
+
+/// Implements `safe_write_vec_to_file`, getting the free space from the
+/// given file system and writing to the temporary file through the writer
+/// returned by the given function, e.g. for injecting faults in tests
+pub(crate) fn
+safe_write_vec_to_file_through
+<W: SyncWrite>
+(
+	file_system: &dyn FileSystem,
+	path:        &std::path::Path,
+	file_buffer: &[u8],
+	location:    &TempFileLocation,
+	writer:      impl FnOnce(std::fs::File) -> W
+)
+-> Result<(), std::io::Error>
+{
+	let permissions = std::fs::metadata(path)?.permissions();
+	let insufficient_space = |kind: std::io::ErrorKind| std::io::Error::new(kind, InsufficientSpace::new(path, file_buffer.len() as u64));
+
+	// If the free space is unknown (e.g. for some network shares), running
+	// out of space still gets detected while writing
+	if let Ok(Some(available_space)) = file_system.available_space(&temp_file_directory(path, location))
+	{
+		if available_space < file_buffer.len() as u64
+		{
+			return Err(insufficient_space(std::io::ErrorKind::StorageFull));
+		}
+	}
+
+	// Only the directory of the file itself is part of the target
+	let (temp_path, temp_file) = match create_temp_file(path, location)
+	{
+		Err(error) if is_read_only_error(&error) && *location == TempFileLocation::SameDirectory
+			=> return Err(std::io::Error::new(error.kind(), ReadOnlyTarget::new(path))),
+		result
+			=> result?,
+	};
+
+	let mut temp_file = writer(temp_file);
+	let write_result = temp_file.write_all(file_buffer)
+		.and_then(|_| temp_file.sync_all())
+		.and_then(|_| std::fs::set_permissions(&temp_path, permissions));
+	drop(temp_file);
+
+	let result = match write_result.and_then(|_| replace_by_temp_file(path, &temp_path))
+	{
+		Err(error) if is_insufficient_space_error(&error) => Err(insufficient_space(error.kind())),
+		result                                            => result,
+	};
+
+	if result.is_err()
+	{
+		let _ = std::fs::remove_file(&temp_path);
+	}
+	return result;
+}
+
+/// Gets the path of the backup of the file at the given path, following the
+/// convention of ExifTool by appending `_original` (e.g. `image.jpg_original`)
+pub(crate) fn
+backup_path
+(
+	path: &std::path::Path
+)
+-> std::path::PathBuf
+{
+	let mut backup_name = path.file_name().unwrap_or_default().to_os_string();
+	backup_name.push("_original");
+	return path.with_file_name(backup_name);
+}
+
+/// Copies the file at the given path to its backup path, unless there
+/// already is a backup. In that case the existing one is kept, as it holds
+/// an older state of the file, which is what ExifTool does as well.
+/// The backup is created exclusively, so that of several concurrent writers
+/// only the first one creates it. As the file is opened beforehand, the
+/// backup holds its content from before any of them replaced it.
+/// Returns whether a new backup has been created
+pub(crate) fn
+create_backup
+(
+	path: &std::path::Path
+)
+-> Result<bool, std::io::Error>
+{
+	let mut file = retry_if_shared(path, || std::fs::File::open(path))?;
+	let permissions = file.metadata()?.permissions();
+
+	let backup_path = backup_path(path);
+	let mut backup_file = match std::fs::OpenOptions::new().write(true).create_new(true).open(&backup_path)
+	{
+		Ok(file)                                                         => file,
+		Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => {
+			if !backup_path.is_file()
+			{
+				return io_error!(AlreadyExists, format!("Can't create backup - {} exists but is not a file!", backup_path.display()));
+			}
+			return Ok(false);
+		},
+		Err(error)                                                       => return Err(error),
+	};
+
+	let copy_result = std::io::copy(&mut file, &mut backup_file)
+		.and_then(|_| std::fs::set_permissions(&backup_path, permissions));
+	drop(backup_file);
+
+	if let Err(error) = copy_result
+	{
+		let _ = std::fs::remove_file(&backup_path);
+		return Err(error);
+	}
+	return Ok(true);
+}
+
+/// The maximum length of a path (including the terminating NUL character)
+/// that Windows accepts without the `\\?\` prefix
+#[cfg_attr(not(any(feature = "sqlite", feature = "notify")), allow(dead_code))]
+const WINDOWS_MAX_PATH: usize = 260;
+
+/// Removes the `\\?\` prefix of a verbatim Windows path (as returned e.g. by
+/// `std::fs::canonicalize`) if the path also works without it. That's not
+/// the case for paths exceeding `MAX_PATH` or containing names that are only
+/// allowed in verbatim paths (e.g. `CON` or names ending with a dot).
+/// Returns `None` if the prefix needs to be kept
+#[cfg_attr(not(any(feature = "sqlite", feature = "notify")), allow(dead_code))]
+fn
+strip_verbatim_prefix
+(
+	path: &str
+)
+-> Option<String>
+{
+	let simplified = if let Some(unc_path) = path.strip_prefix(r"\\?\UNC\")
+	{
+		format!(r"\\{}", unc_path)
+	}
+	else
+	{
+		let disk_path = path.strip_prefix(r"\\?\")?;
+		let bytes = disk_path.as_bytes();
+		if bytes.len() < 3 || !bytes[0].is_ascii_alphabetic() || bytes[1] != b':' || bytes[2] != b'\\'
+		{
+			return None;
+		}
+		disk_path.to_string()
+	};
+
+	if simplified.encode_utf16().count() >= WINDOWS_MAX_PATH
+	{
+		return None;
+	}
+
+	const RESERVED_NAMES: [&str; 22] = [
+		"CON", "PRN", "AUX", "NUL",
+		"COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+		"LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+	];
+
+	let requires_prefix = simplified.split('\\')
+		.filter(|name| !name.is_empty() && !name.ends_with(':'))
+		.any(|name| {
+			let stem = name.split('.').next().unwrap_or(name).trim_end();
+			name.ends_with('.') || name.ends_with(' ') || RESERVED_NAMES.iter().any(|reserved| reserved.eq_ignore_ascii_case(stem))
+		});
+
+	if requires_prefix
+	{
+		return None;
+	}
+	return Some(simplified);
+}
+
+/// Returns the absolute path of the given file or directory, like
+/// `std::fs::canonicalize`. On Windows, this avoids the `\\?\` prefix
+/// whenever possible so that the paths match the ones reported by the file
+/// system (e.g. in change events) and are understood by other programs.
+/// Paths longer than `MAX_PATH` keep the prefix as they require it.
+#[cfg_attr(not(any(feature = "sqlite", feature = "notify")), allow(dead_code))]
+pub(crate) fn
+canonicalize
+(
+	path: &std::path::Path
+)
+-> Result<std::path::PathBuf, std::io::Error>
+{
+	let canonical_path = std::fs::canonicalize(path)?;
+	if cfg!(windows)
+	{
+		if let Some(simplified) = canonical_path.to_str().and_then(strip_verbatim_prefix)
+		{
+			return Ok(std::path::PathBuf::from(simplified));
+		}
+	}
+	return Ok(canonical_path);
+}
+
+#[cfg(test)]
+mod tests
+{
+	use std::cell::Cell;
+	use std::path::Path;
+	use std::time::Duration;
+
+	use crate::errors::InsufficientSpace;
+	use crate::errors::SharingViolation;
+	use crate::general_file_io::retry_with_delays;
+	use crate::general_file_io::safe_write_vec_to_file_through;
+	use crate::general_file_io::strip_verbatim_prefix;
+	use crate::vfs::FileSystem;
+	use crate::vfs::OsFileSystem;
+	use crate::vfs::ReadSeek;
+	use crate::write_options::TempFileLocation;
+
+	/// The file system of the operating system, but with only 1000 bytes of
+	/// free space
+	struct
+	LowSpaceFileSystem;
+
+	impl
+	FileSystem
+	for
+	LowSpaceFileSystem
+	{
+		fn
+		exists
+		(
+			&self,
+			path: &Path
+		)
+		-> bool
+		{
+			OsFileSystem.exists(path)
+		}
+
+		fn
+		open
+		(
+			&self,
+			path: &Path
+		)
+		-> Result<Box<dyn ReadSeek>, std::io::Error>
+		{
+			OsFileSystem.open(path)
+		}
+
+		fn
+		write
+		(
+			&self,
+			path: &Path,
+			data: &[u8]
+		)
+		-> Result<(), std::io::Error>
+		{
+			OsFileSystem.write(path, data)
+		}
+
+		fn
+		available_space
+		(
+			&self,
+			_directory: &Path
+		)
+		-> Result<Option<u64>, std::io::Error>
+		{
+			return Ok(Some(1000));
+		}
+	}
+
+	#[test]
+	fn
+	strip_verbatim_prefixes()
+	{
+		assert_eq!(strip_verbatim_prefix(r"\\?\C:\Fotos\Ürlaub 2024\写真.jpg"), Some(r"C:\Fotos\Ürlaub 2024\写真.jpg".to_string()));
+		assert_eq!(strip_verbatim_prefix(r"\\?\UNC\nas\photos\a.png"),           Some(r"\\nas\photos\a.png".to_string()));
+
+		// Not verbatim, verbatim without a drive or only valid as verbatim
+		assert_eq!(strip_verbatim_prefix(r"C:\Fotos\a.jpg"),                      None);
+		assert_eq!(strip_verbatim_prefix(r"\\?\Volume{1234}\a.jpg"),              None);
+		assert_eq!(strip_verbatim_prefix(r"\\?\C:\Fotos\con.jpg"),                None);
+		assert_eq!(strip_verbatim_prefix(r"\\?\C:\Fotos.\a.jpg"),                 None);
+
+		let long_path = format!(r"\\?\C:\{}\a.jpg", "Ordner\\".repeat(40));
+		assert_eq!(strip_verbatim_prefix(&long_path), None);
+	}
+
+	#[test]
+	fn
+	retries()
+	{
+		let path = std::path::Path::new("image.jpg");
+		let delays = [Duration::from_millis(1), Duration::from_millis(2)];
+		let is_transient = |error: &std::io::Error| error.kind() == std::io::ErrorKind::WouldBlock;
+
+		// Succeeds on the last attempt
+		let mut attempts = 0;
+		let result = retry_with_delays(path, &delays, is_transient, || {
+			attempts += 1;
+			match attempts
+			{
+				3 => Ok(attempts),
+				_ => Err(std::io::Error::from(std::io::ErrorKind::WouldBlock)),
+			}
+		});
+		assert_eq!(result.unwrap(), 3);
+
+		// Gives up after the last delay
+		let error = retry_with_delays(path, &delays, is_transient, || Err::<(), _>(std::io::Error::from(std::io::ErrorKind::WouldBlock))).err().unwrap();
+		assert_eq!(error.kind(), std::io::ErrorKind::ResourceBusy);
+		let sharing_violation = SharingViolation::from_io_error(&error).unwrap();
+		assert_eq!(sharing_violation.path(), path);
+		assert_eq!(sharing_violation.attempts(), 3);
+
+		// Other errors are not retried
+		let mut attempts = 0;
+		let error = retry_with_delays(path, &delays, is_transient, || {
+			attempts += 1;
+			Err::<(), _>(std::io::Error::from(std::io::ErrorKind::NotFound))
+		}).err().unwrap();
+		assert_eq!((error.kind(), attempts), (std::io::ErrorKind::NotFound, 1));
+	}
+
+	#[test]
+	fn
+	safe_write_checks_free_space()
+	-> Result<(), std::io::Error>
+	{
+		let path = Path::new("tests/sample2_free_space_copy.jpg");
+		std::fs::copy("tests/sample2.jpg", path)?;
+		let original = std::fs::read(path)?;
+
+		// Refused before even creating the temporary file
+		let temp_file_created = Cell::new(false);
+		let error = safe_write_vec_to_file_through(&LowSpaceFileSystem, path, &original, &TempFileLocation::SameDirectory, |temp_file| {
+			temp_file_created.set(true);
+			temp_file
+		}).err().unwrap();
+		assert_eq!(error.kind(), std::io::ErrorKind::StorageFull);
+		assert_eq!(InsufficientSpace::from_io_error(&error).unwrap().required_bytes(), original.len() as u64);
+		assert!(!temp_file_created.get());
+		assert_eq!(std::fs::read(path)?, original);
+
+		// Fits into the free space
+		safe_write_vec_to_file_through(&LowSpaceFileSystem, path, &original[..1000], &TempFileLocation::SameDirectory, |temp_file| temp_file)?;
+		assert_eq!(std::fs::read(path)?, &original[..1000]);
+
+		std::fs::remove_file(path)?;
+		Ok(())
+	}
+}
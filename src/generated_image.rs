@@ -0,0 +1,894 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Stamping images that were created by generative models (e.g. diffusion
+//! models) with information about their origin, right after encoding them in
+//! memory and before they are stored or sent anywhere. Everything happens via
+//! the in-memory API of `Metadata`, existing tags of the image are kept.
+//!
+//! The information ends up in the following tags:
+//! - `Software`: The generating software
+//! - `Model`: The generative model
+//! - `UserComment`: The prompt, using the ASCII or - if the prompt contains
+//!   other characters - the Unicode (UTF-16) character code
+//! - `ImageDescription`: A provenance statement, e.g. the digital source type
+//!   and a reference to a C2PA manifest stored elsewhere
+//!
+//! XMP is only supported for JPEG files so far (see the `xmp` module), so the
+//! prompt is only stored in EXIF.
+//!
+//! Many generative tools store their own payloads instead, which
+//! `read_generation_payloads` recognizes and `write_generation_payload`
+//! writes back after editing them:
+//! - PNG text chunks with the keyword `parameters` (AUTOMATIC1111 web UI and
+//!   compatible tools like Forge or SD.Next), `prompt` and `workflow`
+//!   (ComfyUI) or `Comment` (NovelAI)
+//! - `UserComment` tags holding such a payload, as written by the web UI for
+//!   JPEG and WebP files
+//!
+//! Payloads are either JSON documents (`JsonPayload`) or the text format of
+//! the web UI (`GenerationParameters`): The prompt, optionally followed by a
+//! line starting with `Negative prompt: `, and a final line of settings like
+//! `Steps: 20, Sampler: Euler a, CFG scale: 7`. A `UserComment` is only taken
+//! as the latter if that line holds at least three settings, as done by the
+//! web UI itself. The setters keep the payloads parseable, e.g. by quoting
+//! setting values that contain commas and by changing only the edited member
+//! of JSON documents.
+
+use crate::endian::*;
+use crate::exif_tag::ExifTag;
+use crate::filetype::FileExtension;
+use crate::general_file_io::io_error;
+use crate::metadata::Metadata;
+use crate::png;
+
+pub(crate) const ASCII_CHARACTER_CODE:   [u8; 8] = *b"ASCII\0\0\0";
+pub(crate) const UNICODE_CHARACTER_CODE: [u8; 8] = *b"UNICODE\0";
+
+/// Keywords of the PNG text chunks generative tools store payloads in
+const PAYLOAD_KEYWORDS: [&str; 4] = ["parameters", "prompt", "workflow", "Comment"];
+
+const NEGATIVE_PROMPT_PREFIX: &str = "Negative prompt:";
+
+/// The number of settings the last line of a `UserComment` needs to have to
+/// be taken as `GenerationParameters`
+const MIN_RECOGNIZED_SETTINGS: usize = 3;
+
+/// Information about the origin of a generated image. Set using chained
+/// calls, starting with the generating software given to `GenInfo::new`.
+///
+/// # Examples
+/// ```no_run
+/// use little_exif::generated_image::GenInfo;
+///
+/// let info = GenInfo::new("ImageGen 2.1")
+///     .model("diffusion-xl")
+///     .prompt("A lighthouse at dawn, oil painting")
+///     .provenance("trainedAlgorithmicMedia");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct
+GenInfo
+{
+	pub(crate) software:   String,
+	pub(crate) model:      Option<String>,
+	pub(crate) prompt:     Option<String>,
+	pub(crate) provenance: Option<String>,
+}
+
+impl
+GenInfo
+{
+	/// Constructs the information with the given generating software and
+	/// nothing else
+	pub fn
+	new
+	(
+		software: &str
+	)
+	-> GenInfo
+	{
+		GenInfo
+		{
+			software:   software.to_string(),
+			model:      None,
+			prompt:     None,
+			provenance: None,
+		}
+	}
+
+	/// Sets the name of the generative model (ASCII only)
+	pub fn
+	model
+	(
+		mut self,
+		model: &str
+	)
+	-> GenInfo
+	{
+		self.model = Some(model.to_string());
+		self
+	}
+
+	/// Sets the prompt the image was generated from
+	pub fn
+	prompt
+	(
+		mut self,
+		prompt: &str
+	)
+	-> GenInfo
+	{
+		self.prompt = Some(prompt.to_string());
+		self
+	}
+
+	/// Sets the provenance statement (ASCII only)
+	pub fn
+	provenance
+	(
+		mut self,
+		provenance: &str
+	)
+	-> GenInfo
+	{
+		self.provenance = Some(provenance.to_string());
+		self
+	}
+}
+
+/// Where a payload of a generative tool is stored
+#[derive(Debug, Clone, PartialEq)]
+pub enum
+PayloadLocation
+{
+	/// A PNG text chunk (tEXt, zTXt or iTXt) with the given keyword
+	PngText(String),
+
+	/// The `UserComment` tag of the EXIF data
+	UserComment,
+}
+
+/// Generation parameters in the text format of the AUTOMATIC1111 web UI,
+/// see the module documentation
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct
+GenerationParameters
+{
+	prompt:          String,
+	negative_prompt: Option<String>,
+	settings:        Vec<(String, String)>,
+}
+
+/// Checks if the name of a setting is one the web UI can parse, i.e. starts
+/// with a word character and consists of word characters, spaces, hyphens
+/// and slashes only
+fn
+is_valid_setting_name
+(
+	name: &str
+)
+-> bool
+{
+	let is_word_character = |character: char| character.is_alphanumeric() || character == '_';
+	return name.starts_with(is_word_character)
+		&& name.chars().all(|character| is_word_character(character) || matches!(character, ' ' | '-' | '/'));
+}
+
+/// Parses a line of settings, returning `None` if it isn't one
+fn
+parse_settings
+(
+	line: &str
+)
+-> Option<Vec<(String, String)>>
+{
+	let mut settings = Vec::new();
+	let mut rest = line.trim();
+	while !rest.is_empty()
+	{
+		let (name, after_name) = rest.split_once(':')?;
+		if !is_valid_setting_name(name.trim())
+		{
+			return None;
+		}
+
+		// Quoted values are JSON strings
+		let after_name = after_name.trim_start();
+		let (value, after_value) = match after_name.starts_with('"')
+		{
+			true  => {
+				let mut end = 1;
+				let mut escaped = false;
+				for (index, character) in after_name.char_indices().skip(1)
+				{
+					match (escaped, character)
+					{
+						(false, '\\') => escaped = true,
+						(false, '"')  => { end = index + 1; break; },
+						_             => escaped = false,
+					}
+				}
+				(serde_json::from_str::<String>(&after_name[..end]).ok()?, &after_name[end..])
+			},
+			false => {
+				let end = after_name.find(',').unwrap_or(after_name.len());
+				(after_name[..end].trim().to_string(), &after_name[end..])
+			},
+		};
+
+		settings.push((name.trim().to_string(), value));
+		rest = after_value.trim_start();
+		match rest.strip_prefix(',')
+		{
+			Some(after_separator)   => rest = after_separator.trim_start(),
+			None if rest.is_empty() => (),
+			None                    => return None,
+		}
+	}
+	return Some(settings);
+}
+
+impl
+GenerationParameters
+{
+	/// Constructs parameters with the given prompt and nothing else
+	pub fn
+	new
+	(
+		prompt: &str
+	)
+	-> Result<GenerationParameters, std::io::Error>
+	{
+		let mut parameters = GenerationParameters::default();
+		parameters.set_prompt(prompt)?;
+		return Ok(parameters);
+	}
+
+	/// Parses the text. If its last line is not a line of settings, all of
+	/// the text is taken as prompt and negative prompt.
+	pub fn
+	parse
+	(
+		text: &str
+	)
+	-> GenerationParameters
+	{
+		let mut lines = text.trim().lines().collect::<Vec<&str>>();
+
+		let settings = lines.last()
+			.and_then(|line| parse_settings(line))
+			.filter(|settings| settings.len() >= MIN_RECOGNIZED_SETTINGS);
+		if settings.is_some()
+		{
+			lines.pop();
+		}
+
+		let negative_start = lines.iter().position(|line| line.starts_with(NEGATIVE_PROMPT_PREFIX));
+		let negative_prompt = negative_start.map(|start| {
+			let mut negative_lines = lines[start..].to_vec();
+			negative_lines[0] = &negative_lines[0][NEGATIVE_PROMPT_PREFIX.len()..];
+			negative_lines.join("\n").trim().to_string()
+		});
+		lines.truncate(negative_start.unwrap_or(lines.len()));
+
+		return GenerationParameters
+		{
+			prompt:   lines.join("\n").trim().to_string(),
+			negative_prompt,
+			settings: settings.unwrap_or_default(),
+		};
+	}
+
+	/// Gets the prompt
+	pub fn
+	prompt
+	(
+		&self
+	)
+	-> &str
+	{
+		&self.prompt
+	}
+
+	/// Gets the negative prompt, if any
+	pub fn
+	negative_prompt
+	(
+		&self
+	)
+	-> Option<&str>
+	{
+		self.negative_prompt.as_deref()
+	}
+
+	/// Gets the settings, e.g. `("Steps", "20")`, in the order they are stored
+	pub fn
+	settings
+	(
+		&self
+	)
+	-> &[(String, String)]
+	{
+		&self.settings
+	}
+
+	/// Gets the value of the setting with the given name
+	pub fn
+	setting
+	(
+		&self,
+		name: &str
+	)
+	-> Option<&str>
+	{
+		self.settings.iter().find(|(known, _)| known == name).map(|(_, value)| value.as_str())
+	}
+
+	/// Sets the prompt
+	/// - If a line of the prompt starts with `Negative prompt:`, an error of
+	///   kind `InvalidInput` gets returned, as it couldn't be parsed again
+	pub fn
+	set_prompt
+	(
+		&mut self,
+		prompt: &str
+	)
+	-> Result<(), std::io::Error>
+	{
+		if prompt.lines().any(|line| line.starts_with(NEGATIVE_PROMPT_PREFIX))
+		{
+			return io_error!(InvalidInput, "Prompt must not contain a negative prompt line!");
+		}
+		self.prompt = prompt.trim().to_string();
+		return Ok(());
+	}
+
+	/// Sets or (if `None`) removes the negative prompt
+	pub fn
+	set_negative_prompt
+	(
+		&mut self,
+		negative_prompt: Option<&str>
+	)
+	{
+		self.negative_prompt = negative_prompt.map(|negative_prompt| negative_prompt.trim().to_string());
+	}
+
+	/// Sets or (if `None`) removes the setting with the given name. Existing
+	/// settings keep their position, new ones are added at the end.
+	/// - If the name is not one the web UI can parse (see the module
+	///   documentation), an error of kind `InvalidInput` gets returned
+	pub fn
+	set_setting
+	(
+		&mut self,
+		name:  &str,
+		value: Option<&str>
+	)
+	-> Result<(), std::io::Error>
+	{
+		if !is_valid_setting_name(name) || name.trim() != name
+		{
+			return io_error!(InvalidInput, "Invalid name of a setting!");
+		}
+
+		match (self.settings.iter().position(|(known, _)| known == name), value)
+		{
+			(Some(index), Some(value)) => self.settings[index].1 = value.to_string(),
+			(Some(index), None)        => { self.settings.remove(index); },
+			(None,        Some(value)) => self.settings.push((name.to_string(), value.to_string())),
+			(None,        None)        => (),
+		}
+		return Ok(());
+	}
+
+	/// Encodes the parameters in the text format of the web UI. Values are
+	/// quoted if they contain commas, colons, quotes or line breaks.
+	pub fn
+	to_text
+	(
+		&self
+	)
+	-> String
+	{
+		let mut text = self.prompt.clone();
+		if let Some(negative_prompt) = &self.negative_prompt
+		{
+			text.push_str(&format!("\n{} {}", NEGATIVE_PROMPT_PREFIX, negative_prompt));
+		}
+
+		let settings = self.settings.iter()
+			.map(|(name, value)| match value.contains([',', ':', '"', '\n', '\r'])
+			{
+				true  => format!("{}: {}", name, serde_json::Value::from(value.as_str())),
+				false => format!("{}: {}", name, value.trim()),
+			})
+			.collect::<Vec<String>>();
+		if !settings.is_empty()
+		{
+			text.push_str(&format!("\n{}", settings.join(", ")));
+		}
+		return text;
+	}
+}
+
+/// A JSON document stored by a generative tool, e.g. the prompt graph of
+/// ComfyUI or the generation settings of NovelAI
+#[derive(Debug, Clone, PartialEq)]
+pub struct
+JsonPayload
+{
+	text: String,
+}
+
+impl
+JsonPayload
+{
+	/// Takes the text as payload, if it is a JSON object
+	pub fn
+	parse
+	(
+		text: &str
+	)
+	-> Option<JsonPayload>
+	{
+		match serde_json::from_str(text)
+		{
+			Ok(serde_json::Value::Object(_)) => Some(JsonPayload { text: text.to_string() }),
+			_                                => None,
+		}
+	}
+
+	/// Gets the JSON document
+	pub fn
+	as_str
+	(
+		&self
+	)
+	-> &str
+	{
+		&self.text
+	}
+
+	/// Gets the value of the string member of the object, e.g. `prompt`
+	pub fn
+	get_string
+	(
+		&self,
+		member: &str
+	)
+	-> Option<String>
+	{
+		let document = serde_json::from_str::<serde_json::Value>(&self.text).ok()?;
+		return document.get(member)?.as_str().map(str::to_string);
+	}
+
+	/// Sets the member of the object to the given string, adding it at the
+	/// end if necessary. The other members keep their order and values, but
+	/// the document gets written without any insignificant whitespace.
+	pub fn
+	set_string
+	(
+		&mut self,
+		member: &str,
+		value:  &str
+	)
+	-> Result<(), std::io::Error>
+	{
+		let mut members = match serde_json::from_str(&self.text)
+		{
+			Ok(serde_json::Value::Object(members)) => members,
+			_                                      => return io_error!(InvalidData, "JSON document is not an object!"),
+		};
+		members.insert(member.to_string(), serde_json::Value::from(value));
+		self.text = serde_json::Value::Object(members).to_string();
+		return Ok(());
+	}
+}
+
+/// A payload of a generative tool, see the module documentation
+#[derive(Debug, Clone, PartialEq)]
+pub enum
+GenerationPayload
+{
+	Parameters(GenerationParameters),
+	Json(JsonPayload),
+}
+
+impl
+GenerationPayload
+{
+	/// Recognizes the payload in the text, trying JSON first. Returns `None`
+	/// if it is neither a JSON object nor parameters with a line of settings.
+	pub fn
+	recognize
+	(
+		text: &str
+	)
+	-> Option<GenerationPayload>
+	{
+		if let Some(json) = JsonPayload::parse(text)
+		{
+			return Some(GenerationPayload::Json(json));
+		}
+		let parameters = GenerationParameters::parse(text);
+		return match parameters.settings.is_empty()
+		{
+			true  => None,
+			false => Some(GenerationPayload::Parameters(parameters)),
+		};
+	}
+
+	/// Encodes the payload as text
+	pub fn
+	to_text
+	(
+		&self
+	)
+	-> String
+	{
+		match self
+		{
+			GenerationPayload::Parameters(parameters) => parameters.to_text(),
+			GenerationPayload::Json(json)             => json.as_str().to_string(),
+		}
+	}
+}
+
+/// Reads the payloads of generative tools stored in the image, see the module
+/// documentation. The type of the image is determined by its signature.
+/// - If the type can't be determined or the image is invalid, an error gets
+///   returned
+///
+/// # Examples
+/// ```no_run
+/// use little_exif::generated_image::GenerationPayload;
+/// use little_exif::generated_image::read_generation_payloads;
+///
+/// let file_buffer = std::fs::read("generated.png").unwrap();
+/// for (location, payload) in read_generation_payloads(&file_buffer).unwrap()
+/// {
+///     if let GenerationPayload::Parameters(parameters) = payload
+///     {
+///         println!("{:?}: {} ({:?} steps)", location, parameters.prompt(), parameters.setting("Steps"));
+///     }
+/// }
+/// ```
+pub fn
+read_generation_payloads
+(
+	file_buffer: &Vec<u8>
+)
+-> Result<Vec<(PayloadLocation, GenerationPayload)>, std::io::Error>
+{
+	let file_type = match FileExtension::from_signature(file_buffer)
+	{
+		Some(file_type) => file_type,
+		None            => return io_error!(InvalidData, "Can't read payloads - Unknown or unsupported image type!"),
+	};
+
+	let mut payloads = Vec::new();
+	if let FileExtension::PNG { .. } = file_type
+	{
+		for (keyword, text) in png::read_text_chunks(file_buffer)?
+		{
+			let payload = match keyword.as_str()
+			{
+				"parameters"                                     => Some(GenerationPayload::Parameters(GenerationParameters::parse(&text))),
+				_ if PAYLOAD_KEYWORDS.contains(&keyword.as_str()) => JsonPayload::parse(&text).map(GenerationPayload::Json),
+				_                                                => None,
+			};
+			if let Some(payload) = payload
+			{
+				payloads.push((PayloadLocation::PngText(keyword), payload));
+			}
+		}
+	}
+
+	let metadata = Metadata::new_from_vec(file_buffer, file_type)?;
+	if let Some(ExifTag::UserComment(user_comment)) = metadata.get_tag(&ExifTag::UserComment(Vec::new()))
+	{
+		if let Some(payload) = GenerationPayload::recognize(&decode_user_comment(user_comment, metadata.endian()))
+		{
+			payloads.push((PayloadLocation::UserComment, payload));
+		}
+	}
+
+	return Ok(payloads);
+}
+
+/// Writes the payload to the given location of the image, replacing what is
+/// stored there. The type of the image is determined by its signature.
+/// - If the type can't be determined or the location is a PNG text chunk
+///   but the image is no PNG, an error gets returned and the data is left
+///   unchanged.
+pub fn
+write_generation_payload
+(
+	file_buffer: &mut Vec<u8>,
+	location:    &PayloadLocation,
+	payload:     &GenerationPayload
+)
+-> Result<(), std::io::Error>
+{
+	let file_type = match FileExtension::from_signature(file_buffer)
+	{
+		Some(file_type) => file_type,
+		None            => return io_error!(InvalidData, "Can't write payload - Unknown or unsupported image type!"),
+	};
+
+	match (location, file_type)
+	{
+		(PayloadLocation::PngText(keyword), FileExtension::PNG { .. }) => png::write_text_chunk(file_buffer, keyword, Some(&payload.to_text())),
+		(PayloadLocation::PngText(_),       _)                         => io_error!(Unsupported, "Text chunks are only supported for PNG files!"),
+		(PayloadLocation::UserComment,      _)                         => {
+			let mut metadata = Metadata::new_from_vec(file_buffer, file_type)?;
+			metadata.set_tag(ExifTag::UserComment(encode_user_comment(&payload.to_text(), metadata.endian())));
+			metadata.write_to_vec(file_buffer, file_type)
+		},
+	}
+}
+
+/// Stamps the given image data with the information about its origin. The
+/// type of the image (PNG, WebP or JPEG) is determined by its signature.
+/// - If the type can't be determined, the image is invalid or a value that
+///   requires ASCII contains other characters, an error gets returned and
+///   the data is left unchanged.
+///
+/// # Examples
+/// ```no_run
+/// use little_exif::generated_image::GenInfo;
+/// use little_exif::generated_image::stamp_generated_image;
+///
+/// let mut file_buffer = std::fs::read("generated.png").unwrap();
+/// stamp_generated_image(
+///     &mut file_buffer,
+///     &GenInfo::new("ImageGen 2.1").prompt("A lighthouse at dawn")
+/// ).unwrap();
+/// ```
+pub fn
+stamp_generated_image
+(
+	file_buffer: &mut Vec<u8>,
+	info:        &GenInfo
+)
+-> Result<(), std::io::Error>
+{
+	let file_type = match FileExtension::from_signature(file_buffer)
+	{
+		Some(file_type) => file_type,
+		None            => return io_error!(InvalidData, "Can't stamp image - Unknown or unsupported image type!"),
+	};
+
+	// EXIF strings are ASCII
+	let string_values = [Some(&info.software), info.model.as_ref(), info.provenance.as_ref()];
+	if string_values.iter().flatten().any(|value| !value.is_ascii())
+	{
+		return io_error!(InvalidInput, "Can't stamp image - Software, model and provenance need to be ASCII!");
+	}
+
+	let mut metadata = Metadata::new_from_vec(file_buffer, file_type)?;
+
+	metadata.set_tag(ExifTag::Software(info.software.clone()));
+	if let Some(model) = &info.model
+	{
+		metadata.set_tag(ExifTag::Model(model.clone()));
+	}
+	if let Some(prompt) = &info.prompt
+	{
+		metadata.set_tag(ExifTag::UserComment(encode_user_comment(prompt, metadata.endian())));
+	}
+	if let Some(provenance) = &info.provenance
+	{
+		metadata.set_tag(ExifTag::ImageDescription(provenance.clone()));
+	}
+
+	return metadata.write_to_vec(file_buffer, file_type);
+}
+
+/// Encodes the text as value of a `UserComment` tag, consisting of the
+/// character code followed by the encoded characters
+pub(crate) fn
+encode_user_comment
+(
+	text:   &str,
+	endian: &Endian
+)
+-> Vec<u8>
+{
+	if text.is_ascii()
+	{
+		return ASCII_CHARACTER_CODE.iter().chain(text.as_bytes()).copied().collect();
+	}
+
+	// Unicode comments use the byte order of the EXIF data
+	let mut user_comment = UNICODE_CHARACTER_CODE.to_vec();
+	for code_unit in text.encode_utf16()
+	{
+		endian.write(&mut user_comment, code_unit);
+	}
+	return user_comment;
+}
+
+/// Decodes the value of a `UserComment` tag, see `encode_user_comment`.
+/// Comments with an undefined or unknown character code are decoded as
+/// UTF-8.
+pub(crate) fn
+decode_user_comment
+(
+	user_comment: &[u8],
+	endian:       &Endian
+)
+-> String
+{
+	let (code, text) = user_comment.split_at(user_comment.len().min(UNICODE_CHARACTER_CODE.len()));
+	let text = match code == UNICODE_CHARACTER_CODE
+	{
+		true  => String::from_utf16_lossy(&text.chunks_exact(2).filter_map(|unit| endian.read::<u16>(unit, 0)).collect::<Vec<u16>>()),
+		false => String::from_utf8_lossy(text).to_string(),
+	};
+	return text.trim_end_matches('\0').to_string();
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::exif_tag::ExifTag;
+	use crate::filetype::FileExtension;
+	use crate::generated_image::*;
+	use crate::metadata::Metadata;
+	use crate::png;
+
+	#[test]
+	fn
+	stamp_png_and_webp()
+	-> Result<(), std::io::Error>
+	{
+		let info = GenInfo::new("ImageGen 2.1")
+			.model("diffusion-xl")
+			.prompt("Ein Leuchtturm im Morgengrauen")
+			.provenance("trainedAlgorithmicMedia");
+
+		for (path, file_type) in [
+			("tests/png_parse_test_image.png",    FileExtension::PNG { as_zTXt_chunk: true }),
+			("tests/sample2_simple_lossy.webp",   FileExtension::WEBP),
+		]
+		{
+			let mut file_buffer = std::fs::read(path)?;
+			stamp_generated_image(&mut file_buffer, &info)?;
+
+			let metadata = Metadata::new_from_vec(&file_buffer, file_type)?;
+			assert_eq!(metadata.get_tag(&ExifTag::Software(String::new())),         Some(&ExifTag::Software("ImageGen 2.1".to_string())));
+			assert_eq!(metadata.get_tag(&ExifTag::Model(String::new())),            Some(&ExifTag::Model("diffusion-xl".to_string())));
+			assert_eq!(metadata.get_tag(&ExifTag::ImageDescription(String::new())), Some(&ExifTag::ImageDescription("trainedAlgorithmicMedia".to_string())));
+			assert_eq!(
+				metadata.get_tag(&ExifTag::UserComment(Vec::new())),
+				Some(&ExifTag::UserComment(b"ASCII\0\0\0Ein Leuchtturm im Morgengrauen".to_vec()))
+			);
+		}
+		Ok(())
+	}
+
+	#[test]
+	fn
+	stamp_unicode_prompt_and_invalid_input()
+	-> Result<(), std::io::Error>
+	{
+		let mut file_buffer = std::fs::read("tests/png_parse_test_image.png")?;
+		stamp_generated_image(&mut file_buffer, &GenInfo::new("ImageGen").prompt("Möwe"))?;
+
+		let metadata = Metadata::new_from_vec(&file_buffer, FileExtension::PNG { as_zTXt_chunk: true })?;
+		assert_eq!(
+			metadata.get_tag(&ExifTag::UserComment(Vec::new())),
+			Some(&ExifTag::UserComment(b"UNICODE\0M\0\xf6\0w\0e\0".to_vec()))
+		);
+
+		let unchanged = file_buffer.clone();
+		assert!(stamp_generated_image(&mut file_buffer, &GenInfo::new("Gen©")).is_err());
+		assert!(stamp_generated_image(&mut b"no image".to_vec(), &GenInfo::new("Gen")).is_err());
+		assert_eq!(file_buffer, unchanged);
+		Ok(())
+	}
+
+	#[test]
+	fn
+	parse_and_edit_parameters()
+	-> Result<(), std::io::Error>
+	{
+		let text = concat!(
+			"a lighthouse at dawn,\noil painting\n",
+			"Negative prompt: blurry, low quality\n",
+			"Steps: 20, Sampler: Euler a, CFG scale: 7, Seed: 1234, Size: 512x512, Lora hashes: \"detail: 4f1c, light: 9a2b\""
+		);
+		let mut parameters = GenerationParameters::parse(text);
+		assert_eq!(parameters.prompt(), "a lighthouse at dawn,\noil painting");
+		assert_eq!(parameters.negative_prompt(), Some("blurry, low quality"));
+		assert_eq!(parameters.settings().len(), 6);
+		assert_eq!(parameters.setting("Sampler"), Some("Euler a"));
+		assert_eq!(parameters.setting("Lora hashes"), Some("detail: 4f1c, light: 9a2b"));
+		assert_eq!(parameters.to_text(), text);
+
+		// Values that would break the format get quoted
+		parameters.set_setting("Steps", Some("30"))?;
+		parameters.set_setting("Seed", None)?;
+		parameters.set_setting("Comment", Some("looks \"great\", really"))?;
+		parameters.set_prompt("a lighthouse at dusk")?;
+		let read_back = GenerationParameters::parse(&parameters.to_text());
+		assert_eq!(read_back, parameters);
+		assert_eq!(read_back.setting("Steps"), Some("30"));
+		assert_eq!(read_back.setting("Comment"), Some("looks \"great\", really"));
+
+		assert!(parameters.set_setting("Bad: name", Some("1")).is_err());
+		assert!(parameters.set_prompt("fine\nNegative prompt: not fine").is_err());
+
+		// Without enough settings, the last line is part of the prompt
+		assert_eq!(GenerationParameters::parse("a cat\nSteps: 20").prompt(), "a cat\nSteps: 20");
+		assert_eq!(GenerationPayload::recognize("Taken with a tripod: yes"), None);
+		Ok(())
+	}
+
+	#[test]
+	fn
+	png_text_payloads()
+	-> Result<(), std::io::Error>
+	{
+		let mut file_buffer = std::fs::read("tests/png_parse_test_image.png")?;
+		assert!(read_generation_payloads(&file_buffer)?.is_empty());
+
+		// NovelAI style JSON and web UI parameters with non Latin-1 prompt
+		let comment = r#"{"prompt": "lighthouse", "steps": 28, "seed": 18446744073709551615}"#;
+		png::write_text_chunk(&mut file_buffer, "Comment", Some(comment))?;
+		let parameters = GenerationParameters::parse("灯台\nSteps: 20, Sampler: Euler, CFG scale: 7");
+		let location = PayloadLocation::PngText("parameters".to_string());
+		write_generation_payload(&mut file_buffer, &location, &GenerationPayload::Parameters(parameters.clone()))?;
+
+		let payloads = read_generation_payloads(&file_buffer)?;
+		assert_eq!(payloads.len(), 2);
+		assert_eq!(payloads[1], (location, GenerationPayload::Parameters(parameters)));
+		let mut json = match &payloads[0]
+		{
+			(PayloadLocation::PngText(keyword), GenerationPayload::Json(json)) if keyword == "Comment" => json.clone(),
+			_                                                                                         => panic!("Comment not recognized"),
+		};
+		assert_eq!(json.get_string("prompt").as_deref(), Some("lighthouse"));
+
+		// Editing keeps the rest of the document and replaces the chunk
+		json.set_string("prompt", "lighthouse, \"stormy\"")?;
+		write_generation_payload(&mut file_buffer, &payloads[0].0, &GenerationPayload::Json(json))?;
+		let payloads = read_generation_payloads(&file_buffer)?;
+		assert_eq!(payloads.len(), 2);
+		let text = payloads.iter().find_map(|(location, payload)| match location
+		{
+			PayloadLocation::PngText(keyword) if keyword == "Comment" => Some(payload.to_text()),
+			_                                                         => None,
+		}).unwrap();
+		assert_eq!(text, r#"{"prompt":"lighthouse, \"stormy\"","steps":28,"seed":18446744073709551615}"#);
+		assert!(png::write_text_chunk(&mut file_buffer, "", Some("text")).is_err());
+
+		// Chunk lengths exceeding the data, see tests/regressions
+		let truncated = std::fs::read("tests/regressions/28f33a0f76d4bbb0.bin")?;
+		assert_eq!(read_generation_payloads(&truncated).err().map(|error| error.kind()), Some(std::io::ErrorKind::InvalidData));
+		Ok(())
+	}
+
+	#[test]
+	fn
+	user_comment_payloads()
+	-> Result<(), std::io::Error>
+	{
+		let mut file_buffer = std::fs::read("tests/sample2.jpg")?;
+		let parameters = GenerationParameters::parse("Möwe\nNegative prompt: Hund\nSteps: 20, Sampler: Euler, CFG scale: 7");
+		let payload = GenerationPayload::Parameters(parameters);
+		write_generation_payload(&mut file_buffer, &PayloadLocation::UserComment, &payload)?;
+		assert_eq!(read_generation_payloads(&file_buffer)?, vec![(PayloadLocation::UserComment, payload)]);
+
+		// Ordinary comments are not taken as payloads
+		stamp_generated_image(&mut file_buffer, &GenInfo::new("ImageGen").prompt("A lighthouse"))?;
+		assert!(read_generation_payloads(&file_buffer)?.is_empty());
+
+		let error = write_generation_payload(&mut file_buffer, &PayloadLocation::PngText("parameters".to_string()), &GenerationPayload::Json(JsonPayload::parse("{}").unwrap()));
+		assert_eq!(error.err().unwrap().kind(), std::io::ErrorKind::Unsupported);
+		Ok(())
+	}
+}
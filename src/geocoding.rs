@@ -0,0 +1,446 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Reverse geocoding, i.e. turning the GPS position where an image was taken
+//! into the IPTC location fields that photo management tools display and
+//! search for.
+//!
+//! The lookup itself is up to the user by implementing `ReverseGeocoder`
+//! (also implemented for closures), e.g. using an online service or an
+//! offline gazetteer. `geocode_files` then processes a batch of images:
+//! Their position is read via `Metadata::gps_position`, passed to the
+//! geocoder and the resulting `Location` gets written as IPTC-IIM data:
+//!
+//! | Field            | IIM dataset                             | Max. bytes |
+//! |------------------|-----------------------------------------|------------|
+//! | `sublocation`    | 2:92 Sub-location                       | 32         |
+//! | `city`           | 2:90 City                               | 32         |
+//! | `province_state` | 2:95 Province/State                     | 32         |
+//! | `country_code`   | 2:100 Country/Primary Location Code     | 3          |
+//! | `country`        | 2:101 Country/Primary Location Name     | 64         |
+//!
+//! Longer values are truncated. The values are stored as UTF-8 (which is
+//! declared via dataset 1:90), all other IPTC datasets and Photoshop image
+//! resources are kept. As the digest of the IPTC data stored by Photoshop
+//! becomes outdated, it gets removed.
+//!
+//! IPTC data is only supported for JPEG files, where it is stored in an APP13
+//! segment. The EXIF data of the images is not changed.
+//!
+//! # Examples
+//! ```no_run
+//! use little_exif::geocoding::geocode_files;
+//! use little_exif::geocoding::Location;
+//!
+//! let geocoder = |latitude: f64, longitude: f64| {
+//!     // Look up the position in a gazetteer...
+//!     Some(Location::new().city("Graz").country("Austria").country_code("AUT"))
+//! };
+//!
+//! let paths = [std::path::Path::new("IMG_0001.jpg"), std::path::Path::new("IMG_0002.jpg")];
+//! for (path, result) in paths.iter().zip(geocode_files(&paths, &geocoder))
+//! {
+//!     match result
+//!     {
+//!         Ok(Some(location)) => println!("{}: {:?}", path.display(), location.city),
+//!         Ok(None)           => println!("{}: No position or unknown place", path.display()),
+//!         Err(error)         => println!("{}: {}", path.display(), error),
+//!     }
+//! }
+//! ```
+
+use std::path::Path;
+
+use crate::filetype::FileExtension;
+use crate::general_file_io::io_error;
+use crate::general_file_io::write_vec_to_file;
+use crate::iptc;
+use crate::iptc::Dataset;
+use crate::iptc::Resource;
+use crate::jpg;
+use crate::metadata::Metadata;
+
+/// The record and number of a dataset and the maximum length of its value
+type DatasetInfo = (u8, u8, usize);
+
+const CODED_CHARACTER_SET: (u8, u8) = (1, 90);
+const RECORD_VERSION:      (u8, u8) = (2, 0);
+const UTF8_ESCAPE:         &[u8]    = b"\x1b%G";
+
+// Datasets of the location fields and their maximum lengths
+const SUBLOCATION:    DatasetInfo = (2, 92,  32);
+const CITY:           DatasetInfo = (2, 90,  32);
+const PROVINCE_STATE: DatasetInfo = (2, 95,  32);
+const COUNTRY_CODE:   DatasetInfo = (2, 100, 3);
+const COUNTRY:        DatasetInfo = (2, 101, 64);
+
+/// The location fields of an image, see the module documentation. Fields
+/// that are `None` are removed when writing the location.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct
+Location
+{
+	pub sublocation:    Option<String>,                                         // E.g. a district or landmark
+	pub city:           Option<String>,
+	pub province_state: Option<String>,
+	pub country_code:   Option<String>,                                         // ISO 3166 code, preferably alpha-3 (e.g. "AUT")
+	pub country:        Option<String>,
+}
+
+impl
+Location
+{
+	/// Constructs a location without any fields
+	pub fn
+	new()
+	-> Location
+	{
+		Location::default()
+	}
+
+	/// Sets the sublocation, e.g. a district or landmark
+	pub fn
+	sublocation
+	(
+		mut self,
+		sublocation: &str
+	)
+	-> Location
+	{
+		self.sublocation = Some(sublocation.to_string());
+		self
+	}
+
+	/// Sets the city
+	pub fn
+	city
+	(
+		mut self,
+		city: &str
+	)
+	-> Location
+	{
+		self.city = Some(city.to_string());
+		self
+	}
+
+	/// Sets the province or state
+	pub fn
+	province_state
+	(
+		mut self,
+		province_state: &str
+	)
+	-> Location
+	{
+		self.province_state = Some(province_state.to_string());
+		self
+	}
+
+	/// Sets the ISO 3166 country code
+	pub fn
+	country_code
+	(
+		mut self,
+		country_code: &str
+	)
+	-> Location
+	{
+		self.country_code = Some(country_code.to_string());
+		self
+	}
+
+	/// Sets the name of the country
+	pub fn
+	country
+	(
+		mut self,
+		country: &str
+	)
+	-> Location
+	{
+		self.country = Some(country.to_string());
+		self
+	}
+
+	/// Gets the fields together with their datasets
+	fn
+	fields
+	(
+		&self
+	)
+	-> [(&Option<String>, DatasetInfo); 5]
+	{
+		[
+			(&self.sublocation,    SUBLOCATION),
+			(&self.city,           CITY),
+			(&self.province_state, PROVINCE_STATE),
+			(&self.country_code,   COUNTRY_CODE),
+			(&self.country,        COUNTRY),
+		]
+	}
+}
+
+/// Looks up the location of GPS positions
+pub trait
+ReverseGeocoder
+{
+	/// Gets the location at the given coordinates in degrees (negative for
+	/// south and west), or `None` if it is unknown
+	fn
+	reverse_geocode
+	(
+		&self,
+		latitude:  f64,
+		longitude: f64
+	)
+	-> Option<Location>;
+}
+
+impl<F>
+ReverseGeocoder
+for
+F
+where
+	F: Fn(f64, f64) -> Option<Location>
+{
+	fn
+	reverse_geocode
+	(
+		&self,
+		latitude:  f64,
+		longitude: f64
+	)
+	-> Option<Location>
+	{
+		self(latitude, longitude)
+	}
+}
+
+/// Cuts the value down to the given number of bytes without splitting a
+/// character
+fn
+truncate
+(
+	value:     &str,
+	max_bytes: usize
+)
+-> &str
+{
+	let mut end = value.len().min(max_bytes);
+	while !value.is_char_boundary(end)
+	{
+		end -= 1;
+	}
+	return &value[..end];
+}
+
+/// Gets the datasets of the IPTC data stored in the resources
+fn
+get_datasets
+(
+	resources: &[Resource]
+)
+-> Result<Vec<Dataset>, std::io::Error>
+{
+	match resources.iter().find(|resource| resource.id == iptc::IPTC_RESOURCE_ID)
+	{
+		Some(resource) => iptc::decode_iim(&resource.data),
+		None           => Ok(Vec::new()),
+	}
+}
+
+/// Reads the location fields of the JPEG image. Returns `None` if there is
+/// no IPTC data.
+pub fn
+read_location_from_vec
+(
+	file_buffer: &[u8]
+)
+-> Result<Option<Location>, std::io::Error>
+{
+	if FileExtension::from_signature(file_buffer) != Some(FileExtension::JPEG)
+	{
+		return io_error!(Unsupported, "IPTC data is only supported for JPEG files!");
+	}
+
+	let resources = match jpg::read_photoshop_resources(file_buffer)?
+	{
+		Some(resources) => iptc::decode_resources(&resources)?,
+		None            => return Ok(None),
+	};
+	if !resources.iter().any(|resource| resource.id == iptc::IPTC_RESOURCE_ID)
+	{
+		return Ok(None);
+	}
+
+	let datasets = get_datasets(&resources)?;
+	let get_field = |(record, number, _): DatasetInfo| datasets.iter()
+		.find(|dataset| dataset.record == record && dataset.number == number)
+		.map(|dataset| String::from_utf8_lossy(&dataset.data).to_string());
+
+	return Ok(Some(Location
+	{
+		sublocation:    get_field(SUBLOCATION),
+		city:           get_field(CITY),
+		province_state: get_field(PROVINCE_STATE),
+		country_code:   get_field(COUNTRY_CODE),
+		country:        get_field(COUNTRY),
+	}));
+}
+
+/// Writes the location fields to the JPEG image, see the module
+/// documentation
+pub fn
+write_location_to_vec
+(
+	file_buffer: &mut Vec<u8>,
+	location:    &Location
+)
+-> Result<(), std::io::Error>
+{
+	if FileExtension::from_signature(file_buffer) != Some(FileExtension::JPEG)
+	{
+		return io_error!(Unsupported, "IPTC data is only supported for JPEG files!");
+	}
+
+	let mut resources = match jpg::read_photoshop_resources(file_buffer)?
+	{
+		Some(resources) => iptc::decode_resources(&resources)?,
+		None            => Vec::new(),
+	};
+
+	// Replace the location fields and declare the character set
+	let mut datasets = get_datasets(&resources)?;
+	datasets.retain(|dataset| {
+		let id = (dataset.record, dataset.number);
+		id != CODED_CHARACTER_SET && location.fields().iter().all(|(_, (record, number, _))| id != (*record, *number))
+	});
+	datasets.push(Dataset { record: CODED_CHARACTER_SET.0, number: CODED_CHARACTER_SET.1, data: UTF8_ESCAPE.to_vec() });
+	if !datasets.iter().any(|dataset| (dataset.record, dataset.number) == RECORD_VERSION)
+	{
+		datasets.push(Dataset { record: RECORD_VERSION.0, number: RECORD_VERSION.1, data: vec![0, 4] });
+	}
+	for (value, (record, number, max_bytes)) in location.fields()
+	{
+		if let Some(value) = value
+		{
+			datasets.push(Dataset { record, number, data: truncate(value, max_bytes).as_bytes().to_vec() });
+		}
+	}
+
+	// The datasets have to be in numerical order, i.e. the record version
+	// first. Repeated datasets (e.g. keywords) keep their order.
+	datasets.sort_by_key(|dataset| (dataset.record, dataset.number));
+	let iim = iptc::encode_iim(&datasets);
+
+	resources.retain(|resource| resource.id != iptc::IPTC_DIGEST_RESOURCE_ID);
+	match resources.iter_mut().find(|resource| resource.id == iptc::IPTC_RESOURCE_ID)
+	{
+		Some(resource) => resource.data = iim,
+		None           => resources.push(Resource { id: iptc::IPTC_RESOURCE_ID, name: Vec::new(), data: iim }),
+	}
+
+	return jpg::write_photoshop_resources(file_buffer, &iptc::encode_resources(&resources));
+}
+
+/// Looks up the location of the JPEG image using its GPS position and writes
+/// it (see the module documentation). Returns the location, or `None` if the
+/// image has no position or the geocoder doesn't know it - in which case the
+/// image is not changed.
+pub fn
+geocode_vec
+(
+	file_buffer: &mut Vec<u8>,
+	geocoder:    &impl ReverseGeocoder
+)
+-> Result<Option<Location>, std::io::Error>
+{
+	if FileExtension::from_signature(file_buffer) != Some(FileExtension::JPEG)
+	{
+		return io_error!(Unsupported, "IPTC data is only supported for JPEG files!");
+	}
+
+	let position = Metadata::new_from_vec(file_buffer, FileExtension::JPEG)
+		.ok()
+		.and_then(|metadata| metadata.gps_position());
+	let location = match position.and_then(|(latitude, longitude)| geocoder.reverse_geocode(latitude, longitude))
+	{
+		Some(location) => location,
+		None           => return Ok(None),
+	};
+
+	write_location_to_vec(file_buffer, &location)?;
+	return Ok(Some(location));
+}
+
+/// Looks up the location of the JPEG image at the given path and writes it,
+/// see `geocode_vec`
+pub fn
+geocode_file
+(
+	path:     &Path,
+	geocoder: &impl ReverseGeocoder
+)
+-> Result<Option<Location>, std::io::Error>
+{
+	let mut file_buffer = std::fs::read(path)?;
+	let location = geocode_vec(&mut file_buffer, geocoder)?;
+	if location.is_some()
+	{
+		write_vec_to_file(path, &file_buffer)?;
+	}
+	return Ok(location);
+}
+
+/// Looks up and writes the locations of the JPEG images at the given paths
+/// (see `geocode_vec`), returning the results in the same order. Failing
+/// images don't stop the processing of the others.
+pub fn
+geocode_files
+(
+	paths:    &[&Path],
+	geocoder: &impl ReverseGeocoder
+)
+-> Vec<Result<Option<Location>, std::io::Error>>
+{
+	paths.iter().map(|path| geocode_file(path, geocoder)).collect()
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::geocoding::*;
+
+	#[test]
+	fn
+	write_and_replace_location()
+	-> Result<(), std::io::Error>
+	{
+		// The sample already has IPTC data, but no location
+		let mut file_buffer = std::fs::read("tests/sample2.jpg")?;
+		assert_eq!(read_location_from_vec(&file_buffer)?, Some(Location::new()));
+		let resource_count = iptc::decode_resources(&jpg::read_photoshop_resources(&file_buffer)?.unwrap())?.len();
+
+		let location = Location::new()
+			.city("Sankt Johann im Pongau, Salzburger Land")
+			.country_code("AUT")
+			.country("Österreich");
+		write_location_to_vec(&mut file_buffer, &location)?;
+
+		let read_back = read_location_from_vec(&file_buffer)?.unwrap();
+		assert_eq!(read_back.city.as_deref(), Some("Sankt Johann im Pongau, Salzburg"));
+		assert_eq!(read_back.country.as_deref(), Some("Österreich"));
+		assert_eq!(read_back.sublocation, None);
+
+		// Fields of the previous location don't survive
+		write_location_to_vec(&mut file_buffer, &Location::new().city("Graz"))?;
+		assert_eq!(read_location_from_vec(&file_buffer)?, Some(Location::new().city("Graz")));
+		assert!(Metadata::new_from_vec(&file_buffer, FileExtension::JPEG).is_ok());
+		assert!(iptc::decode_resources(&jpg::read_photoshop_resources(&file_buffer)?.unwrap())?.len() <= resource_count);
+
+		assert_eq!(truncate("Wörthersee", 2), "W");
+		assert!(write_location_to_vec(&mut std::fs::read("tests/sample2.png")?, &location).is_err());
+		Ok(())
+	}
+}
@@ -0,0 +1,259 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Geoid models for converting GPS altitudes between heights above the sea
+//! level (orthometric) and above the WGS84 ellipsoid, see
+//! `Metadata::convert_gps_altitude`.
+//!
+//! The geoid approximates the mean sea level. Its height above the
+//! ellipsoid - the undulation - ranges from about -106 m to +85 m, so the
+//! ellipsoidal height of a position is its orthometric height plus the
+//! undulation there.
+//!
+//! `GeoidGrid` reads the grids provided by GeographicLib in PGM format (e.g.
+//! `egm96-5.pgm`, see https://geographiclib.sourceforge.io/C++/doc/geoid.html)
+//! and interpolates them bilinearly. Other models can be used by
+//! implementing `GeoidModel`, which is also implemented for closures.
+//!
+//! # Examples
+//! ```no_run
+//! use little_exif::geoid::GeoidGrid;
+//! use little_exif::geoid::GeoidModel;
+//!
+//! let geoid = GeoidGrid::from_path(std::path::Path::new("egm96-5.pgm")).unwrap();
+//! println!("{:?}", geoid.undulation(47.07, 15.44));
+//!
+//! // A constant undulation, e.g. for a small survey area
+//! let local = |_latitude: f64, _longitude: f64| Some(47.5);
+//! assert_eq!(local.undulation(47.07, 15.44), Some(47.5));
+//! ```
+
+use std::path::Path;
+
+use crate::general_file_io::io_error;
+
+/// A model of the height of the geoid above the WGS84 ellipsoid
+pub trait
+GeoidModel
+{
+	/// Gets the height of the geoid above the ellipsoid in meters at the
+	/// given coordinates in degrees, or `None` if the model doesn't cover
+	/// them
+	fn
+	undulation
+	(
+		&self,
+		latitude:  f64,
+		longitude: f64
+	)
+	-> Option<f64>;
+}
+
+impl<F>
+GeoidModel
+for
+F
+where
+	F: Fn(f64, f64) -> Option<f64>
+{
+	fn
+	undulation
+	(
+		&self,
+		latitude:  f64,
+		longitude: f64
+	)
+	-> Option<f64>
+	{
+		self(latitude, longitude)
+	}
+}
+
+/// A global grid of undulations in the PGM format of GeographicLib: The rows
+/// go from 90° north to 90° south, the columns eastwards from 0° longitude,
+/// both equally spaced. The 16 bit values are converted into meters using
+/// the offset and scale given in the comments of the header.
+#[derive(Debug, Clone)]
+pub struct
+GeoidGrid
+{
+	width:  usize,
+	height: usize,
+	offset: f64,
+	scale:  f64,
+	values: Vec<u16>,
+}
+
+impl
+GeoidGrid
+{
+	/// Reads the grid from the PGM file at the given path
+	pub fn
+	from_path
+	(
+		path: &Path
+	)
+	-> Result<GeoidGrid, std::io::Error>
+	{
+		GeoidGrid::from_pgm(&std::fs::read(path)?)
+	}
+
+	/// Reads the grid from the contents of a PGM file. If it is not a
+	/// binary 16 bit PGM file or lacks the offset and scale, an error of
+	/// kind `InvalidData` gets returned.
+	pub fn
+	from_pgm
+	(
+		data: &[u8]
+	)
+	-> Result<GeoidGrid, std::io::Error>
+	{
+		let mut position = 0;
+		let mut fields   = Vec::new();
+		let mut offset   = None;
+		let mut scale    = None;
+
+		// The header consists of the magic number, width, height and the
+		// maximum value, separated by whitespace and comments
+		while fields.len() < 4
+		{
+			while data.get(position).is_some_and(u8::is_ascii_whitespace)
+			{
+				position += 1;
+			}
+
+			let start = position;
+			if data.get(position) == Some(&b'#')
+			{
+				while data.get(position).is_some_and(|byte| *byte != b'\n')
+				{
+					position += 1;
+				}
+				let comment = String::from_utf8_lossy(&data[start+1..position]);
+				let mut words = comment.split_whitespace();
+				match (words.next(), words.next().and_then(|value| value.parse::<f64>().ok()))
+				{
+					(Some("Offset"), Some(value)) => offset = Some(value),
+					(Some("Scale"),  Some(value)) => scale  = Some(value),
+					_                             => (),
+				}
+				continue;
+			}
+
+			while data.get(position).is_some_and(|byte| !byte.is_ascii_whitespace() && *byte != b'#')
+			{
+				position += 1;
+			}
+			if start == position
+			{
+				return io_error!(InvalidData, "Incomplete PGM header!");
+			}
+			fields.push(String::from_utf8_lossy(&data[start..position]).to_string());
+		}
+
+		// Exactly one whitespace character separates the header from the data
+		position += 1;
+
+		let width  = fields[1].parse::<usize>().unwrap_or(0);
+		let height = fields[2].parse::<usize>().unwrap_or(0);
+		if fields[0] != "P5" || fields[3] != "65535" || width < 2 || height < 2
+		{
+			return io_error!(InvalidData, "Not a binary 16 bit PGM file!");
+		}
+
+		let (offset, scale) = match (offset, scale)
+		{
+			(Some(offset), Some(scale)) => (offset, scale),
+			_                           => return io_error!(InvalidData, "PGM file lacks the offset and scale of the geoid grid!"),
+		};
+
+		let values = data.get(position..)
+			.and_then(|pixels| pixels.get(..width * height * 2))
+			.map(|pixels| pixels.chunks_exact(2).map(|pixel| u16::from_be_bytes([pixel[0], pixel[1]])).collect::<Vec<u16>>());
+
+		match values
+		{
+			Some(values) => Ok(GeoidGrid { width, height, offset, scale, values }),
+			None         => io_error!(InvalidData, "PGM file is truncated!"),
+		}
+	}
+
+	/// Gets the undulation at the given row and column in meters
+	fn
+	get_value
+	(
+		&self,
+		row:    usize,
+		column: usize
+	)
+	-> f64
+	{
+		self.offset + self.scale * self.values[row * self.width + column] as f64
+	}
+}
+
+impl
+GeoidModel
+for
+GeoidGrid
+{
+	fn
+	undulation
+	(
+		&self,
+		latitude:  f64,
+		longitude: f64
+	)
+	-> Option<f64>
+	{
+		if !(-90.0..=90.0).contains(&latitude) || !longitude.is_finite()
+		{
+			return None;
+		}
+
+		let y = (90.0 - latitude) / (180.0 / (self.height - 1) as f64);
+		let x = longitude.rem_euclid(360.0) / (360.0 / self.width as f64);
+
+		// The last row is the south pole, the columns wrap around
+		let row    = (y.floor() as usize).min(self.height - 2);
+		let column = (x.floor() as usize).min(self.width - 1);
+		let next   = (column + 1) % self.width;
+		let (fy, fx) = (y - row as f64, x - column as f64);
+
+		let north = self.get_value(row,     column) * (1.0 - fx) + self.get_value(row,     next) * fx;
+		let south = self.get_value(row + 1, column) * (1.0 - fx) + self.get_value(row + 1, next) * fx;
+		return Some(north * (1.0 - fy) + south * fy);
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::geoid::GeoidGrid;
+	use crate::geoid::GeoidModel;
+
+	#[test]
+	fn
+	interpolate_grid()
+	{
+		// 4 columns (0°, 90°, 180°, 270°) and 3 rows (90°, 0°, -90°) with
+		// undulations of -100 m + 0.01 m * value
+		let mut pgm = b"P5\n# Geoid file in PGM format\n# Offset -100\n# Scale 0.01\n4 3\n65535\n".to_vec();
+		for value in [10000u16, 10000, 10000, 10000, 12000, 14000, 16000, 18000, 5000, 5000, 5000, 5000]
+		{
+			pgm.extend(value.to_be_bytes());
+		}
+
+		let grid = GeoidGrid::from_pgm(&pgm).unwrap();
+		assert_eq!(grid.undulation(90.0,   123.0), Some(0.0));
+		assert_eq!(grid.undulation(0.0,    90.0),  Some(40.0));
+		assert_eq!(grid.undulation(0.0,    45.0),  Some(30.0));
+		assert_eq!(grid.undulation(0.0,    -45.0), Some(50.0));
+		assert_eq!(grid.undulation(-45.0,  0.0),   Some(-15.0));
+		assert_eq!(grid.undulation(-90.0,  0.0),   Some(-50.0));
+		assert_eq!(grid.undulation(90.5,   0.0),   None);
+
+		assert!(GeoidGrid::from_pgm(&pgm[..pgm.len() - 1]).is_err());
+		assert!(GeoidGrid::from_pgm(b"P5\n4 3\n65535\n").is_err());
+	}
+}
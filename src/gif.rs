@@ -0,0 +1,553 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! GIF files consist of the header, the logical screen descriptor (with the
+//! optional global color table) and a sequence of blocks: Images, extensions
+//! and the trailer ending the data stream. Extensions carry their data in
+//! sub-blocks of up to 255 bytes, each preceded by its size and the last one
+//! followed by a zero size byte.
+//!
+//! Metadata is stored in application extensions, identified by an 8 byte
+//! identifier and a 3 byte authentication code:
+//! - XMP packets use `XMP DataXMP`. The packet is written as it is instead
+//!   of being split into sub-blocks, followed by a "magic trailer" of 258
+//!   bytes that lets readers unaware of this skip the packet as if it were
+//!   sub-blocks (see part 3 of the XMP specification).
+//! - There is no standardized extension for EXIF data. little_exif uses
+//!   `EXIFDATAEXF` with the EXIF data (starting with `Exif\0\0`) split into
+//!   sub-blocks, and reads any application extension whose identifier starts
+//!   with `EXIF` (ignoring its case), as long as it contains EXIF data.
+//!
+//! Both are placed in front of the first image (or rather the graphic
+//! control extension belonging to it), after any other extensions already
+//! located there (e.g. `NETSCAPE2.0` for looping). As extensions require
+//! version 89a, files of version 87a get upgraded when writing.
+
+use std::io::Read;
+use std::io::Seek;
+use std::path::Path;
+
+use crate::chunk_types::GifBlockType;
+use crate::errors::MissingExif;
+use crate::exif_block;
+use crate::exif_block::ExifPrefix;
+use crate::general_file_io::*;
+use crate::vfs::FileSystem;
+use crate::vfs::ReadSeek;
+
+pub(crate) const GIF_SIGNATURES: [[u8; 6]; 2] = [*b"GIF87a", *b"GIF89a"];
+
+const LOGICAL_SCREEN_END:   usize     = 13;                                     // Header and logical screen descriptor
+const EXIF_APPLICATION:     [u8; 11]  = *b"EXIFDATAEXF";
+const XMP_APPLICATION:      [u8; 11]  = *b"XMP DataXMP";
+const MAX_SUB_BLOCK_SIZE:   usize     = 255;
+
+/// Opens the GIF file at the given path for reading
+fn
+open_file
+(
+	file_system: &dyn FileSystem,
+	path:        &Path
+)
+-> Result<Box<dyn ReadSeek>, std::io::Error>
+{
+	if !file_system.exists(path)
+	{
+		return io_error!(NotFound, "Can't open GIF file - File does not exist!");
+	}
+
+	return file_system.open(path);
+}
+
+/// A block of the data stream, given by its position in the data. For
+/// application extensions, the identifier and authentication code is given
+struct
+GifBlock
+{
+	block_type:  GifBlockType,
+	start:       usize,
+	end:         usize,
+	application: Option<[u8; 11]>,
+}
+
+impl
+GifBlock
+{
+	fn
+	is_exif
+	(
+		&self
+	)
+	-> bool
+	{
+		self.application.is_some_and(|application| application[..4].eq_ignore_ascii_case(b"EXIF"))
+	}
+
+	fn
+	is_xmp
+	(
+		&self
+	)
+	-> bool
+	{
+		self.application == Some(XMP_APPLICATION)
+	}
+}
+
+/// The magic trailer following XMP packets: 0x01, 0xff down to 0x00 and the
+/// block terminator
+fn
+xmp_magic_trailer
+()
+-> Vec<u8>
+{
+	let mut trailer = vec![0x01];
+	trailer.extend((0..=0xff).rev());
+	trailer.push(0x00);
+	return trailer;
+}
+
+/// Gets the position after the sub-blocks starting at the given position,
+/// i.e. after the terminating zero size byte
+fn
+skip_sub_blocks
+(
+	data:     &[u8],
+	position: usize
+)
+-> Result<usize, std::io::Error>
+{
+	let mut position = position;
+	loop
+	{
+		match data.get(position)
+		{
+			Some(0)    => return Ok(position + 1),
+			Some(size) => position += 1 + *size as usize,
+			None       => return io_error!(InvalidData, "Can't read GIF file - Truncated sub-blocks!"),
+		}
+	}
+}
+
+/// Gets the data of the sub-blocks starting at the given position
+fn
+read_sub_blocks
+(
+	data:     &[u8],
+	position: usize
+)
+-> Vec<u8>
+{
+	let mut content = Vec::new();
+	let mut position = position;
+	while let Some(size) = data.get(position).map(|size| *size as usize).filter(|size| *size > 0)
+	{
+		content.extend(data.get(position + 1..position + 1 + size).unwrap_or_default());
+		position += 1 + size;
+	}
+	return content;
+}
+
+/// Splits the data into sub-blocks, including the terminating zero size byte
+fn
+write_sub_blocks
+(
+	content: &[u8]
+)
+-> Vec<u8>
+{
+	let mut data = Vec::with_capacity(content.len() + content.len() / MAX_SUB_BLOCK_SIZE + 2);
+	for sub_block in content.chunks(MAX_SUB_BLOCK_SIZE)
+	{
+		data.push(sub_block.len() as u8);
+		data.extend(sub_block);
+	}
+	data.push(0x00);
+	return data;
+}
+
+/// Checks the signature and gets the position of the first block, i.e. after
+/// the global color table if there is one
+fn
+first_block_position
+(
+	data: &[u8]
+)
+-> Result<usize, std::io::Error>
+{
+	if !GIF_SIGNATURES.iter().any(|signature| data.starts_with(signature))
+	{
+		return io_error!(InvalidData, "Can't open GIF file - Wrong signature!");
+	}
+
+	let Some(flags) = data.get(LOGICAL_SCREEN_END - 3) else
+	{
+		return io_error!(InvalidData, "Can't read GIF file - Truncated logical screen descriptor!");
+	};
+
+	// The size of the color table is given as exponent of its entry count
+	let color_table_size = match flags & 0x80
+	{
+		0 => 0,
+		_ => 3 * (2 << (flags & 0x07)),
+	};
+	return Ok(LOGICAL_SCREEN_END + color_table_size);
+}
+
+/// Gets the blocks of the data stream, up to and including the trailer. Data
+/// following the trailer is ignored, a missing trailer is tolerated
+fn
+parse_gif
+(
+	data: &[u8]
+)
+-> Result<Vec<GifBlock>, std::io::Error>
+{
+	let mut blocks = Vec::new();
+	let mut position = first_block_position(data)?;
+	while let Some(introducer) = data.get(position)
+	{
+		let label = data.get(position + 1).copied().unwrap_or_default();
+		let Some(block_type) = GifBlockType::from_bytes(*introducer, label) else
+		{
+			return io_error!(InvalidData, format!("Can't read GIF file - Unknown block introducer {:#04x}!", introducer));
+		};
+
+		let mut application = None;
+		let end = match block_type
+		{
+			GifBlockType::Trailer => position + 1,
+			GifBlockType::Image   => {
+				// Image descriptor, local color table, LZW code size and the
+				// image data in sub-blocks
+				let flags = data.get(position + 9).copied().unwrap_or_default();
+				let color_table_size = match flags & 0x80
+				{
+					0 => 0,
+					_ => 3 * (2 << (flags & 0x07)),
+				};
+				skip_sub_blocks(data, position + 10 + color_table_size + 1)?
+			},
+			GifBlockType::Application => {
+				if data.get(position + 2) == Some(&11)
+				{
+					application = data.get(position + 3..position + 14).map(|identifier| identifier.try_into().unwrap());
+				}
+				skip_sub_blocks(data, position + 2)?
+			},
+			_ => skip_sub_blocks(data, position + 2)?,
+		};
+
+		blocks.push(GifBlock { block_type, start: position, end, application });
+		if block_type == GifBlockType::Trailer
+		{
+			break;
+		}
+		position = end;
+	}
+	return Ok(blocks);
+}
+
+/// Gets the position where metadata gets inserted: In front of the first
+/// image, its graphic control extension or plain text
+fn
+metadata_insert_position
+(
+	data:   &[u8],
+	blocks: &[GifBlock]
+)
+-> usize
+{
+	blocks.iter()
+		.find(|block| !matches!(block.block_type, GifBlockType::Application | GifBlockType::Comment | GifBlockType::Extension(_)))
+		.map(|block| block.start)
+		.unwrap_or(data.len())
+}
+
+/// Removes the application extensions matching the predicate
+fn
+remove_applications
+(
+	file_buffer: &mut Vec<u8>,
+	predicate:   impl Fn(&GifBlock) -> bool
+)
+-> Result<(), std::io::Error>
+{
+	let blocks = parse_gif(file_buffer)?;
+	for block in blocks.iter().rev().filter(|block| predicate(block))
+	{
+		file_buffer.drain(block.start..block.end);
+	}
+	return Ok(());
+}
+
+/// Inserts the given application extension in front of the first image and
+/// upgrades the file to version 89a if necessary
+fn
+insert_application
+(
+	file_buffer: &mut Vec<u8>,
+	application: Vec<u8>
+)
+-> Result<(), std::io::Error>
+{
+	let blocks = parse_gif(file_buffer)?;
+	let insert_position = metadata_insert_position(file_buffer, &blocks);
+	file_buffer.splice(insert_position..insert_position, application);
+	file_buffer[..6].copy_from_slice(&GIF_SIGNATURES[1]);
+	return Ok(());
+}
+
+/// Builds the application extension with the given identifier, without the
+/// data following it
+fn
+application_header
+(
+	identifier: &[u8; 11]
+)
+-> Vec<u8>
+{
+	let (introducer, label) = GifBlockType::Application.as_bytes();
+	let mut header = vec![introducer, label.unwrap_or_default(), identifier.len() as u8];
+	header.extend(identifier);
+	return header;
+}
+
+/// Reads the raw EXIF data from the first EXIF application extension of the
+/// GIF file
+pub(crate) fn
+read_metadata
+<T: Read + Seek>
+(
+	cursor: &mut T
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	let _span = span!("parse", format = "GIF");
+
+	let mut file_buffer = Vec::new();
+	cursor.read_to_end(&mut file_buffer)?;
+
+	for block in parse_gif(&file_buffer)?.iter().filter(|block| block.is_exif())
+	{
+		// Other applications may use similar identifiers
+		if let Ok(exif_data) = exif_block::normalize(&read_sub_blocks(&file_buffer, block.start + 14))
+		{
+			return Ok(exif_data);
+		}
+	}
+	return MissingExif::error("No EXIF data found!");
+}
+
+
+
+pub(crate) fn
+file_read_metadata
+(
+	file_system: &dyn FileSystem,
+	path:        &Path
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	read_metadata(&mut open_file(file_system, path)?)
+}
+
+
+
+/// Gets the width and height of the logical screen
+pub(crate) fn
+image_dimensions
+(
+	file_buffer: &[u8]
+)
+-> Option<(u32, u32)>
+{
+	first_block_position(file_buffer).ok()?;
+	let dimension = |position: usize| u16::from_le_bytes([file_buffer[position], file_buffer[position + 1]]) as u32;
+	return Some((dimension(6), dimension(8)));
+}
+
+
+
+/// Removes all EXIF application extensions from the GIF data
+pub(crate) fn
+clear_metadata
+(
+	file_buffer: &mut Vec<u8>
+)
+-> Result<(), std::io::Error>
+{
+	remove_applications(file_buffer, GifBlock::is_exif)
+}
+
+
+
+pub(crate) fn
+file_clear_metadata
+(
+	file_system: &dyn FileSystem,
+	path:        &Path
+)
+-> Result<(), std::io::Error>
+{
+	let mut file_buffer = read_file_to_vec(open_file(file_system, path)?)?;
+	let original_buffer = file_buffer.clone();
+	clear_metadata(&mut file_buffer)?;
+	if file_buffer != original_buffer
+	{
+		file_system.write(path, &file_buffer)?;
+	}
+	return Ok(());
+}
+
+
+
+/// Provides the GIF specific encoding result as vector of bytes to be used
+/// by the user, i.e. the complete application extension
+pub(crate) fn
+as_u8_vec
+(
+	general_encoded_metadata: &Vec<u8>
+)
+-> Vec<u8>
+{
+	let mut application = application_header(&EXIF_APPLICATION);
+	application.extend(write_sub_blocks(&exif_block::add_prefix(general_encoded_metadata, ExifPrefix::ExifHeader)));
+	return application;
+}
+
+
+
+/// Writes the given generally encoded metadata to the GIF data, replacing
+/// all previously stored EXIF application extensions
+pub(crate) fn
+write_metadata
+(
+	file_buffer:              &mut Vec<u8>,
+	general_encoded_metadata: &Vec<u8>
+)
+-> Result<(), std::io::Error>
+{
+	clear_metadata(file_buffer)?;
+	return insert_application(file_buffer, as_u8_vec(general_encoded_metadata));
+}
+
+
+
+/// Writes the given generally encoded metadata to the GIF image file at the
+/// specified path (see `write_metadata`)
+pub(crate) fn
+file_write_metadata
+(
+	file_system:              &dyn FileSystem,
+	path:                     &Path,
+	general_encoded_metadata: &Vec<u8>
+)
+-> Result<(), std::io::Error>
+{
+	let mut file_buffer = read_file_to_vec(open_file(file_system, path)?)?;
+	write_metadata(&mut file_buffer, general_encoded_metadata)?;
+	file_system.write(path, &file_buffer)?;
+	return Ok(());
+}
+
+
+
+/// Reads the XMP packet of the GIF data, i.e. the content of the first XMP
+/// application extension without the magic trailer. Returns `None` if there
+/// is none.
+pub(crate) fn
+read_xmp
+(
+	file_buffer: &[u8]
+)
+-> Result<Option<Vec<u8>>, std::io::Error>
+{
+	let blocks = parse_gif(file_buffer)?;
+	let Some(block) = blocks.iter().find(|block| block.is_xmp()) else
+	{
+		return Ok(None);
+	};
+
+	let trailer = xmp_magic_trailer();
+	let content = &file_buffer[block.start + 14..block.end];
+	match content.strip_suffix(trailer.as_slice())
+	{
+		Some(packet) => Ok(Some(packet.to_vec())),
+		None         => Ok(Some(read_sub_blocks(file_buffer, block.start + 14))),
+	}
+}
+
+/// Replaces the XMP packet of the GIF data by the given one. The EXIF data
+/// is not changed.
+pub(crate) fn
+write_xmp
+(
+	file_buffer: &mut Vec<u8>,
+	packet:      &[u8]
+)
+-> Result<(), std::io::Error>
+{
+	// A zero byte within the packet would end the extension early
+	if packet.contains(&0x00)
+	{
+		return io_error!(InvalidInput, "Can't write XMP packet to GIF file - Contains NUL characters!");
+	}
+
+	remove_applications(file_buffer, GifBlock::is_xmp)?;
+
+	let mut application = application_header(&XMP_APPLICATION);
+	application.extend(packet);
+	application.extend(xmp_magic_trailer());
+	return insert_application(file_buffer, application);
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::gif::parse_gif;
+	use crate::gif::read_sub_blocks;
+	use crate::gif::skip_sub_blocks;
+	use crate::gif::write_sub_blocks;
+	use crate::gif::xmp_magic_trailer;
+
+	#[test]
+	fn
+	sub_blocks()
+	{
+		for length in [0, 1, 254, 255, 256, 1000]
+		{
+			let content = (0..length).map(|index| index as u8).collect::<Vec<u8>>();
+			let data = write_sub_blocks(&content);
+			assert_eq!(read_sub_blocks(&data, 0), content);
+			assert_eq!(skip_sub_blocks(&data, 0).unwrap(), data.len());
+		}
+		assert!(skip_sub_blocks(&[0x03, 0x01, 0x02], 0).is_err());
+	}
+
+	#[test]
+	fn
+	magic_trailer()
+	{
+		// Reading the packet as sub-blocks ends at the end of the trailer,
+		// regardless of where in the trailer the reader enters it
+		let trailer = xmp_magic_trailer();
+		assert_eq!(trailer.len(), 258);
+		for packet in [b"".to_vec(), b"<x:xmpmeta/>".to_vec(), vec![b'x'; 300]]
+		{
+			let data = [packet.clone(), trailer.clone()].concat();
+			assert_eq!(skip_sub_blocks(&data, 0).unwrap(), data.len(), "{}", packet.len());
+		}
+	}
+
+	#[test]
+	fn
+	broken_files()
+	{
+		assert!(parse_gif(b"GIF89a").is_err());
+		assert!(parse_gif(b"GIF90a\x01\x00\x01\x00\x00\x00\x00").is_err());
+		assert!(parse_gif(b"GIF89a\x01\x00\x01\x00\x00\x00\x00\x42").is_err());
+		assert!(parse_gif(b"GIF89a\x01\x00\x01\x00\x00\x00\x00\x21\xff\x0b").is_err());
+		assert_eq!(parse_gif(b"GIF89a\x01\x00\x01\x00\x00\x00\x00").unwrap().len(), 0);
+	}
+}
@@ -0,0 +1,229 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Typed access to the GPS tags describing where the image was taken, where
+//! the camera was heading and where it was pointed at, e.g. for navigation
+//! and survey applications. See `Metadata::gps_position`,
+//! `Metadata::gps_altitude`, `Metadata::gps_destination`,
+//! `Metadata::gps_img_direction`, `Metadata::gps_track` and
+//! `Metadata::gps_dop`.
+//!
+//! Values are handled as decimal numbers and converted from and to the
+//! rationals (and references like `N`/`S`) stored in the GPS IFD:
+//! - Coordinates are in degrees, negative for south and west. They are
+//!   stored as degrees, minutes and seconds with a precision of 1/1000 of a
+//!   second (about 3 cm).
+//! - Altitudes are in meters, negative for below the reference, and stored
+//!   with a precision of 1 mm. The reference is either the sea level
+//!   (orthometric height, as shown on maps) or the WGS84 ellipsoid
+//!   (ellipsoidal height, as computed by GPS receivers). The latter is only
+//!   defined since EXIF 3.0, but many drones write ellipsoidal heights
+//!   labeled as sea level anyway - which is why they may be off by up to
+//!   100 m. With the `geoid` feature, `Metadata::convert_gps_altitude`
+//!   converts between both using a geoid model, see the `geoid` module.
+//! - Directions are in degrees from 0 up to (excluding) 360, relative to true
+//!   or magnetic north, stored with a precision of 1/100 of a degree.
+//! - The dilution of precision is stored with a precision of 1/1000.
+
+/// The north a direction is relative to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum
+GpsDirectionRef
+{
+	/// Geographic north, stored as `T`
+	TrueNorth,
+
+	/// Magnetic north, stored as `M`
+	MagneticNorth,
+}
+
+impl
+GpsDirectionRef
+{
+	/// Gets the value of the reference tag, e.g. `GPSTrackRef`
+	pub(crate) fn
+	as_ref_value
+	(
+		&self
+	)
+	-> &'static str
+	{
+		match self
+		{
+			GpsDirectionRef::TrueNorth     => "T",
+			GpsDirectionRef::MagneticNorth => "M",
+		}
+	}
+
+	/// Gets the reference for the value of a reference tag
+	pub(crate) fn
+	from_ref_value
+	(
+		value: &str
+	)
+	-> Option<GpsDirectionRef>
+	{
+		match value
+		{
+			"T" => Some(GpsDirectionRef::TrueNorth),
+			"M" => Some(GpsDirectionRef::MagneticNorth),
+			_   => None,
+		}
+	}
+}
+
+/// The reference level of an altitude
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum
+GpsAltitudeRef
+{
+	/// The mean sea level, i.e. the geoid, stored as `0` (above) or `1`
+	/// (below). This is also assumed if the reference is missing.
+	SeaLevel,
+
+	/// The WGS84 ellipsoid, stored as `2` (above) or `3` (below)
+	Ellipsoid,
+}
+
+impl
+GpsAltitudeRef
+{
+	/// Gets the value of the `GPSAltitudeRef` tag for an altitude above or
+	/// below this reference
+	pub(crate) fn
+	as_ref_value
+	(
+		&self,
+		below: bool
+	)
+	-> u8
+	{
+		match self
+		{
+			GpsAltitudeRef::SeaLevel  => below as u8,
+			GpsAltitudeRef::Ellipsoid => 2 + below as u8,
+		}
+	}
+
+	/// Gets the reference for the value of the `GPSAltitudeRef` tag and
+	/// whether the altitude is below it
+	pub(crate) fn
+	from_ref_value
+	(
+		value: u8
+	)
+	-> Option<(GpsAltitudeRef, bool)>
+	{
+		match value
+		{
+			0 => Some((GpsAltitudeRef::SeaLevel,  false)),
+			1 => Some((GpsAltitudeRef::SeaLevel,  true)),
+			2 => Some((GpsAltitudeRef::Ellipsoid, false)),
+			3 => Some((GpsAltitudeRef::Ellipsoid, true)),
+			_ => None,
+		}
+	}
+}
+
+/// Converts an absolute coordinate into degrees, minutes and seconds
+pub(crate) fn
+degrees_to_dms
+(
+	degrees: f64
+)
+-> Vec<u32>
+{
+	let milliseconds = (degrees.abs() * 3_600_000.0).round() as u64;
+	return vec![
+		(milliseconds / 3_600_000) as u32,      1,
+		(milliseconds / 60_000 % 60) as u32,    1,
+		(milliseconds % 60_000) as u32,         1000,
+	];
+}
+
+/// Converts degrees, minutes and seconds into an absolute coordinate.
+/// Returns `None` if the value is malformed.
+pub(crate) fn
+dms_to_degrees
+(
+	dms: &[u32]
+)
+-> Option<f64>
+{
+	if dms.len() != 6
+	{
+		return None;
+	}
+	let degrees = rational_to_f64(&dms[0..2])?;
+	let minutes = rational_to_f64(&dms[2..4])?;
+	let seconds = rational_to_f64(&dms[4..6])?;
+	return Some(degrees + minutes / 60.0 + seconds / 3600.0);
+}
+
+/// Converts a non-negative value into a rational with the given denominator
+pub(crate) fn
+f64_to_rational
+(
+	value:       f64,
+	denominator: u32
+)
+-> Vec<u32>
+{
+	vec![(value * denominator as f64).round() as u32, denominator]
+}
+
+/// Converts a rational into a decimal number. Returns `None` if the value is
+/// malformed or the denominator is zero.
+pub(crate) fn
+rational_to_f64
+(
+	rational: &[u32]
+)
+-> Option<f64>
+{
+	match rational
+	{
+		[numerator, denominator] if *denominator != 0 => Some(*numerator as f64 / *denominator as f64),
+		_                                            => None,
+	}
+}
+
+/// Brings a direction into the range from 0 up to (excluding) 360 degrees,
+/// taking the rounding to 1/100 degree into account
+pub(crate) fn
+normalize_direction
+(
+	degrees: f64
+)
+-> f64
+{
+	let normalized = degrees.rem_euclid(360.0);
+	if (normalized * 100.0).round() >= 36000.0
+	{
+		return 0.0;
+	}
+	return normalized;
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::gps::degrees_to_dms;
+	use crate::gps::dms_to_degrees;
+	use crate::gps::normalize_direction;
+
+	#[test]
+	fn
+	convert_coordinates()
+	{
+		assert_eq!(degrees_to_dms(48.858222),  vec![48, 1, 51, 1, 29599, 1000]);
+		assert_eq!(degrees_to_dms(-2.2945),    vec![2, 1, 17, 1, 40200, 1000]);
+		assert_eq!(degrees_to_dms(9.9999999),  vec![10, 1, 0, 1, 0, 1000]);
+		assert!((dms_to_degrees(&degrees_to_dms(48.858222)).unwrap() - 48.858222).abs() < 1e-6);
+		assert_eq!(dms_to_degrees(&[48, 0, 51, 1, 0, 1]), None);
+
+		assert_eq!(normalize_direction(-90.0),    270.0);
+		assert_eq!(normalize_direction(359.999),  0.0);
+		assert_eq!(normalize_direction(720.5),    0.5);
+	}
+}
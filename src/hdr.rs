@@ -0,0 +1,293 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Detecting the exposure brackets in a set of images, i.e. the series of
+//! shots with varying exposure that HDR merge tools combine into one image.
+//!
+//! Frames are considered in chronological order (see the `burst` module for
+//! the tags used), separately for each camera. A frame continues the current
+//! bracket of its camera if it was taken within the given maximum interval
+//! after the previous frame and
+//! - its `BracketShotNumber` (stored in the maker notes, see
+//!   `MakerNote::bracket`) follows the one of the previous frame, or
+//! - if there is no shot number, its `ExposureCompensation` (also known as
+//!   `ExposureBiasValue`) differs from the ones of all frames of the bracket.
+//!
+//! Only series of at least two frames with different exposures (or with
+//! shot numbers marking them as exposure bracket) are reported.
+//!
+//! # Examples
+//! ```no_run
+//! use std::path::Path;
+//! use std::time::Duration;
+//! use little_exif::hdr::detect_exposure_brackets;
+//!
+//! let paths = [Path::new("IMG_0001.jpg"), Path::new("IMG_0002.jpg"), Path::new("IMG_0003.jpg")];
+//! for bracket in detect_exposure_brackets(&paths, Duration::from_secs(2)).unwrap()
+//! {
+//!     println!("Merge {:?}, base frame {}", bracket.frames, bracket.base_frame());
+//! }
+//! ```
+
+use std::path::Path;
+use std::time::Duration;
+
+use crate::burst::camera_identity;
+use crate::burst::capture_time_millis;
+use crate::environment::signed_rational_to_f64;
+use crate::exif_tag::ExifTag;
+use crate::maker_note::BracketKind;
+use crate::metadata::Metadata;
+
+/// An exposure bracket, see `detect_exposure_brackets`
+#[derive(Debug, Clone, PartialEq)]
+pub struct
+ExposureBracket
+{
+	pub frames:          Vec<usize>,                                            // Indices of the paths, chronologically
+	pub exposure_biases: Vec<Option<f64>>,                                      // In EV, for each of the frames
+}
+
+impl
+ExposureBracket
+{
+	/// Gets the index of the path of the frame whose exposure is closest to
+	/// the metered one, which HDR merge tools usually align the others to.
+	/// Frames without a known exposure bias are only used if no frame has one.
+	pub fn
+	base_frame
+	(
+		&self
+	)
+	-> usize
+	{
+		self.frames.iter()
+			.zip(&self.exposure_biases)
+			.min_by(|(_, a), (_, b)| a.map(f64::abs).unwrap_or(f64::INFINITY).total_cmp(&b.map(f64::abs).unwrap_or(f64::INFINITY)))
+			.map(|(frame, _)| *frame)
+			.unwrap_or_default()
+	}
+}
+
+/// The information about a frame that is relevant for detecting brackets
+#[derive(Debug, Clone, PartialEq)]
+struct
+Frame
+{
+	camera:        Vec<String>,
+	timestamp:     i64,                                                         // In milliseconds
+	exposure_bias: Option<f64>,
+	shot_number:   Option<u32>,                                                 // Of an exposure bracket
+}
+
+impl
+Frame
+{
+	/// Gets the information from the metadata. Returns `None` if it doesn't
+	/// tell when the image was taken.
+	fn
+	from_metadata
+	(
+		metadata: &Metadata
+	)
+	-> Option<Frame>
+	{
+		let exposure_bias = match metadata.get_tag(&ExifTag::ExposureCompensation(Vec::new()))
+		{
+			Some(ExifTag::ExposureCompensation(value)) => signed_rational_to_f64(value),
+			_                                          => None,
+		};
+
+		let shot_number = metadata.maker_note()
+			.and_then(|maker_note| maker_note.bracket())
+			.filter(|bracket| bracket.kinds.contains(&BracketKind::Exposure))
+			.map(|bracket| bracket.shot_number);
+
+		return Some(Frame
+		{
+			camera:        camera_identity(metadata),
+			timestamp:     capture_time_millis(metadata)?,
+			exposure_bias,
+			shot_number,
+		});
+	}
+}
+
+/// Checks whether the frame continues the bracket, see module documentation
+fn
+continues_bracket
+(
+	frames:       &[Option<Frame>],
+	bracket:      &[usize],
+	frame:        &Frame,
+	max_interval: i64
+)
+-> bool
+{
+	let previous = match bracket.last().and_then(|index| frames[*index].as_ref())
+	{
+		Some(previous) => previous,
+		None           => return false,
+	};
+
+	if frame.timestamp - previous.timestamp > max_interval
+	{
+		return false;
+	}
+
+	if let (Some(previous_shot), Some(shot)) = (previous.shot_number, frame.shot_number)
+	{
+		return previous_shot.checked_add(1) == Some(shot);
+	}
+
+	// Without shot numbers, the exposure has to change for every frame
+	let bias = match frame.exposure_bias
+	{
+		Some(bias) => bias,
+		None       => return false,
+	};
+	return bracket.iter().all(|index| frames[*index]
+		.as_ref()
+		.and_then(|other| other.exposure_bias)
+		.is_some_and(|other_bias| (other_bias - bias).abs() > 1e-6)
+	);
+}
+
+/// Checks whether the frames of a finished series form an exposure bracket
+fn
+is_bracket
+(
+	frames:  &[Option<Frame>],
+	bracket: &[usize]
+)
+-> bool
+{
+	if bracket.len() < 2
+	{
+		return false;
+	}
+	let frames = bracket.iter().filter_map(|index| frames[*index].as_ref()).collect::<Vec<&Frame>>();
+	let has_shot_numbers = frames.iter().all(|frame| frame.shot_number.is_some());
+	let has_varying_bias = frames.iter().filter_map(|frame| frame.exposure_bias).any(|bias| frames[0].exposure_bias.is_some_and(|first| (first - bias).abs() > 1e-6));
+	return has_shot_numbers || has_varying_bias;
+}
+
+/// Detects the brackets among the frames, see `detect_exposure_brackets`
+fn
+detect_brackets
+(
+	frames:       &[Option<Frame>],
+	max_interval: Duration
+)
+-> Vec<Vec<usize>>
+{
+	let mut order = (0..frames.len())
+		.filter(|index| frames[*index].is_some())
+		.collect::<Vec<usize>>();
+	order.sort_by_key(|index| (frames[*index].as_ref().map(|frame| frame.timestamp), *index));
+
+	let max_interval = max_interval.as_millis() as i64;
+	let mut brackets = Vec::new();
+
+	// The camera and the frames of each camera's current series
+	let mut open_series: Vec<(&Vec<String>, Vec<usize>)> = Vec::new();
+
+	for index in order
+	{
+		let frame = match &frames[index]
+		{
+			Some(frame) => frame,
+			None        => continue,
+		};
+
+		match open_series.iter_mut().find(|(camera, _)| **camera == frame.camera)
+		{
+			Some((_, series)) if continues_bracket(frames, series, frame, max_interval) => series.push(index),
+			Some((_, series)) => brackets.push(std::mem::replace(series, vec![index])),
+			None              => open_series.push((&frame.camera, vec![index])),
+		}
+	}
+	brackets.extend(open_series.into_iter().map(|(_, series)| series));
+
+	// Chronological order of the brackets
+	brackets.retain(|bracket| is_bracket(frames, bracket));
+	brackets.sort_by_key(|bracket| (frames[bracket[0]].as_ref().map(|frame| frame.timestamp), bracket[0]));
+	return brackets;
+}
+
+/// Detects the exposure brackets among the images at the given paths (see
+/// module documentation), in chronological order. Images without a
+/// timestamp or that are not part of a bracket are not included.
+/// - If one of the files can't be read (e.g. because it doesn't exist or has
+///   an unsupported type), an error gets returned.
+pub fn
+detect_exposure_brackets
+(
+	paths:        &[&Path],
+	max_interval: Duration
+)
+-> Result<Vec<ExposureBracket>, std::io::Error>
+{
+	let mut frames = Vec::new();
+	for path in paths
+	{
+		frames.push(Frame::from_metadata(&Metadata::new_from_path(path)?));
+	}
+
+	return Ok(detect_brackets(&frames, max_interval)
+		.into_iter()
+		.map(|bracket| ExposureBracket
+		{
+			exposure_biases: bracket.iter().map(|index| frames[*index].as_ref().and_then(|frame| frame.exposure_bias)).collect(),
+			frames:          bracket,
+		})
+		.collect()
+	);
+}
+
+#[cfg(test)]
+mod tests
+{
+	use std::time::Duration;
+
+	use crate::hdr::ExposureBracket;
+	use crate::hdr::Frame;
+	use crate::hdr::detect_brackets;
+
+	fn
+	frame
+	(
+		model:         &str,
+		timestamp:     i64,
+		exposure_bias: Option<f64>,
+		shot_number:   Option<u32>
+	)
+	-> Option<Frame>
+	{
+		Some(Frame { camera: vec!["Cam".to_string(), model.to_string(), String::new()], timestamp, exposure_bias, shot_number })
+	}
+
+	#[test]
+	fn
+	detect_exposure_brackets()
+	{
+		let frames = [
+			frame("A", 1_000, Some(0.0),  None),
+			frame("A", 1_100, Some(-2.0), None),
+			frame("A", 1_200, Some(2.0),  None),
+			frame("A", 1_300, Some(0.0),  None),                                // Repeated exposure starts the next one
+			frame("B", 1_250, Some(0.0),  Some(1)),                             // Other camera, interleaved
+			frame("B", 1_350, Some(0.0),  Some(2)),                             // Same exposure, but numbered
+			frame("A", 1_400, Some(-1.0), None),
+			None,
+			frame("A", 9_000, Some(1.0),  None),                                // Gap too large, alone
+			frame("B", 9_100, Some(0.0),  None),                                // Single frame
+		];
+
+		assert_eq!(detect_brackets(&frames, Duration::from_secs(1)), vec![vec![0, 1, 2], vec![4, 5], vec![3, 6]]);
+		assert_eq!(detect_brackets(&frames, Duration::from_millis(50)), Vec::<Vec<usize>>::new());
+
+		let bracket = ExposureBracket { frames: vec![0, 1, 2], exposure_biases: vec![Some(-2.0), None, Some(0.3)] };
+		assert_eq!(bracket.base_frame(), 2);
+	}
+}
@@ -0,0 +1,1154 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! HEIF files (e.g. the HEIC photos of phones) and AVIF files (which use the
+//! same structure for AV1 coded images) are ISOBMFF files, i.e. a
+//! sequence of boxes that may contain further boxes. The images and the
+//! metadata are items described by the boxes within the `meta` box: `iinf`
+//! gives the type of each item, `iloc` where its data is located (usually
+//! in an `mdat` box, sometimes in the `idat` box within `meta`) and `iref`
+//! how the items relate to each other. The EXIF data is the item of type
+//! `Exif`, which describes the primary image by a `cdsc` reference. Its data
+//! starts with the offset to the TIFF header (see `exif_block`).
+//!
+//! Writing never moves the data of the other items, as their locations are
+//! spread across `iloc` and possibly other boxes. Instead, the EXIF data is
+//! appended in a new `mdat` box and the item locations get updated - the
+//! ones pointing behind the `meta` box are shifted if its size changes.
+//! The previous EXIF data gets overwritten with zeros, so that it can't be
+//! recovered after clearing or replacing it. If it is located in an `mdat`
+//! box of its own at the end of the file (as after a previous write), that
+//! box gets removed instead, so that writing over and over again doesn't let
+//! the file grow. Image sequences (which have a `moov` box with sample
+//! offsets) can't be written to if this would require shifting data.
+
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::chunk_types::IsoBoxType;
+use crate::errors::MissingExif;
+use crate::errors::ParseErrorContext;
+use crate::errors::WithParseContext;
+use crate::exif_block;
+use crate::exif_block::ExifPrefix;
+use crate::general_file_io::*;
+use crate::vfs::FileSystem;
+use crate::vfs::ReadSeek;
+
+/// The brands (given by the `ftyp` box) of HEIF files with HEVC coded images
+/// or images of an unspecified coding
+pub(crate) const HEIF_BRANDS: [[u8; 4]; 8] = [
+	*b"heic", *b"heix", *b"heim", *b"heis",                                     // HEVC coded images
+	*b"hevc", *b"hevx",                                                         // HEVC coded image sequences
+	*b"mif1", *b"msf1",                                                         // Unspecified coding
+];
+
+/// The brands of AVIF files, which are checked before the HEIF brands as
+/// AVIF files usually list `mif1` as well
+pub(crate) const AVIF_BRANDS: [[u8; 4]; 2] = [
+	*b"avif", *b"avis",                                                         // AV1 coded images and image sequences
+];
+
+const EXIF_ITEM_TYPE:    [u8; 4] = *b"Exif";
+const CONTENT_DESCRIBES: [u8; 4] = *b"cdsc";                                    // Reference from the EXIF item to the image
+
+/// The maximum number of bytes of a box header: Size, type, large size and
+/// the UUID of `uuid` boxes
+const MAX_HEADER_SIZE: usize = 32;
+
+/// A box, given by its type and where it and its payload are located
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct
+IsoBox
+{
+	pub(crate) box_type:      IsoBoxType,
+	pub(crate) start:         usize,
+	pub(crate) payload_start: usize,
+	pub(crate) end:           usize,
+}
+
+/// The type of an item, given by its `infe` box
+#[derive(Debug, Clone)]
+struct
+ItemInfo
+{
+	id:        u32,
+	item_type: [u8; 4],
+	raw:       Vec<u8>,                                                         // The entire infe box
+}
+
+/// An extent of the data of an item, relative to the base offset
+#[derive(Debug, Clone, Copy)]
+struct
+Extent
+{
+	index:  u64,
+	offset: u64,
+	length: u64,                                                                // 0 for the rest of the file
+}
+
+/// Where the data of an item is located, as given by the `iloc` box
+#[derive(Debug, Clone)]
+struct
+ItemLocation
+{
+	id:                   u32,
+	construction_method:  u8,                                                   // 0: File offsets, 1: Offsets in idat
+	data_reference_index: u16,                                                  // 0 for this file
+	base_offset:          u64,
+	extents:              Vec<Extent>,
+}
+
+/// The contents of the `iloc` box, with the number of bytes of its fields
+#[derive(Debug, Clone)]
+struct
+ItemLocations
+{
+	version:          u8,
+	offset_size:      usize,
+	length_size:      usize,
+	base_offset_size: usize,
+	index_size:       usize,
+	items:            Vec<ItemLocation>,
+}
+
+/// A reference of the given type from one item to others, as given by the
+/// `iref` box
+#[derive(Debug, Clone)]
+struct
+ItemReference
+{
+	reference_type: [u8; 4],
+	from:           u32,
+	to:             Vec<u32>,
+}
+
+/// The parsed `meta` box of a file, as far as needed for reading and
+/// writing the EXIF data
+#[derive(Debug, Clone)]
+struct
+MetaBox
+{
+	children:     Vec<IsoBox>,
+	primary_item: Option<u32>,
+	iinf_version: u8,
+	items:        Vec<ItemInfo>,
+	locations:    ItemLocations,
+	iref_version: u8,
+	references:   Vec<ItemReference>,
+}
+
+/// Opens the HEIF file at the given path for reading
+fn
+open_file
+(
+	file_system: &dyn FileSystem,
+	path:        &Path
+)
+-> Result<Box<dyn ReadSeek>, std::io::Error>
+{
+	if !file_system.exists(path)
+	{
+		return io_error!(NotFound, "Can't open HEIF file - File does not exist!");
+	}
+
+	return file_system.open(path);
+}
+
+/// Checks if the data starts with an `ftyp` box listing one of the given
+/// brands, either as major or as compatible brand
+pub(crate) fn
+has_brand
+(
+	data:   &[u8],
+	brands: &[[u8; 4]]
+)
+-> bool
+{
+	if data.get(4..8) != Some(&IsoBoxType::ftyp.as_bytes())
+	{
+		return false;
+	}
+
+	// Major brand, minor version and the compatible brands
+	let size = u32::from_be_bytes(data[0..4].try_into().unwrap()) as usize;
+	let Some(ftyp) = data.get(8..size.min(data.len())) else { return false; };
+	return ftyp.chunks_exact(4)
+		.enumerate()
+		.any(|(index, brand)| index != 1 && brands.iter().any(|candidate| candidate == brand));
+}
+
+/// Reads a big endian number of the given number of bytes (0 to 8) at the
+/// position, advancing the position. A size of 0 results in 0
+fn
+read_number
+(
+	data:     &[u8],
+	position: &mut usize,
+	size:     usize
+)
+-> Result<u64, std::io::Error>
+{
+	let Some(bytes) = data.get(*position..position.saturating_add(size)) else
+	{
+		return io_error!(InvalidData, "Can't read HEIF box - Field exceeds the data!");
+	};
+	*position += size;
+	return Ok(bytes.iter().fold(0u64, |number, byte| (number << 8) | *byte as u64));
+}
+
+/// Writes a big endian number using the given number of bytes (0 to 8)
+fn
+write_number
+(
+	buffer: &mut Vec<u8>,
+	number: u64,
+	size:   usize
+)
+{
+	buffer.extend(&number.to_be_bytes()[8 - size..]);
+}
+
+/// Gets the box at the start of the given header bytes, which are located
+/// at `start` within a container ending at `end`
+fn
+parse_box_header
+(
+	header: &[u8],
+	start:  usize,
+	end:    usize
+)
+-> Result<IsoBox, std::io::Error>
+{
+	let mut position = 0;
+	let size     = read_number(header, &mut position, 4)?;
+	let box_type = IsoBoxType::from_bytes(header[4..8].try_into().unwrap());
+	position = 8;
+
+	let size = match size
+	{
+		0 => (end - start) as u64,                                              // Extends to the end of the container
+		1 => read_number(header, &mut position, 8)?,                            // Large size
+		_ => size,
+	};
+	if box_type == IsoBoxType::uuid
+	{
+		position += 16;
+	}
+
+	let box_end = (start as u64).checked_add(size).filter(|box_end| *box_end <= end as u64);
+	match box_end
+	{
+		Some(box_end) if size >= position as u64 => Ok(IsoBox { box_type, start, payload_start: start + position, end: box_end as usize }),
+		_ => io_error!(InvalidData, "Can't read HEIF box - Size exceeds its container!")
+			.with_parse_context(|| ParseErrorContext::new().offset(start as u64).chunk(&box_type.to_string())),
+	}
+}
+
+/// Gets the boxes in the given range of the data
+pub(crate) fn
+read_boxes
+(
+	data:  &[u8],
+	start: usize,
+	end:   usize
+)
+-> Result<Vec<IsoBox>, std::io::Error>
+{
+	let mut boxes = Vec::new();
+	let mut position = start;
+	while position < end
+	{
+		if end - position < 8
+		{
+			return io_error!(InvalidData, "Can't read HEIF box - Header exceeds its container!")
+				.with_parse_context(|| ParseErrorContext::new().offset(position as u64));
+		}
+
+		let iso_box = parse_box_header(&data[position..end.min(position + MAX_HEADER_SIZE)], position, end)?;
+		position = iso_box.end;
+		boxes.push(iso_box);
+	}
+	return Ok(boxes);
+}
+
+/// Serializes a box with the given payload, using a large size if needed
+fn
+serialize_box
+(
+	box_type: IsoBoxType,
+	payload:  &[u8]
+)
+-> Vec<u8>
+{
+	let mut serialized = Vec::with_capacity(payload.len() + 16);
+	let size = payload.len() as u64 + 8;
+	if size <= u32::MAX as u64
+	{
+		serialized.extend((size as u32).to_be_bytes());
+		serialized.extend(box_type.as_bytes());
+	}
+	else
+	{
+		serialized.extend(1u32.to_be_bytes());
+		serialized.extend(box_type.as_bytes());
+		serialized.extend((size + 8).to_be_bytes());
+	}
+	serialized.extend(payload);
+	return serialized;
+}
+
+/// Gets the version and flags of a full box, i.e. of a box starting with
+/// them, e.g. `meta`
+fn
+full_box_header
+(
+	data:    &[u8],
+	iso_box: &IsoBox
+)
+-> Result<(u8, u32), std::io::Error>
+{
+	let mut position = iso_box.payload_start;
+	let version_and_flags = read_number(&data[..iso_box.end], &mut position, 4)?;
+	return Ok(((version_and_flags >> 24) as u8, version_and_flags as u32 & 0x00ff_ffff));
+}
+
+/// Serializes a full box with the given version, flags and payload
+fn
+serialize_full_box
+(
+	box_type: IsoBoxType,
+	version:  u8,
+	flags:    u32,
+	payload:  &[u8]
+)
+-> Vec<u8>
+{
+	let version_and_flags = ((version as u32) << 24) | (flags & 0x00ff_ffff);
+	return serialize_box(box_type, &[&version_and_flags.to_be_bytes()[..], payload].concat());
+}
+
+/// Gets the items listed by the `iinf` box
+fn
+read_item_infos
+(
+	data: &[u8],
+	iinf: &IsoBox
+)
+-> Result<(u8, Vec<ItemInfo>), std::io::Error>
+{
+	let (version, _) = full_box_header(data, iinf)?;
+	let entries_start = iinf.payload_start + 4 + if version == 0 { 2 } else { 4 };
+
+	let mut items = Vec::new();
+	for infe in read_boxes(data, entries_start.min(iinf.end), iinf.end)?
+	{
+		if infe.box_type != IsoBoxType::infe
+		{
+			continue;
+		}
+
+		// Versions 0 and 1 have no item type, so they can't be EXIF items
+		let (infe_version, _) = full_box_header(data, &infe)?;
+		let mut position = infe.payload_start + 4;
+		let id = read_number(&data[..infe.end], &mut position, if infe_version == 3 { 4 } else { 2 })? as u32;
+		let mut item_type = [0u8; 4];
+		if infe_version >= 2
+		{
+			position += 2;                                                      // Item protection index
+			item_type = read_number(&data[..infe.end], &mut position, 4)?.to_be_bytes()[4..8].try_into().unwrap();
+		}
+		items.push(ItemInfo { id, item_type, raw: data[infe.start..infe.end].to_vec() });
+	}
+	return Ok((version, items));
+}
+
+/// Serializes the `iinf` box listing the given items
+fn
+serialize_item_infos
+(
+	version: u8,
+	items:   &[ItemInfo]
+)
+-> Vec<u8>
+{
+	// Version 0 can only count up to 65535 items
+	let version = if items.len() > u16::MAX as usize { 1 } else { version.min(1) };
+	let mut payload = Vec::new();
+	write_number(&mut payload, items.len() as u64, if version == 0 { 2 } else { 4 });
+	for item in items
+	{
+		payload.extend(&item.raw);
+	}
+	return serialize_full_box(IsoBoxType::iinf, version, 0, &payload);
+}
+
+/// Serializes the `infe` box of a new EXIF item
+fn
+serialize_exif_item_info
+(
+	id: u32
+)
+-> Vec<u8>
+{
+	let version = if id > u16::MAX as u32 { 3 } else { 2 };
+	let mut payload = Vec::new();
+	write_number(&mut payload, id as u64, if version == 3 { 4 } else { 2 });
+	payload.extend([0x00, 0x00]);                                               // Item protection index
+	payload.extend(EXIF_ITEM_TYPE);
+	payload.push(0x00);                                                         // Empty item name
+	return serialize_full_box(IsoBoxType::infe, version, 0, &payload);
+}
+
+/// Gets the item locations given by the `iloc` box
+fn
+read_item_locations
+(
+	data: &[u8],
+	iloc: &IsoBox
+)
+-> Result<ItemLocations, std::io::Error>
+{
+	let data = &data[..iloc.end];
+	let (version, _) = full_box_header(data, iloc)?;
+	if version > 2
+	{
+		return io_error!(InvalidData, format!("Can't read HEIF file - Unknown iloc version {}!", version));
+	}
+
+	let mut position = iloc.payload_start + 4;
+	let sizes = read_number(data, &mut position, 2)?;
+	let offset_size      = (sizes >> 12) as usize;
+	let length_size      = (sizes >> 8 & 0x0f) as usize;
+	let base_offset_size = (sizes >> 4 & 0x0f) as usize;
+	let index_size       = if version == 0 { 0 } else { (sizes & 0x0f) as usize };
+	if [offset_size, length_size, base_offset_size, index_size].iter().any(|size| ![0, 4, 8].contains(size))
+	{
+		return io_error!(InvalidData, "Can't read HEIF file - Invalid field sizes in iloc!");
+	}
+
+	let id_size = if version < 2 { 2 } else { 4 };
+	let item_count = read_number(data, &mut position, id_size)?;
+	let mut items = Vec::new();
+	for _ in 0..item_count
+	{
+		let id = read_number(data, &mut position, id_size)? as u32;
+		let construction_method = match version
+		{
+			0 => 0,
+			_ => (read_number(data, &mut position, 2)? & 0x0f) as u8,
+		};
+		let data_reference_index = read_number(data, &mut position, 2)? as u16;
+		let base_offset = read_number(data, &mut position, base_offset_size)?;
+		let extent_count = read_number(data, &mut position, 2)?;
+
+		let mut extents = Vec::new();
+		for _ in 0..extent_count
+		{
+			extents.push(Extent
+			{
+				index:  read_number(data, &mut position, index_size)?,
+				offset: read_number(data, &mut position, offset_size)?,
+				length: read_number(data, &mut position, length_size)?,
+			});
+		}
+		items.push(ItemLocation { id, construction_method, data_reference_index, base_offset, extents });
+	}
+
+	return Ok(ItemLocations { version, offset_size, length_size, base_offset_size, index_size, items });
+}
+
+/// Serializes the `iloc` box with the given item locations
+fn
+serialize_item_locations
+(
+	locations: &ItemLocations
+)
+-> Vec<u8>
+{
+	let id_size = if locations.version < 2 { 2 } else { 4 };
+
+	let mut payload = Vec::new();
+	payload.push((locations.offset_size << 4 | locations.length_size) as u8);
+	payload.push((locations.base_offset_size << 4 | locations.index_size) as u8);
+	write_number(&mut payload, locations.items.len() as u64, id_size);
+	for item in &locations.items
+	{
+		write_number(&mut payload, item.id as u64, id_size);
+		if locations.version > 0
+		{
+			write_number(&mut payload, item.construction_method as u64, 2);
+		}
+		write_number(&mut payload, item.data_reference_index as u64, 2);
+		write_number(&mut payload, item.base_offset, locations.base_offset_size);
+		write_number(&mut payload, item.extents.len() as u64, 2);
+		for extent in &item.extents
+		{
+			write_number(&mut payload, extent.index,  locations.index_size);
+			write_number(&mut payload, extent.offset, locations.offset_size);
+			write_number(&mut payload, extent.length, locations.length_size);
+		}
+	}
+	return serialize_full_box(IsoBoxType::iloc, locations.version, 0, &payload);
+}
+
+/// Gets the references given by the `iref` box
+fn
+read_item_references
+(
+	data: &[u8],
+	iref: &IsoBox
+)
+-> Result<(u8, Vec<ItemReference>), std::io::Error>
+{
+	let (version, _) = full_box_header(data, iref)?;
+	let id_size = if version == 0 { 2 } else { 4 };
+
+	let mut references = Vec::new();
+	for reference in read_boxes(data, (iref.payload_start + 4).min(iref.end), iref.end)?
+	{
+		let reference_data = &data[..reference.end];
+		let mut position = reference.payload_start;
+		let from = read_number(reference_data, &mut position, id_size)? as u32;
+		let count = read_number(reference_data, &mut position, 2)?;
+		let to = (0..count)
+			.map(|_| read_number(reference_data, &mut position, id_size).map(|id| id as u32))
+			.collect::<Result<Vec<u32>, std::io::Error>>()?;
+		references.push(ItemReference { reference_type: reference.box_type.as_bytes(), from, to });
+	}
+	return Ok((version, references));
+}
+
+/// Serializes the `iref` box with the given references
+fn
+serialize_item_references
+(
+	version:    u8,
+	references: &[ItemReference]
+)
+-> Vec<u8>
+{
+	let large_ids = references.iter().any(|reference| reference.from > u16::MAX as u32 || reference.to.iter().any(|id| *id > u16::MAX as u32));
+	let version = if large_ids { 1 } else { version.min(1) };
+	let id_size = if version == 0 { 2 } else { 4 };
+
+	let mut payload = Vec::new();
+	for reference in references
+	{
+		let mut reference_payload = Vec::new();
+		write_number(&mut reference_payload, reference.from as u64, id_size);
+		write_number(&mut reference_payload, reference.to.len() as u64, 2);
+		for id in &reference.to
+		{
+			write_number(&mut reference_payload, *id as u64, id_size);
+		}
+		payload.extend(serialize_box(IsoBoxType::from_bytes(reference.reference_type), &reference_payload));
+	}
+	return serialize_full_box(IsoBoxType::iref, version, 0, &payload);
+}
+
+/// Serializes the `iprp` box without the property associations of the
+/// given items. The properties themselves are kept as they are
+fn
+serialize_properties_without
+(
+	data:     &[u8],
+	iprp:     &IsoBox,
+	item_ids: &[u32]
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	let mut payload = Vec::new();
+	for child in read_boxes(data, iprp.payload_start, iprp.end)?
+	{
+		if child.box_type != IsoBoxType::ipma
+		{
+			payload.extend(&data[child.start..child.end]);
+			continue;
+		}
+
+		let ipma_data = &data[..child.end];
+		let (version, flags) = full_box_header(data, &child)?;
+		let id_size          = if version < 1 { 2 } else { 4 };
+		let association_size = if flags & 1 == 1 { 2 } else { 1 };
+
+		let mut position = child.payload_start + 4;
+		let entry_count = read_number(ipma_data, &mut position, 4)?;
+		let mut entries = Vec::new();
+		for _ in 0..entry_count
+		{
+			let entry_start = position;
+			let id = read_number(ipma_data, &mut position, id_size)? as u32;
+			let association_count = read_number(ipma_data, &mut position, 1)? as usize;
+			position += association_count * association_size;
+			if position > child.end
+			{
+				return io_error!(InvalidData, "Can't read HEIF file - Property associations exceed ipma!");
+			}
+			if !item_ids.contains(&id)
+			{
+				entries.push(&ipma_data[entry_start..position]);
+			}
+		}
+
+		let mut ipma_payload = (entries.len() as u32).to_be_bytes().to_vec();
+		ipma_payload.extend(entries.concat());
+		payload.extend(serialize_full_box(IsoBoxType::ipma, version, flags, &ipma_payload));
+	}
+	return Ok(serialize_box(IsoBoxType::iprp, &payload));
+}
+
+/// Parses the `meta` box located in the given data
+fn
+read_meta_box
+(
+	data: &[u8],
+	meta: IsoBox
+)
+-> Result<MetaBox, std::io::Error>
+{
+	let children = read_boxes(data, (meta.payload_start + 4).min(meta.end), meta.end)?;
+	let child = |box_type: IsoBoxType| children.iter().find(|child| child.box_type == box_type);
+
+	let primary_item = match child(IsoBoxType::pitm)
+	{
+		Some(pitm) => {
+			let (version, _) = full_box_header(data, pitm)?;
+			let mut position = pitm.payload_start + 4;
+			Some(read_number(&data[..pitm.end], &mut position, if version == 0 { 2 } else { 4 })? as u32)
+		},
+		None => None,
+	};
+
+	let (iinf_version, items) = match child(IsoBoxType::iinf)
+	{
+		Some(iinf) => read_item_infos(data, iinf).with_parse_context(|| ParseErrorContext::new().chunk("iinf"))?,
+		None       => (0, Vec::new()),
+	};
+
+	let locations = match child(IsoBoxType::iloc)
+	{
+		Some(iloc) => read_item_locations(data, iloc).with_parse_context(|| ParseErrorContext::new().chunk("iloc"))?,
+		None       => ItemLocations { version: 0, offset_size: 4, length_size: 4, base_offset_size: 0, index_size: 0, items: Vec::new() },
+	};
+
+	let (iref_version, references) = match child(IsoBoxType::iref)
+	{
+		Some(iref) => read_item_references(data, iref).with_parse_context(|| ParseErrorContext::new().chunk("iref"))?,
+		None       => (0, Vec::new()),
+	};
+
+	return Ok(MetaBox { children, primary_item, iinf_version, items, locations, iref_version, references });
+}
+
+impl
+MetaBox
+{
+	/// Gets the IDs of the EXIF items
+	fn
+	exif_item_ids
+	(
+		&self
+	)
+	-> Vec<u32>
+	{
+		self.items.iter()
+			.filter(|item| item.item_type == EXIF_ITEM_TYPE)
+			.map(|item| item.id)
+			.collect()
+	}
+
+	/// Gets the location of the item with the given ID
+	fn
+	location
+	(
+		&self,
+		id: u32
+	)
+	-> Option<&ItemLocation>
+	{
+		self.locations.items.iter().find(|location| location.id == id)
+	}
+
+	/// Gets the child box of the given type
+	fn
+	child
+	(
+		&self,
+		box_type: IsoBoxType
+	)
+	-> Option<&IsoBox>
+	{
+		self.children.iter().find(|child| child.box_type == box_type)
+	}
+
+	/// Gets the ranges the data of the item consists of. They are located in
+	/// the file for construction method 0 and in the data the `meta` box has
+	/// been read from for construction method 1 (i.e. in `idat`)
+	fn
+	item_ranges
+	(
+		&self,
+		location:  &ItemLocation,
+		file_size: u64
+	)
+	-> Result<Vec<Range<u64>>, std::io::Error>
+	{
+		let (container_start, container_end) = match (location.construction_method, location.data_reference_index)
+		{
+			(0, 0) => (0, file_size),
+			(1, _) => match self.child(IsoBoxType::idat)
+			{
+				Some(idat) => (idat.payload_start as u64, idat.end as u64),
+				None       => return io_error!(InvalidData, "Can't read HEIF item - Located in missing idat box!"),
+			},
+			_ => return io_error!(Unsupported, "Can't read HEIF item - Only data within the file is supported!"),
+		};
+
+		let mut ranges = Vec::new();
+		for extent in &location.extents
+		{
+			let start = container_start.checked_add(location.base_offset).and_then(|start| start.checked_add(extent.offset));
+			let end = match extent.length
+			{
+				0      => Some(container_end),
+				length => start.and_then(|start| start.checked_add(length)),
+			};
+			match (start, end)
+			{
+				(Some(start), Some(end)) if start <= end && end <= container_end => ranges.push(start..end),
+				_ => return io_error!(InvalidData, "Can't read HEIF item - Extent exceeds its container!"),
+			}
+		}
+		return Ok(ranges);
+	}
+}
+
+/// Reads the top level boxes of the file up to the `meta` box, which gets
+/// read entirely. Returns the data of the `meta` box (starting with its
+/// header) and the parsed box, whose positions are relative to that data
+fn
+read_meta_box_from_file
+<T: Read + Seek>
+(
+	cursor: &mut T
+)
+-> Result<(Vec<u8>, MetaBox), std::io::Error>
+{
+	let file_size = cursor.seek(SeekFrom::End(0))?;
+	let mut position = 0u64;
+	while position < file_size
+	{
+		let mut header = [0u8; MAX_HEADER_SIZE];
+		let header_size = (file_size - position).min(MAX_HEADER_SIZE as u64) as usize;
+		cursor.seek(SeekFrom::Start(position))?;
+		if header_size < 8 || !try_read_exact(cursor, &mut header[..header_size])?
+		{
+			return io_error!(InvalidData, "Can't read HEIF file - Truncated box header!")
+				.with_parse_context(|| ParseErrorContext::new().offset(position));
+		}
+
+		// Parse the header as if the box would start at 0, as the positions
+		// in the file may exceed the address space
+		let remaining = usize::try_from(file_size - position).unwrap_or(usize::MAX);
+		let iso_box = parse_box_header(&header[..header_size], 0, remaining)
+			.with_parse_context(|| ParseErrorContext::new().offset(position))?;
+		if position == 0 && iso_box.box_type != IsoBoxType::ftyp
+		{
+			return io_error!(InvalidData, "Can't open HEIF file - Doesn't start with an ftyp box!");
+		}
+
+		if iso_box.box_type == IsoBoxType::meta
+		{
+			let mut meta_data = vec![0u8; iso_box.end];
+			cursor.seek(SeekFrom::Start(position))?;
+			if !try_read_exact(cursor, &mut meta_data)?
+			{
+				return io_error!(InvalidData, "Can't read HEIF file - Truncated meta box!");
+			}
+			let meta_box = read_meta_box(&meta_data, iso_box)
+				.with_parse_context(|| ParseErrorContext::new().offset(position).chunk("meta"))?;
+			return Ok((meta_data, meta_box));
+		}
+
+		position += iso_box.end as u64;
+	}
+
+	return MissingExif::error("No EXIF data found!");
+}
+
+/// Reads the raw EXIF data from the EXIF item of the HEIF file. Only the
+/// top level box headers, the `meta` box and the EXIF data are read
+pub(crate) fn
+read_metadata
+<T: Read + Seek>
+(
+	cursor: &mut T
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	let _span = span!("parse", format = "HEIF");
+
+	let (meta_data, meta_box) = read_meta_box_from_file(cursor)?;
+	let file_size = cursor.seek(SeekFrom::End(0))?;
+
+	let Some(location) = meta_box.exif_item_ids().first().and_then(|id| meta_box.location(*id)) else
+	{
+		return MissingExif::error("No EXIF data found!");
+	};
+
+	let mut exif_data = Vec::new();
+	for range in meta_box.item_ranges(location, file_size)?
+	{
+		if location.construction_method == 1
+		{
+			exif_data.extend(&meta_data[range.start as usize..range.end as usize]);
+			continue;
+		}
+
+		let mut extent_data = vec![0u8; (range.end - range.start) as usize];
+		cursor.seek(SeekFrom::Start(range.start))?;
+		if !try_read_exact(cursor, &mut extent_data)?
+		{
+			return io_error!(InvalidData, "Can't read HEIF file - Truncated EXIF item!");
+		}
+		exif_data.extend(extent_data);
+	}
+
+	return exif_block::normalize(&exif_data);
+}
+
+
+
+pub(crate) fn
+file_read_metadata
+(
+	file_system: &dyn FileSystem,
+	path:        &Path
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	read_metadata(&mut open_file(file_system, path)?)
+}
+
+
+
+/// Gets the width and height of the primary image, as given by its `ispe`
+/// property
+pub(crate) fn
+image_dimensions
+(
+	file_buffer: &[u8]
+)
+-> Option<(u32, u32)>
+{
+	let meta = read_boxes(file_buffer, 0, file_buffer.len()).ok()?
+		.into_iter()
+		.find(|iso_box| iso_box.box_type == IsoBoxType::meta)?;
+	let meta_box = read_meta_box(file_buffer, meta).ok()?;
+	let primary_item = meta_box.primary_item?;
+
+	let iprp = meta_box.child(IsoBoxType::iprp)?;
+	let iprp_children = read_boxes(file_buffer, iprp.payload_start, iprp.end).ok()?;
+	let ipco = iprp_children.iter().find(|child| child.box_type == IsoBoxType::ipco)?;
+	let properties = read_boxes(file_buffer, ipco.payload_start, ipco.end).ok()?;
+
+	// Find the associations of the primary item, referring to the properties
+	// by their 1-based index
+	for ipma in iprp_children.iter().filter(|child| child.box_type == IsoBoxType::ipma)
+	{
+		let ipma_data = &file_buffer[..ipma.end];
+		let (version, flags) = full_box_header(file_buffer, ipma).ok()?;
+		let id_size = if version < 1 { 2 } else { 4 };
+		let (association_size, index_mask) = if flags & 1 == 1 { (2, 0x7fff) } else { (1, 0x7f) };
+
+		let mut position = ipma.payload_start + 4;
+		let entry_count = read_number(ipma_data, &mut position, 4).ok()?;
+		for _ in 0..entry_count
+		{
+			let id = read_number(ipma_data, &mut position, id_size).ok()? as u32;
+			let association_count = read_number(ipma_data, &mut position, 1).ok()?;
+			for _ in 0..association_count
+			{
+				let index = (read_number(ipma_data, &mut position, association_size).ok()? & index_mask) as usize;
+				let property = index.checked_sub(1).and_then(|index| properties.get(index));
+				if let Some(ispe) = property.filter(|property| id == primary_item && property.box_type == IsoBoxType::ispe)
+				{
+					let mut ispe_position = ispe.payload_start + 4;
+					let width  = read_number(&file_buffer[..ispe.end], &mut ispe_position, 4).ok()?;
+					let height = read_number(&file_buffer[..ispe.end], &mut ispe_position, 4).ok()?;
+					return Some((width as u32, height as u32));
+				}
+			}
+		}
+	}
+	return None;
+}
+
+
+
+/// Replaces the EXIF items of the HEIF data by a single one with the given
+/// data, or removes them if there is none (see module documentation)
+fn
+replace_exif_item
+(
+	file_buffer: &mut Vec<u8>,
+	exif_data:   Option<Vec<u8>>
+)
+-> Result<(), std::io::Error>
+{
+	let top_level_boxes = read_boxes(file_buffer, 0, file_buffer.len())?;
+	if top_level_boxes.first().map(|iso_box| iso_box.box_type) != Some(IsoBoxType::ftyp)
+	{
+		return io_error!(InvalidData, "Can't open HEIF file - Doesn't start with an ftyp box!");
+	}
+	let Some(meta) = top_level_boxes.iter().find(|iso_box| iso_box.box_type == IsoBoxType::meta).copied() else
+	{
+		return io_error!(InvalidData, "Can't open HEIF file - No meta box!");
+	};
+	let mut meta_box = read_meta_box(file_buffer, meta)?;
+	if meta_box.child(IsoBoxType::iinf).is_none()
+	{
+		return io_error!(InvalidData, "Can't open HEIF file - No iinf box!");
+	}
+
+	let old_exif_ids = meta_box.exif_item_ids();
+	if exif_data.is_none() && old_exif_ids.is_empty()
+	{
+		return Ok(());
+	}
+
+	// Overwrite the previous EXIF data. If it is the only content of the
+	// last box (an mdat box written by a previous write), the box goes away
+	let mut truncate_position = file_buffer.len();
+	for id in &old_exif_ids
+	{
+		let Some(location) = meta_box.location(*id) else { continue; };
+		for range in meta_box.item_ranges(location, file_buffer.len() as u64)?
+		{
+			file_buffer[range.start as usize..range.end as usize].fill(0x00);
+
+			let last_box = top_level_boxes.last().unwrap();
+			let is_last_box = last_box.box_type == IsoBoxType::mdat
+				&& last_box.start >= meta.end
+				&& location.construction_method == 0
+				&& range == (last_box.payload_start as u64..last_box.end as u64);
+			if is_last_box
+			{
+				truncate_position = last_box.start;
+			}
+		}
+	}
+
+	// Remove all EXIF items but the one that gets reused for the new data
+	let max_id = meta_box.items.iter().map(|item| item.id).chain(meta_box.locations.items.iter().map(|location| location.id)).max();
+	let new_exif_id = exif_data.as_ref().map(|_| old_exif_ids.first().copied().unwrap_or(max_id.unwrap_or(0).saturating_add(1)));
+	let removed_ids = old_exif_ids.iter().copied().filter(|id| Some(*id) != new_exif_id).collect::<Vec<u32>>();
+	meta_box.items.retain(|item| !removed_ids.contains(&item.id));
+	meta_box.locations.items.retain(|location| Some(location.id) != new_exif_id && !removed_ids.contains(&location.id));
+	meta_box.references.retain_mut(|reference| {
+		reference.to.retain(|id| !removed_ids.contains(id));
+		!removed_ids.contains(&reference.from) && !reference.to.is_empty()
+	});
+
+	if let Some(id) = new_exif_id
+	{
+		if !old_exif_ids.contains(&id)
+		{
+			meta_box.items.push(ItemInfo { id, item_type: EXIF_ITEM_TYPE, raw: serialize_exif_item_info(id) });
+			if let Some(primary_item) = meta_box.primary_item
+			{
+				meta_box.references.push(ItemReference { reference_type: CONTENT_DESCRIBES, from: id, to: vec![primary_item] });
+			}
+		}
+	}
+
+	let has_moov = top_level_boxes.iter().any(|iso_box| iso_box.box_type == IsoBoxType::moov && iso_box.start >= meta.end);
+
+	// The offsets of the new EXIF data may exceed 4 GiB, while the field
+	// sizes have to be known before the offsets
+	let mut locations = meta_box.locations.clone();
+	let max_file_size = file_buffer.len() as u64 + exif_data.as_ref().map(|data| data.len() as u64).unwrap_or(0) + meta.end as u64 + 1024;
+	let needed_size = if max_file_size > u32::MAX as u64 { 8 } else { 4 };
+	locations.offset_size = locations.offset_size.max(needed_size);
+	locations.length_size = locations.length_size.max(4);
+	if new_exif_id.is_some_and(|id| id > u16::MAX as u32)
+	{
+		locations.version = 2;
+	}
+
+	let serialize_meta = |locations: &ItemLocations| -> Result<Vec<u8>, std::io::Error> {
+		let (version, flags) = full_box_header(file_buffer, &meta)?;
+		let mut payload = Vec::new();
+		for child in &meta_box.children
+		{
+			match child.box_type
+			{
+				IsoBoxType::iinf => {
+					payload.extend(serialize_item_infos(meta_box.iinf_version, &meta_box.items));
+					if meta_box.child(IsoBoxType::iref).is_none() && !meta_box.references.is_empty()
+					{
+						payload.extend(serialize_item_references(0, &meta_box.references));
+					}
+				},
+				IsoBoxType::iloc => payload.extend(serialize_item_locations(locations)),
+				IsoBoxType::iref => if !meta_box.references.is_empty()
+				{
+					payload.extend(serialize_item_references(meta_box.iref_version, &meta_box.references));
+				},
+				IsoBoxType::iprp => payload.extend(serialize_properties_without(file_buffer, child, &removed_ids)?),
+				_                => payload.extend(&file_buffer[child.start..child.end]),
+			}
+		}
+		if meta_box.child(IsoBoxType::iloc).is_none()
+		{
+			payload.extend(serialize_item_locations(locations));
+		}
+		return Ok(serialize_full_box(IsoBoxType::meta, version, flags, &payload));
+	};
+
+	// The new EXIF data goes into an mdat box at the end of the file. Its
+	// location is added first with a placeholder offset, so that the size
+	// of the meta box is known
+	let exif_mdat = exif_data.as_ref().map(|data| serialize_box(IsoBoxType::mdat, data));
+	if let (Some(id), Some(exif_data)) = (new_exif_id, &exif_data)
+	{
+		locations.items.push(ItemLocation
+		{
+			id,
+			construction_method:  0,
+			data_reference_index: 0,
+			base_offset:          0,
+			extents:              vec![Extent { index: 0, offset: 0, length: exif_data.len() as u64 }],
+		});
+	}
+
+	// Shift the locations pointing behind the meta box by the change of its
+	// size, which doesn't depend on the values of the offsets
+	let meta_size = serialize_meta(&locations)?.len();
+	let shift = meta_size as i64 - (meta.end - meta.start) as i64;
+	if shift != 0 && has_moov
+	{
+		return io_error!(Unsupported, "Can't write HEIF file - Image sequences are not supported!");
+	}
+	for location in &mut locations.items
+	{
+		if location.construction_method != 0 || location.data_reference_index != 0 || Some(location.id) == new_exif_id
+		{
+			continue;
+		}
+		if location.base_offset >= meta.end as u64
+		{
+			location.base_offset = location.base_offset.saturating_add_signed(shift);
+			continue;
+		}
+		for extent in &mut location.extents
+		{
+			if location.base_offset.saturating_add(extent.offset) >= meta.end as u64
+			{
+				extent.offset = extent.offset.saturating_add_signed(shift);
+			}
+		}
+	}
+
+	if let (Some(exif_location), Some(exif_mdat)) = (locations.items.last_mut().filter(|location| Some(location.id) == new_exif_id), &exif_mdat)
+	{
+		let mdat_header_size = exif_mdat.len() - exif_location.extents[0].length as usize;
+		exif_location.extents[0].offset = (meta.start + meta_size + (truncate_position - meta.end) + mdat_header_size) as u64;
+	}
+	let new_meta = serialize_meta(&locations)?;
+	debug_assert_eq!(new_meta.len(), meta_size);
+
+	let mut new_file_buffer = file_buffer[..meta.start].to_vec();
+	new_file_buffer.extend(new_meta);
+	new_file_buffer.extend(&file_buffer[meta.end..truncate_position]);
+	new_file_buffer.extend(exif_mdat.unwrap_or_default());
+	*file_buffer = new_file_buffer;
+	return Ok(());
+}
+
+
+
+/// Removes the EXIF items from the HEIF data, overwriting their data with
+/// zeros. Data without EXIF items is left untouched
+pub(crate) fn
+clear_metadata
+(
+	file_buffer: &mut Vec<u8>
+)
+-> Result<(), std::io::Error>
+{
+	replace_exif_item(file_buffer, None)
+}
+
+
+
+pub(crate) fn
+file_clear_metadata
+(
+	file_system: &dyn FileSystem,
+	path:        &Path
+)
+-> Result<(), std::io::Error>
+{
+	let mut file_buffer = read_file_to_vec(open_file(file_system, path)?)?;
+	let original_buffer = file_buffer.clone();
+	clear_metadata(&mut file_buffer)?;
+	if file_buffer != original_buffer
+	{
+		file_system.write(path, &file_buffer)?;
+	}
+	return Ok(());
+}
+
+
+
+/// Provides the HEIF specific encoding result as vector of bytes to be used
+/// by the user, i.e. the data of an EXIF item: The offset to the TIFF
+/// header, followed by `Exif\0\0` and the TIFF data
+pub(crate) fn
+as_u8_vec
+(
+	general_encoded_metadata: &Vec<u8>
+)
+-> Vec<u8>
+{
+	exif_block::add_prefix(general_encoded_metadata, ExifPrefix::HeifOffset)
+}
+
+
+
+/// Writes the given generally encoded metadata to the HEIF data, replacing
+/// the previous EXIF items (see module documentation)
+pub(crate) fn
+write_metadata
+(
+	file_buffer:              &mut Vec<u8>,
+	general_encoded_metadata: &Vec<u8>
+)
+-> Result<(), std::io::Error>
+{
+	replace_exif_item(file_buffer, Some(as_u8_vec(general_encoded_metadata)))
+}
+
+
+
+/// Writes the given generally encoded metadata to the HEIF image file at
+/// the specified path (see `write_metadata`)
+pub(crate) fn
+file_write_metadata
+(
+	file_system:              &dyn FileSystem,
+	path:                     &Path,
+	general_encoded_metadata: &Vec<u8>
+)
+-> Result<(), std::io::Error>
+{
+	let mut file_buffer = read_file_to_vec(open_file(file_system, path)?)?;
+	write_metadata(&mut file_buffer, general_encoded_metadata)?;
+	file_system.write(path, &file_buffer)?;
+	return Ok(());
+}
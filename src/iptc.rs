@@ -0,0 +1,269 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Encoding and decoding of IPTC-IIM data and the Photoshop image resource
+//! blocks (IRB) it is stored in. In JPEG files, the resources are stored in
+//! an APP13 segment starting with `PHOTOSHOP_HEADER`, the IIM data being the
+//! resource with ID `IPTC_RESOURCE_ID`.
+
+use crate::general_file_io::io_error;
+
+pub(crate) const PHOTOSHOP_HEADER:         &[u8] = b"Photoshop 3.0\0";
+pub(crate) const IPTC_RESOURCE_ID:         u16   = 0x0404;
+pub(crate) const IPTC_DIGEST_RESOURCE_ID:  u16   = 0x0425;                      // MD5 of the IIM data, outdated after changing it
+
+const RESOURCE_SIGNATURE: &[u8] = b"8BIM";
+const DATASET_MARKER:     u8    = 0x1c;
+
+/// Names of the datasets of the application record, as used by IPTC
+const DATASET_NAMES: [(u8, &str); 27] = [
+	(5,   "ObjectName"),
+	(7,   "EditStatus"),
+	(10,  "Urgency"),
+	(15,  "Category"),
+	(20,  "SupplementalCategories"),
+	(25,  "Keywords"),
+	(40,  "SpecialInstructions"),
+	(55,  "DateCreated"),
+	(60,  "TimeCreated"),
+	(62,  "DigitalCreationDate"),
+	(63,  "DigitalCreationTime"),
+	(65,  "OriginatingProgram"),
+	(80,  "By-line"),
+	(85,  "By-lineTitle"),
+	(90,  "City"),
+	(92,  "Sub-location"),
+	(95,  "Province-State"),
+	(100, "Country-PrimaryLocationCode"),
+	(101, "Country-PrimaryLocationName"),
+	(103, "OriginalTransmissionReference"),
+	(105, "Headline"),
+	(110, "Credit"),
+	(115, "Source"),
+	(116, "CopyrightNotice"),
+	(118, "Contact"),
+	(120, "Caption-Abstract"),
+	(122, "Writer-Editor"),
+];
+
+/// A single dataset of IIM data, e.g. record 2, dataset 90 for the city
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct
+Dataset
+{
+	pub(crate) record: u8,
+	pub(crate) number: u8,
+	pub(crate) data:   Vec<u8>,
+}
+
+impl
+Dataset
+{
+	/// Gets the name of the dataset, e.g. `Keywords` for record 2, dataset
+	/// 25, or `record:number` for datasets without a known name
+	pub(crate) fn
+	name
+	(
+		&self
+	)
+	-> String
+	{
+		let known = DATASET_NAMES.iter()
+			.find(|(number, _)| self.record == 2 && *number == self.number)
+			.map(|(_, name)| name.to_string());
+		return known.unwrap_or_else(|| format!("{}:{}", self.record, self.number));
+	}
+}
+
+/// A single Photoshop image resource
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct
+Resource
+{
+	pub(crate) id:   u16,
+	pub(crate) name: Vec<u8>,
+	pub(crate) data: Vec<u8>,
+}
+
+/// Decodes IIM data into its datasets. Decoding stops at the first byte that
+/// doesn't start a dataset, as writers may pad the data with zeros.
+pub(crate) fn
+decode_iim
+(
+	data: &[u8]
+)
+-> Result<Vec<Dataset>, std::io::Error>
+{
+	let mut datasets = Vec::new();
+	let mut position = 0;
+
+	while data.get(position) == Some(&DATASET_MARKER)
+	{
+		let header = match data.get(position+1..position+5)
+		{
+			Some(header) => header,
+			None         => return io_error!(UnexpectedEof, "Truncated IIM dataset header!"),
+		};
+		let mut length = u16::from_be_bytes([header[2], header[3]]) as usize;
+		position += 5;
+
+		// Extended datasets store the number of bytes of the actual length
+		if length & 0x8000 != 0
+		{
+			let length_bytes = match data.get(position..position + (length & 0x7fff))
+			{
+				Some(length_bytes) if length_bytes.len() <= 4 => length_bytes,
+				_                                              => return io_error!(InvalidData, "Invalid length of extended IIM dataset!"),
+			};
+			position += length_bytes.len();
+			length = length_bytes.iter().fold(0, |length, byte| length << 8 | *byte as usize);
+		}
+
+		match data.get(position..position+length)
+		{
+			Some(value) => datasets.push(Dataset { record: header[0], number: header[1], data: value.to_vec() }),
+			None        => return io_error!(UnexpectedEof, "IIM dataset exceeds the IPTC data!"),
+		}
+		position += length;
+	}
+
+	return Ok(datasets);
+}
+
+/// Encodes the datasets as IIM data
+pub(crate) fn
+encode_iim
+(
+	datasets: &[Dataset]
+)
+-> Vec<u8>
+{
+	let mut data = Vec::new();
+	for dataset in datasets
+	{
+		data.extend([DATASET_MARKER, dataset.record, dataset.number]);
+		if dataset.data.len() < 0x8000
+		{
+			data.extend((dataset.data.len() as u16).to_be_bytes());
+		}
+		else
+		{
+			data.extend([0x80, 0x04]);
+			data.extend((dataset.data.len() as u32).to_be_bytes());
+		}
+		data.extend(&dataset.data);
+	}
+	return data;
+}
+
+/// Decodes the image resource blocks following the Photoshop header
+pub(crate) fn
+decode_resources
+(
+	data: &[u8]
+)
+-> Result<Vec<Resource>, std::io::Error>
+{
+	let mut resources = Vec::new();
+	let mut position  = 0;
+
+	while position < data.len()
+	{
+		if data.get(position..position+4) != Some(RESOURCE_SIGNATURE)
+		{
+			return io_error!(InvalidData, "Expected Photoshop image resource signature!");
+		}
+
+		let id = match data.get(position+4..position+6)
+		{
+			Some(id) => u16::from_be_bytes([id[0], id[1]]),
+			None     => return io_error!(UnexpectedEof, "Truncated Photoshop image resource!"),
+		};
+
+		// The name is a Pascal string, padded to an even length
+		let name_length = *data.get(position+6).unwrap_or(&0) as usize;
+		let name        = data.get(position+7..position+7+name_length).map(|name| name.to_vec());
+		position += 6 + (name_length + 2) / 2 * 2;
+
+		let size = match data.get(position..position+4)
+		{
+			Some(size) => u32::from_be_bytes([size[0], size[1], size[2], size[3]]) as usize,
+			None       => return io_error!(UnexpectedEof, "Truncated Photoshop image resource!"),
+		};
+		position += 4;
+
+		match (name, data.get(position..position+size))
+		{
+			(Some(name), Some(value)) => resources.push(Resource { id, name, data: value.to_vec() }),
+			_                         => return io_error!(UnexpectedEof, "Photoshop image resource exceeds the data!"),
+		}
+		position += size + size % 2;
+	}
+
+	return Ok(resources);
+}
+
+/// Encodes the resources as image resource blocks (without the header)
+pub(crate) fn
+encode_resources
+(
+	resources: &[Resource]
+)
+-> Vec<u8>
+{
+	let mut data = Vec::new();
+	for resource in resources
+	{
+		data.extend(RESOURCE_SIGNATURE);
+		data.extend(resource.id.to_be_bytes());
+		data.push(resource.name.len() as u8);
+		data.extend(&resource.name);
+		if resource.name.len() % 2 == 0
+		{
+			data.push(0);
+		}
+		data.extend((resource.data.len() as u32).to_be_bytes());
+		data.extend(&resource.data);
+		if resource.data.len() % 2 == 1
+		{
+			data.push(0);
+		}
+	}
+	return data;
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::iptc::*;
+
+	#[test]
+	fn
+	round_trip()
+	-> Result<(), std::io::Error>
+	{
+		let datasets = vec![
+			Dataset { record: 1, number: 90, data: b"\x1b%G".to_vec() },
+			Dataset { record: 2, number: 90, data: "Graz".as_bytes().to_vec() },
+			Dataset { record: 2, number: 120, data: vec![b'x'; 0x9000] },
+		];
+		let iim = encode_iim(&datasets);
+		assert_eq!(&iim[..8], &[0x1c, 1, 90, 0, 3, 0x1b, b'%', b'G']);
+
+		let mut padded = iim.clone();
+		padded.extend([0, 0]);
+		assert_eq!(decode_iim(&padded)?, datasets);
+		assert!(decode_iim(&iim[..iim.len()-1]).is_err());
+		assert_eq!(datasets[1].name(), "City");
+		assert_eq!(datasets[0].name(), "1:90");
+
+		let resources = vec![
+			Resource { id: 0x03ed, name: Vec::new(),    data: vec![1, 2, 3] },
+			Resource { id: IPTC_RESOURCE_ID, name: b"IPTC".to_vec(), data: iim },
+		];
+		let encoded = encode_resources(&resources);
+		assert_eq!(&encoded[..16], b"8BIM\x03\xed\0\0\0\0\0\x03\x01\x02\x03\0");
+		assert_eq!(decode_resources(&encoded)?, resources);
+		Ok(())
+	}
+}
@@ -2,20 +2,60 @@
 // See https://github.com/TechnikTobi/little_exif#license for licensing details
 
 use std::path::Path;
+use std::io::Cursor;
 use std::io::Read;
-use std::io::Write;
 use std::io::Seek;
 use std::io::SeekFrom;
-use std::fs::File;
-use std::fs::OpenOptions;
 
+use crate::chunk_order::chunk_order;
+use crate::chunk_types::JpegMarker;
 use crate::endian::*;
+use crate::exif_block;
+use crate::exif_block::ExifPrefix;
+use crate::errors::MissingExif;
+use crate::errors::ParseErrorContext;
+use crate::errors::WithParseContext;
+use crate::filetype::FileExtension;
 use crate::general_file_io::*;
+use crate::iptc::PHOTOSHOP_HEADER;
+use crate::jpg_frame::JpgFrameInfo;
+use crate::vfs::FileSystem;
+use crate::vfs::ReadSeek;
 
 pub(crate) const JPG_SIGNATURE: [u8; 2] = [0xff, 0xd8];
 
 const JPG_MARKER_PREFIX: u8  = 0xff;
-const JPG_APP1_MARKER:   u16 = 0xffe1;
+
+const ADOBE_IDENTIFIER:  [u8; 5] = [0x41, 0x64, 0x6f, 0x62, 0x65];              // "Adobe"
+const XMP_HEADER:        &[u8]   = b"http://ns.adobe.com/xap/1.0/\0";
+
+/// Describes a single marker segment that comes before the (first) start of
+/// scan. Everything after that is entropy coded image data which is of no
+/// interest to little_exif and can't be walked segment by segment anyway.
+#[derive(Clone)]
+pub(crate) struct
+JpgSegment
+{
+	marker: JpegMarker,                                                         // The marker following the 0xff prefix, e.g. APP1
+	offset: u64,                                                                // Position of the 0xff marker prefix in the file
+	length: u16,                                                                // Value of the length field (includes its own 2 bytes), 0 for standalone markers
+}
+
+impl
+JpgSegment
+{
+	/// Number of bytes this segment occupies in the file, including the two
+	/// bytes of the marker itself
+	pub(crate) fn
+	byte_count
+	(
+		&self
+	)
+	-> u64
+	{
+		2u64 + self.length as u64
+	}
+}
 
 fn
 encode_metadata_jpg
@@ -32,137 +72,249 @@ encode_metadata_jpg
 	let length = 2u16 + (EXIF_HEADER.len() as u16) + (exif_vec.len() as u16);
 
 	// Start with the APP1 marker and the length of the data
-	// Then copy the previously encoded EXIF data 
-	jpg_exif.extend(to_u8_vec_macro!(u16, &JPG_APP1_MARKER, &Endian::Big));
-	jpg_exif.extend(to_u8_vec_macro!(u16, &length, &Endian::Big));
-	jpg_exif.extend(EXIF_HEADER.iter());
-	jpg_exif.extend(exif_vec.iter());
+	// Then copy the previously encoded EXIF data
+	jpg_exif.extend([JPG_MARKER_PREFIX, JpegMarker::APP(1).as_byte()]);
+	Endian::Big.write(&mut jpg_exif, length);
+	jpg_exif.extend(exif_block::add_prefix(exif_vec, ExifPrefix::ExifHeader));
 
 	return jpg_exif;
 }
 
+/// Opens the JPG file at the given path for reading
 fn
-check_signature
+open_file
 (
-	path: &Path
+	file_system: &dyn FileSystem,
+	path:        &Path
 )
--> Result<File, std::io::Error>
+-> Result<Box<dyn ReadSeek>, std::io::Error>
 {
-	if !path.exists()
+	if !file_system.exists(path)
 	{
 		return io_error!(NotFound, "Can't open JPG file - File does not exist!");
 	}
 
-	let mut file = OpenOptions::new()
-		.read(true)
-		.write(true)
-		.open(path)
-		.expect("Could not open file");
-	
+	return file_system.open(path);
+}
+
+/// Checks the signature at the start of the given JPG data. Afterwards, the
+/// cursor is located right after the signature
+fn
+check_signature
+<T: Read + Seek>
+(
+	cursor: &mut T
+)
+-> Result<(), std::io::Error>
+{
 	// Check the signature
 	let mut signature_buffer = [0u8; 2];
-	file.read(&mut signature_buffer).unwrap();
-	let signature_is_valid = signature_buffer.iter()
-		.zip(JPG_SIGNATURE.iter())
-		.filter(|&(read, constant)| read == constant)
-		.count() == JPG_SIGNATURE.len();
-
-	if !signature_is_valid
+	perform_file_action!(cursor.seek(SeekFrom::Start(0)));
+	if !try_read_exact(cursor, &mut signature_buffer)? || signature_buffer != JPG_SIGNATURE
 	{
 		return io_error!(InvalidData, "Can't open JPG file - Wrong signature!");
 	}
 
-	// Signature is valid - can proceed using the file as JPG file
-	return Ok(file);
+	// Signature is valid - can proceed using the data as JPG file
+	return Ok(());
 }
 
+/// "Parses" the JPG file by walking from segment to segment using their
+/// length information, starting right after the SOI marker and ending with
+/// the first SOS (or an EOI) marker.
+/// In contrast to looking at the file byte by byte this can't be fooled by
+/// payloads that happen to contain something that looks like a marker, e.g.
+/// the 16-bit quantization tables of 12-bit JPEGs or ICC profiles of CMYK
+/// files. The segments are returned together with the frame information.
 pub(crate) fn
-clear_metadata
+parse_jpg
+<T: Read + Seek>
 (
-	path: &Path
+	file: &mut T
 )
--> Result<u8, std::io::Error>
+-> Result<(Vec<JpgSegment>, Option<JpgFrameInfo>), std::io::Error>
 {
-	let file_result = check_signature(path);
+	let span = span!("parse", format = "JPEG"; segments);
 
-	if file_result.is_err()
-	{
-		return Err(file_result.err().unwrap());
-	}
+	let mut segments        = Vec::new();
+	let mut frame_info      = None;
+	let mut adobe_transform = None;
 
-	// Setup of variables necessary for going through the file
-	let mut file = file_result.unwrap();                                        // The struct for interacting with the file
-	let mut seek_counter = 2u64;                                                // A counter for keeping track of where in the file we currently are
-	let mut byte_buffer = [0u8; 1];                                             // A buffer for reading in a byte of data from the file
-	let mut previous_byte_was_marker_prefix = false;                            // A boolean for remembering if the previous byte was a marker prefix (0xFF)
-	let mut cleared_segments: u8 = 0;                                           // A counter for keeping track of how many segements were cleared
+	check_signature(file)?;
 
 	loop
 	{
-		// Read next byte into buffer
-		perform_file_action!(file.read(&mut byte_buffer));
+		// Every segment starts with the marker prefix...
+		let prefix_offset = file.stream_position()?;
+		let mut byte_buffer = [0u8; 1];
+		if !try_read_exact(file, &mut byte_buffer)?
+		{
+			return io_error!(UnexpectedEof, "Reached end of JPG file before start of scan!").with_parse_context(|| ParseErrorContext::new().offset(prefix_offset));
+		}
 
-		if previous_byte_was_marker_prefix
+		if byte_buffer[0] != JPG_MARKER_PREFIX
 		{
-			match byte_buffer[0]
+			return io_error!(InvalidData, "Expected JPG marker prefix!").with_parse_context(|| ParseErrorContext::new().offset(prefix_offset));
+		}
+
+		// ...which may be followed by any number of fill bytes (0xff)
+		while byte_buffer[0] == JPG_MARKER_PREFIX
+		{
+			perform_file_action!(file.read_exact(&mut byte_buffer).with_parse_context(|| ParseErrorContext::new().offset(prefix_offset)));
+		}
+
+		let marker  = JpegMarker::from_byte(byte_buffer[0]);
+		let offset  = file.stream_position()? - 2;
+		let context = || ParseErrorContext::new().offset(offset).chunk(&marker.to_string());
+
+		if marker.is_standalone()
+		{
+			if marker == JpegMarker::EOI
 			{
-				0xe1	=> {                                                    // APP1 marker
-
-					// Read in the length of the segment
-					// (which follows immediately after the marker)
-					let mut length_buffer = [0u8; 2];
-					perform_file_action!(file.read(&mut length_buffer));
-
-					// Decode the length to determine how much more data there is
-					let length = from_u8_vec_macro!(u16, &length_buffer.to_vec(), &Endian::Big);
-					let remaining_length = length - 2;
-
-					// Get to the next section
-					perform_file_action!(file.seek(SeekFrom::Current(remaining_length as i64)));
-
-					// ...copy data from there onwards into a buffer...
-					let mut buffer = Vec::new();
-					perform_file_action!(file.read_to_end(&mut buffer));
-
-					// ...compute the new file length while we are at it...
-					let new_file_length = (seek_counter-1) + buffer.len() as u64;
-
-					// ...go back to the chunk to be removed...
-					// Note on why -1: This has to do with "previous_byte_was_marker_prefix"
-					// We need to overwrite this byte as well - however, it was 
-					// read in the *previous* iteration, not this one
-					perform_file_action!(file.seek(SeekFrom::Start(seek_counter-1)));
-
-					// ...and overwrite it using the data from the buffer
-					perform_file_action!(file.write_all(&buffer));
-
-					// Seek back to where we started (-1 for same reason as above) 
-					// and decrement the seek_counter by 2 (= length of marker)
-					// as it will be incremented at the end of the loop again
-					perform_file_action!(file.seek(SeekFrom::Start(seek_counter-1)));
-					seek_counter -= 2;
-					cleared_segments += 1;
-
-					// Update the size of the file - otherwise there will be
-					// duplicate bytes at the end!
-					perform_file_action!(file.set_len(new_file_length));
-				},
-				0xd9	=> break,                                               // EOI marker
-				_		=> (),                                                  // Every other marker
+				break;
 			}
 
-			previous_byte_was_marker_prefix = false;
+			segments.push(JpgSegment { marker, offset, length: 0 });
+			continue;
+		}
+
+		// Read in the length of the segment
+		let mut length_buffer = [0u8; 2];
+		perform_file_action!(file.read_exact(&mut length_buffer).with_parse_context(context));
+		let length = u16::from_be_bytes(length_buffer);
+		if length < 2
+		{
+			return io_error!(InvalidData, "Illegal JPG segment length!").with_parse_context(context);
+		}
+
+		// Frame headers and the Adobe segment are the only ones whose payload
+		// we are interested in at this point
+		if matches!(marker, JpegMarker::SOF(_) | JpegMarker::APP(14))
+		{
+			let mut payload = vec![0u8; (length - 2) as usize];
+			perform_file_action!(file.read_exact(&mut payload).with_parse_context(context));
+
+			if matches!(marker, JpegMarker::SOF(_)) && payload.len() >= 6 && frame_info.is_none()
+			{
+				frame_info = Some(JpgFrameInfo {
+					sof_marker:      marker,
+					precision:       payload[0],
+					components:      payload[5],
+					width:           u16::from_be_bytes([payload[3], payload[4]]),
+					height:          u16::from_be_bytes([payload[1], payload[2]]),
+					adobe_transform: None,
+				});
+			}
+			else if marker == JpegMarker::APP(14) && payload.len() >= 12 && payload.starts_with(&ADOBE_IDENTIFIER)
+			{
+				adobe_transform = Some(payload[11]);
+			}
 		}
 		else
 		{
-			previous_byte_was_marker_prefix = byte_buffer[0] == JPG_MARKER_PREFIX;
+			perform_file_action!(file.seek(SeekFrom::Current((length - 2) as i64)).with_parse_context(context));
 		}
 
-		seek_counter += 1;
+		segments.push(JpgSegment { marker, offset, length });
+
+		if marker == JpegMarker::SOS
+		{
+			break;
+		}
+	}
 
+	if let Some(ref mut info) = frame_info
+	{
+		info.adobe_transform = adobe_transform;
 	}
 
-	return Ok(cleared_segments);
+	record!(span, segments, segments.len());
+	return Ok((segments, frame_info));
+}
+
+/// Checks if the given APP1 segment contains EXIF data (and not e.g. XMP)
+fn
+is_exif_segment
+<T: Read + Seek>
+(
+	file:    &mut T,
+	segment: &JpgSegment
+)
+-> Result<bool, std::io::Error>
+{
+	if segment.marker != JpegMarker::APP(1) || (segment.length as usize) < 2 + EXIF_HEADER.len()
+	{
+		return Ok(false);
+	}
+
+	let mut header_buffer = [0u8; 6];
+	perform_file_action!(file.seek(SeekFrom::Start(segment.offset + 4)));
+	perform_file_action!(file.read_exact(&mut header_buffer));
+
+	return Ok(header_buffer == EXIF_HEADER);
+}
+
+/// Gets the segments of the JPG data that contain EXIF data
+fn
+find_exif_segments
+<T: Read + Seek>
+(
+	cursor: &mut T
+)
+-> Result<Vec<JpgSegment>, std::io::Error>
+{
+	let (segments, _) = parse_jpg(cursor)?;
+
+	let mut exif_segments = Vec::new();
+	for segment in segments
+	{
+		if is_exif_segment(cursor, &segment)?
+		{
+			exif_segments.push(segment);
+		}
+	}
+
+	return Ok(exif_segments);
+}
+
+/// Removes all segments containing EXIF data from the JPG data and returns
+/// how many there were
+pub(crate) fn
+clear_metadata
+(
+	file_buffer: &mut Vec<u8>
+)
+-> Result<usize, std::io::Error>
+{
+	let exif_segments = find_exif_segments(&mut Cursor::new(&file_buffer))?;
+
+	// Remove the segments, starting with the last one so that the offsets of
+	// the remaining ones stay valid
+	for segment in exif_segments.iter().rev()
+	{
+		let start = segment.offset as usize;
+		let end   = start + segment.byte_count() as usize;
+		file_buffer.drain(start..end);
+	}
+
+	return Ok(exif_segments.len());
+}
+
+pub(crate) fn
+file_clear_metadata
+(
+	file_system: &dyn FileSystem,
+	path:        &Path
+)
+-> Result<usize, std::io::Error>
+{
+	let mut file_buffer = read_file_to_vec(open_file(file_system, path)?)?;
+	let removed_segment_count = clear_metadata(&mut file_buffer)?;
+	if removed_segment_count > 0
+	{
+		file_system.write(path, &file_buffer)?;
+	}
+	return Ok(removed_segment_count);
 }
 
 /// Provides the JPEG specific encoding result as vector of bytes to be used
@@ -177,101 +329,388 @@ as_u8_vec
 	encode_metadata_jpg(general_encoded_metadata)
 }
 
-/// Writes the given generally encoded metadata to the JP(E)G image file at 
-/// the specified path. 
-/// Note that any previously stored metadata under the APP1 marker gets removed
-/// first before writing the "new" metadata. 
+/// Writes the given generally encoded metadata to the JP(E)G data, right
+/// after the signature.
+/// Note that any previously stored EXIF metadata under the APP1 marker gets
+/// removed first before writing the "new" metadata.
 pub(crate) fn
 write_metadata
 (
-	path: &Path,
+	file_buffer:              &mut Vec<u8>,
 	general_encoded_metadata: &Vec<u8>
 )
 -> Result<(), std::io::Error>
 {
-	clear_metadata(path)?;
+	// The length field of the APP1 segment also covers itself and the header
+	if 2 + EXIF_HEADER.len() + general_encoded_metadata.len() > u16::MAX as usize
+	{
+		return io_error!(InvalidInput, "EXIF data too large for a JPG APP1 segment!");
+	}
+
+	clear_metadata(file_buffer)?;
 
-	// Encode the data specifically for JPG and open the file...
+	// Encode the data specifically for JPG and insert it after the signature
 	let encoded_metadata = encode_metadata_jpg(general_encoded_metadata);
-	let mut file = OpenOptions::new()
-		.write(true)
-		.read(true)
-		.open(path)
-		.expect("Could not open file");
-
-	// ...and copy everything after the signature into a buffer...
-	let mut buffer = Vec::new();
-	perform_file_action!(file.seek(SeekFrom::Start(JPG_SIGNATURE.len() as u64)));
-	perform_file_action!(file.read_to_end(&mut buffer));
-
-	// ...seek back to where the encoded data will be written
-	perform_file_action!(file.seek(SeekFrom::Start(JPG_SIGNATURE.len() as u64)));
-
-	// ...and write the exif data...
-	perform_file_action!(file.write_all(&encoded_metadata));
-
-	// ...and the rest of the file from the buffer
-	perform_file_action!(file.write_all(&buffer));
-	
+	let insert_position  = chunk_order(FileExtension::JPEG).metadata_insert_position(Vec::new());
+	file_buffer.splice(insert_position..insert_position, encoded_metadata);
+
+	return Ok(());
+}
+
+/// Writes the given generally encoded metadata to the JP(E)G image file at
+/// the specified path (see `write_metadata`)
+pub(crate) fn
+file_write_metadata
+(
+	file_system:              &dyn FileSystem,
+	path:                     &Path,
+	general_encoded_metadata: &Vec<u8>
+)
+-> Result<(), std::io::Error>
+{
+	let mut file_buffer = read_file_to_vec(open_file(file_system, path)?)?;
+	write_metadata(&mut file_buffer, general_encoded_metadata)?;
+	file_system.write(path, &file_buffer)?;
 	return Ok(());
 }
 
 pub(crate) fn
 read_metadata
+<T: Read + Seek>
 (
-	path: &Path
+	cursor:   &mut T,
+	warnings: &mut Vec<ParseErrorContext>
 )
 -> Result<Vec<u8>, std::io::Error>
 {
-	let file_result = check_signature(path);
+	let exif_segments = find_exif_segments(cursor)?;
+	for segment in exif_segments.iter().skip(1)
+	{
+		warnings.push(ParseErrorContext::new().offset(segment.offset).chunk(&segment.marker.to_string()).warning(
+			&format!("Found {} segments with EXIF data, only the first one is used!", exif_segments.len())
+		));
+	}
 
-	if file_result.is_err()
+	// Read in the data after the marker and length field of the first segment
+	if let Some(segment) = exif_segments.first()
 	{
-		return Err(file_result.err().unwrap());
+		let context = || ParseErrorContext::new().offset(segment.offset).chunk(&segment.marker.to_string());
+		let mut buffer = vec![0u8; (segment.length - 2) as usize];
+		perform_file_action!(cursor.seek(SeekFrom::Start(segment.offset + 4)).with_parse_context(context));
+		perform_file_action!(cursor.read_exact(&mut buffer).with_parse_context(context));
+
+		return exif_block::normalize(&buffer).with_parse_context(context);
 	}
 
-	// Setup of variables necessary for going through the file
-	let mut file = file_result.unwrap();                                        // The struct for interacting with the file
-	let mut byte_buffer = [0u8; 1];                                             // A buffer for reading in a byte of data from the file
-	let mut previous_byte_was_marker_prefix = false;                            // A boolean for remembering if the previous byte was a marker prefix (0xFF)
+	return MissingExif::error("No EXIF data found!");
+}
 
-	loop
+/// Gets the application segments of the JPG data with the given marker whose
+/// payload starts with the given header (e.g. `PHOTOSHOP_HEADER` in APP13)
+fn
+find_app_segments
+(
+	file_buffer: &[u8],
+	marker:      JpegMarker,
+	header:      &[u8]
+)
+-> Result<Vec<JpgSegment>, std::io::Error>
+{
+	let (segments, _) = parse_jpg(&mut Cursor::new(file_buffer))?;
+	return Ok(segments.into_iter().filter(|segment| {
+		let start = segment.offset as usize + 4;
+		segment.marker == marker && file_buffer.get(start..start + header.len()) == Some(header)
+	}).collect());
+}
+
+/// Replaces the application segments with the given marker and header (see
+/// `find_app_segments`) by a single one with the given payload following
+/// the header. The new segment takes the place of the first previous one,
+/// or - if there was none - follows the APP0 and APP1 segments (e.g. JFIF
+/// and EXIF) at the start of the file.
+fn
+replace_app_segments
+(
+	file_buffer: &mut Vec<u8>,
+	marker:      JpegMarker,
+	header:      &[u8],
+	payload:     &[u8]
+)
+-> Result<(), std::io::Error>
+{
+	let length = 2 + header.len() + payload.len();
+	if length > u16::MAX as usize
 	{
-		// Read next byte into buffer
-		perform_file_action!(file.read(&mut byte_buffer));
+		return io_error!(InvalidInput, format!("Data too large for a JPG {} segment!", marker));
+	}
 
-		if previous_byte_was_marker_prefix
-		{
-			match byte_buffer[0]
-			{
-				0xe1	=> {                                                    // APP1 marker
+	let app_segments = find_app_segments(file_buffer, marker, header)?;
+	let insert_position = match app_segments.first()
+	{
+		Some(segment) => segment.offset as usize,
+		None          => {
+			let (segments, _) = parse_jpg(&mut Cursor::new(&file_buffer))?;
+			segments.iter()
+				.take_while(|segment| matches!(segment.marker, JpegMarker::APP(0) | JpegMarker::APP(1)))
+				.last()
+				.map(|segment| (segment.offset + segment.byte_count()) as usize)
+				.unwrap_or(JPG_SIGNATURE.len())
+		}
+	};
 
-					// Read in the length of the segment
-					// (which follows immediately after the marker)
-					let mut length_buffer = [0u8; 2];
-					perform_file_action!(file.read(&mut length_buffer));
+	for segment in app_segments.iter().rev()
+	{
+		let start = segment.offset as usize;
+		file_buffer.drain(start..start + segment.byte_count() as usize);
+	}
 
-					// Decode the length to determine how much more data there is
-					let length = from_u8_vec_macro!(u16, &length_buffer.to_vec(), &Endian::Big);
-					let remaining_length = (length - 2) as usize;
+	let mut segment = vec![JPG_MARKER_PREFIX, marker.as_byte()];
+	segment.extend((length as u16).to_be_bytes());
+	segment.extend(header);
+	segment.extend(payload);
+	file_buffer.splice(insert_position..insert_position, segment);
 
-					// Read in the remaining data
-					let mut buffer = vec![0u8; remaining_length];
-					perform_file_action!(file.read(&mut buffer));
+	return Ok(());
+}
 
-					return Ok(buffer);
-				},
-				0xd9	=> break,                                               // EOI marker
-				_		=> (),                                                  // Every other marker
-			}
+/// Reads the Photoshop image resource blocks (without the header) of the
+/// JPG data. Resources split across several APP13 segments are joined.
+/// Returns `None` if there are none.
+pub(crate) fn
+read_photoshop_resources
+(
+	file_buffer: &[u8]
+)
+-> Result<Option<Vec<u8>>, std::io::Error>
+{
+	let segments = find_app_segments(file_buffer, JpegMarker::APP(13), PHOTOSHOP_HEADER)?;
+	if segments.is_empty()
+	{
+		return Ok(None);
+	}
+
+	let mut resources = Vec::new();
+	for segment in segments
+	{
+		let start = segment.offset as usize + 4 + PHOTOSHOP_HEADER.len();
+		let end   = segment.offset as usize + segment.byte_count() as usize;
+		resources.extend(&file_buffer[start..end]);
+	}
+	return Ok(Some(resources));
+}
+
+/// Replaces the Photoshop image resource blocks of the JPG data by the given
+/// ones, see `replace_app_segments`
+pub(crate) fn
+write_photoshop_resources
+(
+	file_buffer: &mut Vec<u8>,
+	resources:   &[u8]
+)
+-> Result<(), std::io::Error>
+{
+	replace_app_segments(file_buffer, JpegMarker::APP(13), PHOTOSHOP_HEADER, resources)
+}
+
+/// Reads the XMP packet of the JPG data, i.e. the payload of the first APP1
+/// segment with the XMP header. Returns `None` if there is none.
+pub(crate) fn
+read_xmp
+(
+	file_buffer: &[u8]
+)
+-> Result<Option<Vec<u8>>, std::io::Error>
+{
+	let segments = find_app_segments(file_buffer, JpegMarker::APP(1), XMP_HEADER)?;
+	return Ok(segments.first().map(|segment| {
+		let start = segment.offset as usize + 4 + XMP_HEADER.len();
+		let end   = segment.offset as usize + segment.byte_count() as usize;
+		file_buffer[start..end].to_vec()
+	}));
+}
+
+/// Replaces the XMP packet of the JPG data by the given one, see
+/// `replace_app_segments`. Extended XMP (split across several segments) is
+/// not supported.
+pub(crate) fn
+write_xmp
+(
+	file_buffer: &mut Vec<u8>,
+	packet:      &[u8]
+)
+-> Result<(), std::io::Error>
+{
+	replace_app_segments(file_buffer, JpegMarker::APP(1), XMP_HEADER, packet)
+}
+
+/// Gets the width and height of the image as given by the frame header.
+/// Returns `None` if there is none or the height is only defined by a DNL
+/// segment after the first scan.
+pub(crate) fn
+image_dimensions
+(
+	file_buffer: &[u8]
+)
+-> Option<(u32, u32)>
+{
+	let frame_info = parse_jpg(&mut Cursor::new(file_buffer)).ok()?.1?;
+	if frame_info.width == 0 || frame_info.height == 0
+	{
+		return None;
+	}
+	return Some((frame_info.width as u32, frame_info.height as u32));
+}
+
+pub(crate) fn
+file_read_metadata
+(
+	file_system: &dyn FileSystem,
+	path:        &Path,
+	warnings:    &mut Vec<ParseErrorContext>
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	read_metadata(&mut open_file(file_system, path)?, warnings)
+}
 
-			previous_byte_was_marker_prefix = false;
+#[cfg(test)]
+mod tests
+{
+	use std::path::Path;
+
+	use crate::exif_tag::ExifTag;
+	use crate::metadata::Metadata;
+	use crate::vfs::MemoryFileSystem;
+
+	/// Builds a small (not decodable, but structurally valid) JPEG with the
+	/// given frame header. The quantization table deliberately contains byte
+	/// sequences that look like APP1 and EOI markers
+	fn
+	build_jpg
+	(
+		sof_marker: u8,
+		precision:  u8,
+		components: u8,
+		adobe:      bool
+	)
+	-> Vec<u8>
+	{
+		let mut jpg = vec![0xff, 0xd8];
+
+		if adobe
+		{
+			// APP14 with "Adobe", version, flags0, flags1 and transform (2 = YCCK)
+			jpg.extend([0xff, 0xee, 0x00, 0x0e]);
+			jpg.extend(b"Adobe");
+			jpg.extend([0x00, 0x64, 0x00, 0x00, 0x00, 0x00, 0x02]);
 		}
-		else
+
+		// DQT with 16-bit precision values
+		jpg.extend([0xff, 0xdb, 0x00, 0x0b, 0x10]);
+		jpg.extend([0xff, 0xe1, 0xff, 0xd9, 0xff, 0xe1, 0x00, 0x01]);
+
+		// Frame header
+		let length = 8u16 + 3 * components as u16;
+		jpg.extend([0xff, sof_marker, (length >> 8) as u8, length as u8, precision, 0x00, 0x10, 0x00, 0x10, components]);
+		for id in 1..=components
 		{
-			previous_byte_was_marker_prefix = byte_buffer[0] == JPG_MARKER_PREFIX;
+			jpg.extend([id, 0x11, 0x00]);
 		}
+
+		// Start of scan followed by some entropy coded data and EOI
+		jpg.extend([0xff, 0xda, 0x00, 0x08, 0x01, 0x01, 0x00, 0x00, 0x3f, 0x00]);
+		jpg.extend([0x12, 0xff, 0x00, 0x34, 0xff, 0xd0, 0x56]);
+		jpg.extend([0xff, 0xd9]);
+
+		return jpg;
+	}
+
+	fn
+	round_trip
+	(
+		file_name: &str,
+		jpg:       &Vec<u8>
+	)
+	-> Result<(), std::io::Error>
+	{
+		let path = Path::new(file_name);
+		let file_system = MemoryFileSystem::new();
+		file_system.insert(path, jpg.clone());
+
+		let mut metadata = Metadata::new();
+		metadata.set_tag(ExifTag::ImageDescription("Print".to_string()));
+		metadata.set_tag(ExifTag::ISO(vec![100]));
+		metadata.write_to_file_in(&file_system, path)?;
+
+		// The image data (everything from the DQT segment on) has to be
+		// untouched, only the APP1 segment is new
+		let written = file_system.get(path).unwrap();
+		assert!(written.ends_with(&jpg[jpg.len() - 40..]));
+
+		let read_back = Metadata::new_from_path_in(&file_system, path)?;
+		assert_eq!(
+			read_back.get_tag(&ExifTag::ImageDescription(String::new())),
+			Some(&ExifTag::ImageDescription("Print".to_string()))
+		);
+
+		// Clearing has to restore the original file
+		assert_eq!(crate::jpg::file_clear_metadata(&file_system, path)?, 1);
+		assert_eq!(&file_system.get(path).unwrap(), jpg);
+
+		Ok(())
 	}
 
-	return io_error!(Other, "No EXIF data found!");
-}
\ No newline at end of file
+	#[test]
+	fn
+	detect_cmyk_adobe()
+	-> Result<(), std::io::Error>
+	{
+		let path = Path::new("cmyk_adobe.jpg");
+		let file_system = MemoryFileSystem::new();
+		file_system.insert(path, build_jpg(0xc0, 8, 4, true));
+
+		let mut file = crate::jpg::open_file(&file_system, path)?;
+		let (segments, _) = crate::jpg::parse_jpg(&mut file)?;
+		assert_eq!(segments.len(), 4);
+
+		let frame_info = crate::jpg_frame::jpg_frame_info(&build_jpg(0xc0, 8, 4, true))?;
+		assert_eq!(frame_info, Some(crate::jpg_frame::JpgFrameInfo {
+			sof_marker:      crate::chunk_types::JpegMarker::SOF(0),
+			precision:       8,
+			components:      4,
+			width:           16,
+			height:          16,
+			adobe_transform: Some(2),
+		}));
+		Ok(())
+	}
+
+	#[test]
+	fn
+	detect_12_bit()
+	-> Result<(), std::io::Error>
+	{
+		let frame_info = crate::jpg_frame::jpg_frame_info(&build_jpg(0xc1, 12, 3, false))?.unwrap();
+		assert_eq!(frame_info.sof_marker, crate::chunk_types::JpegMarker::SOF(1));
+		assert_eq!(frame_info.precision,  12);
+		assert_eq!(frame_info.components, 3);
+		assert_eq!(frame_info.adobe_transform, None);
+		Ok(())
+	}
+
+	#[test]
+	fn
+	write_cmyk_adobe()
+	-> Result<(), std::io::Error>
+	{
+		round_trip("cmyk_adobe.jpg", &build_jpg(0xc2, 8, 4, true))
+	}
+
+	#[test]
+	fn
+	write_12_bit()
+	-> Result<(), std::io::Error>
+	{
+		round_trip("12_bit.jpg", &build_jpg(0xc1, 12, 3, false))
+	}
+}
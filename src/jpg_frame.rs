@@ -0,0 +1,59 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Information about the frame of a JPEG file, as collected while walking the
+//! segments for reading or writing the metadata. Not needed for the metadata
+//! itself, but helpful for telling apart the many (more or less exotic) types
+//! of JPEG files, e.g. 12-bit extended sequential files or CMYK files with an
+//! Adobe APP14 segment as used by the print industry.
+
+use std::io::Cursor;
+
+use crate::chunk_types::JpegMarker;
+use crate::general_file_io::*;
+use crate::jpg;
+
+/// The frame header (SOFn segment) of a JPEG file and the color transform
+/// of its Adobe APP14 segment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct
+JpgFrameInfo
+{
+	pub sof_marker:      JpegMarker,                                            // SOF0 for baseline, SOF1 for extended sequential, ...
+	pub precision:       u8,                                                    // Bits per sample, usually 8 but 12 is possible as well
+	pub components:      u8,                                                    // 1 for grayscale, 3 for YCbCr/RGB, 4 for CMYK/YCCK
+	pub width:           u16,
+	pub height:          u16,                                                   // 0 if defined by the DNL segment instead
+	pub adobe_transform: Option<u8>,                                            // Color transform flag of the APP14 segment (if present)
+}
+
+/// Gets the frame information of a JPEG file
+/// - If the data is not a valid JPEG file, an error gets returned.
+/// - If there is no frame header before the first scan, `None` is returned.
+///
+/// # Examples
+/// ```no_run
+/// use little_exif::jpg_frame::jpg_frame_info;
+///
+/// let file_buffer = std::fs::read("image.jpg").unwrap();
+/// if let Some(frame_info) = jpg_frame_info(&file_buffer).unwrap()
+/// {
+///     let cmyk = frame_info.components == 4 && frame_info.adobe_transform.is_some();
+///     println!("{} ({} bit{})", frame_info.sof_marker, frame_info.precision, if cmyk { ", CMYK" } else { "" });
+/// }
+/// ```
+pub fn
+jpg_frame_info
+(
+	file_buffer: &[u8]
+)
+-> Result<Option<JpgFrameInfo>, std::io::Error>
+{
+	if !file_buffer.starts_with(&jpg::JPG_SIGNATURE)
+	{
+		return io_error!(InvalidData, "Can't open JPG file - Wrong signature!");
+	}
+
+	let (_, frame_info) = jpg::parse_jpg(&mut Cursor::new(file_buffer))?;
+	return Ok(frame_info);
+}
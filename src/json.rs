@@ -0,0 +1,43 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Minimal JSON support for the reports of the crate, which only need their
+//! strings to be escaped.
+
+/// Encodes the given string as JSON string literal, including the quotes
+pub(crate) fn
+json_string
+(
+	value: &str
+)
+-> String
+{
+	let mut escaped = String::from("\"");
+	for character in value.chars()
+	{
+		match character
+		{
+			'"'                          => escaped.push_str("\\\""),
+			'\\'                         => escaped.push_str("\\\\"),
+			character if character < ' ' => escaped.push_str(&format!("\\u{:04x}", character as u32)),
+			character                    => escaped.push(character),
+		}
+	}
+	escaped.push('"');
+	return escaped;
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::json::json_string;
+
+	#[test]
+	fn
+	escape_strings()
+	{
+		assert_eq!(json_string("Jane \"JD\" Doe"), r#""Jane \"JD\" Doe""#);
+		assert_eq!(json_string("C:\\Fotos\n"),     r#""C:\\Fotos\u000a""#);
+		assert_eq!(json_string("Ürlaub 写真"),      "\"Ürlaub 写真\"");
+	}
+}
@@ -2,10 +2,26 @@
 // See https://github.com/TechnikTobi/little_exif#license for licensing details
 
 //! # little_exif
-//! A small crate for reading and writing (at least some) EXIF data written entirely in Rust. Currently supports only .png and .jp(e)g files and a few dozen tags in IFD0 and ExifIFD.
+//! A small crate for reading and writing (at least some) EXIF data written entirely in Rust. Supports the tags of IFD0, ExifIFD, GPSIFD and InteropIFD.
 //! 
 //! Interaction is done via the [`Metadata`](metadata/struct.Metadata.html) struct and the [`ExifTag`](exif_tag/enum.ExifTag.html) enum.
 //!
+//! # Supported formats
+//! - JPEG (.jpg, .jpeg)
+//! - PNG (.png)
+//! - WebP (.webp)
+//! - TIFF (.tif, .tiff)
+//! - HEIF (.heic, .heif, .hif) and AVIF (.avif)
+//! - GIF (.gif)
+//! - Camera raw files: Canon (.cr2, .cr3), Olympus (.orf), Panasonic (.rw2) and Fujifilm (.raf)
+//! - AVI (.avi)
+//!
+//! Files that can't hold embedded EXIF data at all, like BMP (.bmp, .dib),
+//! are recognized and rejected with a
+//! [`NoEmbeddedMetadata`](errors/struct.NoEmbeddedMetadata.html) error.
+//! [`Metadata::write_to_file_or_sidecar`](metadata/struct.Metadata.html#method.write_to_file_or_sidecar)
+//! writes the metadata of such files to a sidecar file instead.
+//!
 //! # Usage
 //! ## Write EXIF data
 //! ```no_run
@@ -18,20 +34,107 @@
 //! );
 //! metadata.write_to_file(std::path::Path::new("image.png"));
 //! ```
+//!
+//! # Concurrent access
+//! `Metadata` is `Send` and `Sync`, and little_exif doesn't lock files.
+//! When the same file is accessed by several threads or processes at once,
+//! `Metadata::write_to_file_with_options` with `WriteOptions::safe_write`
+//! guarantees:
+//! - Readers (e.g. `Metadata::new_from_path`) see either the complete file
+//!   before or after a write, never a partially written one, as the new
+//!   file replaces the old one in a single rename.
+//! - Of concurrent writers, the last one wins: Each write is based on the
+//!   file as read at its start, so the changes of writers finishing earlier
+//!   may get lost, but the file is never a mix of several writes. Callers
+//!   that need every change to be kept have to serialize their writes.
+//! - With `WriteOptions::backup`, the backup holds the file as it was before
+//!   any of the writers changed it.
+//!
+//! Without `safe_write`, files are changed in place. Concurrent readers may
+//! then fail with an error or, in rare cases, read inconsistent data.
 
 #![forbid(unsafe_code)]
 #![crate_type = "lib"]
 #![crate_name = "little_exif"]
 
+mod chunk_order;
+mod codec;
+mod exif_block;
+#[cfg(test)]
+mod fault_injection;
+mod fingerprint;
 mod general_file_io;
+mod iptc;
+mod json;
 mod png;
 mod png_chunk;
 mod jpg;
 mod webp;
+mod tiff;
+mod heif;
+mod gif;
+mod avi;
+mod cr2;
+mod cr3;
+mod orf;
+mod rw2;
+mod raf;
+mod raw_file;
 mod riff_chunk;
+mod truncation;
 
 pub mod endian;
+pub mod astro;
+pub mod audit;
+pub mod bplist;
+pub mod burst;
+pub mod environment;
+pub mod errors;
+#[cfg(feature = "sqlite")]
+pub mod catalog;
+pub mod chunk_types;
+pub mod config;
+pub mod conversion;
+pub mod date_inference;
+pub mod dng_opcode;
+pub mod document;
+pub mod exif_date;
 pub mod exif_tag;
 pub mod exif_tag_format;
+pub mod exif_version;
+pub mod export;
+#[cfg(feature = "xattr")]
+pub mod extended_attributes;
 pub mod filetype;
-pub mod metadata;
\ No newline at end of file
+#[cfg(feature = "fits")]
+pub mod fits;
+pub mod focus_stack;
+pub mod generated_image;
+pub mod geocoding;
+#[cfg(feature = "geoid")]
+pub mod geoid;
+pub mod gps;
+pub mod hdr;
+pub mod jpg_frame;
+pub mod maker_note;
+pub mod metadata;
+pub mod metadata_cache;
+#[cfg(feature = "notify")]
+pub mod metadata_watcher;
+pub mod object_store;
+pub mod orientation;
+pub mod panorama;
+#[cfg(feature = "policy")]
+pub mod policy;
+pub mod provenance;
+pub mod range_reader;
+pub mod read_options;
+pub mod redaction;
+pub mod scan;
+pub mod shell_properties;
+pub mod timelapse;
+pub mod undo;
+pub mod vfs;
+pub mod write_options;
+pub mod xmp;
+pub mod xmp_schema;
\ No newline at end of file
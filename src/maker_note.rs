@@ -0,0 +1,902 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Decoding the vendor specific maker notes that cameras store in the
+//! `MakerNote` tag. Most vendors use an IFD, but with their own header, byte
+//! order and base for offsets, so the entries can only be found if the vendor
+//! is known. The values of the entries are kept as raw bytes, as their
+//! meaning is vendor (and often model) specific.
+//!
+//! Supported vendors:
+//! - Nikon (type 3 maker notes, with their own TIFF header)
+//! - Pentax (`AOC` and `PENTAX` headers)
+//! - Sony (with or without `SONY DSC`/`SONY CAM` header)
+//! - Apple (`Apple iOS` header), including selected fields of iPhones like
+//!   the content identifier linking a Live Photo to its video
+//! - Olympus/OM System (`OLYMP`, `OLYMPUS` and `OM SYSTEM` headers),
+//!   including the entries of the camera settings sub-IFD
+//! - Canon (without header)
+//!
+//! Editing the maker notes is not supported. They are written back as they
+//! are, which works for Nikon, Pentax and newer Olympus models as their
+//! offsets don't depend on the position of the maker notes. Sony, Canon and
+//! older Olympus offsets are relative to the start of the EXIF data and may
+//! no longer be valid after writing the metadata.
+
+use crate::bplist;
+use crate::bplist::PlistValue;
+use crate::endian::*;
+use crate::exif_tag::ExifTag;
+use crate::exif_tag_format::ExifTagFormat;
+
+const EXIF_OFFSET_TAG: u16 = 0x8769;
+const MAKER_NOTE_TAG:  u16 = 0x927c;
+const IFD_FORMAT:      u16 = 0x000d;
+
+const OLYMPUS_CAMERA_SETTINGS_TAG: u16 = 0x2020;
+
+/// The vendors whose maker notes can be decoded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum
+MakerNoteVendor
+{
+	Nikon,
+	Pentax,
+	Sony,
+	Apple,
+	Olympus,
+	Canon,
+}
+
+/// The setting that is varied over a bracketed series of shots
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum
+BracketKind
+{
+	Exposure,
+	Flash,
+	Iso,
+	WhiteBalance,
+	Focus,
+}
+
+/// The bracketing of a shot, see `MakerNote::bracket`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct
+Bracket
+{
+	pub kinds:       Vec<BracketKind>,                                          // Settings varied at once
+	pub shot_number: u32,                                                       // Position within the series
+	pub step:        Option<i32>,                                               // Step between the shots, in vendor specific units
+}
+
+/// An entry of the maker notes IFD, with its value as raw bytes in the byte
+/// order of the maker notes
+#[derive(Debug, Clone, PartialEq)]
+pub struct
+MakerNoteEntry
+{
+	pub tag:             u16,
+	pub format:          u16,
+	pub component_count: u32,
+	pub raw_data:        Vec<u8>,
+}
+
+/// The decoded maker notes, see `Metadata::maker_note`
+#[derive(Debug, Clone, PartialEq)]
+pub struct
+MakerNote
+{
+	vendor:   MakerNoteVendor,
+	endian:   Endian,
+	entries:  Vec<MakerNoteEntry>,
+	sub_ifds: Vec<(u16, Vec<MakerNoteEntry>)>,                                  // Tag of the pointer and entries
+}
+
+/// Reads the entries of the IFD starting at `ifd_start` within the data.
+/// Offsets to values that don't fit into an entry are relative to `origin`,
+/// which may be outside of the data (e.g. for Sony maker notes, whose
+/// offsets are relative to the start of the EXIF data). Entries with an
+/// unknown format or a value outside of the data are skipped.
+fn
+read_ifd_entries
+(
+	data:      &[u8],
+	ifd_start: usize,
+	origin:    i64,
+	endian:    &Endian
+)
+-> Option<Vec<MakerNoteEntry>>
+{
+	let read_u16 = |position: usize| endian.read::<u16>(data, position);
+	let read_u32 = |position: usize| endian.read::<u32>(data, position);
+
+	let entry_count = read_u16(ifd_start)? as usize;
+	if data.len() < ifd_start + 2 + entry_count * 12
+	{
+		return None;
+	}
+
+	let mut entries = Vec::new();
+	for index in 0..entry_count
+	{
+		let entry_start = ifd_start + 2 + index * 12;
+		let tag             = read_u16(entry_start)?;
+		let format          = read_u16(entry_start + 2)?;
+		let component_count = read_u32(entry_start + 4)?;
+
+		let bytes_per_component = match (ExifTagFormat::from_u16(format), format)
+		{
+			(Some(format), _)  => format.bytes_per_component() as u64,
+			(None, IFD_FORMAT) => 4,                                            // Offset of a sub-IFD
+			(None, _)          => continue,
+		};
+		let byte_count = bytes_per_component * component_count as u64;
+
+		let value_start = if byte_count <= 4
+		{
+			entry_start as i64 + 8
+		}
+		else
+		{
+			origin + read_u32(entry_start + 8)? as i64
+		};
+
+		if value_start < 0 || value_start as u64 + byte_count > data.len() as u64
+		{
+			continue;
+		}
+
+		entries.push(MakerNoteEntry
+		{
+			tag,
+			format,
+			component_count,
+			raw_data: data[value_start as usize..(value_start as u64 + byte_count) as usize].to_vec(),
+		});
+	}
+
+	return Some(entries);
+}
+
+fn
+endian_from_marker
+(
+	marker: &[u8]
+)
+-> Option<Endian>
+{
+	match marker
+	{
+		b"II" => Some(Endian::Little),
+		b"MM" => Some(Endian::Big),
+		_     => None,
+	}
+}
+
+/// Finds the offset of the `MakerNote` value relative to the start of the
+/// given TIFF data (i.e. the EXIF data without the `Exif\0\0` header), which
+/// is needed for vendors whose offsets are relative to it
+pub(crate) fn
+find_maker_note_offset
+(
+	tiff_data: &[u8],
+	endian:    &Endian
+)
+-> Option<u32>
+{
+	let ifd0_offset = endian.read::<u32>(tiff_data, 4)?;
+	let exif_offset = read_ifd_entries(tiff_data, ifd0_offset as usize, 0, endian)?
+		.into_iter()
+		.find(|entry| entry.tag == EXIF_OFFSET_TAG)
+		.and_then(|entry| endian.read::<u32>(&entry.raw_data, 0))?;
+
+	// The value itself is not needed, only its offset
+	let exif_offset = exif_offset as usize;
+	for index in 0..endian.read::<u16>(tiff_data, exif_offset)? as usize
+	{
+		let entry_start = exif_offset + 2 + index * 12;
+		if endian.read::<u16>(tiff_data, entry_start)? == MAKER_NOTE_TAG
+		{
+			return endian.read::<u32>(tiff_data, entry_start + 8);
+		}
+	}
+	return None;
+}
+
+impl
+MakerNote
+{
+	/// Decodes the maker notes, using the camera's make for determining the
+	/// vendor. `maker_note_offset` is the offset of the maker notes within
+	/// the EXIF data, `exif_endian` the byte order of the EXIF data.
+	pub(crate) fn
+	decode
+	(
+		make:              &str,
+		raw_data:          &[u8],
+		maker_note_offset: Option<u32>,
+		exif_endian:       &Endian
+	)
+	-> Option<MakerNote>
+	{
+		let make = make.to_uppercase();
+
+		if make.starts_with("NIKON")
+		{
+			// "Nikon\0", version (2 bytes), 2 unused bytes, then a complete
+			// TIFF structure that the offsets are relative to
+			if !raw_data.starts_with(b"Nikon\0\x02")
+			{
+				return None;
+			}
+			let endian = endian_from_marker(raw_data.get(10..12)?)?;
+			let ifd_offset = endian.read::<u32>(raw_data, 14)?;
+			let entries = read_ifd_entries(raw_data, 10 + ifd_offset as usize, 10, &endian)?;
+			return Some(MakerNote { vendor: MakerNoteVendor::Nikon, endian, entries, sub_ifds: Vec::new() });
+		}
+
+		if make.starts_with("PENTAX") || make.starts_with("RICOH IMAGING") || make.starts_with("ASAHI")
+		{
+			// Offsets are relative to the start of the maker notes
+			let (endian_marker, ifd_start) = if raw_data.starts_with(b"AOC\0")
+			{
+				(raw_data.get(4..6)?, 6)
+			}
+			else if raw_data.starts_with(b"PENTAX \0")
+			{
+				(raw_data.get(8..10)?, 10)
+			}
+			else
+			{
+				return None;
+			};
+			let endian = endian_from_marker(endian_marker).unwrap_or(Endian::Big);
+			let entries = read_ifd_entries(raw_data, ifd_start, 0, &endian)?;
+			return Some(MakerNote { vendor: MakerNoteVendor::Pentax, endian, entries, sub_ifds: Vec::new() });
+		}
+
+		if make.starts_with("SONY")
+		{
+			// Offsets are relative to the start of the EXIF data
+			let ifd_start = if raw_data.starts_with(b"SONY DSC \0\0\0") || raw_data.starts_with(b"SONY CAM \0\0\0") { 12 } else { 0 };
+			let endian = exif_endian.clone();
+			let entries = read_ifd_entries(raw_data, ifd_start, -(maker_note_offset? as i64), &endian)?;
+			return Some(MakerNote { vendor: MakerNoteVendor::Sony, endian, entries, sub_ifds: Vec::new() });
+		}
+
+		if make.starts_with("APPLE")
+		{
+			// "Apple iOS\0", version (2 bytes) and byte order, followed by
+			// the IFD. Offsets are relative to the start of the maker notes
+			if !raw_data.starts_with(b"Apple iOS\0")
+			{
+				return None;
+			}
+			let endian = endian_from_marker(raw_data.get(12..14)?)?;
+			let entries = read_ifd_entries(raw_data, 14, 0, &endian)?;
+			return Some(MakerNote { vendor: MakerNoteVendor::Apple, endian, entries, sub_ifds: Vec::new() });
+		}
+
+		if make.starts_with("OLYMPUS") || make.starts_with("OM DIGITAL")
+		{
+			// Newer models use their own header and byte order, with offsets
+			// relative to the start of the maker notes. Older ones ("OLYMP")
+			// use offsets relative to the start of the EXIF data
+			let (endian, ifd_start, origin) = if raw_data.starts_with(b"OLYMPUS\0")
+			{
+				(endian_from_marker(raw_data.get(8..10)?)?, 12, 0)
+			}
+			else if raw_data.starts_with(b"OM SYSTEM\0\0\0")
+			{
+				(endian_from_marker(raw_data.get(12..14)?)?, 16, 0)
+			}
+			else if raw_data.starts_with(b"OLYMP\0")
+			{
+				(exif_endian.clone(), 8, -(maker_note_offset? as i64))
+			}
+			else
+			{
+				return None;
+			};
+			let entries = read_ifd_entries(raw_data, ifd_start, origin, &endian)?;
+
+			// The camera settings are stored in a sub-IFD
+			let sub_ifds = entries.iter()
+				.filter(|entry| entry.tag == OLYMPUS_CAMERA_SETTINGS_TAG)
+				.filter_map(|entry| {
+					let sub_ifd_start = origin + endian.read::<u32>(&entry.raw_data, 0)? as i64;
+					let sub_entries = read_ifd_entries(raw_data, usize::try_from(sub_ifd_start).ok()?, origin, &endian)?;
+					Some((entry.tag, sub_entries))
+				})
+				.collect();
+			return Some(MakerNote { vendor: MakerNoteVendor::Olympus, endian, entries, sub_ifds });
+		}
+
+		if make.starts_with("CANON")
+		{
+			// A plain IFD with offsets relative to the start of the EXIF data
+			let endian = exif_endian.clone();
+			let entries = read_ifd_entries(raw_data, 0, -(maker_note_offset? as i64), &endian)?;
+			return Some(MakerNote { vendor: MakerNoteVendor::Canon, endian, entries, sub_ifds: Vec::new() });
+		}
+
+		return None;
+	}
+
+	pub fn
+	vendor
+	(
+		&self
+	)
+	-> MakerNoteVendor
+	{
+		self.vendor
+	}
+
+	/// The byte order of the raw data of the entries
+	pub fn
+	endian
+	(
+		&self
+	)
+	-> &Endian
+	{
+		&self.endian
+	}
+
+	pub fn
+	entries
+	(
+		&self
+	)
+	-> &Vec<MakerNoteEntry>
+	{
+		&self.entries
+	}
+
+	/// Gets the raw data of the entry with the given tag
+	pub fn
+	get_raw_data
+	(
+		&self,
+		tag: u16
+	)
+	-> Option<&Vec<u8>>
+	{
+		self.entries.iter().find(|entry| entry.tag == tag).map(|entry| &entry.raw_data)
+	}
+
+	/// Gets the number of shutter actuations, if the camera stores it:
+	/// - Nikon: `ShutterCount` (0x00a7)
+	/// - Pentax: `ShutterCount` (0x005d), which is obfuscated using the
+	///   date and time of the shot (0x0006 and 0x0007)
+	/// - Sony: The enciphered `Tag9050` block (0x9050) of bodies from the
+	///   SLT/NEX/ILCE generations up to 2020
+	pub fn
+	shutter_count
+	(
+		&self
+	)
+	-> Option<u32>
+	{
+		let read_u32 = |bytes: &[u8], endian: &Endian| endian.read::<u32>(bytes, 0);
+
+		match self.vendor
+		{
+			MakerNoteVendor::Nikon => read_u32(self.get_raw_data(0x00a7)?, &self.endian),
+			MakerNoteVendor::Pentax => {
+				let obfuscated = read_u32(self.get_raw_data(0x005d)?, &Endian::Big)?;
+				let date = read_u32(self.get_raw_data(0x0006)?, &Endian::Big)?;
+				let mut time = self.get_raw_data(0x0007)?.get(0..3)?.to_vec();
+				time.push(0x00);
+				Some(obfuscated ^ date ^ !read_u32(&time, &Endian::Big)?)
+			},
+			MakerNoteVendor::Sony => {
+				let block = decipher_sony(self.get_raw_data(0x9050)?);
+				Some(read_u32(block.get(0x3a..0x3e)?, &Endian::Little)? & 0x00ffffff)
+			},
+			MakerNoteVendor::Apple | MakerNoteVendor::Olympus | MakerNoteVendor::Canon => None,
+		}
+	}
+
+	/// Gets the temperature inside the camera body in degrees Celsius at the
+	/// time of the shot, which is close to the sensor temperature that
+	/// matters for the noise of long exposures, if the camera stores it:
+	/// - Canon: `CameraTemperature` of the shot info (0x0004)
+	/// - Pentax: `CameraTemperature` (0x0047)
+	///
+	/// The gain of the sensor is not stored by any of the vendors apart from
+	/// the ISO speed (see the `ISO` tag).
+	pub fn
+	sensor_temperature
+	(
+		&self
+	)
+	-> Option<f64>
+	{
+		match self.vendor
+		{
+			MakerNoteVendor::Canon => {
+				// Stored with an offset of 128, zero if unknown
+				let temperature = self.endian.read::<i16>(self.get_raw_data(0x0004)?, 12 * 2)?;
+				if temperature == 0 { None } else { Some((temperature - 128) as f64) }
+			},
+			MakerNoteVendor::Pentax => Some(*self.get_raw_data(0x0047)?.first()? as i8 as f64),
+			_                       => None,
+		}
+	}
+
+	/// Gets the position of the shot within its sequence (e.g. a burst or a
+	/// series taken by the interval timer), starting with 1, and the number
+	/// of the sequence's first file, if the camera stores them:
+	/// - Sony: `SequenceImageNumber` and `SequenceFileNumber` of the
+	///   enciphered `Tag9400` block (0x9400) in its first two versions, used
+	///   by the SLT/NEX/ILCE generations up to 2020
+	///
+	/// The interval timer settings of other vendors are stored in enciphered
+	/// or undocumented blocks and are not decoded.
+	pub fn
+	sequence_number
+	(
+		&self
+	)
+	-> Option<(u32, u32)>
+	{
+		if self.vendor != MakerNoteVendor::Sony
+		{
+			return None;
+		}
+
+		let block = decipher_sony(self.get_raw_data(0x9400)?);
+		if ![0x07, 0x09, 0x0a, 0x0c].contains(block.first()?)
+		{
+			return None;
+		}
+		let image_number = Endian::Little.read::<u32>(&block, 0x08)?;
+		let file_number  = Endian::Little.read::<u32>(&block, 0x0c)?;
+		Some((image_number.checked_add(1)?, file_number.checked_add(1)?))
+	}
+
+	/// Gets the bracketing a shot was taken with and its position within the
+	/// bracketed series, if the camera stores them:
+	/// - Olympus: `DriveMode` (0x0600) of the camera settings (0x2020)
+	/// - Canon: `BracketMode`, `BracketValue` and `BracketShotNumber` of the
+	///   file info (0x0093). Focus bracketing is not recorded there.
+	pub fn
+	bracket
+	(
+		&self
+	)
+	-> Option<Bracket>
+	{
+		match self.vendor
+		{
+			MakerNoteVendor::Olympus => {
+				let drive_mode = self.get_sub_ifd_raw_data(OLYMPUS_CAMERA_SETTINGS_TAG, 0x0600)?;
+				let value = |index: usize| self.endian.read::<u16>(drive_mode, index * 2);
+
+				let kinds = match value(0)?
+				{
+					2 => vec![BracketKind::Exposure],
+					3 => vec![BracketKind::WhiteBalance],
+					4 => vec![BracketKind::Exposure, BracketKind::WhiteBalance],
+					5 => {
+						// The bracketed settings are given as bit mask
+						let mask = value(2)?;
+						[
+							(0x0001, BracketKind::Exposure),
+							(0x0002, BracketKind::WhiteBalance),
+							(0x0004, BracketKind::Flash),
+							(0x0010, BracketKind::Iso),
+							(0x0040, BracketKind::Focus),
+						]
+							.into_iter()
+							.filter(|(bit, _)| mask & bit != 0)
+							.map(|(_, kind)| kind)
+							.collect()
+					},
+					_ => return None,
+				};
+				Some(Bracket { kinds, shot_number: value(1)? as u32, step: None })
+			},
+			MakerNoteVendor::Canon => {
+				let file_info = self.get_raw_data(0x0093)?;
+				let value = |index: usize| self.endian.read::<i16>(file_info, index * 2);
+
+				let kind = match value(3)?
+				{
+					1 => BracketKind::Exposure,
+					2 => BracketKind::Flash,
+					3 => BracketKind::Iso,
+					4 => BracketKind::WhiteBalance,
+					_ => return None,
+				};
+				Some(Bracket { kinds: vec![kind], shot_number: u32::try_from(value(5)?).ok()?, step: Some(value(4)? as i32) })
+			},
+			_ => None,
+		}
+	}
+
+	/// Gets the raw data of the entry with the given tag in the sub-IFD that
+	/// the entry `ifd_tag` points to
+	fn
+	get_sub_ifd_raw_data
+	(
+		&self,
+		ifd_tag: u16,
+		tag:     u16
+	)
+	-> Option<&Vec<u8>>
+	{
+		let (_, entries) = self.sub_ifds.iter().find(|(sub_ifd_tag, _)| *sub_ifd_tag == ifd_tag)?;
+		entries.iter().find(|entry| entry.tag == tag).map(|entry| &entry.raw_data)
+	}
+
+	/// Gets the value of a rational or float entry as floating point number
+	fn
+	get_f64
+	(
+		&self,
+		tag: u16
+	)
+	-> Option<f64>
+	{
+		let entry = self.entries.iter().find(|entry| entry.tag == tag)?;
+		match ExifTagFormat::from_u16(entry.format)?
+		{
+			ExifTagFormat::RATIONAL64U => Some(self.endian.read::<u32>(&entry.raw_data, 0)? as f64 / self.endian.read::<u32>(&entry.raw_data, 4)? as f64),
+			ExifTagFormat::RATIONAL64S => Some(self.endian.read::<i32>(&entry.raw_data, 0)? as f64 / self.endian.read::<i32>(&entry.raw_data, 4)? as f64),
+			ExifTagFormat::FLOAT       => Some(self.endian.read::<f32>(&entry.raw_data, 0)? as f64),
+			ExifTagFormat::DOUBLE      => Some(self.endian.read::<f64>(&entry.raw_data, 0)?),
+			_                          => None,
+		}
+	}
+
+	/// Gets the content identifier (0x0011) of Apple devices, which links a
+	/// Live Photo to its video
+	pub fn
+	apple_content_identifier
+	(
+		&self
+	)
+	-> Option<String>
+	{
+		if self.vendor != MakerNoteVendor::Apple
+		{
+			return None;
+		}
+		let raw_data = self.get_raw_data(0x0011)?;
+		Some(raw_data.iter().take_while(|byte| **byte != 0).map(|byte| *byte as char).collect())
+	}
+
+	/// Gets the HDR gain (0x0030) of Apple devices, which together with the
+	/// HDR headroom (0x0021) describes the gain map of HDR photos
+	pub fn
+	apple_hdr_gain
+	(
+		&self
+	)
+	-> Option<f64>
+	{
+		if self.vendor != MakerNoteVendor::Apple
+		{
+			return None;
+		}
+		self.get_f64(0x0030)
+	}
+
+	/// Gets the HDR headroom (0x0021) of Apple devices
+	pub fn
+	apple_hdr_headroom
+	(
+		&self
+	)
+	-> Option<f64>
+	{
+		if self.vendor != MakerNoteVendor::Apple
+		{
+			return None;
+		}
+		self.get_f64(0x0021)
+	}
+
+	/// Gets the acceleration vector (0x0008) of Apple devices, i.e. the
+	/// gravity in the x, y and z direction of the camera in units of g
+	pub fn
+	apple_acceleration_vector
+	(
+		&self
+	)
+	-> Option<[f64; 3]>
+	{
+		if self.vendor != MakerNoteVendor::Apple
+		{
+			return None;
+		}
+		let raw_data = self.get_raw_data(0x0008)?;
+		let component = |index: usize| Some(self.endian.read::<i32>(raw_data, index * 8)? as f64 / self.endian.read::<i32>(raw_data, index * 8 + 4)? as f64);
+		Some([component(0)?, component(1)?, component(2)?])
+	}
+
+	/// Gets the decoded binary property list of the `RunTime` entry (0x0003)
+	/// of Apple devices. It is a dictionary with the keys `flags`, `value`,
+	/// `timescale` and `epoch`, describing the uptime of the device when the
+	/// photo was taken, in `value / timescale` seconds.
+	pub fn
+	apple_run_time
+	(
+		&self
+	)
+	-> Option<PlistValue>
+	{
+		if self.vendor != MakerNoteVendor::Apple
+		{
+			return None;
+		}
+		bplist::decode(self.get_raw_data(0x0003)?).ok()
+	}
+}
+
+/// Deciphers the data of the Sony `0x94xx` and `0x9050` tags. Each byte
+/// below 249 is enciphered as its cube modulo 249, the others are unchanged
+fn
+decipher_sony
+(
+	enciphered: &[u8]
+)
+-> Vec<u8>
+{
+	let mut decipher_table: [u8; 256] = std::array::from_fn(|byte| byte as u8);
+	for byte in 0..249u32
+	{
+		decipher_table[(byte * byte * byte % 249) as usize] = byte as u8;
+	}
+	enciphered.iter().map(|byte| decipher_table[*byte as usize]).collect()
+}
+
+/// Gets the camera's make from the tags, which is needed for decoding the
+/// maker notes
+pub(crate) fn
+get_make
+(
+	tags: &[ExifTag]
+)
+-> Option<&str>
+{
+	tags.iter().find_map(|tag| match tag
+	{
+		ExifTag::Make(make) => Some(make.trim_end_matches(['\0', ' '])),
+		_                   => None,
+	})
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::endian::*;
+	use crate::maker_note::Bracket;
+	use crate::maker_note::BracketKind;
+	use crate::maker_note::MakerNote;
+	use crate::maker_note::MakerNoteVendor;
+	use crate::maker_note::decipher_sony;
+
+	/// Encodes an IFD with INT16/INT32U/RATIONAL/UNDEF entries whose values are stored after
+	/// the IFD, with offsets relative to `origin` within the data
+	fn
+	encode_ifd
+	(
+		prefix:  &[u8],
+		entries: &[(u16, u16, Vec<u8>)],
+		origin:  usize,
+		endian:  &Endian
+	)
+	-> Vec<u8>
+	{
+		let mut data = prefix.to_vec();
+		let mut value_area: Vec<u8> = Vec::new();
+		let value_area_start = prefix.len() + 2 + entries.len() * 12 + 4;
+
+		endian.write(&mut data, entries.len() as u16);
+		for (tag, format, value) in entries
+		{
+			let component_count = match *format
+			{
+				0x0003 | 0x0008 => value.len() as u32 / 2,
+				0x0004 | 0x000d => value.len() as u32 / 4,
+				0x0005 | 0x000a => value.len() as u32 / 8,
+				_               => value.len() as u32,
+			};
+			endian.write(&mut data, *tag);
+			endian.write(&mut data, *format);
+			endian.write(&mut data, component_count);
+			if value.len() <= 4
+			{
+				data.extend(value.iter().chain(std::iter::repeat(&0x00)).take(4));
+			}
+			else
+			{
+				let offset = (value_area_start + value_area.len() - origin) as u32;
+				endian.write(&mut data, offset);
+				value_area.extend(value);
+			}
+		}
+		data.extend([0x00; 4]);
+		data.extend(value_area);
+		return data;
+	}
+
+	#[test]
+	fn
+	nikon_shutter_count()
+	{
+		let mut prefix = b"Nikon\0\x02\x10\0\0MM\0\x2a".to_vec();
+		prefix.extend([0x00, 0x00, 0x00, 0x08]);
+		let raw_data = encode_ifd(&prefix, &[
+			(0x0001, 0x0007, b"0210".to_vec()),
+			(0x00a7, 0x0004, 12345u32.to_be_bytes().to_vec()),
+		], 10, &Endian::Big);
+
+		let maker_note = MakerNote::decode("NIKON CORPORATION", &raw_data, None, &Endian::Little).unwrap();
+		assert_eq!(maker_note.vendor(), MakerNoteVendor::Nikon);
+		assert_eq!(maker_note.shutter_count(), Some(12345));
+	}
+
+	#[test]
+	fn
+	pentax_shutter_count()
+	{
+		let date = [0x07, 0xe8, 0x01, 0x1f];                                    // 2024-01-31
+		let time = [0x12, 0x05, 0x00];                                          // 18:05:00
+		let obfuscated = 4711u32 ^ u32::from_be_bytes(date) ^ !u32::from_be_bytes([0x12, 0x05, 0x00, 0x00]);
+
+		let raw_data = encode_ifd(b"AOC\0MM", &[
+			(0x0006, 0x0007, date.to_vec()),
+			(0x0007, 0x0007, time.to_vec()),
+			(0x005d, 0x0007, obfuscated.to_be_bytes().to_vec()),
+		], 0, &Endian::Big);
+
+		let maker_note = MakerNote::decode("PENTAX", &raw_data, None, &Endian::Little).unwrap();
+		assert_eq!(maker_note.shutter_count(), Some(4711));
+	}
+
+	#[test]
+	fn
+	sony_shutter_count()
+	{
+		// Encipher a 9050 block with the shutter count at 0x3a
+		let mut block = vec![0x00; 0x100];
+		block[0x3a..0x3e].copy_from_slice(&98765u32.to_le_bytes());
+		let enciphered = block.iter()
+			.map(|byte| if *byte < 249 { ((*byte as u32).pow(3) % 249) as u8 } else { *byte })
+			.collect::<Vec<u8>>();
+		assert_eq!(decipher_sony(&enciphered), block);
+
+		// The maker notes are located at offset 1000 within the EXIF data
+		let raw_data = encode_ifd(b"SONY DSC \0\0\0", &[(0x9050, 0x0007, enciphered)], 0, &Endian::Little);
+		let raw_data_with_exif_offsets = {
+			let mut data = raw_data.clone();
+			let offset = u32::from_le_bytes(data[22..26].try_into().unwrap()) + 1000;
+			data[22..26].copy_from_slice(&offset.to_le_bytes());
+			data
+		};
+
+		let maker_note = MakerNote::decode("SONY", &raw_data_with_exif_offsets, Some(1000), &Endian::Little).unwrap();
+		assert_eq!(maker_note.shutter_count(), Some(98765));
+		assert!(MakerNote::decode("SONY", &raw_data_with_exif_offsets, None, &Endian::Little).is_none());
+		assert!(MakerNote::decode("Canon", &raw_data, None, &Endian::Little).is_none());
+	}
+
+	#[test]
+	fn
+	sony_sequence_number()
+	{
+		let encipher = |block: &[u8]| block.iter()
+			.map(|byte| if *byte < 249 { ((*byte as u32).pow(3) % 249) as u8 } else { *byte })
+			.collect::<Vec<u8>>();
+
+		// Third shot of a series whose first file is DSC01234
+		let mut block = vec![0x00; 0x40];
+		block[0x00] = 0x0c;
+		block[0x08..0x0c].copy_from_slice(&2u32.to_le_bytes());
+		block[0x0c..0x10].copy_from_slice(&1233u32.to_le_bytes());
+		let raw_data = encode_ifd(b"", &[(0x9400, 0x0007, encipher(&block))], 0, &Endian::Little);
+		let maker_note = MakerNote::decode("SONY", &raw_data, Some(0), &Endian::Little).unwrap();
+		assert_eq!(maker_note.sequence_number(), Some((3, 1234)));
+
+		// Unknown version of the block
+		block[0x00] = 0x23;
+		let raw_data = encode_ifd(b"", &[(0x9400, 0x0007, encipher(&block))], 0, &Endian::Little);
+		let maker_note = MakerNote::decode("SONY", &raw_data, Some(0), &Endian::Little).unwrap();
+		assert_eq!(maker_note.sequence_number(), None);
+	}
+
+	#[test]
+	fn
+	olympus_bracket()
+	{
+		// Camera settings sub-IFD directly after the main IFD, with the drive
+		// mode of the third shot of a focus bracketing series
+		let main_ifd = encode_ifd(b"OLYMPUS\0II\x03\0", &[(0x2020, 0x000d, 30u32.to_le_bytes().to_vec())], 0, &Endian::Little);
+		assert_eq!(main_ifd.len(), 30);
+		let drive_mode = [5u16, 3, 0x0040].iter().flat_map(|value| value.to_le_bytes()).collect::<Vec<u8>>();
+		let raw_data = encode_ifd(&main_ifd, &[(0x0600, 0x0003, drive_mode)], 0, &Endian::Little);
+
+		let maker_note = MakerNote::decode("OLYMPUS CORPORATION", &raw_data, None, &Endian::Big).unwrap();
+		assert_eq!(maker_note.vendor(), MakerNoteVendor::Olympus);
+		assert_eq!(maker_note.bracket(), Some(Bracket { kinds: vec![BracketKind::Focus], shot_number: 3, step: None }));
+		assert_eq!(maker_note.shutter_count(), None);
+	}
+
+	#[test]
+	fn
+	canon_bracket()
+	{
+		// File info with the second shot of an exposure bracketing series
+		let file_info = [0i16, 1234, 0, 1, 64, 2, 0].iter().flat_map(|value| value.to_be_bytes()).collect::<Vec<u8>>();
+		let raw_data = encode_ifd(b"", &[(0x0093, 0x0008, file_info)], 0, &Endian::Big);
+
+		let maker_note = MakerNote::decode("Canon", &raw_data, Some(0), &Endian::Big).unwrap();
+		assert_eq!(maker_note.vendor(), MakerNoteVendor::Canon);
+		assert_eq!(maker_note.bracket(), Some(Bracket { kinds: vec![BracketKind::Exposure], shot_number: 2, step: Some(64) }));
+		assert!(MakerNote::decode("Canon", &raw_data, None, &Endian::Big).is_none());
+	}
+
+	#[test]
+	fn
+	sensor_temperature()
+	{
+		let raw_data = encode_ifd(b"AOC\0MM", &[(0x0047, 0x0006, vec![0xfb])], 0, &Endian::Big);
+		let maker_note = MakerNote::decode("PENTAX", &raw_data, None, &Endian::Little).unwrap();
+		assert_eq!(maker_note.sensor_temperature(), Some(-5.0));
+
+		let mut shot_info = [0i16; 16];
+		shot_info[12] = 128 + 31;
+		let shot_info = shot_info.iter().flat_map(|value| value.to_le_bytes()).collect::<Vec<u8>>();
+		let raw_data = encode_ifd(b"", &[(0x0004, 0x0008, shot_info)], 0, &Endian::Little);
+		let maker_note = MakerNote::decode("Canon", &raw_data, Some(0), &Endian::Little).unwrap();
+		assert_eq!(maker_note.sensor_temperature(), Some(31.0));
+		assert_eq!(maker_note.bracket(), None);
+	}
+
+	#[test]
+	fn
+	apple_fields()
+	{
+		// {"value": 5000, "timescale": 1000} as binary plist
+		let mut run_time = b"bplist00".to_vec();
+		run_time.extend([0xd2, 1, 2, 3, 4]);
+		run_time.extend(b"\x55value\x59timescale");
+		run_time.extend([0x11, 0x13, 0x88, 0x11, 0x03, 0xe8]);
+		run_time.extend([8, 13, 19, 29, 32]);
+		run_time.extend([0, 0, 0, 0, 0, 0, 1, 1]);
+		run_time.extend(5u64.to_be_bytes());
+		run_time.extend(0u64.to_be_bytes());
+		run_time.extend(35u64.to_be_bytes());
+
+		let mut acceleration = Vec::new();
+		for value in [-98i32, 100, 3, 100, 1, 10]
+		{
+			acceleration.extend(value.to_be_bytes());
+		}
+
+		let raw_data = encode_ifd(b"Apple iOS\0\0\x01MM", &[
+			(0x0003, 0x0007, run_time),
+			(0x0008, 0x000a, acceleration),
+			(0x0011, 0x0002, b"1F2E3D4C-0000-1111-2222-333344445555\0".to_vec()),
+			(0x0030, 0x000a, [3i32.to_be_bytes(), 4i32.to_be_bytes()].concat()),
+		], 0, &Endian::Big);
+
+		let maker_note = MakerNote::decode("Apple", &raw_data, None, &Endian::Big).unwrap();
+		assert_eq!(maker_note.vendor(), MakerNoteVendor::Apple);
+		assert_eq!(maker_note.apple_content_identifier(), Some("1F2E3D4C-0000-1111-2222-333344445555".to_string()));
+		assert_eq!(maker_note.apple_hdr_gain(), Some(0.75));
+		assert_eq!(maker_note.apple_hdr_headroom(), None);
+		assert_eq!(maker_note.apple_acceleration_vector(), Some([-0.98, 0.03, 0.1]));
+		assert_eq!(maker_note.shutter_count(), None);
+
+		let run_time = maker_note.apple_run_time().unwrap();
+		assert_eq!(run_time.get("value").and_then(|value| value.as_integer()), Some(5000));
+		assert_eq!(run_time.get("timescale").and_then(|value| value.as_integer()), Some(1000));
+	}
+}
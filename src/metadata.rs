@@ -1,19 +1,74 @@
 // Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
 // See https://github.com/TechnikTobi/little_exif#license for licensing details
 
+use std::io::Cursor;
+use std::io::Read;
+use std::io::Seek;
+use std::io::Write;
 use std::path::Path;
 use std::str::FromStr;
 
+use crate::astro::AcquisitionKeywords;
+use crate::config::Config;
+use crate::date_inference;
 use crate::endian::*;
+use crate::environment;
+use crate::environment::AccelerationUnit;
+use crate::environment::LengthUnit;
+use crate::environment::PressureUnit;
+use crate::environment::TemperatureUnit;
+use crate::exif_block;
+use crate::errors::MetadataTooLarge;
+use crate::errors::MissingExif;
+use crate::errors::NoEmbeddedMetadata;
+use crate::errors::ParseErrorContext;
+use crate::errors::ReadOnlyTarget;
+use crate::errors::SharingViolation;
+use crate::errors::WithParseContext;
 use crate::exif_tag::ExifTag;
 use crate::exif_tag::ExifTagGroup;
 use crate::exif_tag_format::ExifTagFormat;
+use crate::exif_version;
 use crate::filetype::FileExtension;
+use crate::fingerprint;
+use crate::generated_image::ASCII_CHARACTER_CODE;
+use crate::gps;
+use crate::gps::GpsAltitudeRef;
+use crate::gps::GpsDirectionRef;
+use crate::maker_note;
+use crate::maker_note::MakerNote;
 use crate::general_file_io::*;
+#[cfg(feature = "policy")]
+use crate::policy::Policy;
+use crate::read_options::EmptyValues;
+use crate::read_options::ReadOptions;
+use crate::scan::Oecf;
+use crate::undo::UndoToken;
+use crate::vfs::FileSystem;
+use crate::vfs::OsFileSystem;
+use crate::write_options::LITTLE_EXIF_SOFTWARE;
+use crate::write_options::SoftwareStamp;
+use crate::write_options::TagHookAction;
+use crate::write_options::Truncation;
+use crate::write_options::SidecarFormat;
+use crate::write_options::WriteDestination;
+use crate::write_options::WriteOptions;
 
 use crate::jpg;
 use crate::png;
 use crate::webp;
+use crate::tiff;
+use crate::heif;
+use crate::gif;
+use crate::cr2;
+use crate::cr3;
+use crate::orf;
+use crate::rw2;
+use crate::raf;
+use crate::raw_file;
+use crate::avi;
+use crate::truncation;
+use crate::xmp;
 
 const IFD_ENTRY_LENGTH: u32     = 12;
 const IFD_END:          [u8; 4] = [0x00, 0x00, 0x00, 0x00];
@@ -21,8 +76,24 @@ const IFD_END:          [u8; 4] = [0x00, 0x00, 0x00, 0x00];
 pub struct
 Metadata
 {
-	data:   Vec<ExifTag>,
-	endian: Endian 
+	data:              Vec<ExifTag>,
+	endian:            Endian,
+	maker_note_offset: Option<u32>,                                             // Relative to the TIFF header, see maker_note module
+	warnings:          Vec<ParseErrorContext>,                                  // Non-fatal issues found while reading
+}
+
+impl
+Default
+for
+Metadata
+{
+	fn
+	default
+	()
+	-> Metadata
+	{
+		Metadata::new()
+	}
 }
 
 impl
@@ -44,7 +115,28 @@ Metadata
 	()
 	-> Metadata
 	{
-		Metadata { endian: Endian::Little, data: Vec::new() }
+		Self::new_with_config(&Config::current())
+	}
+
+	/// Constructs a new, empty `Metadata` object, using the byte order of
+	/// the given config instead of the current one (see `Config::current`).
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::config::Config;
+	/// use little_exif::endian::Endian;
+	/// use little_exif::metadata::Metadata;
+	///
+	/// let metadata = Metadata::new_with_config(&Config::new().endian(Endian::Big));
+	/// ```
+	pub fn
+	new_with_config
+	(
+		config: &Config
+	)
+	-> Metadata
+	{
+		Metadata { endian: config.endian.clone(), data: Vec::new(), maker_note_offset: None, warnings: Vec::new() }
 	}
 
 	/// Constructs a new `Metadata` object with the metadata from the image at the specified path.
@@ -64,60 +156,528 @@ Metadata
 	)
 	-> Result<Metadata, std::io::Error>
 	{
-		if !path.exists()
+		Self::new_from_path_with_options(path, &ReadOptions::new())
+	}
+
+	/// Constructs a new `Metadata` object with the metadata from the image at
+	/// the specified path, using the given options e.g. for validating every
+	/// PNG chunk. See `new_from_path` for further details.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::metadata::Metadata;
+	/// use little_exif::read_options::ReadOptions;
+	///
+	/// let metadata = Metadata::new_from_path_with_options(
+	///     std::path::Path::new("image.png"),
+	///     &ReadOptions::new().validate_crc(true)
+	/// ).unwrap();
+	/// ```
+	pub fn
+	new_from_path_with_options
+	(
+		path:    &Path,
+		options: &ReadOptions
+	)
+	-> Result<Metadata, std::io::Error>
+	{
+		let file_type = Self::get_file_type_for_read(&OsFileSystem, path)?;
+		let _span = span!("read", format = file_type.name(), file_size = std::fs::metadata(path).map(|metadata| metadata.len()).unwrap_or(0));
+
+		let mut warnings = Vec::new();
+		let raw_pre_decode_general = Self::read_pre_decode_general(&OsFileSystem, path, &file_type, options, &mut warnings);
+		return Self::from_raw_pre_decode_general(raw_pre_decode_general, warnings).handle_empty_values(options).check_lenient(options);
+	}
+
+	/// Constructs a new `Metadata` object with the metadata from the image at
+	/// the specified path of the given file system, e.g. a
+	/// `vfs::MemoryFileSystem`. See `new_from_path` for further details.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::metadata::Metadata;
+	/// use little_exif::vfs::MemoryFileSystem;
+	///
+	/// let path = std::path::Path::new("image.png");
+	/// let file_system = MemoryFileSystem::new();
+	/// file_system.insert(path, std::fs::read("image.png").unwrap());
+	/// let metadata = Metadata::new_from_path_in(&file_system, path).unwrap();
+	/// ```
+	pub fn
+	new_from_path_in
+	(
+		file_system: &dyn FileSystem,
+		path:        &Path
+	)
+	-> Result<Metadata, std::io::Error>
+	{
+		let options = ReadOptions::new();
+		let file_type = Self::get_file_type_for_read(file_system, path)?;
+		let _span = span!("read", format = file_type.name());
+
+		let mut warnings = Vec::new();
+		let raw_pre_decode_general = Self::read_pre_decode_general(file_system, path, &file_type, &options, &mut warnings);
+		return Self::from_raw_pre_decode_general(raw_pre_decode_general, warnings).handle_empty_values(&options).check_lenient(&options);
+	}
+
+	/// Constructs a new `Metadata` object with the metadata from the given
+	/// image data, e.g. received via network, without having to write it to a
+	/// file first. The type of the data can't be derived from a file name, so
+	/// it has to be provided.
+	/// - If the data is not a valid image of the given type, an error gets 
+	///   returned.
+	/// - If unable to decode the metadata, a new, empty object gets created 
+	///   and returned.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::metadata::Metadata;
+	/// use little_exif::filetype::FileExtension;
+	///
+	/// let file_buffer = std::fs::read("image.jpg").unwrap();
+	/// let metadata = Metadata::new_from_vec(&file_buffer, FileExtension::JPEG).unwrap();
+	/// ```
+	pub fn
+	new_from_vec
+	(
+		file_buffer: &Vec<u8>,
+		file_type:   FileExtension
+	)
+	-> Result<Metadata, std::io::Error>
+	{
+		Self::new_from_vec_with_options(file_buffer, file_type, &ReadOptions::new())
+	}
+
+	/// Constructs a new `Metadata` object with the metadata from the given
+	/// image data, using the given options. See `new_from_vec` for details.
+	fn
+	new_from_vec_with_options
+	(
+		file_buffer: &Vec<u8>,
+		file_type:   FileExtension,
+		options:     &ReadOptions
+	)
+	-> Result<Metadata, std::io::Error>
+	{
+		Self::new_from_seekable_reader(&mut Cursor::new(file_buffer), file_type, options)
+	}
+
+	/// Constructs a new `Metadata` object with the metadata from the image
+	/// provided by the given seekable reader. In contrast to `new_from_reader`
+	/// the image doesn't get buffered: Only the parts needed for finding and
+	/// reading the metadata are read, e.g. when fetching a remote file via a
+	/// `range_reader::RangeReader`. Reading PNG files without validating the
+	/// CRC of every chunk (see `ReadOptions::validate_crc`) skips the image
+	/// data.
+	/// - If the reader fails (e.g. due to a network error), its error gets
+	///   returned.
+	/// - Otherwise this behaves like `new_from_vec`.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::metadata::Metadata;
+	/// use little_exif::filetype::FileExtension;
+	/// use little_exif::read_options::ReadOptions;
+	///
+	/// let mut file = std::fs::File::open("image.webp").unwrap();
+	/// let metadata = Metadata::new_from_seekable_reader(&mut file, FileExtension::WEBP, &ReadOptions::new()).unwrap();
+	/// ```
+	pub fn
+	new_from_seekable_reader
+	<T: Read + Seek>
+	(
+		reader:    &mut T,
+		file_type: FileExtension,
+		options:   &ReadOptions
+	)
+	-> Result<Metadata, std::io::Error>
+	{
+		let _span = span!("read", format = file_type.name());
+
+		let mut warnings = Vec::new();
+		let mut tracked_reader = TrackedReader::new(reader);
+		let raw_pre_decode_general = Self::read_pre_decode_general_from_reader(&mut tracked_reader, &file_type, options, &mut warnings);
+
+		// Invalid image data and failures of the reader are reported as such,
+		// while missing metadata results in an empty object, just like when
+		// reading from a file
+		if let Err(error) = &raw_pre_decode_general
+		{
+			if error.kind() == std::io::ErrorKind::InvalidData || tracked_reader.failed()
+			{
+				return Err(raw_pre_decode_general.err().unwrap());
+			}
+		}
+
+		return Self::from_raw_pre_decode_general(raw_pre_decode_general, warnings).handle_empty_values(options).check_lenient(options);
+	}
+
+	/// Constructs a new `Metadata` object with the metadata from the image
+	/// read from the given stream, e.g. `stdin` in a shell pipeline. As the
+	/// stream doesn't need to be seekable, it gets buffered in memory up to
+	/// the limit set by `ReadOptions::max_stream_size`.
+	/// - If reading fails or the stream exceeds the limit, an error gets
+	///   returned.
+	/// - Otherwise this behaves like `new_from_vec`.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::metadata::Metadata;
+	/// use little_exif::filetype::FileExtension;
+	/// use little_exif::read_options::ReadOptions;
+	///
+	/// let metadata = Metadata::new_from_reader(std::io::stdin(), FileExtension::JPEG, &ReadOptions::new()).unwrap();
+	/// ```
+	pub fn
+	new_from_reader
+	(
+		reader:    impl Read,
+		file_type: FileExtension,
+		options:   &ReadOptions
+	)
+	-> Result<Metadata, std::io::Error>
+	{
+		let file_buffer = read_stream_to_vec(reader, options.max_stream_size)?;
+		return Self::new_from_vec_with_options(&file_buffer, file_type, options);
+	}
+
+	/// Calls the file specific decoders for obtaining the raw EXIF data from
+	/// image data provided by a reader, e.g. a cursor over data in memory
+	fn
+	read_pre_decode_general_from_reader
+	<T: Read + Seek>
+	(
+		reader:    &mut T,
+		file_type: &FileExtension,
+		options:   &ReadOptions,
+		warnings:  &mut Vec<ParseErrorContext>
+	)
+	-> Result<Vec<u8>, std::io::Error>
+	{
+		match file_type
+		{
+			FileExtension::JPEG 
+				=>  jpg::read_metadata(reader, warnings),
+			FileExtension::PNG {as_zTXt_chunk: _} 
+				=>  png::read_metadata(reader, options, warnings),
+			FileExtension::WEBP 
+				=> webp::read_metadata(reader, warnings),
+			FileExtension::TIFF
+				=> tiff::read_metadata(reader),
+			FileExtension::HEIF | FileExtension::AVIF
+				=> heif::read_metadata(reader),
+			FileExtension::GIF
+				=> gif::read_metadata(reader),
+			FileExtension::CR2
+				=> cr2::CR2.read_metadata(reader),
+			FileExtension::CR3
+				=> cr3::read_metadata(reader),
+			FileExtension::ORF
+				=> orf::ORF.read_metadata(reader),
+			FileExtension::RW2
+				=> rw2::RW2.read_metadata(reader),
+			FileExtension::RAF
+				=> raf::read_metadata(reader, warnings),
+			FileExtension::AVI
+				=> avi::read_metadata(reader, warnings),
+		}
+	}
+
+	/// Decodes the raw EXIF data read by one of the file specific decoders.
+	/// Falls back to an empty object if reading or decoding failed, in which
+	/// case the error is added to the given warnings - unless the file simply
+	/// doesn't contain any EXIF data
+	fn
+	from_raw_pre_decode_general
+	(
+		raw_pre_decode_general: Result<Vec<u8>, std::io::Error>,
+		mut warnings:           Vec<ParseErrorContext>
+	)
+	-> Metadata
+	{
+		let error = match raw_pre_decode_general
+		{
+			Ok(pre_decode_general) => match Self::decode(&pre_decode_general)
+			{
+				Ok(mut metadata) => {
+					metadata.warnings = warnings;
+					return metadata;
+				},
+				Err(error)       => Some(error),
+			},
+			Err(error) if MissingExif::is(&error) => None,
+			Err(error)                            => Some(error),
+		};
+
+		if let Some(error) = error
+		{
+			warnings.push(match ParseErrorContext::from_io_error(&error)
+			{
+				Some(context) => context.clone(),
+				None          => ParseErrorContext::new().warning(&error.to_string()),
+			});
+		}
+
+		let mut metadata = Metadata::new();
+		metadata.warnings = warnings;
+		return metadata;
+	}
+	
+	/// Drops or reports the tags without an actual value, see
+	/// `ReadOptions::empty_values`
+	fn
+	handle_empty_values
+	(
+		mut self,
+		options: &ReadOptions
+	)
+	-> Metadata
+	{
+		match options.empty_values
+		{
+			EmptyValues::Keep   => (),
+			EmptyValues::Skip   => self.data.retain(|tag| !tag.is_empty_value()),
+			EmptyValues::Reject => {
+				for tag in self.data.iter().filter(|tag| tag.is_empty_value())
+				{
+					self.warnings.push(ParseErrorContext::new()
+						.ifd(&format!("{:?}", tag.get_group()))
+						.warning(&format!("Tag {} has an empty value", tag.name()))
+					);
+				}
+			},
+		}
+		return self;
+	}
+
+	/// Turns the first warning into an error if reading is not lenient, see
+	/// `ReadOptions::lenient`
+	fn
+	check_lenient
+	(
+		self,
+		options: &ReadOptions
+	)
+	-> Result<Metadata, std::io::Error>
+	{
+		match self.warnings.first()
+		{
+			Some(warning) if !options.lenient => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, warning.clone())),
+			_                                 => Ok(self),
+		}
+	}
+
+	/// Checks whether the image at the specified path contains EXIF data 
+	/// with at least one tag. This only reads the raw EXIF data from the file
+	/// and does not decode the tags, making it a fast yes/no probe e.g. for 
+	/// deciding whether a file needs to be stripped.
+	/// - If the file does not exist, its type is unsupported or its EXIF data
+	///   can't be read (e.g. because the file is truncated or IFD0 is
+	///   corrupt), an error gets returned. A file that can't be probed is
+	///   thereby never mistaken for one without metadata.
+	/// - If there is no EXIF data, this returns `false`.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::metadata::Metadata;
+	///
+	/// if Metadata::has_exif(std::path::Path::new("image.png")).unwrap()
+	/// {
+	///     // strip the metadata
+	/// }
+	/// ```
+	pub fn
+	has_exif
+	(
+		path: &Path
+	)
+	-> Result<bool, std::io::Error>
+	{
+		return Ok(Self::probe_ifd0_tag_hexes(path)?.is_some_and(|tag_hexes| !tag_hexes.is_empty()));
+	}
+
+	/// Checks whether the image at the specified path contains GPS data, i.e.
+	/// an IFD0 entry pointing to the GPS IFD. As with `has_exif`, the tags
+	/// are not decoded and the same error semantics apply.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::metadata::Metadata;
+	///
+	/// if Metadata::has_gps(std::path::Path::new("image.png")).unwrap()
+	/// {
+	///     // strip the location
+	/// }
+	/// ```
+	pub fn
+	has_gps
+	(
+		path: &Path
+	)
+	-> Result<bool, std::io::Error>
+	{
+		return Ok(Self::probe_ifd0_tag_hexes(path)?.is_some_and(|tag_hexes| tag_hexes.contains(&ExifTag::GPSInfo(Vec::new()).as_u16())));
+	}
+
+	/// Reads the raw EXIF data of the file and gets the hex values of the
+	/// tags in IFD0, see `get_ifd0_tag_hexes`. Returns `None` if the file
+	/// has no EXIF data, while all other errors (e.g. a truncated file or a
+	/// corrupt IFD0) are passed on.
+	fn
+	probe_ifd0_tag_hexes
+	(
+		path: &Path
+	)
+	-> Result<Option<Vec<u16>>, std::io::Error>
+	{
+		let file_type = Self::get_file_type_for_read(&OsFileSystem, path)?;
+		let pre_decode_general = match Self::read_pre_decode_general(&OsFileSystem, path, &file_type, &ReadOptions::new(), &mut Vec::new())
+		{
+			Ok(pre_decode_general)                => pre_decode_general,
+			Err(error) if MissingExif::is(&error) => return Ok(None),
+			Err(error)                            => return Err(error),
+		};
+		return Ok(Some(Self::get_ifd0_tag_hexes(&pre_decode_general)?));
+	}
+
+	/// Determines the type of the file at the given path for reading from it
+	fn
+	get_file_type_for_read
+	(
+		file_system: &dyn FileSystem,
+		path:        &Path
+	)
+	-> Result<FileExtension, std::io::Error>
+	{
+		if !file_system.exists(path)
 		{
 			return io_error!(Other, "Can't read Metadata - File does not exist!");
 		}
 
-		let raw_file_type_str = path.extension();
-		if raw_file_type_str.is_none()
+		if path.extension().is_none()
 		{
 			return io_error!(Other, "Can't get extension from given path!");
 		}
 
-		let file_type_str = raw_file_type_str.unwrap().to_str();
-		if file_type_str.is_none()
+		if let Some(file_type) = FileExtension::from_path(path)
 		{
-			return io_error!(Other, "Can't convert file type to string!");
+			return Ok(file_type);
 		}
+		return Self::unsupported_file_type(path, "Can't read Metadata - Unsupported file type!");
+	}
 
-		let raw_file_type = FileExtension::from_str(file_type_str.unwrap().to_lowercase().as_str());
-		if raw_file_type.is_err()
+	/// Gets the error for a path with an unsupported extension, which is a
+	/// `NoEmbeddedMetadata` for file types that can't hold metadata at all
+	fn
+	unsupported_file_type<T>
+	(
+		path:    &Path,
+		message: &str
+	)
+	-> Result<T, std::io::Error>
+	{
+		if let Some(format) = FileExtension::without_embedded_metadata(path)
 		{
-			return io_error!(Unsupported, "Can't read Metadata - Unsupported file type!");
+			return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, NoEmbeddedMetadata::new(path, format)));
 		}
+		return io_error!(Unsupported, message.to_string());
+	}
 
-		// Call the file specific decoders as a starting point for obtaining
-		// the raw EXIF data that gets further processed
-		let raw_pre_decode_general = match raw_file_type.unwrap()
+	/// Calls the file specific decoders as a starting point for obtaining
+	/// the raw EXIF data that gets further processed
+	fn
+	read_pre_decode_general
+	(
+		file_system: &dyn FileSystem,
+		path:        &Path,
+		file_type:   &FileExtension,
+		options:     &ReadOptions,
+		warnings:    &mut Vec<ParseErrorContext>
+	)
+	-> Result<Vec<u8>, std::io::Error>
+	{
+		match file_type
 		{
 			FileExtension::JPEG 
-				=>  jpg::read_metadata(&path),
+				=>  jpg::file_read_metadata(file_system, path, warnings),
 			FileExtension::PNG {as_zTXt_chunk: _} 
-				=>  png::read_metadata(&path),
+				=>  png::file_read_metadata(file_system, path, options, warnings),
 			FileExtension::WEBP 
-				=> webp::read_metadata(&path),
-		};
+				=> webp::file_read_metadata(file_system, path, warnings),
+			FileExtension::TIFF
+				=> tiff::file_read_metadata(file_system, path),
+			FileExtension::HEIF | FileExtension::AVIF
+				=> heif::file_read_metadata(file_system, path),
+			FileExtension::GIF
+				=> gif::file_read_metadata(file_system, path),
+			FileExtension::CR2
+				=> cr2::CR2.file_read_metadata(file_system, path),
+			FileExtension::CR3
+				=> cr3::file_read_metadata(file_system, path),
+			FileExtension::ORF
+				=> orf::ORF.file_read_metadata(file_system, path),
+			FileExtension::RW2
+				=> rw2::RW2.file_read_metadata(file_system, path),
+			FileExtension::RAF
+				=> raf::file_read_metadata(file_system, path, warnings),
+			FileExtension::AVI
+				=> avi::file_read_metadata(file_system, path, warnings),
+		}
+	}
 
-		if let Ok(pre_decode_general) = raw_pre_decode_general
+	/// Gets the hex values of the tags in IFD0 without decoding them, which
+	/// includes the offset tags pointing to the SubIFDs (e.g. GPSInfo)
+	fn
+	get_ifd0_tag_hexes
+	(
+		encoded_data: &Vec<u8>
+	)
+	-> Result<Vec<u16>, std::io::Error>
+	{
+		// The readers may return the data without the EXIF header (e.g. AVI),
+		// which `decode` accepts as well
+		let encoded_data = exif_block::normalize(encoded_data)?;
+
+		// Validate EXIF header and determine endian
+		if !encoded_data.starts_with(&EXIF_HEADER) || encoded_data.len() < EXIF_HEADER.len() + 8
 		{
-			let decoding_result = Self::decode_metadata_general(&pre_decode_general);
-			if let Ok((endian, data)) = decoding_result
-			{
-				return Ok(Metadata { endian, data });
-			}
-			else
-			{
-				eprintln!("{}", decoding_result.err().unwrap());
-			}
+			return io_error!(Other, "Could not validate EXIF header!");
+		}
+
+		let tiff_data = &encoded_data[EXIF_HEADER.len()..];
+		let endian = if tiff_data.starts_with(&Endian::Little.header()[0..4])
+		{
+			Endian::Little
+		}
+		else if tiff_data.starts_with(&Endian::Big.header()[0..4])
+		{
+			Endian::Big
 		}
 		else
 		{
-			eprintln!("Error during decoding: {:?}", raw_pre_decode_general.err().unwrap());
+			return io_error!(Other, "Illegal endian information!");
+		};
+
+		// Locate IFD0 and read the tag of each of its entries
+		let ifd0_offset = endian.read::<u32>(tiff_data, 4).unwrap_or_default() as usize;
+		let number_of_entries = match endian.read::<u16>(tiff_data, ifd0_offset)
+		{
+			Some(number_of_entries) => number_of_entries as usize,
+			None                    => return io_error!(Other, "IFD0 offset out of range!"),
+		};
+		if tiff_data.len() < ifd0_offset + 2 + number_of_entries * IFD_ENTRY_LENGTH as usize
+		{
+			return io_error!(Other, "Not enough data for IFD0 entries!");
+		}
+
+		let mut tag_hexes = Vec::new();
+		for i in 0..number_of_entries
+		{
+			let entry_start_index = ifd0_offset + 2 + i * IFD_ENTRY_LENGTH as usize;
+			tag_hexes.extend(endian.read::<u16>(tiff_data, entry_start_index));
 		}
 
-		eprintln!("WARNING: Can't read metadata from file - Create new & empty struct");
-		return Ok(Metadata::new());
+		return Ok(tag_hexes);
 	}
 	
 	/// Gets a shared reference to the list of all tags currently stored in the object.
@@ -129,7 +689,7 @@ Metadata
 	/// let metadata = Metadata::new_from_path(std::path::Path::new("image.png")).unwrap();
 	/// for tag in metadata.data()
 	/// {
-	///     // do something with the tags	
+	///     // do something with the tags
 	/// }
 	/// ```
 	pub fn
@@ -142,18 +702,57 @@ Metadata
 		&self.data
 	}
 
-	/// Gets the stored tag in the metadata for the given tag. 
-	/// Returns `None` if the tag is not present in the metadata struct.
+	/// Gets the byte order the metadata gets encoded with
+	pub(crate) fn
+	endian
+	(
+		&self
+	)
+	-> &Endian
+	{
+		&self.endian
+	}
+
+	/// Gets the non-fatal issues found while reading the metadata, e.g. an
+	/// EXIF chunk in a WebP file whose VP8X flag is not set, or the error
+	/// that occurred while decoding the EXIF data if it couldn't be read at
+	/// all (in which case the object is empty). Objects not created by
+	/// reading a file or image data have no warnings.
 	///
 	/// # Examples
 	/// ```no_run
 	/// use little_exif::metadata::Metadata;
-	/// use little_exif::exif_tag::ExifTag;
-	/// 
-	/// let metadata = Metadata::new_from_path(std::path::Path::new("image.png")).unwrap();
-	/// let tag = metadata.get_tag(&ExifTag::ImageDescription(String::new()));
-	/// ```
-	pub fn
+	///
+	/// let metadata = Metadata::new_from_path(std::path::Path::new("image.webp")).unwrap();
+	/// for warning in metadata.warnings()
+	/// {
+	///     println!("{}", warning);
+	/// }
+	/// ```
+	pub fn
+	warnings
+	(
+		&self
+	)
+	-> &Vec<ParseErrorContext>
+	{
+		&self.warnings
+	}
+
+	/// Gets the stored tag in the metadata for the given tag. 
+	/// Returns `None` if the tag is not present in the metadata struct.
+	/// Tags of the GPS and the Interop IFD are only matched by tags of the
+	/// same IFD, as their IDs overlap with those of the other IFDs.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::metadata::Metadata;
+	/// use little_exif::exif_tag::ExifTag;
+	/// 
+	/// let metadata = Metadata::new_from_path(std::path::Path::new("image.png")).unwrap();
+	/// let tag = metadata.get_tag(&ExifTag::ImageDescription(String::new()));
+	/// ```
+	pub fn
 	get_tag
 	(
 		&self,
@@ -161,167 +760,1901 @@ Metadata
 	)
 	-> Option<&ExifTag> 
 	{
-		self.get_tag_by_hex(input_tag.as_u16())
+		self.data.iter().find(|tag| tag.as_u16() == input_tag.as_u16() && tag.get_group().id_space() == input_tag.get_group().id_space())
+	}
+
+	/// Gets the sored tag in the metadata by its hex value.
+	/// Returns `None`if the tag is not present in the metadata struct.
+	/// 
+	/// # Examples
+	/// ```no_run
+	/// // Note that the tag identifier of course does not need to be written in hex format
+	/// // Hex notation only used in this example for more clarity
+	/// use little_exif::metadata::Metadata;
+	/// 
+	/// let metadata = Metadata::new_from_path(std::path::Path::new("image.png")).unwrap();
+	/// let tag = metadata.get_tag_by_hex(0x010e);
+	/// ```
+	pub fn
+	get_tag_by_hex
+	(
+		&self,
+		input_tag_hex: u16
+	)
+	-> Option<&ExifTag>
+	{
+		self.data.iter().find(|tag| tag.as_u16() == input_tag_hex)
+	}
+
+	/// Gets the stored tag in the metadata by its name, ignoring the case and
+	/// accepting alternative names, see `ExifTag::from_name`.
+	/// Returns `None` if the name is unknown or the tag is not present in the
+	/// metadata struct.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::metadata::Metadata;
+	///
+	/// let metadata = Metadata::new_from_path(std::path::Path::new("image.png")).unwrap();
+	/// let tag = metadata.get_tag_by_name("Aperture");
+	/// ```
+	pub fn
+	get_tag_by_name
+	(
+		&self,
+		name: &str
+	)
+	-> Option<&ExifTag>
+	{
+		self.get_tag(&ExifTag::from_name(name).ok()?)
+	}
+
+	/// Gets the value of the stored string tag, without trailing NUL
+	/// characters and spaces (some cameras pad e.g. `Make` with spaces).
+	/// Returns `None` if the tag is not present or not a string tag.
+	pub(crate) fn
+	get_string_value
+	(
+		&self,
+		input_tag: &ExifTag
+	)
+	-> Option<String>
+	{
+		let tag = self.get_tag(input_tag)?;
+		if !tag.is_string()
+		{
+			return None;
+		}
+
+		let raw_data = tag.value_as_u8_vec(&Endian::Little);
+		let value = String::from_u8_vec(&raw_data, &Endian::Little);
+		return Some(value.trim_end_matches(['\0', ' ']).to_string());
+	}
+
+	/// Sets the tag in the metadata struct. If the tag is already in there it gets replaced
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::metadata::Metadata;
+	/// use little_exif::exif_tag::ExifTag;
+	/// 
+	/// let mut metadata = Metadata::new();
+	/// metadata.set_tag(
+	///     ExifTag::ISO(vec![1234])
+	/// );
+	/// ```
+	pub fn
+	set_tag
+	(
+		&mut self,
+		input_tag: ExifTag,
+	)
+	{
+		// Replaced maker notes are no longer located where they were read from
+		if let ExifTag::MakerNote(_) = input_tag
+		{
+			self.maker_note_offset = None;
+		}
+
+		self.data.retain(|tag| tag.as_u16() != input_tag.as_u16() || tag.get_group().id_space() != input_tag.get_group().id_space());
+		self.data.push(input_tag);
+
+		// Sort the tags by the IFD they will go into the file later on
+		self.data.sort_by(
+			|a, b| 
+			if a.get_group() == b.get_group() 
+			{
+				// Same group, but unknown should go last 
+				if a.is_unknown() == b.is_unknown()
+				{
+					std::cmp::Ordering::Equal
+				}
+				else if !a.is_unknown() && b.is_unknown()
+				{
+					std::cmp::Ordering::Less
+				}
+				else
+				{
+					std::cmp::Ordering::Greater
+				}
+				
+			}
+			else
+			{
+				if a.get_group() < b.get_group()                                // e.g. IFD0 < ExifIFD
+				{
+					std::cmp::Ordering::Less
+				}
+				else
+				{
+					std::cmp::Ordering::Greater
+				}
+			}
+		);
+	}
+
+	/// Applies the given redaction policy, removing or hashing tags as
+	/// described in the `policy` module. Only the object is modified, write
+	/// it to the image afterwards. Only available with the `policy` feature.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::exif_tag::ExifTag;
+	/// use little_exif::metadata::Metadata;
+	/// use little_exif::policy::Policy;
+	///
+	/// let mut metadata = Metadata::new_from_path(std::path::Path::new("image.jpg")).unwrap();
+	/// metadata.apply_policy(&Policy::new().hash(&ExifTag::SerialNumber(String::new())));
+	/// ```
+	#[cfg(feature = "policy")]
+	pub fn
+	apply_policy
+	(
+		&mut self,
+		policy: &Policy
+	)
+	{
+		let maker_note = self.get_tag(&ExifTag::MakerNote(Vec::new())).cloned();
+
+		let endian = self.endian.clone();
+		self.data = std::mem::take(&mut self.data)
+			.into_iter()
+			.filter_map(|tag| policy.apply_to_tag(tag, &endian))
+			.collect();
+
+		// Hashed or removed maker notes are no longer what has been read
+		if self.get_tag(&ExifTag::MakerNote(Vec::new())).cloned() != maker_note
+		{
+			self.maker_note_offset = None;
+		}
+	}
+
+	/// Sets `DateTimeOriginal` based on the name of the file at the given
+	/// path, using the first of the given patterns that matches (see the
+	/// `date_inference` module for their syntax and default patterns).
+	/// Nothing happens if `DateTimeOriginal` is already set. Returns whether
+	/// the tag has been set.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::date_inference::DEFAULT_FILENAME_DATE_PATTERNS;
+	/// use little_exif::metadata::Metadata;
+	///
+	/// let path = std::path::Path::new("IMG-20240131-WA0003.jpg");
+	/// let mut metadata = Metadata::new_from_path(path).unwrap();
+	/// if metadata.infer_date_from_filename(path, &DEFAULT_FILENAME_DATE_PATTERNS)
+	/// {
+	///     metadata.write_to_file(path).unwrap();
+	/// }
+	/// ```
+	pub fn
+	infer_date_from_filename
+	(
+		&mut self,
+		path:     &Path,
+		patterns: &[&str]
+	)
+	-> bool
+	{
+		if self.get_tag(&ExifTag::DateTimeOriginal(String::new())).is_some()
+		{
+			return false;
+		}
+
+		let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+		if let Some(date) = date_inference::parse_date_from_name(&file_name, patterns)
+		{
+			self.set_tag(ExifTag::DateTimeOriginal(date));
+			return true;
+		}
+		return false;
+	}
+
+	/// Sets `DateTimeOriginal` to the modification time of the file at the
+	/// given path, as last resort if neither the EXIF data nor the file name
+	/// tell when the image was taken. The time is given in UTC, so
+	/// `OffsetTimeOriginal` gets set to `+00:00` as well. Nothing happens if
+	/// `DateTimeOriginal` is already set. Returns whether the tag has been set.
+	pub fn
+	infer_date_from_modification_time
+	(
+		&mut self,
+		path: &Path
+	)
+	-> Result<bool, std::io::Error>
+	{
+		if self.get_tag(&ExifTag::DateTimeOriginal(String::new())).is_some()
+		{
+			return Ok(false);
+		}
+
+		let modified = std::fs::metadata(path)?.modified()?;
+		if let Some(date) = date_inference::format_system_time_utc(modified)
+		{
+			self.set_tag(ExifTag::DateTimeOriginal(date));
+			self.set_tag(ExifTag::OffsetTimeOriginal("+00:00".to_string()));
+			return Ok(true);
+		}
+		return Ok(false);
+	}
+
+	/// Computes a fingerprint of the shot the metadata describes, for
+	/// recognizing it again - e.g. to skip files that have already been
+	/// imported - even if the image has been re-encoded or its other
+	/// metadata has been edited. The fingerprint is a stable hash of:
+	/// - The capture time including fractional seconds, normalized to UTC
+	///   if the time zone offset is known
+	/// - The camera's make, model and serial number (ignoring case)
+	/// - The image dimensions, regardless of the orientation
+	/// - The shutter count, if available (see `shutter_count`), which tells
+	///   apart the shots of a burst taken within the same second
+	///
+	/// Returns `None` if the capture time is unknown, as the other values
+	/// alone don't identify a shot.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::metadata::Metadata;
+	///
+	/// let metadata = Metadata::new_from_path(std::path::Path::new("image.jpg")).unwrap();
+	/// if let Some(fingerprint) = metadata.fingerprint()
+	/// {
+	///     println!("{:016x}", fingerprint);
+	/// }
+	/// ```
+	pub fn
+	fingerprint
+	(
+		&self
+	)
+	-> Option<u64>
+	{
+		fingerprint::compute_fingerprint(self)
+	}
+
+	/// Decodes the vendor specific maker notes, using `Make` for determining
+	/// the vendor (see the `maker_note` module for the supported ones).
+	/// Returns `None` if there are no maker notes or they can't be decoded.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::metadata::Metadata;
+	///
+	/// let metadata = Metadata::new_from_path(std::path::Path::new("image.jpg")).unwrap();
+	/// if let Some(maker_note) = metadata.maker_note()
+	/// {
+	///     for entry in maker_note.entries()
+	///     {
+	///         println!("{:04x}: {} bytes", entry.tag, entry.raw_data.len());
+	///     }
+	/// }
+	/// ```
+	pub fn
+	maker_note
+	(
+		&self
+	)
+	-> Option<MakerNote>
+	{
+		let make = maker_note::get_make(&self.data)?;
+		let raw_data = match self.get_tag(&ExifTag::MakerNote(Vec::new()))?
+		{
+			ExifTag::MakerNote(raw_data) => raw_data,
+			_                            => return None,
+		};
+		MakerNote::decode(make, raw_data, self.maker_note_offset, &self.endian)
+	}
+
+	/// Gets the number of shutter actuations of the camera at the time the
+	/// image was taken, as stored in the maker notes of Nikon, Pentax and
+	/// Sony bodies. Returns `None` if it is not available.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::metadata::Metadata;
+	///
+	/// let metadata = Metadata::new_from_path(std::path::Path::new("image.jpg")).unwrap();
+	/// if let Some(shutter_count) = metadata.shutter_count()
+	/// {
+	///     println!("{} actuations", shutter_count);
+	/// }
+	/// ```
+	pub fn
+	shutter_count
+	(
+		&self
+	)
+	-> Option<u32>
+	{
+		self.maker_note()?.shutter_count()
+	}
+
+	/// Gets the temperature inside the camera at the time the image was
+	/// taken in degrees Celsius, as stored in the maker notes of Canon and
+	/// Pentax bodies (see `MakerNote::sensor_temperature`). Returns `None`
+	/// if it is not available.
+	pub fn
+	sensor_temperature
+	(
+		&self
+	)
+	-> Option<f64>
+	{
+		self.maker_note()?.sensor_temperature()
+	}
+
+	/// Gets the FITS keywords describing the acquisition of the image that
+	/// are stored in `UserComment`, see the `astro` module. Returns `None` if
+	/// the comment doesn't contain any.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::astro::SENSOR_TEMPERATURE;
+	/// use little_exif::metadata::Metadata;
+	///
+	/// let metadata = Metadata::new_from_path(std::path::Path::new("m31_stacked.jpg")).unwrap();
+	/// if let Some(temperature) = metadata.acquisition_keywords().and_then(|keywords| keywords.get(SENSOR_TEMPERATURE).cloned())
+	/// {
+	///     println!("Sensor temperature: {:?}", temperature);
+	/// }
+	/// ```
+	pub fn
+	acquisition_keywords
+	(
+		&self
+	)
+	-> Option<AcquisitionKeywords>
+	{
+		match self.get_tag(&ExifTag::UserComment(Vec::new()))?
+		{
+			ExifTag::UserComment(raw_data) => AcquisitionKeywords::from_cards(
+				std::str::from_utf8(raw_data.strip_prefix(&ASCII_CHARACTER_CODE)?).ok()?
+			),
+			_                              => None,
+		}
+	}
+
+	/// Stores the FITS keywords describing the acquisition of the image in
+	/// `UserComment`, replacing the previous comment (see the `astro`
+	/// module).
+	/// - If a keyword or value is invalid, an error of kind `InvalidInput`
+	///   gets returned and the tag is not changed.
+	pub fn
+	set_acquisition_keywords
+	(
+		&mut self,
+		keywords: &AcquisitionKeywords
+	)
+	-> Result<(), std::io::Error>
+	{
+		let cards = keywords.to_cards()?;
+		self.set_tag(ExifTag::UserComment(ASCII_CHARACTER_CODE.iter().chain(cards.as_bytes()).copied().collect()));
+		return Ok(());
+	}
+
+	/// Gets the opto-electronic conversion function stored in the `OECF`
+	/// tag, see `scan::Oecf`. Returns `None` if the tag is not set or can't
+	/// be decoded.
+	pub fn
+	oecf
+	(
+		&self
+	)
+	-> Option<Oecf>
+	{
+		match self.get_tag(&ExifTag::OECF(Vec::new()))?
+		{
+			ExifTag::OECF(raw_data) => Oecf::decode(raw_data, &self.endian).ok(),
+			_                       => None,
+		}
+	}
+
+	/// Stores the opto-electronic conversion function in the `OECF` tag,
+	/// using the byte order of the metadata
+	/// - If the table can't be encoded, an error of kind `InvalidInput` gets
+	///   returned and the tag is not changed.
+	pub fn
+	set_oecf
+	(
+		&mut self,
+		oecf: &Oecf
+	)
+	-> Result<(), std::io::Error>
+	{
+		let raw_data = oecf.encode(&self.endian)?;
+		self.set_tag(ExifTag::OECF(raw_data));
+		return Ok(());
+	}
+
+	/// Gets the coordinates where the image was taken in degrees, negative
+	/// for south and west, see the `gps` module. Returns `None` if they are
+	/// not available.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::metadata::Metadata;
+	///
+	/// let metadata = Metadata::new_from_path(std::path::Path::new("image.jpg")).unwrap();
+	/// if let Some((latitude, longitude)) = metadata.gps_position()
+	/// {
+	///     println!("Taken at {}, {}", latitude, longitude);
+	/// }
+	/// ```
+	pub fn
+	gps_position
+	(
+		&self
+	)
+	-> Option<(f64, f64)>
+	{
+		return Some((
+			self.get_coordinate(&ExifTag::GPSLatitude(Vec::new()),  &ExifTag::GPSLatitudeRef(String::new()),  "S")?,
+			self.get_coordinate(&ExifTag::GPSLongitude(Vec::new()), &ExifTag::GPSLongitudeRef(String::new()), "W")?,
+		));
+	}
+
+	/// Sets the coordinates where the image was taken in degrees, negative
+	/// for south and west, see the `gps` module.
+	/// - If the coordinates are out of range, an error of kind `InvalidInput`
+	///   gets returned and nothing is changed.
+	pub fn
+	set_gps_position
+	(
+		&mut self,
+		latitude:  f64,
+		longitude: f64
+	)
+	-> Result<(), std::io::Error>
+	{
+		Self::check_coordinates(latitude, longitude)?;
+
+		self.set_tag(ExifTag::GPSLatitudeRef( String::from(if latitude  < 0.0 { "S" } else { "N" })));
+		self.set_tag(ExifTag::GPSLatitude(    gps::degrees_to_dms(latitude)));
+		self.set_tag(ExifTag::GPSLongitudeRef(String::from(if longitude < 0.0 { "W" } else { "E" })));
+		self.set_tag(ExifTag::GPSLongitude(   gps::degrees_to_dms(longitude)));
+		return Ok(());
+	}
+
+	/// Gets the altitude where the image was taken in meters, negative for
+	/// below the reference level, see the `gps` module. If the reference is
+	/// missing, the sea level is assumed. Returns `None` if the altitude is
+	/// not available or the reference is invalid.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::gps::GpsAltitudeRef;
+	/// use little_exif::metadata::Metadata;
+	///
+	/// let metadata = Metadata::new_from_path(std::path::Path::new("image.jpg")).unwrap();
+	/// if let Some((altitude, GpsAltitudeRef::SeaLevel)) = metadata.gps_altitude()
+	/// {
+	///     println!("Taken {} m above sea level", altitude);
+	/// }
+	/// ```
+	pub fn
+	gps_altitude
+	(
+		&self
+	)
+	-> Option<(f64, GpsAltitudeRef)>
+	{
+		let altitude = match self.get_tag(&ExifTag::GPSAltitude(Vec::new()))?
+		{
+			ExifTag::GPSAltitude(rational) => gps::rational_to_f64(rational)?,
+			_                              => return None,
+		};
+		let (reference, below) = match self.get_tag(&ExifTag::GPSAltitudeRef(Vec::new()))
+		{
+			Some(ExifTag::GPSAltitudeRef(value)) => GpsAltitudeRef::from_ref_value(*value.first()?)?,
+			_                                    => (GpsAltitudeRef::SeaLevel, false),
+		};
+		return Some((if below { -altitude } else { altitude }, reference));
+	}
+
+	/// Sets the altitude where the image was taken in meters, negative for
+	/// below the reference level, see the `gps` module. Note that readers
+	/// supporting only EXIF versions before 3.0 interpret ellipsoidal
+	/// heights as invalid.
+	/// - If the altitude is not a finite number or too large, an error of
+	///   kind `InvalidInput` gets returned and nothing is changed.
+	pub fn
+	set_gps_altitude
+	(
+		&mut self,
+		meters:    f64,
+		reference: GpsAltitudeRef
+	)
+	-> Result<(), std::io::Error>
+	{
+		if !meters.is_finite() || meters.abs() * 1000.0 > u32::MAX as f64
+		{
+			return io_error!(InvalidInput, format!("Invalid GPS altitude {}!", meters));
+		}
+
+		self.set_tag(ExifTag::GPSAltitudeRef(vec![reference.as_ref_value(meters < 0.0)]));
+		self.set_tag(ExifTag::GPSAltitude(gps::f64_to_rational(meters.abs(), 1000)));
+		return Ok(());
+	}
+
+	/// Converts the altitude to the given reference level, using the geoid
+	/// model to look up the height of the geoid (i.e. the sea level) above
+	/// the ellipsoid at the position where the image was taken. Does nothing
+	/// if the altitude already uses that reference.
+	/// - If the altitude or the position is not available, an error of kind
+	///   `NotFound` gets returned.
+	/// - If the model doesn't cover the position, an error of kind
+	///   `InvalidData` gets returned.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::geoid::GeoidGrid;
+	/// use little_exif::gps::GpsAltitudeRef;
+	/// use little_exif::metadata::Metadata;
+	///
+	/// // E.g. egm96-5.pgm of GeographicLib
+	/// let geoid = GeoidGrid::from_path(std::path::Path::new("egm96-5.pgm")).unwrap();
+	///
+	/// // The drone wrote ellipsoidal heights but labeled them as sea level
+	/// let path = std::path::Path::new("drone.jpg");
+	/// let mut metadata = Metadata::new_from_path(path).unwrap();
+	/// let (altitude, _) = metadata.gps_altitude().unwrap();
+	/// metadata.set_gps_altitude(altitude, GpsAltitudeRef::Ellipsoid).unwrap();
+	/// metadata.convert_gps_altitude(GpsAltitudeRef::SeaLevel, &geoid).unwrap();
+	/// metadata.write_to_file(path).unwrap();
+	/// ```
+	#[cfg(feature = "geoid")]
+	pub fn
+	convert_gps_altitude
+	(
+		&mut self,
+		target: GpsAltitudeRef,
+		model:  &impl crate::geoid::GeoidModel
+	)
+	-> Result<(), std::io::Error>
+	{
+		let (altitude, reference) = match self.gps_altitude()
+		{
+			Some(altitude) => altitude,
+			None           => return io_error!(NotFound, "No GPS altitude available!"),
+		};
+		if reference == target
+		{
+			return Ok(());
+		}
+
+		let (latitude, longitude) = match self.gps_position()
+		{
+			Some(position) => position,
+			None           => return io_error!(NotFound, "No GPS position available for converting the altitude!"),
+		};
+		let undulation = match model.undulation(latitude, longitude)
+		{
+			Some(undulation) => undulation,
+			None             => return io_error!(InvalidData, format!("Geoid model doesn't cover {}, {}!", latitude, longitude)),
+		};
+
+		// The ellipsoidal height is the orthometric one plus the undulation
+		return match target
+		{
+			GpsAltitudeRef::SeaLevel  => self.set_gps_altitude(altitude - undulation, target),
+			GpsAltitudeRef::Ellipsoid => self.set_gps_altitude(altitude + undulation, target),
+		};
+	}
+
+	/// Gets the coordinates of the destination (e.g. the subject that has
+	/// been photographed) in degrees, negative for south and west, see the
+	/// `gps` module. Returns `None` if they are not available.
+	pub fn
+	gps_destination
+	(
+		&self
+	)
+	-> Option<(f64, f64)>
+	{
+		return Some((
+			self.get_coordinate(&ExifTag::GPSDestLatitude(Vec::new()),  &ExifTag::GPSDestLatitudeRef(String::new()),  "S")?,
+			self.get_coordinate(&ExifTag::GPSDestLongitude(Vec::new()), &ExifTag::GPSDestLongitudeRef(String::new()), "W")?,
+		));
+	}
+
+	/// Sets the coordinates of the destination in degrees, negative for
+	/// south and west, see the `gps` module.
+	/// - If the coordinates are out of range, an error of kind `InvalidInput`
+	///   gets returned and nothing is changed.
+	pub fn
+	set_gps_destination
+	(
+		&mut self,
+		latitude:  f64,
+		longitude: f64
+	)
+	-> Result<(), std::io::Error>
+	{
+		Self::check_coordinates(latitude, longitude)?;
+
+		self.set_tag(ExifTag::GPSDestLatitudeRef( String::from(if latitude  < 0.0 { "S" } else { "N" })));
+		self.set_tag(ExifTag::GPSDestLatitude(    gps::degrees_to_dms(latitude)));
+		self.set_tag(ExifTag::GPSDestLongitudeRef(String::from(if longitude < 0.0 { "W" } else { "E" })));
+		self.set_tag(ExifTag::GPSDestLongitude(   gps::degrees_to_dms(longitude)));
+		return Ok(());
+	}
+
+	/// Gets the direction the camera was pointed at when taking the image, in
+	/// degrees. If the reference is missing, true north is assumed. Returns
+	/// `None` if the direction is not available.
+	pub fn
+	gps_img_direction
+	(
+		&self
+	)
+	-> Option<(f64, GpsDirectionRef)>
+	{
+		let direction = match self.get_tag(&ExifTag::GPSImgDirection(Vec::new()))?
+		{
+			ExifTag::GPSImgDirection(rational) => gps::rational_to_f64(rational)?,
+			_                                  => return None,
+		};
+		return Some((direction, self.get_direction_ref(&ExifTag::GPSImgDirectionRef(String::new()))));
+	}
+
+	/// Sets the direction the camera was pointed at when taking the image, in
+	/// degrees (normalized to the range from 0 up to 360).
+	/// - If the direction is not a finite number, an error of kind
+	///   `InvalidInput` gets returned and nothing is changed.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::gps::GpsDirectionRef;
+	/// use little_exif::metadata::Metadata;
+	///
+	/// let mut metadata = Metadata::new();
+	/// metadata.set_gps_img_direction(271.5, GpsDirectionRef::MagneticNorth).unwrap();
+	/// ```
+	pub fn
+	set_gps_img_direction
+	(
+		&mut self,
+		degrees:   f64,
+		reference: GpsDirectionRef
+	)
+	-> Result<(), std::io::Error>
+	{
+		let direction = Self::check_direction(degrees)?;
+		self.set_tag(ExifTag::GPSImgDirectionRef(reference.as_ref_value().to_string()));
+		self.set_tag(ExifTag::GPSImgDirection(direction));
+		return Ok(());
+	}
+
+	/// Gets the direction the camera was moving in when taking the image, in
+	/// degrees. If the reference is missing, true north is assumed. Returns
+	/// `None` if the direction is not available.
+	pub fn
+	gps_track
+	(
+		&self
+	)
+	-> Option<(f64, GpsDirectionRef)>
+	{
+		let direction = match self.get_tag(&ExifTag::GPSTrack(Vec::new()))?
+		{
+			ExifTag::GPSTrack(rational) => gps::rational_to_f64(rational)?,
+			_                           => return None,
+		};
+		return Some((direction, self.get_direction_ref(&ExifTag::GPSTrackRef(String::new()))));
+	}
+
+	/// Sets the direction the camera was moving in when taking the image, in
+	/// degrees (normalized to the range from 0 up to 360).
+	/// - If the direction is not a finite number, an error of kind
+	///   `InvalidInput` gets returned and nothing is changed.
+	pub fn
+	set_gps_track
+	(
+		&mut self,
+		degrees:   f64,
+		reference: GpsDirectionRef
+	)
+	-> Result<(), std::io::Error>
+	{
+		let direction = Self::check_direction(degrees)?;
+		self.set_tag(ExifTag::GPSTrackRef(reference.as_ref_value().to_string()));
+		self.set_tag(ExifTag::GPSTrack(direction));
+		return Ok(());
+	}
+
+	/// Gets the dilution of precision of the GPS fix, i.e. how much the
+	/// geometry of the satellites degraded the accuracy of the position.
+	/// Returns `None` if it is not available.
+	pub fn
+	gps_dop
+	(
+		&self
+	)
+	-> Option<f64>
+	{
+		match self.get_tag(&ExifTag::GPSDOP(Vec::new()))?
+		{
+			ExifTag::GPSDOP(rational) => gps::rational_to_f64(rational),
+			_                         => None,
+		}
+	}
+
+	/// Sets the dilution of precision of the GPS fix.
+	/// - If it is negative or not a finite number, an error of kind
+	///   `InvalidInput` gets returned and nothing is changed.
+	pub fn
+	set_gps_dop
+	(
+		&mut self,
+		dop: f64
+	)
+	-> Result<(), std::io::Error>
+	{
+		if !dop.is_finite() || dop < 0.0 || dop * 1000.0 > u32::MAX as f64
+		{
+			return io_error!(InvalidInput, format!("Invalid GPS dilution of precision {}!", dop));
+		}
+		self.set_tag(ExifTag::GPSDOP(gps::f64_to_rational(dop, 1000)));
+		return Ok(());
+	}
+
+	/// Gets the ambient temperature when the image was taken in the given
+	/// unit, see the `environment` module. Returns `None` if it is not
+	/// available.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::environment::TemperatureUnit;
+	/// use little_exif::metadata::Metadata;
+	///
+	/// let metadata = Metadata::new_from_path(std::path::Path::new("dive.jpg")).unwrap();
+	/// if let Some(temperature) = metadata.ambient_temperature(TemperatureUnit::Fahrenheit)
+	/// {
+	///     println!("Water temperature: {:.1} °F", temperature);
+	/// }
+	/// ```
+	pub fn
+	ambient_temperature
+	(
+		&self,
+		unit: TemperatureUnit
+	)
+	-> Option<f64>
+	{
+		match self.get_tag(&ExifTag::AmbientTemperature(Vec::new()))?
+		{
+			ExifTag::AmbientTemperature(rational) => Some(unit.celsius_to_unit(environment::signed_rational_to_f64(rational)?)),
+			_                                     => None,
+		}
+	}
+
+	/// Sets the ambient temperature when the image was taken in the given
+	/// unit.
+	/// - If the temperature is below absolute zero or not a finite number,
+	///   an error of kind `InvalidInput` gets returned and nothing is changed.
+	pub fn
+	set_ambient_temperature
+	(
+		&mut self,
+		temperature: f64,
+		unit:        TemperatureUnit
+	)
+	-> Result<(), std::io::Error>
+	{
+		let celsius = unit.to_celsius(temperature);
+		match environment::f64_to_signed_rational(celsius, 10)
+		{
+			Some(rational) if celsius >= -273.15 => self.set_tag(ExifTag::AmbientTemperature(rational)),
+			_                                    => return io_error!(InvalidInput, format!("Invalid ambient temperature {}!", temperature)),
+		}
+		return Ok(());
+	}
+
+	/// Gets the relative humidity when the image was taken in percent.
+	/// Returns `None` if it is not available.
+	pub fn
+	humidity
+	(
+		&self
+	)
+	-> Option<f64>
+	{
+		match self.get_tag(&ExifTag::Humidity(Vec::new()))?
+		{
+			ExifTag::Humidity(rational) => environment::unsigned_rational_to_f64(rational),
+			_                           => None,
+		}
+	}
+
+	/// Sets the relative humidity when the image was taken in percent.
+	/// - If it is not in the range from 0 to 100, an error of kind
+	///   `InvalidInput` gets returned and nothing is changed.
+	pub fn
+	set_humidity
+	(
+		&mut self,
+		percent: f64
+	)
+	-> Result<(), std::io::Error>
+	{
+		if !(0.0..=100.0).contains(&percent)
+		{
+			return io_error!(InvalidInput, format!("Invalid humidity {}!", percent));
+		}
+		self.set_tag(ExifTag::Humidity(gps::f64_to_rational(percent, 10)));
+		return Ok(());
+	}
+
+	/// Gets the air pressure (or water pressure, for underwater images) when
+	/// the image was taken in the given unit. Returns `None` if it is not
+	/// available.
+	pub fn
+	pressure
+	(
+		&self,
+		unit: PressureUnit
+	)
+	-> Option<f64>
+	{
+		match self.get_tag(&ExifTag::Pressure(Vec::new()))?
+		{
+			ExifTag::Pressure(rational) => Some(environment::unsigned_rational_to_f64(rational)? / unit.hectopascal()),
+			_                           => None,
+		}
+	}
+
+	/// Sets the air (or water) pressure when the image was taken in the
+	/// given unit.
+	/// - If it is negative, too large or not a finite number, an error of
+	///   kind `InvalidInput` gets returned and nothing is changed.
+	pub fn
+	set_pressure
+	(
+		&mut self,
+		pressure: f64,
+		unit:     PressureUnit
+	)
+	-> Result<(), std::io::Error>
+	{
+		let hectopascal = pressure * unit.hectopascal();
+		if !hectopascal.is_finite() || hectopascal < 0.0 || hectopascal * 10.0 >= u32::MAX as f64
+		{
+			return io_error!(InvalidInput, format!("Invalid pressure {}!", pressure));
+		}
+		self.set_tag(ExifTag::Pressure(gps::f64_to_rational(hectopascal, 10)));
+		return Ok(());
+	}
+
+	/// Gets the depth below the water surface when the image was taken in
+	/// the given unit, negative for above the surface (e.g. a split shot).
+	/// Returns `None` if it is not available.
+	pub fn
+	water_depth
+	(
+		&self,
+		unit: LengthUnit
+	)
+	-> Option<f64>
+	{
+		match self.get_tag(&ExifTag::WaterDepth(Vec::new()))?
+		{
+			ExifTag::WaterDepth(rational) => Some(environment::signed_rational_to_f64(rational)? / unit.meters()),
+			_                             => None,
+		}
+	}
+
+	/// Sets the depth below the water surface when the image was taken in
+	/// the given unit, negative for above the surface.
+	/// - If it is too large or not a finite number, an error of kind
+	///   `InvalidInput` gets returned and nothing is changed.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::environment::LengthUnit;
+	/// use little_exif::environment::TemperatureUnit;
+	/// use little_exif::metadata::Metadata;
+	///
+	/// let mut metadata = Metadata::new();
+	/// metadata.set_water_depth(60.0, LengthUnit::Feet).unwrap();
+	/// metadata.set_ambient_temperature(14.5, TemperatureUnit::Celsius).unwrap();
+	/// ```
+	pub fn
+	set_water_depth
+	(
+		&mut self,
+		depth: f64,
+		unit:  LengthUnit
+	)
+	-> Result<(), std::io::Error>
+	{
+		match environment::f64_to_signed_rational(depth * unit.meters(), 1000)
+		{
+			Some(rational) => self.set_tag(ExifTag::WaterDepth(rational)),
+			None           => return io_error!(InvalidInput, format!("Invalid water depth {}!", depth)),
+		}
+		return Ok(());
+	}
+
+	/// Gets the acceleration of the camera when the image was taken in the
+	/// given unit. Returns `None` if it is not available.
+	pub fn
+	acceleration
+	(
+		&self,
+		unit: AccelerationUnit
+	)
+	-> Option<f64>
+	{
+		match self.get_tag(&ExifTag::Acceleration(Vec::new()))?
+		{
+			ExifTag::Acceleration(rational) => Some(environment::unsigned_rational_to_f64(rational)? / unit.milligal()),
+			_                               => None,
+		}
+	}
+
+	/// Sets the acceleration of the camera when the image was taken in the
+	/// given unit.
+	/// - If it is negative, too large or not a finite number, an error of
+	///   kind `InvalidInput` gets returned and nothing is changed.
+	pub fn
+	set_acceleration
+	(
+		&mut self,
+		acceleration: f64,
+		unit:         AccelerationUnit
+	)
+	-> Result<(), std::io::Error>
+	{
+		let milligal = acceleration * unit.milligal();
+		if !milligal.is_finite() || milligal < 0.0 || milligal >= u32::MAX as f64
+		{
+			return io_error!(InvalidInput, format!("Invalid acceleration {}!", acceleration));
+		}
+		self.set_tag(ExifTag::Acceleration(gps::f64_to_rational(milligal, 1)));
+		return Ok(());
+	}
+
+	/// Gets the elevation angle of the camera when the image was taken in
+	/// degrees, negative for pointing below the horizon. Returns `None` if
+	/// it is not available.
+	pub fn
+	camera_elevation_angle
+	(
+		&self
+	)
+	-> Option<f64>
+	{
+		match self.get_tag(&ExifTag::CameraElevationAngle(Vec::new()))?
+		{
+			ExifTag::CameraElevationAngle(rational) => environment::signed_rational_to_f64(rational),
+			_                                       => None,
+		}
+	}
+
+	/// Sets the elevation angle of the camera when the image was taken in
+	/// degrees, negative for pointing below the horizon.
+	/// - If it is not in the range from -180 to 180, an error of kind
+	///   `InvalidInput` gets returned and nothing is changed.
+	pub fn
+	set_camera_elevation_angle
+	(
+		&mut self,
+		degrees: f64
+	)
+	-> Result<(), std::io::Error>
+	{
+		match environment::f64_to_signed_rational(degrees, 100)
+		{
+			Some(rational) if (-180.0..=180.0).contains(&degrees) => self.set_tag(ExifTag::CameraElevationAngle(rational)),
+			_                                                     => return io_error!(InvalidInput, format!("Invalid camera elevation angle {}!", degrees)),
+		}
+		return Ok(());
+	}
+
+	/// Gets a coordinate stored as degrees, minutes and seconds in the given
+	/// tag, negated if the reference tag has the given value. Without a
+	/// reference, north and east are assumed.
+	fn
+	get_coordinate
+	(
+		&self,
+		value_tag:      &ExifTag,
+		reference_tag:  &ExifTag,
+		negative_value: &str
+	)
+	-> Option<f64>
+	{
+		let coordinate = match self.get_tag(value_tag)?
+		{
+			ExifTag::GPSLatitude(dms)      |
+			ExifTag::GPSLongitude(dms)     |
+			ExifTag::GPSDestLatitude(dms)  |
+			ExifTag::GPSDestLongitude(dms) => gps::dms_to_degrees(dms)?,
+			_                              => return None,
+		};
+		let negative = self.get_string_value(reference_tag).is_some_and(|value| value == negative_value);
+		return Some(if negative { -coordinate } else { coordinate });
+	}
+
+	/// Checks that the coordinates are in range
+	fn
+	check_coordinates
+	(
+		latitude:  f64,
+		longitude: f64
+	)
+	-> Result<(), std::io::Error>
+	{
+		if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude)
+		{
+			return io_error!(InvalidInput, format!("Invalid GPS coordinates {}, {}!", latitude, longitude));
+		}
+		return Ok(());
+	}
+
+	/// Gets the north the direction stored in the given reference tag is
+	/// relative to, defaulting to true north
+	fn
+	get_direction_ref
+	(
+		&self,
+		reference_tag: &ExifTag
+	)
+	-> GpsDirectionRef
+	{
+		self.get_string_value(reference_tag)
+			.and_then(|value| GpsDirectionRef::from_ref_value(&value))
+			.unwrap_or(GpsDirectionRef::TrueNorth)
+	}
+
+	/// Checks and converts a direction for storing it as rational
+	fn
+	check_direction
+	(
+		degrees: f64
+	)
+	-> Result<Vec<u32>, std::io::Error>
+	{
+		if !degrees.is_finite()
+		{
+			return io_error!(InvalidInput, format!("Invalid GPS direction {}!", degrees));
+		}
+		return Ok(gps::f64_to_rational(gps::normalize_direction(degrees), 100));
+	}
+
+	/// Converts the metadata into a file specific vector of bytes
+	/// Only to be used in combination with some other library/code that is
+	/// able to handle the specific file type.
+	/// Simply writing this to a file often is not enough, e.g. with WebP you
+	/// have to determine where to write this, update the file size information
+	/// and so on - check file type specific implementations or documentation
+	/// for further details
+	pub fn
+	as_u8_vec
+	(
+		&self,
+		for_file_type: FileExtension
+	)
+	-> Vec<u8>
+	{
+		let general_encoded_metadata = self.encode_metadata_general();
+
+		match for_file_type
+		{
+			FileExtension::PNG {as_zTXt_chunk} 
+				=>  png::as_u8_vec(&general_encoded_metadata, as_zTXt_chunk),
+			FileExtension::JPEG 
+				=>  jpg::as_u8_vec(&general_encoded_metadata),
+			FileExtension::WEBP 
+				=> webp::as_u8_vec(&general_encoded_metadata),
+			FileExtension::TIFF
+				=> tiff::as_u8_vec(&general_encoded_metadata),
+			FileExtension::HEIF | FileExtension::AVIF
+				=> heif::as_u8_vec(&general_encoded_metadata),
+			FileExtension::GIF
+				=> gif::as_u8_vec(&general_encoded_metadata),
+			FileExtension::CR2
+				=> tiff::as_u8_vec(&general_encoded_metadata),
+			FileExtension::CR3
+				=> cr3::as_u8_vec(&general_encoded_metadata),
+			FileExtension::ORF
+				=> tiff::as_u8_vec(&general_encoded_metadata),
+			FileExtension::RW2
+				=> tiff::as_u8_vec(&general_encoded_metadata),
+			FileExtension::RAF
+				=> raf::as_u8_vec(&general_encoded_metadata),
+			FileExtension::AVI
+				=> avi::as_u8_vec(&general_encoded_metadata),
+		}
+	}
+
+	/// Encodes the metadata into the EXIF data block that is independent of
+	/// the file type, i.e. the TIFF structure starting with the byte order
+	/// marker (`II` or `MM`). This is what PNG eXIf chunks and `.exif`
+	/// sidecar files contain, while JPEG APP1 segments and WebP EXIF chunks
+	/// (may) prefix it with `Exif\0\0`. The result can e.g. be cached, sent
+	/// via network or embedded using a custom writer, and turned back into
+	/// `Metadata` using `decode`.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::metadata::Metadata;
+	/// use little_exif::exif_tag::ExifTag;
+	///
+	/// let mut metadata = Metadata::new();
+	/// metadata.set_tag(ExifTag::Artist("Jane Doe".to_string()));
+	/// let encoded = metadata.encode();
+	/// assert_eq!(Metadata::decode(&encoded).unwrap().data(), metadata.data());
+	/// ```
+	pub fn
+	encode
+	(
+		&self
+	)
+	-> Vec<u8>
+	{
+		self.encode_metadata_general()
+	}
+
+	/// Encodes the metadata like `encode`, applying the options that affect
+	/// the tags (software stamping, write hooks and the targeted EXIF
+	/// version). Options for specific file types are ignored, values that
+	/// would be moved to XMP (see `Truncation::MoveToXmp`) are only cut.
+	/// - If a write hook aborts, an error gets returned.
+	pub fn
+	encode_with_options
+	(
+		&self,
+		options: &WriteOptions
+	)
+	-> Result<Vec<u8>, std::io::Error>
+	{
+		self.encode_metadata_with_options(options)
+	}
+
+	/// Decodes an EXIF data block as returned by `encode`. The block may be
+	/// prefixed with `Exif\0\0`, as in JPEG APP1 segments, or with the 4 byte
+	/// offset to the TIFF header used by HEIF Exif items.
+	/// - If the data can't be decoded, an error gets returned.
+	pub fn
+	decode
+	(
+		encoded_data: &[u8]
+	)
+	-> Result<Metadata, std::io::Error>
+	{
+		let span = span!("decode", size = encoded_data.len(); tags);
+
+		let pre_decode_general = exif_block::normalize(encoded_data)?;
+
+		let (endian, data) = Self::decode_metadata_general(&pre_decode_general)?;
+		record!(span, tags, data.len());
+		let maker_note_offset = maker_note::find_maker_note_offset(&pre_decode_general[EXIF_HEADER.len()..], &endian);
+		return Ok(Metadata { endian, data, maker_note_offset, warnings: Vec::new() });
+	}
+
+	/// Writes the metadata to the specified file.
+	/// This could return an error for multiple reasons:
+	/// - The file does not exist at the given path
+	/// - Interpreting the given path fails
+	/// - The file type is not supported
+	/// - The file is read-only, in which case the error contains a
+	///   `ReadOnlyTarget` (see `write_to_file_or_sidecar` for a fallback)
+	/// - The file is in use by another process (e.g. a viewer on Windows)
+	///   for longer than about one and a half seconds, in which case the
+	///   error contains a `SharingViolation`
+	/// - There is not enough free space, in which case the error contains an
+	///   `InsufficientSpace` if `WriteOptions::safe_write` is set
+	pub fn
+	write_to_file
+	(
+		&self,
+		path: &Path
+	)
+	-> Result<(), std::io::Error>
+	{
+		self.write_to_file_with_options(path, &WriteOptions::new())
+	}
+
+	/// Writes the metadata to the specified file, using the given options
+	/// e.g. for choosing how the data gets stored in PNG files.
+	/// See `write_to_file` for further details.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::metadata::Metadata;
+	/// use little_exif::write_options::PngExifChunk;
+	/// use little_exif::write_options::WriteOptions;
+	///
+	/// let metadata = Metadata::new_from_path(std::path::Path::new("image.png")).unwrap();
+	/// metadata.write_to_file_with_options(
+	///     std::path::Path::new("image.png"),
+	///     &WriteOptions::new().png_exif_chunk(PngExifChunk::eXIf)
+	/// );
+	/// ```
+	pub fn
+	write_to_file_with_options
+	(
+		&self,
+		path:    &Path,
+		options: &WriteOptions
+	)
+	-> Result<(), std::io::Error>
+	{
+		if !path.exists()
+		{
+			return io_error!(Other, "Can't write Metadata - File does not exist!");
+		}
+
+		let raw_file_type_str = path.extension();
+		if raw_file_type_str.is_none()
+		{
+			return io_error!(Other, "Can't get extension from given path!");
+		}
+		let file_type_str = raw_file_type_str.unwrap().to_str();
+		if file_type_str.is_none()
+		{
+			return io_error!(Other, "Can't convert file type to string!");
+		}
+
+		let raw_file_type = FileExtension::from_str(file_type_str.unwrap().to_lowercase().as_str());
+		if raw_file_type.is_err()
+		{
+			return Self::unsupported_file_type(path, "Can't read Metadata - Unsupported file type!");
+		}
+
+		// Detect read-only files before reading them in
+		check_writable(path)?;
+
+		let created_backup = options.backup && create_backup(path)?;
+		let result = self.write_file_with_options(path, raw_file_type.unwrap(), options);
+
+		// A backup of a file that has not been written to is of no use
+		if result.is_err() && created_backup
+		{
+			let _ = std::fs::remove_file(backup_path(path));
+		}
+
+		#[cfg(feature = "xattr")]
+		if result.is_ok() && options.mirror_xattrs
+		{
+			crate::extended_attributes::mirror_to_xattrs(path)?;
+		}
+		return result;
+	}
+
+	fn
+	write_file_with_options
+	(
+		&self,
+		path:      &Path,
+		file_type: FileExtension,
+		options:   &WriteOptions
+	)
+	-> Result<(), std::io::Error>
+	{
+		let _span = span!("write", format = file_type.name(), safe_write = options.safe_write);
+
+		if options.safe_write
+		{
+			let mut file_buffer = std::fs::read(path)?;
+			self.write_to_vec_with_options(&mut file_buffer, file_type, options)?;
+			return safe_write_vec_to_file(path, &file_buffer, &options.temp_file_location);
+		}
+		return self.write_file_through(&OsFileSystem, path, file_type, options);
+	}
+
+	/// Writes the metadata to the file at the given path of the given file
+	/// system, without any of the options that only apply to real files
+	fn
+	write_file_through
+	(
+		&self,
+		file_system: &dyn FileSystem,
+		path:        &Path,
+		file_type:   FileExtension,
+		options:     &WriteOptions
+	)
+	-> Result<(), std::io::Error>
+	{
+		// Moving values to XMP requires the XMP packet of the file
+		if options.moves_overflow_to_xmp()
+		{
+			let mut file_buffer = file_system.read(path)?;
+			self.write_to_vec_with_options(&mut file_buffer, file_type, options)?;
+			return file_system.write(path, &file_buffer);
+		}
+
+		let general_encoded_metadata = self.encode_metadata_with_options(options)?;
+		match file_type
+		{
+			FileExtension::JPEG 
+				=>  jpg::file_write_metadata(file_system, path, &general_encoded_metadata),
+			FileExtension::PNG {as_zTXt_chunk: _}
+				=>  png::file_write_metadata(file_system, path, &general_encoded_metadata, options),
+			FileExtension::WEBP 
+				=> webp::file_write_metadata(file_system, path, &general_encoded_metadata),
+			FileExtension::TIFF
+				=> tiff::file_write_metadata(file_system, path, &general_encoded_metadata),
+			FileExtension::HEIF | FileExtension::AVIF
+				=> heif::file_write_metadata(file_system, path, &general_encoded_metadata),
+			FileExtension::GIF
+				=> gif::file_write_metadata(file_system, path, &general_encoded_metadata),
+			FileExtension::CR2 | FileExtension::CR3 | FileExtension::ORF | FileExtension::RW2 | FileExtension::RAF
+				=> raw_file::unsupported_write(&file_type),
+			FileExtension::AVI
+				=> avi::file_write_metadata(file_system, path, &general_encoded_metadata),
+		}
+	}
+
+	/// Writes the metadata to the file at the specified path of the given
+	/// file system, e.g. a `vfs::MemoryFileSystem`. In contrast to
+	/// `write_to_file` neither a backup nor a safe write is possible, as these
+	/// require real files.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::metadata::Metadata;
+	/// use little_exif::vfs::MemoryFileSystem;
+	///
+	/// let path = std::path::Path::new("image.jpg");
+	/// let file_system = MemoryFileSystem::new();
+	/// file_system.insert(path, std::fs::read("image.jpg").unwrap());
+	/// Metadata::new().write_to_file_in(&file_system, path).unwrap();
+	/// ```
+	pub fn
+	write_to_file_in
+	(
+		&self,
+		file_system: &dyn FileSystem,
+		path:        &Path
+	)
+	-> Result<(), std::io::Error>
+	{
+		if !file_system.exists(path)
+		{
+			return io_error!(Other, "Can't write Metadata - File does not exist!");
+		}
+
+		let Some(file_type) = FileExtension::from_path(path) else
+		{
+			return Self::unsupported_file_type(path, "Can't write Metadata - Unsupported file type!");
+		};
+
+		let _span = span!("write", format = file_type.name(), safe_write = false);
+		return self.write_file_through(file_system, path, file_type, &WriteOptions::new());
+	}
+
+	/// Restores the file at the given path from the backup created when
+	/// writing with `WriteOptions::backup` (e.g. `image.jpg_original`). The
+	/// backup replaces the file and is thereby removed.
+	/// - If there is no backup, an error gets returned and the file is left
+	///   unchanged.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::metadata::Metadata;
+	/// use little_exif::write_options::WriteOptions;
+	///
+	/// let path = std::path::Path::new("image.jpg");
+	/// Metadata::new().write_to_file_with_options(path, &WriteOptions::new().backup(true)).unwrap();
+	/// Metadata::restore_backup(path).unwrap();
+	/// ```
+	pub fn
+	restore_backup
+	(
+		path: &Path
+	)
+	-> Result<(), std::io::Error>
+	{
+		let backup_path = backup_path(path);
+		if !backup_path.is_file()
+		{
+			return io_error!(NotFound, format!("Can't restore {} - There is no backup!", path.display()));
+		}
+		return std::fs::rename(backup_path, path);
+	}
+
+	/// Writes the metadata to the specified file (see `write_to_file`). If
+	/// the file is read-only (e.g. stored on a write-protected memory card),
+	/// in use by another process or of a type that can't hold embedded EXIF
+	/// data (e.g. BMP, see `errors::NoEmbeddedMetadata`), the metadata gets
+	/// written to a sidecar file in the given directory instead, named after
+	/// the file with the additional extension `.exif` (e.g. `image.jpg.exif`).
+	/// Such a sidecar contains the raw TIFF encoded EXIF data, like the
+	/// `.exif` files written by ExifTool. See
+	/// `write_to_file_or_sidecar_with_format` for XMP sidecars.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::metadata::Metadata;
+	/// use little_exif::write_options::WriteDestination;
+	///
+	/// let metadata = Metadata::new();
+	/// let destination = metadata.write_to_file_or_sidecar(
+	///     std::path::Path::new("/media/card/image.jpg"),
+	///     std::path::Path::new("sidecars")
+	/// ).unwrap();
+	/// if let WriteDestination::Sidecar(sidecar_path) = destination
+	/// {
+	///     println!("Wrote sidecar {}", sidecar_path.display());
+	/// }
+	/// ```
+	pub fn
+	write_to_file_or_sidecar
+	(
+		&self,
+		path:              &Path,
+		sidecar_directory: &Path
+	)
+	-> Result<WriteDestination, std::io::Error>
+	{
+		self.write_to_file_or_sidecar_with_format(path, sidecar_directory, SidecarFormat::Exif)
+	}
+
+	/// Writes the metadata to the specified file or to a sidecar file in the
+	/// given format, see `write_to_file_or_sidecar` and `SidecarFormat`. The
+	/// sidecar is named after the file with the additional extension of the
+	/// format, e.g. `scan.bmp.xmp`.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::metadata::Metadata;
+	/// use little_exif::exif_tag::ExifTag;
+	/// use little_exif::write_options::SidecarFormat;
+	///
+	/// let mut metadata = Metadata::new();
+	/// metadata.set_tag(ExifTag::Artist("Jane Doe".to_string()));
+	/// for path in std::fs::read_dir("batch").unwrap()
+	/// {
+	///     let path = path.unwrap().path();
+	///     metadata.write_to_file_or_sidecar_with_format(&path, std::path::Path::new("batch"), SidecarFormat::Xmp).unwrap();
+	/// }
+	/// ```
+	pub fn
+	write_to_file_or_sidecar_with_format
+	(
+		&self,
+		path:              &Path,
+		sidecar_directory: &Path,
+		format:            SidecarFormat
+	)
+	-> Result<WriteDestination, std::io::Error>
+	{
+		let error = match self.write_to_file(path)
+		{
+			Ok(())     => return Ok(WriteDestination::File),
+			Err(error) => error,
+		};
+
+		let fallback = ReadOnlyTarget::from_io_error(&error).is_some()
+			|| SharingViolation::from_io_error(&error).is_some()
+			|| NoEmbeddedMetadata::from_io_error(&error).is_some();
+		if !fallback
+		{
+			return Err(error);
+		}
+
+		let mut sidecar_name = path.file_name().unwrap_or_default().to_os_string();
+		sidecar_name.push(format.extension());
+		let sidecar_path = sidecar_directory.join(sidecar_name);
+
+		let sidecar = match format
+		{
+			SidecarFormat::Exif => self.encode_metadata_general(),
+			SidecarFormat::Xmp  => {
+				let tags = self.data.iter()
+					.filter(|tag| tag.is_writable())
+					.cloned()
+					.collect::<Vec<ExifTag>>();
+				let packet = truncation::set_xmp_values(Some(xmp::EMPTY_PACKET.to_string()), &tags, &self.endian)?;
+				packet.unwrap_or_default().into_bytes()
+			},
+		};
+
+		write_vec_to_file(&sidecar_path, &sidecar)?;
+		return Ok(WriteDestination::Sidecar(sidecar_path));
+	}
+
+	/// Writes the metadata to the given image data in memory, replacing any
+	/// EXIF data that is already stored in there. In contrast to `as_u8_vec`
+	/// the result is a complete image, so this takes care of everything that
+	/// is specific to the file type (e.g. updating the WebP file size).
+	/// - If the data is not a valid image of the given type, an error gets 
+	///   returned and the data is left unchanged.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::metadata::Metadata;
+	/// use little_exif::exif_tag::ExifTag;
+	/// use little_exif::filetype::FileExtension;
+	///
+	/// let mut file_buffer = std::fs::read("image.jpg").unwrap();
+	/// let mut metadata = Metadata::new();
+	/// metadata.set_tag(ExifTag::ImageDescription("Hello World!".to_string()));
+	/// metadata.write_to_vec(&mut file_buffer, FileExtension::JPEG).unwrap();
+	/// ```
+	pub fn
+	write_to_vec
+	(
+		&self,
+		file_buffer: &mut Vec<u8>,
+		file_type:   FileExtension
+	)
+	-> Result<(), std::io::Error>
+	{
+		self.write_to_vec_with_options(file_buffer, file_type, &WriteOptions::new())
+	}
+
+	/// Writes the metadata to the given image data in memory, using the given
+	/// options. See `write_to_vec` and `write_to_file_with_options` for
+	/// further details.
+	pub fn
+	write_to_vec_with_options
+	(
+		&self,
+		file_buffer: &mut Vec<u8>,
+		file_type:   FileExtension,
+		options:     &WriteOptions
+	)
+	-> Result<(), std::io::Error>
+	{
+		let (general_encoded_metadata, overflow) = self.encode_metadata_with_overflow(options)?;
+		if let Some(tag) = overflow.first().filter(|_| !file_type.capabilities().xmp)
+		{
+			return io_error!(Unsupported, format!("Can't move the value of {} to XMP - Not supported for {} files!", tag.name(), file_type.name()));
+		}
+
+		Self::write_encoded_to_vec(file_buffer, file_type, &general_encoded_metadata, options)?;
+		if !overflow.is_empty()
+		{
+			truncation::write_to_xmp(file_buffer, &overflow, &self.endian)?;
+		}
+		return Ok(());
+	}
+
+	/// Writes the metadata to the image read from the given input stream and
+	/// writes the resulting image to the given output stream, e.g. for
+	/// reading from `stdin` and writing to `stdout` in a shell pipeline
+	/// without temporary files. The input gets buffered in memory up to the
+	/// limit set by `WriteOptions::max_stream_size`.
+	/// - If reading fails, the input exceeds the limit or the input is not a
+	///   valid image of the given type, an error gets returned and nothing is
+	///   written to the output.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::metadata::Metadata;
+	/// use little_exif::exif_tag::ExifTag;
+	/// use little_exif::filetype::FileExtension;
+	/// use little_exif::write_options::WriteOptions;
+	///
+	/// let mut metadata = Metadata::new();
+	/// metadata.set_tag(ExifTag::Artist("Jane Doe".to_string()));
+	/// metadata.write_to_stream(
+	///     std::io::stdin(),
+	///     std::io::stdout(),
+	///     FileExtension::JPEG,
+	///     &WriteOptions::new()
+	/// ).unwrap();
+	/// ```
+	pub fn
+	write_to_stream
+	(
+		&self,
+		input:      impl Read,
+		mut output: impl Write,
+		file_type:  FileExtension,
+		options:    &WriteOptions
+	)
+	-> Result<(), std::io::Error>
+	{
+		let mut file_buffer = read_stream_to_vec(input, options.max_stream_size)?;
+		self.write_to_vec_with_options(&mut file_buffer, file_type, options)?;
+		output.write_all(&file_buffer)?;
+		return output.flush();
+	}
+
+	/// Writes already encoded metadata (the TIFF data, see `encode`) to the
+	/// given image data in memory. See `write_to_vec` for further details.
+	pub(crate) fn
+	write_encoded_to_vec
+	(
+		file_buffer:              &mut Vec<u8>,
+		file_type:                FileExtension,
+		general_encoded_metadata: &Vec<u8>,
+		options:                  &WriteOptions
+	)
+	-> Result<(), std::io::Error>
+	{
+		let _span = span!("write", format = file_type.name(), file_size = file_buffer.len(), metadata_size = general_encoded_metadata.len());
+
+		// Work on a copy so that the data stays untouched in case of an error
+		let mut new_file_buffer = file_buffer.clone();
+		match file_type
+		{
+			FileExtension::JPEG 
+				=>  jpg::write_metadata(&mut new_file_buffer, general_encoded_metadata),
+			FileExtension::PNG {as_zTXt_chunk: _}
+				=>  png::write_metadata(&mut new_file_buffer, general_encoded_metadata, options),
+			FileExtension::WEBP 
+				=> webp::write_metadata(&mut new_file_buffer, general_encoded_metadata),
+			FileExtension::TIFF
+				=> tiff::write_metadata(&mut new_file_buffer, general_encoded_metadata),
+			FileExtension::HEIF | FileExtension::AVIF
+				=> heif::write_metadata(&mut new_file_buffer, general_encoded_metadata),
+			FileExtension::GIF
+				=> gif::write_metadata(&mut new_file_buffer, general_encoded_metadata),
+			FileExtension::CR2 | FileExtension::CR3 | FileExtension::ORF | FileExtension::RW2 | FileExtension::RAF
+				=> raw_file::unsupported_write(&file_type),
+			FileExtension::AVI
+				=> avi::write_metadata(&mut new_file_buffer, general_encoded_metadata),
+		}?;
+
+		*file_buffer = new_file_buffer;
+		return Ok(());
+	}
+
+	/// Removes all EXIF data from the given image data in memory. 
+	/// - If the data is not a valid image of the given type, an error gets 
+	///   returned and the data is left unchanged.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::metadata::Metadata;
+	/// use little_exif::filetype::FileExtension;
+	///
+	/// let mut file_buffer = std::fs::read("image.webp").unwrap();
+	/// Metadata::clear_metadata(&mut file_buffer, FileExtension::WEBP).unwrap();
+	/// ```
+	pub fn
+	clear_metadata
+	(
+		file_buffer: &mut Vec<u8>,
+		file_type:   FileExtension
+	)
+	-> Result<(), std::io::Error>
+	{
+		// Work on a copy so that the data stays untouched in case of an error
+		let mut new_file_buffer = file_buffer.clone();
+		match file_type
+		{
+			FileExtension::JPEG 
+				=>  jpg::clear_metadata(&mut new_file_buffer).map(|_| ()),
+			FileExtension::PNG {as_zTXt_chunk: _}
+				=>  png::clear_metadata(&mut new_file_buffer, false),
+			FileExtension::WEBP 
+				=> webp::clear_metadata(&mut new_file_buffer),
+			FileExtension::TIFF
+				=> tiff::clear_metadata(&mut new_file_buffer),
+			FileExtension::HEIF | FileExtension::AVIF
+				=> heif::clear_metadata(&mut new_file_buffer),
+			FileExtension::GIF
+				=> gif::clear_metadata(&mut new_file_buffer),
+			FileExtension::CR2 | FileExtension::CR3 | FileExtension::ORF | FileExtension::RW2 | FileExtension::RAF
+				=> raw_file::unsupported_write(&file_type),
+			FileExtension::AVI
+				=> avi::clear_metadata(&mut new_file_buffer),
+		}?;
+
+		*file_buffer = new_file_buffer;
+		return Ok(());
+	}
+
+	/// Removes all EXIF data from the image file at the given path. The file
+	/// is only written to if there actually is something to remove.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::metadata::Metadata;
+	///
+	/// Metadata::file_clear_metadata(std::path::Path::new("image.png")).unwrap();
+	/// ```
+	pub fn
+	file_clear_metadata
+	(
+		path: &Path
+	)
+	-> Result<(), std::io::Error>
+	{
+		Self::file_clear_metadata_in(&OsFileSystem, path)
+	}
+
+	/// Removes all EXIF data from the image file at the given path of the
+	/// given file system, e.g. a `vfs::MemoryFileSystem`. See
+	/// `file_clear_metadata` for further details.
+	pub fn
+	file_clear_metadata_in
+	(
+		file_system: &dyn FileSystem,
+		path:        &Path
+	)
+	-> Result<(), std::io::Error>
+	{
+		let file_type = Self::get_file_type_for_read(file_system, path)?;
+		match file_type
+		{
+			FileExtension::JPEG 
+				=>  jpg::file_clear_metadata(file_system, path).map(|_| ()),
+			FileExtension::PNG {as_zTXt_chunk: _}
+				=>  png::file_clear_metadata(file_system, path, false),
+			FileExtension::WEBP 
+				=> webp::file_clear_metadata(file_system, path),
+			FileExtension::TIFF
+				=> tiff::file_clear_metadata(file_system, path),
+			FileExtension::HEIF | FileExtension::AVIF
+				=> heif::file_clear_metadata(file_system, path),
+			FileExtension::GIF
+				=> gif::file_clear_metadata(file_system, path),
+			FileExtension::CR2 | FileExtension::CR3 | FileExtension::ORF | FileExtension::RW2 | FileExtension::RAF
+				=> raw_file::unsupported_write(&file_type),
+			FileExtension::AVI
+				=> avi::file_clear_metadata(file_system, path),
+		}
 	}
 
-	/// Gets the sored tag in the metadata by its hex value.
-	/// Returns `None`if the tag is not present in the metadata struct.
-	/// 
+	/// Moves the EXIF data to the position given by the specification of the
+	/// file format, in case it has been put somewhere else by another tool.
+	/// Reading such data works anyway (with a warning, see
+	/// `Metadata::warnings`) and writing always puts the EXIF data at the
+	/// right position, so this is only needed for fixing files without
+	/// rewriting their metadata. The EXIF data itself is left as it is.
+	/// Returns whether the data has been changed.
+	/// - Currently only WebP data is supported, where some tools put the
+	///   EXIF chunk in front of the image data. For other types, an error of
+	///   kind `Unsupported` gets returned.
+	/// - If the data is not a valid image of the given type, an error gets
+	///   returned and the data is left unchanged.
+	///
 	/// # Examples
 	/// ```no_run
-	/// // Note that the tag identifier of course does not need to be written in hex format
-	/// // Hex notation only used in this example for more clarity
 	/// use little_exif::metadata::Metadata;
-	/// 
-	/// let metadata = Metadata::new_from_path(std::path::Path::new("image.png")).unwrap();
-	/// let tag = metadata.get_tag_by_hex(0x010e);
+	/// use little_exif::filetype::FileExtension;
+	///
+	/// let mut file_buffer = std::fs::read("image.webp").unwrap();
+	/// if Metadata::normalize_metadata_position(&mut file_buffer, FileExtension::WEBP).unwrap()
+	/// {
+	///     std::fs::write("image.webp", &file_buffer).unwrap();
+	/// }
 	/// ```
 	pub fn
-	get_tag_by_hex
+	normalize_metadata_position
 	(
-		&self,
-		input_tag_hex: u16
+		file_buffer: &mut Vec<u8>,
+		file_type:   FileExtension
 	)
-	-> Option<&ExifTag>
+	-> Result<bool, std::io::Error>
 	{
-		for tag in &self.data
+		// Work on a copy so that the data stays untouched in case of an error
+		let mut new_file_buffer = file_buffer.clone();
+		let changed = match file_type
 		{
-			if tag.as_u16() == input_tag_hex
-			{
-				return Some(tag);
-			}
-		}
-		return None;
+			FileExtension::WEBP
+				=> webp::normalize_metadata_position(&mut new_file_buffer),
+			_
+				=> io_error!(Unsupported, format!("Normalizing the position of the metadata is not supported for {} files!", file_type.name())),
+		}?;
+
+		*file_buffer = new_file_buffer;
+		return Ok(changed);
 	}
 
-	/// Sets the tag in the metadata struct. If the tag is already in there it gets replaced
+	/// Moves the EXIF data of the image file at the given path to the
+	/// position given by the specification of the file format (see
+	/// `normalize_metadata_position`). The file is only written to if there
+	/// actually is something to move.
 	///
 	/// # Examples
 	/// ```no_run
 	/// use little_exif::metadata::Metadata;
-	/// use little_exif::exif_tag::ExifTag;
-	/// 
-	/// let mut metadata = Metadata::new();
-	/// metadata.set_tag(
-	///     ExifTag::ISO(vec![1234])
-	/// );
+	///
+	/// Metadata::file_normalize_metadata_position(std::path::Path::new("image.webp")).unwrap();
 	/// ```
 	pub fn
-	set_tag
+	file_normalize_metadata_position
 	(
-		&mut self,
-		input_tag: ExifTag,
+		path: &Path
 	)
+	-> Result<bool, std::io::Error>
 	{
-		self.data.retain(|tag| tag.as_u16() != input_tag.as_u16());
-		self.data.push(input_tag);
-
-		// Sort the tags by the IFD they will go into the file later on
-		self.data.sort_by(
-			|a, b| 
-			if a.get_group() == b.get_group() 
-			{
-				// Same group, but unknown should go last 
-				if a.is_unknown() == b.is_unknown()
-				{
-					std::cmp::Ordering::Equal
-				}
-				else if !a.is_unknown() && b.is_unknown()
-				{
-					std::cmp::Ordering::Less
-				}
-				else
-				{
-					std::cmp::Ordering::Greater
-				}
-				
-			}
-			else
-			{
-				if a.get_group() < b.get_group()                                // e.g. IFD0 < ExifIFD
-				{
-					std::cmp::Ordering::Less
-				}
-				else
-				{
-					std::cmp::Ordering::Greater
-				}
-			}
-		);
+		match Self::get_file_type_for_read(&OsFileSystem, path)?
+		{
+			FileExtension::WEBP
+				=> webp::file_normalize_metadata_position(&OsFileSystem, path),
+			file_type
+				=> io_error!(Unsupported, format!("Normalizing the position of the metadata is not supported for {} files!", file_type.name())),
+		}
 	}
 
-	/// Converts the metadata into a file specific vector of bytes
-	/// Only to be used in combination with some other library/code that is
-	/// able to handle the specific file type.
-	/// Simply writing this to a file often is not enough, e.g. with WebP you
-	/// have to determine where to write this, update the file size information
-	/// and so on - check file type specific implementations or documentation
-	/// for further details
+	/// Removes all EXIF data from the given image data in memory like
+	/// `clear_metadata`, returning an `UndoToken` holding the removed data.
+	/// This allows undoing the removal later on, e.g. for an undo function of
+	/// a GUI application, without keeping a copy of the whole image, and
+	/// reporting what has been removed (see `UndoToken::redaction_report`).
+	/// - If the data is not a valid image of the given type, an error gets 
+	///   returned and the data is left unchanged.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::metadata::Metadata;
+	/// use little_exif::filetype::FileExtension;
+	///
+	/// let mut file_buffer = std::fs::read("image.jpg").unwrap();
+	/// let undo_token = Metadata::clear_metadata_with_undo(&mut file_buffer, FileExtension::JPEG).unwrap();
+	/// undo_token.apply_to_vec(&mut file_buffer).unwrap();
+	/// ```
 	pub fn
-	as_u8_vec
+	clear_metadata_with_undo
 	(
-		&self,
-		for_file_type: FileExtension
+		file_buffer: &mut Vec<u8>,
+		file_type:   FileExtension
 	)
-	-> Vec<u8>
+	-> Result<UndoToken, std::io::Error>
 	{
-		let general_encoded_metadata = self.encode_metadata_general();
-
-		match for_file_type
+		let removed_data = match Self::read_pre_decode_general_from_reader(&mut Cursor::new(file_buffer.as_slice()), &file_type, &ReadOptions::new(), &mut Vec::new())
 		{
-			FileExtension::PNG {as_zTXt_chunk} 
-				=>  png::as_u8_vec(&general_encoded_metadata, as_zTXt_chunk),
-			FileExtension::JPEG 
-				=>  jpg::as_u8_vec(&general_encoded_metadata),
-			FileExtension::WEBP 
-				=> webp::as_u8_vec(&general_encoded_metadata),
-		}
+			Ok(pre_decode_general) 
+				=> Some(pre_decode_general[EXIF_HEADER.len()..].to_vec()),
+			Err(error) if error.kind() == std::io::ErrorKind::InvalidData 
+				=> return Err(error),
+			Err(_) 
+				=> None,
+		};
+
+		Self::clear_metadata(file_buffer, file_type)?;
+		return Ok(UndoToken::new(file_type, removed_data));
 	}
 
-	/// Writes the metadata to the specified file.
-	/// This could return an error for multiple reasons:
-	/// - The file does not exist at the given path
-	/// - Interpreting the given path fails
-	/// - The file type is not supported
+	/// Removes all EXIF data from the image file at the given path like
+	/// `file_clear_metadata`, returning an `UndoToken` holding the removed
+	/// data (see `clear_metadata_with_undo`). The file is only written to if
+	/// there actually is something to remove.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::metadata::Metadata;
+	///
+	/// let path = std::path::Path::new("image.png");
+	/// let undo_token = Metadata::file_clear_metadata_with_undo(path).unwrap();
+	/// undo_token.apply_to_file(path).unwrap();
+	/// ```
 	pub fn
-	write_to_file
+	file_clear_metadata_with_undo
 	(
-		&self,
 		path: &Path
 	)
-	-> Result<(), std::io::Error>
+	-> Result<UndoToken, std::io::Error>
 	{
-		if !path.exists()
-		{
-			return io_error!(Other, "Can't write Metadata - File does not exist!");
-		}
-
-		let raw_file_type_str = path.extension();
-		if raw_file_type_str.is_none()
-		{
-			return io_error!(Other, "Can't get extension from given path!");
-		}
-		let file_type_str = raw_file_type_str.unwrap().to_str();
-		if file_type_str.is_none()
-		{
-			return io_error!(Other, "Can't convert file type to string!");
-		}
-
-		let raw_file_type = FileExtension::from_str(file_type_str.unwrap().to_lowercase().as_str());
-		if raw_file_type.is_err()
-		{
-			return io_error!(Unsupported, "Can't read Metadata - Unsupported file type!");
-		}
+		let file_type = Self::get_file_type_for_read(&OsFileSystem, path)?;
+		let file_buffer = std::fs::read(path)?;
 
-		match raw_file_type.unwrap()
+		let mut new_file_buffer = file_buffer.clone();
+		let undo_token = Self::clear_metadata_with_undo(&mut new_file_buffer, file_type)?;
+		if new_file_buffer != file_buffer
 		{
-			FileExtension::JPEG 
-				=>  jpg::write_metadata(&path, &self.encode_metadata_general()),
-			FileExtension::PNG {as_zTXt_chunk: _}
-				=>  png::write_metadata(&path, &self.encode_metadata_general()),
-			FileExtension::WEBP 
-				=> webp::write_metadata(&path, &self.encode_metadata_general()),
+			write_vec_to_file(path, &new_file_buffer)?;
 		}
+		return Ok(undo_token);
 	}
 
 	fn
@@ -365,20 +2698,18 @@ Metadata
 		// Decode all the tags
 		let mut all_tags = Vec::new();
 
-		// Start with IFD0
-		if let Ok(ifd0_and_subifd_tags) = Self::decode_ifd(
-			&encoded_data[14..].to_vec(),
+		// Start with IFD0, which usually follows the TIFF header but may be
+		// located anywhere (e.g. at the end of TIFF files). Errors come with
+		// the IFD and entry that failed
+		let tiff_data = &encoded_data[EXIF_HEADER.len()..];
+		let ifd0_offset = endian.read::<u32>(tiff_data, 4).unwrap_or_default();
+		all_tags.extend(Self::decode_ifd(
+			tiff_data,
 			&ExifTagGroup::IFD0,
-			8,
-			&endian
-		)
-		{
-			all_tags.extend(ifd0_and_subifd_tags);
-		}
-		else
-		{
-			return io_error!(Other, "Could not get IFD0 tags!");
-		}
+			ifd0_offset,
+			&endian,
+			&mut vec![ifd0_offset]
+		)?);
 
 		return Ok((endian, all_tags));
 	}
@@ -387,29 +2718,45 @@ Metadata
 	fn
 	decode_ifd
 	(
-		encoded_data: &Vec<u8>,
+		tiff_data: &[u8],                                                       // Starting with the TIFF header, which all offsets are relative to
 		group: &ExifTagGroup,
-		given_offset: u32,
-		endian: &Endian
+		ifd_offset: u32,
+		endian: &Endian,
+		visited_offsets: &mut Vec<u32>                                          // Of the IFDs decoded so far, for detecting loops
 	)
 	-> Result<Vec<ExifTag>, std::io::Error>
 	{
+		let ifd_context = || ParseErrorContext::new().ifd(&format!("{:?}", group));
+
+		let encoded_data = match tiff_data.get(ifd_offset as usize..)
+		{
+			Some(encoded_data) => encoded_data,
+			None               => return io_error!(UnexpectedEof, "IFD offset exceeds EXIF data!").with_parse_context(ifd_context),
+		};
+
+		let read_u16 = |position: usize| endian.read::<u16>(encoded_data, position).ok_or_else(|| std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "IFD exceeds EXIF data!"));
+		let read_u32 = |position: usize| endian.read::<u32>(encoded_data, position).ok_or_else(|| std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "IFD exceeds EXIF data!"));
+
 		// The first two bytes give us the number of entries in this IFD
-		let number_of_entries = from_u8_vec_macro!(u16, &encoded_data[0..2].to_vec(), endian);
+		let number_of_entries = read_u16(0).with_parse_context(ifd_context)?;
 
-		// Assert that we have enough data to unpack
-		assert!(2 + IFD_ENTRY_LENGTH as usize * number_of_entries as usize + IFD_END.len() <= encoded_data.len());
+		// Check that we have enough data to unpack
+		if 2 + IFD_ENTRY_LENGTH as usize * number_of_entries as usize + IFD_END.len() > encoded_data.len()
+		{
+			return io_error!(UnexpectedEof, "Not enough data for IFD entries!").with_parse_context(ifd_context);
+		}
 
 		let mut tags: Vec<ExifTag> = Vec::new();
 		for i in 0..number_of_entries
 		{
 			// index within the given data where the current entry starts
 			let ifd_start_index = (2 + (i as u32)*IFD_ENTRY_LENGTH) as usize;
+			let entry_context = || ifd_context().entry(i as usize);
 
 			// Decode the first 8 bytes with the tag, format and component number
-			let hex_tag = from_u8_vec_macro!(u16, &encoded_data[(ifd_start_index)..(ifd_start_index+2)].to_vec(), endian);
-			let hex_format = from_u8_vec_macro!(u16, &encoded_data[(ifd_start_index+2)..(ifd_start_index+4)].to_vec(), endian);
-			let hex_component_number = from_u8_vec_macro!(u32, &encoded_data[(ifd_start_index+4)..(ifd_start_index+8)].to_vec(), endian);
+			let hex_tag = read_u16(ifd_start_index).with_parse_context(entry_context)?;
+			let hex_format = read_u16(ifd_start_index+2).with_parse_context(entry_context)?;
+			let hex_component_number = read_u32(ifd_start_index+4).with_parse_context(entry_context)?;
 
 			// Decoding the format
 			let format;
@@ -419,63 +2766,75 @@ Metadata
 			}
 			else
 			{
-				return io_error!(Other, "Illegal format value!");
+				return io_error!(Other, "Illegal format value!").with_parse_context(entry_context);
 			}
 
 			// Check if the tag is known and compatible with the given format
 			// Return error if incompatible
 			// Use one of the unkown tags if unknown
-			if let Ok(tag) = ExifTag::from_u16(hex_tag)
+			if let Ok(tag) = ExifTag::from_u16_in_group(hex_tag, group)
 			{
 				if tag.format().as_u16() != format.as_u16()
 				{
-					return io_error!(Other, "Illegal format for known tag!");
+					return io_error!(Other, "Illegal format for known tag!").with_parse_context(entry_context);
 				}
 			}
 
 			// Calculating the number of required bytes to determine if next
 			// 4 bytes are data or an offset to data
-			let byte_count = format.bytes_per_component() * hex_component_number;
+			let byte_count = format.bytes_per_component() as u64 * hex_component_number as u64;
 
-			let raw_data;
-			if byte_count > 4
+			let raw_data = if byte_count > 4
 			{
 				// Compute the offset
-				let hex_offset = from_u8_vec_macro!(u32, &encoded_data[(ifd_start_index+8)..(ifd_start_index+12)].to_vec(), endian) - given_offset;
-				raw_data = encoded_data[(hex_offset as usize)..((hex_offset+byte_count) as usize)].to_vec();
+				let hex_offset = read_u32(ifd_start_index+8).with_parse_context(entry_context)? as u64;
+				match tiff_data.get((hex_offset as usize)..((hex_offset+byte_count) as usize))
+				{
+					Some(raw_data) => raw_data.to_vec(),
+					None           => return io_error!(UnexpectedEof, "Tag data exceeds EXIF data!").with_parse_context(entry_context),
+				}
 			}
 			else
 			{
-				// The 4 bytes are the actual data
-				raw_data = encoded_data[(ifd_start_index+8)..(ifd_start_index+12)].to_vec();
-			}
+				// The first bytes of the 4 bytes are the actual data, the
+				// rest is padding
+				encoded_data[(ifd_start_index+8)..(ifd_start_index+8+byte_count as usize)].to_vec()
+			};
 
 			// If this is known tag...
-			if let Ok(tag) = ExifTag::from_u16(hex_tag)
+			if let Ok(tag) = ExifTag::from_u16_in_group(hex_tag, group)
 			{
 				// ...for a SubIFD...
 				if let Some(subifd_group) = tag.is_offset_tag()
 				{
 					// ...perform a recursive call
-					let offset = from_u8_vec_macro!(u32, &raw_data, endian) - given_offset;
-					if let Ok(subifd_result) = Self::decode_ifd(
-						&encoded_data[offset as usize..].to_vec(),
-						&subifd_group,
-						offset,
-						endian
-					)
+					let absolute_offset = read_u32(ifd_start_index+8).with_parse_context(entry_context)?;
+					if absolute_offset as usize > tiff_data.len()
 					{
-						tags.extend(subifd_result);
-						continue;
+						return io_error!(Other, "SubIFD offset out of range!").with_parse_context(entry_context);
 					}
-					else
+
+					// A SubIFD pointing back to an IFD would be decoded over
+					// and over again until the stack overflows
+					if visited_offsets.contains(&absolute_offset)
 					{
-						return io_error!(Other, "Could not decode SubIFD!");
+						return io_error!(Other, "SubIFD loop detected!").with_parse_context(entry_context);
 					}
+					visited_offsets.push(absolute_offset);
+
+					// Errors keep the context of the SubIFD entry that failed
+					tags.extend(Self::decode_ifd(
+						tiff_data,
+						&subifd_group,
+						absolute_offset,
+						endian,
+						visited_offsets
+					)?);
+					continue;
 				}
 			}
 			
-			tags.push(ExifTag::from_u16_with_data(hex_tag, &format, &raw_data, &endian, group).unwrap());
+			tags.push(ExifTag::from_u16_with_data(hex_tag, &format, &raw_data, endian, group).unwrap());
 			
 		}
 
@@ -489,14 +2848,14 @@ Metadata
 		group: ExifTagGroup,                                                    // The group the specific tags need to belong to (e.g. IFD0, ExifIFD, ...)
 		given_offset: u32,                                                      // How much offset already exists
 		next_ifd_link: &[u8; 4],                                                // A link to the next IFD (e.g. IFD1 for IFD0) or 4 bytes of 0x00 to signal "no next IFD"
-		subifd_tag: Option<ExifTag>                                             // An optional ExifTag signaling that a SubIFD will follow
+		subifd_tags: &[(ExifTag, u32)]                                          // The offset tags of the SubIFDs that follow, with the length of each SubIFD
 	)
 	-> Option<(u32, Vec<u8>)>
 	{
 		// Start Interop IFD with number of entries
 		// If there are none, return None
 		let mut ifd_vec: Vec<u8> = Vec::new();
-		let mut count_entries = subifd_tag.is_some() as u16;
+		let mut count_entries = subifd_tags.len() as u16;
 		for tag in &self.data
 		{
 			if tag.is_writable() && tag.get_group() == group
@@ -511,12 +2870,11 @@ Metadata
 		}
 
 		// Start by adding the number of entries
-		ifd_vec.extend(to_u8_vec_macro!(u16, &count_entries, &self.endian).iter());
+		self.endian.write(&mut ifd_vec, count_entries);
 		assert_eq!(ifd_vec.len(), 2);
 
 		// Compute first offset value and provide offset area in case its needed
-		let mut next_offset: u32 = 0                    as u32
-		+ given_offset                                  as u32
+		let mut next_offset: u32 = given_offset
 		+ ifd_vec.len()                                 as u32
 		+ IFD_ENTRY_LENGTH * count_entries              as u32
 		+ next_ifd_link.len()                           as u32;
@@ -531,70 +2889,63 @@ Metadata
 				continue;
 			}
 
-			let value = tag.value_as_u8_vec(&self.endian);
-			
 			// Add Tag & Data Format /                                          2 + 2 bytes
-			ifd_vec.extend(to_u8_vec_macro!(u16, &tag.as_u16(), &self.endian).iter());
-			ifd_vec.extend(to_u8_vec_macro!(u16, &tag.format().as_u16(), &self.endian).iter());
+			self.endian.write(&mut ifd_vec, tag.as_u16());
+			self.endian.write(&mut ifd_vec, tag.format().as_u16());
 
 			// Add number of components /                                       4 bytes
 			let number_of_components: u32 = tag.number_of_components();
-			ifd_vec.extend(to_u8_vec_macro!(u32, &number_of_components, &self.endian).iter());
+			self.endian.write(&mut ifd_vec, number_of_components);
 
-			// Optional string padding (i.e. string is shorter than it should be)
-			let mut string_padding: Vec<u8> = Vec::new();
-			if tag.is_string()
+			// The value has to match the number of components: Strings may be
+			// shorter than they should be and get padded, while values of tags
+			// with a predefined number of components may be longer (e.g. when
+			// decoded from a malformed file) and get cut off, keeping the NUL
+			// terminator of strings
+			let byte_count: u32 = number_of_components * tag.format().bytes_per_component();
+			let mut value = tag.value_as_u8_vec(&self.endian);
+			value.resize(byte_count as usize, 0x00);
+			if let (true, Some(last)) = (tag.is_string(), value.last_mut())
 			{
-				for _ in 0..(number_of_components - value.len() as u32)
-				{
-					string_padding.push(0x00);
-				}	
+				*last = 0x00;
 			}
 
 			// Add offset or value /                                            4 bytes
 			// Depending on the amount of data, either put it directly into
 			// next 4 bytes or write an offset where the data can be found 
-			let byte_count: u32 = number_of_components * tag.format().bytes_per_component();
 			if byte_count > 4
 			{
-				ifd_vec.extend(to_u8_vec_macro!(u32, &next_offset, &self.endian).iter());
+				self.endian.write(&mut ifd_vec, next_offset);
 				ifd_offset_area.extend(value.iter());
-				ifd_offset_area.extend(string_padding.iter());
 
 				next_offset += byte_count;
 			}
 			else
 			{
-				let pre_length = ifd_vec.len();
-
-				ifd_vec.extend(value.iter());
-				ifd_vec.extend(string_padding.iter());
-
-				let post_length = ifd_vec.len();
-
 				// Make sure that this area is indeed *exactly* 4 bytes long
-				for _ in 0..(4-(post_length - pre_length) ) {
-					ifd_vec.push(0x00);
-				}
+				ifd_vec.extend(value.iter());
+				ifd_vec.resize(ifd_vec.len() + 4 - value.len(), 0x00);
 			}
 			
 		}
 
-		// In case we have to write a SubIFD (e.g. ExifIFD) next
-		// Do NOT mix this up with link to next IFD (like e.g. IFD1)
-		if let Some(tag) = subifd_tag
+		// In case we have to write SubIFDs (e.g. ExifIFD) next, one after
+		// the other. Do NOT mix this up with link to next IFD (like e.g. IFD1)
+		let mut subifd_offset = next_offset;
+		for (tag, subifd_length) in subifd_tags
 		{
 			// Write the offset tag & data format /                             2 + 2 bytes
-			ifd_vec.extend(to_u8_vec_macro!(u16, &tag.as_u16(), &self.endian).iter());
-			ifd_vec.extend(to_u8_vec_macro!(u16, &tag.format().as_u16(), &self.endian).iter());
+			self.endian.write(&mut ifd_vec, tag.as_u16());
+			self.endian.write(&mut ifd_vec, tag.format().as_u16());
 
 			// Add number of components /                                       4 bytes
-			ifd_vec.extend(to_u8_vec_macro!(u32, &tag.number_of_components(), &self.endian).iter());
+			self.endian.write(&mut ifd_vec, tag.number_of_components());
 
 			// Add the offset /                                                 4 bytes
 			// We assume (know) that this is one component which has exactly
 			// 4 bytes, thus fitting perfectly into the directory entry
-			ifd_vec.extend(to_u8_vec_macro!(u32, &next_offset, &self.endian).iter());
+			self.endian.write(&mut ifd_vec, subifd_offset);
+			subifd_offset += subifd_length;
 		}
 
 		// Write link and offset data
@@ -606,6 +2957,196 @@ Metadata
 		return Some((next_offset, ifd_vec));
 	}
 
+	/// Encodes the metadata like `encode_metadata_with_overflow`, dropping
+	/// the values to be moved to XMP
+	fn
+	encode_metadata_with_options
+	(
+		&self,
+		options: &WriteOptions
+	)
+	-> Result<Vec<u8>, std::io::Error>
+	{
+		self.encode_metadata_with_overflow(options).map(|(encoded, _)| encoded)
+	}
+
+	/// Gets the tags to write: Stamps `Software` and `ModifyDate` (see
+	/// `WriteOptions::auto_stamp_software`), passes every tag through the
+	/// write hooks (see `WriteOptions::hook`), handles empty values (see
+	/// `WriteOptions::empty_values`), shortens text values (see
+	/// `WriteOptions::max_length`), converts the tags to the
+	/// targeted EXIF version (see `WriteOptions::exif_version`), sorts them
+	/// if requested (see `WriteOptions::deterministic`) and encodes the
+	/// result, enforcing the budget (see `WriteOptions::max_metadata_bytes`).
+	/// Returns the encoded data and the complete versions of the tags whose
+	/// values have to be moved to XMP.
+	fn
+	encode_metadata_with_overflow
+	(
+		&self,
+		options: &WriteOptions
+	)
+	-> Result<(Vec<u8>, Vec<ExifTag>), std::io::Error>
+	{
+		let stamp = match &options.software_stamp
+		{
+			SoftwareStamp::Off          => None,
+			SoftwareStamp::LittleExif   => Some(LITTLE_EXIF_SOFTWARE),
+			SoftwareStamp::Custom(name) => Some(name.as_str()),
+		};
+
+		if stamp.is_none() && options.hooks.is_empty() && options.exif_version.is_none() && !options.deterministic && options.max_metadata_bytes.is_none() && options.empty_values == EmptyValues::Keep && options.length_limits.is_empty()
+		{
+			return Ok((self.encode_metadata_general(), Vec::new()));
+		}
+
+		let mut data = self.data.clone();
+		if let Some(stamp) = stamp
+		{
+			let software = match self.get_string_value(&ExifTag::Software(String::new()))
+			{
+				Some(software) if software.contains(stamp) => software,
+				Some(software) if !software.is_empty()     => format!("{}; {}", software, stamp),
+				_                                          => stamp.to_string(),
+			};
+			data.push(ExifTag::Software(software));
+
+			// The current time would make every write produce different data
+			let now = match options.deterministic
+			{
+				true  => None,
+				false => date_inference::format_system_time_utc(std::time::SystemTime::now()),
+			};
+			if let Some(now) = now
+			{
+				data.push(ExifTag::ModifyDate(now));
+				data.push(ExifTag::OffsetTime("+00:00".to_string()));
+			}
+		}
+
+		let mut hooked = Metadata { endian: self.endian.clone(), data: Vec::new(), maker_note_offset: None, warnings: Vec::new() };
+		let mut overflow = Vec::new();
+		'tags: for tag in data
+		{
+			let mut tag = tag;
+			for hook in &options.hooks
+			{
+				match hook(&tag)
+				{
+					TagHookAction::Keep                 => (),
+					TagHookAction::Replace(replacement) => tag = replacement,
+					TagHookAction::Skip                 => continue 'tags,
+					TagHookAction::Abort(reason)        => return io_error!(Other, format!("Write aborted by hook at tag {}: {}", tag.name(), reason)),
+				}
+			}
+			if tag.is_empty_value()
+			{
+				match options.empty_values
+				{
+					EmptyValues::Keep   => (),
+					EmptyValues::Skip   => continue 'tags,
+					EmptyValues::Reject => return io_error!(InvalidInput, format!("Tag {} has an empty value", tag.name())),
+				}
+			}
+			let limit = options.length_limits.iter()
+				.find(|(limited, _, _)| limited.as_u16() == tag.as_u16() && limited.get_group() == tag.get_group());
+			if let Some((_, max_length, truncation)) = limit
+			{
+				if let Some(truncated) = truncation::truncate(&tag, *max_length, *truncation, &self.endian)
+				{
+					if *truncation == Truncation::MoveToXmp
+					{
+						overflow.push(tag);
+					}
+					tag = truncated;
+				}
+			}
+			hooked.set_tag(tag);
+		}
+
+		if let Some(exif_version) = options.exif_version
+		{
+			let tags = std::mem::take(&mut hooked.data);
+			for tag in exif_version::convert_tags_to_version(tags, exif_version)
+			{
+				hooked.set_tag(tag);
+			}
+		}
+
+		// The tags are ordered by group when being set, but within a group
+		// they stay in the order they have been set in
+		if options.deterministic
+		{
+			for group in hooked.data.chunk_by_mut(|a, b| a.get_group() == b.get_group())
+			{
+				group.sort_by_key(|tag| tag.as_u16());
+			}
+		}
+
+		let mut encoded = hooked.encode_metadata_general();
+		if let Some(limit) = options.max_metadata_bytes
+		{
+			while encoded.len() > limit
+			{
+				// Drop the tag with the lowest priority, preferring large ones
+				let lowest_priority = hooked.data.iter()
+					.enumerate()
+					.filter(|(_, tag)| tag.is_writable())
+					.min_by_key(|(_, tag)| (Self::trim_priority(tag), std::cmp::Reverse(tag.value_as_u8_vec(&hooked.endian).len())))
+					.map(|(index, _)| index);
+
+				match lowest_priority
+				{
+					Some(index) if options.trim_to_budget => hooked.data.remove(index),
+					_ => return Err(std::io::Error::new(std::io::ErrorKind::FileTooLarge, MetadataTooLarge::new(encoded.len(), limit))),
+				};
+				encoded = hooked.encode_metadata_general();
+			}
+		}
+
+		return Ok((encoded, overflow));
+	}
+
+	/// The priority of a tag when trimming the metadata to fit the budget
+	/// set by `WriteOptions::max_metadata_bytes`, tags with lower values get
+	/// dropped first:
+	/// 0. Maker notes and unknown tags, which are large and rarely used
+	/// 1. Tags of the ExifIFD and further IFDs describing the capture
+	/// 2. Tags of IFD0 describing the image
+	/// 3. Tags affecting how the image is displayed or who owns it, as well
+	///    as when and with what it was taken
+	fn
+	trim_priority
+	(
+		tag: &ExifTag
+	)
+	-> u8
+	{
+		if tag.is_unknown()
+		{
+			return 0;
+		}
+
+		match tag
+		{
+			ExifTag::MakerNote(_)
+				=> 0,
+			ExifTag::Orientation(_)        |
+			ExifTag::ColorSpace(_)         |
+			ExifTag::Copyright(_)          |
+			ExifTag::Artist(_)             |
+			ExifTag::DateTimeOriginal(_)   |
+			ExifTag::OffsetTimeOriginal(_) |
+			ExifTag::Make(_)               |
+			ExifTag::Model(_)
+				=> 3,
+			_ if tag.get_group() == ExifTagGroup::IFD0
+				=> 2,
+			_
+				=> 1,
+		}
+	}
+
 	#[allow(unused_assignments)]
 	fn
 	encode_metadata_general
@@ -614,36 +3155,64 @@ Metadata
 	)
 	-> Vec<u8>
 	{
+		let span = span!("encode", tags = self.data.len(); size);
+
 		// Start construction with TIFF header
 		let mut exif_vec: Vec<u8> = Vec::from(self.endian.header());
 		let mut current_offset: u32 = 8;
 
+		// Only link to the SubIFDs that exist, as a dangling offset would
+		// render the entire data unreadable. Their lengths don't depend on
+		// where they are located, so they can be determined up front
+		let mut subifd_tags = Vec::new();
+		for (subifd_tag, subifd_group) in [
+			(ExifTag::ExifOffset(vec![0]), ExifTagGroup::ExifIFD),
+			(ExifTag::GPSInfo(vec![0]),    ExifTagGroup::GPSIFD),
+		]
+		{
+			if let Some((_, subifd_data)) = self.encode_ifd(subifd_group, 0, &[0x00, 0x00, 0x00, 0x00], &[])
+			{
+				subifd_tags.push((subifd_tag, subifd_data.len() as u32));
+			}
+		}
+
 		// IFD0
 		if let Some((offset_post_ifd0, ifd0_data)) = self.encode_ifd(
 			ExifTagGroup::IFD0,
 			current_offset,                                                     // For the TIFF header
 			&[0x00, 0x00, 0x00, 0x00],                                          // For now no link to IFD1
-			Some(ExifTag::ExifOffset(vec![0]))
+			&subifd_tags
 		)
 		{
 			current_offset = offset_post_ifd0;
 			exif_vec.extend(ifd0_data.iter());
 		}
+		else
+		{
+			// The TIFF header always points to IFD0, so it has to exist even
+			// if it's empty: No entries and no link to a next IFD
+			exif_vec.extend([0x00, 0x00]);
+			exif_vec.extend(IFD_END);
+		}
 
-		// ExifIFD
-		if let Some((offset_post_exififd, exififd_data)) = self.encode_ifd(
-			ExifTagGroup::ExifIFD,
-			current_offset,                                                     // Don't need +8 as already accounted for in this value due to previous function call
-			&[0x00, 0x00, 0x00, 0x00],
-			None
-		)
+		// ExifIFD and GPSIFD, in the order of the offsets in IFD0
+		for subifd_group in [ExifTagGroup::ExifIFD, ExifTagGroup::GPSIFD]
 		{
-			current_offset = offset_post_exififd;
-			exif_vec.extend(exififd_data.iter());
+			if let Some((offset_post_subifd, subifd_data)) = self.encode_ifd(
+				subifd_group,
+				current_offset,                                                 // Don't need +8 as already accounted for in this value due to previous function call
+				&[0x00, 0x00, 0x00, 0x00],
+				&[]
+			)
+			{
+				current_offset = offset_post_subifd;
+				exif_vec.extend(subifd_data.iter());
+			}
 		}
 
 		// Other directories here... (someday)
-		
+
+		record!(span, size, exif_vec.len());
 		return exif_vec;
 	}
 }
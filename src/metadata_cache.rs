@@ -0,0 +1,271 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::metadata::Metadata;
+use crate::read_options::ReadOptions;
+
+/// What identifies an unchanged file: Its modification time and size
+#[derive(Debug, Clone, PartialEq)]
+struct
+FileStamp
+{
+	modified: SystemTime,
+	len:      u64,
+}
+
+impl
+FileStamp
+{
+	fn
+	new
+	(
+		path: &Path
+	)
+	-> Result<FileStamp, std::io::Error>
+	{
+		let file_metadata = std::fs::metadata(path)?;
+		return Ok(FileStamp
+		{
+			modified: file_metadata.modified()?,
+			len:      file_metadata.len(),
+		});
+	}
+}
+
+/// An in-memory cache of parsed metadata, keyed by the path of the file.
+/// Entries are only used as long as the modification time and size of the
+/// file are unchanged - otherwise the file gets parsed again. Once the cache
+/// is full, the least recently used entry gets evicted.
+///
+/// Changes made by the application itself (e.g. via `write_to_file`) may
+/// happen within the resolution of the file system's timestamps, so they
+/// should be announced using `invalidate`.
+///
+/// # Examples
+/// ```no_run
+/// use little_exif::metadata_cache::MetadataCache;
+///
+/// let mut cache = MetadataCache::new(128);
+/// let metadata = cache.get(std::path::Path::new("image.png")).unwrap();
+/// for tag in metadata.data()
+/// {
+///     // do something with the tags
+/// }
+/// ```
+pub struct
+MetadataCache
+{
+	capacity:     usize,
+	read_options: ReadOptions,
+	entries:      HashMap<PathBuf, (FileStamp, Arc<Metadata>)>,
+	usage_order:  VecDeque<PathBuf>, // Least recently used path at the front
+}
+
+impl
+MetadataCache
+{
+	/// Constructs a new, empty cache holding the metadata of up to `capacity`
+	/// files. A capacity of 0 disables caching.
+	pub fn
+	new
+	(
+		capacity: usize
+	)
+	-> MetadataCache
+	{
+		MetadataCache
+		{
+			capacity,
+			read_options: ReadOptions::new(),
+			entries:      HashMap::new(),
+			usage_order:  VecDeque::new(),
+		}
+	}
+
+	/// Sets the options used for reading files that are not (or no longer
+	/// validly) cached
+	pub fn
+	read_options
+	(
+		mut self,
+		read_options: ReadOptions
+	)
+	-> MetadataCache
+	{
+		self.read_options = read_options;
+		self
+	}
+
+	/// Gets the metadata of the file at the given path, either from the cache
+	/// or - if the file is not cached or has changed since - by reading it.
+	/// Errors are the same as for `Metadata::new_from_path` and additionally
+	/// occur if the modification time of the file can't be determined.
+	pub fn
+	get
+	(
+		&mut self,
+		path: &Path
+	)
+	-> Result<Arc<Metadata>, std::io::Error>
+	{
+		let stamp = FileStamp::new(path)?;
+
+		if let Some((cached_stamp, metadata)) = self.entries.get(path)
+		{
+			if *cached_stamp == stamp
+			{
+				let metadata = metadata.clone();
+				self.mark_as_used(path);
+				return Ok(metadata);
+			}
+		}
+
+		let metadata = Arc::new(Metadata::new_from_path_with_options(path, &self.read_options)?);
+		if self.capacity == 0
+		{
+			return Ok(metadata);
+		}
+
+		self.invalidate(path);
+		while self.entries.len() >= self.capacity
+		{
+			if let Some(least_recently_used) = self.usage_order.pop_front()
+			{
+				self.entries.remove(&least_recently_used);
+			}
+		}
+
+		self.entries.insert(path.to_path_buf(), (stamp, metadata.clone()));
+		self.usage_order.push_back(path.to_path_buf());
+
+		return Ok(metadata);
+	}
+
+	/// Removes the entry for the given path so that the next `get` reads the
+	/// file again. Returns whether there was such an entry.
+	pub fn
+	invalidate
+	(
+		&mut self,
+		path: &Path
+	)
+	-> bool
+	{
+		if self.entries.remove(path).is_none()
+		{
+			return false;
+		}
+
+		self.usage_order.retain(|cached_path| cached_path != path);
+		return true;
+	}
+
+	/// Removes all entries
+	pub fn
+	clear
+	(
+		&mut self
+	)
+	{
+		self.entries.clear();
+		self.usage_order.clear();
+	}
+
+	/// The number of files currently cached
+	pub fn
+	len
+	(
+		&self
+	)
+	-> usize
+	{
+		self.entries.len()
+	}
+
+	pub fn
+	is_empty
+	(
+		&self
+	)
+	-> bool
+	{
+		self.entries.is_empty()
+	}
+
+	fn
+	mark_as_used
+	(
+		&mut self,
+		path: &Path
+	)
+	{
+		if let Some(position) = self.usage_order.iter().position(|cached_path| cached_path == path)
+		{
+			if let Some(cached_path) = self.usage_order.remove(position)
+			{
+				self.usage_order.push_back(cached_path);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use std::path::Path;
+	use std::sync::Arc;
+	use std::time::Duration;
+	use std::time::SystemTime;
+
+	use crate::exif_tag::ExifTag;
+	use crate::metadata::Metadata;
+	use crate::metadata_cache::MetadataCache;
+
+	#[test]
+	fn
+	cache_hits_changes_and_eviction()
+	-> Result<(), std::io::Error>
+	{
+		let path_a = Path::new("tests/tmp_cache_a.png");
+		let path_b = Path::new("tests/tmp_cache_b.png");
+		std::fs::copy("tests/png_parse_test_image.png", path_a)?;
+		std::fs::copy("tests/png_parse_test_image.png", path_b)?;
+
+		let mut cache = MetadataCache::new(1);
+
+		// Unchanged file: Same object
+		let first = cache.get(path_a)?;
+		assert!(Arc::ptr_eq(&first, &cache.get(path_a)?));
+
+		// Changed file: Parsed again, even if the modification time is kept
+		let mut metadata = Metadata::new();
+		metadata.set_tag(ExifTag::ISO(vec![100]));
+		metadata.write_to_file(path_a)?;
+		let changed = cache.get(path_a)?;
+		assert!(!Arc::ptr_eq(&first, &changed));
+		assert!(changed.get_tag(&ExifTag::ISO(vec![])).is_some());
+
+		// A different modification time also causes a new parse
+		let file = std::fs::File::options().write(true).open(path_a)?;
+		file.set_modified(SystemTime::now() + Duration::from_secs(60))?;
+		assert!(!Arc::ptr_eq(&changed, &cache.get(path_a)?));
+
+		// Capacity of 1: b evicts a
+		cache.get(path_b)?;
+		assert_eq!(cache.len(), 1);
+		assert!(!cache.invalidate(path_a));
+		assert!(cache.invalidate(path_b));
+		assert!(cache.is_empty());
+
+		std::fs::remove_file(path_a)?;
+		std::fs::remove_file(path_b)?;
+		Ok(())
+	}
+}
@@ -0,0 +1,374 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Watching image files for changes of their metadata, e.g. for keeping an
+//! asset management front-end in sync with external editors. Only available
+//! with the `notify` feature.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use notify::event::AccessKind;
+use notify::event::AccessMode;
+use notify::EventKind;
+use notify::RecommendedWatcher;
+use notify::RecursiveMode;
+use notify::Watcher;
+
+use crate::exif_tag::ExifTag;
+use crate::filetype::FileExtension;
+use crate::general_file_io::canonicalize;
+use crate::general_file_io::io_error;
+use crate::metadata::Metadata;
+
+/// The difference between two sets of tags. Tags are matched by their group
+/// and hex value, so a tag that is present in both sets but with a different value
+/// counts as changed.
+#[derive(Debug, Default, PartialEq)]
+pub struct
+MetadataDiff
+{
+	pub added:   Vec<ExifTag>,
+	pub removed: Vec<ExifTag>,
+	pub changed: Vec<(ExifTag, ExifTag)>, // Old and new version of the tag
+}
+
+impl
+MetadataDiff
+{
+	/// Computes the difference going from the old to the new tags
+	pub fn
+	between
+	(
+		old_tags: &[ExifTag],
+		new_tags: &[ExifTag]
+	)
+	-> MetadataDiff
+	{
+		let mut diff = MetadataDiff::default();
+
+		for new_tag in new_tags
+		{
+			match old_tags.iter().find(|old_tag| Self::same_tag(old_tag, new_tag))
+			{
+				None                                 => diff.added.push(new_tag.clone()),
+				Some(old_tag) if old_tag != new_tag  => diff.changed.push((old_tag.clone(), new_tag.clone())),
+				_                                    => (),
+			}
+		}
+
+		for old_tag in old_tags
+		{
+			if !new_tags.iter().any(|new_tag| Self::same_tag(new_tag, old_tag))
+			{
+				diff.removed.push(old_tag.clone());
+			}
+		}
+
+		return diff;
+	}
+
+	/// Whether two tags are the same tag, possibly with different values.
+	/// The hex value alone is not enough as it is only unique within a group,
+	/// e.g. `0x0001` is `InteroperabilityIndex` as well as `GPSLatitudeRef`.
+	fn
+	same_tag
+	(
+		a: &ExifTag,
+		b: &ExifTag
+	)
+	-> bool
+	{
+		a.as_u16() == b.as_u16() && a.get_group() == b.get_group()
+	}
+
+	pub fn
+	is_empty
+	(
+		&self
+	)
+	-> bool
+	{
+		self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+	}
+}
+
+/// What the handler of a `MetadataWatcher` gets informed about
+#[derive(Debug)]
+pub enum
+MetadataEvent
+{
+	/// The metadata of the file has changed (or the file was created)
+	Changed { path: PathBuf, diff: MetadataDiff },
+
+	/// The file has been removed
+	Removed { path: PathBuf },
+
+	/// The file system reported an error or the file could not be read
+	Error   { path: Option<PathBuf>, error: std::io::Error },
+}
+
+/// Re-reads the metadata of watched files when they change and reports the
+/// differences to the previously known state to a handler. The handler gets
+/// called from a background thread.
+///
+/// Note that a single write to a file may cause multiple file system events.
+/// Events that don't change the metadata are not reported, however states
+/// in between (e.g. a partially written file) may be.
+///
+/// # Examples
+/// ```no_run
+/// use little_exif::metadata_watcher::MetadataEvent;
+/// use little_exif::metadata_watcher::MetadataWatcher;
+///
+/// let mut watcher = MetadataWatcher::new(|event| {
+///     if let MetadataEvent::Changed { path, diff } = event
+///     {
+///         println!("{:?}: {} tags changed", path, diff.changed.len());
+///     }
+/// }).unwrap();
+/// watcher.watch(std::path::Path::new("photos")).unwrap();
+/// ```
+pub struct
+MetadataWatcher
+{
+	watcher:   RecommendedWatcher,
+	snapshots: Arc<Mutex<HashMap<PathBuf, Vec<ExifTag>>>>,
+}
+
+impl
+MetadataWatcher
+{
+	pub fn
+	new
+	(
+		mut handler: impl FnMut(MetadataEvent) + Send + 'static
+	)
+	-> Result<MetadataWatcher, std::io::Error>
+	{
+		let snapshots: Arc<Mutex<HashMap<PathBuf, Vec<ExifTag>>>> = Arc::new(Mutex::new(HashMap::new()));
+		let handler_snapshots = snapshots.clone();
+
+		let watcher = notify::recommended_watcher(move |event_result: notify::Result<notify::Event>| {
+			let event = match event_result
+			{
+				Ok(event) => event,
+				Err(error) => {
+					let path = error.paths.first().cloned();
+					handler(MetadataEvent::Error { path, error: std::io::Error::other(error.to_string()) });
+					return;
+				},
+			};
+
+			let mut snapshots = handler_snapshots.lock().unwrap();
+			for path in event.paths
+			{
+				if !is_supported_file(&path)
+				{
+					continue;
+				}
+
+				// Writes are reported when they happen and when the file is
+				// closed, as the kernel may merge multiple events of one write
+				let is_change = matches!(
+					event.kind,
+					EventKind::Create(_) | EventKind::Modify(_) | EventKind::Access(AccessKind::Close(AccessMode::Write))
+				);
+
+				// Removed or e.g. renamed away
+				if matches!(event.kind, EventKind::Remove(_)) || (is_change && !path.is_file())
+				{
+					if snapshots.remove(&path).is_some()
+					{
+						handler(MetadataEvent::Removed { path });
+					}
+					continue;
+				}
+
+				if !is_change
+				{
+					continue;
+				}
+
+				match Metadata::new_from_path(&path)
+				{
+					Ok(metadata) => {
+						let old_tags = snapshots.get(&path).map(|tags| tags.as_slice()).unwrap_or(&[]);
+						let diff = MetadataDiff::between(old_tags, metadata.data());
+						snapshots.insert(path.clone(), metadata.data().clone());
+						if !diff.is_empty()
+						{
+							handler(MetadataEvent::Changed { path, diff });
+						}
+					},
+					Err(error) => handler(MetadataEvent::Error { path: Some(path), error }),
+				}
+			}
+		});
+
+		match watcher
+		{
+			Ok(watcher) => Ok(MetadataWatcher { watcher, snapshots }),
+			Err(error)  => io_error!(Other, error.to_string()),
+		}
+	}
+
+	/// Starts watching the given file or - recursively - directory. The
+	/// current metadata of the files is used as the state that the first
+	/// changes are compared against. Events report absolute paths.
+	pub fn
+	watch
+	(
+		&mut self,
+		path: &Path
+	)
+	-> Result<(), std::io::Error>
+	{
+		// The file system reports absolute paths, based on the watched one
+		let absolute_path = canonicalize(path)?;
+		let mut snapshots = HashMap::new();
+		take_snapshots(&absolute_path, &mut snapshots)?;
+		self.snapshots.lock().unwrap().extend(snapshots);
+
+		if let Err(error) = self.watcher.watch(&absolute_path, RecursiveMode::Recursive)
+		{
+			return io_error!(Other, error.to_string());
+		}
+		return Ok(());
+	}
+
+	/// Stops watching the given file or directory
+	pub fn
+	unwatch
+	(
+		&mut self,
+		path: &Path
+	)
+	-> Result<(), std::io::Error>
+	{
+		let absolute_path = canonicalize(path)?;
+		self.snapshots.lock().unwrap().retain(|snapshot_path, _| !snapshot_path.starts_with(&absolute_path));
+
+		if let Err(error) = self.watcher.unwatch(&absolute_path)
+		{
+			return io_error!(Other, error.to_string());
+		}
+		return Ok(());
+	}
+}
+
+fn
+is_supported_file
+(
+	path: &Path
+)
+-> bool
+{
+	FileExtension::from_path(path).is_some()
+}
+
+/// Reads the metadata of the given file or all supported files in the given
+/// directory and its subdirectories
+fn
+take_snapshots
+(
+	path:      &Path,
+	snapshots: &mut HashMap<PathBuf, Vec<ExifTag>>
+)
+-> Result<(), std::io::Error>
+{
+	if path.is_dir()
+	{
+		for entry in std::fs::read_dir(path)?
+		{
+			take_snapshots(&entry?.path(), snapshots)?;
+		}
+	}
+	else if is_supported_file(path)
+	{
+		let metadata = Metadata::new_from_path(path)?;
+		snapshots.insert(path.to_path_buf(), metadata.data().clone());
+	}
+
+	return Ok(());
+}
+
+#[cfg(test)]
+mod tests
+{
+	use std::path::Path;
+	use std::sync::mpsc::channel;
+	use std::time::Duration;
+
+	use crate::exif_tag::ExifTag;
+	use crate::exif_tag::ExifTagGroup;
+	use crate::metadata::Metadata;
+	use crate::metadata_watcher::MetadataDiff;
+	use crate::metadata_watcher::MetadataEvent;
+	use crate::metadata_watcher::MetadataWatcher;
+
+	#[test]
+	fn
+	diff()
+	{
+		let old_tags = vec![ExifTag::ISO(vec![100]), ExifTag::Model("A".to_string())];
+		let new_tags = vec![ExifTag::ISO(vec![200]), ExifTag::Make("B".to_string())];
+
+		let diff = MetadataDiff::between(&old_tags, &new_tags);
+		assert_eq!(diff.added,   vec![ExifTag::Make("B".to_string())]);
+		assert_eq!(diff.removed, vec![ExifTag::Model("A".to_string())]);
+		assert_eq!(diff.changed, vec![(ExifTag::ISO(vec![100]), ExifTag::ISO(vec![200]))]);
+		assert!(MetadataDiff::between(&new_tags, &new_tags).is_empty());
+
+		// Same hex value, but different groups
+		let old_tags = vec![ExifTag::InteroperabilityIndex("R98".to_string())];
+		let new_tags = vec![ExifTag::UnknownSTRING("N".to_string(), 0x0001, ExifTagGroup::GPSIFD)];
+
+		let diff = MetadataDiff::between(&old_tags, &new_tags);
+		assert_eq!(diff.added,   new_tags);
+		assert_eq!(diff.removed, old_tags);
+		assert!(diff.changed.is_empty());
+	}
+
+	#[test]
+	fn
+	watch_file()
+	-> Result<(), std::io::Error>
+	{
+		let directory = Path::new("tests/tmp_watch");
+		let path = directory.join("image.png");
+		let _ = std::fs::remove_dir_all(directory);
+		std::fs::create_dir(directory)?;
+		std::fs::copy("tests/png_parse_test_image.png", &path)?;
+
+		let (sender, receiver) = channel();
+		let mut watcher = MetadataWatcher::new(move |event| { let _ = sender.send(event); })?;
+		watcher.watch(directory)?;
+
+		let mut metadata = Metadata::new();
+		metadata.set_tag(ExifTag::ISO(vec![100]));
+		metadata.write_to_file(&path)?;
+
+		// Wait for the event reporting the new tag
+		let mut found = false;
+		while let Ok(event) = receiver.recv_timeout(Duration::from_secs(5))
+		{
+			if let MetadataEvent::Changed { diff, .. } = event
+			{
+				if diff.added.iter().any(|tag| tag.as_u16() == ExifTag::ISO(Vec::new()).as_u16())
+				{
+					found = true;
+					break;
+				}
+			}
+		}
+
+		drop(watcher);
+		std::fs::remove_dir_all(directory)?;
+		assert!(found);
+		Ok(())
+	}
+}
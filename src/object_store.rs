@@ -0,0 +1,307 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Reading and writing the metadata of images stored in object stores like
+//! S3, GCS or Azure Blob Storage through one code path.
+//!
+//! Implement `ObjectStore` for the client of the storage service, providing
+//! ranged reads and uploads of whole objects. Reading metadata then only
+//! fetches the parts of the object that are needed (using a
+//! `range_reader::RangeReader`), while writing fetches the object, modifies
+//! it in memory and uploads it again. `FileSystemStore` implements the trait
+//! for a directory, e.g. for testing or for local deployments.
+//!
+//! The type of an image is derived from the extension of its key.
+//!
+//! # Examples
+//! ```no_run
+//! use little_exif::exif_tag::ExifTag;
+//! use little_exif::object_store;
+//! use little_exif::object_store::FileSystemStore;
+//! use little_exif::read_options::ReadOptions;
+//! use little_exif::write_options::WriteOptions;
+//!
+//! let store = FileSystemStore::new(std::path::Path::new("/srv/images"));
+//! let mut metadata = object_store::read_metadata(&store, "2024/image.jpg", &ReadOptions::new()).unwrap();
+//! metadata.set_tag(ExifTag::Artist("Jane Doe".to_string()));
+//! object_store::write_metadata(&store, "2024/image.jpg", &metadata, &WriteOptions::new()).unwrap();
+//! ```
+
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::filetype::FileExtension;
+use crate::general_file_io::io_error;
+use crate::general_file_io::write_vec_to_file;
+use crate::metadata::Metadata;
+use crate::range_reader::RangeReader;
+use crate::range_reader::RangeSource;
+use crate::read_options::ReadOptions;
+use crate::write_options::WriteOptions;
+
+/// A storage for objects identified by keys, e.g. a bucket of a cloud
+/// storage service
+pub trait
+ObjectStore
+{
+	/// Gets the size of the object with the given key in bytes
+	fn
+	size
+	(
+		&self,
+		key: &str
+	)
+	-> Result<u64, std::io::Error>;
+
+	/// Fetches `length` bytes of the object with the given key, starting at
+	/// `offset`. The range never exceeds the size of the object
+	fn
+	get_range
+	(
+		&self,
+		key:    &str,
+		offset: u64,
+		length: u64
+	)
+	-> Result<Vec<u8>, std::io::Error>;
+
+	/// Stores the given data as object with the given key, replacing the
+	/// previous content
+	fn
+	put
+	(
+		&self,
+		key:  &str,
+		data: &[u8]
+	)
+	-> Result<(), std::io::Error>;
+}
+
+/// Provides an object of a store as `RangeSource`
+struct
+ObjectRangeSource<'a, S: ObjectStore + ?Sized>
+{
+	store: &'a S,
+	key:   &'a str,
+}
+
+impl<S: ObjectStore + ?Sized>
+RangeSource
+for
+ObjectRangeSource<'_, S>
+{
+	fn
+	size
+	(
+		&mut self
+	)
+	-> Result<u64, std::io::Error>
+	{
+		self.store.size(self.key)
+	}
+
+	fn
+	fetch
+	(
+		&mut self,
+		offset: u64,
+		length: u64
+	)
+	-> Result<Vec<u8>, std::io::Error>
+	{
+		self.store.get_range(self.key, offset, length)
+	}
+}
+
+/// Determines the type of the image from the extension of its key
+fn
+file_type_of_key
+(
+	key: &str
+)
+-> Result<FileExtension, std::io::Error>
+{
+	match FileExtension::from_path(Path::new(key))
+	{
+		Some(file_type) => Ok(file_type),
+		None            => io_error!(Unsupported, format!("Can't determine a supported file type from key {}!", key)),
+	}
+}
+
+/// Fetches the whole object with the given key
+fn
+get_object
+(
+	store: &(impl ObjectStore + ?Sized),
+	key:   &str
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	let size = store.size(key)?;
+	let data = store.get_range(key, 0, size)?;
+	if data.len() as u64 != size
+	{
+		return io_error!(UnexpectedEof, format!("Expected {} bytes of {} but got {}!", size, key, data.len()));
+	}
+	return Ok(data);
+}
+
+/// Reads the metadata of the image with the given key, fetching only the
+/// parts of the object that are needed. See `Metadata::new_from_vec` for
+/// how errors are handled.
+pub fn
+read_metadata
+(
+	store:   &(impl ObjectStore + ?Sized),
+	key:     &str,
+	options: &ReadOptions
+)
+-> Result<Metadata, std::io::Error>
+{
+	let file_type = file_type_of_key(key)?;
+	let mut reader = RangeReader::new(ObjectRangeSource { store, key });
+	return Metadata::new_from_seekable_reader(&mut reader, file_type, options);
+}
+
+/// Writes the given metadata to the image with the given key, replacing any
+/// EXIF data stored in there. The object is fetched, modified in memory and
+/// stored again.
+/// - If the object is not a valid image, an error gets returned and the
+///   object is left unchanged.
+pub fn
+write_metadata
+(
+	store:    &(impl ObjectStore + ?Sized),
+	key:      &str,
+	metadata: &Metadata,
+	options:  &WriteOptions
+)
+-> Result<(), std::io::Error>
+{
+	let file_type = file_type_of_key(key)?;
+	let mut file_buffer = get_object(store, key)?;
+	metadata.write_to_vec_with_options(&mut file_buffer, file_type, options)?;
+	return store.put(key, &file_buffer);
+}
+
+/// Removes all EXIF data from the image with the given key. The object is
+/// only stored again if there actually is something to remove.
+/// - If the object is not a valid image, an error gets returned and the
+///   object is left unchanged.
+pub fn
+clear_metadata
+(
+	store: &(impl ObjectStore + ?Sized),
+	key:   &str
+)
+-> Result<(), std::io::Error>
+{
+	let file_type = file_type_of_key(key)?;
+	let file_buffer = get_object(store, key)?;
+
+	let mut new_file_buffer = file_buffer.clone();
+	Metadata::clear_metadata(&mut new_file_buffer, file_type)?;
+	if new_file_buffer != file_buffer
+	{
+		store.put(key, &new_file_buffer)?;
+	}
+	return Ok(());
+}
+
+/// An `ObjectStore` keeping the objects as files in a directory, with the
+/// keys being paths relative to it. Keys leaving the directory (e.g. using
+/// `..`) are rejected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct
+FileSystemStore
+{
+	root: PathBuf,
+}
+
+impl
+FileSystemStore
+{
+	/// Constructs a new store for the given directory
+	pub fn
+	new
+	(
+		root: &Path
+	)
+	-> FileSystemStore
+	{
+		FileSystemStore { root: root.to_path_buf() }
+	}
+
+	/// Gets the path of the file of the object with the given key
+	fn
+	path
+	(
+		&self,
+		key: &str
+	)
+	-> Result<PathBuf, std::io::Error>
+	{
+		let relative_path = Path::new(key);
+		if key.is_empty() || !relative_path.components().all(|component| matches!(component, Component::Normal(_)))
+		{
+			return io_error!(InvalidInput, format!("Invalid key {}!", key));
+		}
+		return Ok(self.root.join(relative_path));
+	}
+}
+
+impl
+ObjectStore
+for
+FileSystemStore
+{
+	fn
+	size
+	(
+		&self,
+		key: &str
+	)
+	-> Result<u64, std::io::Error>
+	{
+		Ok(std::fs::metadata(self.path(key)?)?.len())
+	}
+
+	fn
+	get_range
+	(
+		&self,
+		key:    &str,
+		offset: u64,
+		length: u64
+	)
+	-> Result<Vec<u8>, std::io::Error>
+	{
+		let mut file = std::fs::File::open(self.path(key)?)?;
+		file.seek(SeekFrom::Start(offset))?;
+
+		let mut data = Vec::new();
+		file.take(length).read_to_end(&mut data)?;
+		return Ok(data);
+	}
+
+	fn
+	put
+	(
+		&self,
+		key:  &str,
+		data: &[u8]
+	)
+	-> Result<(), std::io::Error>
+	{
+		let path = self.path(key)?;
+		if let Some(parent) = path.parent()
+		{
+			std::fs::create_dir_all(parent)?;
+		}
+		return write_vec_to_file(&path, data);
+	}
+}
@@ -0,0 +1,33 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Olympus ORF raw files are TIFF files whose header uses a magic of their
+//! own instead of 42: `IIRO` or `MMOR`, and `IIRS` for files of some older
+//! cameras. IFD0 describes the raw data and holds the metadata (including
+//! the ExifIFD with the exposure settings and Olympus' maker notes), IFD1
+//! the thumbnail.
+//!
+//! Only reading is supported, see `raw_file` - the header check of `tiff`
+//! accepts these signatures.
+
+use crate::filetype::FileExtension;
+use crate::raw_file::TiffRawFormat;
+use crate::tiff;
+
+pub(crate) const ORF: TiffRawFormat = TiffRawFormat
+{
+	file_type:        FileExtension::ORF,
+	signature_length: 4,
+	is_format:        is_orf,
+};
+
+/// Checks whether the data starts with the signature of an ORF file
+pub(crate) fn
+is_orf
+(
+	file_buffer: &[u8]
+)
+-> bool
+{
+	return tiff::ORF_SIGNATURES.iter().any(|signature| file_buffer.starts_with(signature));
+}
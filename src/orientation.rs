@@ -0,0 +1,224 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Fixing images that are shown sideways, e.g. by slideshow software, TVs or
+//! digital photo frames that don't respect the `Orientation` tag, or by
+//! viewers that respect it for images whose pixels have already been
+//! rotated (and thereby get rotated twice).
+//!
+//! little_exif doesn't decode the image data, so it can't rotate the pixels.
+//! `normalize_orientation` therefore only sets `Orientation` to 1 (i.e. the
+//! pixels are shown as they are stored) where this doesn't change how the
+//! image looks like, and reports the images that need their pixels rotated
+//! by an image editor:
+//!
+//! | `Orientation`         | Condition                          | Result                 |
+//! |-----------------------|------------------------------------|------------------------|
+//! | 1 or not set          |                                    | `Unchanged`            |
+//! | Not in the range 1..8 |                                    | `Normalized`           |
+//! | 5 to 8 (90° rotation) | Pixels already rotated, see below  | `Normalized`           |
+//! | 2 to 8                | Otherwise                          | `NeedsPixelRotation`   |
+//!
+//! The pixels are considered as already rotated if the dimensions of the
+//! stored image are the ones given by `ExifImageWidth` and `ExifImageHeight`
+//! (which cameras set to the dimensions of the unrotated image), but
+//! swapped. Editors that rotate the pixels and update these tags as well but
+//! keep the orientation can't be recognized this way. Rotations by 180° and
+//! mirroring don't change the dimensions, so they always need the pixels to
+//! be rotated.
+//!
+//! # Examples
+//! ```no_run
+//! use std::path::Path;
+//! use little_exif::orientation::normalize_orientation;
+//! use little_exif::orientation::OrientationFix;
+//!
+//! let paths = [Path::new("IMG_0001.jpg"), Path::new("IMG_0002.jpg")];
+//! for (path, result) in paths.iter().zip(normalize_orientation(&paths))
+//! {
+//!     match result
+//!     {
+//!         Ok(OrientationFix::NeedsPixelRotation(orientation)) => println!("{}: Rotate pixels ({})", path.display(), orientation),
+//!         Ok(fix)                                             => println!("{}: {:?}", path.display(), fix),
+//!         Err(error)                                          => println!("{}: {}", path.display(), error),
+//!     }
+//! }
+//! ```
+
+use std::path::Path;
+
+use crate::exif_tag::ExifTag;
+use crate::filetype::FileExtension;
+use crate::general_file_io::io_error;
+use crate::general_file_io::write_vec_to_file;
+use crate::jpg;
+use crate::metadata::Metadata;
+use crate::png;
+use crate::webp;
+use crate::tiff;
+use crate::heif;
+use crate::gif;
+use crate::avi;
+
+/// The result of normalizing the orientation of an image, see the module
+/// documentation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum
+OrientationFix
+{
+	/// The image is already shown as stored
+	Unchanged,
+	/// `Orientation` has been set to 1
+	Normalized,
+	/// The pixels have to be rotated or mirrored as described by the given
+	/// value of `Orientation`, the image has not been changed
+	NeedsPixelRotation(u16),
+}
+
+/// Gets the width and height of the stored image
+fn
+image_dimensions
+(
+	file_buffer: &[u8],
+	file_type:   FileExtension
+)
+-> Option<(u32, u32)>
+{
+	match file_type
+	{
+		FileExtension::JPEG       => jpg::image_dimensions(file_buffer),
+		FileExtension::PNG { .. } => png::image_dimensions(file_buffer),
+		FileExtension::WEBP       => webp::image_dimensions(file_buffer),
+		FileExtension::TIFF       |
+		FileExtension::CR2        |
+		FileExtension::ORF        |
+		FileExtension::RW2        => tiff::image_dimensions(file_buffer),
+		FileExtension::CR3        => None,                                      // Only stored in Canon's own boxes
+		FileExtension::RAF        => None,                                      // Only stored in Fujifilm's own header
+		FileExtension::HEIF       |
+		FileExtension::AVIF       => heif::image_dimensions(file_buffer),
+		FileExtension::GIF        => gif::image_dimensions(file_buffer),
+		FileExtension::AVI        => avi::image_dimensions(file_buffer),
+	}
+}
+
+/// Decides what to do about the orientation, see the module documentation
+fn
+check_orientation
+(
+	orientation:     Option<u16>,
+	exif_dimensions: Option<(u32, u32)>,
+	dimensions:      Option<(u32, u32)>
+)
+-> OrientationFix
+{
+	let already_rotated = exif_dimensions.is_some_and(|(width, height)| width != height && dimensions == Some((height, width)));
+
+	match orientation
+	{
+		None | Some(1)                 => OrientationFix::Unchanged,
+		Some(0) | Some(9..)            => OrientationFix::Normalized,
+		Some(5..=8) if already_rotated => OrientationFix::Normalized,
+		Some(orientation)              => OrientationFix::NeedsPixelRotation(orientation),
+	}
+}
+
+/// Normalizes the orientation of the image (see the module documentation),
+/// writing the changed `Orientation` tag only if it can be set to 1
+/// - If the image can't be read, an error gets returned and the image is
+///   not changed.
+pub fn
+normalize_orientation_of_vec
+(
+	file_buffer: &mut Vec<u8>
+)
+-> Result<OrientationFix, std::io::Error>
+{
+	let file_type = match FileExtension::from_signature(file_buffer)
+	{
+		Some(file_type) => file_type,
+		None            => return io_error!(Unsupported, "Unsupported file type!"),
+	};
+	let mut metadata = Metadata::new_from_vec(file_buffer, file_type)?;
+
+	let get_first = |tag: &ExifTag| match metadata.get_tag(tag)
+	{
+		Some(ExifTag::Orientation(values))     => values.first().map(|value| *value as u32),
+		Some(ExifTag::ExifImageWidth(values))  |
+		Some(ExifTag::ExifImageHeight(values)) => values.first().copied(),
+		_                                      => None,
+	};
+	let orientation = get_first(&ExifTag::Orientation(Vec::new())).map(|value| value as u16);
+	let exif_dimensions = get_first(&ExifTag::ExifImageWidth(Vec::new()))
+		.zip(get_first(&ExifTag::ExifImageHeight(Vec::new())));
+
+	let fix = check_orientation(orientation, exif_dimensions, image_dimensions(file_buffer, file_type));
+	if fix == OrientationFix::Normalized
+	{
+		metadata.set_tag(ExifTag::Orientation(vec![1]));
+		metadata.write_to_vec(file_buffer, file_type)?;
+	}
+	return Ok(fix);
+}
+
+/// Normalizes the orientation of the image at the given path, see
+/// `normalize_orientation_of_vec`. The file is only written to if the
+/// orientation has been normalized.
+pub fn
+normalize_orientation_of_file
+(
+	path: &Path
+)
+-> Result<OrientationFix, std::io::Error>
+{
+	let mut file_buffer = std::fs::read(path)?;
+	let fix = normalize_orientation_of_vec(&mut file_buffer)?;
+	if fix == OrientationFix::Normalized
+	{
+		write_vec_to_file(path, &file_buffer)?;
+	}
+	return Ok(fix);
+}
+
+/// Normalizes the orientation of the images at the given paths (see
+/// `normalize_orientation_of_vec`), returning the results in the same order.
+/// Failing images don't stop the processing of the others.
+pub fn
+normalize_orientation
+(
+	paths: &[&Path]
+)
+-> Vec<Result<OrientationFix, std::io::Error>>
+{
+	paths.iter().map(|path| normalize_orientation_of_file(path)).collect()
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::orientation::OrientationFix;
+	use crate::orientation::check_orientation;
+
+	#[test]
+	fn
+	check_orientations()
+	{
+		let landscape = Some((6000, 4000));
+		let portrait  = Some((4000, 6000));
+
+		assert_eq!(check_orientation(None,     landscape, landscape), OrientationFix::Unchanged);
+		assert_eq!(check_orientation(Some(1),  landscape, portrait),  OrientationFix::Unchanged);
+		assert_eq!(check_orientation(Some(0),  None,      None),      OrientationFix::Normalized);
+		assert_eq!(check_orientation(Some(42), None,      None),      OrientationFix::Normalized);
+
+		// Rotated by 90° - already done if the dimensions are swapped
+		assert_eq!(check_orientation(Some(6),  landscape, portrait),  OrientationFix::Normalized);
+		assert_eq!(check_orientation(Some(8),  landscape, landscape), OrientationFix::NeedsPixelRotation(8));
+		assert_eq!(check_orientation(Some(6),  None,      portrait),  OrientationFix::NeedsPixelRotation(6));
+		assert_eq!(check_orientation(Some(6),  Some((500, 500)), Some((500, 500))), OrientationFix::NeedsPixelRotation(6));
+
+		// Rotated by 180° or mirrored
+		assert_eq!(check_orientation(Some(3),  landscape, portrait),  OrientationFix::NeedsPixelRotation(3));
+		assert_eq!(check_orientation(Some(2),  landscape, landscape), OrientationFix::NeedsPixelRotation(2));
+	}
+}
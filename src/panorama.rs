@@ -0,0 +1,337 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Writing the metadata that panorama viewers need to display the output of
+//! stitching software, i.e. the Photo Sphere XMP properties (`GPano`
+//! namespace, see https://developers.google.com/streetview/spherical-metadata).
+//!
+//! A `PanoInfo` describes the full panorama, the area of it covered by the
+//! image (stitchers often crop the poles) and the initial view, and gets
+//! written using `write_pano_to_vec` or `write_pano_to_file`. All `GPano`
+//! properties of the image are replaced, the rest of the XMP packet is kept
+//! (see the `xmp` module). As XMP is only supported for JPEG, PNG and GIF
+//! files so far, these are the only supported types.
+//!
+//! # Examples
+//! ```no_run
+//! use little_exif::panorama::PanoInfo;
+//! use little_exif::panorama::write_pano_to_file;
+//!
+//! // A 360° panorama of which the stitcher cropped 500 pixels at each pole
+//! let info = PanoInfo::new(8000, 4000)
+//!     .cropped_area(0, 500, 8000, 3000)
+//!     .initial_view(90.0, -10.0)
+//!     .stitching_software("PanoStitch 3.1")
+//!     .source_photos_count(24);
+//! write_pano_to_file(std::path::Path::new("panorama.jpg"), &info).unwrap();
+//! ```
+
+use std::path::Path;
+
+use crate::general_file_io::io_error;
+use crate::general_file_io::write_vec_to_file;
+use crate::xmp;
+use crate::xmp::Namespace;
+
+const GPANO_NAMESPACE: Namespace = ("GPano", "http://ns.google.com/photos/1.0/panorama/");
+
+/// The projection of a panorama
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum
+ProjectionType
+{
+	/// Longitude and latitude mapped linearly to x and y, covering 360° by
+	/// 180° for the full panorama
+	Equirectangular,
+}
+
+impl
+ProjectionType
+{
+	/// Gets the value of the `GPano:ProjectionType` property
+	fn
+	as_str
+	(
+		&self
+	)
+	-> &'static str
+	{
+		match self
+		{
+			ProjectionType::Equirectangular => "equirectangular",
+		}
+	}
+}
+
+/// The panorama metadata of an image, see the module documentation. Set
+/// using chained calls, starting with the size of the full panorama given
+/// to `PanoInfo::new`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct
+PanoInfo
+{
+	pub(crate) projection:          ProjectionType,
+	pub(crate) full_width:          u32,
+	pub(crate) full_height:         u32,
+	pub(crate) cropped_area:        Option<(u32, u32, u32, u32)>,               // Left, top, width, height
+	pub(crate) initial_view:        Option<(f64, f64)>,                         // Heading, pitch
+	pub(crate) initial_fov:         Option<f64>,
+	pub(crate) pose_heading:        Option<f64>,
+	pub(crate) use_panorama_viewer: bool,
+	pub(crate) stitching_software:  Option<String>,
+	pub(crate) source_photos_count: Option<u32>,
+}
+
+impl
+PanoInfo
+{
+	/// Constructs the metadata of an equirectangular panorama with the given
+	/// full size in pixels, covered entirely by the image, to be shown in a
+	/// panorama viewer
+	pub fn
+	new
+	(
+		full_width:  u32,
+		full_height: u32
+	)
+	-> PanoInfo
+	{
+		PanoInfo
+		{
+			projection:          ProjectionType::Equirectangular,
+			full_width,
+			full_height,
+			cropped_area:        None,
+			initial_view:        None,
+			initial_fov:         None,
+			pose_heading:        None,
+			use_panorama_viewer: true,
+			stitching_software:  None,
+			source_photos_count: None,
+		}
+	}
+
+	/// Sets the projection
+	pub fn
+	projection
+	(
+		mut self,
+		projection: ProjectionType
+	)
+	-> PanoInfo
+	{
+		self.projection = projection;
+		self
+	}
+
+	/// Sets the area of the full panorama that is covered by the image, in
+	/// pixels. The size of the area has to match the size of the image.
+	pub fn
+	cropped_area
+	(
+		mut self,
+		left:   u32,
+		top:    u32,
+		width:  u32,
+		height: u32
+	)
+	-> PanoInfo
+	{
+		self.cropped_area = Some((left, top, width, height));
+		self
+	}
+
+	/// Sets the direction the viewer initially looks at in degrees: The
+	/// heading from 0 up to 360 (clockwise from north, relative to the
+	/// center of the panorama unless a pose heading is set) and the pitch
+	/// from -90 (down) to 90 (up). Both are rounded to whole degrees, as
+	/// required by the `GPano` namespace.
+	pub fn
+	initial_view
+	(
+		mut self,
+		heading: f64,
+		pitch:   f64
+	)
+	-> PanoInfo
+	{
+		self.initial_view = Some((heading, pitch));
+		self
+	}
+
+	/// Sets the initial horizontal field of view in degrees
+	pub fn
+	initial_fov
+	(
+		mut self,
+		degrees: f64
+	)
+	-> PanoInfo
+	{
+		self.initial_fov = Some(degrees);
+		self
+	}
+
+	/// Sets the compass heading of the center of the panorama in degrees
+	pub fn
+	pose_heading
+	(
+		mut self,
+		degrees: f64
+	)
+	-> PanoInfo
+	{
+		self.pose_heading = Some(degrees);
+		self
+	}
+
+	/// Sets whether the image should be shown in a panorama viewer (instead
+	/// of as regular, flat image)
+	pub fn
+	use_panorama_viewer
+	(
+		mut self,
+		use_panorama_viewer: bool
+	)
+	-> PanoInfo
+	{
+		self.use_panorama_viewer = use_panorama_viewer;
+		self
+	}
+
+	/// Sets the software that stitched the panorama
+	pub fn
+	stitching_software
+	(
+		mut self,
+		software: &str
+	)
+	-> PanoInfo
+	{
+		self.stitching_software = Some(software.to_string());
+		self
+	}
+
+	/// Sets the number of images the panorama was stitched from
+	pub fn
+	source_photos_count
+	(
+		mut self,
+		count: u32
+	)
+	-> PanoInfo
+	{
+		self.source_photos_count = Some(count);
+		self
+	}
+
+	/// Checks that the values are consistent and converts them into the
+	/// `GPano` properties. Properties that are not set have a value of
+	/// `None`, so that previous values get removed.
+	fn
+	to_properties
+	(
+		&self
+	)
+	-> Result<Vec<(&'static str, Option<String>)>, std::io::Error>
+	{
+		let (left, top, width, height) = self.cropped_area.unwrap_or((0, 0, self.full_width, self.full_height));
+		if self.full_width == 0 || self.full_height == 0 || width == 0 || height == 0
+			|| left as u64 + width as u64 > self.full_width as u64
+			|| top as u64 + height as u64 > self.full_height as u64
+		{
+			return io_error!(InvalidInput, "Invalid panorama size or cropped area!");
+		}
+
+		if let Some((heading, pitch)) = self.initial_view
+		{
+			if !heading.is_finite() || !(-90.0..=90.0).contains(&pitch)
+			{
+				return io_error!(InvalidInput, format!("Invalid initial view {}, {}!", heading, pitch));
+			}
+		}
+		if self.initial_fov.is_some_and(|degrees| !(degrees > 0.0 && degrees <= 360.0))
+			|| self.pose_heading.is_some_and(|degrees| !degrees.is_finite())
+		{
+			return io_error!(InvalidInput, "Invalid initial field of view or pose heading!");
+		}
+
+		// Headings are normalized to the range from 0 up to 360 degrees
+		let heading_of = |degrees: f64| (degrees.round() as i64).rem_euclid(360);
+
+		return Ok(vec![
+			("UsePanoramaViewer",            Some(String::from(if self.use_panorama_viewer { "True" } else { "False" }))),
+			("ProjectionType",               Some(self.projection.as_str().to_string())),
+			("FullPanoWidthPixels",          Some(self.full_width.to_string())),
+			("FullPanoHeightPixels",         Some(self.full_height.to_string())),
+			("CroppedAreaImageWidthPixels",  Some(width.to_string())),
+			("CroppedAreaImageHeightPixels", Some(height.to_string())),
+			("CroppedAreaLeftPixels",        Some(left.to_string())),
+			("CroppedAreaTopPixels",         Some(top.to_string())),
+			("PoseHeadingDegrees",           self.pose_heading.map(|degrees| format!("{:.1}", degrees.rem_euclid(360.0)))),
+			("InitialViewHeadingDegrees",    self.initial_view.map(|(heading, _)| heading_of(heading).to_string())),
+			("InitialViewPitchDegrees",      self.initial_view.map(|(_, pitch)| (pitch.round() as i64).to_string())),
+			("InitialHorizontalFOVDegrees",  self.initial_fov.map(|degrees| format!("{:.1}", degrees))),
+			("StitchingSoftware",            self.stitching_software.clone()),
+			("SourcePhotosCount",            self.source_photos_count.map(|count| count.to_string())),
+		]);
+	}
+}
+
+/// Writes the panorama metadata to the image, see the module
+/// documentation.
+/// - If the values are inconsistent (e.g. the cropped area exceeds the full
+///   panorama), an error of kind `InvalidInput` gets returned and the image
+///   is not changed.
+pub fn
+write_pano_to_vec
+(
+	file_buffer: &mut Vec<u8>,
+	info:        &PanoInfo
+)
+-> Result<(), std::io::Error>
+{
+	let properties = info.to_properties()?;
+	let packet = xmp::read_xmp_from_vec(file_buffer)?;
+	let packet = xmp::set_properties(packet.as_deref(), GPANO_NAMESPACE, &properties)?;
+	return xmp::write_xmp_to_vec(file_buffer, &packet);
+}
+
+/// Writes the panorama metadata to the image at the given path, see
+/// `write_pano_to_vec`
+pub fn
+write_pano_to_file
+(
+	path: &Path,
+	info: &PanoInfo
+)
+-> Result<(), std::io::Error>
+{
+	let mut file_buffer = std::fs::read(path)?;
+	write_pano_to_vec(&mut file_buffer, info)?;
+	return write_vec_to_file(path, &file_buffer);
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::panorama::PanoInfo;
+
+	#[test]
+	fn
+	check_values()
+	{
+		let properties = PanoInfo::new(8000, 4000)
+			.cropped_area(0, 500, 8000, 3000)
+			.initial_view(-90.4, 10.0)
+			.to_properties()
+			.unwrap();
+		assert!(properties.contains(&("CroppedAreaTopPixels",      Some("500".to_string()))));
+		assert!(properties.contains(&("InitialViewHeadingDegrees", Some("270".to_string()))));
+		assert!(properties.contains(&("StitchingSoftware",         None)));
+
+		assert!(PanoInfo::new(8000, 4000).cropped_area(1, 0, 8000, 4000).to_properties().is_err());
+		assert!(PanoInfo::new(0, 0).to_properties().is_err());
+		assert!(PanoInfo::new(8000, 4000).initial_view(0.0, 91.0).to_properties().is_err());
+	}
+}
@@ -2,21 +2,30 @@
 // See https://github.com/TechnikTobi/little_exif#license for licensing details
 
 use std::path::Path;
+use std::io::Cursor;
 use std::io::Read;
-use std::io::Write;
 use std::io::Seek;
 use std::io::SeekFrom;
-use std::fs::File;
-use std::fs::OpenOptions;
-use std::collections::VecDeque;
-
-use crc::Crc;
-use crc::CRC_32_ISO_HDLC;
-use miniz_oxide::deflate::compress_to_vec_zlib;
-use miniz_oxide::inflate::decompress_to_vec_zlib;
 
+use crate::codec::crc32;
+use crate::codec::deflate;
+use crate::codec::inflate_with_limit;
+use crate::chunk_order::chunk_order;
+use crate::chunk_types::PngChunkType;
+use crate::endian::*;
+use crate::exif_block;
+use crate::exif_block::ExifPrefix;
+use crate::errors::MissingExif;
+use crate::errors::ParseErrorContext;
+use crate::errors::WithParseContext;
+use crate::filetype::FileExtension;
 use crate::png_chunk::PngChunk;
 use crate::general_file_io::*;
+use crate::read_options::ReadOptions;
+use crate::write_options::PngExifChunk;
+use crate::write_options::WriteOptions;
+use crate::vfs::FileSystem;
+use crate::vfs::ReadSeek;
 
 pub(crate) const PNG_SIGNATURE: [u8; 8] = [0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a];
 pub(crate) const RAW_PROFILE_TYPE_EXIF: [u8; 23] = [
@@ -26,6 +35,18 @@ pub(crate) const RAW_PROFILE_TYPE_EXIF: [u8; 23] = [
 	0x65, 0x78, 0x69, 0x66, 0x00, 0x00                  // exif NUL NUL
 ];
 
+// Keyword of the iTXt chunk holding the XMP packet, including the null
+// separator
+const XMP_KEYWORD: &[u8] = b"XML:com.adobe.xmp\0";
+
+// Number of profile bytes per line of hex digits in a raw profile
+const RAW_PROFILE_BYTES_PER_LINE: usize = 36;
+
+// Upper limit for the amount of data that inflating a zTXt chunk may produce.
+// Due to the hex encoding this still allows for roughly 8 MB of EXIF data but
+// protects against zip bombs, i.e. tiny chunks that inflate to gigabytes
+pub(crate) const MAX_INFLATED_PROFILE_SIZE: usize = 16 * 1024 * 1024;
+
 // The bytes during encoding need to be encoded themselves:
 // A given byte (e.g. 0x30 for the char '0') has two values in the string of its hex representation ('3' and '0')
 // These two characters need to be encoded themselves (51 for '3', 48 for '0'), resulting in the final encoded
@@ -34,11 +55,22 @@ pub(crate) const RAW_PROFILE_TYPE_EXIF: [u8; 23] = [
 fn encode_byte(byte: &u8) -> [u8; 2] 
 {
 	[
-		byte / 16 + (if byte / 16 < 10 {'0' as u8} else {'a' as u8 - 10}),
-		byte % 16 + (if byte % 16 < 10 {'0' as u8} else {'a' as u8 - 10}) 
+		byte / 16 + (if byte / 16 < 10 {b'0'} else {b'a' - 10}),
+		byte % 16 + (if byte % 16 < 10 {b'0'} else {b'a' - 10}) 
 	]
 }
 
+/// Encodes the EXIF data the same way ImageMagick encodes its raw profiles,
+/// which is what exiftool and other readers expect to find in a "Raw profile
+/// type exif" text chunk:
+/// - A newline, the name of the profile ("exif") and another newline
+/// - The length of the profile in bytes, right-aligned in 8 characters,
+///   followed by a newline
+/// - The profile bytes in hex notation, 36 bytes (= 72 characters) per line,
+///   with each line *starting* with a newline
+/// - A final newline
+///
+/// The profile itself consists of the EXIF header followed by the EXIF data
 fn
 encode_metadata_png
 (
@@ -46,47 +78,39 @@ encode_metadata_png
 )
 -> Vec<u8>
 {
-	// The size of the EXIF data area, consists of
-	// - length of EXIF header (follows after ssss)
-	// - length of exif_vec
-	// - 1 for ssss itself (why not 4? idk)
-	let ssss = (
-		EXIF_HEADER.len() as u32 
-		+ exif_vec.len()  as u32 
-		+ 1               as u32
-	).to_string();
+	let profile = exif_block::add_prefix(exif_vec, ExifPrefix::ExifHeader);
 
 	// Construct final vector with the bytes as they will be sent to the encoder
 	//                               \n       e     x     i     f     \n
 	let mut png_exif: Vec<u8> = vec![NEWLINE, 0x65, 0x78, 0x69, 0x66, NEWLINE];
 
-	// Write ssss
-	for _ in 0..(8-ssss.len())
-	{
-		png_exif.push(SPACE);
-	}
-	png_exif.extend(ssss.as_bytes().to_vec().iter());
+	// Write the length of the profile
+	png_exif.extend(format!("{:>8}", profile.len()).as_bytes().iter());
 	png_exif.push(NEWLINE);
 
-	// Write EXIF header and previously constructed EXIF data as encoded bytes
-	for byte in &EXIF_HEADER
-	{
-		png_exif.extend(encode_byte(byte).iter());
-	}
-
-	for byte in exif_vec
+	// Write the profile as encoded bytes
+	for (index, byte) in profile.iter().enumerate()
 	{
+		if index % RAW_PROFILE_BYTES_PER_LINE == 0
+		{
+			png_exif.push(NEWLINE);
+		}
 		png_exif.extend(encode_byte(byte).iter());
 	}
-	
-	// Write end of EXIF data - 2* 0x30 results in the String "00" for 0x00
-	png_exif.push(0x30);
-	png_exif.push(0x30);
 	png_exif.push(NEWLINE);
 
 	return png_exif;
 }
 
+/// Performs the reverse operation to `encode_metadata_png`. Two forms are
+/// accepted:
+/// - The raw profile text as described above. The profile name and length
+///   are separated by whitespace, the hex digits may be spread over any number
+///   of lines. Only as many bytes as the length states are decoded, so the
+///   additional trailing "00" written by earlier versions of little_exif is
+///   ignored.
+/// - The EXIF data itself without any hex encoding, as some writers simply
+///   put the bytes into the chunk.
 fn
 decode_metadata_png
 (
@@ -94,115 +118,122 @@ decode_metadata_png
 )
 -> Result<Vec<u8>, std::io::Error>
 {
+	// Check for the raw binary form first
+	if let Ok(exif_data) = exif_block::normalize(encoded_data)
+	{
+		return Ok(exif_data);
+	}
 
-	let mut exif_all: VecDeque<u8> = VecDeque::new();
-	let mut other_byte: Option<u8> = None;
+	let mut tokens = encoded_data
+		.split(|byte| byte.is_ascii_whitespace())
+		.filter(|token| !token.is_empty());
 
-	// This performs the reverse operation to encode_byte:
-	// Two successing bytes represent the ASCII values of the digits of 
-	// a hex value, e.g. 0x31, 0x32 represent '1' and '2', so the resulting
-	// hex value is 0x12, which gets pushed onto exif_all
-	for byte in encoded_data
+	// Skip the profile name and get the length information
+	if tokens.next().is_none()
 	{
-		// Ignore newline characters
-		if *byte == '\n' as u8
-		{
-			continue;
-		}
+		return io_error!(InvalidData, "Raw profile is empty!");
+	}
 
-		if other_byte.is_none()
-		{
-			other_byte = Some(*byte);
-			continue;
-		}
+	let length = match tokens.next()
+		.and_then(|token| std::str::from_utf8(token).ok())
+		.and_then(|token| token.parse::<usize>().ok())
+	{
+		Some(length) => length,
+		None         => return io_error!(InvalidData, "Could not read length of raw profile!"),
+	};
 
-		let value_string = "".to_owned()
-			+ &(other_byte.unwrap() as char).to_string()
-			+ &(*byte as char).to_string();
-			
-		if let Ok(value) = u8::from_str_radix(value_string.trim(), 16)
-		{
-			exif_all.push_back(value);
-		}
-		
-		other_byte = None;
+	// Decode the hex digits, two of them make up a byte
+	let hex_digits: Vec<u8> = tokens.flatten().copied().collect();
+	if hex_digits.len() < 2 * length
+	{
+		return io_error!(InvalidData, "Raw profile contains less data than promised by its length!");
 	}
 
-	// Now remove the first element until the exif header is found
-	// Store the popped elements to get the size information
-	let mut exif_header_found = false;
-	let mut pop_storage: Vec<u8> = Vec::new();
-
-	while !exif_header_found
+	let mut profile = Vec::with_capacity(length);
+	for pair in hex_digits[..2 * length].chunks(2)
 	{
-		let mut counter = 0;
-		for header_value in &EXIF_HEADER
+		let digits = std::str::from_utf8(pair).unwrap_or("");
+		if let Ok(value) = u8::from_str_radix(digits, 16)
 		{
-			if *header_value != exif_all[counter]
-			{
-				break;
-			}
-			counter += 1;
+			profile.push(value);
 		}
-
-		exif_header_found = counter == EXIF_HEADER.len();
-
-		if exif_header_found
+		else
 		{
-			break;
+			return io_error!(InvalidData, "Raw profile contains invalid hex digits!");
 		}
-		pop_storage.push(exif_all.pop_front().unwrap());
 	}
 
-	// The exif header has been found
-	// -> exif_all now starts with the exif header information
-	// -> pop_storage has in its last 4 elements the size information
-	//    that will now get extracted
-	// Consider this part optional as it might be removed in the future and
-	// isn't strictly necessary and just for validating the data we get
-	assert!(pop_storage.len() > 0);
+	return exif_block::normalize(&profile);
+}
 
-	// Using the encode_byte function re-encode the bytes regarding the size
-	// information and construct its value using decimal based shifting
-	// Example: 153 = 0
-	// + 5*10*10^(2*0) + 3*1*10^(2*0) 
-	// + 0*10*10^(2*1) + 1*1*10^(2*1)
-	let mut given_exif_len = 0u64;
-	for i in 0..std::cmp::min(4, pop_storage.len())
+/// Checks if the given zTXt chunk data starts with the "Raw profile type exif"
+/// keyword, followed by its NUL separator and the compression method (zlib)
+#[allow(non_snake_case)]
+fn
+is_raw_profile_exif
+(
+	zTXt_chunk_data: &[u8]
+)
+-> bool
+{
+	zTXt_chunk_data.starts_with(&RAW_PROFILE_TYPE_EXIF)
+}
+
+/// Gets the EXIF data from the data of a "Raw profile type exif" zTXt chunk
+/// by inflating the compressed part (see `codec::inflate_with_limit`) and decoding
+/// the resulting raw profile (see `decode_metadata_png`)
+#[allow(non_snake_case)]
+fn
+read_raw_profile
+(
+	zTXt_chunk_data: &[u8],
+	limit:           usize
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	if !is_raw_profile_exif(zTXt_chunk_data)
 	{
-		let re_encoded_byte = encode_byte(&pop_storage[pop_storage.len() -1 -i]);
-		let tens_place = u64::from_str_radix(&(re_encoded_byte[0] as char).to_string(), 10).unwrap();
-		let ones_place = u64::from_str_radix(&(re_encoded_byte[1] as char).to_string(), 10).unwrap();
-		given_exif_len = given_exif_len + tens_place * 10 * 10_u64.pow((2 * i).try_into().unwrap());
-		given_exif_len = given_exif_len + ones_place *  1 * 10_u64.pow((2 * i).try_into().unwrap());
+		return io_error!(InvalidData, "Not a zTXt chunk with a raw EXIF profile!");
 	}
 
-	assert!(given_exif_len == exif_all.len().try_into().unwrap());
-	// End optional part
-
-	return Ok(Vec::from(exif_all));
+	let inflated_data = inflate_with_limit(&zTXt_chunk_data[RAW_PROFILE_TYPE_EXIF.len()..], limit)?;
+	return decode_metadata_png(&inflated_data);
 }
 
+/// Opens the PNG file at the given path for reading
 fn
-check_signature
+open_file
 (
-	path: &Path
+	file_system: &dyn FileSystem,
+	path:        &Path
 )
--> Result<File, std::io::Error>
+-> Result<Box<dyn ReadSeek>, std::io::Error>
 {
-	if !path.exists()
+	if !file_system.exists(path)
 	{
 		return io_error!(NotFound, "Can't open PNG file - File does not exist!");
 	}
 
-	let mut file = OpenOptions::new()
-		.read(true)
-		.open(path)
-		.expect("Could not open file");
-	
+	return file_system.open(path);
+}
+
+/// Checks the signature at the start of the given PNG data. Afterwards, the
+/// cursor is located at the start of the first chunk
+fn
+check_signature
+<T: Read + Seek>
+(
+	cursor: &mut T
+)
+-> Result<(), std::io::Error>
+{
 	// Check the signature
 	let mut signature_buffer = [0u8; 8];
-	file.read(&mut signature_buffer).unwrap();
+	perform_file_action!(cursor.seek(SeekFrom::Start(0)));
+	if !try_read_exact(cursor, &mut signature_buffer)?
+	{
+		return io_error!(InvalidData, "Can't open PNG file - Wrong signature!");
+	}
 	let signature_is_valid = signature_buffer.iter()
 		.zip(PNG_SIGNATURE.iter())
 		.filter(|&(read, constant)| read == constant)
@@ -213,267 +244,705 @@ check_signature
 		return io_error!(InvalidData, "Can't open PNG file - Wrong signature!");
 	}
 
-	// Signature is valid - can proceed using the file as PNG file
-	return Ok(file);
+	// Signature is valid - can proceed using the data as PNG file
+	return Ok(());
 }
 
 // TODO: Check if this is also affected by endianness
 // Edit: Should... not? I guess?
+/// Reads the chunk at the current file position and moves the cursor to the
+/// start of the next chunk. The data of the chunk is read and its CRC gets
+/// validated if requested or if the chunk may contain metadata - otherwise
+/// its data is skipped without reading it
 fn
-get_next_chunk_descriptor
+get_next_chunk
+<T: Read + Seek>
 (
-	file: &mut File
+	file:         &mut T,
+	validate_crc: bool
 )
--> Result<PngChunk, std::io::Error>
+-> Result<(PngChunk, Option<Vec<u8>>), std::io::Error>
 {
+	let offset = file.stream_position()?;
+
 	// Read the start of the chunk
 	let mut chunk_start = [0u8; 8];
-	let mut bytes_read = file.read(&mut chunk_start).unwrap();
-
-	// Check that indeed 8 bytes were read
-	if bytes_read != 8
+	if !try_read_exact(file, &mut chunk_start)?
 	{
-		return io_error!(Other, "Could not read start of chunk");
+		return io_error!(Other, "Could not read start of chunk").with_parse_context(|| ParseErrorContext::new().offset(offset));
 	}
 
-	// Construct name of chunk and its length
-	let chunk_name = String::from_utf8((&chunk_start[4..8]).to_vec());
+	// Construct type of chunk and its length
 	let mut chunk_length = 0u32;
 	for byte in &chunk_start[0..4]
 	{
 		chunk_length = chunk_length * 256 + *byte as u32;
 	}
 
+	// The length may be anything up to 4 GiB, so check it before allocating
+	// the buffer for the data (or seeking past it)
+	let data_position = file.stream_position()?;
+	let data_end      = file.seek(SeekFrom::End(0))?;
+	file.seek(SeekFrom::Start(data_position))?;
+	if chunk_length > 0x7fff_ffff
+	{
+		return io_error!(InvalidData, format!("Chunk length of {} bytes exceeds the maximum of 2^31-1 bytes!", chunk_length)).with_parse_context(|| ParseErrorContext::new().offset(offset));
+	}
+	if data_position + chunk_length as u64 > data_end
+	{
+		return io_error!(InvalidData, format!("Chunk length of {} bytes exceeds the remaining data!", chunk_length)).with_parse_context(|| ParseErrorContext::new().offset(offset));
+	}
+
+	// Note: chunk_length does NOT include the +4 for the CRC area!
+	let png_chunk = match PngChunk::new(chunk_start[4..8].try_into().unwrap(), chunk_length)
+	{
+		Ok(png_chunk) => png_chunk,
+		Err(_)        => return io_error!(Other, "Invalid PNG chunk name").with_parse_context(|| ParseErrorContext::new().offset(offset)),
+	};
+	let context = || ParseErrorContext::new().offset(offset).chunk(&png_chunk.chunk_type().to_string());
+
+	// Chunks that are not of interest don't need to be read at all
+	if !validate_crc && !may_contain_exif(&png_chunk)
+	{
+		perform_file_action!(file.seek(SeekFrom::Current(chunk_length as i64 + 4)).with_parse_context(context));
+		return Ok((png_chunk, None));
+	}
+
 	// Read chunk data ...
 	let mut chunk_data_buffer = vec![0u8; chunk_length as usize];
-	bytes_read = file.read(&mut chunk_data_buffer).unwrap();
-	if bytes_read != chunk_length as usize
+	if !try_read_exact(file, &mut chunk_data_buffer)?
 	{
-		return io_error!(Other, "Could not read chunk data");
+		return io_error!(Other, "Could not read chunk data").with_parse_context(context);
 	}
 
 	// ... and CRC values
 	let mut chunk_crc_buffer = [0u8; 4];
-	bytes_read = file.read(&mut chunk_crc_buffer).unwrap();
-	if bytes_read != 4
+	if !try_read_exact(file, &mut chunk_crc_buffer)?
 	{
-		return io_error!(Other, "Could not read chunk CRC");
+		return io_error!(Other, "Could not read chunk CRC").with_parse_context(context);
 	}
 
 	// Compute CRC on chunk
-	let mut crc_input = Vec::new();
-	crc_input.extend(chunk_start[4..8].iter());
-	crc_input.extend(chunk_data_buffer.iter());
-
-	let crc_struct = Crc::<u32>::new(&CRC_32_ISO_HDLC);
-	let checksum = crc_struct.checksum(&crc_input) as u32;
+	let checksum = crc32(&[&chunk_start[4..8], &chunk_data_buffer]);
 
-	for i in 0..4
+	for (i, crc_byte) in chunk_crc_buffer.iter().enumerate()
 	{
-		if ((checksum >> (8 * (3-i))) as u8) != chunk_crc_buffer[i]
+		if ((checksum >> (8 * (3-i))) as u8) != *crc_byte
 		{
-			return io_error!(InvalidData, "Checksum check failed while reading PNG!");
+			return io_error!(InvalidData, "Checksum check failed while reading PNG!").with_parse_context(context);
 		}
 	}
 
-	// If validating the chunk using the CRC was successful, return its descriptor
-	// Note: chunk_length does NOT include the +4 for the CRC area!
-	if let Ok(png_chunk) = PngChunk::from_string(
-		&chunk_name.unwrap(),
-		chunk_length
-	)
-	{
-		return Ok(png_chunk);
-	}
-	else
-	{
-		return io_error!(Other, "Invalid PNG chunk name");
-	}
+	// Validating the chunk using the CRC was successful
+	return Ok((png_chunk, Some(chunk_data_buffer)));
+}
+
+/// Gets the descriptor of the chunk at the current file position and moves
+/// the cursor to the start of the next chunk. Relies on `get_next_chunk` by
+/// calling that function and throwing away the data
+fn
+get_next_chunk_descriptor
+<T: Read + Seek>
+(
+	file:         &mut T,
+	validate_crc: bool
+)
+-> Result<PngChunk, std::io::Error>
+{
+	let (png_chunk, _) = get_next_chunk(file, validate_crc)?;
+	return Ok(png_chunk);
 }
 
 /// "Parses" the PNG by checking various properties:
-/// - Can the file be opened and is the signature valid?
+/// - Is the signature valid?
 /// - Are the various chunks OK or not? For this, the local subroutine `get_next_chunk_descriptor` is used
+///
+/// If `validate_crc` is false, only the CRCs of chunks that may contain
+/// metadata are validated
 pub(crate) fn
 parse_png
+<T: Read + Seek>
 (
-	path: &Path
+	cursor:       &mut T,
+	validate_crc: bool
 )
 -> Result<Vec<PngChunk>, std::io::Error>
 {
-	let mut file = check_signature(path)?;
+	let span = span!("parse", format = "PNG"; chunks);
+
+	check_signature(cursor)?;
 	let mut chunks = Vec::new();
 
 	loop
 	{
-		let chunk_descriptor = get_next_chunk_descriptor(&mut file)?;
+		let chunk_descriptor = get_next_chunk_descriptor(cursor, validate_crc)?;
 		chunks.push(chunk_descriptor);
 
-		if chunks.last().unwrap().as_string() == "IEND".to_string()
+		if chunks.last().unwrap().chunk_type() == PngChunkType::IEND
 		{
 			break;
 		}
 	}
 
+	record!(span, chunks, chunks.len());
 	return Ok(chunks);
 }
 
-// Clears existing metadata chunk from a png file
-// Gets called before writing any new metadata
+/// Checks if the type of the chunk is one that may contain EXIF data
+fn
+may_contain_exif
+(
+	chunk: &PngChunk
+)
+-> bool
+{
+	matches!(chunk.chunk_type(), PngChunkType::zTXt | PngChunkType::tEXt | PngChunkType::eXIf)
+}
+
+/// Checks if the given chunk holds EXIF data, which is the case for
+/// - zTXt and tEXt chunks with the "Raw profile type exif" keyword
+/// - eXIf chunks
 #[allow(non_snake_case)]
-pub(crate) fn
-clear_metadata
+fn
+is_exif_chunk
 (
-	path: &Path
+	chunk:      &PngChunk,
+	chunk_data: &[u8]
 )
--> Result<(), std::io::Error>
+-> bool
 {
+	match chunk.chunk_type()
+	{
+		PngChunkType::zTXt => is_raw_profile_exif(chunk_data),
+		PngChunkType::tEXt => chunk_data.starts_with(&RAW_PROFILE_TYPE_EXIF[..RAW_PROFILE_TYPE_EXIF.len()-1]),
+		PngChunkType::eXIf => true,
+		_                  => false,
+	}
+}
 
-	// Parse the PNG - if this fails, the clear operation fails as well
-	let parse_png_result = parse_png(path)?;
+/// Gets the EXIF data from the data of a chunk for which `is_exif_chunk`
+/// holds true
+fn
+decode_exif_chunk
+(
+	chunk:      &PngChunk,
+	chunk_data: &[u8]
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	match chunk.chunk_type()
+	{
+		PngChunkType::zTXt => read_raw_profile(chunk_data, MAX_INFLATED_PROFILE_SIZE),
+		PngChunkType::tEXt => decode_metadata_png(&chunk_data[RAW_PROFILE_TYPE_EXIF.len()-1..].to_vec()),
+		_                  => exif_block::normalize(chunk_data),
+	}
+}
+
+/// Reads the data of the given chunk, assuming that the file cursor is at the
+/// start of the chunk. Afterwards, the cursor is located at the start of the
+/// next chunk
+fn
+read_chunk_data
+<T: Read + Seek>
+(
+	file:  &mut T,
+	chunk: &PngChunk
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	// Skip chunk length and type (4+4 Bytes)
+	perform_file_action!(file.seek(SeekFrom::Current(8)));
 
-	// Parsed PNG is Ok to use - Open the file and go through the chunks
-	let mut file = OpenOptions::new()
-		.write(true)
-		.read(true)
-		.open(path)
-		.expect("Could not open file");
-	let mut seek_counter = 8u64;
+	let mut chunk_data = vec![0u8; chunk.length() as usize];
+	if !try_read_exact(file, &mut chunk_data)?
+	{
+		return io_error!(Other, "Could not read chunk data").with_parse_context(|| ParseErrorContext::new().chunk(&chunk.chunk_type().to_string()));
+	}
 
-	for chunk in &parse_png_result
+	// Skip the CRC as it is not important at this point
+	perform_file_action!(file.seek(SeekFrom::Current(4)));
+
+	return Ok(chunk_data);
+}
+
+/// A chunk together with its position in and its data from the PNG data, see
+/// `locate_chunks`
+type LocatedChunk<'a> = (usize, PngChunk, &'a [u8]);
+
+/// Gets the chunks of the PNG data together with their positions and data.
+/// Unlike `parse_png`, which only checks the chunks that may contain EXIF
+/// data, this checks that every chunk including its CRC lies within the
+/// data.
+/// - If a chunk exceeds the data, an error of kind `InvalidData` gets
+///   returned.
+fn
+locate_chunks
+(
+	file_buffer: &[u8]
+)
+-> Result<Vec<LocatedChunk<'_>>, std::io::Error>
+{
+	let chunks = parse_png(&mut Cursor::new(file_buffer), false)?;
+	let mut position = PNG_SIGNATURE.len();
+	let mut located = Vec::new();
+
+	for chunk in chunks
 	{
-		// If this is not a zTXt chunk, jump to the next chunk
-		if chunk.as_string() != String::from("zTXt")
+		let data_start = position + 8;
+		let data_end   = data_start + chunk.length() as usize;
+		let chunk_data = match file_buffer.get(data_start..data_end + 4)
 		{
-			seek_counter += chunk.length() as u64 + 12;
-			perform_file_action!(file.seek(SeekFrom::Current(chunk.length() as i64 + 12)));
-			continue;
-		}
+			Some(chunk_data) => &chunk_data[..chunk.length() as usize],
+			None             => return io_error!(InvalidData, "PNG chunk exceeds the data!")
+				.with_parse_context(|| ParseErrorContext::new().offset(position as u64).chunk(&chunk.chunk_type().to_string())),
+		};
+		located.push((position, chunk, chunk_data));
+		position = data_end + 4;
+	}
 
-		// Skip chunk length and type (4+4 Bytes)
-		perform_file_action!(file.seek(SeekFrom::Current(8)));
+	return Ok(located);
+}
 
-		// Read chunk data into buffer for checking that this is the 
-		// correct chunk to delete
-		let mut zTXt_chunk_data = vec![0u8; chunk.length() as usize];
-		if file.read(&mut zTXt_chunk_data).unwrap() != chunk.length() as usize
+/// Gets the positions and byte counts of the iTXt chunks holding an XMP
+/// packet, i.e. the ones with the keyword "XML:com.adobe.xmp"
+fn
+find_xmp_chunks
+(
+	file_buffer: &[u8]
+)
+-> Result<Vec<(usize, usize)>, std::io::Error>
+{
+	let xmp_chunks = locate_chunks(file_buffer)?.into_iter()
+		.filter(|(_, chunk, chunk_data)| chunk.chunk_type() == PngChunkType::iTXt && chunk_data.starts_with(XMP_KEYWORD))
+		.map(|(position, chunk, _)| (position, chunk.length() as usize + 12))
+		.collect();
+	return Ok(xmp_chunks);
+}
+
+/// Reads the XMP packet of the PNG data, stored in the first iTXt chunk with
+/// the keyword "XML:com.adobe.xmp". Returns `None` if there is none.
+pub(crate) fn
+read_xmp
+(
+	file_buffer: &[u8]
+)
+-> Result<Option<Vec<u8>>, std::io::Error>
+{
+	let chunk_data = match find_xmp_chunks(file_buffer)?.first()
+	{
+		Some((start, byte_count)) => &file_buffer[start + 8..start + byte_count - 4],
+		None                      => return Ok(None),
+	};
+
+	// Keyword, compression flag and method, language tag and translated
+	// keyword, the latter two being null-terminated
+	let rest = &chunk_data[XMP_KEYWORD.len()..];
+	let (compressed, rest) = match rest
+	{
+		[flag, _, rest @ ..] => (*flag != 0, rest),
+		_                    => return io_error!(InvalidData, "iTXt chunk is truncated!"),
+	};
+	let mut text = rest;
+	for _ in 0..2
+	{
+		text = match text.iter().position(|byte| *byte == 0)
 		{
-			return io_error!(Other, "Could not read chunk data");
-		}
+			Some(end) => &text[end + 1..],
+			None      => return io_error!(InvalidData, "iTXt chunk is truncated!"),
+		};
+	}
+
+	if compressed
+	{
+		return Ok(Some(inflate_with_limit(text, MAX_INFLATED_PROFILE_SIZE)?));
+	}
+	return Ok(Some(text.to_vec()));
+}
+
+/// Replaces the XMP packet of the PNG data by the given one. It gets stored
+/// uncompressed as recommended by the XMP specification, in an iTXt chunk
+/// before the image data so that readers find it without scanning the whole
+/// file.
+pub(crate) fn
+write_xmp
+(
+	file_buffer: &mut Vec<u8>,
+	packet:      &[u8]
+)
+-> Result<(), std::io::Error>
+{
+	for (start, length) in find_xmp_chunks(file_buffer)?.iter().rev()
+	{
+		file_buffer.drain(*start..*start + *length);
+	}
+
+	let mut chunk = b"iTXt".to_vec();
+	chunk.extend(XMP_KEYWORD);
+	chunk.extend([0x00, 0x00, 0x00, 0x00]);                                     // Uncompressed, empty language tag and translated keyword
+	chunk.extend(packet);
 
-		// Compare to the "Raw profile type exif" string constant
-		let mut correct_zTXt_chunk = true;
-		for i in 0..RAW_PROFILE_TYPE_EXIF.len()
+	return insert_before_image_data(file_buffer, chunk);
+}
+
+/// Inserts the chunk, given by its type followed by its data, before the
+/// first IDAT chunk (which always exists in a valid PNG)
+fn
+insert_before_image_data
+(
+	file_buffer: &mut Vec<u8>,
+	chunk:       Vec<u8>
+)
+-> Result<(), std::io::Error>
+{
+	let mut new_chunk = Vec::new();
+	Endian::Big.write(&mut new_chunk, chunk.len() as u32 - 4);
+	let checksum = crc32(&[&chunk]);
+	new_chunk.extend(chunk);
+	Endian::Big.write(&mut new_chunk, checksum);
+
+	// Before the first IDAT chunk, which always exists in a valid PNG
+	let insert_position = match locate_chunks(file_buffer)?.iter().find(|(_, chunk, _)| matches!(chunk.chunk_type(), PngChunkType::IDAT | PngChunkType::IEND))
+	{
+		Some((position, _, _)) => *position,
+		None                   => file_buffer.len(),
+	};
+	file_buffer.splice(insert_position..insert_position, new_chunk);
+
+	return Ok(());
+}
+
+/// Gets the positions and byte counts of the tEXt, zTXt and iTXt chunks
+/// together with their keyword and text. The text of tEXt and zTXt chunks is
+/// Latin-1, the one of iTXt chunks UTF-8 (decoded lossily). Chunks that
+/// can't be decoded are skipped.
+fn
+find_text_chunks
+(
+	file_buffer: &[u8]
+)
+-> Result<Vec<(usize, usize, String, String)>, std::io::Error>
+{
+	let latin1 = |bytes: &[u8]| bytes.iter().map(|byte| *byte as char).collect::<String>();
+
+	let mut text_chunks = Vec::new();
+
+	for (start, chunk, chunk_data) in locate_chunks(file_buffer)?
+	{
+		let chunk_byte_count = chunk.length() as usize + 12;
+
+		let (keyword, rest) = match chunk_data.iter().position(|byte| *byte == 0)
 		{
-			if zTXt_chunk_data[i] != RAW_PROFILE_TYPE_EXIF[i]
+			Some(end) => (latin1(&chunk_data[..end]), &chunk_data[end + 1..]),
+			None      => continue,
+		};
+		let text = match (chunk.chunk_type(), rest)
+		{
+			(PngChunkType::tEXt, text)                      => latin1(text),
+			(PngChunkType::zTXt, [_, compressed @ ..])      => match inflate_with_limit(compressed, MAX_INFLATED_PROFILE_SIZE)
 			{
-				correct_zTXt_chunk = false;
-				break;
-			}
-		}
+				Ok(text) => latin1(&text),
+				Err(_)   => continue,
+			},
+			(PngChunkType::iTXt, [flag, _, rest @ ..])      => {
+				// Language tag and translated keyword, both null-terminated
+				let mut text = rest;
+				for _ in 0..2
+				{
+					text = match text.iter().position(|byte| *byte == 0)
+					{
+						Some(end) => &text[end + 1..],
+						None      => &[],
+					};
+				}
+				match *flag != 0
+				{
+					true  => match inflate_with_limit(text, MAX_INFLATED_PROFILE_SIZE)
+					{
+						Ok(text) => String::from_utf8_lossy(&text).to_string(),
+						Err(_)   => continue,
+					},
+					false => String::from_utf8_lossy(text).to_string(),
+				}
+			},
+			_                                               => continue,
+		};
+		text_chunks.push((start, chunk_byte_count, keyword, text));
+	}
 
-		// Skip the CRC as it is not important at this point
-		perform_file_action!(file.seek(SeekFrom::Current(4)));
+	return Ok(text_chunks);
+}
 
-		// If this is not the correct zTXt chunk, ignore current
-		// (wrong) zTXt chunk and continue with next chunk
-		if !correct_zTXt_chunk
-		{	
-			continue;
-		}
-		
-		// We have now established that this is the correct chunk to delete
-		// Therefore: Copy data from here (after CRC) onwards into a buffer...
-		let mut buffer = Vec::new();
-		perform_file_action!(file.read_to_end(&mut buffer));
+/// Reads the keywords and texts of the tEXt, zTXt and iTXt chunks of the
+/// PNG data, in the order they are stored
+pub(crate) fn
+read_text_chunks
+(
+	file_buffer: &[u8]
+)
+-> Result<Vec<(String, String)>, std::io::Error>
+{
+	return Ok(find_text_chunks(file_buffer)?
+		.into_iter()
+		.map(|(_, _, keyword, text)| (keyword, text))
+		.collect());
+}
 
-		// ...compute the new file length while we are at it...
-		let new_file_length = seek_counter + buffer.len() as u64;
+/// Replaces the text chunks with the given keyword by a single one with the
+/// given text, placed before the image data. Texts that can be encoded as
+/// Latin-1 are stored in a tEXt chunk, all others in an uncompressed iTXt
+/// chunk. If the text is `None`, the chunks are only removed.
+pub(crate) fn
+write_text_chunk
+(
+	file_buffer: &mut Vec<u8>,
+	keyword:     &str,
+	text:        Option<&str>
+)
+-> Result<(), std::io::Error>
+{
+	// Keywords are 1 to 79 Latin-1 characters without NUL
+	if keyword.is_empty() || keyword.chars().count() > 79 || keyword.chars().any(|character| character == '\0' || character as u32 > 0xff)
+	{
+		return io_error!(InvalidInput, "Invalid keyword for a PNG text chunk!");
+	}
 
-		// ...go back to the chunk to be removed...
-		perform_file_action!(file.seek(SeekFrom::Start(seek_counter)));
+	for (start, length, _, _) in find_text_chunks(file_buffer)?.iter().rev().filter(|(_, _, found, _)| found == keyword)
+	{
+		file_buffer.drain(*start..*start + *length);
+	}
 
-		// ...and overwrite it using the data from the buffer
-		perform_file_action!(file.write_all(&buffer));
-		perform_file_action!(file.seek(SeekFrom::Start(seek_counter)));		
+	let text = match text
+	{
+		Some(text) => text,
+		None       => return Ok(()),
+	};
 
-		// Update the size of the file - otherwise there will be
-		// duplicate bytes at the end!
-		perform_file_action!(file.set_len(new_file_length));
-	}
+	let latin1_keyword = keyword.chars().map(|character| character as u8).collect::<Vec<u8>>();
+	let chunk = match text.chars().all(|character| character as u32 <= 0xff)
+	{
+		true  => [b"tEXt".to_vec(), latin1_keyword, vec![0x00], text.chars().map(|character| character as u8).collect()].concat(),
+		false => [b"iTXt".to_vec(), latin1_keyword, vec![0x00, 0x00, 0x00, 0x00, 0x00], text.as_bytes().to_vec()].concat(),
+	};
+	return insert_before_image_data(file_buffer, chunk);
+}
 
-	return Ok(());
+/// Gets the width and height of the image as given by the IHDR chunk, which
+/// has to be the first one
+pub(crate) fn
+image_dimensions
+(
+	file_buffer: &[u8]
+)
+-> Option<(u32, u32)>
+{
+	if !file_buffer.starts_with(&PNG_SIGNATURE) || file_buffer.get(12..16)? != b"IHDR"
+	{
+		return None;
+	}
+	return Some((Endian::Big.read(file_buffer, 16)?, Endian::Big.read(file_buffer, 20)?));
 }
 
-#[allow(non_snake_case)]
+// Clears existing metadata chunks from png data
+// Gets called before writing any new metadata
 pub(crate) fn
-read_metadata
+clear_metadata
 (
-	path: &Path
+	file_buffer:  &mut Vec<u8>,
+	validate_crc: bool
 )
--> Result<Vec<u8>, std::io::Error>
+-> Result<(), std::io::Error>
 {
-	// Parse the PNG - if this fails, the read fails as well
-	let parse_png_result = parse_png(path)?;
+	// Parse the PNG - if this fails, the clear operation fails as well
+	let mut cursor = Cursor::new(&file_buffer);
+	let parse_png_result = parse_png(&mut cursor, validate_crc)?;
+
+	// Parsed PNG is Ok to use - Go through the chunks to find the ones that
+	// need to be removed (start & length)
+	let mut seek_counter = PNG_SIGNATURE.len() as u64;
+	let mut exif_chunk_ranges = Vec::new();
+
+	perform_file_action!(cursor.seek(SeekFrom::Start(seek_counter)));
 
-	// Parsed PNG is Ok to use - Open the file and go through the chunks
-	let mut file = check_signature(path).unwrap();
 	for chunk in &parse_png_result
 	{
-		// Wrong chunk? Seek to the next one
-		if chunk.as_string() != String::from("zTXt")
+		let chunk_byte_count = chunk.length() as u64 + 12;
+
+		if may_contain_exif(chunk)
 		{
-			perform_file_action!(file.seek(SeekFrom::Current(chunk.length() as i64 + 12)));
-			continue;
+			let chunk_data = read_chunk_data(&mut cursor, chunk)?;
+			if is_exif_chunk(chunk, &chunk_data)
+			{
+				exif_chunk_ranges.push((seek_counter as usize, chunk_byte_count as usize));
+			}
 		}
-
-		// We now have a zTXt chunk:
-		// Skip chunk length and type (4+4 Bytes)
-		perform_file_action!(file.seek(SeekFrom::Current(8)));
-
-		// Read chunk data into buffer
-		// No need to verify this using CRC as already done by parse_png(path)
-		let mut zTXt_chunk_data = vec![0u8; chunk.length() as usize];
-		if file.read(&mut zTXt_chunk_data).unwrap() != chunk.length() as usize
+		else
 		{
-			return io_error!(Other, "Could not read chunk data");
+			perform_file_action!(cursor.seek(SeekFrom::Current(chunk_byte_count as i64)));
 		}
 
-		// Check that this is the correct zTXt chunk...
-		let mut correct_zTXt_chunk = true;
-		for i in 0..RAW_PROFILE_TYPE_EXIF.len()
+		seek_counter += chunk_byte_count;
+	}
+
+	// Remove the chunks, starting with the last one so that the other 
+	// positions stay valid
+	for (start, length) in exif_chunk_ranges.iter().rev()
+	{
+		file_buffer.drain(*start..(*start + *length));
+	}
+
+	return Ok(());
+}
+
+pub(crate) fn
+file_clear_metadata
+(
+	file_system:  &dyn FileSystem,
+	path:         &Path,
+	validate_crc: bool
+)
+-> Result<(), std::io::Error>
+{
+	let mut file_buffer = read_file_to_vec(open_file(file_system, path)?)?;
+	let original_length = file_buffer.len();
+	clear_metadata(&mut file_buffer, validate_crc)?;
+
+	// Chunks only get removed, so the length tells if anything has changed
+	if file_buffer.len() != original_length
+	{
+		file_system.write(path, &file_buffer)?;
+	}
+	return Ok(());
+}
+
+/// Reads the EXIF data from the first chunk that holds some. The chunks after
+/// it are neither read nor validated
+pub(crate) fn
+read_metadata
+<T: Read + Seek>
+(
+	cursor:   &mut T,
+	options:  &ReadOptions,
+	warnings: &mut Vec<ParseErrorContext>
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	let _span = span!("parse", format = "PNG");
+
+	check_signature(cursor)?;
+	loop
+	{
+		// Read the next chunk - if this fails, the read fails as well
+		// Chunks that may contain EXIF data always come with their data, 
+		// which is already verified using the CRC
+		let offset = cursor.stream_position()?;
+		let (chunk, chunk_data) = get_next_chunk(cursor, options.validate_crc)?;
+
+		// Check that this is the correct chunk and decode its data
+		if let Some(chunk_data) = chunk_data
 		{
-			if zTXt_chunk_data[i] != RAW_PROFILE_TYPE_EXIF[i]
+			if is_exif_chunk(&chunk, &chunk_data)
 			{
-				correct_zTXt_chunk = false;
-				break;
+				// eXIf chunks are supposed to start with the TIFF header
+				if chunk.chunk_type() == PngChunkType::eXIf && chunk_data.starts_with(&EXIF_HEADER)
+				{
+					warnings.push(ParseErrorContext::new().offset(offset).chunk("eXIf").warning("eXIf chunk starts with an EXIF header!"));
+				}
+
+				return decode_exif_chunk(&chunk, &chunk_data).with_parse_context(|| ParseErrorContext::new().offset(offset).chunk(&chunk.chunk_type().to_string()));
 			}
 		}
 
-		if !correct_zTXt_chunk
+		if chunk.chunk_type() == PngChunkType::IEND
 		{
-			// Skip CRC from current (wrong) zTXt chunk and continue
-			perform_file_action!(file.seek(SeekFrom::Current(4)));
-			continue;
+			break;
 		}
+	}
+
+	return MissingExif::error("No metadata found!");
+
+}
+
+/// Checks whether the EXIF data of the PNG data is stored in an eXIf chunk,
+/// which is the only one of the chunks holding EXIF data (see
+/// `is_exif_chunk`) that some readers support
+#[allow(non_snake_case)]
+pub(crate) fn
+has_eXIf_chunk
+(
+	file_buffer: &[u8]
+)
+-> bool
+{
+	let mut cursor = Cursor::new(file_buffer);
+	if check_signature(&mut cursor).is_err()
+	{
+		return false;
+	}
 
-		// Decode zlib data...
-		if let Ok(decompressed_data) = decompress_to_vec_zlib(&zTXt_chunk_data[RAW_PROFILE_TYPE_EXIF.len()..])
+	while let Ok((chunk, chunk_data)) = get_next_chunk(&mut cursor, false)
+	{
+		if chunk_data.is_some_and(|chunk_data| is_exif_chunk(&chunk, &chunk_data))
 		{
-			// ...and perform PNG-specific decoding & return the result
-			return Ok(decode_metadata_png(&decompressed_data).unwrap());
+			return chunk.chunk_type() == PngChunkType::eXIf;
 		}
-		else
+		if chunk.chunk_type() == PngChunkType::IEND
 		{
-			return io_error!(Other, "Could not inflate compressed chunk data!");
+			break;
 		}
 	}
+	return false;
+}
 
-	return io_error!(Other, "No metadata found!");
+pub(crate) fn
+file_read_metadata
+(
+	file_system: &dyn FileSystem,
+	path:        &Path,
+	options:     &ReadOptions,
+	warnings:    &mut Vec<ParseErrorContext>
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	read_metadata(&mut open_file(file_system, path)?, options, warnings)
+}
 
+/// Encodes the metadata as the requested type of chunk. The result starts
+/// with the chunk type and is followed by the chunk data, so only the length
+/// and CRC are missing for a complete chunk
+fn
+encode_exif_chunk
+(
+	general_encoded_metadata: &Vec<u8>,
+	png_exif_chunk:           PngExifChunk
+)
+-> Vec<u8>
+{
+	match png_exif_chunk
+	{
+		PngExifChunk::zTXt(level) => {
+			// Build data of new chunk using zlib compression
+			let mut chunk: Vec<u8> = vec![0x7a, 0x54, 0x58, 0x74];
+			chunk.extend(RAW_PROFILE_TYPE_EXIF.iter());
+			chunk.extend(deflate(&encode_metadata_png(general_encoded_metadata), level).iter());
+			chunk
+		},
+		PngExifChunk::tEXt => {
+			// Same keyword, but without the compression method byte
+			let mut chunk: Vec<u8> = vec![0x74, 0x45, 0x58, 0x74];
+			chunk.extend(RAW_PROFILE_TYPE_EXIF[..RAW_PROFILE_TYPE_EXIF.len()-1].iter());
+			chunk.extend(encode_metadata_png(general_encoded_metadata).iter());
+			chunk
+		},
+		PngExifChunk::eXIf => {
+			// The EXIF data starting with the TIFF header
+			let mut chunk: Vec<u8> = vec![0x65, 0x58, 0x49, 0x66];
+			chunk.extend(exif_block::add_prefix(general_encoded_metadata, ExifPrefix::None));
+			chunk
+		},
+	}
 }
 
-/// Provides the WebP specific encoding result as vector of bytes to be used
+/// Provides the PNG specific encoding result as vector of bytes to be used
 /// by the user (e.g. in combination with another library)
 #[allow(non_snake_case)]
 pub(crate) fn
@@ -484,27 +953,21 @@ as_u8_vec
 )
 -> Vec<u8>
 {
-	let basic_png_encode_result = encode_metadata_png(general_encoded_metadata);
-
 	if !as_zTXt_chunk
 	{
-		return basic_png_encode_result;
+		return encode_metadata_png(general_encoded_metadata);
 	}
 
-	// Build data of new chunk using zlib compression (level=8 -> default)
-	let mut zTXt_chunk_data: Vec<u8> = vec![0x7a, 0x54, 0x58, 0x74];
-	zTXt_chunk_data.extend(RAW_PROFILE_TYPE_EXIF.iter());
-	zTXt_chunk_data.extend(compress_to_vec_zlib(&basic_png_encode_result, 8).iter());
-
-	return zTXt_chunk_data;
+	return encode_exif_chunk(general_encoded_metadata, WriteOptions::new().png_exif_chunk);
 }
 
 #[allow(non_snake_case)]
 pub(crate) fn
 write_metadata
 (
-	path: &Path,
-	general_encoded_metadata: &Vec<u8>
+	file_buffer:              &mut Vec<u8>,
+	general_encoded_metadata: &Vec<u8>,
+	options:                  &WriteOptions
 )
 -> Result<(), std::io::Error>
 {
@@ -512,57 +975,47 @@ write_metadata
 	// First clear the existing metadata
 	// This also parses the PNG and checks its validity, so it is safe to
 	// assume that is, in fact, a usable PNG file
-	let _ = clear_metadata(path)?;
+	clear_metadata(file_buffer, options.validate_crc)?;
 
-	let mut IHDR_length = 0u32;
-	if let Ok(chunks) = parse_png(path)
-	{
-		IHDR_length = chunks[0].length();
-	}
+	let chunks = parse_png(&mut Cursor::new(&file_buffer), false)?;
+	let insert_position = chunk_order(FileExtension::PNG { as_zTXt_chunk: matches!(options.png_exif_chunk, PngExifChunk::zTXt(_)) }).metadata_insert_position(
+		chunks.iter().map(|chunk| (
+			chunk.chunk_type().as_bytes(),
+			chunk.length() as usize + 12                                     // Length, type and CRC
+		))
+	);
 
-	// Encode the data specifically for PNG and open the image file
-	let encoded_metadata = encode_metadata_png(general_encoded_metadata);
-	let mut file = OpenOptions::new()
-		.write(true)
-		.read(true)
-		.open(path)
-		.expect("Could not open file");
-	let seek_start = 0u64         // Skip ...
-	+ PNG_SIGNATURE.len() as u64  // PNG Signature
-	+ IHDR_length         as u64  // IHDR data section
-	+ 12                  as u64; // rest of IHDR chunk (length, type, CRC)
+	// Build the new chunk, which is placed right after IHDR. This is also 
+	// valid for eXIf chunks, which must come before the first IDAT chunk
+	let exif_chunk = encode_exif_chunk(general_encoded_metadata, options.png_exif_chunk);
 
-	// Get to first chunk after IHDR, copy all the data starting from there
-	let mut buffer = Vec::new();
-	perform_file_action!(file.seek(SeekFrom::Start(seek_start)));
-	perform_file_action!(file.read_to_end(&mut buffer));
-	perform_file_action!(file.seek(SeekFrom::Start(seek_start)));
+	// Start with length of the new chunk (subtracting 4 for the type)...
+	let mut new_chunk = Vec::new();
+	Endian::Big.write(&mut new_chunk, exif_chunk.len() as u32 - 4);
 
-	// Build data of new chunk using zlib compression (level=8 -> default)
-	let mut zTXt_chunk_data: Vec<u8> = vec![0x7a, 0x54, 0x58, 0x74];
-	zTXt_chunk_data.extend(RAW_PROFILE_TYPE_EXIF.iter());
-	zTXt_chunk_data.extend(compress_to_vec_zlib(&encoded_metadata, 8).iter());
+	// ...followed by the type and data of the chunk and the CRC over those
+	let checksum = crc32(&[&exif_chunk]);
+	new_chunk.extend(exif_chunk);
+	Endian::Big.write(&mut new_chunk, checksum);
 
-	// Compute CRC and append it to the chunk data
-	let crc_struct = Crc::<u32>::new(&CRC_32_ISO_HDLC);
-	let checksum = crc_struct.checksum(&zTXt_chunk_data) as u32;
-	for i in 0..4
-	{
-		zTXt_chunk_data.push( (checksum >> (8 * (3-i))) as u8);		
-	}
-
-	// Write new data to PNG file
-	// Start with length of the new chunk (subtracting 8 for type and CRC)
-	let chunk_data_len = zTXt_chunk_data.len() as u32 - 8;
-	for i in 0..4
-	{
-		perform_file_action!(file.write( &[(chunk_data_len >> (8 * (3-i))) as u8] ));
-	}
+	file_buffer.splice(insert_position..insert_position, new_chunk);
 
-	// Write data of new chunk and rest of PNG file
-	perform_file_action!(file.write_all(&zTXt_chunk_data));
-	perform_file_action!(file.write_all(&buffer));
+	return Ok(());
+}
 
+pub(crate) fn
+file_write_metadata
+(
+	file_system:              &dyn FileSystem,
+	path:                     &Path,
+	general_encoded_metadata: &Vec<u8>,
+	options:                  &WriteOptions
+)
+-> Result<(), std::io::Error>
+{
+	let mut file_buffer = read_file_to_vec(open_file(file_system, path)?)?;
+	write_metadata(&mut file_buffer, general_encoded_metadata, options)?;
+	file_system.write(path, &file_buffer)?;
 	return Ok(());
 }
 
@@ -574,10 +1027,212 @@ mod tests
 	fn
 	parsing_test() 
 	{
-		let chunks = crate::png::parse_png(
-			std::path::Path::new("tests/png_parse_test_image.png")
-		).unwrap();
+		let mut file = std::fs::File::open("tests/png_parse_test_image.png").unwrap();
+		let chunks = crate::png::parse_png(&mut file, true).unwrap();
 		assert_eq!(chunks.len(), 3);
 	}
-	
+
+	#[test]
+	fn
+	parse_other_chunk_types()
+	{
+		use crate::chunk_types::PngChunkType;
+		use crate::endian::*;
+
+		// Ancillary chunks little_exif doesn't need to know (pHYs) or can't
+		// know (private ones) are kept, but chunk types must be letters
+		let original = std::fs::read("tests/png_parse_test_image.png").unwrap();
+		let with_chunks = |chunk_types: &[&[u8; 4]]| {
+			let mut png = original[..33].to_vec();
+			for chunk_type in chunk_types
+			{
+				let chunk = [&chunk_type[..], &[0u8; 9]].concat();
+				Endian::Big.write(&mut png, 9u32);
+				png.extend(&chunk);
+				Endian::Big.write(&mut png, crate::codec::crc32(&[&chunk]));
+			}
+			png.extend(&original[33..]);
+			crate::png::parse_png(&mut std::io::Cursor::new(png), true)
+		};
+
+		let chunks = with_chunks(&[b"pHYs", b"prVt"]).unwrap();
+		assert_eq!(chunks[1].chunk_type(), PngChunkType::pHYs);
+		assert_eq!(chunks[2].chunk_type(), PngChunkType::Unknown(*b"prVt"));
+		assert!(with_chunks(&[b"pH1s"]).is_err());
+	}
+
+	#[test]
+	fn
+	raw_profile_round_trip()
+	{
+		let exif_data: Vec<u8> = [0x49, 0x49, 0x2a, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00].repeat(5);
+		let encoded = crate::png::encode_metadata_png(&exif_data);
+
+		// Header lines, one line of 36 bytes and one with the remaining 20
+		let text = String::from_utf8(encoded.clone()).unwrap();
+		assert!(text.starts_with("\nexif\n      56\n\n457869660000"));
+		assert_eq!(text.lines().map(|line| line.len()).collect::<Vec<_>>(), vec![0, 4, 8, 0, 72, 40]);
+		assert!(text.ends_with("\n"));
+
+		let decoded = crate::png::decode_metadata_png(&encoded).unwrap();
+		assert_eq!(&decoded[..6], &crate::general_file_io::EXIF_HEADER);
+		assert_eq!(&decoded[6..], &exif_data[..]);
+	}
+
+	#[test]
+	fn
+	raw_profile_decode_variants()
+	{
+		let expected: Vec<u8> = vec![0x45, 0x78, 0x69, 0x66, 0x00, 0x00, 0x4d, 0x4d, 0x00, 0x2a];
+
+		// Written by earlier versions of little_exif: length too large by
+		// one and an additional "00" at the end, no line breaks
+		let legacy = b"\nexif\n      11\n4578696600004d4d002a00\n".to_vec();
+		let mut expected_legacy = expected.clone();
+		expected_legacy.push(0x00);
+		assert_eq!(crate::png::decode_metadata_png(&legacy).unwrap(), expected_legacy);
+
+		// Profile without the EXIF header
+		let headerless = b"\nexif\n       4\n\n4d4d002a\n".to_vec();
+		assert_eq!(crate::png::decode_metadata_png(&headerless).unwrap(), expected);
+
+		// Raw bytes instead of hex digits
+		assert_eq!(crate::png::decode_metadata_png(&expected).unwrap(), expected);
+
+		// Less data than promised
+		let truncated = b"\nexif\n      20\n4d4d\n".to_vec();
+		assert!(crate::png::decode_metadata_png(&truncated).is_err());
+	}
+
+	#[test]
+	fn
+	skip_crc_validation()
+	{
+		// Corrupt the CRC of the IDAT chunk, which is followed by IEND
+		let mut png = std::fs::read("tests/png_parse_test_image.png").unwrap();
+		let idat_crc_position = png.len() - 12 - 1;
+		png[idat_crc_position] ^= 0xff;
+
+		assert!(crate::png::parse_png(&mut std::io::Cursor::new(&png), true).is_err());
+		assert_eq!(crate::png::parse_png(&mut std::io::Cursor::new(&png), false).unwrap().len(), 3);
+
+		// Writing with validation fails without touching the data...
+		let mut buffer = png.clone();
+		let exif_data = vec![0x4d, 0x4d, 0x00, 0x2a, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00];
+		let validating = crate::write_options::WriteOptions::new();
+		assert!(crate::png::write_metadata(&mut buffer, &exif_data, &validating).is_err());
+		assert_eq!(buffer, png);
+
+		// ...while skipping it only checks the chunks that get touched
+		let skipping = crate::write_options::WriteOptions::new().validate_crc(false);
+		crate::png::write_metadata(&mut buffer, &exif_data, &skipping).unwrap();
+		let read_options = crate::read_options::ReadOptions::new();
+		let raw_exif_data = crate::png::read_metadata(&mut std::io::Cursor::new(&buffer), &read_options, &mut Vec::new());
+		assert!(raw_exif_data.unwrap().ends_with(&exif_data));
+	}
+
+	#[test]
+	fn
+	read_metadata_stops_at_exif_chunk()
+	{
+		// The EXIF chunk gets placed after IHDR, so cutting off the end of the
+		// file (including the IDAT CRC and IEND) must not affect reading it
+		let path = std::path::Path::new("early_exit.png");
+		let file_system = crate::vfs::MemoryFileSystem::new();
+		file_system.insert(path, std::fs::read("tests/png_parse_test_image.png").unwrap());
+
+		let exif_data = vec![0x4d, 0x4d, 0x00, 0x2a, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00];
+		let options = crate::write_options::WriteOptions::new();
+		crate::png::file_write_metadata(&file_system, path, &exif_data, &options).unwrap();
+
+		let png = file_system.get(path).unwrap();
+		file_system.insert(path, png[..png.len() - 14].to_vec());
+
+		let read_options = crate::read_options::ReadOptions::new().validate_crc(true);
+		let raw_exif_data = crate::png::file_read_metadata(&file_system, path, &read_options, &mut Vec::new());
+
+		assert!(raw_exif_data.unwrap().ends_with(&exif_data));
+	}
+
+	#[test]
+	fn
+	xmp_round_trip()
+	{
+		use crate::endian::*;
+
+		let mut png = std::fs::read("tests/png_parse_test_image.png").unwrap();
+		assert_eq!(crate::png::read_xmp(&png).unwrap(), None);
+
+		crate::png::write_xmp(&mut png, b"<x:xmpmeta/>").unwrap();
+		crate::png::write_xmp(&mut png, b"<x:xmpmeta>new</x:xmpmeta>").unwrap();
+		assert_eq!(crate::png::read_xmp(&png).unwrap(), Some(b"<x:xmpmeta>new</x:xmpmeta>".to_vec()));
+
+		// Placed right before IDAT, replacing the previous packet
+		let chunks = crate::png::parse_png(&mut std::io::Cursor::new(&png), true).unwrap();
+		let chunk_types = chunks.iter().map(|chunk| chunk.chunk_type().to_string()).collect::<Vec<String>>();
+		assert_eq!(chunk_types, vec!["IHDR", "iTXt", "IDAT", "IEND"]);
+
+		// Compressed packets written by other software
+		let mut chunk = b"iTXt".to_vec();
+		chunk.extend(crate::png::XMP_KEYWORD);
+		chunk.extend([0x01, 0x00]);
+		chunk.extend(b"en\0\0");
+		chunk.extend(crate::codec::deflate(b"<x:xmpmeta>zip</x:xmpmeta>", 8));
+		let mut compressed = png[..33].to_vec();
+		Endian::Big.write(&mut compressed, chunk.len() as u32 - 4);
+		compressed.extend(&chunk);
+		Endian::Big.write(&mut compressed, crate::codec::crc32(&[&chunk]));
+		compressed.extend(&std::fs::read("tests/png_parse_test_image.png").unwrap()[33..]);
+		assert_eq!(crate::png::read_xmp(&compressed).unwrap(), Some(b"<x:xmpmeta>zip</x:xmpmeta>".to_vec()));
+	}
+
+	#[test]
+	fn
+	chunk_exceeding_data()
+	{
+		// IEND declaring 0x1000 bytes of data, see tests/regressions
+		let png = std::fs::read("tests/regressions/28f33a0f76d4bbb0.bin").unwrap();
+		let error = crate::png::read_xmp(&png).err().unwrap();
+		assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+		assert!(crate::png::write_xmp(&mut png.clone(), b"<x:xmpmeta/>").is_err());
+	}
+
+	#[test]
+	#[allow(non_snake_case)]
+	fn
+	raw_profile_inflate_limit()
+	{
+		let zTXt_chunk_data = crate::png::as_u8_vec(&vec![0x4d, 0x4d, 0x00, 0x2a], true)[4..].to_vec();
+		assert!(crate::png::read_raw_profile(&zTXt_chunk_data, 1024).is_ok());
+
+		// Something that inflates to more than allowed must not be decoded
+		let mut bomb = crate::png::RAW_PROFILE_TYPE_EXIF.to_vec();
+		bomb.extend(crate::codec::deflate(&vec![0x30; 1 << 20], 10));
+		assert!(bomb.len() < 4096);
+		assert_eq!(
+			crate::png::read_raw_profile(&bomb, 1 << 16).err().unwrap().to_string(),
+			"Inflated chunk data exceeds the limit of 65536 bytes!"
+		);
+	}
+
+	#[test]
+	fn
+	chunk_length_limit()
+	-> Result<(), std::io::Error>
+	{
+		// Lengths above 2^31-1 are invalid even if there were enough data
+		let original = std::fs::read("tests/png_parse_test_image.png")?;
+		for chunk_length in [0x8000_0000u32, 0x7fff_ffff]
+		{
+			let mut png = original[..33].to_vec();
+			png.extend(chunk_length.to_be_bytes());
+			png.extend(b"zTXt");
+			png.extend(&original[33..]);
+
+			let error = crate::png::parse_png(&mut std::io::Cursor::new(&png), true).err().unwrap();
+			assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+		}
+		Ok(())
+	}
+
 }
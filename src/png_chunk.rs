@@ -1,98 +1,54 @@
 // Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
 // See https://github.com/TechnikTobi/little_exif#license for licensing details
 
-#[allow(non_camel_case_types, dead_code)]
-pub(crate) enum
-PngChunkOrdering
+use crate::chunk_types::PngChunkType;
+
+/// Describes a single PNG chunk by its type and the length of its data
+pub(crate) struct
+PngChunk
 {
-	FIRST,
-	BEFORE_IDAT,
-	BEFORE_PLTE_AND_IDAT,
-	AFTER_PLTE_BEFORE_IDAT,
-	LAST,
-	NONE
+	chunk_type: PngChunkType,
+	length:     u32,                                                            // Length of the data, NOT including type and CRC
 }
 
-/// This macro builds the enum for the different type of PNG chunks
-macro_rules! build_png_chunk_type_enum {
+impl
+PngChunk
+{
+	/// Constructs the descriptor, validating the type: Chunk types consist
+	/// of four ASCII letters, whose case carries the properties of the chunk
+	pub(crate) fn
+	new
 	(
-		$( (
-			$tag:ident,
-			$critical:expr,
-			$multiple:expr,
-			$ordering:ident
-		) ),*
+		chunk_type: [u8; 4],
+		length:     u32
 	)
-	=>
+	-> Result<PngChunk, String>
 	{
-		/// These are the different PNG chunk types currently known to
-		/// little_exif. These might be expanded in the future if necessary.
-		#[allow(non_camel_case_types)]
-		pub(crate) enum
-		PngChunk
+		if !chunk_type.iter().all(u8::is_ascii_alphabetic)
 		{
-			$(
-				$tag(u32),
-			)*
+			return Err("Invalid chunk name".to_string());
 		}
 
-		impl PngChunk
-		{
-			pub(crate) fn
-			length
-			(
-				&self
-			)
-			-> u32
-			{
-				match *self
-				{
-					$(
-						PngChunk::$tag(length) => length,
-					)*
-				}
-			}
+		return Ok(PngChunk { chunk_type: PngChunkType::from_bytes(chunk_type), length });
+	}
 
-			pub(crate) fn
-			as_string
-			(
-				&self
-			)
-			-> String
-			{
-				match *self
-				{
-					$(
-						PngChunk::$tag(_) => String::from(stringify!($tag)),
-					)*
-				}
-			}
+	pub(crate) fn
+	chunk_type
+	(
+		&self
+	)
+	-> PngChunkType
+	{
+		self.chunk_type
+	}
 
-			pub(crate) fn
-			from_string
-			(
-				string_name: &String,
-				length: u32
-			)
-			-> Result<PngChunk, String>
-			{
-				match &(*string_name.as_str())
-				{
-					$(
-						stringify!($tag) => Ok(PngChunk::$tag(length)),
-					)*
-					_ => Err("Invalid chunk name".to_string()),
-				}
-			}
-		}
+	pub(crate) fn
+	length
+	(
+		&self
+	)
+	-> u32
+	{
+		self.length
 	}
 }
-
-build_png_chunk_type_enum![
-	// Tag	Critical	Multiple	Ordering
-	(IHDR,  true,       false,      FIRST),
-	(PLTE,  true,       false,      BEFORE_IDAT),
-	(IDAT,  true,       true,       NONE),
-	(IEND,  true,       false,      LAST),
-	(zTXt,  false,      true,       NONE)
-];
@@ -0,0 +1,487 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Redaction policies, so that the privacy rules of an organization can be
+//! kept in one configuration file instead of being encoded in every tool.
+//!
+//! A `Policy` lists tags to always remove, tags to hash and tags to keep,
+//! and gets applied using `Metadata::apply_policy`:
+//! - Tags listed in `remove` are removed, regardless of the other lists.
+//! - Tags listed in `hash` are replaced by their SHA-256 digest: String
+//!   values become `sha256:` followed by the hex digits of the digest of the
+//!   value (without trailing NUL characters and spaces), binary values
+//!   become the 32 bytes of the digest. This keeps e.g. serial numbers
+//!   comparable without revealing them. Tags with other values can't hold a
+//!   digest and are removed.
+//! - If `keep` is not empty, it is an allow-list: All tags neither listed in
+//!   `keep` nor in `hash` are removed. The offsets to the SubIFDs are managed
+//!   by little_exif and are never removed this way.
+//!
+//! Note that plain digests of values with few possible inputs (like
+//! camera models) can be reversed by simply hashing all candidates. To
+//! prevent this, set a secret key using `pseudonymization_key`: The values
+//! are then replaced by their HMAC-SHA256 using that key, with string values
+//! becoming `hmac-sha256:` followed by the hex digits. Images with the same
+//! value still get the same pseudonym (e.g. for grouping a dataset by
+//! camera), but without the key the values can't be recovered.
+//! `pseudonymize` does this for the tags in `IDENTIFYING_TAGS`. The key is
+//! never part of a policy file.
+//!
+//! Policies are loaded from JSON or TOML, with the tags given by their names
+//! (see `ExifTag::from_name`). Unknown tag names and keys are rejected when
+//! loading, so that a typo doesn't silently leave data in the images. Only
+//! available with the `policy` feature.
+//! ```json
+//! { "remove": ["Artist", "OwnerName"], "hash": ["SerialNumber"], "keep": [] }
+//! ```
+//! ```toml
+//! # Privacy rules for published images
+//! remove = ["Artist", "OwnerName"]
+//! hash   = ["SerialNumber", "LensSerialNumber"]
+//! ```
+//!
+//! # Examples
+//! ```no_run
+//! use little_exif::metadata::Metadata;
+//! use little_exif::policy::Policy;
+//!
+//! let policy = Policy::from_path(std::path::Path::new("privacy.toml")).unwrap();
+//! let path = std::path::Path::new("image.jpg");
+//! let mut metadata = Metadata::new_from_path(path).unwrap();
+//! metadata.apply_policy(&policy);
+//! metadata.write_to_file(path).unwrap();
+//!
+//! // Replace serial numbers etc. by pseudonyms
+//! let key = std::fs::read("pseudonymization.key").unwrap();
+//! metadata.apply_policy(&Policy::new().pseudonymize(&key));
+//! ```
+
+use std::path::Path;
+
+use hmac::Hmac;
+use hmac::Mac;
+use sha2::Digest;
+use sha2::Sha256;
+
+use crate::endian::Endian;
+use crate::exif_tag::ExifTag;
+use crate::exif_tag::ExifTagGroup;
+use crate::exif_tag_format::ExifTagFormat;
+use crate::general_file_io::io_error;
+
+/// The prefix of hashed string values
+pub const HASH_PREFIX: &str = "sha256:";
+
+/// The prefix of string values replaced by a keyed pseudonym
+pub const HMAC_PREFIX: &str = "hmac-sha256:";
+
+/// The tags identifying a person or a device, see `Policy::pseudonymize`
+pub const IDENTIFYING_TAGS: [&str; 4] = ["SerialNumber", "LensSerialNumber", "OwnerName", "ImageUniqueID"];
+
+/// The keys a policy file may contain
+const POLICY_KEYS: [&str; 3] = ["remove", "hash", "keep"];
+
+/// Identifies a tag by its ID and the IFD its ID is unique in, see
+/// `ExifTagGroup::id_space`
+type TagId = (u16, ExifTagGroup);
+
+/// Gets the ID identifying the tag within the policy
+fn
+tag_id
+(
+	tag: &ExifTag
+)
+-> TagId
+{
+	(tag.as_u16(), tag.get_group().id_space())
+}
+
+/// Rules for which tags to remove, hash or keep, see the module
+/// documentation
+#[derive(Clone, PartialEq, Default)]
+pub struct
+Policy
+{
+	remove: Vec<TagId>,
+	hash:   Vec<TagId>,
+	keep:   Vec<TagId>,
+	key:    Option<Vec<u8>>,
+}
+
+impl
+std::fmt::Debug
+for
+Policy
+{
+	fn
+	fmt
+	(
+		&self,
+		formatter: &mut std::fmt::Formatter<'_>
+	)
+	-> std::fmt::Result
+	{
+		// The key is secret, so only whether there is one gets printed
+		formatter.debug_struct("Policy")
+			.field("remove", &self.remove)
+			.field("hash",   &self.hash)
+			.field("keep",   &self.keep)
+			.field("key",    &self.key.as_ref().map(|_| "<secret>"))
+			.finish()
+	}
+}
+
+impl
+Policy
+{
+	/// Constructs a new policy that doesn't change anything
+	pub fn
+	new()
+	-> Policy
+	{
+		Policy::default()
+	}
+
+	/// Always removes the given tag
+	pub fn
+	remove
+	(
+		mut self,
+		tag: &ExifTag
+	)
+	-> Policy
+	{
+		self.remove.push(tag_id(tag));
+		self
+	}
+
+	/// Replaces the value of the given tag by its digest
+	pub fn
+	hash
+	(
+		mut self,
+		tag: &ExifTag
+	)
+	-> Policy
+	{
+		self.hash.push(tag_id(tag));
+		self
+	}
+
+	/// Sets the secret key for replacing the values of the tags to hash by
+	/// keyed pseudonyms instead of plain digests. Keep the key the same for
+	/// all images that should get the same pseudonyms for the same values.
+	pub fn
+	pseudonymization_key
+	(
+		mut self,
+		key: &[u8]
+	)
+	-> Policy
+	{
+		self.key = Some(key.to_vec());
+		self
+	}
+
+	/// Replaces the values of the tags in `IDENTIFYING_TAGS` (serial numbers,
+	/// owner names and unique IDs) by keyed pseudonyms, so that images can
+	/// still be grouped by camera without exposing identities
+	pub fn
+	pseudonymize
+	(
+		mut self,
+		key: &[u8]
+	)
+	-> Policy
+	{
+		for name in IDENTIFYING_TAGS
+		{
+			self = self.hash(&ExifTag::from_name(name).unwrap());
+		}
+		return self.pseudonymization_key(key);
+	}
+
+	/// Adds the given tag to the allow-list
+	pub fn
+	keep
+	(
+		mut self,
+		tag: &ExifTag
+	)
+	-> Policy
+	{
+		self.keep.push(tag_id(tag));
+		self
+	}
+
+	/// Loads a policy from the file at the given path, which is parsed as
+	/// TOML if its extension is `toml` and as JSON otherwise
+	pub fn
+	from_path
+	(
+		path: &Path
+	)
+	-> Result<Policy, std::io::Error>
+	{
+		let text = std::fs::read_to_string(path)?;
+		match path.extension().and_then(|extension| extension.to_str())
+		{
+			Some(extension) if extension.eq_ignore_ascii_case("toml") => Policy::from_toml(&text),
+			_                                                          => Policy::from_json(&text),
+		}
+	}
+
+	/// Loads a policy from a JSON object with the optional members `remove`,
+	/// `hash` and `keep`, each being an array of tag names
+	pub fn
+	from_json
+	(
+		text: &str
+	)
+	-> Result<Policy, std::io::Error>
+	{
+		let members = match serde_json::from_str(text)
+		{
+			Ok(serde_json::Value::Object(members)) => members,
+			Ok(_)                                  => return io_error!(InvalidData, "Policy must be a JSON object!"),
+			Err(error)                             => return io_error!(InvalidData, format!("Invalid JSON policy: {}", error)),
+		};
+
+		let mut lists = Vec::new();
+		for (key, value) in members
+		{
+			let names = match value
+			{
+				serde_json::Value::Array(elements) => elements.into_iter()
+					.map(|element| match element
+					{
+						serde_json::Value::String(name) => Ok(name),
+						_                               => io_error!(InvalidData, format!("Policy list '{}' must only contain tag names!", key)),
+					})
+					.collect::<Result<Vec<String>, std::io::Error>>()?,
+				_ => return io_error!(InvalidData, format!("Policy key '{}' must be an array of tag names!", key)),
+			};
+			lists.push((key, names));
+		}
+		return Policy::from_lists(lists);
+	}
+
+	/// Loads a policy from a TOML document with the optional keys `remove`,
+	/// `hash` and `keep`, each being an array of tag names
+	pub fn
+	from_toml
+	(
+		text: &str
+	)
+	-> Result<Policy, std::io::Error>
+	{
+		let table = match text.parse::<toml::Table>()
+		{
+			Ok(table)  => table,
+			Err(error) => return io_error!(InvalidData, format!("Invalid TOML policy: {}", error)),
+		};
+
+		let mut lists = Vec::new();
+		for (key, value) in table
+		{
+			let names = match value
+			{
+				toml::Value::Array(elements) => elements.into_iter()
+					.map(|element| match element
+					{
+						toml::Value::String(name) => Ok(name),
+						_                         => io_error!(InvalidData, format!("Policy list '{}' must only contain tag names!", key)),
+					})
+					.collect::<Result<Vec<String>, std::io::Error>>()?,
+				_ => return io_error!(InvalidData, format!("Policy key '{}' must be an array of tag names!", key)),
+			};
+			lists.push((key, names));
+		}
+		return Policy::from_lists(lists);
+	}
+
+	/// Resolves the tag names of the loaded lists
+	fn
+	from_lists
+	(
+		lists: Vec<(String, Vec<String>)>
+	)
+	-> Result<Policy, std::io::Error>
+	{
+		let mut policy = Policy::new();
+		for (key, names) in lists
+		{
+			if !POLICY_KEYS.contains(&key.as_str())
+			{
+				return io_error!(InvalidData, format!("Unknown policy key '{}'!", key));
+			}
+
+			for name in names
+			{
+				let tag = match ExifTag::from_name(&name)
+				{
+					Ok(tag)    => tag,
+					Err(error) => return io_error!(InvalidData, format!("{} in policy list '{}'!", error, key)),
+				};
+
+				policy = match key.as_str()
+				{
+					"remove" => policy.remove(&tag),
+					"hash"   => policy.hash(&tag),
+					_        => policy.keep(&tag),
+				};
+			}
+		}
+		return Ok(policy);
+	}
+
+	/// Checks whether the policy doesn't change anything
+	pub fn
+	is_empty
+	(
+		&self
+	)
+	-> bool
+	{
+		self.remove.is_empty() && self.hash.is_empty() && self.keep.is_empty()
+	}
+
+	/// Applies the policy to a single tag, returning `None` if the tag gets
+	/// removed
+	pub(crate) fn
+	apply_to_tag
+	(
+		&self,
+		tag:    ExifTag,
+		endian: &Endian
+	)
+	-> Option<ExifTag>
+	{
+		let id = tag_id(&tag);
+		if self.remove.contains(&id)
+		{
+			return None;
+		}
+		if self.hash.contains(&id)
+		{
+			return hash_tag(&tag, endian, self.key.as_deref());
+		}
+		if !self.keep.is_empty() && !self.keep.contains(&id) && tag.is_offset_tag().is_none()
+		{
+			return None;
+		}
+		return Some(tag);
+	}
+}
+
+/// Replaces the value of a string or binary tag by its digest, or by its
+/// HMAC if there is a key
+fn
+hash_tag
+(
+	tag:    &ExifTag,
+	endian: &Endian,
+	key:    Option<&[u8]>
+)
+-> Option<ExifTag>
+{
+	let digest = |data: &[u8]| -> Vec<u8> {
+		match key
+		{
+			Some(key) => Hmac::<Sha256>::new_from_slice(key)
+				.expect("HMAC accepts keys of any length")
+				.chain_update(data)
+				.finalize()
+				.into_bytes()
+				.to_vec(),
+			None      => Sha256::digest(data).to_vec(),
+		}
+	};
+	let prefix = if key.is_some() { HMAC_PREFIX } else { HASH_PREFIX };
+
+	let raw_data = tag.value_as_u8_vec(endian);
+	let hashed_data = match tag.format()
+	{
+		ExifTagFormat::STRING => {
+			let value = String::from_utf8_lossy(&raw_data);
+			let hex_digits = digest(value.trim_end_matches(['\0', ' ']).as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect::<String>();
+			format!("{}{}", prefix, hex_digits).into_bytes()
+		},
+		ExifTagFormat::UNDEF => digest(&raw_data),
+		_                    => return None,
+	};
+	return ExifTag::from_u16_with_data(tag.as_u16(), &tag.format(), &hashed_data, endian, &tag.get_group()).ok();
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::endian::Endian;
+	use crate::exif_tag::ExifTag;
+	use crate::policy::Policy;
+
+	#[test]
+	fn
+	load_policies()
+	{
+		let expected = Policy::new()
+			.remove(&ExifTag::OwnerName(String::new()))
+			.hash(&ExifTag::SerialNumber(String::new()))
+			.keep(&ExifTag::Make(String::new()))
+			.keep(&ExifTag::Model(String::new()));
+
+		let json = r#"{ "remove": ["OwnerName"], "hash": ["serial number"], "keep": ["Make", "Camera"] }"#;
+		assert_eq!(Policy::from_json(json).unwrap(), expected);
+
+		let toml = "# Privacy rules\nremove = [\"OwnerName\"] # no names\nhash = ['serial number']\n\nkeep = [\n\t\"Make\",\n\t\"Camera\", # alias of Model\n]\n";
+		assert_eq!(Policy::from_toml(toml).unwrap(), expected);
+
+		assert!(Policy::from_toml("").unwrap().is_empty());
+		for invalid in ["remove = [\"NoSuchTag\"]", "delete = []", "remove = \"Make\"", "remove = [\"Make\"", "[policy]"]
+		{
+			assert!(Policy::from_toml(invalid).is_err(), "{}", invalid);
+		}
+		for invalid in [r#"{"remove": ["NoSuchTag"]}"#, r#"{"delete": []}"#, r#"{"remove": [1]}"#, "[]"]
+		{
+			assert!(Policy::from_json(invalid).is_err(), "{}", invalid);
+		}
+	}
+
+	#[test]
+	fn
+	hash_tags()
+	{
+		let policy = Policy::new()
+			.hash(&ExifTag::SerialNumber(String::new()))
+			.hash(&ExifTag::MakerNote(Vec::new()))
+			.hash(&ExifTag::ISO(Vec::new()));
+
+		assert_eq!(
+			policy.apply_to_tag(ExifTag::SerialNumber("abc ".to_string()), &Endian::Little),
+			Some(ExifTag::SerialNumber("sha256:ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string()))
+		);
+		match policy.apply_to_tag(ExifTag::MakerNote(vec![0x61, 0x62, 0x63]), &Endian::Little)
+		{
+			Some(ExifTag::MakerNote(digest)) => assert_eq!(digest[..4], [0xba, 0x78, 0x16, 0xbf]),
+			other                            => panic!("{:?}", other),
+		}
+		assert_eq!(policy.apply_to_tag(ExifTag::ISO(vec![100]), &Endian::Little), None);
+	}
+
+	#[test]
+	fn
+	pseudonymize_tags()
+	{
+		let policy = Policy::new().pseudonymize(b"Jefe");
+		assert!(!format!("{:?}", policy).contains("Jefe"));
+
+		assert_eq!(
+			policy.apply_to_tag(ExifTag::OwnerName("what do ya want for nothing?".to_string()), &Endian::Little),
+			Some(ExifTag::OwnerName("hmac-sha256:5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843".to_string()))
+		);
+		assert_eq!(
+			policy.apply_to_tag(ExifTag::Artist("Jane Doe".to_string()), &Endian::Little),
+			Some(ExifTag::Artist("Jane Doe".to_string()))
+		);
+	}
+}
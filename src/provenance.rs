@@ -0,0 +1,616 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Reading fields that may be stored in the EXIF data, the IPTC-IIM data and
+//! the XMP packet of an image at the same time, together with the source
+//! that supplied the returned value:
+//!
+//! | Getter        | EXIF               | IPTC-IIM                              | XMP                     |
+//! |---------------|--------------------|---------------------------------------|-------------------------|
+//! | `description` | `ImageDescription` | 2:120 Caption/Abstract                | `dc:description`        |
+//! | `creator`     | `Artist`           | 2:80 By-line                          | `dc:creator`            |
+//! | `copyright`   | `Copyright`        | 2:116 Copyright Notice                | `dc:rights`             |
+//! | `keywords`    | -                  | 2:25 Keywords                         | `dc:subject`            |
+//! | `date_taken`  | `DateTimeOriginal` | 2:55 Date Created + 2:60 Time Created | `photoshop:DateCreated` |
+//!
+//! If the sources disagree, `SourcePriority::Mwg` picks the value following
+//! the reconciliation rules of the Metadata Working Group (MWG):
+//! - A value stored in the EXIF data is preferred over the others
+//! - Photoshop stores a MD5 digest of the IPTC data when writing it together
+//!   with the XMP packet. If the digest doesn't match the IPTC data, it was
+//!   changed by a tool that doesn't know about XMP and is preferred over the
+//!   XMP packet. Otherwise (or if there is no digest) the XMP packet is
+//!   preferred, and the IPTC data only used if the packet lacks the field.
+//!
+//! `SourcePriority::Fixed` uses the given order instead, ignoring sources
+//! that are not listed.
+//!
+//! IPTC data is only read from JPEG files, XMP packets from JPEG, PNG and
+//! GIF files. For `Artist`, multiple creators separated by semicolons are
+//! split as recommended by the MWG. The XMP description and copyright are
+//! language alternatives (see `LangAlt`), which `description` and `copyright`
+//! return the default value of. `localized_description` and
+//! `localized_copyright` return all of them, with the values from EXIF and
+//! IPTC as default value.
+//!
+//! # Examples
+//! ```no_run
+//! use little_exif::provenance::ReconciledMetadata;
+//! use little_exif::provenance::SourcePriority;
+//!
+//! let file_buffer = std::fs::read("image.jpg").unwrap();
+//! let metadata = ReconciledMetadata::read_from_vec(&file_buffer, SourcePriority::Mwg).unwrap();
+//! if let Some(description) = metadata.description()
+//! {
+//!     println!("{} (from {:?})", description.value, description.source);
+//! }
+//! ```
+
+use std::path::Path;
+
+use md5::Digest;
+use md5::Md5;
+
+use crate::exif_date::DateCoercion;
+use crate::exif_date::ExifDate;
+use crate::exif_tag::ExifTag;
+use crate::filetype::FileExtension;
+use crate::general_file_io::io_error;
+use crate::iptc;
+use crate::jpg;
+use crate::metadata::Metadata;
+use crate::xmp;
+use crate::xmp::LangAlt;
+use crate::xmp::Namespace;
+use crate::xmp::XmpValue;
+use crate::xmp::DEFAULT_LANGUAGE;
+
+const DC_NAMESPACE:        Namespace = ("dc",        "http://purl.org/dc/elements/1.1/");
+const PHOTOSHOP_NAMESPACE: Namespace = ("photoshop", "http://ns.adobe.com/photoshop/1.0/");
+
+// IIM datasets of the fields
+const KEYWORDS:         (u8, u8) = (2, 25);
+const DATE_CREATED:     (u8, u8) = (2, 55);
+const TIME_CREATED:     (u8, u8) = (2, 60);
+const BY_LINE:          (u8, u8) = (2, 80);
+const COPYRIGHT_NOTICE: (u8, u8) = (2, 116);
+const CAPTION_ABSTRACT: (u8, u8) = (2, 120);
+
+/// The kinds of metadata a value can be read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum
+MetadataSource
+{
+	Exif,
+	Iptc,
+	Xmp,
+}
+
+/// A value together with the source it was read from
+#[derive(Debug, Clone, PartialEq)]
+pub struct
+Sourced<T>
+{
+	pub value:  T,
+	pub source: MetadataSource,
+}
+
+/// How to choose between the sources, see the module documentation
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum
+SourcePriority
+{
+	/// Follow the reconciliation rules of the MWG
+	#[default]
+	Mwg,
+
+	/// Use the first of the given sources that stores the field, ignoring
+	/// the sources that are not listed
+	Fixed(Vec<MetadataSource>),
+}
+
+/// The values of the fields as stored by a single source
+#[derive(Debug, Clone, Default)]
+struct
+Fields
+{
+	description: Option<LangAlt>,
+	creator:     Option<Vec<String>>,
+	copyright:   Option<LangAlt>,
+	keywords:    Option<Vec<String>>,
+	date_taken:  Option<ExifDate>,
+}
+
+/// The fields of an image read from all sources, see the module
+/// documentation
+#[derive(Debug, Clone)]
+pub struct
+ReconciledMetadata
+{
+	priority:     SourcePriority,
+	iptc_in_sync: Option<bool>,
+	exif:         Fields,
+	iptc:         Fields,
+	xmp:          Fields,
+}
+
+/// Turns empty values into `None`
+fn
+non_empty
+(
+	value: String
+)
+-> Option<String>
+{
+	let value = value.trim().to_string();
+	return match value.is_empty()
+	{
+		true  => None,
+		false => Some(value),
+	};
+}
+
+/// Turns a value of unknown language into a language alternative
+fn
+default_language
+(
+	value: String
+)
+-> LangAlt
+{
+	LangAlt::new().with(DEFAULT_LANGUAGE, &value)
+}
+
+/// Turns lists without any non-empty values into `None`
+fn
+non_empty_list
+(
+	values: Vec<String>
+)
+-> Option<Vec<String>>
+{
+	let values = values.into_iter().filter_map(non_empty).collect::<Vec<String>>();
+	return match values.is_empty()
+	{
+		true  => None,
+		false => Some(values),
+	};
+}
+
+/// Reads the fields from the EXIF data
+fn
+read_exif_fields
+(
+	metadata: &Metadata
+)
+-> Fields
+{
+	let get_string = |tag: ExifTag| metadata.get_string_value(&tag).and_then(non_empty);
+
+	return Fields
+	{
+		description: get_string(ExifTag::ImageDescription(String::new())).map(default_language),
+		creator:     get_string(ExifTag::Artist(String::new()))
+			.and_then(|artist| non_empty_list(artist.split(';').map(str::to_string).collect())),
+		copyright:   get_string(ExifTag::Copyright(String::new())).map(default_language),
+		keywords:    None,
+		date_taken:  get_string(ExifTag::DateTimeOriginal(String::new()))
+			.and_then(|date| ExifDate::parse(&date, DateCoercion::Lenient)),
+	};
+}
+
+/// Reads the fields from the IIM datasets
+fn
+read_iptc_fields
+(
+	datasets: &[iptc::Dataset]
+)
+-> Fields
+{
+	let get_all = |(record, number): (u8, u8)| datasets.iter()
+		.filter(|dataset| dataset.record == record && dataset.number == number)
+		.map(|dataset| String::from_utf8_lossy(&dataset.data).to_string())
+		.collect::<Vec<String>>();
+	let get = |id: (u8, u8)| get_all(id).into_iter().next().and_then(non_empty);
+
+	// The date is stored as CCYYMMDD, the time as HHMMSS±HHMM
+	let date_taken = get(DATE_CREATED)
+		.filter(|date| date.len() == 8 && date.bytes().all(|byte| byte.is_ascii_digit()))
+		.and_then(|date| {
+			let time = get(TIME_CREATED)
+				.filter(|time| time.len() >= 6 && time.bytes().take(6).all(|byte| byte.is_ascii_digit()))
+				.map(|time| format!(" {}:{}:{}", &time[0..2], &time[2..4], &time[4..6]))
+				.unwrap_or_default();
+			ExifDate::parse(&format!("{}:{}:{}{}", &date[0..4], &date[4..6], &date[6..8], time), DateCoercion::Lenient)
+		});
+
+	return Fields
+	{
+		description: get(CAPTION_ABSTRACT).map(default_language),
+		creator:     non_empty_list(get_all(BY_LINE)),
+		copyright:   get(COPYRIGHT_NOTICE).map(default_language),
+		keywords:    non_empty_list(get_all(KEYWORDS)),
+		date_taken,
+	};
+}
+
+/// Reads the fields from the XMP packet
+fn
+read_xmp_fields
+(
+	packet: &str
+)
+-> Fields
+{
+	// Other values than language alternatives are taken as default value
+	let get_localized = |name: &str| {
+		let localized = match xmp::get_value(packet, DC_NAMESPACE, name)?
+		{
+			XmpValue::LangAlt(localized) => localized,
+			value                        => default_language(value.items().into_iter().next()?),
+		};
+		let mut non_empty_localized = LangAlt::new();
+		for (language, value) in localized.alternatives()
+		{
+			if !value.trim().is_empty()
+			{
+				non_empty_localized.set(language, value.trim());
+			}
+		}
+		return match non_empty_localized.is_empty()
+		{
+			true  => None,
+			false => Some(non_empty_localized),
+		};
+	};
+
+	return Fields
+	{
+		description: get_localized("description"),
+		creator:     xmp::get_array_items(packet, DC_NAMESPACE, "creator").and_then(non_empty_list),
+		copyright:   get_localized("rights"),
+		keywords:    xmp::get_array_items(packet, DC_NAMESPACE, "subject").and_then(non_empty_list),
+		date_taken:  xmp::get_property(packet, PHOTOSHOP_NAMESPACE, "DateCreated")
+			.and_then(|date| ExifDate::parse(&date, DateCoercion::Lenient)),
+	};
+}
+
+/// Reads the IIM datasets of the JPEG image and checks them against the
+/// digest stored by Photoshop. The latter is `None` if there is no digest.
+fn
+read_iptc
+(
+	file_buffer: &[u8]
+)
+-> Result<(Vec<iptc::Dataset>, Option<bool>), std::io::Error>
+{
+	let resources = match jpg::read_photoshop_resources(file_buffer)?
+	{
+		Some(resources) => iptc::decode_resources(&resources)?,
+		None            => return Ok((Vec::new(), None)),
+	};
+
+	let iim = match resources.iter().find(|resource| resource.id == iptc::IPTC_RESOURCE_ID)
+	{
+		Some(resource) => &resource.data,
+		None           => return Ok((Vec::new(), None)),
+	};
+	let in_sync = resources.iter()
+		.find(|resource| resource.id == iptc::IPTC_DIGEST_RESOURCE_ID)
+		.map(|digest| digest.data == Md5::digest(iim).as_slice());
+
+	return Ok((iptc::decode_iim(iim)?, in_sync));
+}
+
+/// Gets the default value of the language alternative, which is never empty
+fn
+default_value
+(
+	localized: Sourced<LangAlt>
+)
+-> Sourced<String>
+{
+	Sourced
+	{
+		value:  localized.value.default_value().unwrap_or_default().to_string(),
+		source: localized.source,
+	}
+}
+
+impl
+ReconciledMetadata
+{
+	/// Reads the fields of the image from all of its sources. Sources the
+	/// file type doesn't support are treated as empty.
+	/// - If the file type is unknown, an error of kind `Unsupported` gets
+	///   returned.
+	pub fn
+	read_from_vec
+	(
+		file_buffer: &Vec<u8>,
+		priority:    SourcePriority
+	)
+	-> Result<ReconciledMetadata, std::io::Error>
+	{
+		let file_type = match FileExtension::from_signature(file_buffer)
+		{
+			Some(file_type) => file_type,
+			None            => return io_error!(Unsupported, "Unsupported file type!"),
+		};
+
+		let exif = read_exif_fields(&Metadata::new_from_vec(file_buffer, file_type)?);
+
+		let (datasets, iptc_in_sync) = match file_type
+		{
+			FileExtension::JPEG => read_iptc(file_buffer)?,
+			_                   => (Vec::new(), None),
+		};
+
+		let packet = match file_type.capabilities().xmp
+		{
+			true  => xmp::read_xmp_from_vec(file_buffer)?,
+			false => None,
+		};
+
+		return Ok(ReconciledMetadata
+		{
+			priority,
+			iptc_in_sync,
+			exif,
+			iptc: read_iptc_fields(&datasets),
+			xmp:  packet.as_deref().map(read_xmp_fields).unwrap_or_default(),
+		});
+	}
+
+	/// Reads the fields of the image at the given path, see `read_from_vec`
+	pub fn
+	read_from_path
+	(
+		path:     &Path,
+		priority: SourcePriority
+	)
+	-> Result<ReconciledMetadata, std::io::Error>
+	{
+		Self::read_from_vec(&std::fs::read(path)?, priority)
+	}
+
+	/// Checks whether the IPTC data matches the digest stored by Photoshop.
+	/// Returns `None` if there is no digest or no IPTC data.
+	pub fn
+	iptc_in_sync
+	(
+		&self
+	)
+	-> Option<bool>
+	{
+		self.iptc_in_sync
+	}
+
+	/// Gets the sources in the order they are checked for each field
+	pub fn
+	source_order
+	(
+		&self
+	)
+	-> Vec<MetadataSource>
+	{
+		match (&self.priority, self.iptc_in_sync)
+		{
+			(SourcePriority::Fixed(order), _)  => order.clone(),
+			(SourcePriority::Mwg, Some(false)) => vec![MetadataSource::Exif, MetadataSource::Iptc, MetadataSource::Xmp],
+			(SourcePriority::Mwg, _)           => vec![MetadataSource::Exif, MetadataSource::Xmp, MetadataSource::Iptc],
+		}
+	}
+
+	/// Gets the field from the first source in `source_order` storing it
+	fn
+	pick
+	<T: Clone>
+	(
+		&self,
+		get: impl Fn(&Fields) -> &Option<T>
+	)
+	-> Option<Sourced<T>>
+	{
+		self.source_order().into_iter().find_map(|source| {
+			let fields = match source
+			{
+				MetadataSource::Exif => &self.exif,
+				MetadataSource::Iptc => &self.iptc,
+				MetadataSource::Xmp  => &self.xmp,
+			};
+			get(fields).clone().map(|value| Sourced { value, source })
+		})
+	}
+
+	/// Gets the description (or caption) of the image, in case of multiple
+	/// languages the default one
+	pub fn
+	description
+	(
+		&self
+	)
+	-> Option<Sourced<String>>
+	{
+		self.localized_description().map(default_value)
+	}
+
+	/// Gets the description (or caption) of the image in all languages
+	pub fn
+	localized_description
+	(
+		&self
+	)
+	-> Option<Sourced<LangAlt>>
+	{
+		self.pick(|fields| &fields.description)
+	}
+
+	/// Gets the creators of the image
+	pub fn
+	creator
+	(
+		&self
+	)
+	-> Option<Sourced<Vec<String>>>
+	{
+		self.pick(|fields| &fields.creator)
+	}
+
+	/// Gets the copyright notice of the image, in case of multiple languages
+	/// the default one
+	pub fn
+	copyright
+	(
+		&self
+	)
+	-> Option<Sourced<String>>
+	{
+		self.localized_copyright().map(default_value)
+	}
+
+	/// Gets the copyright notice of the image in all languages
+	pub fn
+	localized_copyright
+	(
+		&self
+	)
+	-> Option<Sourced<LangAlt>>
+	{
+		self.pick(|fields| &fields.copyright)
+	}
+
+	/// Gets the keywords of the image, which are not stored in the EXIF data
+	pub fn
+	keywords
+	(
+		&self
+	)
+	-> Option<Sourced<Vec<String>>>
+	{
+		self.pick(|fields| &fields.keywords)
+	}
+
+	/// Gets the date the image was taken. Time zone offsets are ignored.
+	pub fn
+	date_taken
+	(
+		&self
+	)
+	-> Option<Sourced<ExifDate>>
+	{
+		self.pick(|fields| &fields.date_taken)
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::filetype::FileExtension;
+	use crate::iptc;
+	use crate::iptc::Dataset;
+	use crate::jpg;
+	use crate::metadata::Metadata;
+	use crate::exif_tag::ExifTag;
+	use crate::provenance::*;
+
+	/// Replaces the IIM data of the JPEG image, setting the digest to a
+	/// matching (`Some(true)`) or outdated one (`Some(false)`) or removing it
+	fn
+	set_iim
+	(
+		file_buffer:     &mut Vec<u8>,
+		datasets:        &[Dataset],
+		matching_digest: Option<bool>
+	)
+	-> Result<(), std::io::Error>
+	{
+		let mut resources = iptc::decode_resources(&jpg::read_photoshop_resources(file_buffer)?.unwrap())?;
+		let iim = iptc::encode_iim(datasets);
+		for resource in resources.iter_mut()
+		{
+			match resource.id
+			{
+				iptc::IPTC_RESOURCE_ID        => resource.data = iim.clone(),
+				iptc::IPTC_DIGEST_RESOURCE_ID => resource.data = match matching_digest
+				{
+					Some(true) => Md5::digest(&iim).to_vec(),
+					_          => vec![0; 16],
+				},
+				_                             => (),
+			}
+		}
+		if matching_digest.is_none()
+		{
+			resources.retain(|resource| resource.id != iptc::IPTC_DIGEST_RESOURCE_ID);
+		}
+		return jpg::write_photoshop_resources(file_buffer, &iptc::encode_resources(&resources));
+	}
+
+	#[test]
+	fn
+	mwg_reconciliation()
+	-> Result<(), std::io::Error>
+	{
+		// The sample has empty IIM data with a matching digest
+		let mut file_buffer = std::fs::read("tests/sample2.jpg")?;
+		let metadata = ReconciledMetadata::read_from_vec(&file_buffer, SourcePriority::Mwg)?;
+		assert_eq!(metadata.iptc_in_sync(), Some(true));
+		assert_eq!(metadata.description(), None);
+
+		let packet = concat!(
+			"<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"><rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">",
+			"<rdf:Description rdf:about=\"\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\" xmlns:photoshop=\"http://ns.adobe.com/photoshop/1.0/\" photoshop:DateCreated=\"2024-05-06T07:08:09+02:00\">",
+			"<dc:description><rdf:Alt><rdf:li xml:lang=\"x-default\">From XMP</rdf:li><rdf:li xml:lang=\"de\">Aus XMP</rdf:li></rdf:Alt></dc:description>",
+			"<dc:subject><rdf:Bag><rdf:li>Fish</rdf:li><rdf:li>Chips</rdf:li></rdf:Bag></dc:subject>",
+			"</rdf:Description></rdf:RDF></x:xmpmeta>"
+		);
+		xmp::write_xmp_to_vec(&mut file_buffer, packet)?;
+		let datasets = [
+			Dataset { record: 2, number: 25,  data: b"Legacy".to_vec() },
+			Dataset { record: 2, number: 55,  data: b"20230102".to_vec() },
+			Dataset { record: 2, number: 80,  data: b"Jane Doe".to_vec() },
+			Dataset { record: 2, number: 120, data: b"From IPTC".to_vec() },
+		];
+
+		// In sync: XMP first, IPTC only for fields the packet lacks
+		set_iim(&mut file_buffer, &datasets, Some(true))?;
+		let metadata = ReconciledMetadata::read_from_vec(&file_buffer, SourcePriority::Mwg)?;
+		assert_eq!(metadata.iptc_in_sync(), Some(true));
+		assert_eq!(metadata.description(), Some(Sourced { value: "From XMP".to_string(), source: MetadataSource::Xmp }));
+		assert_eq!(metadata.localized_description().unwrap().value.get("de"), Some("Aus XMP"));
+		assert_eq!(metadata.keywords().unwrap().value, vec!["Fish".to_string(), "Chips".to_string()]);
+		assert_eq!(metadata.creator(), Some(Sourced { value: vec!["Jane Doe".to_string()], source: MetadataSource::Iptc }));
+		assert_eq!(metadata.date_taken().unwrap().value.to_exif_string(), "2024:05:06 07:08:09");
+
+		// Changed by a tool unaware of XMP: IPTC first
+		set_iim(&mut file_buffer, &datasets, Some(false))?;
+		let metadata = ReconciledMetadata::read_from_vec(&file_buffer, SourcePriority::Mwg)?;
+		assert_eq!(metadata.iptc_in_sync(), Some(false));
+		assert_eq!(metadata.description(), Some(Sourced { value: "From IPTC".to_string(), source: MetadataSource::Iptc }));
+		assert_eq!(metadata.localized_description().unwrap().value.alternatives().len(), 1);
+		assert_eq!(metadata.keywords().unwrap().value, vec!["Legacy".to_string()]);
+		assert_eq!(metadata.date_taken().unwrap().value.to_exif_string(), "2023:01:02 00:00:00");
+
+		// No digest: XMP first
+		set_iim(&mut file_buffer, &datasets, None)?;
+		let metadata = ReconciledMetadata::read_from_vec(&file_buffer, SourcePriority::Mwg)?;
+		assert_eq!(metadata.iptc_in_sync(), None);
+		assert_eq!(metadata.description().unwrap().source, MetadataSource::Xmp);
+
+		// EXIF always comes first
+		let mut exif = Metadata::new_from_vec(&file_buffer, FileExtension::JPEG)?;
+		exif.set_tag(ExifTag::ImageDescription("From EXIF".to_string()));
+		exif.set_tag(ExifTag::Artist("Jane Doe; John Doe".to_string()));
+		exif.write_to_vec(&mut file_buffer, FileExtension::JPEG)?;
+		let metadata = ReconciledMetadata::read_from_vec(&file_buffer, SourcePriority::Mwg)?;
+		assert_eq!(metadata.description(), Some(Sourced { value: "From EXIF".to_string(), source: MetadataSource::Exif }));
+		assert_eq!(metadata.creator().unwrap().value, vec!["Jane Doe".to_string(), "John Doe".to_string()]);
+
+		// Unless the priority says otherwise
+		let metadata = ReconciledMetadata::read_from_vec(&file_buffer, SourcePriority::Fixed(vec![MetadataSource::Iptc]))?;
+		assert_eq!(metadata.description(), Some(Sourced { value: "From IPTC".to_string(), source: MetadataSource::Iptc }));
+		assert_eq!(metadata.copyright(), None);
+		Ok(())
+	}
+}
@@ -0,0 +1,129 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Fujifilm RAF raw files start with a header of their own, followed by a
+//! JPEG preview, the CFA header and the raw data. The header consists of
+//! - the magic `FUJIFILMCCD-RAW ` (16 bytes)
+//! - the format version, e.g. `0201` (4 bytes)
+//! - the camera ID (8 bytes) and name (32 bytes, NUL padded)
+//! - the directory version (4 bytes) and 20 unknown bytes
+//! - the offset and length of the JPEG preview, the CFA header and the raw
+//!   data (big endian, 4 bytes each)
+//!
+//! The EXIF data of the camera is stored in the JPEG preview, so reading
+//! works just like for JPEG files (see `jpg`). Only reading is supported
+//! (see `raw_file`), also as the preview can't change its size without
+//! moving the data following it.
+
+use std::io::Cursor;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::path::Path;
+
+use crate::errors::MissingExif;
+use crate::errors::ParseErrorContext;
+use crate::general_file_io::*;
+use crate::jpg;
+use crate::vfs::FileSystem;
+use crate::vfs::ReadSeek;
+
+const RAF_MAGIC:            &[u8; 16] = b"FUJIFILMCCD-RAW ";
+const JPEG_OFFSET_POSITION: usize     = 0x54;
+const JPEG_LENGTH_POSITION: usize     = 0x58;
+const HEADER_LENGTH:        usize     = 0x5c;
+
+/// Checks whether the data starts with the magic of a RAF file
+pub(crate) fn
+is_raf
+(
+	file_buffer: &[u8]
+)
+-> bool
+{
+	return file_buffer.starts_with(RAF_MAGIC);
+}
+
+/// Opens the RAF file at the given path for reading
+fn
+open_file
+(
+	file_system: &dyn FileSystem,
+	path:        &Path
+)
+-> Result<Box<dyn ReadSeek>, std::io::Error>
+{
+	if !file_system.exists(path)
+	{
+		return io_error!(NotFound, "Can't open RAF file - File does not exist!");
+	}
+
+	return file_system.open(path);
+}
+
+/// Reads the raw EXIF data from the JPEG preview embedded in the RAF file,
+/// see `jpg::read_metadata`
+pub(crate) fn
+read_metadata
+<T: Read + Seek>
+(
+	cursor:   &mut T,
+	warnings: &mut Vec<ParseErrorContext>
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	let _span = span!("parse", format = "RAF");
+
+	let mut header = [0u8; HEADER_LENGTH];
+	if !try_read_exact(cursor, &mut header)? || !header.starts_with(RAF_MAGIC)
+	{
+		return io_error!(InvalidData, "Can't open RAF file - Wrong signature!");
+	}
+
+	let jpeg_offset = u32::from_be_bytes(header[JPEG_OFFSET_POSITION..JPEG_OFFSET_POSITION + 4].try_into().unwrap());
+	let jpeg_length = u32::from_be_bytes(header[JPEG_LENGTH_POSITION..JPEG_LENGTH_POSITION + 4].try_into().unwrap());
+	if jpeg_length == 0
+	{
+		return MissingExif::error("No EXIF data found!");
+	}
+
+	// The length is only trusted as far as the file actually goes
+	let mut jpeg = Vec::new();
+	cursor.seek(SeekFrom::Start(jpeg_offset as u64))?;
+	cursor.take(jpeg_length as u64).read_to_end(&mut jpeg)?;
+	if jpeg.len() != jpeg_length as usize
+	{
+		return io_error!(InvalidData, "Embedded JPEG exceeds RAF file!");
+	}
+
+	return jpg::read_metadata(&mut Cursor::new(jpeg), warnings);
+}
+
+
+
+pub(crate) fn
+file_read_metadata
+(
+	file_system: &dyn FileSystem,
+	path:        &Path,
+	warnings:    &mut Vec<ParseErrorContext>
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	read_metadata(&mut open_file(file_system, path)?, warnings)
+}
+
+
+
+/// Provides the RAF specific encoding result as vector of bytes to be used
+/// by the user, which is the same as for JPEG files, as that's where the
+/// EXIF data is stored
+pub(crate) fn
+as_u8_vec
+(
+	general_encoded_metadata: &Vec<u8>
+)
+-> Vec<u8>
+{
+	jpg::as_u8_vec(general_encoded_metadata)
+}
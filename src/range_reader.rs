@@ -0,0 +1,657 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Reading metadata from remote files (e.g. images hosted on S3 or a web
+//! server) by fetching only the parts of the file that are needed, instead of
+//! downloading the whole object.
+//!
+//! A `RangeSource` fetches byte ranges of a file, e.g. using HTTP range
+//! requests. The `RangeReader` turns such a source into a reader that can be
+//! passed to `Metadata::new_from_seekable_reader`, fetching the file in
+//! chunks of a configurable size and keeping the fetched chunks, so that the
+//! many small reads of the decoders result in only a few requests.
+//!
+//! With the `http` feature, `HttpRangeSource` provides a source for plain
+//! HTTP URLs that is built on the standard library alone. For HTTPS (or for
+//! signing requests), implement `RangeSource` using the HTTP client of your
+//! choice.
+//!
+//! # Examples
+//! ```no_run
+//! # #[cfg(feature = "http")]
+//! # {
+//! use little_exif::filetype::FileExtension;
+//! use little_exif::metadata::Metadata;
+//! use little_exif::range_reader::HttpRangeSource;
+//! use little_exif::range_reader::RangeReader;
+//! use little_exif::read_options::ReadOptions;
+//!
+//! let source = HttpRangeSource::new("http://example.com/image.jpg").unwrap();
+//! let mut reader = RangeReader::new(source);
+//! let metadata = Metadata::new_from_seekable_reader(&mut reader, FileExtension::JPEG, &ReadOptions::new()).unwrap();
+//! println!("Fetched {} bytes", reader.fetched_bytes());
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+
+use crate::general_file_io::io_error;
+
+/// The default number of bytes fetched per request, see
+/// `RangeReader::chunk_size`
+pub const DEFAULT_CHUNK_SIZE: u64 = 16 * 1024;
+
+/// Something that provides byte ranges of a file, e.g. a remote file
+/// accessed via HTTP range requests
+pub trait
+RangeSource
+{
+	/// Gets the size of the file in bytes
+	fn
+	size
+	(
+		&mut self
+	)
+	-> Result<u64, std::io::Error>;
+
+	/// Fetches `length` bytes starting at `offset`. The range never exceeds
+	/// the size of the file
+	fn
+	fetch
+	(
+		&mut self,
+		offset: u64,
+		length: u64
+	)
+	-> Result<Vec<u8>, std::io::Error>;
+}
+
+/// Reads a file provided by a `RangeSource` in chunks, implementing `Read`
+/// and `Seek`. Every chunk is only fetched once.
+pub struct
+RangeReader<S: RangeSource>
+{
+	source:        S,
+	size:          Option<u64>,
+	position:      u64,
+	chunk_size:    u64,
+	chunks:        HashMap<u64, Vec<u8>>,                                       // Chunk number -> data
+	fetched_bytes: u64,
+	request_count: usize,
+}
+
+impl<S: RangeSource>
+RangeReader<S>
+{
+	/// Constructs a new reader for the given source, fetching chunks of
+	/// `DEFAULT_CHUNK_SIZE` bytes
+	pub fn
+	new
+	(
+		source: S
+	)
+	-> RangeReader<S>
+	{
+		RangeReader
+		{
+			source,
+			size:          None,
+			position:      0,
+			chunk_size:    DEFAULT_CHUNK_SIZE,
+			chunks:        HashMap::new(),
+			fetched_bytes: 0,
+			request_count: 0,
+		}
+	}
+
+	/// Sets the number of bytes fetched per request. Smaller chunks mean
+	/// fewer unneeded bytes, larger chunks fewer requests. Values below 1 are
+	/// treated as 1.
+	pub fn
+	chunk_size
+	(
+		mut self,
+		chunk_size: u64
+	)
+	-> RangeReader<S>
+	{
+		self.chunk_size = std::cmp::max(chunk_size, 1);
+		self.chunks.clear();
+		self
+	}
+
+	/// The number of bytes fetched from the source so far
+	pub fn
+	fetched_bytes
+	(
+		&self
+	)
+	-> u64
+	{
+		self.fetched_bytes
+	}
+
+	/// The number of requests sent to the source so far, not counting the
+	/// one for determining the size
+	pub fn
+	request_count
+	(
+		&self
+	)
+	-> usize
+	{
+		self.request_count
+	}
+
+	/// Gets the source back, e.g. for reusing its connection
+	pub fn
+	into_inner
+	(
+		self
+	)
+	-> S
+	{
+		self.source
+	}
+
+	fn
+	size
+	(
+		&mut self
+	)
+	-> Result<u64, std::io::Error>
+	{
+		if let Some(size) = self.size
+		{
+			return Ok(size);
+		}
+		let size = self.source.size()?;
+		self.size = Some(size);
+		return Ok(size);
+	}
+
+	/// Gets the chunk with the given number, fetching it if necessary
+	fn
+	chunk
+	(
+		&mut self,
+		chunk_number: u64
+	)
+	-> Result<&Vec<u8>, std::io::Error>
+	{
+		if !self.chunks.contains_key(&chunk_number)
+		{
+			let offset = chunk_number * self.chunk_size;
+			let length = std::cmp::min(self.chunk_size, self.size()?.saturating_sub(offset));
+			let data   = self.source.fetch(offset, length)?;
+			if data.len() as u64 != length
+			{
+				return io_error!(UnexpectedEof, format!("Expected {} bytes at offset {} but got {}!", length, offset, data.len()));
+			}
+
+			self.fetched_bytes += length;
+			self.request_count += 1;
+			self.chunks.insert(chunk_number, data);
+		}
+		return Ok(&self.chunks[&chunk_number]);
+	}
+}
+
+impl<S: RangeSource>
+Read
+for
+RangeReader<S>
+{
+	fn
+	read
+	(
+		&mut self,
+		buffer: &mut [u8]
+	)
+	-> Result<usize, std::io::Error>
+	{
+		if buffer.is_empty() || self.position >= self.size()?
+		{
+			return Ok(0);
+		}
+
+		let chunk_size   = self.chunk_size;
+		let chunk_number = self.position / chunk_size;
+		let chunk_offset = (self.position % chunk_size) as usize;
+		let chunk        = self.chunk(chunk_number)?;
+
+		let byte_count = std::cmp::min(buffer.len(), chunk.len() - chunk_offset);
+		buffer[..byte_count].copy_from_slice(&chunk[chunk_offset..chunk_offset + byte_count]);
+		self.position += byte_count as u64;
+		return Ok(byte_count);
+	}
+}
+
+impl<S: RangeSource>
+Seek
+for
+RangeReader<S>
+{
+	fn
+	seek
+	(
+		&mut self,
+		position: SeekFrom
+	)
+	-> Result<u64, std::io::Error>
+	{
+		let new_position = match position
+		{
+			SeekFrom::Start(offset)   => Some(offset),
+			SeekFrom::End(offset)     => self.size()?.checked_add_signed(offset),
+			SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+		};
+
+		match new_position
+		{
+			Some(new_position) => {
+				self.position = new_position;
+				Ok(new_position)
+			},
+			None => io_error!(InvalidInput, "Invalid seek to a negative or overflowing position!"),
+		}
+	}
+}
+
+/// The status code, the headers (with lowercase names) and the body of a
+/// HTTP response
+#[cfg(feature = "http")]
+type HttpResponse = (u16, Vec<(String, String)>, Vec<u8>);
+
+/// Fetches byte ranges of a file via plain HTTP (no TLS) range requests,
+/// using a new connection per request. Servers not supporting range requests
+/// are detected by their response, in which case the whole file gets
+/// downloaded once and the ranges are taken from it.
+#[cfg(feature = "http")]
+pub struct
+HttpRangeSource
+{
+	authority:  String,                                                         // Host and port as given in the URL
+	host:       String,
+	port:       u16,
+	path:       String,
+	headers:    Vec<(String, String)>,
+	size:       Option<u64>,
+	whole_file: Option<Vec<u8>>,                                                // If the server ignores ranges
+}
+
+#[cfg(feature = "http")]
+impl
+HttpRangeSource
+{
+	/// Constructs a new source for the given URL of the form
+	/// `http://host[:port]/path`.
+	/// - If the URL can't be parsed or doesn't use HTTP, an error gets
+	///   returned.
+	pub fn
+	new
+	(
+		url: &str
+	)
+	-> Result<HttpRangeSource, std::io::Error>
+	{
+		let without_scheme = match url.strip_prefix("http://")
+		{
+			Some(without_scheme) => without_scheme,
+			None                 => return io_error!(InvalidInput, "Only http:// URLs are supported, implement RangeSource for others!"),
+		};
+
+		let (authority, path) = match without_scheme.find('/')
+		{
+			Some(index) => (&without_scheme[..index], &without_scheme[index..]),
+			None        => (without_scheme, "/"),
+		};
+
+		// IPv6 addresses are enclosed in brackets, e.g. [::1]:8080
+		let (host, port) = match authority.strip_prefix('[').and_then(|rest| rest.split_once(']'))
+		{
+			Some((host, port)) => (host, port.strip_prefix(':')),
+			None               => match authority.split_once(':')
+			{
+				Some((host, port)) => (host, Some(port)),
+				None               => (authority, None),
+			},
+		};
+
+		let port = match port.map(|port| port.parse::<u16>())
+		{
+			None           => 80,
+			Some(Ok(port)) => port,
+			Some(Err(_))   => return io_error!(InvalidInput, format!("Invalid port in URL {}!", url)),
+		};
+
+		if host.is_empty()
+		{
+			return io_error!(InvalidInput, format!("No host in URL {}!", url));
+		}
+
+		return Ok(HttpRangeSource
+		{
+			authority:  authority.to_string(),
+			host:       host.to_string(),
+			port,
+			path:       path.to_string(),
+			headers:    Vec::new(),
+			size:       None,
+			whole_file: None,
+		});
+	}
+
+	/// Adds a header that gets sent with every request, e.g. for
+	/// authorization
+	pub fn
+	header
+	(
+		mut self,
+		name:  &str,
+		value: &str
+	)
+	-> HttpRangeSource
+	{
+		self.headers.push((name.to_string(), value.to_string()));
+		self
+	}
+
+	/// Sends a request for the given inclusive range and returns the status
+	/// code, the headers (with lowercase names) and the body of the response
+	fn
+	request
+	(
+		&self,
+		first_byte: u64,
+		last_byte:  u64
+	)
+	-> Result<HttpResponse, std::io::Error>
+	{
+		let mut request = format!(
+			"GET {} HTTP/1.1\r\nHost: {}\r\nRange: bytes={}-{}\r\nConnection: close\r\n",
+			self.path, self.authority, first_byte, last_byte
+		);
+		for (name, value) in &self.headers
+		{
+			request.push_str(&format!("{}: {}\r\n", name, value));
+		}
+		request.push_str("\r\n");
+
+		let mut stream = std::net::TcpStream::connect((self.host.as_str(), self.port))?;
+		std::io::Write::write_all(&mut stream, request.as_bytes())?;
+
+		let mut response = Vec::new();
+		stream.read_to_end(&mut response)?;
+		return parse_http_response(&response);
+	}
+}
+
+#[cfg(feature = "http")]
+impl
+RangeSource
+for
+HttpRangeSource
+{
+	fn
+	size
+	(
+		&mut self
+	)
+	-> Result<u64, std::io::Error>
+	{
+		if self.size.is_none()
+		{
+			self.fetch(0, 1)?;
+		}
+		return Ok(self.size.unwrap_or(0));
+	}
+
+	fn
+	fetch
+	(
+		&mut self,
+		offset: u64,
+		length: u64
+	)
+	-> Result<Vec<u8>, std::io::Error>
+	{
+		if length == 0
+		{
+			return Ok(Vec::new());
+		}
+
+		if self.whole_file.is_none()
+		{
+			let (status, headers, body) = self.request(offset, offset + length - 1)?;
+			match status
+			{
+				206 => {
+					let content_range = headers.iter()
+						.find(|(name, _)| name == "content-range")
+						.and_then(|(_, value)| value.rsplit_once('/'))
+						.and_then(|(_, size)| size.trim().parse::<u64>().ok());
+					if content_range.is_none()
+					{
+						return io_error!(InvalidData, "Response to range request lacks the size of the file!");
+					}
+					self.size = content_range;
+					return Ok(body);
+				},
+				200 => {
+					self.size = Some(body.len() as u64);
+					self.whole_file = Some(body);
+				},
+				416 => {
+					// The file is empty, so even the first byte is out of range
+					self.size = Some(0);
+					return Ok(Vec::new());
+				},
+				_ => return io_error!(Other, format!("HTTP request failed with status {}!", status)),
+			}
+		}
+
+		let whole_file = self.whole_file.as_ref().unwrap();
+		let start      = std::cmp::min(offset, whole_file.len() as u64) as usize;
+		let end        = std::cmp::min(offset.saturating_add(length), whole_file.len() as u64) as usize;
+		return Ok(whole_file[start..end].to_vec());
+	}
+}
+
+/// Splits a HTTP/1.1 response into the status code, the headers (with
+/// lowercase names) and the body, decoding chunked transfer encoding
+#[cfg(feature = "http")]
+fn
+parse_http_response
+(
+	response: &[u8]
+)
+-> Result<HttpResponse, std::io::Error>
+{
+	let header_end = match response.windows(4).position(|window| window == b"\r\n\r\n")
+	{
+		Some(header_end) => header_end,
+		None             => return io_error!(InvalidData, "Incomplete HTTP response!"),
+	};
+
+	let head = String::from_utf8_lossy(&response[..header_end]);
+	let mut lines = head.split("\r\n");
+
+	let status = lines.next()
+		.and_then(|status_line| status_line.split(' ').nth(1))
+		.and_then(|status| status.parse::<u16>().ok());
+	if status.is_none()
+	{
+		return io_error!(InvalidData, "Invalid HTTP status line!");
+	}
+
+	let headers = lines
+		.filter_map(|line| line.split_once(':'))
+		.map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+		.collect::<Vec<_>>();
+
+	let mut body = response[header_end + 4..].to_vec();
+	let chunked = headers.iter().any(|(name, value)| name == "transfer-encoding" && value.eq_ignore_ascii_case("chunked"));
+	if chunked
+	{
+		body = decode_chunked_body(&body)?;
+	}
+	else if let Some(content_length) = headers.iter()
+		.find(|(name, _)| name == "content-length")
+		.and_then(|(_, value)| value.parse::<usize>().ok())
+	{
+		if body.len() < content_length
+		{
+			return io_error!(UnexpectedEof, "HTTP response body is shorter than announced!");
+		}
+		body.truncate(content_length);
+	}
+
+	return Ok((status.unwrap(), headers, body));
+}
+
+/// Decodes a body sent using chunked transfer encoding
+#[cfg(feature = "http")]
+fn
+decode_chunked_body
+(
+	mut encoded: &[u8]
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	let mut body = Vec::new();
+	loop
+	{
+		let line_end = match encoded.windows(2).position(|window| window == b"\r\n")
+		{
+			Some(line_end) => line_end,
+			None           => return io_error!(InvalidData, "Invalid chunk in HTTP response!"),
+		};
+
+		// Chunk extensions after a ';' are ignored
+		let size_field = String::from_utf8_lossy(&encoded[..line_end]);
+		let size = match usize::from_str_radix(size_field.split(';').next().unwrap_or("").trim(), 16)
+		{
+			Ok(size) => size,
+			Err(_)   => return io_error!(InvalidData, "Invalid chunk size in HTTP response!"),
+		};
+
+		let data_start = line_end + 2;
+		if size == 0
+		{
+			return Ok(body);
+		}
+		if encoded.len() < data_start + size + 2
+		{
+			return io_error!(UnexpectedEof, "Incomplete chunk in HTTP response!");
+		}
+
+		body.extend_from_slice(&encoded[data_start..data_start + size]);
+		encoded = &encoded[data_start + size + 2..];
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use std::io::Read;
+	use std::io::Seek;
+	use std::io::SeekFrom;
+
+	use crate::range_reader::RangeReader;
+	use crate::range_reader::RangeSource;
+
+	struct
+	MemorySource
+	{
+		data: Vec<u8>,
+	}
+
+	impl
+	RangeSource
+	for
+	MemorySource
+	{
+		fn
+		size
+		(
+			&mut self
+		)
+		-> Result<u64, std::io::Error>
+		{
+			Ok(self.data.len() as u64)
+		}
+
+		fn
+		fetch
+		(
+			&mut self,
+			offset: u64,
+			length: u64
+		)
+		-> Result<Vec<u8>, std::io::Error>
+		{
+			Ok(self.data[offset as usize..(offset + length) as usize].to_vec())
+		}
+	}
+
+	#[test]
+	fn
+	read_and_seek_in_chunks()
+	-> Result<(), std::io::Error>
+	{
+		let data = (0..=255u8).cycle().take(1000).collect::<Vec<u8>>();
+		let mut reader = RangeReader::new(MemorySource { data: data.clone() }).chunk_size(64);
+
+		// Reads spanning chunk borders
+		let mut buffer = [0u8; 100];
+		reader.seek(SeekFrom::Start(60))?;
+		reader.read_exact(&mut buffer)?;
+		assert_eq!(&buffer[..], &data[60..160]);
+		assert_eq!(reader.request_count(), 3);
+
+		// Fetched chunks are reused
+		reader.seek(SeekFrom::Current(-50))?;
+		reader.read_exact(&mut buffer[..10])?;
+		assert_eq!(&buffer[..10], &data[110..120]);
+		assert_eq!(reader.request_count(), 3);
+
+		// The last chunk is shorter
+		reader.seek(SeekFrom::End(-10))?;
+		let mut rest = Vec::new();
+		reader.read_to_end(&mut rest)?;
+		assert_eq!(rest, &data[990..]);
+		assert_eq!(reader.fetched_bytes(), 3 * 64 + (1000 - 15 * 64));
+
+		assert!(reader.seek(SeekFrom::Current(-2000)).is_err());
+		reader.seek(SeekFrom::Start(2000))?;
+		assert_eq!(reader.read(&mut buffer)?, 0);
+
+		Ok(())
+	}
+
+	#[cfg(feature = "http")]
+	#[test]
+	fn
+	parse_http_responses()
+	-> Result<(), std::io::Error>
+	{
+		use crate::range_reader::parse_http_response;
+
+		let (status, headers, body) = parse_http_response(b"HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 0-3/100\r\nContent-Length: 4\r\n\r\nabcd")?;
+		assert_eq!(status, 206);
+		assert!(headers.contains(&("content-range".to_string(), "bytes 0-3/100".to_string())));
+		assert_eq!(body, b"abcd");
+
+		let (_, _, body) = parse_http_response(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3;x=y\r\nabc\r\n2\r\nde\r\n0\r\n\r\n")?;
+		assert_eq!(body, b"abcde");
+
+		assert!(parse_http_response(b"HTTP/1.1 206 Partial Content\r\nContent-Length: 10\r\n\r\nabcd").is_err());
+		assert!(parse_http_response(b"garbage").is_err());
+
+		Ok(())
+	}
+}
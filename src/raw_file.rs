@@ -0,0 +1,82 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Raw files of cameras, which can only be read. Writing and clearing fail
+//! with an error of kind `Unsupported`, as raw processors locate the raw
+//! data in ways little_exif can't check.
+//!
+//! Several raw formats are TIFF files with a signature of their own, which
+//! are read just like TIFF files (see `tiff`) once the signature has been
+//! checked. Their modules only provide the signature as `TiffRawFormat`.
+
+use std::io::Read;
+use std::io::Seek;
+use std::path::Path;
+
+use crate::filetype::FileExtension;
+use crate::general_file_io::*;
+use crate::tiff;
+use crate::vfs::FileSystem;
+
+/// A raw format based on TIFF, told apart from TIFF files by its signature
+pub(crate) struct
+TiffRawFormat
+{
+	pub(crate) file_type:        FileExtension,
+	pub(crate) signature_length: usize,                                         // Bytes at the start of the file needed by `is_format`
+	pub(crate) is_format:        fn(&[u8]) -> bool,
+}
+
+impl
+TiffRawFormat
+{
+	/// Reads the raw EXIF data from the file, see `tiff::read_metadata`
+	pub(crate) fn
+	read_metadata
+	<T: Read + Seek>
+	(
+		&self,
+		cursor: &mut T
+	)
+	-> Result<Vec<u8>, std::io::Error>
+	{
+		let _span = span!("parse", format = self.file_type.name());
+
+		let mut header = vec![0u8; self.signature_length];
+		if !try_read_exact(cursor, &mut header)? || !(self.is_format)(&header)
+		{
+			return io_error!(InvalidData, format!("Can't open {} file - Wrong signature!", self.file_type.name()));
+		}
+		cursor.rewind()?;
+
+		return tiff::read_metadata(cursor);
+	}
+
+	pub(crate) fn
+	file_read_metadata
+	(
+		&self,
+		file_system: &dyn FileSystem,
+		path:        &Path
+	)
+	-> Result<Vec<u8>, std::io::Error>
+	{
+		if !file_system.exists(path)
+		{
+			return io_error!(NotFound, format!("Can't open {} file - File does not exist!", self.file_type.name()));
+		}
+
+		return self.read_metadata(&mut file_system.open(path)?);
+	}
+}
+
+/// The error returned when trying to change the metadata of a raw file
+pub(crate) fn
+unsupported_write
+(
+	file_type: &FileExtension
+)
+-> Result<(), std::io::Error>
+{
+	io_error!(Unsupported, format!("Can't write {} file - Only reading is supported!", file_type.name()))
+}
@@ -0,0 +1,169 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+use crate::config::Config;
+
+/// The default limit for buffering streams, see
+/// `ReadOptions::max_stream_size` and `WriteOptions::max_stream_size`
+pub const DEFAULT_MAX_STREAM_SIZE: u64 = 256 * 1024 * 1024;
+
+/// How tags without an actual value (see `ExifTag::is_empty_value`), e.g. an
+/// `Artist` consisting of spaces only, are handled when reading and writing,
+/// see `ReadOptions::empty_values` and `WriteOptions::empty_values`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum
+EmptyValues
+{
+	/// Read and write the tags as they are
+	Keep,
+
+	/// Drop the tags silently
+	Skip,
+
+	/// Report the tags: When reading, every one of them results in a warning
+	/// (see `Metadata::warnings`) and thus in an error if reading is not
+	/// lenient. Writing fails with an error of kind `InvalidInput`.
+	Reject,
+}
+
+/// Options for fine-tuning how `Metadata::new_from_path_with_options` reads
+/// the metadata from a file. The options are set using chained calls,
+/// starting with the defaults provided by `ReadOptions::new()`, which can be
+/// changed using a `config::Config`.
+///
+/// # Examples
+/// ```no_run
+/// use little_exif::read_options::ReadOptions;
+///
+/// let options = ReadOptions::new().validate_crc(true);
+/// ```
+#[derive(Debug, Clone)]
+pub struct
+ReadOptions
+{
+	pub(crate) validate_crc:    bool,
+	pub(crate) max_stream_size: u64,
+	pub(crate) lenient:         bool,
+	pub(crate) empty_values:    EmptyValues,
+}
+
+impl
+ReadOptions
+{
+	/// Constructs the default options, taking the defaults set by the
+	/// current `Config` (see `Config::current`) into account:
+	/// - PNG: Only validate the CRC of chunks that may contain metadata
+	/// - Streams are buffered up to `DEFAULT_MAX_STREAM_SIZE` bytes
+	/// - Lenient reading (unless configured otherwise)
+	/// - Tags without an actual value are kept
+	pub fn
+	new
+	()
+	-> ReadOptions
+	{
+		ReadOptions::from_config(&Config::current())
+	}
+
+	/// Constructs the default options, taking the defaults from the given
+	/// config instead of the current one
+	pub fn
+	from_config
+	(
+		config: &Config
+	)
+	-> ReadOptions
+	{
+		ReadOptions
+		{
+			validate_crc:    false,
+			max_stream_size: DEFAULT_MAX_STREAM_SIZE,
+			lenient:         config.lenient,
+			empty_values:    EmptyValues::Keep,
+		}
+	}
+
+	/// Sets whether the CRC of every PNG chunk gets validated. If not, chunks
+	/// that can't contain metadata (e.g. the image data in IDAT chunks) are
+	/// skipped without reading them, which is a lot faster for large files.
+	/// Chunks that the metadata gets read from are always validated.
+	pub fn
+	validate_crc
+	(
+		mut self,
+		validate_crc: bool
+	)
+	-> ReadOptions
+	{
+		self.validate_crc = validate_crc;
+		self
+	}
+
+	/// Sets the maximum number of bytes that get buffered when reading from
+	/// a stream that isn't seekable, see `Metadata::new_from_reader`. Larger
+	/// streams are rejected with an error of kind `FileTooLarge`.
+	pub fn
+	max_stream_size
+	(
+		mut self,
+		max_stream_size: u64
+	)
+	-> ReadOptions
+	{
+		self.max_stream_size = max_stream_size;
+		self
+	}
+
+	/// Sets whether issues found while reading are tolerated: If lenient,
+	/// they are collected as warnings (see `Metadata::warnings`) and EXIF
+	/// data that can't be read or decoded results in an empty object.
+	/// Otherwise, the first issue is returned as an error of kind
+	/// `InvalidData`, containing the `ParseErrorContext` of the issue.
+	pub fn
+	lenient
+	(
+		mut self,
+		lenient: bool
+	)
+	-> ReadOptions
+	{
+		self.lenient = lenient;
+		self
+	}
+
+	/// Sets how tags without an actual value (e.g. zero-length entries or
+	/// strings consisting of whitespace only) are handled, see `EmptyValues`.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::read_options::EmptyValues;
+	/// use little_exif::read_options::ReadOptions;
+	///
+	/// // Fail on the first empty tag
+	/// let options = ReadOptions::new().empty_values(EmptyValues::Reject).lenient(false);
+	/// ```
+	pub fn
+	empty_values
+	(
+		mut self,
+		empty_values: EmptyValues
+	)
+	-> ReadOptions
+	{
+		self.empty_values = empty_values;
+		self
+	}
+}
+
+impl
+Default
+for
+ReadOptions
+{
+	fn
+	default
+	()
+	-> ReadOptions
+	{
+		ReadOptions::new()
+	}
+}
@@ -0,0 +1,296 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Reports of what has been removed when stripping metadata, e.g. for
+//! compliance logging in pipelines processing personal data.
+//!
+//! A `RedactionReport` lists every removed tag with its name, IFD, a short
+//! summary of its value and the number of bytes of the value, as well as the
+//! size of the removed EXIF data as a whole. It is obtained from the
+//! `UndoToken` returned by `Metadata::clear_metadata_with_undo` and
+//! `Metadata::file_clear_metadata_with_undo`, and can be written to a log as
+//! JSON using `to_json`.
+//!
+//! The value summaries are meant for telling what kind of information has
+//! been removed without keeping the information itself: Strings and numbers
+//! are cut off after `SUMMARY_LENGTH` characters and binary data is only
+//! summarized by its size.
+//!
+//! # Examples
+//! ```no_run
+//! use little_exif::metadata::Metadata;
+//!
+//! let undo_token = Metadata::file_clear_metadata_with_undo(std::path::Path::new("image.jpg")).unwrap();
+//! println!("{}", undo_token.redaction_report().to_json());
+//! ```
+
+use crate::exif_tag::ExifTag;
+use crate::exif_tag::ExifTagGroup;
+use crate::exif_tag_format::ExifTagFormat;
+use crate::export::format_value;
+use crate::filetype::FileExtension;
+use crate::json::json_string;
+
+/// The maximum number of characters of a value summary, not counting the
+/// ellipsis that indicates a cut off value
+pub const SUMMARY_LENGTH: usize = 24;
+
+/// A tag that has been removed
+#[derive(Debug, Clone, PartialEq)]
+pub struct
+RemovedTag
+{
+	name:       String,
+	tag_id:     u16,
+	group:      ExifTagGroup,
+	summary:    String,
+	byte_count: usize,
+}
+
+impl
+RemovedTag
+{
+	fn
+	new
+	(
+		tag: &ExifTag
+	)
+	-> RemovedTag
+	{
+		let byte_count = tag.value_as_u8_vec(&crate::endian::Endian::Little).len();
+		let summary = match tag.format()
+		{
+			ExifTagFormat::UNDEF => format!("{} bytes of binary data", byte_count),
+			_                    => summarize(&format_value(tag)),
+		};
+
+		RemovedTag
+		{
+			name:       tag.name(),
+			tag_id:     tag.as_u16(),
+			group:      tag.get_group(),
+			summary,
+			byte_count,
+		}
+	}
+
+	/// The name of the tag, e.g. `SerialNumber`
+	pub fn
+	name
+	(
+		&self
+	)
+	-> &str
+	{
+		&self.name
+	}
+
+	/// The ID of the tag, e.g. `0xa431` for `SerialNumber`
+	pub fn
+	tag_id
+	(
+		&self
+	)
+	-> u16
+	{
+		self.tag_id
+	}
+
+	/// The IFD the tag has been stored in
+	pub fn
+	group
+	(
+		&self
+	)
+	-> ExifTagGroup
+	{
+		self.group
+	}
+
+	/// A summary of the value (see the module documentation)
+	pub fn
+	summary
+	(
+		&self
+	)
+	-> &str
+	{
+		&self.summary
+	}
+
+	/// The number of bytes of the value
+	pub fn
+	byte_count
+	(
+		&self
+	)
+	-> usize
+	{
+		self.byte_count
+	}
+}
+
+/// Cuts off the formatted value after `SUMMARY_LENGTH` characters
+fn
+summarize
+(
+	value: &str
+)
+-> String
+{
+	if value.chars().count() <= SUMMARY_LENGTH
+	{
+		return value.to_string();
+	}
+	return format!("{}…", value.chars().take(SUMMARY_LENGTH).collect::<String>());
+}
+
+/// What has been removed by stripping the metadata of an image
+#[derive(Debug, Clone, PartialEq)]
+pub struct
+RedactionReport
+{
+	file_type:          FileExtension,
+	removed_tags:       Vec<RemovedTag>,
+	removed_data_bytes: usize,
+}
+
+impl
+RedactionReport
+{
+	pub(crate) fn
+	new
+	(
+		file_type:          FileExtension,
+		removed_tags:       &[ExifTag],
+		removed_data_bytes: usize
+	)
+	-> RedactionReport
+	{
+		RedactionReport
+		{
+			file_type,
+			removed_tags:       removed_tags.iter().map(RemovedTag::new).collect(),
+			removed_data_bytes,
+		}
+	}
+
+	/// The type of the stripped image
+	pub fn
+	file_type
+	(
+		&self
+	)
+	-> FileExtension
+	{
+		self.file_type
+	}
+
+	/// The removed tags, in the order they have been stored in
+	pub fn
+	removed_tags
+	(
+		&self
+	)
+	-> &Vec<RemovedTag>
+	{
+		&self.removed_tags
+	}
+
+	/// The size of the removed EXIF data (the TIFF data, without the
+	/// container specific header) in bytes
+	pub fn
+	removed_data_bytes
+	(
+		&self
+	)
+	-> usize
+	{
+		self.removed_data_bytes
+	}
+
+	/// Checks whether anything has been removed at all
+	pub fn
+	is_empty
+	(
+		&self
+	)
+	-> bool
+	{
+		self.removed_data_bytes == 0
+	}
+
+	/// Formats the report as JSON object, e.g.
+	/// `{"file_type":"JPEG","removed_data_bytes":120,"removed_tags":[{"name":"Artist","tag_id":315,"group":"IFD0","summary":"Jane Doe","byte_count":9}]}`
+	pub fn
+	to_json
+	(
+		&self
+	)
+	-> String
+	{
+		let removed_tags = self.removed_tags.iter()
+			.map(|removed_tag| format!(
+				"{{\"name\":{},\"tag_id\":{},\"group\":{},\"summary\":{},\"byte_count\":{}}}",
+				json_string(&removed_tag.name),
+				removed_tag.tag_id,
+				json_string(&format!("{:?}", removed_tag.group)),
+				json_string(&removed_tag.summary),
+				removed_tag.byte_count
+			))
+			.collect::<Vec<String>>();
+
+		let file_type = match self.file_type
+		{
+			FileExtension::JPEG    => "JPEG",
+			FileExtension::PNG{..} => "PNG",
+			FileExtension::WEBP    => "WEBP",
+			FileExtension::TIFF    => "TIFF",
+			FileExtension::HEIF    => "HEIF",
+			FileExtension::AVIF    => "AVIF",
+			FileExtension::GIF     => "GIF",
+			FileExtension::CR2     => "CR2",
+			FileExtension::CR3     => "CR3",
+			FileExtension::ORF     => "ORF",
+			FileExtension::RW2     => "RW2",
+			FileExtension::RAF     => "RAF",
+			FileExtension::AVI     => "AVI",
+		};
+
+		return format!(
+			"{{\"file_type\":{},\"removed_data_bytes\":{},\"removed_tags\":[{}]}}",
+			json_string(file_type),
+			self.removed_data_bytes,
+			removed_tags.join(",")
+		);
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::exif_tag::ExifTag;
+	use crate::filetype::FileExtension;
+	use crate::redaction::RedactionReport;
+	use crate::redaction::summarize;
+
+	#[test]
+	fn
+	report_as_json()
+	{
+		assert_eq!(summarize("Jane Doe"), "Jane Doe");
+		assert_eq!(summarize("A very long description of the image"), "A very long description …");
+
+		let report = RedactionReport::new(
+			FileExtension::JPEG,
+			&[ExifTag::Artist("Jane \"JD\" Doe".to_string()), ExifTag::MakerNote(vec![0x00; 300])],
+			400
+		);
+		assert_eq!(report.to_json(), concat!(
+			r#"{"file_type":"JPEG","removed_data_bytes":400,"removed_tags":["#,
+			r#"{"name":"Artist","tag_id":315,"group":"IFD0","summary":"Jane \"JD\" Doe","byte_count":14},"#,
+			r#"{"name":"MakerNote","tag_id":37500,"group":"ExifIFD","summary":"300 bytes of binary data","byte_count":300}"#,
+			r#"]}"#
+		));
+	}
+}
@@ -1,42 +1,152 @@
 // Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
 // See https://github.com/TechnikTobi/little_exif#license for licensing details
 
-#[allow(non_snake_case)]
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+
+use crate::chunk_types::RiffChunkType;
+use crate::endian::*;
+use crate::errors::ParseErrorContext;
+use crate::errors::WithParseContext;
+use crate::general_file_io::*;
+
+/// The four character code identifying the type of a RIFF chunk. Consists of
+/// exactly four printable ASCII characters (spaces included, e.g. "VP8 ")
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct
+FourCC([u8; 4]);
+
+impl
+FourCC
+{
+	/// Validates the bytes and constructs the fourCC from them
+	pub fn
+	new
+	(
+		bytes: &[u8]
+	)
+	-> Result<FourCC, std::io::Error>
+	{
+		let bytes: [u8; 4] = match bytes.try_into()
+		{
+			Ok(bytes) => bytes,
+			Err(_)    => return io_error!(InvalidData, format!("Invalid RIFF fourCC - Expected 4 bytes but got {}!", bytes.len())),
+		};
+
+		if !bytes.iter().all(|byte| (0x20..=0x7e).contains(byte))
+		{
+			return io_error!(InvalidData, format!("Invalid RIFF fourCC {:02x?} - Only printable ASCII characters are allowed!", bytes));
+		}
+
+		return Ok(FourCC(bytes));
+	}
+
+	pub fn
+	as_bytes
+	(
+		&self
+	)
+	-> &[u8; 4]
+	{
+		&self.0
+	}
+
+	pub fn
+	as_str
+	(
+		&self
+	)
+	-> &str
+	{
+		// Can't fail, as only ASCII characters are allowed
+		std::str::from_utf8(&self.0).unwrap()
+	}
+
+	/// Gets the type of the chunk. FourCCs are case sensitive, so e.g. an
+	/// "exif" chunk is not an EXIF chunk but an unknown one
+	pub fn
+	chunk_type
+	(
+		&self
+	)
+	-> RiffChunkType
+	{
+		RiffChunkType::from_bytes(self.0)
+	}
+}
+
+/// Only meant for the known types, as the bytes of `RiffChunkType::Unknown`
+/// are not validated
+impl
+From<RiffChunkType>
+for
+FourCC
+{
+	fn
+	from
+	(
+		chunk_type: RiffChunkType
+	)
+	-> FourCC
+	{
+		FourCC(chunk_type.as_bytes())
+	}
+}
+
+impl
+std::fmt::Display
+for
+FourCC
+{
+	fn
+	fmt
+	(
+		&self,
+		f: &mut std::fmt::Formatter<'_>
+	)
+	-> std::fmt::Result
+	{
+		write!(f, "{}", self.as_str())
+	}
+}
+
 #[derive(Clone)]
 pub(crate) struct
 RiffChunkDescriptor
 {
-	fourCC:  String, // The 4 byte long header at the start of the chunk
-	size:    usize,  // Chunk size WITHOUT the 8 bytes for the header and size section
+	fourcc: FourCC, // The 4 byte long header at the start of the chunk
+	size:   usize,  // Chunk size WITHOUT the 8 bytes for the header and size section and the padding byte
 }
 
 impl
 RiffChunkDescriptor
 {
-	#[allow(non_snake_case)]
 	pub fn
 	new
 	(
-		fourCC: String,
+		fourcc: FourCC,
 		size:   usize
 	)
 	-> RiffChunkDescriptor
 	{
 		RiffChunkDescriptor
 		{
-			fourCC: fourCC,
-			size:   size
+			fourcc,
+			size
 		}
 	}
 
+	/// The number of bytes the entire chunk takes up: header, size section,
+	/// payload and padding byte (if the payload size is odd)
 	pub fn
-	len
+	byte_count
 	(
 		&self
 	)
 	-> usize
 	{
-		self.size
+		4 + 4 + self.size + self.size % 2
 	}
 
 	pub fn
@@ -44,9 +154,20 @@ RiffChunkDescriptor
 	(
 		&self
 	)
-	-> String
+	-> FourCC
+	{
+		self.fourcc
+	}
+
+	/// The size of the payload, without the padding byte
+	pub fn
+	payload_size
+	(
+		&self
+	)
+	-> usize
 	{
-		self.fourCC.clone()
+		self.size
 	}
 }
 
@@ -61,20 +182,20 @@ RiffChunk
 impl
 RiffChunk
 {
-	#[allow(non_snake_case)]
+	/// Constructs a chunk with the given payload, which must NOT include
+	/// the padding byte
 	pub fn
 	new
 	(
-		fourCC:  String,
-		size:    usize,
+		fourcc:  FourCC,
 		payload: Vec<u8>
 	)
 	-> RiffChunk
 	{
 		RiffChunk
 		{
-			descriptor: RiffChunkDescriptor::new(fourCC, size),
-			payload:    payload
+			descriptor: RiffChunkDescriptor::new(fourcc, payload.len()),
+			payload
 		}
 	}
 
@@ -97,4 +218,301 @@ RiffChunk
 	{
 		&self.payload
 	}
+
+	/// Serializes the chunk: fourCC, payload size (little endian), payload
+	/// and the padding byte if the payload size is odd
+	pub fn
+	serialize
+	(
+		&self
+	)
+	-> Vec<u8>
+	{
+		let mut serialized = Vec::with_capacity(self.descriptor.byte_count());
+		serialized.extend(self.descriptor.header().as_bytes());
+		Endian::Little.write(&mut serialized, self.payload.len() as u32);
+		serialized.extend(&self.payload);
+		if !self.payload.len().is_multiple_of(2)
+		{
+			serialized.push(0x00);
+		}
+		return serialized;
+	}
+}
+
+
+
+/// A RIFF file (e.g. WebP or AVI) starts as follows
+/// - The RIFF signature: ASCII characters "R", "I", "F", "F"  -> 4 bytes
+/// - The size of the RIFF chunk starting at offset 8          -> 4 bytes
+/// - The form type, e.g. ASCII characters "W", "E", "B", "P"  -> 4 bytes
+///
+/// This function checks these 3 sections and their correctness. With
+/// `exact_size`, the data has to end where the RIFF chunk ends, otherwise
+/// there may be further data after it (e.g. the extension chunks of large
+/// AVI files). Afterwards, the cursor is located at the start of the first
+/// chunk. Returns the position where the RIFF chunk ends
+pub(crate) fn
+check_header
+<T: Read + Seek>
+(
+	cursor:      &mut T,
+	form_type:   [u8; 4],
+	format_name: &str,
+	exact_size:  bool
+)
+-> Result<u64, std::io::Error>
+{
+	// Check the RIFF signature
+	let mut riff_signature_buffer = [0u8; 4];
+	perform_file_action!(cursor.seek(SeekFrom::Start(0)));
+	if !try_read_exact(cursor, &mut riff_signature_buffer)?
+	{
+		return io_error!(InvalidData, format!("Can't open {} file - Data too short for RIFF header!", format_name));
+	}
+	if riff_signature_buffer != RiffChunkType::RIFF.as_bytes()
+	{
+		return io_error!(
+			InvalidData, 
+			format!("Can't open {} file - Expected RIFF signature but found {}!", format_name, String::from_utf8_lossy(&riff_signature_buffer))
+		);
+	}
+
+	// Read the size in byte and validate it using the length of the data
+	let mut size_buffer = [0u8; 4];
+	if !try_read_exact(cursor, &mut size_buffer)?
+	{
+		return io_error!(InvalidData, format!("Can't open {} file - Data too short for RIFF header!", format_name));
+	}
+	let riff_end = u32::from_le_bytes(size_buffer) as u64 + 8;
+	let data_length = cursor.seek(SeekFrom::End(0))?;
+	if riff_end < 12 || (data_length != riff_end && (exact_size || data_length < riff_end))
+	{
+		return io_error!(InvalidData, format!("Can't open {} file - Promised byte count does not correspond with file size!", format_name));
+	}
+
+	// Check the form type
+	let mut form_type_buffer = [0u8; 4];
+	perform_file_action!(cursor.seek(SeekFrom::Start(8)));
+	perform_file_action!(cursor.read_exact(&mut form_type_buffer));
+	if form_type_buffer != form_type
+	{
+		return io_error!(
+			InvalidData, 
+			format!("Can't open {} file - Expected {} signature but found {}!", format_name, String::from_utf8_lossy(&form_type), String::from_utf8_lossy(&form_type_buffer))
+		);
+	}
+
+	// Signature is valid - can proceed using the data as RIFF file
+	return Ok(riff_end);
+}
+
+
+
+/// Gets the next RIFF chunk, starting at the current cursor position, which
+/// has to end before the given end of the RIFF chunk (see `check_header`).
+/// Advances the cursor to the start of the next chunk
+pub(crate) fn
+get_next_chunk
+<T: Read + Seek>
+(
+	cursor:   &mut T,
+	riff_end: u64
+)
+-> Result<RiffChunk, std::io::Error>
+{
+	// Read the start of the chunk
+	let chunk_position = cursor.stream_position()?;
+	let mut chunk_start = [0u8; 8];
+	if !try_read_exact(cursor, &mut chunk_start)?
+	{
+		return io_error!(UnexpectedEof, "Could not read start of chunk");
+	}
+
+	// Construct name of chunk and its length
+	let fourcc = FourCC::new(&chunk_start[0..4])?;
+	let chunk_length = u32::from_bytes(&chunk_start[4..8], &Endian::Little) as usize;
+	let context = || ParseErrorContext::new().chunk(fourcc.as_str());
+
+	// The length may be anything up to 4 GiB, so check it before allocating
+	// the buffer for the data
+	if chunk_position + 8 + chunk_length as u64 > riff_end
+	{
+		return io_error!(InvalidData, format!("RIFF chunk data of {} bytes exceeds the RIFF chunk!", chunk_length)).with_parse_context(context);
+	}
+
+	// Read RIFF chunk data, including the possible padding byte
+	let mut chunk_data_buffer = vec![0u8; chunk_length + chunk_length % 2];
+	if !try_read_exact(cursor, &mut chunk_data_buffer)?
+	{
+		return io_error!(
+			Other, 
+			format!("Could not read RIFF chunk data! Expected {} bytes", chunk_data_buffer.len())
+		).with_parse_context(context);
+	}
+	chunk_data_buffer.truncate(chunk_length);
+
+	return Ok(RiffChunk::new(fourcc, chunk_data_buffer));
+}
+
+
+
+/// Gets a descriptor of the next RIFF chunk, starting at the current cursor
+/// position. Advances the cursor to the start of the next chunk
+/// Relies on `get_next_chunk` by basically calling that function and throwing
+/// away the actual payload
+pub(crate) fn
+get_next_chunk_descriptor
+<T: Read + Seek>
+(
+	cursor:   &mut T,
+	riff_end: u64
+)
+-> Result<RiffChunkDescriptor, std::io::Error>
+{
+	let next_chunk_result = get_next_chunk(cursor, riff_end)?;
+	return Ok(next_chunk_result.descriptor());
+}
+
+
+
+/// Gets the descriptors of the chunks following the RIFF header (see
+/// `check_header`) up to the given end of the RIFF chunk, checking that the
+/// chunks and their size descriptions are OK
+pub(crate) fn
+parse_chunks
+<T: Read + Seek>
+(
+	cursor:   &mut T,
+	riff_end: u64
+)
+-> Result<Vec<RiffChunkDescriptor>, std::io::Error>
+{
+	let mut chunks = Vec::new();
+
+	// How much data we have parsed so far.
+	// Starts with 12 bytes: 
+	// - 4 bytes for RIFF signature
+	// - 4 bytes for the size
+	// - 4 bytes for the form type
+	// These bytes are already read in by `check_header`
+	let mut parsed_length = 12u64;
+	perform_file_action!(cursor.seek(SeekFrom::Start(parsed_length)));
+
+	while parsed_length < riff_end
+	{
+		let next_chunk_descriptor_result = get_next_chunk_descriptor(cursor, riff_end)
+			.with_parse_context(|| ParseErrorContext::new().offset(parsed_length));
+		if let Ok(chunk_descriptor) = next_chunk_descriptor_result
+		{
+			// The parsed length increases by the length of the chunk's 
+			// header (4 byte) + it's size section (4 byte), the payload
+			// size, which is noted by the aforementioned size section, and
+			// the possible padding byte
+			parsed_length += chunk_descriptor.byte_count() as u64;
+
+			// Add the chunk descriptor
+			chunks.push(chunk_descriptor);
+		}
+		else
+		{
+			// This is the case when the read of the next chunk descriptor 
+			// fails due to not being able to fetch 8 bytes for the header and
+			// chunk size information, indicating that there is no further data
+			// in the file and we are done with parsing.
+			// If the subroutine fails due to other reasons, the error gets
+			// propagated further.
+			if next_chunk_descriptor_result.as_ref().err().unwrap().kind() == std::io::ErrorKind::UnexpectedEof
+			{
+				break;
+			}
+			else
+			{
+				return Err(next_chunk_descriptor_result.err().unwrap());
+			}
+		}
+	}
+
+	return Ok(chunks);
+}
+
+
+
+/// Gets the positions and descriptors of the chunks stored one after another
+/// in the given data, e.g. the payload of a LIST chunk following its list
+/// type. The padding byte of the last chunk may be missing.
+/// - If a chunk exceeds the data, an error gets returned
+pub(crate) fn
+sub_chunks
+(
+	data: &[u8]
+)
+-> Result<Vec<(usize, RiffChunkDescriptor)>, std::io::Error>
+{
+	let mut chunks   = Vec::new();
+	let mut position = 0usize;
+	while position + 8 <= data.len()
+	{
+		let fourcc = FourCC::new(&data[position..position + 4])
+			.with_parse_context(|| ParseErrorContext::new().offset(position as u64))?;
+		let size   = u32::from_bytes(&data[position + 4..position + 8], &Endian::Little) as usize;
+		if size > data.len() - position - 8
+		{
+			return io_error!(InvalidData, format!("RIFF chunk '{}' exceeds the data!", fourcc))
+				.with_parse_context(|| ParseErrorContext::new().offset(position as u64).chunk(fourcc.as_str()));
+		}
+
+		let descriptor = RiffChunkDescriptor::new(fourcc, size);
+		let byte_count = descriptor.byte_count();
+		chunks.push((position, descriptor));
+		position += byte_count;
+	}
+	return Ok(chunks);
+}
+
+
+
+/// Writes the size of the RIFF chunk (excluding the RIFF signature and the
+/// size information itself) into the RIFF header. The RIFF chunk ends the
+/// given number of bytes before the end of the data
+pub(crate) fn
+update_riff_size
+(
+	file_buffer:    &mut Vec<u8>,
+	trailing_bytes: usize
+)
+{
+	// Note from the documentation:
+	// As the size of any chunk is even, the size given by the RIFF header is also even.
+	let new_riff_size = (file_buffer.len() - trailing_bytes) as u32 - 8;
+	assert!(new_riff_size.is_multiple_of(2));
+
+	file_buffer.splice(4..8, new_riff_size.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::chunk_types::RiffChunkType;
+	use crate::riff_chunk::FourCC;
+	use crate::riff_chunk::RiffChunk;
+
+	#[test]
+	fn
+	fourcc_validation()
+	{
+		assert_eq!(FourCC::new(b"VP8 ").unwrap().as_str(), "VP8 ");
+		assert_eq!(FourCC::new(b"exif").unwrap().chunk_type(), RiffChunkType::Unknown(*b"exif"));
+		assert_eq!(FourCC::new(b"VP8 ").unwrap().chunk_type(), RiffChunkType::VP8);
+		assert!(FourCC::new(b"VP8").is_err());
+		assert!(FourCC::new(b"VP8\0").is_err());
+	}
+
+	#[test]
+	fn
+	serialize_chunks()
+	{
+		assert_eq!(RiffChunk::new(RiffChunkType::EXIF.into(), vec![0x01, 0x02, 0x03]).serialize(), b"EXIF\x03\0\0\0\x01\x02\x03\0".to_vec());
+		assert_eq!(RiffChunk::new(RiffChunkType::EXIF.into(), vec![0x01, 0x02]).serialize(),       b"EXIF\x02\0\0\0\x01\x02".to_vec());
+	}
 }
@@ -0,0 +1,34 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Panasonic RW2 raw files (also used by Leica as RWL) are little endian
+//! TIFF files whose header uses the magic 0x55 instead of 42, i.e. they start
+//! with `IIU\0`. IFD0 describes the raw data and holds the metadata, next
+//! to Panasonic's own tags (stored with tag numbers below 0x0100, like the
+//! sensor size and the ISO value) and the ExifIFD. A complete JPEG preview
+//! with its own EXIF data is embedded as well, which is not read.
+//!
+//! Only reading is supported, see `raw_file` - the header check of `tiff`
+//! accepts this signature.
+
+use crate::filetype::FileExtension;
+use crate::raw_file::TiffRawFormat;
+use crate::tiff;
+
+pub(crate) const RW2: TiffRawFormat = TiffRawFormat
+{
+	file_type:        FileExtension::RW2,
+	signature_length: 4,
+	is_format:        is_rw2,
+};
+
+/// Checks whether the data starts with the signature of an RW2 file
+pub(crate) fn
+is_rw2
+(
+	file_buffer: &[u8]
+)
+-> bool
+{
+	return file_buffer.starts_with(&tiff::RW2_SIGNATURE);
+}
@@ -0,0 +1,646 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Metadata of scanned film and documents, e.g. for archive digitization
+//! projects. A `ScanProfile` collects the values that describe the scan and
+//! its source and writes them to the following tags:
+//!
+//! | Field           | Stored in                                             |
+//! |-----------------|-------------------------------------------------------|
+//! | `scanner`       | `Make`, `Model`                                       |
+//! | `software`      | `Software`                                            |
+//! | `document_name` | `DocumentName`, e.g. the roll or folder               |
+//! | `page`          | `PageNumber`, e.g. the frame on the roll              |
+//! | `resolution`    | `XResolution`, `YResolution`, `ResolutionUnit` (inch) |
+//! | `artist`        | `Artist`                                              |
+//! | `copyright`     | `Copyright`                                           |
+//! | `oecf`          | `OECF`, see `Oecf`                                    |
+//! | `film`          | XMP, see below                                        |
+//!
+//! EXIF has no tags describing the scanned film, so it is written to the XMP
+//! packet (see the `xmp` module, so only JPEG, PNG and GIF files are
+//! supported) using these properties of the namespace
+//! `https://github.com/TechnikTobi/little_exif/ns/film/1.0/` (prefix `film`):
+//! - `FilmType`: The kind of film, see `FilmType`
+//! - `FilmStock`: The product name of the film, e.g. "Kodak Portra 400"
+//!
+//! `ScanProfile::archive` provides a template for archive digitization
+//! projects, which gets completed per scan.
+//!
+//! # Examples
+//! ```no_run
+//! use little_exif::scan::Film;
+//! use little_exif::scan::FilmType;
+//! use little_exif::scan::ScanProfile;
+//!
+//! let template = ScanProfile::archive("City Archive Graz")
+//!     .scanner("Nikon", "LS-5000")
+//!     .film(Film::new(FilmType::ColorNegative).stock("Kodak Portra 400"));
+//!
+//! for frame in 0..36
+//! {
+//!     let path = format!("roll_12/frame_{:02}.jpg", frame + 1);
+//!     template.clone()
+//!         .document_name("Roll 12")
+//!         .page(frame, 36)
+//!         .write_to_file(std::path::Path::new(&path))
+//!         .unwrap();
+//! }
+//! ```
+
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::endian::*;
+use crate::exif_tag::ExifTag;
+use crate::filetype::FileExtension;
+use crate::general_file_io::io_error;
+use crate::general_file_io::write_vec_to_file;
+use crate::metadata::Metadata;
+use crate::xmp;
+use crate::xmp::Namespace;
+
+const FILM_NAMESPACE: Namespace = ("film", "https://github.com/TechnikTobi/little_exif/ns/film/1.0/");
+
+/// The resolution used by `ScanProfile::archive`, in pixels per inch. This
+/// is the one recommended for 35 mm film by the FADGI guidelines (four
+/// stars), documents require less.
+pub const ARCHIVE_RESOLUTION: u32 = 4000;
+
+/// The opto-electronic conversion function (OECF) of the scanner or camera,
+/// i.e. the relation between the optical input and the resulting values,
+/// as stored in the `OECF` tag (see ISO 14524): A table with named columns
+/// (e.g. "Log exposure", "R", "G", "B") of signed rationals.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct
+Oecf
+{
+	pub column_names: Vec<String>,
+	pub rows:         Vec<Vec<(i32, i32)>>,                                      // Numerator and denominator for each column
+}
+
+impl
+Oecf
+{
+	/// Decodes the raw data of an `OECF` tag, which uses the byte order of
+	/// the EXIF data
+	/// - If the data is truncated, an error of kind `UnexpectedEof` gets
+	///   returned.
+	pub fn
+	decode
+	(
+		raw_data: &[u8],
+		endian:   &Endian
+	)
+	-> Result<Oecf, std::io::Error>
+	{
+		let (column_count, row_count) = match (endian.read::<u16>(raw_data, 0), endian.read::<u16>(raw_data, 2))
+		{
+			(Some(column_count), Some(row_count)) => (column_count as usize, row_count as usize),
+			_                                     => return io_error!(UnexpectedEof, "OECF data is truncated!"),
+		};
+
+		let mut position = 4;
+		let mut column_names = Vec::new();
+		for _ in 0..column_count
+		{
+			let length = match raw_data.get(position..).and_then(|rest| rest.iter().position(|byte| *byte == 0))
+			{
+				Some(length) => length,
+				None         => return io_error!(UnexpectedEof, "OECF data is truncated!"),
+			};
+			column_names.push(String::from_utf8_lossy(&raw_data[position..position + length]).into_owned());
+			position += length + 1;
+		}
+
+		let mut rows = Vec::new();
+		for _ in 0..row_count
+		{
+			let mut row = Vec::new();
+			for _ in 0..column_count
+			{
+				match (endian.read::<i32>(raw_data, position), endian.read::<i32>(raw_data, position + 4))
+				{
+					(Some(numerator), Some(denominator)) => row.push((numerator, denominator)),
+					_                                    => return io_error!(UnexpectedEof, "OECF data is truncated!"),
+				}
+				position += 8;
+			}
+			rows.push(row);
+		}
+
+		return Ok(Oecf { column_names, rows });
+	}
+
+	/// Encodes the table as raw data for an `OECF` tag
+	/// - If a row doesn't have a value for each column, there are too many
+	///   rows or columns or a name contains a null character, an error of
+	///   kind `InvalidInput` gets returned.
+	pub fn
+	encode
+	(
+		&self,
+		endian: &Endian
+	)
+	-> Result<Vec<u8>, std::io::Error>
+	{
+		let column_count = u16::try_from(self.column_names.len());
+		let row_count    = u16::try_from(self.rows.len());
+		let (column_count, row_count) = match (column_count, row_count)
+		{
+			(Ok(column_count), Ok(row_count)) => (column_count, row_count),
+			_                                 => return io_error!(InvalidInput, "Too many OECF rows or columns!"),
+		};
+		if self.rows.iter().any(|row| row.len() != self.column_names.len())
+		{
+			return io_error!(InvalidInput, "Each OECF row needs a value for each column!");
+		}
+		if self.column_names.iter().any(|name| name.contains('\0'))
+		{
+			return io_error!(InvalidInput, "OECF column names can't contain null characters!");
+		}
+
+		let mut raw_data = Vec::new();
+		endian.write(&mut raw_data, column_count);
+		endian.write(&mut raw_data, row_count);
+		for name in &self.column_names
+		{
+			raw_data.extend(name.as_bytes());
+			raw_data.push(0);
+		}
+		for (numerator, denominator) in self.rows.iter().flatten()
+		{
+			endian.write(&mut raw_data, *numerator);
+			endian.write(&mut raw_data, *denominator);
+		}
+		return Ok(raw_data);
+	}
+}
+
+/// The kind of the scanned film
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum
+FilmType
+{
+	ColorNegative,
+	ColorReversal,                                                              // Slide film
+	BlackAndWhiteNegative,
+	BlackAndWhiteReversal,
+}
+
+impl
+FilmType
+{
+	/// Gets the value of the `film:FilmType` property
+	fn
+	as_str
+	(
+		&self
+	)
+	-> &'static str
+	{
+		match self
+		{
+			FilmType::ColorNegative         => "ColorNegative",
+			FilmType::ColorReversal         => "ColorReversal",
+			FilmType::BlackAndWhiteNegative => "BlackAndWhiteNegative",
+			FilmType::BlackAndWhiteReversal => "BlackAndWhiteReversal",
+		}
+	}
+}
+
+impl
+FromStr
+for
+FilmType
+{
+	type Err = std::io::Error;
+
+	fn
+	from_str
+	(
+		value: &str
+	)
+	-> Result<FilmType, std::io::Error>
+	{
+		match value
+		{
+			"ColorNegative"         => Ok(FilmType::ColorNegative),
+			"ColorReversal"         => Ok(FilmType::ColorReversal),
+			"BlackAndWhiteNegative" => Ok(FilmType::BlackAndWhiteNegative),
+			"BlackAndWhiteReversal" => Ok(FilmType::BlackAndWhiteReversal),
+			_                       => io_error!(InvalidData, format!("Unknown film type '{}'!", value)),
+		}
+	}
+}
+
+/// The scanned film, see module documentation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct
+Film
+{
+	pub film_type: FilmType,
+	pub stock:     Option<String>,
+}
+
+impl
+Film
+{
+	pub fn
+	new
+	(
+		film_type: FilmType
+	)
+	-> Film
+	{
+		Film { film_type, stock: None }
+	}
+
+	/// Sets the product name of the film, e.g. "Ilford HP5 Plus"
+	pub fn
+	stock
+	(
+		mut self,
+		stock: &str
+	)
+	-> Film
+	{
+		self.stock = Some(stock.to_string());
+		self
+	}
+}
+
+/// Writes the scanned film to the XMP packet of the image, replacing
+/// previous values (see module documentation)
+pub fn
+write_film_to_vec
+(
+	file_buffer: &mut Vec<u8>,
+	film:        &Film
+)
+-> Result<(), std::io::Error>
+{
+	let packet = xmp::read_xmp_from_vec(file_buffer)?;
+	let packet = xmp::set_properties(packet.as_deref(), FILM_NAMESPACE, &[
+		("FilmType",  Some(film.film_type.as_str().to_string())),
+		("FilmStock", film.stock.clone()),
+	])?;
+	return xmp::write_xmp_to_vec(file_buffer, &packet);
+}
+
+/// Reads the scanned film from the XMP packet of the image. Returns
+/// `None` if it is not described or the film type is unknown.
+pub fn
+read_film_from_vec
+(
+	file_buffer: &[u8]
+)
+-> Result<Option<Film>, std::io::Error>
+{
+	let packet = match xmp::read_xmp_from_vec(file_buffer)?
+	{
+		Some(packet) => packet,
+		None         => return Ok(None),
+	};
+
+	let film_type = xmp::get_property(&packet, FILM_NAMESPACE, "FilmType")
+		.and_then(|film_type| FilmType::from_str(&film_type).ok());
+	return Ok(film_type.map(|film_type| Film
+	{
+		film_type,
+		stock: xmp::get_property(&packet, FILM_NAMESPACE, "FilmStock"),
+	}));
+}
+
+/// The metadata of a scan, see the module documentation. Set using chained
+/// calls, fields that are not set don't change the image.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct
+ScanProfile
+{
+	pub(crate) scanner:       Option<(String, String)>,                         // Make, model
+	pub(crate) software:      Option<String>,
+	pub(crate) document_name: Option<String>,
+	pub(crate) page:          Option<(u16, u16)>,                               // Page, total number of pages
+	pub(crate) resolution:    Option<u32>,                                      // In pixels per inch
+	pub(crate) artist:        Option<String>,
+	pub(crate) copyright:     Option<String>,
+	pub(crate) oecf:          Option<Oecf>,
+	pub(crate) film:          Option<Film>,
+}
+
+impl
+ScanProfile
+{
+	pub fn
+	new
+	()
+	-> ScanProfile
+	{
+		ScanProfile::default()
+	}
+
+	/// Constructs the template for scans of an archive digitization project
+	/// carried out by the given institution, which is set as artist. The
+	/// resolution is set to `ARCHIVE_RESOLUTION`. The values describing the
+	/// individual scans (e.g. `document_name` and `page`) still have to be
+	/// set, as well as the copyright if known.
+	pub fn
+	archive
+	(
+		institution: &str
+	)
+	-> ScanProfile
+	{
+		ScanProfile::new()
+			.artist(institution)
+			.resolution(ARCHIVE_RESOLUTION)
+	}
+
+	/// Sets the make and model of the scanner
+	pub fn
+	scanner
+	(
+		mut self,
+		make:  &str,
+		model: &str
+	)
+	-> ScanProfile
+	{
+		self.scanner = Some((make.to_string(), model.to_string()));
+		self
+	}
+
+	/// Sets the software used for scanning
+	pub fn
+	software
+	(
+		mut self,
+		software: &str
+	)
+	-> ScanProfile
+	{
+		self.software = Some(software.to_string());
+		self
+	}
+
+	/// Sets the name of the scanned document, e.g. the roll of film or the
+	/// folder of an archive
+	pub fn
+	document_name
+	(
+		mut self,
+		document_name: &str
+	)
+	-> ScanProfile
+	{
+		self.document_name = Some(document_name.to_string());
+		self
+	}
+
+	/// Sets the page of the document (e.g. the frame of the roll) that got
+	/// scanned, starting at 0 as defined by TIFF, and the total number of
+	/// pages, or 0 if unknown
+	pub fn
+	page
+	(
+		mut self,
+		page:  u16,
+		total: u16
+	)
+	-> ScanProfile
+	{
+		self.page = Some((page, total));
+		self
+	}
+
+	/// Sets the resolution of the scan in pixels per inch
+	pub fn
+	resolution
+	(
+		mut self,
+		pixels_per_inch: u32
+	)
+	-> ScanProfile
+	{
+		self.resolution = Some(pixels_per_inch);
+		self
+	}
+
+	/// Sets the person or institution that created the scan
+	pub fn
+	artist
+	(
+		mut self,
+		artist: &str
+	)
+	-> ScanProfile
+	{
+		self.artist = Some(artist.to_string());
+		self
+	}
+
+	/// Sets the copyright notice
+	pub fn
+	copyright
+	(
+		mut self,
+		copyright: &str
+	)
+	-> ScanProfile
+	{
+		self.copyright = Some(copyright.to_string());
+		self
+	}
+
+	/// Sets the opto-electronic conversion function of the scanner
+	pub fn
+	oecf
+	(
+		mut self,
+		oecf: Oecf
+	)
+	-> ScanProfile
+	{
+		self.oecf = Some(oecf);
+		self
+	}
+
+	/// Sets the scanned film
+	pub fn
+	film
+	(
+		mut self,
+		film: Film
+	)
+	-> ScanProfile
+	{
+		self.film = Some(film);
+		self
+	}
+
+	/// Converts the values into EXIF tags (except for the film, which is
+	/// stored in XMP)
+	fn
+	to_tags
+	(
+		&self,
+		endian: &Endian
+	)
+	-> Result<Vec<ExifTag>, std::io::Error>
+	{
+		let mut tags = Vec::new();
+
+		if let Some((make, model)) = &self.scanner
+		{
+			tags.push(ExifTag::Make(make.clone()));
+			tags.push(ExifTag::Model(model.clone()));
+		}
+		if let Some((page, total)) = self.page
+		{
+			if total != 0 && page >= total
+			{
+				return io_error!(InvalidInput, format!("Page {} exceeds the total of {} pages!", page, total));
+			}
+			tags.push(ExifTag::PageNumber(vec![page, total]));
+		}
+		if let Some(pixels_per_inch) = self.resolution
+		{
+			if pixels_per_inch == 0
+			{
+				return io_error!(InvalidInput, "Resolution has to be positive!");
+			}
+			tags.push(ExifTag::XResolution(vec![pixels_per_inch, 1]));
+			tags.push(ExifTag::YResolution(vec![pixels_per_inch, 1]));
+			tags.push(ExifTag::ResolutionUnit(vec![2]));
+		}
+		if let Some(oecf) = &self.oecf
+		{
+			tags.push(ExifTag::OECF(oecf.encode(endian)?));
+		}
+
+		for (value, tag) in [
+			(&self.software,      ExifTag::Software as fn(String) -> ExifTag),
+			(&self.document_name, ExifTag::DocumentName),
+			(&self.artist,        ExifTag::Artist),
+			(&self.copyright,     ExifTag::Copyright),
+		]
+		{
+			if let Some(value) = value
+			{
+				tags.push(tag(value.clone()));
+			}
+		}
+
+		return Ok(tags);
+	}
+
+	/// Sets the EXIF tags of the profile, see the module documentation. The
+	/// film is not included, see `write_film_to_vec`.
+	/// - If the page exceeds the total number of pages, the resolution is 0
+	///   or the OECF can't be encoded, an error of kind `InvalidInput` gets
+	///   returned and the metadata is not changed.
+	pub fn
+	apply
+	(
+		&self,
+		metadata: &mut Metadata
+	)
+	-> Result<(), std::io::Error>
+	{
+		for tag in self.to_tags(metadata.endian())?
+		{
+			metadata.set_tag(tag);
+		}
+		return Ok(());
+	}
+
+	/// Writes the profile to the image at the given path, including the film
+	/// (see the module documentation)
+	/// - If a film is set but the image is neither a JPEG nor a PNG file, an
+	///   error of kind `Unsupported` gets returned.
+	/// - In case of an error, the image is not changed.
+	pub fn
+	write_to_file
+	(
+		&self,
+		path: &Path
+	)
+	-> Result<(), std::io::Error>
+	{
+		let mut file_buffer = std::fs::read(path)?;
+		let file_type = match FileExtension::from_signature(&file_buffer).or_else(|| FileExtension::from_path(path))
+		{
+			Some(file_type) => file_type,
+			None            => return io_error!(Unsupported, format!("Unsupported file type of {}!", path.display())),
+		};
+
+		let mut metadata = Metadata::new_from_vec(&file_buffer, file_type)?;
+		self.apply(&mut metadata)?;
+
+		if let Some(film) = &self.film
+		{
+			write_film_to_vec(&mut file_buffer, film)?;
+		}
+		metadata.write_to_vec(&mut file_buffer, file_type)?;
+		return write_vec_to_file(path, &file_buffer);
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::endian::Endian;
+	use crate::exif_tag::ExifTag;
+	use crate::scan::*;
+
+	#[test]
+	fn
+	encode_and_decode_oecf()
+	-> Result<(), std::io::Error>
+	{
+		let oecf = Oecf
+		{
+			column_names: vec!["Log exposure".to_string(), "Y".to_string()],
+			rows:         vec![vec![(-3, 1), (12, 1)], vec![(0, 1), (4095, 1)]],
+		};
+
+		let raw_data = oecf.encode(&Endian::Big)?;
+		assert_eq!(&raw_data[..4], &[0x00, 0x02, 0x00, 0x02]);
+		assert_eq!(&raw_data[4..19], b"Log exposure\0Y\0");
+		assert_eq!(&raw_data[19..23], &[0xff, 0xff, 0xff, 0xfd]);
+		assert_eq!(raw_data.len(), 19 + 4 * 8);
+		assert_eq!(Oecf::decode(&raw_data, &Endian::Big)?, oecf);
+		assert_eq!(Oecf::decode(&oecf.encode(&Endian::Little)?, &Endian::Little)?, oecf);
+
+		assert!(Oecf::decode(&raw_data[..raw_data.len()-1], &Endian::Big).is_err());
+		assert!(Oecf::decode(&raw_data[..10], &Endian::Big).is_err());
+		assert!(Oecf { column_names: vec!["R".to_string()], rows: vec![vec![]] }.encode(&Endian::Big).is_err());
+		return Ok(());
+	}
+
+	#[test]
+	fn
+	archive_profile_tags()
+	-> Result<(), std::io::Error>
+	{
+		let tags = ScanProfile::archive("City Archive")
+			.scanner("Nikon", "LS-5000")
+			.document_name("Roll 12")
+			.page(3, 36)
+			.to_tags(&Endian::Little)?;
+
+		assert!(tags.contains(&ExifTag::XResolution(vec![ARCHIVE_RESOLUTION, 1])));
+		assert!(tags.contains(&ExifTag::ResolutionUnit(vec![2])));
+		assert!(tags.contains(&ExifTag::PageNumber(vec![3, 36])));
+		assert!(tags.contains(&ExifTag::DocumentName("Roll 12".to_string())));
+		assert!(tags.contains(&ExifTag::Artist("City Archive".to_string())));
+		assert!(!tags.iter().any(|tag| matches!(tag, ExifTag::Copyright(_))));
+
+		assert!(ScanProfile::new().page(36, 36).to_tags(&Endian::Little).is_err());
+		assert!(ScanProfile::new().page(36, 0).to_tags(&Endian::Little).is_ok());
+		assert!(ScanProfile::new().resolution(0).to_tags(&Endian::Little).is_err());
+		assert_eq!("ColorReversal".parse::<FilmType>()?, FilmType::ColorReversal);
+		return Ok(());
+	}
+}
@@ -0,0 +1,502 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! The fields file managers show for images, i.e. macOS Finder (Get Info and
+//! Spotlight, via its image importer) and Windows Explorer (Details pane and
+//! properties, via the Windows Property System). Both only look at some of
+//! the places a field may be stored in, so tags that are perfectly valid
+//! EXIF often don't show up. `ShellProperties` writes each field to all the
+//! places the file managers read it from:
+//!
+//! | Field        | Written to                              | Explorer   | Finder            |
+//! |--------------|-----------------------------------------|------------|-------------------|
+//! | `title`      | `ImageDescription`, `XPTitle`           | Title      | Description       |
+//! | `author`     | `Artist`, `XPAuthor`                    | Authors    | Authors           |
+//! | `keywords`   | `XPKeywords`                            | Tags       |                   |
+//! | `comment`    | `XPComment`                             | Comments   |                   |
+//! | `copyright`  | `Copyright`                             | Copyright  | Copyright         |
+//! | `rating`     | `Rating`, `RatingPercent`, `xmp:Rating` | Rating     | Rating (XMP only) |
+//! | `date_taken` | `DateTimeOriginal`                      | Date taken | Content created   |
+//!
+//! Some details the file managers are picky about:
+//! - Explorer ignores `XPTitle` and `XPAuthor` if `ImageDescription` and
+//!   `Artist` are present, so both get the same value.
+//! - The `XP` tags are UTF-16LE strings with a terminating NUL character,
+//!   stored as bytes regardless of the byte order of the EXIF data (see
+//!   `encode_xp_string`). Keywords are separated by semicolons.
+//! - Explorer only shows dates in the exact form `YYYY:MM:DD HH:MM:SS`.
+//! - Neither reads the EXIF data of PNG files from the zTXt or tEXt chunks
+//!   little_exif writes by default, only from eXIf chunks (see
+//!   `PngExifChunk`).
+//! - Neither reads the EXIF data of GIF files. The XMP rating is only
+//!   written to the files supporting XMP (see the `xmp` module).
+//!
+//! `ShellProperties::read_from_vec` reads the fields the way the given file
+//! manager does, which is what the compatibility tests check the written
+//! files against.
+//!
+//! # Examples
+//! ```no_run
+//! use little_exif::shell_properties::ShellProperties;
+//!
+//! ShellProperties::new()
+//!     .title("Sunset at the lake")
+//!     .author("Jane Doe")
+//!     .keywords(&["sunset", "lake"])
+//!     .rating(4)
+//!     .write_to_file(std::path::Path::new("image.png"))
+//!     .unwrap();
+//! ```
+
+use std::path::Path;
+
+use crate::date_inference::parse_exif_date_millis;
+use crate::exif_tag::ExifTag;
+use crate::filetype::FileExtension;
+use crate::general_file_io::io_error;
+use crate::general_file_io::write_vec_to_file;
+use crate::metadata::Metadata;
+use crate::png;
+use crate::write_options::PngExifChunk;
+use crate::write_options::WriteOptions;
+use crate::xmp;
+use crate::xmp::Namespace;
+
+const XMP_NAMESPACE: Namespace = ("xmp", "http://ns.adobe.com/xap/1.0/");
+
+/// The file managers whose view of the fields `ShellProperties` emulates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum
+FileManager
+{
+	/// macOS Finder and Spotlight
+	Finder,
+
+	/// Windows Explorer
+	Explorer,
+}
+
+/// Checks whether the file manager shows the metadata of files of the given
+/// type at all. Some types require extensions that don't come with every
+/// installation, e.g. the HEIF, WebP and Raw Image Extensions on Windows.
+/// For PNG files, the EXIF data also has to be stored in an eXIf chunk.
+pub fn
+shows_metadata
+(
+	_file_manager: FileManager,
+	file_type:     FileExtension
+)
+-> bool
+{
+	return file_type != FileExtension::GIF;
+}
+
+/// Encodes the text as value of the `XP` tags (e.g. `XPTitle`), i.e. as
+/// UTF-16LE with a terminating NUL character
+pub fn
+encode_xp_string
+(
+	text: &str
+)
+-> Vec<u8>
+{
+	return text.encode_utf16()
+		.chain(std::iter::once(0))
+		.flat_map(|unit| unit.to_le_bytes())
+		.collect();
+}
+
+/// Decodes the value of the `XP` tags, see `encode_xp_string`. Returns
+/// `None` if it is not valid UTF-16.
+pub fn
+decode_xp_string
+(
+	value: &[u8]
+)
+-> Option<String>
+{
+	let units = value.chunks_exact(2)
+		.map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+		.take_while(|unit| *unit != 0)
+		.collect::<Vec<u16>>();
+	return String::from_utf16(&units).ok();
+}
+
+/// The value of `RatingPercent` Windows uses for the number of stars
+fn
+rating_percent
+(
+	rating: u8
+)
+-> u16
+{
+	match rating
+	{
+		0 => 0,
+		1 => 1,
+		5 => 99,
+		_ => (rating as u16 - 1) * 25,
+	}
+}
+
+/// The fields shown by file managers, see the module documentation. Fields
+/// that are `None` (or empty keywords) don't change the image when writing.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct
+ShellProperties
+{
+	pub title:      Option<String>,
+	pub author:     Option<String>,
+	pub keywords:   Vec<String>,
+	pub comment:    Option<String>,
+	pub copyright:  Option<String>,
+	pub rating:     Option<u8>,                                                 // 0 to 5 stars
+	pub date_taken: Option<String>,                                             // YYYY:MM:DD HH:MM:SS
+}
+
+impl
+ShellProperties
+{
+	/// Constructs properties without any fields
+	pub fn
+	new()
+	-> ShellProperties
+	{
+		ShellProperties::default()
+	}
+
+	/// Sets the title (Finder: description)
+	pub fn
+	title
+	(
+		mut self,
+		title: &str
+	)
+	-> ShellProperties
+	{
+		self.title = Some(title.to_string());
+		self
+	}
+
+	/// Sets the author
+	pub fn
+	author
+	(
+		mut self,
+		author: &str
+	)
+	-> ShellProperties
+	{
+		self.author = Some(author.to_string());
+		self
+	}
+
+	/// Sets the keywords (Explorer: tags)
+	pub fn
+	keywords
+	(
+		mut self,
+		keywords: &[&str]
+	)
+	-> ShellProperties
+	{
+		self.keywords = keywords.iter().map(|keyword| keyword.to_string()).collect();
+		self
+	}
+
+	/// Sets the comment
+	pub fn
+	comment
+	(
+		mut self,
+		comment: &str
+	)
+	-> ShellProperties
+	{
+		self.comment = Some(comment.to_string());
+		self
+	}
+
+	/// Sets the copyright notice
+	pub fn
+	copyright
+	(
+		mut self,
+		copyright: &str
+	)
+	-> ShellProperties
+	{
+		self.copyright = Some(copyright.to_string());
+		self
+	}
+
+	/// Sets the rating in stars, from 0 (unrated) to 5
+	pub fn
+	rating
+	(
+		mut self,
+		rating: u8
+	)
+	-> ShellProperties
+	{
+		self.rating = Some(rating);
+		self
+	}
+
+	/// Sets the date and time the image was taken, in the form
+	/// `YYYY:MM:DD HH:MM:SS`
+	pub fn
+	date_taken
+	(
+		mut self,
+		date_taken: &str
+	)
+	-> ShellProperties
+	{
+		self.date_taken = Some(date_taken.to_string());
+		self
+	}
+
+	/// Checks the rating, the date and the keywords
+	fn
+	check
+	(
+		&self
+	)
+	-> Result<(), std::io::Error>
+	{
+		if self.rating.is_some_and(|rating| rating > 5)
+		{
+			return io_error!(InvalidInput, "The rating has to be between 0 and 5 stars!");
+		}
+		if let Some(date_taken) = &self.date_taken
+		{
+			if date_taken.len() != 19 || parse_exif_date_millis(date_taken, None).is_none()
+			{
+				return io_error!(InvalidInput, format!("Date '{}' is not of the form YYYY:MM:DD HH:MM:SS!", date_taken));
+			}
+		}
+		if self.keywords.iter().any(|keyword| keyword.contains(';'))
+		{
+			return io_error!(InvalidInput, "Keywords must not contain semicolons!");
+		}
+		return Ok(());
+	}
+
+	/// Sets the EXIF tags of the fields that are set, see the module
+	/// documentation. The XMP rating is not included.
+	/// - If the rating exceeds 5 stars, the date is not of the form
+	///   `YYYY:MM:DD HH:MM:SS` or a keyword contains a semicolon, an error
+	///   of kind `InvalidInput` gets returned and the metadata is not changed.
+	pub fn
+	apply
+	(
+		&self,
+		metadata: &mut Metadata
+	)
+	-> Result<(), std::io::Error>
+	{
+		self.check()?;
+
+		if let Some(title) = &self.title
+		{
+			metadata.set_tag(ExifTag::ImageDescription(title.clone()));
+			metadata.set_tag(ExifTag::XPTitle(encode_xp_string(title)));
+		}
+		if let Some(author) = &self.author
+		{
+			metadata.set_tag(ExifTag::Artist(author.clone()));
+			metadata.set_tag(ExifTag::XPAuthor(encode_xp_string(author)));
+		}
+		if !self.keywords.is_empty()
+		{
+			metadata.set_tag(ExifTag::XPKeywords(encode_xp_string(&self.keywords.join(";"))));
+		}
+		if let Some(comment) = &self.comment
+		{
+			metadata.set_tag(ExifTag::XPComment(encode_xp_string(comment)));
+		}
+		if let Some(copyright) = &self.copyright
+		{
+			metadata.set_tag(ExifTag::Copyright(copyright.clone()));
+		}
+		if let Some(rating) = self.rating
+		{
+			metadata.set_tag(ExifTag::Rating(vec![rating as u16]));
+			metadata.set_tag(ExifTag::RatingPercent(vec![rating_percent(rating)]));
+		}
+		if let Some(date_taken) = &self.date_taken
+		{
+			metadata.set_tag(ExifTag::DateTimeOriginal(date_taken.clone()));
+		}
+		return Ok(());
+	}
+
+	/// Reads the fields of the image the way the given file manager does,
+	/// see the module documentation. Fields the file manager doesn't show
+	/// are left empty.
+	/// - If the type of the image can't be determined, an error of kind
+	///   `Unsupported` gets returned.
+	pub fn
+	read_from_vec
+	(
+		file_buffer:  &Vec<u8>,
+		file_manager: FileManager
+	)
+	-> Result<ShellProperties, std::io::Error>
+	{
+		let file_type = match FileExtension::from_signature(file_buffer)
+		{
+			Some(file_type) => file_type,
+			None            => return io_error!(Unsupported, "Unsupported file type!"),
+		};
+		if !shows_metadata(file_manager, file_type)
+		{
+			return Ok(ShellProperties::default());
+		}
+
+		let xmp_rating = match file_type.capabilities().xmp
+		{
+			true  => xmp::read_xmp_from_vec(file_buffer)?
+				.and_then(|packet| xmp::get_property(&packet, XMP_NAMESPACE, "Rating"))
+				.and_then(|rating| rating.parse::<u8>().ok()),
+			false => None,
+		};
+
+		let exif_shown = !matches!(file_type, FileExtension::PNG { .. }) || png::has_eXIf_chunk(file_buffer);
+		let metadata = match exif_shown
+		{
+			true  => Metadata::new_from_vec(file_buffer, file_type)?,
+			false => Metadata::new(),
+		};
+		let get_string = |tag: ExifTag| metadata.get_string_value(&tag)
+			.map(|value| value.trim_end_matches('\0').to_string());
+		let get_xp_string = |tag: ExifTag| match metadata.get_tag(&tag)
+		{
+			Some(ExifTag::XPTitle(value))    |
+			Some(ExifTag::XPComment(value))  |
+			Some(ExifTag::XPAuthor(value))   |
+			Some(ExifTag::XPKeywords(value)) |
+			Some(ExifTag::XPSubject(value))  => decode_xp_string(value),
+			_                                => None,
+		};
+		let exif_rating = match metadata.get_tag(&ExifTag::Rating(Vec::new()))
+		{
+			Some(ExifTag::Rating(values)) => values.first().and_then(|rating| u8::try_from(*rating).ok()),
+			_                             => None,
+		};
+
+		let title      = get_string(ExifTag::ImageDescription(String::new()));
+		let author     = get_string(ExifTag::Artist(String::new()));
+		let copyright  = get_string(ExifTag::Copyright(String::new()));
+		let date_taken = get_string(ExifTag::DateTimeOriginal(String::new()));
+
+		return Ok(match file_manager
+		{
+			FileManager::Explorer => ShellProperties
+			{
+				title:    title.or_else(|| get_xp_string(ExifTag::XPTitle(Vec::new()))),
+				author:   author.or_else(|| get_xp_string(ExifTag::XPAuthor(Vec::new()))),
+				keywords: get_xp_string(ExifTag::XPKeywords(Vec::new()))
+					.map(|keywords| keywords.split(';').map(|keyword| keyword.trim().to_string()).filter(|keyword| !keyword.is_empty()).collect())
+					.unwrap_or_default(),
+				comment:  get_xp_string(ExifTag::XPComment(Vec::new())),
+				copyright,
+				rating:   xmp_rating.or(exif_rating),
+				date_taken,
+			},
+			FileManager::Finder => ShellProperties
+			{
+				title,
+				author,
+				keywords: Vec::new(),
+				comment:  None,
+				copyright,
+				rating:   xmp_rating,
+				date_taken,
+			},
+		});
+	}
+
+	/// Writes the fields to the EXIF data of the image and the rating to its
+	/// XMP packet, if the type of the image supports XMP. The EXIF data of
+	/// PNG files is stored in an eXIf chunk, see the module documentation.
+	/// - If a field is invalid, an error of kind `InvalidInput` gets
+	///   returned, see `apply`.
+	/// - If the type of the image can't be determined or doesn't support
+	///   writing, an error of kind `Unsupported` gets returned.
+	/// - In case of an error, the image is not changed.
+	pub fn
+	write_to_vec
+	(
+		&self,
+		file_buffer: &mut Vec<u8>
+	)
+	-> Result<(), std::io::Error>
+	{
+		self.check()?;
+
+		let file_type = match FileExtension::from_signature(file_buffer)
+		{
+			Some(file_type) if file_type.capabilities().write => file_type,
+			_ => return io_error!(Unsupported, "Unsupported file type!"),
+		};
+
+		let mut metadata = Metadata::new_from_vec(file_buffer, file_type)?;
+		self.apply(&mut metadata)?;
+
+		let mut new_buffer = file_buffer.clone();
+		if let (Some(rating), true) = (self.rating, file_type.capabilities().xmp)
+		{
+			let packet = xmp::read_xmp_from_vec(&new_buffer)?;
+			let packet = xmp::set_properties(packet.as_deref(), XMP_NAMESPACE, &[("Rating", Some(rating.to_string()))])?;
+			xmp::write_xmp_to_vec(&mut new_buffer, &packet)?;
+		}
+		metadata.write_to_vec_with_options(&mut new_buffer, file_type, &WriteOptions::new().png_exif_chunk(PngExifChunk::eXIf))?;
+
+		*file_buffer = new_buffer;
+		return Ok(());
+	}
+
+	/// Writes the fields to the image at the given path, see `write_to_vec`
+	pub fn
+	write_to_file
+	(
+		&self,
+		path: &Path
+	)
+	-> Result<(), std::io::Error>
+	{
+		let mut file_buffer = std::fs::read(path)?;
+		self.write_to_vec(&mut file_buffer)?;
+		return write_vec_to_file(path, &file_buffer);
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::shell_properties::decode_xp_string;
+	use crate::shell_properties::encode_xp_string;
+	use crate::shell_properties::rating_percent;
+
+	#[test]
+	fn
+	xp_strings()
+	{
+		assert_eq!(encode_xp_string("Hé"), vec![b'H', 0, 0xe9, 0, 0, 0]);
+		assert_eq!(decode_xp_string(&encode_xp_string("Sonnenuntergang 🌅")).as_deref(), Some("Sonnenuntergang 🌅"));
+
+		// Some writers omit the NUL character or pad with an odd byte
+		assert_eq!(decode_xp_string(&[b'a', 0, b'b', 0, 0]).as_deref(), Some("ab"));
+		assert_eq!(decode_xp_string(&[0x00, 0xd8, 0, 0]), None);
+	}
+
+	#[test]
+	fn
+	windows_rating_percent()
+	{
+		let percents = (0..=5).map(rating_percent).collect::<Vec<u16>>();
+		assert_eq!(percents, vec![0, 1, 25, 50, 75, 99]);
+	}
+}
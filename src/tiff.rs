@@ -0,0 +1,1027 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! TIFF files are the structure the EXIF data itself is made of: IFD0 holds
+//! the tags describing the image (e.g. `Artist`) and points to the ExifIFD
+//! and GPSIFD, so the metadata is read and written directly in the file
+//! instead of in a separate chunk or segment.
+//!
+//! Besides metadata, IFD0 holds the entries describing the image itself,
+//! like its dimensions and where its strips or tiles are located. These
+//! belong to the container: They are hidden when reading and kept as they
+//! are when writing, see `IMAGE_STRUCTURE_TAGS`.
+//!
+//! Writing never moves the image data, as its offsets are spread across the
+//! file. Instead, a new IFD0 (keeping the image structure entries and the
+//! link to the next IFD, e.g. the next page) is appended together with its
+//! SubIFDs, and the TIFF header is changed to point to it. If the previous
+//! IFD0 is located at the end of the file (as after a previous write), it
+//! gets replaced, so that writing over and over again doesn't let the file
+//! grow. Otherwise the previous IFDs are left behind unreferenced.
+//!
+//! Pyramid TIFFs (e.g. in mapping and slide imaging) store the image in
+//! several resolutions: Either as a chain of IFDs or as SubIFDs of the full
+//! resolution image, with the lower resolutions marked as such by
+//! `NewSubfileType`. The metadata belongs to the base layer, i.e. the first
+//! IFD of the chain that is not a reduced-resolution image. This usually is
+//! IFD0, but some files start with an overview. All other IFDs of the
+//! pyramid are kept where they are, only the link pointing to the base layer
+//! is changed when writing. If the base layer is not IFD0, its previous
+//! version is always left behind, as the extent of the IFDs in front of it
+//! is unknown.
+//!
+//! As a safety net, every write checks afterwards that the strips, tiles and
+//! JPEG thumbnails of all IFDs reachable from the header are still the same,
+//! still lie within the file and don't overlap the written IFDs. Otherwise
+//! the write fails with an error of kind `InvalidData` instead of producing
+//! a file whose image data is broken. Data that already exceeded the file
+//! before the write is not checked, as it can't get any more broken.
+
+use std::io::Read;
+use std::io::Seek;
+use std::path::Path;
+
+use crate::endian::*;
+use crate::exif_tag_format::ExifTagFormat;
+use crate::general_file_io::*;
+use crate::vfs::FileSystem;
+use crate::vfs::ReadSeek;
+
+pub(crate) const TIFF_SIGNATURE_LITTLE_ENDIAN: [u8; 4] = [0x49, 0x49, 0x2a, 0x00];   // "II*\0"
+pub(crate) const TIFF_SIGNATURE_BIG_ENDIAN:    [u8; 4] = [0x4d, 0x4d, 0x00, 0x2a];   // "MM\0*"
+
+/// The signatures of Olympus ORF files (see `orf`), which are TIFF files
+/// with a different magic
+pub(crate) const ORF_SIGNATURES: [[u8; 4]; 3] = [
+	[0x49, 0x49, 0x52, 0x4f],                                                   // "IIRO"
+	[0x49, 0x49, 0x52, 0x53],                                                   // "IIRS"
+	[0x4d, 0x4d, 0x4f, 0x52],                                                   // "MMOR"
+];
+
+/// The signature of Panasonic RW2 files (see `rw2`), which are little endian
+/// TIFF files with the magic 0x55 instead of 42
+pub(crate) const RW2_SIGNATURE: [u8; 4] = [0x49, 0x49, 0x55, 0x00];                // "IIU\0"
+
+const IFD_FORMAT:                     u16 = 0x000d;                              // Offset of an IFD, treated like INT32U
+const LONG_FORMAT:                    u16 = 0x0004;
+
+const NEW_SUBFILE_TYPE:               u16 = 0x00fe;
+const SUBFILE_TYPE:                   u16 = 0x00ff;
+const IMAGE_WIDTH:                    u16 = 0x0100;
+const IMAGE_LENGTH:                   u16 = 0x0101;
+const STRIP_OFFSETS:                  u16 = 0x0111;
+const STRIP_BYTE_COUNTS:              u16 = 0x0117;
+const FREE_OFFSETS:                   u16 = 0x0120;
+const FREE_BYTE_COUNTS:               u16 = 0x0121;
+const TILE_OFFSETS:                   u16 = 0x0144;
+const TILE_BYTE_COUNTS:               u16 = 0x0145;
+const SUB_IFDS:                       u16 = 0x014a;
+const JPEG_INTERCHANGE_FORMAT:        u16 = 0x0201;
+const JPEG_INTERCHANGE_FORMAT_LENGTH: u16 = 0x0202;
+
+/// The tags pointing to the SubIFDs holding metadata
+const SUBIFD_POINTER_TAGS: [u16; 3] = [
+	0x8769,                                                                     // ExifOffset
+	0x8825,                                                                     // GPSInfo
+	0xa005,                                                                     // InteropOffset
+];
+
+/// The tags of IFD0 that describe the image itself instead of being
+/// metadata (TIFF 6.0 and its extensions for JPEG compression and tiles)
+const IMAGE_STRUCTURE_TAGS: [u16; 46] = [
+	NEW_SUBFILE_TYPE, SUBFILE_TYPE,
+	IMAGE_WIDTH, IMAGE_LENGTH,
+	0x0102, 0x0103, 0x0106, 0x0107,                                             // BitsPerSample, Compression, PhotometricInterpretation, Threshholding
+	0x0108, 0x0109, 0x010a,                                                     // CellWidth, CellLength, FillOrder
+	STRIP_OFFSETS,
+	0x0115, 0x0116,                                                             // SamplesPerPixel, RowsPerStrip
+	STRIP_BYTE_COUNTS,
+	0x0118, 0x0119, 0x011c,                                                     // MinSampleValue, MaxSampleValue, PlanarConfiguration
+	FREE_OFFSETS, FREE_BYTE_COUNTS,
+	0x0122, 0x0123, 0x0124, 0x0125,                                             // GrayResponseUnit, GrayResponseCurve, T4Options, T6Options
+	0x012d, 0x013d, 0x0140, 0x0141,                                             // TransferFunction, Predictor, ColorMap, HalftoneHints
+	0x0142, 0x0143,                                                             // TileWidth, TileLength
+	TILE_OFFSETS, TILE_BYTE_COUNTS, SUB_IFDS,
+	0x014c, 0x0152, 0x0153, 0x0154, 0x0155,                                     // InkSet, ExtraSamples, SampleFormat, SMinSampleValue, SMaxSampleValue
+	0x015b, 0x0200,                                                             // JPEGTables, JPEGProc
+	JPEG_INTERCHANGE_FORMAT, JPEG_INTERCHANGE_FORMAT_LENGTH,
+	0x0211, 0x0212, 0x0213, 0x0214,                                             // YCbCrCoefficients, YCbCrSubSampling, YCbCrPositioning, ReferenceBlackWhite
+];
+
+/// An entry of an IFD with its value as raw bytes in the byte order of the
+/// data it has been read from
+#[derive(Debug, Clone)]
+struct
+IfdEntry
+{
+	tag:             u16,
+	format:          u16,
+	component_count: u32,
+	value:           Vec<u8>,
+}
+
+/// An IFD together with the SubIFDs its pointer entries point to, given by
+/// the tag of the pointer. The pointers themselves are not part of the
+/// entries, as their values depend on where the SubIFDs get written to
+#[derive(Debug, Clone, Default)]
+struct
+Directory
+{
+	entries:         Vec<IfdEntry>,
+	sub_directories: Vec<(u16, Directory)>,
+}
+
+/// A range of the file holding image data, given by the tag of the offsets
+/// referencing it and its start and end
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct
+ImageDataRange
+{
+	offsets_tag: u16,
+	start:       u64,
+	end:         u64,
+}
+
+/// Opens the TIFF file at the given path for reading
+fn
+open_file
+(
+	file_system: &dyn FileSystem,
+	path:        &Path
+)
+-> Result<Box<dyn ReadSeek>, std::io::Error>
+{
+	if !file_system.exists(path)
+	{
+		return io_error!(NotFound, "Can't open TIFF file - File does not exist!");
+	}
+
+	return file_system.open(path);
+}
+
+fn
+is_image_structure
+(
+	tag: u16
+)
+-> bool
+{
+	IMAGE_STRUCTURE_TAGS.contains(&tag)
+}
+
+/// Gets the number of bytes per component of the format and the size of the
+/// numbers a component consists of, whose bytes get swapped when changing
+/// the byte order (e.g. 8 and 4 for rationals)
+fn
+format_sizes
+(
+	format: u16
+)
+-> Option<(u64, usize)>
+{
+	if format == IFD_FORMAT
+	{
+		return Some((4, 4));
+	}
+
+	let format = ExifTagFormat::from_u16(format)?;
+	let number_size = match format
+	{
+		ExifTagFormat::RATIONAL64U | ExifTagFormat::RATIONAL64S => 4,
+		_                                                       => format.bytes_per_component() as usize,
+	};
+	return Some((format.bytes_per_component() as u64, number_size));
+}
+
+/// Checks whether the data starts with the signature of a raw format that
+/// is a TIFF file with a different magic, i.e. ORF or RW2
+fn
+has_relaxed_signature
+(
+	data: &[u8]
+)
+-> bool
+{
+	return ORF_SIGNATURES.iter().any(|signature| data.starts_with(signature))
+		|| data.starts_with(&RW2_SIGNATURE);
+}
+
+/// Checks the TIFF header, returning the byte order and the offset of IFD0.
+/// The signatures of ORF and RW2 files are accepted as well, the byte order
+/// of which is given by the first two bytes as usual
+fn
+read_header
+(
+	data: &[u8]
+)
+-> Result<(Endian, u32), std::io::Error>
+{
+	let relaxed = has_relaxed_signature(data);
+	let endian = if data.starts_with(&TIFF_SIGNATURE_LITTLE_ENDIAN) || (relaxed && data.starts_with(b"II"))
+	{
+		Endian::Little
+	}
+	else if data.starts_with(&TIFF_SIGNATURE_BIG_ENDIAN) || relaxed
+	{
+		Endian::Big
+	}
+	else
+	{
+		return io_error!(InvalidData, "Can't open TIFF file - Wrong signature!");
+	};
+
+	match endian.read::<u32>(data, 4)
+	{
+		Some(ifd0_offset) => Ok((endian, ifd0_offset)),
+		None              => io_error!(InvalidData, "TIFF header is incomplete!"),
+	}
+}
+
+/// Reads the entries of the IFD at the given offset, whose values are
+/// resolved, and the offset of the next IFD
+fn
+read_ifd
+(
+	data:       &[u8],
+	ifd_offset: u32,
+	endian:     &Endian
+)
+-> Result<(Vec<IfdEntry>, u32), std::io::Error>
+{
+	let ifd_offset = ifd_offset as usize;
+	let entry_count = match endian.read::<u16>(data, ifd_offset)
+	{
+		Some(entry_count) => entry_count as usize,
+		None              => return io_error!(InvalidData, "IFD offset exceeds TIFF data!"),
+	};
+
+	let next_ifd_offset = match endian.read::<u32>(data, ifd_offset + 2 + entry_count * 12)
+	{
+		Some(next_ifd_offset) => next_ifd_offset,
+		None                  => return io_error!(InvalidData, "Not enough data for IFD entries!"),
+	};
+
+	let mut entries = Vec::with_capacity(entry_count);
+	for index in 0..entry_count
+	{
+		let entry_start = ifd_offset + 2 + index * 12;
+		let tag             = endian.read::<u16>(data, entry_start).unwrap_or_default();
+		let format          = endian.read::<u16>(data, entry_start + 2).unwrap_or_default();
+		let component_count = endian.read::<u32>(data, entry_start + 4).unwrap_or_default();
+
+		let byte_count = match format_sizes(format)
+		{
+			Some((bytes_per_component, _)) => bytes_per_component * component_count as u64,
+			None                           => return io_error!(InvalidData, format!("Illegal format value of IFD entry {:#06x}!", tag)),
+		};
+
+		let value_start = match byte_count
+		{
+			0..=4 => entry_start as u64 + 8,
+			_     => endian.read::<u32>(data, entry_start + 8).unwrap_or_default() as u64,
+		};
+		let value = match data.get(value_start as usize..(value_start + byte_count) as usize)
+		{
+			Some(value) => value.to_vec(),
+			None        => return io_error!(InvalidData, format!("Value of IFD entry {:#06x} exceeds TIFF data!", tag)),
+		};
+
+		entries.push(IfdEntry { tag, format, component_count, value });
+	}
+
+	return Ok((entries, next_ifd_offset));
+}
+
+/// Checks whether the IFD holds a reduced-resolution version of another
+/// image, e.g. a lower layer of a pyramid or a thumbnail
+fn
+is_reduced_resolution
+(
+	entries: &[IfdEntry],
+	endian:  &Endian
+)
+-> bool
+{
+	let value = |tag: u16| entries.iter()
+		.find(|entry| entry.tag == tag)
+		.and_then(|entry| integer_values(entry, endian))
+		.and_then(|values| values.first().copied());
+
+	return value(NEW_SUBFILE_TYPE).is_some_and(|new_subfile_type| new_subfile_type & 0x1 != 0)
+		|| value(SUBFILE_TYPE) == Some(2);
+}
+
+/// Finds the IFD of the base layer (see module docs), following the chain
+/// starting at IFD0. Returns its offset and the position of the offset
+/// pointing to it: 4 for IFD0 (whose offset is part of the header),
+/// otherwise the position of the link of the previous IFD. If every IFD
+/// holds a reduced-resolution image or the chain is broken, IFD0 is used.
+fn
+find_base_layer
+(
+	data:        &[u8],
+	ifd0_offset: u32,
+	endian:      &Endian
+)
+-> Result<(u32, usize), std::io::Error>
+{
+	let mut ifd_offset = ifd0_offset;
+	let mut link_position = 4;
+	let mut visited_offsets = Vec::new();
+	while ifd_offset != 0 && !visited_offsets.contains(&ifd_offset)
+	{
+		visited_offsets.push(ifd_offset);
+		let (entries, next_ifd_offset) = match read_ifd(data, ifd_offset, endian)
+		{
+			Ok(ifd)                          => ifd,
+			Err(error) if link_position == 4 => return Err(error),
+			Err(_)                           => break,
+		};
+
+		if !is_reduced_resolution(&entries, endian)
+		{
+			return Ok((ifd_offset, link_position));
+		}
+		link_position = ifd_offset as usize + 2 + entries.len() * 12;
+		ifd_offset = next_ifd_offset;
+	}
+	return Ok((ifd0_offset, 4));
+}
+
+/// Reads the IFD at the given offset and the SubIFDs its pointer entries
+/// point to
+fn
+read_directory
+(
+	data:            &[u8],
+	ifd_offset:      u32,
+	endian:          &Endian,
+	visited_offsets: &mut Vec<u32>                                              // Of the IFDs read so far, for detecting loops
+)
+-> Result<Directory, std::io::Error>
+{
+	let (mut entries, _) = read_ifd(data, ifd_offset, endian)?;
+
+	let mut sub_directories = Vec::new();
+	for pointer_tag in SUBIFD_POINTER_TAGS
+	{
+		if let Some(index) = entries.iter().position(|entry| entry.tag == pointer_tag)
+		{
+			let pointer = entries.remove(index);
+			let sub_ifd_offset = endian.read::<u32>(&pointer.value, 0).unwrap_or_default();
+			if visited_offsets.contains(&sub_ifd_offset)
+			{
+				return io_error!(InvalidData, "SubIFD loop detected!");
+			}
+			visited_offsets.push(sub_ifd_offset);
+			sub_directories.push((pointer_tag, read_directory(data, sub_ifd_offset, endian, visited_offsets)?));
+		}
+	}
+
+	return Ok(Directory { entries, sub_directories });
+}
+
+/// Changes the byte order of the values of all entries
+fn
+convert_byte_order
+(
+	directory: &mut Directory
+)
+{
+	for entry in &mut directory.entries
+	{
+		let number_size = format_sizes(entry.format).map(|(_, number_size)| number_size).unwrap_or(1);
+		for number in entry.value.chunks_mut(number_size)
+		{
+			number.reverse();
+		}
+	}
+	for (_, sub_directory) in &mut directory.sub_directories
+	{
+		convert_byte_order(sub_directory);
+	}
+}
+
+/// Gets the values of an entry of type `INT16U` or `INT32U` (as used for
+/// offsets and byte counts), `None` for other types
+fn
+integer_values
+(
+	entry:  &IfdEntry,
+	endian: &Endian
+)
+-> Option<Vec<u64>>
+{
+	match entry.format
+	{
+		0x0003 => Some(entry.value.chunks_exact(2).map(|number| endian.read::<u16>(number, 0).unwrap_or_default() as u64).collect()),
+		0x0004 => Some(entry.value.chunks_exact(4).map(|number| endian.read::<u32>(number, 0).unwrap_or_default() as u64).collect()),
+		_      => None,
+	}
+}
+
+/// The number of bytes of an IFD with the given entries, including the
+/// values that don't fit into the entries
+fn
+ifd_size
+(
+	entries: &[IfdEntry]
+)
+-> u64
+{
+	let value_size = entries.iter()
+		.filter(|entry| entry.value.len() > 4)
+		.map(|entry| entry.value.len().next_multiple_of(2) as u64)
+		.sum::<u64>();
+	return 2 + 12 * entries.len() as u64 + 4 + value_size;
+}
+
+/// Writes the IFD with the given entries (which have to be sorted by their
+/// tag) for being placed at the given position. The values that don't fit
+/// into the entries follow the IFD, each starting on a word boundary
+fn
+serialize_ifd
+(
+	entries:         &[IfdEntry],
+	position:        u32,
+	next_ifd_offset: u32,
+	endian:          &Endian
+)
+-> Vec<u8>
+{
+	let mut ifd_data = Vec::new();
+	let mut value_data = Vec::new();
+	let value_position = position + 2 + 12 * entries.len() as u32 + 4;
+
+	endian.write(&mut ifd_data, entries.len() as u16);
+	for entry in entries
+	{
+		endian.write(&mut ifd_data, entry.tag);
+		endian.write(&mut ifd_data, entry.format);
+		endian.write(&mut ifd_data, entry.component_count);
+		if entry.value.len() <= 4
+		{
+			ifd_data.extend(&entry.value);
+			ifd_data.resize(ifd_data.len() + 4 - entry.value.len(), 0x00);
+		}
+		else
+		{
+			endian.write(&mut ifd_data, value_position + value_data.len() as u32);
+			value_data.extend(&entry.value);
+			value_data.resize(value_data.len().next_multiple_of(2), 0x00);
+		}
+	}
+	endian.write(&mut ifd_data, next_ifd_offset);
+
+	ifd_data.extend(value_data);
+	return ifd_data;
+}
+
+/// Gets the entries of the directory including the pointers to its SubIFDs,
+/// sorted by their tag as required by the TIFF specification. The pointers
+/// point to the SubIFDs placed one after the other at the given position
+fn
+entries_with_pointers
+(
+	directory:              &Directory,
+	sub_directory_position: u64,
+	endian:                 &Endian
+)
+-> Vec<IfdEntry>
+{
+	let mut entries = directory.entries.clone();
+	let mut sub_directory_position = sub_directory_position;
+	for (pointer_tag, sub_directory) in &directory.sub_directories
+	{
+		entries.push(IfdEntry
+		{
+			tag:             *pointer_tag,
+			format:          LONG_FORMAT,
+			component_count: 1,
+			value:           endian.bytes(sub_directory_position as u32),
+		});
+		sub_directory_position += directory_size(sub_directory);
+	}
+	entries.sort_by_key(|entry| entry.tag);
+	return entries;
+}
+
+/// The number of bytes of the directory including its SubIFDs
+fn
+directory_size
+(
+	directory: &Directory
+)
+-> u64
+{
+	let pointer_count = directory.sub_directories.len() as u64;
+	return ifd_size(&directory.entries) + 12 * pointer_count + directory.sub_directories.iter()
+		.map(|(_, sub_directory)| directory_size(sub_directory))
+		.sum::<u64>();
+}
+
+/// Writes the directory for being placed at the given position, followed by
+/// its SubIFDs
+fn
+serialize_directory
+(
+	directory:       &Directory,
+	position:        u32,
+	next_ifd_offset: u32,
+	endian:          &Endian
+)
+-> Vec<u8>
+{
+	let ifd_length = ifd_size(&directory.entries) + 12 * directory.sub_directories.len() as u64;
+	let entries = entries_with_pointers(directory, position as u64 + ifd_length, endian);
+
+	let mut directory_data = serialize_ifd(&entries, position, next_ifd_offset, endian);
+	for (_, sub_directory) in &directory.sub_directories
+	{
+		let sub_directory_position = position + directory_data.len() as u32;
+		directory_data.extend(serialize_directory(sub_directory, sub_directory_position, 0, endian));
+	}
+	return directory_data;
+}
+
+/// Checks whether nothing that is kept when replacing IFD0 is located after
+/// it, so that the file can be cut off at IFD0. This requires that IFD0 is
+/// the only IFD, as the extent of further IFDs (and the data they point to)
+/// is unknown, and that the image data ends before IFD0
+fn
+only_metadata_follows_ifd0
+(
+	ifd0_offset:     u32,
+	image_structure: &[IfdEntry],
+	next_ifd_offset: u32,
+	endian:          &Endian
+)
+-> bool
+{
+	if next_ifd_offset != 0 || image_structure.iter().any(|entry| entry.tag == SUB_IFDS)
+	{
+		return false;
+	}
+
+	let values = |tag: u16| match image_structure.iter().find(|entry| entry.tag == tag)
+	{
+		Some(entry) => integer_values(entry, endian),
+		None        => Some(Vec::new()),
+	};
+
+	for (offsets_tag, byte_counts_tag) in [
+		(STRIP_OFFSETS,           STRIP_BYTE_COUNTS),
+		(TILE_OFFSETS,            TILE_BYTE_COUNTS),
+		(FREE_OFFSETS,            FREE_BYTE_COUNTS),
+		(JPEG_INTERCHANGE_FORMAT, JPEG_INTERCHANGE_FORMAT_LENGTH),
+	]
+	{
+		let (Some(offsets), Some(byte_counts)) = (values(offsets_tag), values(byte_counts_tag)) else
+		{
+			return false;
+		};
+		if offsets.len() != byte_counts.len() || offsets.iter().zip(&byte_counts).any(|(offset, byte_count)| offset + byte_count > ifd0_offset as u64)
+		{
+			return false;
+		}
+	}
+	return true;
+}
+
+/// Gets the image data ranges referenced by all IFDs reachable from the
+/// header, i.e. the chain starting at IFD0 and the SubIFDs given by the
+/// `SubIFDs` entries, sorted by their position. IFDs that can't be read are
+/// skipped, as they are of no concern to the writer
+fn
+image_data_ranges
+(
+	data:   &[u8],
+	endian: &Endian
+)
+-> Vec<ImageDataRange>
+{
+	let mut ranges = Vec::new();
+	let Ok((_, ifd0_offset)) = read_header(data) else { return ranges; };
+
+	let mut ifd_offsets = vec![ifd0_offset];
+	let mut visited_offsets = Vec::new();
+	while let Some(ifd_offset) = ifd_offsets.pop()
+	{
+		if ifd_offset == 0 || visited_offsets.contains(&ifd_offset)
+		{
+			continue;
+		}
+		visited_offsets.push(ifd_offset);
+		let Ok((entries, next_ifd_offset)) = read_ifd(data, ifd_offset, endian) else { continue; };
+
+		let values = |tag: u16| entries.iter()
+			.find(|entry| entry.tag == tag)
+			.and_then(|entry| match entry.format
+			{
+				IFD_FORMAT => Some(entry.value.chunks_exact(4).map(|number| endian.read::<u32>(number, 0).unwrap_or_default() as u64).collect()),
+				_          => integer_values(entry, endian),
+			})
+			.unwrap_or_default();
+
+		for (offsets_tag, byte_counts_tag) in [
+			(STRIP_OFFSETS,           STRIP_BYTE_COUNTS),
+			(TILE_OFFSETS,            TILE_BYTE_COUNTS),
+			(JPEG_INTERCHANGE_FORMAT, JPEG_INTERCHANGE_FORMAT_LENGTH),
+		]
+		{
+			ranges.extend(values(offsets_tag).into_iter()
+				.zip(values(byte_counts_tag))
+				.map(|(start, byte_count)| ImageDataRange { offsets_tag, start, end: start + byte_count })
+			);
+		}
+		ifd_offsets.extend(values(SUB_IFDS).into_iter().map(|offset| offset as u32));
+		ifd_offsets.push(next_ifd_offset);
+	}
+
+	ranges.sort();
+	return ranges;
+}
+
+/// Checks that the image data is still intact after writing the IFDs to the
+/// given range of the file (see module docs), given the image data ranges
+/// and the length of the file before the write
+fn
+check_image_data
+(
+	file_buffer:     &[u8],
+	endian:          &Endian,
+	previous_ranges: &[ImageDataRange],
+	previous_length: u64,
+	written:         (u64, u64)
+)
+-> Result<(), std::io::Error>
+{
+	let ranges = image_data_ranges(file_buffer, endian);
+	if ranges != previous_ranges
+	{
+		return io_error!(InvalidData, "Writing metadata to TIFF file changed the offsets of the image data!");
+	}
+
+	for range in ranges.iter().filter(|range| range.end <= previous_length)
+	{
+		let kind = match range.offsets_tag
+		{
+			STRIP_OFFSETS => "Strip",
+			TILE_OFFSETS  => "Tile",
+			_             => "JPEG thumbnail",
+		};
+
+		if range.end > file_buffer.len() as u64
+		{
+			return io_error!(InvalidData, format!("{} at {:#x} exceeds TIFF data after writing metadata!", kind, range.start));
+		}
+		if range.start < written.1 && written.0 < range.end
+		{
+			return io_error!(InvalidData, format!("{} at {:#x} overlaps the written metadata!", kind, range.start));
+		}
+	}
+	return Ok(());
+}
+
+/// Replaces the IFD of the base layer (usually IFD0) and its SubIFDs by the
+/// given metadata, keeping its image structure entries and its link to the
+/// next IFD (see module docs)
+fn
+replace_metadata
+(
+	file_buffer: &mut Vec<u8>,
+	metadata:    Directory
+)
+-> Result<(), std::io::Error>
+{
+	let (endian, ifd0_offset) = read_header(file_buffer)?;
+	let (base_offset, link_position) = find_base_layer(file_buffer, ifd0_offset, &endian)?;
+	let previous_ranges = image_data_ranges(file_buffer, &endian);
+	let previous_length = file_buffer.len() as u64;
+	let (base_entries, next_ifd_offset) = read_ifd(file_buffer, base_offset, &endian)?;
+	let image_structure = base_entries.into_iter()
+		.filter(|entry| is_image_structure(entry.tag))
+		.collect::<Vec<IfdEntry>>();
+
+	if link_position == 4 && only_metadata_follows_ifd0(ifd0_offset, &image_structure, next_ifd_offset, &endian)
+	{
+		file_buffer.truncate(ifd0_offset as usize);
+	}
+
+	// The image structure of the file takes precedence over the one of the
+	// metadata, e.g. after copying the metadata from another file
+	let mut ifd0 = metadata;
+	ifd0.entries.retain(|entry| !is_image_structure(entry.tag));
+	ifd0.entries.extend(image_structure);
+
+	// IFDs have to start on a word boundary
+	file_buffer.resize(file_buffer.len().next_multiple_of(2), 0x00);
+	let position = file_buffer.len() as u64;
+	if position + directory_size(&ifd0) > u32::MAX as u64
+	{
+		return io_error!(FileTooLarge, "Can't write metadata - TIFF files are limited to 4 GiB!");
+	}
+
+	file_buffer.extend(serialize_directory(&ifd0, position as u32, next_ifd_offset, &endian));
+	file_buffer[link_position..link_position + 4].copy_from_slice(&endian.bytes(position as u32));
+	return check_image_data(file_buffer, &endian, &previous_ranges, previous_length, (position, file_buffer.len() as u64));
+}
+
+/// Combines the IFD0s of separate blocks of TIFF data into a single one: The
+/// IFD0 of the first block becomes IFD0, the IFD0s of the others become the
+/// SubIFDs given by the pointer tags (e.g. the ExifIFD), converted to the
+/// byte order of the first block if necessary. Pointers of the first IFD0
+/// and links to further IFDs are dropped, as they don't point anywhere
+/// meaningful in the combined data
+pub(crate) fn
+combine_ifds
+(
+	ifd0_data: &[u8],
+	sub_ifds:  &[(u16, &[u8])]
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	let (endian, ifd0_offset) = read_header(ifd0_data)?;
+	let (mut entries, _) = read_ifd(ifd0_data, ifd0_offset, &endian)?;
+	entries.retain(|entry| !SUBIFD_POINTER_TAGS.contains(&entry.tag));
+	let mut ifd0 = Directory { entries, sub_directories: Vec::new() };
+
+	for (pointer_tag, sub_ifd_data) in sub_ifds
+	{
+		let (sub_ifd_endian, sub_ifd_offset) = read_header(sub_ifd_data)?;
+		let (entries, _) = read_ifd(sub_ifd_data, sub_ifd_offset, &sub_ifd_endian)?;
+		let mut sub_directory = Directory { entries, sub_directories: Vec::new() };
+		if sub_ifd_endian != endian
+		{
+			convert_byte_order(&mut sub_directory);
+		}
+		ifd0.sub_directories.push((*pointer_tag, sub_directory));
+	}
+
+	let mut tiff_data = match endian
+	{
+		Endian::Little => TIFF_SIGNATURE_LITTLE_ENDIAN.to_vec(),
+		Endian::Big    => TIFF_SIGNATURE_BIG_ENDIAN.to_vec(),
+	};
+	endian.write(&mut tiff_data, 8u32);
+	tiff_data.extend(serialize_directory(&ifd0, 8, 0, &endian));
+	return Ok(tiff_data);
+}
+
+/// Reads the raw EXIF data from the TIFF data, which is the entire data, as
+/// the offsets of the metadata are relative to the start of the file. The
+/// image structure entries are removed from (the copy of) the IFD of the
+/// base layer, so that only the metadata is left, and the header of the copy
+/// is changed to point to it. Signatures other than the one of TIFF (i.e.
+/// of ORF and RW2 files) are replaced by it, as expected by the generic
+/// decoder.
+pub(crate) fn
+read_metadata
+<T: Read + Seek>
+(
+	cursor: &mut T
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	let _span = span!("parse", format = "TIFF");
+
+	let mut raw_exif_data = EXIF_HEADER.to_vec();
+	cursor.read_to_end(&mut raw_exif_data)?;
+
+	let tiff_data = &mut raw_exif_data[EXIF_HEADER.len()..];
+	let (endian, ifd0_offset) = read_header(tiff_data)?;
+	let (base_offset, _) = find_base_layer(tiff_data, ifd0_offset, &endian)?;
+	let (base_entries, _) = read_ifd(tiff_data, base_offset, &endian)?;
+
+	// Move the entries that are metadata to the front and the link to the
+	// next IFD right after them. The values stay where they are
+	let entries_start = base_offset as usize + 2;
+	let metadata_entries = (0..base_entries.len())
+		.filter(|index| !is_image_structure(base_entries[*index].tag))
+		.flat_map(|index| tiff_data[entries_start + index * 12..entries_start + (index + 1) * 12].to_vec())
+		.collect::<Vec<u8>>();
+	let next_ifd_link_start = entries_start + base_entries.len() * 12;
+	let next_ifd_link = tiff_data[next_ifd_link_start..next_ifd_link_start + 4].to_vec();
+
+	let mut base_ifd = endian.bytes((metadata_entries.len() / 12) as u16);
+	base_ifd.extend(metadata_entries);
+	base_ifd.extend(next_ifd_link);
+	tiff_data[base_offset as usize..base_offset as usize + base_ifd.len()].copy_from_slice(&base_ifd);
+	tiff_data[4..8].copy_from_slice(&endian.bytes(base_offset));
+	tiff_data[0..4].copy_from_slice(match endian
+	{
+		Endian::Little => &TIFF_SIGNATURE_LITTLE_ENDIAN,
+		Endian::Big    => &TIFF_SIGNATURE_BIG_ENDIAN,
+	});
+
+	return Ok(raw_exif_data);
+}
+
+
+
+pub(crate) fn
+file_read_metadata
+(
+	file_system: &dyn FileSystem,
+	path:        &Path
+)
+-> Result<Vec<u8>, std::io::Error>
+{
+	read_metadata(&mut open_file(file_system, path)?)
+}
+
+
+
+/// Gets the width and height of the image, as given by the IFD of the base
+/// layer
+pub(crate) fn
+image_dimensions
+(
+	file_buffer: &[u8]
+)
+-> Option<(u32, u32)>
+{
+	let (endian, ifd0_offset) = read_header(file_buffer).ok()?;
+	let (base_offset, _) = find_base_layer(file_buffer, ifd0_offset, &endian).ok()?;
+	let (entries, _) = read_ifd(file_buffer, base_offset, &endian).ok()?;
+
+	let dimension = |tag: u16| entries.iter()
+		.find(|entry| entry.tag == tag)
+		.and_then(|entry| integer_values(entry, &endian))
+		.and_then(|values| values.first().copied());
+	return Some((dimension(IMAGE_WIDTH)? as u32, dimension(IMAGE_LENGTH)? as u32));
+}
+
+
+
+/// Removes all metadata from the IFD of the base layer and the SubIFDs it
+/// points to, keeping the image structure. TIFF data without metadata is
+/// left untouched
+pub(crate) fn
+clear_metadata
+(
+	file_buffer: &mut Vec<u8>
+)
+-> Result<(), std::io::Error>
+{
+	let (endian, ifd0_offset) = read_header(file_buffer)?;
+	let (base_offset, _) = find_base_layer(file_buffer, ifd0_offset, &endian)?;
+	let (base_entries, _) = read_ifd(file_buffer, base_offset, &endian)?;
+	if base_entries.iter().all(|entry| is_image_structure(entry.tag))
+	{
+		return Ok(());
+	}
+
+	return replace_metadata(file_buffer, Directory::default());
+}
+
+
+
+pub(crate) fn
+file_clear_metadata
+(
+	file_system: &dyn FileSystem,
+	path:        &Path
+)
+-> Result<(), std::io::Error>
+{
+	let mut file_buffer = read_file_to_vec(open_file(file_system, path)?)?;
+	let original_buffer = file_buffer.clone();
+	clear_metadata(&mut file_buffer)?;
+	if file_buffer != original_buffer
+	{
+		file_system.write(path, &file_buffer)?;
+	}
+	return Ok(());
+}
+
+
+
+/// Provides the TIFF specific encoding result as vector of bytes to be used
+/// by the user. The generally encoded metadata already is TIFF structured,
+/// but only consists of the IFDs holding metadata
+pub(crate) fn
+as_u8_vec
+(
+	general_encoded_metadata: &Vec<u8>
+)
+-> Vec<u8>
+{
+	general_encoded_metadata.clone()
+}
+
+
+
+/// Writes the given generally encoded metadata to the TIFF data, replacing
+/// the metadata of the base layer and the SubIFDs it points to. The
+/// metadata gets converted to the byte order of the file if necessary
+pub(crate) fn
+write_metadata
+(
+	file_buffer:              &mut Vec<u8>,
+	general_encoded_metadata: &Vec<u8>
+)
+-> Result<(), std::io::Error>
+{
+	let (file_endian, _) = read_header(file_buffer)?;
+	let (metadata_endian, metadata_ifd0_offset) = read_header(general_encoded_metadata)?;
+
+	let mut metadata = read_directory(general_encoded_metadata, metadata_ifd0_offset, &metadata_endian, &mut vec![metadata_ifd0_offset])?;
+	if metadata_endian != file_endian
+	{
+		convert_byte_order(&mut metadata);
+	}
+
+	return replace_metadata(file_buffer, metadata);
+}
+
+
+
+/// Writes the given generally encoded metadata to the TIFF image file at
+/// the specified path (see `write_metadata`)
+pub(crate) fn
+file_write_metadata
+(
+	file_system:              &dyn FileSystem,
+	path:                     &Path,
+	general_encoded_metadata: &Vec<u8>
+)
+-> Result<(), std::io::Error>
+{
+	let mut file_buffer = read_file_to_vec(open_file(file_system, path)?)?;
+	write_metadata(&mut file_buffer, general_encoded_metadata)?;
+	file_system.write(path, &file_buffer)?;
+	return Ok(());
+}
+
+
+
+
+
+#[cfg(test)]
+mod tests
+{
+	use crate::endian::Endian;
+	use crate::tiff::check_image_data;
+	use crate::tiff::convert_byte_order;
+	use crate::tiff::image_data_ranges;
+	use crate::tiff::read_header;
+	use crate::tiff::Directory;
+	use crate::tiff::IfdEntry;
+	use crate::tiff::STRIP_OFFSETS;
+
+	#[test]
+	fn
+	relaxed_signatures()
+	{
+		// The magic is ignored for ORF files, their byte order is given by
+		// the first two bytes as usual
+		assert_eq!(read_header(b"IIRO\x08\x00\x00\x00").unwrap(), (Endian::Little, 8));
+		assert_eq!(read_header(b"IIRS\x08\x00\x00\x00").unwrap(), (Endian::Little, 8));
+		assert_eq!(read_header(b"MMOR\x00\x00\x00\x08").unwrap(), (Endian::Big, 8));
+		assert!(read_header(b"MMRO\x00\x00\x00\x08").is_err());
+
+		// RW2 files are always little endian
+		assert_eq!(read_header(b"IIU\x00\x18\x00\x00\x00").unwrap(), (Endian::Little, 24));
+		assert!(read_header(b"MM\x00U\x00\x00\x00\x18").is_err());
+	}
+
+	#[test]
+	fn
+	byte_order_conversion()
+	{
+		// Rationals consist of two numbers, strings of single bytes
+		let entry = |tag: u16, format: u16, value: &[u8]| IfdEntry { tag, format, component_count: 1, value: value.to_vec() };
+		let mut directory = Directory
+		{
+			entries:         vec![entry(0x013b, 0x0002, b"Jane\0")],
+			sub_directories: vec![(0x8769, Directory { entries: vec![entry(0x829a, 0x0005, &[0, 0, 0, 1, 0, 0, 0, 250])], sub_directories: Vec::new() })],
+		};
+		convert_byte_order(&mut directory);
+
+		assert_eq!(directory.entries[0].value, b"Jane\0");
+		assert_eq!(directory.sub_directories[0].1.entries[0].value, [1, 0, 0, 0, 250, 0, 0, 0]);
+	}
+
+	#[test]
+	fn
+	image_data_check()
+	{
+		// The sample has two strips of 12 bytes
+		let file_buffer = std::fs::read("tests/sample.tif").unwrap();
+		let length = file_buffer.len() as u64;
+		let ranges = image_data_ranges(&file_buffer, &Endian::Little);
+		assert_eq!(ranges.len(), 2);
+		assert!(ranges.iter().all(|range| range.offsets_tag == STRIP_OFFSETS && range.end - range.start == 12));
+
+		let strip = ranges[0];
+		assert!(check_image_data(&file_buffer, &Endian::Little, &ranges, length, (length, length)).is_ok());
+		assert!(check_image_data(&file_buffer, &Endian::Little, &ranges, length, (strip.end - 1, length)).is_err());
+		assert!(check_image_data(&file_buffer, &Endian::Little, &ranges[1..], length, (length, length)).is_err());
+
+		// Strips that were broken before are not checked
+		let truncated = &file_buffer[..strip.end as usize - 1];
+		assert!(check_image_data(truncated, &Endian::Little, &ranges, length, (length, length)).is_err());
+		assert!(check_image_data(truncated, &Endian::Little, &ranges, strip.end - 1, (strip.start, length)).is_ok());
+	}
+}
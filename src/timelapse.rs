@@ -0,0 +1,120 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Numbering the frames of a time-lapse (or any other series taken by an
+//! interval timer) consistently, e.g. after assembling the frame set from
+//! several memory cards or removing broken frames.
+//!
+//! `stamp_timelapse` orders the frames by the time they were taken (see the
+//! `burst` module for the tags used) and writes their position to the
+//! `ImageNumber` tag. EXIF has no other tag for the position of a shot within
+//! a sequence. The sequence numbers that cameras store in their maker notes
+//! (see `MakerNote::sequence_number`) are kept as they are, as editing maker
+//! notes is not supported.
+//!
+//! # Examples
+//! ```no_run
+//! use std::path::Path;
+//! use little_exif::timelapse::stamp_timelapse;
+//!
+//! let paths = [Path::new("frame_b.jpg"), Path::new("frame_a.jpg")];
+//! let numbers = stamp_timelapse(&paths, 1).unwrap();
+//! println!("frame_b.jpg is frame {}", numbers[0]);
+//! ```
+
+use std::path::Path;
+
+use crate::burst::capture_time_millis;
+use crate::exif_tag::ExifTag;
+use crate::general_file_io::io_error;
+use crate::metadata::Metadata;
+
+/// Numbers the frames in chronological order, starting with `first_number`.
+/// Frames taken at the same time keep their relative order. Returns the
+/// index of the first frame without a timestamp as error.
+fn
+number_frames
+(
+	timestamps:   &[Option<i64>],
+	first_number: u32
+)
+-> Result<Vec<Option<u32>>, usize>
+{
+	if let Some(index) = timestamps.iter().position(Option::is_none)
+	{
+		return Err(index);
+	}
+
+	let mut order = (0..timestamps.len()).collect::<Vec<usize>>();
+	order.sort_by_key(|index| (timestamps[*index], *index));
+
+	let mut numbers = vec![None; timestamps.len()];
+	for (position, index) in order.into_iter().enumerate()
+	{
+		numbers[index] = u32::try_from(position).ok().and_then(|position| first_number.checked_add(position));
+	}
+	return Ok(numbers);
+}
+
+/// Writes the position of each frame within the time-lapse to its
+/// `ImageNumber` tag, counting in chronological order from `first_number`
+/// (see module documentation). Returns the number of each frame, in the
+/// order of the given paths.
+/// - If one of the files can't be read or has no timestamp, an error gets
+///   returned before any file is changed. Missing timestamps are reported
+///   as `InvalidData`, numbers exceeding the range of the tag as
+///   `InvalidInput`.
+/// - If writing one of the files fails, the error gets returned and the
+///   frames before it remain stamped.
+pub fn
+stamp_timelapse
+(
+	paths:        &[&Path],
+	first_number: u32
+)
+-> Result<Vec<u32>, std::io::Error>
+{
+	let mut frames = Vec::new();
+	for path in paths
+	{
+		frames.push(Metadata::new_from_path(path)?);
+	}
+
+	let timestamps = frames.iter().map(capture_time_millis).collect::<Vec<Option<i64>>>();
+	let numbers = match number_frames(&timestamps, first_number)
+	{
+		Ok(numbers) => numbers,
+		Err(index)  => return io_error!(InvalidData, format!("Time-lapse frame {} lacks the time it was taken!", paths[index].display())),
+	};
+	let numbers = match numbers.into_iter().collect::<Option<Vec<u32>>>()
+	{
+		Some(numbers) => numbers,
+		None          => return io_error!(InvalidInput, "Frame numbers exceed the range of ImageNumber!"),
+	};
+
+	for ((path, frame), number) in paths.iter().zip(frames.iter_mut()).zip(&numbers)
+	{
+		frame.set_tag(ExifTag::ImageNumber(vec![*number]));
+		frame.write_to_file(path)?;
+	}
+
+	return Ok(numbers);
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::timelapse::number_frames;
+
+	#[test]
+	fn
+	number_frames_chronologically()
+	{
+		let timestamps = [Some(3_000), Some(1_000), Some(2_000), Some(1_000)];
+		assert_eq!(number_frames(&timestamps, 1),  Ok(vec![Some(4), Some(1), Some(3), Some(2)]));
+		assert_eq!(number_frames(&timestamps, 10), Ok(vec![Some(13), Some(10), Some(12), Some(11)]));
+		assert_eq!(number_frames(&timestamps, u32::MAX - 1).unwrap()[0], None);
+		assert_eq!(number_frames(&[Some(0), None], 1), Err(1));
+		assert_eq!(number_frames(&[], 1), Ok(Vec::new()));
+	}
+}
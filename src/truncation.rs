@@ -0,0 +1,293 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Shortening text values to the maximum length set by
+//! `WriteOptions::max_length`. Text values are the ones of `STRING` tags and
+//! of `UserComment`, whose first 8 bytes give the character code of the
+//! comment: Unicode comments are UTF-16 encoded using the byte order of the
+//! EXIF data, all others are treated as UTF-8 (which covers ASCII).
+//!
+//! Values moved to XMP (see `Truncation::MoveToXmp`) are written as
+//! properties named after the tag, in the `tiff` namespace for IFD0 tags and
+//! in the `exif` namespace for all other tags. As defined by XMP,
+//! `ImageDescription`, `Copyright` and `UserComment` become language
+//! alternatives with a default value and `Artist` an ordered array, all
+//! other values are simple properties.
+
+use crate::endian::*;
+use crate::exif_tag::ExifTag;
+use crate::exif_tag::ExifTagGroup;
+use crate::exif_tag_format::ExifTagFormat;
+use crate::export::format_value;
+use crate::generated_image::UNICODE_CHARACTER_CODE;
+use crate::write_options::Truncation;
+use crate::xmp;
+use crate::xmp::LangAlt;
+use crate::xmp::Namespace;
+use crate::xmp::XmpValue;
+use crate::xmp::DEFAULT_LANGUAGE;
+
+const TIFF_NAMESPACE: Namespace = ("tiff", "http://ns.adobe.com/tiff/1.0/");
+const EXIF_NAMESPACE: Namespace = ("exif", "http://ns.adobe.com/exif/1.0/");
+
+const ELLIPSIS: &str = "...";
+
+/// Gets the text stored in the tag, without trailing NUL characters.
+/// Returns `None` if the tag doesn't hold text.
+fn
+text
+(
+	tag:    &ExifTag,
+	endian: &Endian
+)
+-> Option<String>
+{
+	let text = match tag
+	{
+		ExifTag::UserComment(value) => {
+			let (code, comment) = value.split_at(value.len().min(UNICODE_CHARACTER_CODE.len()));
+			match code == UNICODE_CHARACTER_CODE
+			{
+				true  => String::from_utf16_lossy(&comment.chunks_exact(2).filter_map(|unit| endian.read::<u16>(unit, 0)).collect::<Vec<_>>()),
+				false => String::from_utf8_lossy(comment).to_string(),
+			}
+		},
+		_ if tag.is_string() => String::from_utf8_lossy(&tag.value_as_u8_vec(endian)).to_string(),
+		_                    => return None,
+	};
+	return Some(text.trim_end_matches('\0').to_string());
+}
+
+/// Shortens the value of the tag to at most `max_length` bytes, see
+/// `WriteOptions::max_length`. Returns `None` if the value already fits or
+/// if the tag doesn't hold text.
+pub(crate) fn
+truncate
+(
+	tag:        &ExifTag,
+	max_length: usize,
+	truncation: Truncation,
+	endian:     &Endian
+)
+-> Option<ExifTag>
+{
+	let use_ellipsis = truncation == Truncation::Ellipsis;
+
+	if let ExifTag::UserComment(value) = tag
+	{
+		let code_length = value.len().min(UNICODE_CHARACTER_CODE.len());
+		let (code, comment) = value.split_at(code_length);
+		if comment.len() <= max_length
+		{
+			return None;
+		}
+
+		let unicode = code == UNICODE_CHARACTER_CODE;
+		let mut ellipsis = Vec::new();
+		for character in ELLIPSIS.bytes()
+		{
+			match unicode
+			{
+				true  => endian.write(&mut ellipsis, character as u16),
+				false => ellipsis.push(character),
+			}
+		}
+		if !use_ellipsis || ellipsis.len() > max_length
+		{
+			ellipsis.clear();
+		}
+
+		// Neither split UTF-16 code units and surrogate pairs nor UTF-8
+		// sequences
+		let mut end = max_length - ellipsis.len();
+		if unicode
+		{
+			end -= end % 2;
+			if end >= 2 && endian.read::<u16>(comment, end - 2).is_some_and(|unit| (0xd800..0xdc00).contains(&unit))
+			{
+				end -= 2;
+			}
+		}
+		else
+		{
+			while end > 0 && (comment[end] & 0xc0) == 0x80
+			{
+				end -= 1;
+			}
+		}
+
+		return Some(ExifTag::UserComment([code, &comment[..end], &ellipsis].concat()));
+	}
+
+	let text = text(tag, endian)?;
+	if text.len() <= max_length
+	{
+		return None;
+	}
+
+	let ellipsis = match use_ellipsis && ELLIPSIS.len() <= max_length
+	{
+		true  => ELLIPSIS,
+		false => "",
+	};
+	let mut end = max_length - ellipsis.len();
+	while !text.is_char_boundary(end)
+	{
+		end -= 1;
+	}
+	return tag.with_string_value(&format!("{}{}", &text[..end], ellipsis));
+}
+
+/// Gets the namespace of the XMP property a value gets moved to
+fn
+xmp_namespace
+(
+	tag: &ExifTag
+)
+-> Namespace
+{
+	match tag.get_group()
+	{
+		ExifTagGroup::IFD0 => TIFF_NAMESPACE,
+		_                  => EXIF_NAMESPACE,
+	}
+}
+
+/// Gets the XMP value the text of the tag gets moved to
+fn
+xmp_value
+(
+	tag:  &ExifTag,
+	text: String
+)
+-> XmpValue
+{
+	match tag
+	{
+		ExifTag::ImageDescription(_) |
+		ExifTag::Copyright(_)        |
+		ExifTag::UserComment(_)      => XmpValue::LangAlt(LangAlt::new().with(DEFAULT_LANGUAGE, &text)),
+		ExifTag::Artist(_)           => XmpValue::Seq(vec![text]),
+		_                            => XmpValue::Simple(text),
+	}
+}
+
+/// Gets the XMP value of the tag: Its text (see `xmp_value`) or, for
+/// numbers, the components formatted by `export::format_value`, as ordered
+/// array if there are several. Returns `None` for undefined data.
+fn
+tag_value
+(
+	tag:    &ExifTag,
+	endian: &Endian
+)
+-> Option<XmpValue>
+{
+	if let Some(text) = text(tag, endian)
+	{
+		return Some(xmp_value(tag, text));
+	}
+	if tag.format() == ExifTagFormat::UNDEF
+	{
+		return None;
+	}
+
+	let mut components = format_value(tag)
+		.split(' ')
+		.filter(|component| !component.is_empty())
+		.map(|component| component.to_string())
+		.collect::<Vec<String>>();
+	match components.len()
+	{
+		0 => None,
+		1 => components.pop().map(XmpValue::Simple),
+		_ => Some(XmpValue::Seq(components)),
+	}
+}
+
+/// Sets the values of the given tags in the packet, or in a new one if there
+/// is none. Returns `None` if there is neither a packet nor a tag.
+pub(crate) fn
+set_xmp_values
+(
+	packet: Option<String>,
+	tags:   &[ExifTag],
+	endian: &Endian
+)
+-> Result<Option<String>, std::io::Error>
+{
+	let mut packet = packet;
+	for namespace in [TIFF_NAMESPACE, EXIF_NAMESPACE]
+	{
+		let values = tags.iter()
+			.filter(|tag| xmp_namespace(tag) == namespace)
+			.map(|tag| (tag.name(), tag_value(tag, endian)))
+			.collect::<Vec<_>>();
+
+		if !values.is_empty()
+		{
+			let values = values.iter()
+				.map(|(name, value)| (name.as_str(), value.clone()))
+				.collect::<Vec<_>>();
+			packet = Some(xmp::set_values(packet.as_deref(), namespace, &values)?);
+		}
+	}
+	return Ok(packet);
+}
+
+/// Writes the complete values of the given tags to the XMP packet of the
+/// file, or to a new one if there is none. Other properties of the packet
+/// are kept.
+pub(crate) fn
+write_to_xmp
+(
+	file_buffer: &mut Vec<u8>,
+	tags:        &[ExifTag],
+	endian:      &Endian
+)
+-> Result<(), std::io::Error>
+{
+	match set_xmp_values(xmp::read_xmp_from_vec(file_buffer)?, tags, endian)?
+	{
+		Some(packet) => xmp::write_xmp_to_vec(file_buffer, &packet),
+		None         => Ok(()),
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::endian::Endian;
+	use crate::exif_tag::ExifTag;
+	use crate::truncation::truncate;
+	use crate::write_options::Truncation;
+
+	#[test]
+	fn
+	truncate_text_values()
+	{
+		let endian = Endian::Little;
+		let description = ExifTag::ImageDescription("Über den Wolken".to_string());
+
+		assert_eq!(truncate(&description, 16, Truncation::Cut, &endian), None);
+		assert_eq!(truncate(&description, 9,  Truncation::Cut, &endian),      Some(ExifTag::ImageDescription("Über den".to_string())));
+		assert_eq!(truncate(&description, 9,  Truncation::Ellipsis, &endian), Some(ExifTag::ImageDescription("Über ...".to_string())));
+		assert_eq!(truncate(&description, 1,  Truncation::Cut, &endian),      Some(ExifTag::ImageDescription(String::new())));
+		assert_eq!(truncate(&description, 2,  Truncation::Ellipsis, &endian), Some(ExifTag::ImageDescription("Ü".to_string())));
+		assert_eq!(truncate(&description, 9,  Truncation::MoveToXmp, &endian), Some(ExifTag::ImageDescription("Über den".to_string())));
+		assert_eq!(truncate(&ExifTag::ISO(vec![100, 200]), 1, Truncation::Cut, &endian), None);
+
+		// The character code is kept and doesn't count towards the length
+		let ascii_comment = ExifTag::UserComment(b"ASCII\0\0\0Lighthouse".to_vec());
+		assert_eq!(truncate(&ascii_comment, 10, Truncation::Cut, &endian), None);
+		assert_eq!(truncate(&ascii_comment, 8,  Truncation::Ellipsis, &endian), Some(ExifTag::UserComment(b"ASCII\0\0\0Light...".to_vec())));
+
+		// Surrogate pairs are not split
+		let unicode_comment = ExifTag::UserComment(b"UNICODE\0A\0\x3d\xd8\x00\xde".to_vec());
+		assert_eq!(truncate(&unicode_comment, 5, Truncation::Cut, &endian), Some(ExifTag::UserComment(b"UNICODE\0A\0".to_vec())));
+		assert_eq!(
+			truncate(&ExifTag::UserComment(b"UNICODE\0\0L\0i\0g\0h\0t".to_vec()), 8, Truncation::Ellipsis, &Endian::Big),
+			Some(ExifTag::UserComment(b"UNICODE\0\0L\0.\0.\0.".to_vec()))
+		);
+	}
+}
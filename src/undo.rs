@@ -0,0 +1,146 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Undoing the removal of metadata, see `Metadata::clear_metadata_with_undo`
+//! and `Metadata::file_clear_metadata_with_undo`.
+//!
+//! An `UndoToken` only holds the removed EXIF data as it was stored in the
+//! file, not the whole image, so keeping it around (e.g. on the undo stack of
+//! a GUI application) is cheap. Re-applying it writes the data back without
+//! decoding and re-encoding it, so the tags are restored byte by byte. The
+//! position of the data within the file may differ from the original one.
+
+use std::path::Path;
+
+use crate::filetype::FileExtension;
+use crate::general_file_io::check_writable;
+use crate::general_file_io::write_vec_to_file;
+use crate::metadata::Metadata;
+use crate::redaction::RedactionReport;
+use crate::write_options::WriteOptions;
+
+/// The EXIF data removed by a clear operation, which can be written back to
+/// undo the removal
+#[derive(Debug, Clone, PartialEq)]
+pub struct
+UndoToken
+{
+	file_type:    FileExtension,
+	removed_data: Option<Vec<u8>>,
+}
+
+impl
+UndoToken
+{
+	pub(crate) fn
+	new
+	(
+		file_type:    FileExtension,
+		removed_data: Option<Vec<u8>>
+	)
+	-> UndoToken
+	{
+		UndoToken { file_type, removed_data }
+	}
+
+	/// The type of the image the data has been removed from
+	pub fn
+	file_type
+	(
+		&self
+	)
+	-> FileExtension
+	{
+		self.file_type
+	}
+
+	/// The removed EXIF data (the TIFF data, as returned by
+	/// `Metadata::encode`) or `None` if there was nothing to remove, in which
+	/// case undoing does nothing
+	pub fn
+	removed_data
+	(
+		&self
+	)
+	-> Option<&[u8]>
+	{
+		self.removed_data.as_deref()
+	}
+
+	/// Decodes the removed EXIF data, e.g. for showing what the undo would
+	/// bring back
+	/// - If there was nothing to remove or the data can't be decoded, an
+	///   error gets returned.
+	pub fn
+	removed_metadata
+	(
+		&self
+	)
+	-> Result<Metadata, std::io::Error>
+	{
+		match &self.removed_data
+		{
+			Some(removed_data) => Metadata::decode(removed_data),
+			None               => Err(std::io::Error::new(std::io::ErrorKind::NotFound, "No metadata has been removed!")),
+		}
+	}
+
+	/// Reports what has been removed, e.g. for compliance logging. If the
+	/// removed data can't be decoded, the report only contains its size.
+	pub fn
+	redaction_report
+	(
+		&self
+	)
+	-> RedactionReport
+	{
+		let removed_tags = self.removed_metadata()
+			.map(|metadata| metadata.data().clone())
+			.unwrap_or_default();
+		let removed_data_bytes = self.removed_data.as_ref().map_or(0, |removed_data| removed_data.len());
+		return RedactionReport::new(self.file_type, &removed_tags, removed_data_bytes);
+	}
+
+	/// Writes the removed EXIF data back to the given image data in memory,
+	/// replacing any EXIF data that has been added in the meantime. PNG files
+	/// get the data stored in a zTXt chunk.
+	/// - If the data is not a valid image of the type the data has been
+	///   removed from, an error gets returned and the data is left unchanged.
+	pub fn
+	apply_to_vec
+	(
+		&self,
+		file_buffer: &mut Vec<u8>
+	)
+	-> Result<(), std::io::Error>
+	{
+		if let Some(removed_data) = &self.removed_data
+		{
+			Metadata::write_encoded_to_vec(file_buffer, self.file_type, removed_data, &WriteOptions::new())?;
+		}
+		return Ok(());
+	}
+
+	/// Writes the removed EXIF data back to the image file at the given path,
+	/// see `apply_to_vec`. The file is only written to if there actually is
+	/// something to restore.
+	/// - If the file is read-only, the error contains a `ReadOnlyTarget`.
+	pub fn
+	apply_to_file
+	(
+		&self,
+		path: &Path
+	)
+	-> Result<(), std::io::Error>
+	{
+		if self.removed_data.is_none()
+		{
+			return Ok(());
+		}
+
+		check_writable(path)?;
+		let mut file_buffer = std::fs::read(path)?;
+		self.apply_to_vec(&mut file_buffer)?;
+		return write_vec_to_file(path, &file_buffer);
+	}
+}
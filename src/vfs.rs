@@ -0,0 +1,266 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! The file system that path based reading, writing and clearing of metadata
+//! goes through.
+//!
+//! The format modules access files only via `FileSystem`, so the functions
+//! taking one (e.g. `Metadata::new_from_path_in`) work with any
+//! implementation: `OsFileSystem` is the one of the operating system, used
+//! by all other path based functions, while `MemoryFileSystem` keeps the
+//! files in memory, e.g. for tests that shouldn't touch the disk. Libraries
+//! embedding little_exif in a sandbox can restrict which files are accessed
+//! by implementing the trait themselves.
+//!
+//! Features that only make sense for real files, like backups and safe
+//! writes (see `WriteOptions`), are not available through this trait, apart
+//! from the free space checked by safe writes (see `available_space`).
+//!
+//! # Examples
+//! ```no_run
+//! use little_exif::exif_tag::ExifTag;
+//! use little_exif::metadata::Metadata;
+//! use little_exif::vfs::MemoryFileSystem;
+//!
+//! let path = std::path::Path::new("image.jpg");
+//! let file_system = MemoryFileSystem::new();
+//! file_system.insert(path, std::fs::read("image.jpg").unwrap());
+//!
+//! let mut metadata = Metadata::new_from_path_in(&file_system, path).unwrap();
+//! metadata.set_tag(ExifTag::Artist("Jane Doe".to_string()));
+//! metadata.write_to_file_in(&file_system, path).unwrap();
+//! let written = file_system.get(path).unwrap();
+//! ```
+
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::io::Read;
+use std::io::Seek;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::general_file_io::io_error;
+use crate::general_file_io::write_vec_to_file;
+
+/// A reader that can seek, as required for parsing most file formats
+pub trait
+ReadSeek
+: Read + Seek
+{}
+
+impl<T: Read + Seek>
+ReadSeek
+for
+T
+{}
+
+/// A file system providing the files metadata is read from and written to
+pub trait
+FileSystem
+{
+	/// Checks whether there is a file at the given path
+	fn
+	exists
+	(
+		&self,
+		path: &Path
+	)
+	-> bool;
+
+	/// Opens the file at the given path for reading
+	fn
+	open
+	(
+		&self,
+		path: &Path
+	)
+	-> Result<Box<dyn ReadSeek>, std::io::Error>;
+
+	/// Replaces the content of the file at the given path with the given
+	/// data, creating the file if necessary
+	fn
+	write
+	(
+		&self,
+		path: &Path,
+		data: &[u8]
+	)
+	-> Result<(), std::io::Error>;
+
+	/// Reads the whole content of the file at the given path
+	fn
+	read
+	(
+		&self,
+		path: &Path
+	)
+	-> Result<Vec<u8>, std::io::Error>
+	{
+		let mut file_buffer = Vec::new();
+		self.open(path)?.read_to_end(&mut file_buffer)?;
+		return Ok(file_buffer);
+	}
+
+	/// Gets the space available for new files in the given directory in
+	/// bytes, or `None` if it is unknown (the default). Safe writes refuse
+	/// to create a temporary file that wouldn't fit.
+	fn
+	available_space
+	(
+		&self,
+		_directory: &Path
+	)
+	-> Result<Option<u64>, std::io::Error>
+	{
+		return Ok(None);
+	}
+}
+
+/// The file system of the operating system. Writing reports read-only files
+/// and files in use by another process like `Metadata::write_to_file`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct
+OsFileSystem;
+
+impl
+FileSystem
+for
+OsFileSystem
+{
+	fn
+	exists
+	(
+		&self,
+		path: &Path
+	)
+	-> bool
+	{
+		path.exists()
+	}
+
+	fn
+	open
+	(
+		&self,
+		path: &Path
+	)
+	-> Result<Box<dyn ReadSeek>, std::io::Error>
+	{
+		return Ok(Box::new(std::fs::File::open(path)?));
+	}
+
+	fn
+	write
+	(
+		&self,
+		path: &Path,
+		data: &[u8]
+	)
+	-> Result<(), std::io::Error>
+	{
+		write_vec_to_file(path, data)
+	}
+
+	fn
+	available_space
+	(
+		&self,
+		directory: &Path
+	)
+	-> Result<Option<u64>, std::io::Error>
+	{
+		return Ok(Some(fs4::available_space(directory)?));
+	}
+}
+
+/// A file system keeping its files in memory. Paths are compared as they
+/// are, without resolving `.` or `..` components
+#[derive(Debug, Default)]
+pub struct
+MemoryFileSystem
+{
+	files: Mutex<HashMap<PathBuf, Vec<u8>>>,
+}
+
+impl
+MemoryFileSystem
+{
+	/// Creates an empty file system
+	pub fn
+	new()
+	-> MemoryFileSystem
+	{
+		MemoryFileSystem::default()
+	}
+
+	/// Stores the given data as file at the given path, replacing any
+	/// previous content
+	pub fn
+	insert
+	(
+		&self,
+		path: &Path,
+		data: Vec<u8>
+	)
+	{
+		self.files.lock().unwrap().insert(path.to_path_buf(), data);
+	}
+
+	/// Gets a copy of the content of the file at the given path
+	pub fn
+	get
+	(
+		&self,
+		path: &Path
+	)
+	-> Option<Vec<u8>>
+	{
+		self.files.lock().unwrap().get(path).cloned()
+	}
+}
+
+impl
+FileSystem
+for
+MemoryFileSystem
+{
+	fn
+	exists
+	(
+		&self,
+		path: &Path
+	)
+	-> bool
+	{
+		self.files.lock().unwrap().contains_key(path)
+	}
+
+	fn
+	open
+	(
+		&self,
+		path: &Path
+	)
+	-> Result<Box<dyn ReadSeek>, std::io::Error>
+	{
+		match self.get(path)
+		{
+			Some(file_buffer) => Ok(Box::new(Cursor::new(file_buffer))),
+			None              => io_error!(NotFound, format!("Can't open {} - File does not exist!", path.display())),
+		}
+	}
+
+	fn
+	write
+	(
+		&self,
+		path: &Path,
+		data: &[u8]
+	)
+	-> Result<(), std::io::Error>
+	{
+		self.insert(path, data.to_vec());
+		return Ok(());
+	}
+}
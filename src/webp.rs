@@ -1,414 +1,193 @@
 // Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
 // See https://github.com/TechnikTobi/little_exif#license for licensing details
 
-use std::fs::File;
-use std::fs::OpenOptions;
+use std::io::Cursor;
 use std::io::Read;
-use std::io::Write;
 use std::io::Seek;
 use std::io::SeekFrom;
 use std::path::Path;
 
+use crate::chunk_order::chunk_order;
+use crate::chunk_types::RiffChunkType;
 use crate::endian::*;
+use crate::exif_block;
+use crate::exif_block::ExifPrefix;
+use crate::errors::MissingExif;
+use crate::errors::ParseErrorContext;
+use crate::errors::WithParseContext;
+use crate::filetype::FileExtension;
 use crate::general_file_io::*;
-use crate::riff_chunk::RiffChunk;
-use crate::riff_chunk::RiffChunkDescriptor;
+use crate::riff_chunk::*;
+use crate::vfs::FileSystem;
+use crate::vfs::ReadSeek;
 
-pub(crate) const RIFF_SIGNATURE:       [u8; 4] = [0x52, 0x49, 0x46, 0x46];
 pub(crate) const WEBP_SIGNATURE:       [u8; 4] = [0x57, 0x45, 0x42, 0x50];
-pub(crate) const VP8X_HEADER:          &str    = "VP8X";
-pub(crate) const EXIF_CHUNK_HEADER:    &str    = "EXIF";
-
-/// A WebP file starts as follows
-/// - The RIFF signature: ASCII characters "R", "I", "F", "F"  -> 4 bytes
-/// - The file size starting at offset 8                       -> 4 bytes
-/// - The WEBP signature: ASCII characters "W", "E", "B", "P"  -> 4 bytes
-/// This function checks these 3 sections and their correctness after making
-/// sure that the file actually exists and can be opened. 
-/// Finally, the file struct is returned for further processing
+
+/// Opens the WebP file at the given path for reading
 fn
-check_signature
+open_file
 (
-	path: &Path
+	file_system: &dyn FileSystem,
+	path:        &Path
 )
--> Result<File, std::io::Error>
+-> Result<Box<dyn ReadSeek>, std::io::Error>
 {
-	if !path.exists()
+	if !file_system.exists(path)
 	{
 		return io_error!(NotFound, "Can't open WebP file - File does not exist!");
 	}
 
-	let mut file = OpenOptions::new()
-		.read(true)
-		.write(true)
-		.open(path)
-		.expect("Could not open file");
-	
-	// Check the RIFF signature
-	let mut riff_signature_buffer = [0u8; 4];
-	perform_file_action!(file.read(&mut riff_signature_buffer));
-	if !riff_signature_buffer.iter()
-		.zip(RIFF_SIGNATURE.iter())
-		.filter(|&(read, constant)| read == constant)
-		.count() == RIFF_SIGNATURE.len()
-	{
-		return io_error!(
-			InvalidData, 
-			format!("Can't open WebP file - Expected RIFF signature but found {}!", from_u8_vec_macro!(String, &riff_signature_buffer.to_vec(), &Endian::Big))
-		);
-	}
-
-	// Read the file size in byte and validate it using the file metadata
-	let mut size_buffer = [0u8; 4];
-	file.read(&mut size_buffer).unwrap();
-	let byte_count = from_u8_vec_macro!(u32, &size_buffer.to_vec(), &Endian::Little);
-	if file.metadata().unwrap().len() != (byte_count + 8) as u64
-	{
-		return io_error!(InvalidData, "Can't open WebP file - Promised byte count does not correspond with file size!");
-	}
-
-	// Check the WEBP signature
-	let mut webp_signature_buffer = [0u8; 4];
-	file.read(&mut webp_signature_buffer).unwrap();
-	if !webp_signature_buffer.iter()
-		.zip(WEBP_SIGNATURE.iter())
-		.filter(|&(read, constant)| read == constant)
-		.count() == WEBP_SIGNATURE.len()
-	{
-		return io_error!(
-			InvalidData, 
-			format!("Can't open WebP file - Expected WEBP signature but found {}!", from_u8_vec_macro!(String, &webp_signature_buffer.to_vec(), &Endian::Big))
-		);
-	}
-
-	// Signature is valid - can proceed using the file as WebP file
-	return Ok(file);
+	return file_system.open(path);
 }
 
 
 
-/// Gets the next RIFF chunk, starting at the current file cursor
-/// Advances the cursor to the start of the next chunk
+/// A WebP file starts with the RIFF header (see `riff_chunk::check_header`)
+/// with form type "WEBP" and must end where the RIFF chunk ends. Afterwards,
+/// the cursor is located at the start of the first chunk. Returns the length
+/// of the data, i.e. where the RIFF chunk ends
 fn
-get_next_chunk
-(
-	file: &mut File
-)
--> Result<RiffChunk, std::io::Error>
-{
-	// Read the start of the chunk
-	let mut chunk_start = [0u8; 8];
-	let mut bytes_read = file.read(&mut chunk_start).unwrap();
-
-	// Check that indeed 8 bytes were read
-	if bytes_read != 8
-	{
-		return io_error!(UnexpectedEof, "Could not read start of chunk");
-	}
-
-	// Construct name of chunk and its length
-	let chunk_name = String::from_utf8(chunk_start[0..4].to_vec());
-	let mut chunk_length = from_u8_vec_macro!(u32, &chunk_start[4..8].to_vec(), &Endian::Little);
-
-	// Account for the possible padding byte
-	chunk_length += chunk_length % 2;
-
-	// Read RIFF chunk data
-	let mut chunk_data_buffer = vec![0u8; chunk_length as usize];
-	bytes_read = file.read(&mut chunk_data_buffer).unwrap();
-	if bytes_read != chunk_length as usize
-	{
-		return io_error!(
-			Other, 
-			format!("Could not read RIFF chunk data! Expected {chunk_length} bytes but read {bytes_read}")
-		);
-	}
-
-	if let Ok(parsed_chunk_name) = chunk_name
-	{
-		return Ok(RiffChunk::new(
-			parsed_chunk_name as String, 
-			chunk_length      as usize,
-			chunk_data_buffer as Vec<u8>
-		));
-	}
-	else
-	{
-		return io_error!(Other, "Could not parse RIFF fourCC chunk name!");
-	}
-}
-
-
-
-/// Gets a descriptor of the next RIFF chunk, starting at the current file
-/// cursor position. Advances the cursor to the start of the next chunk
-/// Relies on `get_next_chunk` by basically calling that function and throwing
-/// away the actual payload
-fn
-get_next_chunk_descriptor
+check_signature
+<T: Read + Seek>
 (
-	file: &mut File
+	cursor: &mut T
 )
--> Result<RiffChunkDescriptor, std::io::Error>
+-> Result<u64, std::io::Error>
 {
-	let next_chunk_result = get_next_chunk(file)?;
-	return Ok(next_chunk_result.descriptor());
+	return check_header(cursor, WEBP_SIGNATURE, "WebP", true);
 }
 
 
 
-/// "Parses" the WebP file by checking various properties:
-/// - Can the file be opened and is the signature valid, including the file size?
-/// - Are the chunks and their size descriptions OK? Relies on the local subroutine `get_next_chunk_descriptor`
+/// "Parses" the WebP data by checking various properties:
+/// - Is the signature valid, including the file size?
+/// - Are the chunks and their size descriptions OK? See `riff_chunk::parse_chunks`
 pub(crate) fn
 parse_webp
+<T: Read + Seek>
 (
-	path: &Path
+	cursor: &mut T
 )
 -> Result<Vec<RiffChunkDescriptor>, std::io::Error>
 {
-	let file_result = check_signature(path);
-	let mut chunks = Vec::new();
-
-	if file_result.is_err()
-	{
-		return Err(file_result.err().unwrap());
-	}
+	let span = span!("parse", format = "WebP"; chunks);
 
-	let mut file = file_result.unwrap();
-
-	// The amount of data we expect to read while parsing the chunks
-	let expected_length = file.metadata().unwrap().len();
-
-	// How much data we have parsed so far.
-	// Starts with 12 bytes: 
-	// - 4 bytes for RIFF signature
-	// - 4 bytes for file size
-	// - 4 bytes for WEBP signature
-	// These bytes are already read in by the `check_signature` subroutine
-	let mut parsed_length = 12u64;
-
-	loop
-	{
-		let next_chunk_descriptor_result = get_next_chunk_descriptor(&mut file);
-		if let Ok(chunk_descriptor) = next_chunk_descriptor_result
-		{
-			// The parsed length increases by the length of the chunk's 
-			// header (4 byte) + it's size section (4 byte) and the payload
-			// size, which is noted by the aforementioned size section
-			parsed_length += 4u64 + 4u64 + chunk_descriptor.len() as u64;
-
-			// Add the chunk descriptor
-			chunks.push(chunk_descriptor);
-			
-			if parsed_length == expected_length
-			{
-				// In this case we don't expect any more data to be in the file
-				break;
-			}			
-		}
-		else
-		{
-			// This is the case when the read of the next chunk descriptor 
-			// fails due to not being able to fetch 8 bytes for the header and
-			// chunk size information, indicating that there is no further data
-			// in the file and we are done with parsing.
-			// If the subroutine fails due to other reasons, the error gets
-			// propagated further.
-			if next_chunk_descriptor_result.as_ref().err().unwrap().kind() == std::io::ErrorKind::UnexpectedEof
-			{
-				break;
-			}
-			else
-			{
-				return Err(next_chunk_descriptor_result.err().unwrap());
-			}
-		}
-	}
+	let riff_end = check_signature(cursor)?;
+	let chunks = parse_chunks(cursor, riff_end)?;
 
+	record!(span, chunks, chunks.len());
 	return Ok(chunks);
 }
 
 
 
-fn
-check_exif_in_file
-(
-	path: &Path
-)
--> Result<(File, Vec<RiffChunkDescriptor>), std::io::Error>
-{
-	// Parse the WebP file - if this fails, we surely can't read any metadata
-	let parsed_webp_result = parse_webp(path);
-	if let Err(error) = parsed_webp_result
-	{
-		return Err(error);
-	}
-
-	// Next, check if this is an Extended File Format WebP file
-	// In this case, the first Chunk SHOULD have the type "VP8X"
-	// Otherwise, the file is either invalid ("VP8X" at wrong location) or a 
-	// Simple File Format WebP file which don't contain any EXIF metadata.
-	if let Some(first_chunk) = parsed_webp_result.as_ref().unwrap().first()
-	{
-		// Compare the chunk descriptor header.
-		if first_chunk.header().to_lowercase() != VP8X_HEADER.to_lowercase()
-		{
-			return io_error!(
-				Other, 
-				format!("Expected first chunk of WebP file to be of type 'VP8X' but instead got {}!", first_chunk.header())
-			);
-		}
-	}
-	else
-	{
-		return io_error!(Other, "Could not read first chunk descriptor of WebP file!");
-	}
-
-	// Finally, check the flag by opening up the file and reading the data of
-	// the VP8X chunk
-	// Regarding the seek:
-	// - RIFF + file size + WEBP -> 12 byte
-	// - VP8X header             ->  4 byte
-	// - VP8X chunk size         ->  4 byte
-	let mut file = check_signature(path).unwrap();
-	let mut flag_buffer = vec![0u8; 4usize];
-	perform_file_action!(file.seek(SeekFrom::Start(12u64 + 4u64 + 4u64)));
-	if file.read(&mut flag_buffer).unwrap() != 4
-	{
-		return io_error!(Other, "Could not read flags of VP8X chunk!");
-	}
-
-	// Check the 5th bit of the 32 bit flag_buffer. 
-	// For further details see the Extended File Format section at
-	// https://developers.google.com/speed/webp/docs/riff_container#extended_file_format
-	if flag_buffer[0] & 0x08 != 0x08
-	{
-		return io_error!(Other, "No EXIF chunk according to VP8X flags!");
-	}
-
-	return Ok((file, parsed_webp_result.unwrap()));
-}
-
-
-
-/// Reads the raw EXIF data from the WebP file. Note that if the file contains
+/// Reads the raw EXIF data from the WebP data. Note that if there are
 /// multiple such chunks, the first one is returned and the others get ignored.
+/// Only the chunks up to the EXIF chunk are visited and their payload is
+/// skipped without reading it, so the rest of the data is never touched.
 pub(crate) fn
 read_metadata
+<T: Read + Seek>
 (
-	path: &Path
+	cursor:   &mut T,
+	warnings: &mut Vec<ParseErrorContext>
 )
 -> Result<Vec<u8>, std::io::Error>
 {
-	// Check the file signature, parse it, check that it has a VP8X chunk and
-	// the EXIF flag is set there
-	let (mut file, parse_webp_result) = check_exif_in_file(path).unwrap();
-
-	// At this point we have established that the file has to contain an EXIF
-	// chunk at some point. So, now we need to find & return it
-	// Start by seeking to the start of the first chunk and visiting chunk after
-	// chunk via checking the type and seeking again to the next chunk via the
-	// size information
-	perform_file_action!(file.seek(SeekFrom::Start(12u64)));
-	let mut header_buffer = vec![0u8; 4usize];
-	let mut chunk_index = 0usize;
+	// Check the file signature and that the first chunk is a VP8X chunk.
+	// Otherwise, the file is either invalid or a Simple File Format WebP
+	// file which can't contain any EXIF metadata.
+	let _span = span!("parse", format = "WebP");
+	let riff_end = check_signature(cursor)?;
+	let first_chunk = get_next_chunk(cursor, riff_end).with_parse_context(|| ParseErrorContext::new().offset(12))?;
+	if first_chunk.descriptor().header().chunk_type() != RiffChunkType::VP8X
+	{
+		// Only the extended file format can hold metadata
+		return MissingExif::error(
+			format!("Expected first chunk of WebP file to be of type 'VP8X' but instead got {}!", first_chunk.descriptor().header())
+		);
+	}
+
+	// Check the 5th bit of the 32 bit flags. 
+	// For further details see the Extended File Format section at
+	// https://developers.google.com/speed/webp/docs/riff_container#extended_file_format
+	// Some writers add an EXIF chunk without setting the flag, so the chunks
+	// are searched anyway, reporting a warning if one is found
+	let exif_flag = first_chunk.payload().first().is_some_and(|flags| flags & 0x08 == 0x08);
+
+	// Now we need to find & return the EXIF chunk by visiting chunk after
+	// chunk via checking the type and seeking to the next chunk via the size
+	// information
+	let mut header_buffer = [0u8; 8];
+	let mut image_data_found = false;
 	loop
 	{
-		// Read the chunk type into the buffer
-		if file.read(&mut header_buffer).unwrap() != 4
+		// Read the chunk type and size into the buffer
+		let offset = cursor.stream_position()?;
+		if !try_read_exact(cursor, &mut header_buffer)?
 		{
-			return io_error!(Other, "Could not read chunk type while traversing WebP file!");
+			if !exif_flag
+			{
+				return MissingExif::error("No EXIF chunk according to VP8X flags!");
+			}
+			return MissingExif::error("Could not find EXIF chunk while traversing WebP file!");
 		}
-		let chunk_type = String::from_u8_vec(&header_buffer.to_vec(), &Endian::Little);
+		let chunk_type = FourCC::new(&header_buffer[0..4]).with_parse_context(|| ParseErrorContext::new().offset(offset))?;
+		let chunk_size = u32::from_bytes(&header_buffer[4..8], &Endian::Little) as usize;
+		let context    = || ParseErrorContext::new().offset(offset).chunk(chunk_type.as_str());
 
-		// Check that this is still the type that we expect from the previous
-		// parsing over the file
-		// TODO: Maybe remove this part?
-		let expected_chunk_type = parse_webp_result.iter().nth(chunk_index).unwrap().header();
-		if chunk_type != expected_chunk_type
+		if chunk_type.chunk_type() == RiffChunkType::EXIF
 		{
-			return io_error!(
-				Other, 
-				format!("Got unexpected chunk type! Exprected {} but got {}", expected_chunk_type, chunk_type)
-			);
-		}
-
-		// Get the size of this chunk from the previous parsing process and skip
-		// the 4 bytes regarding the size
-		let chunk_size = parse_webp_result.iter().nth(chunk_index).unwrap().len();
-		perform_file_action!(file.seek(SeekFrom::Current(4)));
+			// The size may be anything up to 4 GiB, so check it before
+			// allocating the buffer for the data
+			if offset + 8 + chunk_size as u64 > riff_end
+			{
+				return io_error!(InvalidData, "EXIF chunk exceeds the RIFF chunk!").with_parse_context(context);
+			}
 
-		if chunk_type.to_lowercase() == EXIF_CHUNK_HEADER.to_lowercase()
-		{
 			// Read the EXIF chunk's data into a buffer
 			let mut payload_buffer = vec![0u8; chunk_size];
-			perform_file_action!(file.read(&mut payload_buffer));
-
-			// Add the 6 bytes of the EXIF_HEADER as Prefix for the generic EXIF
-			// data parser that is called on the result of this read function
-			// Otherwise the result would directly start with the Endianness
-			// information, leading to a failed EXIF header signature check in 
-			// the function `decode_metadata_general`
-			let mut raw_exif_data = EXIF_HEADER.to_vec();
-			raw_exif_data.append(&mut payload_buffer);
+			if !try_read_exact(cursor, &mut payload_buffer)?
+			{
+				return io_error!(Other, "Could not read EXIF chunk data!").with_parse_context(context);
+			}
 
-			return Ok(raw_exif_data);
-		}
-		else
-		{
-			// Skip the entire chunk
-			perform_file_action!(file.seek(SeekFrom::Current(chunk_size as i64)));
+			// The payload starts with the TIFF header, but some writers also
+			// put the EXIF header in front of it like in JPEG files
+			let raw_exif_data = exif_block::normalize(&payload_buffer).with_parse_context(context)?;
 
-			// Note that we have to seek another byte in case the chunk is of 
-			// uneven size to account for the padding byte that must be included
-			if chunk_size % 2 == 1
+			if !exif_flag
+			{
+				warnings.push(context().warning("EXIF chunk found although the VP8X flag for EXIF data is not set!"));
+			}
+			if !image_data_found
 			{
-				perform_file_action!(file.seek(SeekFrom::Current(1i64)));
+				warnings.push(context().warning("EXIF chunk found before the image data, see Metadata::normalize_metadata_position!"));
 			}
+
+			return Ok(raw_exif_data);
 		}
+		image_data_found |= is_image_data(chunk_type.chunk_type());
 
-		// Update for next loop iteration
-		chunk_index += 1;
+		// Skip the entire chunk. Note that we have to seek another byte in
+		// case the chunk is of uneven size to account for the padding byte
+		// that must be included
+		perform_file_action!(cursor.seek(SeekFrom::Current((chunk_size + chunk_size % 2) as i64)).with_parse_context(context));
 	}
 }
 
 
 
-fn
-update_file_size_information
+pub(crate) fn
+file_read_metadata
 (
-	file:  &mut File,
-	delta: i32
+	file_system: &dyn FileSystem,
+	path:        &Path,
+	warnings:    &mut Vec<ParseErrorContext>
 )
--> Result<(), std::io::Error>
+-> Result<Vec<u8>, std::io::Error>
 {
-	// Note from the documentation:
-	// As the size of any chunk is even, the size given by the RIFF header is also even.
-
-	// Update the file size information, first by reading in the current value...
-	perform_file_action!(file.seek(SeekFrom::Start(4)));
-	let mut file_size_buffer = [0u8; 4];
-
-	// ...converting it to u32 representation...
-	perform_file_action!(file.read(&mut file_size_buffer));
-	let old_file_size = from_u8_vec_macro!(u32, &file_size_buffer.to_vec(), &Endian::Little);
-
-	// ...adding the delta byte count (and performing some checks)...
-	if delta < 0
-	{
-		assert!(old_file_size as i32 > delta);
-	}
-	let new_file_size = (old_file_size as i32 + delta) as u32;
-
-	assert!(old_file_size % 2 == 0);
-	assert!(new_file_size % 2 == 0);
-
-	// ...and writing back to file...
-	perform_file_action!(file.seek(SeekFrom::Start(4)));
-	perform_file_action!(file.write_all(&to_u8_vec_macro!(u32, &new_file_size, &Endian::Little)));
-
-	Ok(())
+	read_metadata(&mut open_file(file_system, path)?, warnings)
 }
 
 
@@ -416,69 +195,71 @@ update_file_size_information
 fn
 convert_to_extended_format
 (
-	file: &mut File
+	file_buffer: &mut Vec<u8>
 )
 -> Result<(), std::io::Error>
 {
 	// Start by getting the first chunk of the WebP file
-	perform_file_action!(file.seek(SeekFrom::Start(12)));
-	let first_chunk_result = get_next_chunk(file);
-
-	// Check that this get operation was successful
-	if first_chunk_result.is_err()
-	{
-		return Err(first_chunk_result.err().unwrap());
-	}
-
-	let first_chunk = first_chunk_result.unwrap();
+	let mut cursor = Cursor::new(&file_buffer);
+	perform_file_action!(cursor.seek(SeekFrom::Start(12)));
+	let first_chunk = get_next_chunk(&mut cursor, file_buffer.len() as u64)?;
 
 	// Find out what simple type of WebP file we are dealing with
-	let (width, height) = match first_chunk.descriptor().header().as_str()
+	let (width, height) = match first_chunk.descriptor().header().chunk_type()
 	{
-		"VP8" 
-			=> {println!("VP8 !"); todo!()},
-		"VP8L"
-			=> get_dimension_info_from_vp8l_chunk(first_chunk.payload()),
-		_ 
-			=> io_error!(Other, "Expected either 'VP8 ' or 'VP8L' chunk for conversion!")
+		RiffChunkType::VP8  => get_dimension_info_from_vp8_chunk(first_chunk.payload()),
+		RiffChunkType::VP8L => get_dimension_info_from_vp8l_chunk(first_chunk.payload()),
+		_                   => io_error!(Other, "Expected either 'VP8 ' or 'VP8L' chunk for conversion!"),
 	}?;
 
-	println!("{} {}", width, height);
+	// Flags and reserved area (4 bytes), followed by the 24 bits for each 
+	// the width and height information (32 + 24 + 24 bit = 10 byte)
+	let mut vp8x_payload = vec![0x00, 0x00, 0x00, 0x00];
+	vp8x_payload.extend(&Endian::Little.bytes(width)[0..3]);
+	vp8x_payload.extend(&Endian::Little.bytes(height)[0..3]);
 
-	let width_vec  = to_u8_vec_macro!(u32, &width,  &Endian::Little);
-	let height_vec = to_u8_vec_macro!(u32, &height, &Endian::Little);
+	// Insert the VP8X chunk right after the header and update the file size
+	file_buffer.splice(12..12, RiffChunk::new(RiffChunkType::VP8X.into(), vp8x_payload).serialize());
+	update_riff_size(file_buffer, 0);
 
-	let mut vp8x_chunk = vec![
-		0x56, 0x50, 0x38, 0x58, // ASCII chars "V", "P", "8", "X"                  -> 4 byte
-		0x0A, 0x00, 0x00, 0x00, // size of this chunk (32 + 24 + 24 bit = 10 byte) -> 4 byte
-		0x00, 0x00, 0x00, 0x00, // Flags and reserved area                         -> 4 byte
-	];
+	Ok(())
+}
 
-	// Add the two 24 bits for width and height information
-	for i in 0..3 { vp8x_chunk.push(width_vec[i]); }
-	for i in 0..3 { vp8x_chunk.push(height_vec[i]); }
 
-	// Write the VP8X chunk, first by reading the file (except for the header)
-	// into a buffer...
-	let mut buffer = Vec::new();
-	perform_file_action!(file.seek(SeekFrom::Start(12u64)));
-	perform_file_action!(file.read_to_end(&mut buffer));
 
-	// ...actually writing the VP8X chunk data...
-	perform_file_action!(file.seek(SeekFrom::Start(12u64)));
-	perform_file_action!(file.write(&vp8x_chunk));
+/// Gets the canvas width and height minus one (as required by the VP8X chunk)
+/// from the frame header of a lossy bitstream
+/// See: https://datatracker.ietf.org/doc/html/rfc6386#section-9.1
+fn
+get_dimension_info_from_vp8_chunk
+(
+	payload: &Vec<u8>
+)
+-> Result<(u32, u32), std::io::Error>
+{
+	// The 3 byte frame tag is followed by the start code 0x9d 0x01 0x2a and
+	// the two 16 bit values whose lower 14 bits are the width and height
+	if payload.len() < 10 || payload[3..6] != [0x9d, 0x01, 0x2a]
+	{
+		return io_error!(InvalidData, "Could not find start code of VP8 frame!");
+	}
 
-	// ...and writing back the file contents
-	perform_file_action!(file.write(&buffer));
+	let width  = u16::from_bytes(&payload[6..8],  &Endian::Little) as u32 & 0x3fff;
+	let height = u16::from_bytes(&payload[8..10], &Endian::Little) as u32 & 0x3fff;
 
-	// Finally, update the file size information
-	update_file_size_information(file, 18)?;
+	if width == 0 || height == 0
+	{
+		return io_error!(InvalidData, "Invalid dimensions in VP8 frame header!");
+	}
 
-	Ok(())
+	return Ok((width - 1, height - 1));
 }
 
 
 
+/// Gets the canvas width and height minus one (as required by the VP8X chunk)
+/// from the header of a lossless bitstream, which stores the values just like
+/// that
 fn
 get_dimension_info_from_vp8l_chunk
 (
@@ -490,27 +271,49 @@ get_dimension_info_from_vp8l_chunk
 	// (although we only need 28 bits)
 	// Starting at byte 1 instead of 0 due to the 0x2F byte
 	// See: https://developers.google.com/speed/webp/docs/webp_lossless_bitstream_specification#3_riff_header
-	let width_height_info_buffer = payload[1..5].to_vec();
-	
+	if payload.len() < 5 || payload[0] != 0x2f
+	{
+		return io_error!(InvalidData, "Could not find signature of VP8L bitstream!");
+	}
 	// Convert to a single u32 number for bit-mask operations
-	let width_height_info = from_u8_vec_macro!(u32, &width_height_info_buffer, &Endian::Little);
+	let width_height_info = u32::from_bytes(&payload[1..5], &Endian::Little);
 	
-	let mut width  = 0;
-	let mut height = 0;
+	// The first 14 bit are the width, the next 14 bit the height
+	let width  =  width_height_info        & 0x3fff;
+	let height = (width_height_info >> 14) & 0x3fff;
 
-	// Get the first 14 bit to construct the width
-	for bit_index in 0..14
-	{
-		width  |= ((width_height_info >> (27 - bit_index)) & 0x01) << (13 - (bit_index % 14));
-	}
+	return Ok((width, height));
+}
 
-	// Get the next 14 bit to construct the height
-	for bit_index in 14..28
-	{
-		height |= ((width_height_info >> (27 - bit_index)) & 0x01) << (13 - (bit_index % 14));
-	}
 
-	return Ok((width, height));
+
+/// Gets the width and height of the canvas, as given by the VP8X chunk or
+/// the bitstream of simple files
+pub(crate) fn
+image_dimensions
+(
+	file_buffer: &[u8]
+)
+-> Option<(u32, u32)>
+{
+	let mut cursor = Cursor::new(file_buffer);
+	cursor.seek(SeekFrom::Start(12)).ok()?;
+	let first_chunk = get_next_chunk(&mut cursor, file_buffer.len() as u64).ok()?;
+
+	let payload = first_chunk.payload();
+	let (width, height) = match first_chunk.descriptor().header().chunk_type()
+	{
+		RiffChunkType::VP8X if payload.len() >= 10 => (
+			u32::from_le_bytes([payload[4], payload[5], payload[6], 0]),
+			u32::from_le_bytes([payload[7], payload[8], payload[9], 0]),
+		),
+		RiffChunkType::VP8  => get_dimension_info_from_vp8_chunk(payload).ok()?,
+		RiffChunkType::VP8L => get_dimension_info_from_vp8l_chunk(payload).ok()?,
+		_                   => return None,
+	};
+
+	// All of them store the dimensions minus one
+	return Some((width + 1, height + 1));
 }
 
 
@@ -518,30 +321,23 @@ get_dimension_info_from_vp8l_chunk
 fn
 set_exif_flag
 (
-	path:  &Path,
+	file_buffer:     &mut Vec<u8>,
 	exif_flag_value: bool
 )
 -> Result<(), std::io::Error>
 {
-	// Parse the WebP file - if this fails, we surely can't read any metadata
-	let parsed_webp_result = parse_webp(path);
-	if let Err(error) = parsed_webp_result
-	{
-		return Err(error);
-	}
-
-	// Open the file for further processing
-	let mut file = check_signature(path).unwrap();
+	// Parse the WebP data - if this fails, we surely can't set the flag
+	let parsed_webp_result = parse_webp(&mut Cursor::new(&file_buffer))?;
 
 	// Next, check if this is an Extended File Format WebP file
 	// In this case, the first Chunk SHOULD have the type "VP8X"
 	// Otherwise we have to create the VP8X chunk!
-	if let Some(first_chunk) = parsed_webp_result.as_ref().unwrap().first()
+	if let Some(first_chunk) = parsed_webp_result.first()
 	{
 		// Compare the chunk descriptor header and call chunk creator if required
-		if first_chunk.header().to_lowercase() != VP8X_HEADER.to_lowercase()
+		if first_chunk.header().chunk_type() != RiffChunkType::VP8X
 		{
-			convert_to_extended_format(&mut file)?;
+			convert_to_extended_format(file_buffer)?;
 		}
 	}
 	else
@@ -550,119 +346,93 @@ set_exif_flag
 	}	
 
 	// At this point we know that we have a VP8X chunk at the expected location
-	// So, read in the flags and set the EXIF flag accoring to the given bool
-	let mut flag_buffer = vec![0u8; 4usize];
-	perform_file_action!(file.seek(SeekFrom::Start(12u64 + 4u64 + 4u64)));
-	if file.read(&mut flag_buffer).unwrap() != 4
-	{
-		return io_error!(Other, "Could not read flags of VP8X chunk!");
-	}
-
-	// Mask the old flag by either or-ing with 1 at the EXIF flag position for
-	// setting it to true, or and-ing with 1 everywhere but the EXIF flag pos
-	// to set it to false
-	flag_buffer[0] = if exif_flag_value
+	// So, set the EXIF flag accoring to the given bool by either or-ing with 1
+	// at the EXIF flag position for setting it to true, or and-ing with 1 
+	// everywhere but the EXIF flag pos to set it to false
+	// Regarding the position:
+	// - RIFF + file size + WEBP -> 12 byte
+	// - VP8X header             ->  4 byte
+	// - VP8X chunk size         ->  4 byte
+	let flag_position = 12 + 4 + 4;
+	file_buffer[flag_position] = if exif_flag_value
 	{
-		flag_buffer[0] | 0x08
+		file_buffer[flag_position] | 0x08
 	}
 	else
 	{
-		flag_buffer[0] & 0b11110111
+		file_buffer[flag_position] & 0b11110111
 	};
 
-	// Write flag buffer back to the file
-	perform_file_action!(file.seek(SeekFrom::Start(12u64 + 4u64 + 4u64)));
-	perform_file_action!(file.write_all(&flag_buffer));
-
 	Ok(())
 }
 
 
 
-fn
+/// Removes all EXIF chunks from the WebP data and clears the EXIF flag of the
+/// VP8X chunk. Simple File Format WebP data can't contain EXIF metadata, so
+/// it is left untouched
+pub(crate) fn
 clear_metadata
 (
-	path: &Path
+	file_buffer: &mut Vec<u8>
 )
 -> Result<(), std::io::Error>
 {
-	// Check the file signature, parse it, check that it has a VP8X chunk and
-	// the EXIF flag is set there
-	let exif_check_result = check_exif_in_file(path);
-	if exif_check_result.is_err()
+	// Parse the data - if this fails, the clear operation fails as well
+	let parse_webp_result = parse_webp(&mut Cursor::new(&file_buffer))?;
+
+	if parse_webp_result.first().is_none_or(|first_chunk| first_chunk.header().chunk_type() != RiffChunkType::VP8X)
 	{
-		match exif_check_result.as_ref().err().unwrap().to_string().as_str()
-		{
-			"No EXIF chunk according to VP8X flags!"
-				=> return Ok(()),
-			"Expected first chunk of WebP file to be of type 'VP8X' but instead got VP8L!"
-				=> return Ok(()),
-			_
-				=> return Err(exif_check_result.err().unwrap())
-		}
+		return Ok(());
 	}
 
-	let (mut file, parse_webp_result) = exif_check_result.unwrap();
-
-	// Compute a delta of how much the file size information has to change
-	let mut delta = 0i32;
-
-	// Skip the WEBP signature
-	perform_file_action!(file.seek(SeekFrom::Current(4i64)));
-
+	// Find the ranges of the EXIF chunks, starting after the header
+	let mut chunk_start = 12usize;
+	let mut exif_chunk_ranges = Vec::new();
 	for parsed_chunk in parse_webp_result
 	{
-		// At the start of each iteration, the file cursor is at the start of
-		// the fourCC section of a chunk
-
 		// Compute how many bytes this chunk has
-		let parsed_chunk_byte_count = 
-			4u64                            // fourCC section of EXIF chunk
-			+ 4u64                          // size information of EXIF chunk
-			+ parsed_chunk.len() as u64     // actual size of EXIF chunk data
-			+ parsed_chunk.len() as u64 % 2 // accounting for possible padding byte
-		;
-
-		// Not an EXIF chunk, seek to next one and continue
-		if parsed_chunk.header().to_lowercase() != EXIF_CHUNK_HEADER.to_lowercase()
+		let parsed_chunk_byte_count = parsed_chunk.byte_count();
+
+		if parsed_chunk.header().chunk_type() == RiffChunkType::EXIF
 		{
-			perform_file_action!(file.seek(SeekFrom::Current(parsed_chunk_byte_count as i64)));
-			continue;
+			exif_chunk_ranges.push(chunk_start..chunk_start + parsed_chunk_byte_count);
 		}
 
-		// Get the current size of the file in bytes
-		let old_file_byte_count = file.metadata().unwrap().len();
-
-		// Get a backup of the current cursor position
-		let exif_chunk_start_cursor_position = SeekFrom::Start(file.seek(SeekFrom::Current(0)).unwrap());
-
-		// Skip the EXIF chunk ...
-		perform_file_action!(file.seek(SeekFrom::Current(parsed_chunk_byte_count as i64)));
+		chunk_start += parsed_chunk_byte_count;
+	}
 
-		// ...and copy everything afterwards into a buffer...
-		let mut buffer = Vec::new();
-		perform_file_action!(file.read_to_end(&mut buffer));
+	// Remove the chunks, starting with the last one so that the other 
+	// positions stay valid, and update the file size information
+	for exif_chunk_range in exif_chunk_ranges.into_iter().rev()
+	{
+		file_buffer.drain(exif_chunk_range);
+	}
+	update_riff_size(file_buffer, 0);
+	
+	// Finally, clear the flag in the VP8X chunk
+	set_exif_flag(file_buffer, false)?;
 
-		// ...and seek back to where the EXIF chunk is located...
-		perform_file_action!(file.seek(exif_chunk_start_cursor_position));
+	return Ok(());
+}
 
-		// ...and overwrite the EXIF chunk...
-		perform_file_action!(file.write_all(&buffer));
 
-		// ...and finally update the size of the file
-		perform_file_action!(file.set_len(old_file_byte_count - parsed_chunk_byte_count));
 
-		// Additionally, update the size information that gets written to the 
-		// file header after this loop
-		delta -= parsed_chunk_byte_count as i32;
+pub(crate) fn
+file_clear_metadata
+(
+	file_system: &dyn FileSystem,
+	path:        &Path
+)
+-> Result<(), std::io::Error>
+{
+	let mut file_buffer = read_file_to_vec(open_file(file_system, path)?)?;
+	let original_buffer = file_buffer.clone();
+	clear_metadata(&mut file_buffer)?;
+	if file_buffer != original_buffer
+	{
+		file_system.write(path, &file_buffer)?;
 	}
-
-	// Update file size information
-	update_file_size_information(&mut file, delta)?;
-	
-	// Set the flags in the VP8X chunk. First, read in the current flags
-	perform_file_action!(set_exif_flag(path, false));
-
 	return Ok(());
 }
 
@@ -675,27 +445,9 @@ encode_metadata_webp
 )
 -> Vec<u8>
 {
-	// Vector storing the data that will be returned
-	let mut webp_exif: Vec<u8> = Vec::new();
-
-	// Compute the length of the exif data chunk 
-	// This does NOT include the fourCC and size information of that chunk 
-	// Also does NOT include the padding byte, i.e. this value may be odd!
-	let length = exif_vec.len() as u32;
-
-	// Start with the fourCC chunk head and the size information.
-	// Then copy the previously encoded EXIF data 
-	webp_exif.extend([0x45, 0x58, 0x49, 0x46]);
-	webp_exif.extend(to_u8_vec_macro!(u32, &length, &Endian::Little));
-	webp_exif.extend(exif_vec.iter());
-
-	// Add the padding byte if required
-	if length % 2 != 0
-	{
-		webp_exif.extend([0x00]);
-	}
-
-	return webp_exif;
+	// The fourCC chunk head and the size information, followed by the
+	// previously encoded EXIF data and the padding byte if required
+	RiffChunk::new(RiffChunkType::EXIF.into(), exif_block::add_prefix(exif_vec, ExifPrefix::None)).serialize()
 }
 
 
@@ -714,95 +466,146 @@ as_u8_vec
 
 
 
-/// Writes the given generally encoded metadata to the WebP image file at 
-/// the specified path. 
+/// Writes the given generally encoded metadata to the WebP data. 
 /// Note that *all* previously stored EXIF metadata gets removed first before
 /// writing the "new" metadata. 
 pub(crate) fn
 write_metadata
 (
-	path:                     &Path,
+	file_buffer:              &mut Vec<u8>,
 	general_encoded_metadata: &Vec<u8>
 )
 -> Result<(), std::io::Error>
 {
-	// Clear the metadata from the file and return if this results in an error
-	clear_metadata(path)?;
+	// Clear the metadata from the data and return if this results in an error
+	clear_metadata(file_buffer)?;
+
+	// Set the EXIF flag, which also converts Simple File Format data to the
+	// Extended File Format
+	set_exif_flag(file_buffer, true)?;
 
 	// Encode the general metadata format to WebP specifications
 	let encoded_metadata = encode_metadata_webp(general_encoded_metadata);
 
-	// Open the file...
-	let mut file = check_signature(path)?;
+	// Find a location where to put the EXIF chunk, i.e. after the chunks
+	// that need to come before it (VP8X, ICCP, ANIM and the image data)
+	let insert_position = chunk_order(FileExtension::WEBP).metadata_insert_position(
+		parse_webp(&mut Cursor::new(&file_buffer))?
+			.iter()
+			.map(|chunk_descriptor| (*chunk_descriptor.header().as_bytes(), chunk_descriptor.byte_count()))
+	);
+
+	// Insert the EXIF chunk at the found location and update the file size
+	// information. (Note: Due to  the WebP specific encoding function, this 
+	// vector already contains the EXIF header characters and size information,
+	// as well as the possible padding byte)
+	file_buffer.splice(insert_position..insert_position, encoded_metadata);
+	update_riff_size(file_buffer, 0);
 
-	// ...and find a location where to put the EXIF chunk
-	// This is done by requesting a chunk descriptor as long as we find a chunk
-	// that is both known and should be located *before* the EXIF chunk
-	let pre_exif_chunks = [
-		"VP8X",
-		"VP8",
-		"VP8L",
-		"ICCP",
-		"ANIM"
-	];
+	return Ok(());
+}
 
-	loop
-	{
-		// Request a chunk descriptor. If this fails, check the error 
-		// Depending on its type, either continue normally or return it
-		let chunk_descriptor_result = get_next_chunk_descriptor(&mut file);
 
-		if let Ok(chunk_descriptor) = chunk_descriptor_result
-		{
-			let mut chunk_type_found_in_pre_exif_chunks = false;
 
-			// Check header of chunk descriptor against any of the known chunks
-			// that should come before the EXIF chunk
-			for pre_exif_chunk in &pre_exif_chunks
-			{
-				chunk_type_found_in_pre_exif_chunks |= pre_exif_chunk.to_lowercase() == chunk_descriptor.header().to_lowercase();
-			}
+/// Checks if the chunk holds (part of) the image data, which has to come
+/// before the EXIF chunk
+fn
+is_image_data
+(
+	chunk_type: RiffChunkType
+)
+-> bool
+{
+	matches!(chunk_type, RiffChunkType::VP8 | RiffChunkType::VP8L | RiffChunkType::ALPH | RiffChunkType::ANMF)
+}
 
-			if !chunk_type_found_in_pre_exif_chunks
-			{
-				break;
-			}
-		}
-		else
+/// Moves EXIF chunks that some tools put in front of the image data to where
+/// the specification places them, i.e. the position `write_metadata` puts
+/// them at. The chunks are moved as they are, without decoding them.
+/// Returns whether the data has been changed.
+pub(crate) fn
+normalize_metadata_position
+(
+	file_buffer: &mut Vec<u8>
+)
+-> Result<bool, std::io::Error>
+{
+	let chunks = parse_webp(&mut Cursor::new(&file_buffer))?;
+
+	// EXIF chunks are misplaced if image data follows them
+	let last_image_data = chunks.iter().rposition(|chunk| is_image_data(chunk.header().chunk_type()));
+	let mut chunk_start = 12usize;
+	let mut misplaced_ranges = Vec::new();
+	for (index, chunk) in chunks.iter().enumerate()
+	{
+		if chunk.header().chunk_type() == RiffChunkType::EXIF && last_image_data.is_some_and(|last| index < last)
 		{
-			match chunk_descriptor_result.as_ref().err().unwrap().kind()
-			{
-				std::io::ErrorKind::UnexpectedEof
-					=> break, // No further chunks, place EXIF chunk here
-				_
-					=> return Err(chunk_descriptor_result.err().unwrap())
-			}
+			misplaced_ranges.push(chunk_start..chunk_start + chunk.byte_count());
 		}
+		chunk_start += chunk.byte_count();
 	}
 
-	// Next, read remaining file into a buffer...
-	let current_file_cursor = SeekFrom::Start(file.seek(SeekFrom::Current(0)).unwrap());
-	let mut read_buffer = Vec::new();
-	perform_file_action!(file.read_to_end(&mut read_buffer));
+	if misplaced_ranges.is_empty()
+	{
+		return Ok(false);
+	}
 
-	// ...and write the EXIF chunk at the previously found location...
-	perform_file_action!(file.seek(current_file_cursor));
-	perform_file_action!(file.write_all(&encoded_metadata));
+	// Take the chunks out, starting with the last one so that the other
+	// positions stay valid, and put them back in their original order
+	let mut misplaced_chunks = Vec::new();
+	for range in misplaced_ranges.into_iter().rev()
+	{
+		misplaced_chunks.splice(0..0, file_buffer.drain(range));
+	}
+	update_riff_size(file_buffer, 0);
 
-	// ...and writing back the remaining file content
-	perform_file_action!(file.write_all(&read_buffer));
+	let insert_position = chunk_order(FileExtension::WEBP).metadata_insert_position(
+		parse_webp(&mut Cursor::new(&file_buffer))?
+			.iter()
+			.map(|chunk_descriptor| (*chunk_descriptor.header().as_bytes(), chunk_descriptor.byte_count()))
+	);
+	file_buffer.splice(insert_position..insert_position, misplaced_chunks);
+	update_riff_size(file_buffer, 0);
+
+	return Ok(true);
+}
+
+/// Moves misplaced EXIF chunks of the WebP image file at the given path (see
+/// `normalize_metadata_position`). The file is only written to if there
+/// actually is something to move.
+pub(crate) fn
+file_normalize_metadata_position
+(
+	file_system: &dyn FileSystem,
+	path:        &Path
+)
+-> Result<bool, std::io::Error>
+{
+	let mut file_buffer = read_file_to_vec(open_file(file_system, path)?)?;
+	let changed = normalize_metadata_position(&mut file_buffer)?;
+	if changed
+	{
+		file_system.write(path, &file_buffer)?;
+	}
+	return Ok(changed);
+}
 
-	// Update the file size information by adding the byte count of the EXIF chunk
-	// (Note: Due to  the WebP specific encoding function, this vector already
-	// contains the EXIF header characters and size information, as well as the
-	// possible padding byte. Therefore, simply taking the length of this
-	// vector takes their byte count also into account and no further values
-	// need to be added)
-	update_file_size_information(&mut file, encoded_metadata.len() as i32)?;
 
-	// Finally, set the EXIF flag
-	perform_file_action!(set_exif_flag(path, true));
 
+/// Writes the given generally encoded metadata to the WebP image file at 
+/// the specified path (see `write_metadata`)
+pub(crate) fn
+file_write_metadata
+(
+	file_system:              &dyn FileSystem,
+	path:                     &Path,
+	general_encoded_metadata: &Vec<u8>
+)
+-> Result<(), std::io::Error>
+{
+	let mut file_buffer = read_file_to_vec(open_file(file_system, path)?)?;
+	write_metadata(&mut file_buffer, general_encoded_metadata)?;
+	file_system.write(path, &file_buffer)?;
 	return Ok(());
 }
 
@@ -813,25 +616,73 @@ write_metadata
 #[cfg(test)]
 mod tests 
 {
-	use std::fs::copy;
-	use std::fs::remove_file;
 	use std::path::Path;
 
+	use crate::vfs::MemoryFileSystem;
+
 	#[test]
 	fn
 	clear_metadata()
 	-> Result<(), std::io::Error>
 	{
-		// Remove file from previous run and replace it with fresh copy
-		if let Err(error) = remove_file("tests/read_sample_no_exif.webp")
-		{
-			println!("{}", error);
-		}
-		copy("tests/read_sample.webp", "tests/read_sample_no_exif.webp")?;
+		let path = Path::new("read_sample.webp");
+		let file_system = MemoryFileSystem::new();
+		file_system.insert(path, std::fs::read("tests/read_sample.webp")?);
 
-		// Clear the metadata
-		crate::webp::clear_metadata(Path::new("tests/read_sample_no_exif.webp"))?;
+		// Clear the metadata, after which there is none left to read
+		crate::webp::file_clear_metadata(&file_system, path)?;
+		let cleared = file_system.get(path).unwrap();
+		assert!(crate::webp::read_metadata(&mut std::io::Cursor::new(&cleared), &mut Vec::new()).is_err());
 
 		Ok(())
 	}
-}
\ No newline at end of file
+	#[test]
+	fn
+	read_metadata_stops_at_exif_chunk()
+	-> Result<(), std::io::Error>
+	{
+		// VP8X chunk with the EXIF flag set, followed by the EXIF chunk and a
+		// chunk promising way more data than there is in the file
+		let exif_data = vec![0x49, 0x49, 0x2a, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00];
+		let mut chunks = Vec::new();
+		chunks.extend(b"VP8X");
+		chunks.extend([10, 0, 0, 0, 0x08, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+		chunks.extend(b"EXIF");
+		chunks.extend((exif_data.len() as u32).to_le_bytes());
+		chunks.extend(exif_data.iter());
+		chunks.extend(b"JUNK");
+		chunks.extend([0x00, 0x00, 0x00, 0x7f, 0x00, 0x00]);
+
+		let mut webp = b"RIFF".to_vec();
+		webp.extend((chunks.len() as u32 + 4).to_le_bytes());
+		webp.extend(b"WEBP");
+		webp.extend(chunks);
+
+		let raw_exif_data = crate::webp::read_metadata(&mut std::io::Cursor::new(&webp), &mut Vec::new())?;
+		assert_eq!(raw_exif_data[6..], exif_data);
+		Ok(())
+	}
+
+	#[test]
+	fn
+	chunk_exceeding_riff_chunk()
+	{
+		let webp = |chunks: &[u8]| {
+			let mut webp = b"RIFF".to_vec();
+			webp.extend((chunks.len() as u32 + 4).to_le_bytes());
+			webp.extend(b"WEBP");
+			webp.extend(chunks);
+			crate::webp::read_metadata(&mut std::io::Cursor::new(webp), &mut Vec::new())
+		};
+
+		// The first chunk and the EXIF chunk claiming almost 4 GiB of data
+		let error = webp(b"VP8X\xf0\xff\xff\xff").err().unwrap();
+		assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+		let mut chunks = b"VP8X".to_vec();
+		chunks.extend([10, 0, 0, 0, 0x08, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+		chunks.extend(b"EXIF\xf0\xff\xff\xff");
+		let error = webp(&chunks).err().unwrap();
+		assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+	}
+}
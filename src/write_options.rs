@@ -0,0 +1,692 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::exif_tag::ExifTag;
+use crate::exif_version::ExifVersion;
+use crate::read_options::DEFAULT_MAX_STREAM_SIZE;
+use crate::read_options::EmptyValues;
+
+/// The different ways EXIF data can be stored in a PNG file
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum
+PngExifChunk
+{
+	/// Hex encoded "Raw profile type exif" in a zlib compressed zTXt chunk,
+	/// using the given compression level (0 = no compression up to 10 = best
+	/// compression). This is what ImageMagick & co. write and what older
+	/// readers expect.
+	zTXt(u8),
+
+	/// Hex encoded "Raw profile type exif" in an uncompressed tEXt chunk.
+	/// Takes roughly twice the space of the EXIF data but no CPU time for
+	/// compressing.
+	tEXt,
+
+	/// The EXIF data as it is in an eXIf chunk, as defined by the Extensions
+	/// to the PNG 1.2 Specification (version 1.5.0). The most compact and
+	/// fastest option, however not every reader supports it yet.
+	eXIf,
+}
+
+/// The default stamp for `WriteOptions::auto_stamp_software`
+pub const LITTLE_EXIF_SOFTWARE: &str = concat!("little_exif ", env!("CARGO_PKG_VERSION_MAJOR"), ".", env!("CARGO_PKG_VERSION_MINOR"));
+
+/// Whether and how writing metadata gets recorded in the metadata itself,
+/// see `WriteOptions::auto_stamp_software`
+#[derive(Debug, Clone, PartialEq)]
+pub enum
+SoftwareStamp
+{
+	/// Write the tags as they are
+	Off,
+
+	/// Append `LITTLE_EXIF_SOFTWARE` (e.g. "little_exif 0.3") to `Software`
+	LittleExif,
+
+	/// Append the given string to `Software`, e.g. the name of the
+	/// application using little_exif
+	Custom(String),
+}
+
+/// What happens with a tag after a write hook has seen it, see
+/// `WriteOptions::hook`
+#[derive(Debug, Clone, PartialEq)]
+pub enum
+TagHookAction
+{
+	/// Write the tag as it is
+	Keep,
+
+	/// Write the given tag instead, e.g. a normalized version of it
+	Replace(ExifTag),
+
+	/// Don't write the tag
+	Skip,
+
+	/// Don't write anything and return an error with the given reason
+	Abort(String),
+}
+
+/// How a text value exceeding the maximum length set by
+/// `WriteOptions::max_length` gets shortened
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum
+Truncation
+{
+	/// Cut the value at the maximum length
+	Cut,
+
+	/// Cut the value and end it with "...", so readers can tell that it has
+	/// been shortened. The dots count towards the maximum length.
+	Ellipsis,
+
+	/// Cut the value and write the complete one to the XMP packet of the file
+	/// instead, using the property of the same name in the `tiff` (IFD0
+	/// tags) or `exif` (all other tags) namespace. Writing fails for file
+	/// types without XMP support if a value actually needs to be cut.
+	MoveToXmp,
+}
+
+/// Where the temporary file of a safe write (see `WriteOptions::safe_write`)
+/// gets created
+#[derive(Debug, Clone, PartialEq)]
+pub enum
+TempFileLocation
+{
+	/// Next to the file, which then gets replaced by renaming the temporary
+	/// file
+	SameDirectory,
+
+	/// In the temporary directory of the system (see `std::env::temp_dir`)
+	SystemTemp,
+
+	/// In the given directory, which has to exist
+	Directory(PathBuf),
+}
+
+/// A callback that gets invoked for every tag before it is written
+pub type TagHook = Arc<dyn Fn(&ExifTag) -> TagHookAction + Send + Sync>;
+
+/// Options for fine-tuning how `Metadata::write_to_file_with_options` writes
+/// the metadata to a file. The options are set using chained calls, starting
+/// with the defaults provided by `WriteOptions::new()`, which can be changed
+/// using a `config::Config`.
+///
+/// # Examples
+/// ```no_run
+/// use little_exif::write_options::PngExifChunk;
+/// use little_exif::write_options::WriteOptions;
+///
+/// let options = WriteOptions::new().png_exif_chunk(PngExifChunk::zTXt(10));
+/// ```
+#[derive(Clone)]
+pub struct
+WriteOptions
+{
+	pub(crate) png_exif_chunk:     PngExifChunk,
+	pub(crate) validate_crc:       bool,
+	pub(crate) software_stamp:     SoftwareStamp,
+	pub(crate) hooks:              Vec<TagHook>,
+	pub(crate) exif_version:       Option<ExifVersion>,
+	pub(crate) safe_write:         bool,
+	pub(crate) temp_file_location: TempFileLocation,
+	pub(crate) backup:             bool,
+	pub(crate) max_stream_size:    u64,
+	pub(crate) deterministic:      bool,
+	pub(crate) max_metadata_bytes: Option<usize>,
+	pub(crate) trim_to_budget:     bool,
+	pub(crate) empty_values:       EmptyValues,
+	pub(crate) length_limits:      Vec<(ExifTag, usize, Truncation)>,
+	#[cfg(feature = "xattr")]
+	pub(crate) mirror_xattrs:      bool,
+}
+
+impl
+WriteOptions
+{
+	/// Constructs the default options, taking the defaults set by the
+	/// current `Config` (see `Config::current`) into account:
+	/// - PNG: zTXt chunk with compression level 8
+	/// - PNG: Validate the CRC of every chunk before modifying the file
+	/// - No stamping of `Software` and `ModifyDate` (unless configured
+	///   otherwise)
+	/// - No write hooks
+	/// - Tags are written regardless of the EXIF version they belong to
+	/// - Files are overwritten in place, without keeping a backup (unless
+	///   configured otherwise)
+	/// - Streams are buffered up to `DEFAULT_MAX_STREAM_SIZE` bytes
+	/// - Tags are written in the order they have been set in
+	/// - No limit for the size of the metadata
+	/// - Tags without an actual value are written as they are
+	/// - No limit for the length of text values
+	pub fn
+	new
+	()
+	-> WriteOptions
+	{
+		WriteOptions::from_config(&Config::current())
+	}
+
+	/// Constructs the default options, taking the defaults from the given
+	/// config instead of the current one
+	pub fn
+	from_config
+	(
+		config: &Config
+	)
+	-> WriteOptions
+	{
+		WriteOptions
+		{
+			png_exif_chunk:     PngExifChunk::zTXt(8),
+			validate_crc:       true,
+			software_stamp:     config.software_stamp.clone(),
+			hooks:              Vec::new(),
+			exif_version:       None,
+			safe_write:         false,
+			temp_file_location: TempFileLocation::SameDirectory,
+			backup:             config.backup,
+			max_stream_size:    DEFAULT_MAX_STREAM_SIZE,
+			deterministic:      false,
+			max_metadata_bytes: None,
+			trim_to_budget:     false,
+			empty_values:       EmptyValues::Keep,
+			length_limits:      Vec::new(),
+			#[cfg(feature = "xattr")]
+			mirror_xattrs:      false,
+		}
+	}
+
+	/// Sets the type of chunk that gets used for storing EXIF data in PNG
+	/// files. Compression levels for zTXt chunks above 10 are treated as 10.
+	pub fn
+	png_exif_chunk
+	(
+		mut self,
+		png_exif_chunk: PngExifChunk
+	)
+	-> WriteOptions
+	{
+		self.png_exif_chunk = match png_exif_chunk
+		{
+			PngExifChunk::zTXt(level) => PngExifChunk::zTXt(std::cmp::min(level, 10)),
+			other                     => other,
+		};
+		self
+	}
+
+	/// Sets whether the CRC of every PNG chunk gets validated before the file
+	/// is modified. If not, only chunks that may contain metadata are checked
+	/// and the rest (e.g. the image data in IDAT chunks) is copied as it is.
+	pub fn
+	validate_crc
+	(
+		mut self,
+		validate_crc: bool
+	)
+	-> WriteOptions
+	{
+		self.validate_crc = validate_crc;
+		self
+	}
+
+	/// Sets whether every write records itself in the written metadata:
+	/// The stamp gets appended to the `Software` tag (separated by "; ",
+	/// unless it's already in there) and `ModifyDate` is set to the current
+	/// time in UTC, with `OffsetTime` set to `+00:00`. The metadata itself
+	/// is not modified. Use `SoftwareStamp::Off` for workflows that require
+	/// the tags to be written exactly as they are.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::write_options::SoftwareStamp;
+	/// use little_exif::write_options::WriteOptions;
+	///
+	/// let options = WriteOptions::new().auto_stamp_software(SoftwareStamp::Custom("PhotoSorter 2.1".to_string()));
+	/// ```
+	pub fn
+	auto_stamp_software
+	(
+		mut self,
+		software_stamp: SoftwareStamp
+	)
+	-> WriteOptions
+	{
+		self.software_stamp = software_stamp;
+		self
+	}
+
+	/// Registers a callback that gets invoked for every tag before it is
+	/// written, e.g. for enforcing a policy like never writing GPS data. It
+	/// decides whether the tag gets written, replaced or skipped, or whether
+	/// the write gets aborted (see `TagHookAction`). Hooks are invoked in the
+	/// order of registration, each one seeing the tag as returned by the
+	/// previous one. The metadata itself is not modified.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::exif_tag::ExifTag;
+	/// use little_exif::exif_tag::ExifTagGroup;
+	/// use little_exif::write_options::TagHookAction;
+	/// use little_exif::write_options::WriteOptions;
+	///
+	/// let options = WriteOptions::new()
+	///     .hook(|tag| {
+	///         println!("Writing {}", tag.name());
+	///         TagHookAction::Keep
+	///     })
+	///     .hook(|tag| match tag.get_group()
+	///     {
+	///         ExifTagGroup::GPSIFD => TagHookAction::Skip,
+	///         _                    => TagHookAction::Keep,
+	///     });
+	/// ```
+	pub fn
+	hook
+	(
+		mut self,
+		hook: impl Fn(&ExifTag) -> TagHookAction + Send + Sync + 'static
+	)
+	-> WriteOptions
+	{
+		self.hooks.push(Arc::new(hook));
+		self
+	}
+
+	/// Sets the version of the EXIF standard the written data has to comply
+	/// with. Tags introduced by later versions get dropped or converted and
+	/// `ExifVersion` is set accordingly, see the `exif_version` module for
+	/// details. This is applied after the write hooks.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::exif_version::ExifVersion;
+	/// use little_exif::write_options::WriteOptions;
+	///
+	/// let options = WriteOptions::new().exif_version(ExifVersion::V220);
+	/// ```
+	pub fn
+	exif_version
+	(
+		mut self,
+		exif_version: ExifVersion
+	)
+	-> WriteOptions
+	{
+		self.exif_version = Some(exif_version);
+		self
+	}
+
+	/// Sets whether files are written in a way that never leaves a truncated
+	/// image behind, e.g. when the disk runs full while rewriting a large
+	/// file. The new file is written to a temporary file next to the original
+	/// one first (see `temp_file_location`), which then replaces the original.
+	/// If there is not enough free space for the temporary file, the write is
+	/// refused with an error containing an `InsufficientSpace` and the
+	/// original stays untouched. The free space is checked before creating
+	/// the temporary file, running out of space nonetheless while writing it
+	/// is detected as well.
+	/// This requires write access to the directory of the file and
+	/// temporarily takes up the space of the file a second time.
+	/// Concurrent readers of the file never see a partially written file,
+	/// see the crate documentation on concurrent access.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::write_options::WriteOptions;
+	///
+	/// let options = WriteOptions::new().safe_write(true);
+	/// ```
+	pub fn
+	safe_write
+	(
+		mut self,
+		safe_write: bool
+	)
+	-> WriteOptions
+	{
+		self.safe_write = safe_write;
+		self
+	}
+
+	/// Sets where the temporary file of a safe write gets created, next to
+	/// the file by default. Another location avoids taking up the space of
+	/// the file twice in its own directory, e.g. if a network share has a
+	/// tight quota per directory. However, the temporary file can't be
+	/// renamed across file systems: If the location is on another file
+	/// system, the temporary file gets copied next to the original first,
+	/// which then gets replaced by renaming the copy. This keeps the original
+	/// intact if copying fails, but takes up the space twice after all.
+	/// Has no effect unless `safe_write` is set.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::write_options::TempFileLocation;
+	/// use little_exif::write_options::WriteOptions;
+	///
+	/// let options = WriteOptions::new()
+	///     .safe_write(true)
+	///     .temp_file_location(TempFileLocation::SystemTemp);
+	/// ```
+	pub fn
+	temp_file_location
+	(
+		mut self,
+		temp_file_location: TempFileLocation
+	)
+	-> WriteOptions
+	{
+		self.temp_file_location = temp_file_location;
+		self
+	}
+
+	/// Sets whether the capture date and the rating get mirrored into the
+	/// extended attributes of the file after writing it, see
+	/// `extended_attributes`. Failing to set them (e.g. on file systems
+	/// without support for them) fails the write, although the file itself
+	/// has already been written. Only available with the `xattr` feature.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::write_options::WriteOptions;
+	///
+	/// let options = WriteOptions::new().mirror_xattrs(true);
+	/// ```
+	#[cfg(feature = "xattr")]
+	pub fn
+	mirror_xattrs
+	(
+		mut self,
+		mirror_xattrs: bool
+	)
+	-> WriteOptions
+	{
+		self.mirror_xattrs = mirror_xattrs;
+		self
+	}
+
+	/// Sets whether the untouched original gets preserved alongside the
+	/// edited file, following the convention of ExifTool: The original of
+	/// `image.jpg` is kept as `image.jpg_original`. If there already is such
+	/// a backup (e.g. from an earlier write), it is kept as it is, so the
+	/// backup always holds the file as it was before the first write. Use
+	/// `Metadata::restore_backup` for undoing the changes.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::write_options::WriteOptions;
+	///
+	/// let options = WriteOptions::new().backup(true);
+	/// ```
+	pub fn
+	backup
+	(
+		mut self,
+		backup: bool
+	)
+	-> WriteOptions
+	{
+		self.backup = backup;
+		self
+	}
+
+	/// Sets the maximum number of bytes of the input that get buffered by
+	/// `Metadata::write_to_stream`. Larger inputs are rejected with an error
+	/// of kind `FileTooLarge`.
+	pub fn
+	max_stream_size
+	(
+		mut self,
+		max_stream_size: u64
+	)
+	-> WriteOptions
+	{
+		self.max_stream_size = max_stream_size;
+		self
+	}
+
+	/// Sets whether identical inputs always result in identical outputs, e.g.
+	/// for content-addressed storage or reproducible builds:
+	/// - `ModifyDate` is not set to the current time when stamping (see
+	///   `auto_stamp_software`), only `Software` gets stamped
+	/// - The tags of each IFD are written ordered by their tag ID, regardless
+	///   of the order they have been set in
+	///
+	/// Padding bytes (e.g. of WebP chunks and strings) are always zero.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::write_options::WriteOptions;
+	///
+	/// let options = WriteOptions::new().deterministic(true);
+	/// ```
+	pub fn
+	deterministic
+	(
+		mut self,
+		deterministic: bool
+	)
+	-> WriteOptions
+	{
+		self.deterministic = deterministic;
+		self
+	}
+
+	/// Sets the maximum size of the encoded EXIF data in bytes (the TIFF data
+	/// as returned by `Metadata::encode`, without the container specific
+	/// header), e.g. for messaging platforms with strict size limits. If the
+	/// metadata exceeds the budget, writing fails with an error containing a
+	/// `MetadataTooLarge`, unless trimming is enabled (see `trim_to_budget`).
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::write_options::WriteOptions;
+	///
+	/// let options = WriteOptions::new().max_metadata_bytes(8 * 1024).trim_to_budget(true);
+	/// ```
+	pub fn
+	max_metadata_bytes
+	(
+		mut self,
+		max_metadata_bytes: usize
+	)
+	-> WriteOptions
+	{
+		self.max_metadata_bytes = Some(max_metadata_bytes);
+		self
+	}
+
+	/// Sets whether metadata exceeding the budget set by `max_metadata_bytes`
+	/// gets trimmed instead of failing: Tags are dropped, starting with the
+	/// lowest priority ones (maker notes and unknown tags first, orientation,
+	/// copyright and capture date last) until the rest fits. If even the
+	/// empty metadata exceeds the budget, writing fails. The metadata itself
+	/// is not modified. There are no thumbnails to drop, as only IFD0, the
+	/// ExifIFD and the GPS IFD get written.
+	pub fn
+	trim_to_budget
+	(
+		mut self,
+		trim_to_budget: bool
+	)
+	-> WriteOptions
+	{
+		self.trim_to_budget = trim_to_budget;
+		self
+	}
+
+	/// Sets how tags without an actual value (e.g. zero-length entries or
+	/// strings consisting of whitespace only) are handled, see `EmptyValues`.
+	/// This is applied after the write hooks, so a hook may replace an empty
+	/// tag by a proper one. The metadata itself is not modified.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::read_options::EmptyValues;
+	/// use little_exif::write_options::WriteOptions;
+	///
+	/// let options = WriteOptions::new().empty_values(EmptyValues::Skip);
+	/// ```
+	pub fn
+	empty_values
+	(
+		mut self,
+		empty_values: EmptyValues
+	)
+	-> WriteOptions
+	{
+		self.empty_values = empty_values;
+		self
+	}
+
+	/// Sets the maximum length of the value of the given tag (selected by
+	/// its type, the value of the given tag is ignored), e.g. for platforms
+	/// that only accept short descriptions, and how longer values get
+	/// shortened (see `Truncation`). The length is measured in bytes as
+	/// stored in the file: Without the NUL terminator of `STRING` tags and
+	/// without the character code of `UserComment`. Values are never cut
+	/// within a character. Limits for tags that don't hold text are ignored
+	/// and a later limit for the same tag replaces the earlier one. This is
+	/// applied after the write hooks. The metadata itself is not modified.
+	///
+	/// # Examples
+	/// ```no_run
+	/// use little_exif::exif_tag::ExifTag;
+	/// use little_exif::write_options::Truncation;
+	/// use little_exif::write_options::WriteOptions;
+	///
+	/// let options = WriteOptions::new()
+	///     .max_length(ExifTag::ImageDescription(String::new()), 200, Truncation::Ellipsis)
+	///     .max_length(ExifTag::UserComment(Vec::new()),         500, Truncation::MoveToXmp);
+	/// ```
+	pub fn
+	max_length
+	(
+		mut self,
+		tag:        ExifTag,
+		max_length: usize,
+		truncation: Truncation
+	)
+	-> WriteOptions
+	{
+		self.length_limits.retain(|(limited, _, _)| limited.as_u16() != tag.as_u16() || limited.get_group() != tag.get_group());
+		self.length_limits.push((tag, max_length, truncation));
+		self
+	}
+
+	/// Checks if values may get moved to XMP, see `Truncation::MoveToXmp`
+	pub(crate) fn
+	moves_overflow_to_xmp
+	(
+		&self
+	)
+	-> bool
+	{
+		self.length_limits.iter().any(|(_, _, truncation)| *truncation == Truncation::MoveToXmp)
+	}
+}
+
+impl
+std::fmt::Debug
+for
+WriteOptions
+{
+	fn
+	fmt
+	(
+		&self,
+		f: &mut std::fmt::Formatter<'_>
+	)
+	-> std::fmt::Result
+	{
+		let mut debug_struct = f.debug_struct("WriteOptions");
+		debug_struct
+			.field("png_exif_chunk",     &self.png_exif_chunk)
+			.field("validate_crc",       &self.validate_crc)
+			.field("software_stamp",     &self.software_stamp)
+			.field("hooks",              &self.hooks.len())
+			.field("exif_version",       &self.exif_version)
+			.field("safe_write",         &self.safe_write)
+			.field("temp_file_location", &self.temp_file_location)
+			.field("backup",             &self.backup)
+			.field("max_stream_size",    &self.max_stream_size)
+			.field("deterministic",      &self.deterministic)
+			.field("max_metadata_bytes", &self.max_metadata_bytes)
+			.field("trim_to_budget",     &self.trim_to_budget)
+			.field("empty_values",       &self.empty_values)
+			.field("length_limits",      &self.length_limits);
+		#[cfg(feature = "xattr")]
+		debug_struct.field("mirror_xattrs", &self.mirror_xattrs);
+		debug_struct.finish()
+	}
+}
+
+impl
+Default
+for
+WriteOptions
+{
+	fn
+	default
+	()
+	-> WriteOptions
+	{
+		WriteOptions::new()
+	}
+}
+
+/// Where `Metadata::write_to_file_or_sidecar` has written the metadata to
+#[derive(Debug, Clone, PartialEq)]
+pub enum
+WriteDestination
+{
+	/// Into the file itself
+	File,
+
+	/// Into the sidecar file at the given path, as the file is read-only, in
+	/// use or can't hold embedded metadata
+	Sidecar(PathBuf),
+}
+
+/// The format of the sidecar file written by
+/// `Metadata::write_to_file_or_sidecar_with_format`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum
+SidecarFormat
+{
+	/// The raw TIFF encoded EXIF data, like the `.exif` files written by
+	/// ExifTool
+	#[default]
+	Exif,
+
+	/// An XMP packet with the tags as properties of the `tiff` (IFD0 tags)
+	/// and `exif` (all other tags) namespaces, named after the tags. Numbers
+	/// are written like by `export::format_value`, as ordered array if there
+	/// are several. Tags with undefined data other than `UserComment` are
+	/// left out.
+	Xmp,
+}
+
+impl
+SidecarFormat
+{
+	/// Gets the extension that gets appended to the name of the file
+	pub(crate) fn
+	extension
+	(
+		&self
+	)
+	-> &'static str
+	{
+		match self
+		{
+			SidecarFormat::Exif => ".exif",
+			SidecarFormat::Xmp  => ".xmp",
+		}
+	}
+}
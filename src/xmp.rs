@@ -0,0 +1,915 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Reading and writing the XMP packet of JPEG, PNG and GIF files, stored in
+//! an APP1 segment after the one with the EXIF data (JPEG), in an iTXt chunk
+//! with the keyword `XML:com.adobe.xmp` (PNG) or in an application extension
+//! identified by `XMP DataXMP` (GIF).
+//!
+//! little_exif doesn't parse XMP into a data model. Packets are read and
+//! written as text, and helpers like `panorama` set their properties by
+//! replacing them in the packet (keeping everything else) or by creating a
+//! new packet if there is none. Properties are expected to use the common
+//! prefix of their namespace (e.g. `GPano` for the Photo Sphere namespace).
+//! Their values are given by `XmpValue`, which keeps arrays and language
+//! alternatives (`LangAlt`) intact, so that e.g. localized captions survive
+//! reading and writing them via `get_value` and `set_values`.
+//! Extended XMP, i.e. packets larger than a single APP1 segment, is not
+//! supported.
+//!
+//! # Examples
+//! ```no_run
+//! use little_exif::xmp::read_xmp_from_vec;
+//!
+//! let file_buffer = std::fs::read("image.jpg").unwrap();
+//! if let Some(packet) = read_xmp_from_vec(&file_buffer).unwrap()
+//! {
+//!     println!("{}", packet);
+//! }
+//! ```
+
+use crate::filetype::FileExtension;
+use crate::general_file_io::io_error;
+use crate::gif;
+use crate::jpg;
+use crate::png;
+
+/// A new, empty packet that properties get added to
+pub(crate) const EMPTY_PACKET: &str = concat!(
+	"<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n",
+	"<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n",
+	" <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n",
+	" </rdf:RDF>\n",
+	"</x:xmpmeta>\n",
+	"<?xpacket end=\"w\"?>",
+);
+
+const RDF_END: &str = "</rdf:RDF>";
+
+/// The prefix and URI of an XMP namespace
+pub type Namespace = (&'static str, &'static str);
+
+/// The language of the default value of a `LangAlt`
+pub const DEFAULT_LANGUAGE: &str = "x-default";
+
+/// A language alternative, i.e. the same text in different languages. Each
+/// alternative is identified by its RFC 3066 language tag (e.g. `en-US`),
+/// the one for `DEFAULT_LANGUAGE` is kept first as required by XMP.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct
+LangAlt
+{
+	alternatives: Vec<(String, String)>,
+}
+
+impl
+LangAlt
+{
+	/// Constructs a language alternative without any values
+	pub fn
+	new()
+	-> LangAlt
+	{
+		LangAlt::default()
+	}
+
+	/// Adds the value for the given language, see `set`
+	pub fn
+	with
+	(
+		mut self,
+		language: &str,
+		value:    &str
+	)
+	-> LangAlt
+	{
+		self.set(language, value);
+		self
+	}
+
+	/// Sets the value for the given language, replacing the previous one
+	pub fn
+	set
+	(
+		&mut self,
+		language: &str,
+		value:    &str
+	)
+	{
+		if let Some(alternative) = self.alternatives.iter_mut().find(|(known, _)| known.eq_ignore_ascii_case(language))
+		{
+			alternative.1 = value.to_string();
+			return;
+		}
+
+		let alternative = (language.to_string(), value.to_string());
+		match language.eq_ignore_ascii_case(DEFAULT_LANGUAGE)
+		{
+			true  => self.alternatives.insert(0, alternative),
+			false => self.alternatives.push(alternative),
+		}
+	}
+
+	/// Gets the value for exactly the given language. Language tags are
+	/// compared case-insensitively.
+	pub fn
+	get
+	(
+		&self,
+		language: &str
+	)
+	-> Option<&str>
+	{
+		self.alternatives.iter()
+			.find(|(known, _)| known.eq_ignore_ascii_case(language))
+			.map(|(_, value)| value.as_str())
+	}
+
+	/// Gets the value that fits the given language best: The one for exactly
+	/// that language, for the same primary language (e.g. `de-AT` for
+	/// `de-DE`) or the default value, in this order
+	pub fn
+	best_match
+	(
+		&self,
+		language: &str
+	)
+	-> Option<&str>
+	{
+		let primary = |tag: &str| tag.split('-').next().unwrap_or_default().to_ascii_lowercase();
+		self.get(language)
+			.or_else(|| self.alternatives.iter()
+				.find(|(known, _)| !known.eq_ignore_ascii_case(DEFAULT_LANGUAGE) && primary(known) == primary(language))
+				.map(|(_, value)| value.as_str()))
+			.or_else(|| self.default_value())
+	}
+
+	/// Gets the value for `DEFAULT_LANGUAGE`, or the first one if there is
+	/// none for it
+	pub fn
+	default_value
+	(
+		&self
+	)
+	-> Option<&str>
+	{
+		self.get(DEFAULT_LANGUAGE).or_else(|| self.alternatives.first().map(|(_, value)| value.as_str()))
+	}
+
+	/// Gets the languages and their values, in the order they are stored
+	pub fn
+	alternatives
+	(
+		&self
+	)
+	-> &[(String, String)]
+	{
+		&self.alternatives
+	}
+
+	/// Checks whether there are no values
+	pub fn
+	is_empty
+	(
+		&self
+	)
+	-> bool
+	{
+		self.alternatives.is_empty()
+	}
+}
+
+/// The value of an XMP property
+#[derive(Debug, Clone, PartialEq)]
+pub enum
+XmpValue
+{
+	/// A simple text value
+	Simple(String),
+
+	/// An unordered array (`rdf:Bag`), e.g. `dc:subject`
+	Bag(Vec<String>),
+
+	/// An ordered array (`rdf:Seq`), e.g. `dc:creator`
+	Seq(Vec<String>),
+
+	/// Alternatives without languages (`rdf:Alt`)
+	Alt(Vec<String>),
+
+	/// Alternatives in different languages (`rdf:Alt` with `xml:lang`
+	/// qualifiers), e.g. `dc:title` and `dc:description`
+	LangAlt(LangAlt),
+}
+
+impl
+XmpValue
+{
+	/// Gets the values of the property: A simple value as single item, the
+	/// items of an array and the values of a language alternative, starting
+	/// with the default one
+	pub fn
+	items
+	(
+		&self
+	)
+	-> Vec<String>
+	{
+		match self
+		{
+			XmpValue::Simple(value)   => vec![value.clone()],
+			XmpValue::Bag(items)      |
+			XmpValue::Seq(items)      |
+			XmpValue::Alt(items)      => items.clone(),
+			XmpValue::LangAlt(values) => values.alternatives().iter().map(|(_, value)| value.clone()).collect(),
+		}
+	}
+}
+
+/// Gets the type of the data, which has to be one supporting XMP so far
+fn
+check_file_type
+(
+	file_buffer: &[u8]
+)
+-> Result<FileExtension, std::io::Error>
+{
+	match FileExtension::from_signature(file_buffer)
+	{
+		Some(FileExtension::JPEG)       => Ok(FileExtension::JPEG),
+		Some(FileExtension::PNG { .. }) => Ok(FileExtension::PNG { as_zTXt_chunk: true }),
+		Some(FileExtension::GIF)        => Ok(FileExtension::GIF),
+		_                               => io_error!(Unsupported, "XMP data is only supported for JPEG, PNG and GIF files!"),
+	}
+}
+
+/// Reads the XMP packet of the JPEG, PNG or GIF image. Returns `None` if there
+/// is none.
+/// - If the packet is not valid UTF-8, an error of kind `InvalidData` gets
+///   returned.
+pub fn
+read_xmp_from_vec
+(
+	file_buffer: &[u8]
+)
+-> Result<Option<String>, std::io::Error>
+{
+	let packet = match check_file_type(file_buffer)?
+	{
+		FileExtension::JPEG => jpg::read_xmp(file_buffer)?,
+		FileExtension::GIF  => gif::read_xmp(file_buffer)?,
+		_                   => png::read_xmp(file_buffer)?,
+	};
+	match packet
+	{
+		Some(packet) => match String::from_utf8(packet)
+		{
+			Ok(packet) => Ok(Some(packet)),
+			Err(_)     => io_error!(InvalidData, "XMP packet is not valid UTF-8!"),
+		},
+		None         => Ok(None),
+	}
+}
+
+/// Replaces the XMP packet of the JPEG, PNG or GIF image by the given one, which
+/// is written as is. The EXIF data is not changed.
+pub fn
+write_xmp_to_vec
+(
+	file_buffer: &mut Vec<u8>,
+	packet:      &str
+)
+-> Result<(), std::io::Error>
+{
+	match check_file_type(file_buffer)?
+	{
+		FileExtension::JPEG => jpg::write_xmp(file_buffer, packet.as_bytes()),
+		FileExtension::GIF  => gif::write_xmp(file_buffer, packet.as_bytes()),
+		_                   => png::write_xmp(file_buffer, packet.as_bytes()),
+	}
+}
+
+/// Escapes the characters that have a special meaning in XML attributes,
+/// including line breaks and tabs that would otherwise be normalized to
+/// spaces by XML parsers
+pub(crate) fn
+escape
+(
+	value: &str
+)
+-> String
+{
+	value
+		.replace('&',  "&amp;")
+		.replace('<',  "&lt;")
+		.replace('>',  "&gt;")
+		.replace('"',  "&quot;")
+		.replace('\n', "&#xA;")
+		.replace('\r', "&#xD;")
+		.replace('\t', "&#x9;")
+}
+
+/// Reverts `escape`, including the numeric character references other tools
+/// may use
+fn
+unescape
+(
+	value: &str
+)
+-> String
+{
+	let mut unescaped = String::new();
+	let mut rest = value;
+	while let Some(start) = rest.find('&')
+	{
+		unescaped.push_str(&rest[..start]);
+		let reference = rest[start..].find(';').map(|end| &rest[start+1..start+end]);
+		let character = match reference
+		{
+			Some("amp")  => Some('&'),
+			Some("lt")   => Some('<'),
+			Some("gt")   => Some('>'),
+			Some("quot") => Some('"'),
+			Some("apos") => Some('\''),
+			Some(number) if number.starts_with("#x") => u32::from_str_radix(&number[2..], 16).ok().and_then(char::from_u32),
+			Some(number) if number.starts_with('#')  => number[1..].parse::<u32>().ok().and_then(char::from_u32),
+			_            => None,
+		};
+		match (character, reference)
+		{
+			(Some(character), Some(reference)) => {
+				unescaped.push(character);
+				rest = &rest[start + reference.len() + 2..];
+			},
+			_                                  => {
+				unescaped.push('&');
+				rest = &rest[start+1..];
+			},
+		}
+	}
+	unescaped.push_str(rest);
+	return unescaped;
+}
+
+/// Gets the value of a simple property of the namespace, written either as
+/// attribute or as element. Returns `None` if the packet doesn't contain it.
+pub(crate) fn
+get_property
+(
+	packet:    &str,
+	namespace: Namespace,
+	name:      &str
+)
+-> Option<String>
+{
+	let property = format!("{}:{}", namespace.0, name);
+
+	// Attributes, preceded by whitespace
+	let attribute = format!("{}=", property);
+	for (found, _) in packet.match_indices(&attribute)
+	{
+		let value_start = found + attribute.len();
+		let quote = packet[value_start..].chars().next()?;
+		if packet[..found].ends_with(char::is_whitespace) && (quote == '"' || quote == '\'')
+		{
+			let value_end = packet[value_start+1..].find(quote)? + value_start + 1;
+			return Some(unescape(&packet[value_start+1..value_end]));
+		}
+	}
+
+	// Elements with text content
+	let opening = format!("<{}>", property);
+	let value_start = packet.find(&opening)? + opening.len();
+	let value_end = packet[value_start..].find(&format!("</{}>", property))? + value_start;
+	return Some(unescape(packet[value_start..value_end].trim()));
+}
+
+/// Gets the value of a property of the namespace, which is either a simple
+/// one (see `get_property`) or an array written as element. Returns `None`
+/// if the packet doesn't contain it.
+///
+/// # Examples
+/// ```
+/// use little_exif::xmp::get_value;
+/// use little_exif::xmp::XmpValue;
+///
+/// let packet = concat!(
+///     "<rdf:Description><dc:title><rdf:Alt>",
+///     "<rdf:li xml:lang=\"x-default\">Lighthouse</rdf:li>",
+///     "<rdf:li xml:lang=\"de-DE\">Leuchtturm</rdf:li>",
+///     "</rdf:Alt></dc:title></rdf:Description>"
+/// );
+/// let namespace = ("dc", "http://purl.org/dc/elements/1.1/");
+/// match get_value(packet, namespace, "title")
+/// {
+///     Some(XmpValue::LangAlt(title)) => assert_eq!(title.best_match("de-AT"), Some("Leuchtturm")),
+///     _                              => panic!("Not a language alternative!"),
+/// }
+/// ```
+pub fn
+get_value
+(
+	packet:    &str,
+	namespace: Namespace,
+	name:      &str
+)
+-> Option<XmpValue>
+{
+	let value = get_property(packet, namespace, name)?;
+	if !value.contains("<rdf:")
+	{
+		return Some(XmpValue::Simple(value));
+	}
+
+	// The value got unescaped, so the array is taken from the packet again
+	let property    = format!("{}:{}", namespace.0, name);
+	let opening     = format!("<{}>", property);
+	let value_start = packet.find(&opening)? + opening.len();
+	let value_end   = packet[value_start..].find(&format!("</{}>", property))? + value_start;
+	let content     = packet[value_start..value_end].trim_start();
+
+	// Items with their language, if any
+	let mut items = Vec::new();
+	let mut rest  = content;
+	while let Some(item_start) = rest.find("<rdf:li")
+	{
+		let content_start = rest[item_start..].find('>')? + item_start + 1;
+		let attributes    = &rest[item_start + "<rdf:li".len()..content_start - 1];
+		let language      = get_property(&format!(" {}", attributes.trim_end_matches('/')), ("xml", ""), "lang");
+		if attributes.ends_with('/')
+		{
+			items.push((language, String::new()));
+			rest = &rest[content_start..];
+			continue;
+		}
+		let content_end = rest[content_start..].find("</rdf:li>")? + content_start;
+		items.push((language, unescape(rest[content_start..content_end].trim())));
+		rest = &rest[content_end..];
+	}
+
+	let values = || items.iter().map(|(_, value)| value.clone()).collect::<Vec<String>>();
+	if content.starts_with("<rdf:Bag")
+	{
+		return Some(XmpValue::Bag(values()));
+	}
+	if content.starts_with("<rdf:Seq")
+	{
+		return Some(XmpValue::Seq(values()));
+	}
+	if content.starts_with("<rdf:Alt")
+	{
+		if items.is_empty() || items.iter().any(|(language, _)| language.is_none())
+		{
+			return Some(XmpValue::Alt(values()));
+		}
+		let mut alternatives = LangAlt::new();
+		for (language, value) in &items
+		{
+			alternatives.set(language.as_deref().unwrap_or_default(), value);
+		}
+		return Some(XmpValue::LangAlt(alternatives));
+	}
+	return Some(XmpValue::Simple(value));
+}
+
+/// Gets the items of a property of the namespace, see `XmpValue::items`.
+/// Returns `None` if the packet doesn't contain it.
+pub(crate) fn
+get_array_items
+(
+	packet:    &str,
+	namespace: Namespace,
+	name:      &str
+)
+-> Option<Vec<String>>
+{
+	get_value(packet, namespace, name).map(|value| value.items())
+}
+
+/// Finds the end of the tag at the start of the text, i.e. the index of its
+/// closing `>`, skipping quoted attribute values
+fn
+tag_end
+(
+	text: &str
+)
+-> Option<usize>
+{
+	let mut quote = None;
+	for (index, character) in text.char_indices()
+	{
+		match (quote, character)
+		{
+			(None,       '"' | '\'')                => quote = Some(character),
+			(Some(open), _) if open == character => quote = None,
+			(None,       '>')                     => return Some(index),
+			_                                     => (),
+		}
+	}
+	return None;
+}
+
+/// Gets the qualified names (e.g. `dc:title`) of the top-level properties of
+/// the packet, written either as attribute or as element, in the order of
+/// their first occurrence. Fields of structures are not included.
+pub(crate) fn
+property_names
+(
+	packet: &str
+)
+-> Vec<String>
+{
+	let mut names: Vec<String> = Vec::new();
+	let mut rest = packet;
+	while let Some(start) = rest.find("<rdf:Description")
+	{
+		rest = &rest[start + "<rdf:Description".len()..];
+		let end = match tag_end(rest)
+		{
+			Some(end) => end,
+			None      => break,
+		};
+
+		// Properties written as attributes
+		let mut attributes = &rest[..end];
+		while let Some(equals) = attributes.find('=')
+		{
+			let value = attributes[equals+1..].trim_start();
+			let value_end = match value.chars().next()
+			{
+				Some(quote @ ('"' | '\'')) => value[1..].find(quote).map(|index| index + 1),
+				_                          => None,
+			};
+			let value_end = match value_end
+			{
+				Some(value_end) => value_end,
+				None            => break,
+			};
+			names.push(attributes[..equals].trim().to_string());
+			attributes = &value[value_end+1..];
+		}
+
+		let self_closing = rest[..end].ends_with('/');
+		rest = &rest[end+1..];
+		if self_closing
+		{
+			continue;
+		}
+
+		// Properties written as elements, up to the end of the description
+		let mut depth = 0;
+		while let Some(open) = rest.find('<')
+		{
+			rest = &rest[open..];
+			if rest.starts_with("<!--")
+			{
+				match rest.find("-->")
+				{
+					Some(comment_end) => rest = &rest[comment_end+3..],
+					None              => break,
+				}
+				continue;
+			}
+
+			let end = match tag_end(rest)
+			{
+				Some(end) => end,
+				None      => break,
+			};
+			let tag = &rest[1..end];
+			rest = &rest[end+1..];
+
+			if tag.starts_with('/')
+			{
+				if depth == 0
+				{
+					break;
+				}
+				depth -= 1;
+			}
+			else if !tag.starts_with('?') && !tag.starts_with('!')
+			{
+				if depth == 0
+				{
+					names.push(tag.split(|character: char| character.is_whitespace() || character == '/').next().unwrap_or("").to_string());
+				}
+				if !tag.ends_with('/')
+				{
+					depth += 1;
+				}
+			}
+		}
+	}
+
+	// Skip the RDF syntax and namespace declarations
+	let mut properties: Vec<String> = Vec::new();
+	for name in names
+	{
+		let syntax = name.starts_with("rdf:") || name.starts_with("xml:") || name.starts_with("xmlns");
+		if name.contains(':') && !syntax && !properties.contains(&name)
+		{
+			properties.push(name);
+		}
+	}
+	return properties;
+}
+
+/// Removes all occurrences of the property from the packet, written either
+/// as attribute (`prefix:Name="..."`) or as element (`<prefix:Name>...`)
+fn
+remove_property
+(
+	packet:   &mut String,
+	property: &str
+)
+{
+	// Attributes, preceded by whitespace
+	let attribute = format!("{}=", property);
+	let mut search_start = 0;
+	while let Some(found) = packet[search_start..].find(&attribute).map(|index| index + search_start)
+	{
+		let preceded_by_whitespace = packet[..found].ends_with(char::is_whitespace);
+		let quote = packet[found + attribute.len()..].chars().next();
+		let end = quote
+			.filter(|quote| preceded_by_whitespace && (*quote == '"' || *quote == '\''))
+			.and_then(|quote| packet[found + attribute.len() + 1..].find(quote))
+			.map(|index| found + attribute.len() + 1 + index + 1);
+
+		match end
+		{
+			Some(end) => {
+				let start = packet[..found].trim_end().len();
+				packet.replace_range(start..end, "");
+				search_start = start;
+			},
+			None      => search_start = found + attribute.len(),
+		}
+	}
+
+	// Elements, either empty or with content
+	let opening = format!("<{}", property);
+	let closing = format!("</{}>", property);
+	let mut search_start = 0;
+	while let Some(found) = packet[search_start..].find(&opening).map(|index| index + search_start)
+	{
+		let after_name = found + opening.len();
+		let is_element = packet[after_name..].starts_with(|character: char| character == '>' || character == '/' || character.is_whitespace());
+		let tag_end    = packet[after_name..].find('>').map(|index| after_name + index + 1);
+
+		let end = match tag_end
+		{
+			Some(tag_end) if is_element && packet[..tag_end].ends_with("/>") => Some(tag_end),
+			Some(tag_end) if is_element                                     => packet[tag_end..].find(&closing).map(|index| tag_end + index + closing.len()),
+			_                                                               => None,
+		};
+
+		match end
+		{
+			Some(end) => {
+				let start = packet[..found].trim_end().len();
+				packet.replace_range(start..end, "");
+				search_start = start;
+			},
+			None      => search_start = after_name,
+		}
+	}
+}
+
+/// Writes the value as child element of a `rdf:Description`
+fn
+push_element
+(
+	description: &mut String,
+	property:    &str,
+	value:       &XmpValue
+)
+{
+	let (container, items) = match value
+	{
+		XmpValue::Simple(value)   => {
+			description.push_str(&format!("\n   <{0}>{1}</{0}>", property, escape(value)));
+			return;
+		},
+		XmpValue::Bag(items)      => ("rdf:Bag", items.iter().map(|item| (None, item.as_str())).collect::<Vec<_>>()),
+		XmpValue::Seq(items)      => ("rdf:Seq", items.iter().map(|item| (None, item.as_str())).collect::<Vec<_>>()),
+		XmpValue::Alt(items)      => ("rdf:Alt", items.iter().map(|item| (None, item.as_str())).collect::<Vec<_>>()),
+		XmpValue::LangAlt(values) => ("rdf:Alt", values.alternatives().iter().map(|(language, value)| (Some(language.as_str()), value.as_str())).collect::<Vec<_>>()),
+	};
+
+	description.push_str(&format!("\n   <{}>\n    <{}>", property, container));
+	for (language, item) in items
+	{
+		match language
+		{
+			Some(language) => description.push_str(&format!("\n     <rdf:li xml:lang=\"{}\">{}</rdf:li>", escape(language), escape(item))),
+			None           => description.push_str(&format!("\n     <rdf:li>{}</rdf:li>", escape(item))),
+		}
+	}
+	description.push_str(&format!("\n    </{}>\n   </{}>", container, property));
+}
+
+/// Sets the values of properties of the namespace in the packet, or in a new
+/// one if there is none, and returns the resulting packet. Previous values
+/// of the properties are removed, properties with a value of `None` are only
+/// removed. The new values are added to a new `rdf:Description`, simple
+/// ones as attributes and arrays as elements.
+pub fn
+set_values
+(
+	packet:    Option<&str>,
+	namespace: Namespace,
+	values:    &[(&str, Option<XmpValue>)]
+)
+-> Result<String, std::io::Error>
+{
+	let (prefix, uri) = namespace;
+	let mut packet = packet.unwrap_or(EMPTY_PACKET).to_string();
+
+	for (name, _) in values
+	{
+		remove_property(&mut packet, &format!("{}:{}", prefix, name));
+	}
+
+	let mut description = format!("  <rdf:Description rdf:about=\"\"\n    xmlns:{}=\"{}\"", prefix, uri);
+	let mut elements    = String::new();
+	for (name, value) in values
+	{
+		match value
+		{
+			Some(XmpValue::Simple(value)) => description.push_str(&format!("\n    {}:{}=\"{}\"", prefix, name, escape(value))),
+			Some(value)                   => push_element(&mut elements, &format!("{}:{}", prefix, name), value),
+			None                          => (),
+		}
+	}
+	match elements.is_empty()
+	{
+		true  => description.push_str("/>\n "),
+		false => description.push_str(&format!(">{}\n  </rdf:Description>\n ", elements)),
+	}
+
+	let insert_position = match packet.rfind(RDF_END)
+	{
+		Some(position) => position,
+		None           => return io_error!(InvalidData, "XMP packet lacks the rdf:RDF element!"),
+	};
+	packet.insert_str(insert_position, &description);
+
+	return Ok(packet);
+}
+
+/// Sets simple properties of the namespace, see `set_values`
+pub(crate) fn
+set_properties
+(
+	packet:     Option<&str>,
+	namespace:  Namespace,
+	properties: &[(&str, Option<String>)]
+)
+-> Result<String, std::io::Error>
+{
+	let values = properties.iter()
+		.map(|(name, value)| (*name, value.clone().map(XmpValue::Simple)))
+		.collect::<Vec<_>>();
+	return set_values(packet, namespace, &values);
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::xmp::*;
+
+	const TEST_NAMESPACE: Namespace = ("test", "http://example.com/test/");
+
+	#[test]
+	fn
+	replace_properties()
+	-> Result<(), std::io::Error>
+	{
+		let packet = set_properties(None, TEST_NAMESPACE, &[
+			("Title",  Some("Fish & Chips".to_string())),
+			("Rating", Some("3".to_string())),
+		])?;
+		assert!(packet.contains("test:Title=\"Fish &amp; Chips\""));
+
+		// Properties written by other tools as attributes and elements
+		let packet = packet.replace(
+			"</rdf:RDF>",
+			"<rdf:Description test:Rating='5' other:test:Rating=\"1\"><test:Title>Old</test:Title><test:TitleShort/></rdf:Description></rdf:RDF>"
+		);
+
+		let packet = set_properties(Some(&packet), TEST_NAMESPACE, &[
+			("Title",  Some("New".to_string())),
+			("Rating", None),
+		])?;
+		assert_eq!(packet.matches("test:Title=").count(), 1);
+		assert!(!packet.contains("<test:Title>"));
+		assert!(packet.contains("test:Title=\"New\""));
+		assert!(!packet.contains("test:Rating=\"3\"") && !packet.contains("test:Rating='5'"));
+		assert!(packet.contains("<test:TitleShort/>"));
+		assert!(packet.contains("other:test:Rating=\"1\""));
+
+		assert!(set_properties(Some("<x:xmpmeta/>"), TEST_NAMESPACE, &[]).is_err());
+		Ok(())
+	}
+
+	#[test]
+	fn
+	read_properties()
+	-> Result<(), std::io::Error>
+	{
+		let packet = set_properties(None, TEST_NAMESPACE, &[("Title", Some("Fish & <Chips>".to_string()))])?;
+		assert_eq!(get_property(&packet, TEST_NAMESPACE, "Title"), Some("Fish & <Chips>".to_string()));
+		assert_eq!(get_property(&packet, TEST_NAMESPACE, "Rating"), None);
+
+		let packet = "<rdf:Description other:test:Rating='1' test:Rating='5'><test:Label> R&#xe9;d &amp;c </test:Label></rdf:Description>";
+		assert_eq!(get_property(packet, TEST_NAMESPACE, "Rating"), Some("5".to_string()));
+		assert_eq!(get_property(packet, TEST_NAMESPACE, "Label"),  Some("Réd &c".to_string()));
+		assert_eq!(unescape("&unknown; & &#65;"), "&unknown; & A");
+		Ok(())
+	}
+
+	#[test]
+	fn
+	list_property_names()
+	{
+		let packet = concat!(
+			"<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">",
+			"<rdf:Description rdf:about='' xmlns:dc='http://purl.org/dc/elements/1.1/' xmp:Rating=\"5\" test:Note='a > b'>",
+			"<!-- <test:Comment/> --><dc:subject><rdf:Bag><rdf:li>Sea</rdf:li></rdf:Bag></dc:subject>",
+			"<test:Struct rdf:parseType='Resource'><test:Field>1</test:Field></test:Struct><test:Empty/>",
+			"</rdf:Description>",
+			"<rdf:Description rdf:about=''><dc:subject/><test:Other>2</test:Other></rdf:Description>",
+			"</rdf:RDF>"
+		);
+		assert_eq!(property_names(packet), vec!["xmp:Rating", "test:Note", "dc:subject", "test:Struct", "test:Empty", "test:Other"]);
+		assert!(property_names(EMPTY_PACKET).is_empty());
+	}
+
+	#[test]
+	fn
+	read_array_items()
+	{
+		let packet = concat!(
+			"<rdf:Description test:Label='Red'>",
+			"<test:Subject><rdf:Bag><rdf:li>Fish &amp; Chips</rdf:li><rdf:li/><rdf:li> Graz </rdf:li></rdf:Bag></test:Subject>",
+			"<test:Title><rdf:Alt><rdf:li xml:lang=\"x-default\">Title</rdf:li></rdf:Alt></test:Title>",
+			"</rdf:Description>"
+		);
+		assert_eq!(get_array_items(packet, TEST_NAMESPACE, "Subject"), Some(vec!["Fish & Chips".to_string(), String::new(), "Graz".to_string()]));
+		assert_eq!(get_array_items(packet, TEST_NAMESPACE, "Title"),   Some(vec!["Title".to_string()]));
+		assert_eq!(get_array_items(packet, TEST_NAMESPACE, "Label"),   Some(vec!["Red".to_string()]));
+		assert_eq!(get_array_items(packet, TEST_NAMESPACE, "Rating"),  None);
+	}
+
+	#[test]
+	fn
+	language_alternatives()
+	{
+		let mut title = LangAlt::new().with("de-DE", "Leuchtturm").with("en-US", "Lighthouse");
+		title.set(DEFAULT_LANGUAGE, "Lighthouse");
+		title.set("DE-de", "Leuchtturm am Meer");
+		assert_eq!(title.alternatives()[0].0, DEFAULT_LANGUAGE);
+		assert_eq!(title.alternatives().len(), 3);
+		assert_eq!(title.get("de-DE"),        Some("Leuchtturm am Meer"));
+		assert_eq!(title.get("de-AT"),        None);
+		assert_eq!(title.best_match("de-AT"), Some("Leuchtturm am Meer"));
+		assert_eq!(title.best_match("fr-FR"), Some("Lighthouse"));
+		assert_eq!(LangAlt::new().with("fr", "Phare").default_value(), Some("Phare"));
+	}
+
+	#[test]
+	fn
+	write_and_read_values()
+	-> Result<(), std::io::Error>
+	{
+		let title = LangAlt::new().with(DEFAULT_LANGUAGE, "Fish & Chips").with("de", "Fisch & Pommes");
+		let packet = set_values(None, TEST_NAMESPACE, &[
+			("Title",    Some(XmpValue::LangAlt(title.clone()))),
+			("Subject",  Some(XmpValue::Bag(vec!["Fish".to_string(), "Chips".to_string()]))),
+			("Creator",  Some(XmpValue::Seq(vec!["Jane".to_string(), "John".to_string()]))),
+			("Variants", Some(XmpValue::Alt(vec!["Small".to_string(), "Large".to_string()]))),
+			("Rating",   Some(XmpValue::Simple("3".to_string()))),
+		])?;
+		assert!(packet.contains("test:Rating=\"3\""));
+		assert!(packet.contains("<rdf:li xml:lang=\"de\">Fisch &amp; Pommes</rdf:li>"));
+
+		// Everything survives reading and writing the values again
+		let read_back = ["Title", "Subject", "Creator", "Variants", "Rating"].map(|name| (name, get_value(&packet, TEST_NAMESPACE, name)));
+		assert_eq!(read_back[0].1, Some(XmpValue::LangAlt(title)));
+		assert_eq!(read_back[1].1, Some(XmpValue::Bag(vec!["Fish".to_string(), "Chips".to_string()])));
+		assert_eq!(read_back[2].1, Some(XmpValue::Seq(vec!["Jane".to_string(), "John".to_string()])));
+		assert_eq!(read_back[3].1, Some(XmpValue::Alt(vec!["Small".to_string(), "Large".to_string()])));
+		assert_eq!(read_back[4].1, Some(XmpValue::Simple("3".to_string())));
+		let rewritten = set_values(Some(&packet), TEST_NAMESPACE, &read_back)?;
+		for (name, value) in &read_back
+		{
+			assert_eq!(&get_value(&rewritten, TEST_NAMESPACE, name), value);
+		}
+		assert_eq!(rewritten.matches("<test:Title>").count(), 1);
+
+		// Removing an array removes the whole element
+		let removed = set_values(Some(&rewritten), TEST_NAMESPACE, &[("Title", None)])?;
+		assert_eq!(get_value(&removed, TEST_NAMESPACE, "Title"), None);
+		assert!(!removed.contains("Fisch"));
+		Ok(())
+	}
+}
@@ -0,0 +1,482 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Typed access to XMP properties by their qualified name (e.g. `dc:title`),
+//! based on schemas describing the namespace and the types of its fields.
+//!
+//! Schemas for the common namespaces (`dc`, `xmp`, `photoshop`, `tiff` and
+//! `exif`) are built in. Custom schemas, e.g. for internal fields of a
+//! company, are registered for the whole process using
+//! `XmpSchema::register` and are used the same way afterwards. They may also
+//! extend a built-in schema by registering its prefix and URI with
+//! additional fields.
+//!
+//! `set_fields` checks the values against the types of the fields before
+//! writing them, e.g. that an `Integer` field gets a number or that a
+//! `LangAlt` field gets a language alternative. `get_field` returns simple
+//! values of array fields written by other tools as arrays with a single
+//! item. Fields a schema doesn't declare are read and written without
+//! checks, properties of unknown namespaces can't be used.
+//!
+//! # Examples
+//! ```no_run
+//! use little_exif::xmp::XmpValue;
+//! use little_exif::xmp::read_xmp_from_vec;
+//! use little_exif::xmp::write_xmp_to_vec;
+//! use little_exif::xmp_schema::XmpFieldType;
+//! use little_exif::xmp_schema::XmpSchema;
+//! use little_exif::xmp_schema::get_field;
+//! use little_exif::xmp_schema::set_fields;
+//!
+//! XmpSchema::register(XmpSchema::new("acme", "https://acme.example/ns/assets/1.0/")
+//!     .field("AssetId",  XmpFieldType::Integer)
+//!     .field("Projects", XmpFieldType::Bag)
+//! ).unwrap();
+//!
+//! let mut file_buffer = std::fs::read("image.jpg").unwrap();
+//! let packet = read_xmp_from_vec(&file_buffer).unwrap();
+//! let packet = set_fields(packet.as_deref(), &[
+//!     ("acme:AssetId",  Some(XmpValue::Simple("4711".to_string()))),
+//!     ("acme:Projects", Some(XmpValue::Bag(vec!["Spring catalog".to_string()]))),
+//! ]).unwrap();
+//! write_xmp_to_vec(&mut file_buffer, &packet).unwrap();
+//!
+//! assert_eq!(get_field(&packet, "acme:AssetId").unwrap(), Some(XmpValue::Simple("4711".to_string())));
+//! ```
+
+use std::sync::RwLock;
+
+use crate::exif_date::DateCoercion;
+use crate::exif_date::ExifDate;
+use crate::general_file_io::io_error;
+use crate::xmp;
+use crate::xmp::LangAlt;
+use crate::xmp::Namespace;
+use crate::xmp::XmpValue;
+use crate::xmp::DEFAULT_LANGUAGE;
+
+/// Values of properties of a single namespace, see `xmp::set_values`
+type NamespaceValues<'a> = (Namespace, Vec<(&'a str, Option<XmpValue>)>);
+
+static REGISTERED_SCHEMAS: RwLock<Vec<XmpSchema>> = RwLock::new(Vec::new());
+
+/// The types of the values of XMP fields
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum
+XmpFieldType
+{
+	/// Any text
+	Text,
+
+	/// A signed decimal integer, e.g. `-42`
+	Integer,
+
+	/// A decimal number, e.g. `2.5`
+	Real,
+
+	/// `True` or `False`
+	Boolean,
+
+	/// An ISO 8601 date, e.g. `2024`, `2024-05` or `2024-05-06T07:08:09+02:00`
+	Date,
+
+	/// An unordered array of texts
+	Bag,
+
+	/// An ordered array of texts
+	Seq,
+
+	/// Alternatives without languages
+	Alt,
+
+	/// Alternatives in different languages, see `LangAlt`
+	LangAlt,
+}
+
+/// The namespace of XMP properties together with the types of its fields
+#[derive(Debug, Clone, PartialEq)]
+pub struct
+XmpSchema
+{
+	namespace: Namespace,
+	fields:    Vec<(&'static str, XmpFieldType)>,
+}
+
+/// Checks if the name is a valid XML name without colon, as required for
+/// prefixes and field names
+fn
+is_valid_name
+(
+	name: &str
+)
+-> bool
+{
+	return name.starts_with(|character: char| character.is_alphabetic() || character == '_')
+		&& name.chars().all(|character| character.is_alphanumeric() || matches!(character, '_' | '-' | '.'));
+}
+
+/// Gets the schemas that are built in
+fn
+builtin_schemas
+()
+-> Vec<XmpSchema>
+{
+	vec![
+		XmpSchema::new("dc", "http://purl.org/dc/elements/1.1/")
+			.field("contributor", XmpFieldType::Bag)
+			.field("creator",     XmpFieldType::Seq)
+			.field("date",        XmpFieldType::Seq)
+			.field("description", XmpFieldType::LangAlt)
+			.field("format",      XmpFieldType::Text)
+			.field("language",    XmpFieldType::Bag)
+			.field("publisher",   XmpFieldType::Bag)
+			.field("rights",      XmpFieldType::LangAlt)
+			.field("subject",     XmpFieldType::Bag)
+			.field("title",       XmpFieldType::LangAlt),
+		XmpSchema::new("xmp", "http://ns.adobe.com/xap/1.0/")
+			.field("CreateDate",   XmpFieldType::Date)
+			.field("CreatorTool",  XmpFieldType::Text)
+			.field("Label",        XmpFieldType::Text)
+			.field("MetadataDate", XmpFieldType::Date)
+			.field("ModifyDate",   XmpFieldType::Date)
+			.field("Rating",       XmpFieldType::Real),
+		XmpSchema::new("photoshop", "http://ns.adobe.com/photoshop/1.0/")
+			.field("City",        XmpFieldType::Text)
+			.field("Country",     XmpFieldType::Text)
+			.field("Credit",      XmpFieldType::Text)
+			.field("DateCreated", XmpFieldType::Date)
+			.field("Headline",    XmpFieldType::Text)
+			.field("Source",      XmpFieldType::Text)
+			.field("State",       XmpFieldType::Text),
+		XmpSchema::new("tiff", "http://ns.adobe.com/tiff/1.0/")
+			.field("Artist",           XmpFieldType::Seq)
+			.field("Copyright",        XmpFieldType::LangAlt)
+			.field("DateTime",         XmpFieldType::Date)
+			.field("ImageDescription", XmpFieldType::LangAlt)
+			.field("ImageLength",      XmpFieldType::Integer)
+			.field("ImageWidth",       XmpFieldType::Integer)
+			.field("Make",             XmpFieldType::Text)
+			.field("Model",            XmpFieldType::Text)
+			.field("Orientation",      XmpFieldType::Integer)
+			.field("Software",         XmpFieldType::Text),
+		XmpSchema::new("exif", "http://ns.adobe.com/exif/1.0/")
+			.field("DateTimeDigitized", XmpFieldType::Date)
+			.field("DateTimeOriginal",  XmpFieldType::Date)
+			.field("PixelXDimension",   XmpFieldType::Integer)
+			.field("PixelYDimension",   XmpFieldType::Integer)
+			.field("UserComment",       XmpFieldType::LangAlt),
+	]
+}
+
+impl
+XmpSchema
+{
+	/// Constructs a schema for the namespace with the given prefix and URI,
+	/// without any fields
+	pub fn
+	new
+	(
+		prefix: &'static str,
+		uri:    &'static str
+	)
+	-> XmpSchema
+	{
+		XmpSchema
+		{
+			namespace: (prefix, uri),
+			fields:    Vec::new(),
+		}
+	}
+
+	/// Adds the field with the given name and type, replacing a previous
+	/// field with the same name
+	pub fn
+	field
+	(
+		mut self,
+		name:       &'static str,
+		field_type: XmpFieldType
+	)
+	-> XmpSchema
+	{
+		self.fields.retain(|(known, _)| *known != name);
+		self.fields.push((name, field_type));
+		self
+	}
+
+	/// Gets the prefix and URI of the namespace
+	pub fn
+	namespace
+	(
+		&self
+	)
+	-> Namespace
+	{
+		self.namespace
+	}
+
+	/// Gets the type of the field, or `None` if the schema doesn't declare
+	/// it
+	pub fn
+	field_type
+	(
+		&self,
+		name: &str
+	)
+	-> Option<XmpFieldType>
+	{
+		self.fields.iter().find(|(known, _)| *known == name).map(|(_, field_type)| *field_type)
+	}
+
+	/// Registers the schema for the whole process, replacing a schema
+	/// registered before for the same namespace. Registered schemas take
+	/// precedence over built-in ones with the same prefix and URI.
+	/// - If the prefix or a field name is not a valid XML name or the prefix
+	///   or URI is already used for another namespace, an error of kind
+	///   `InvalidInput` gets returned.
+	pub fn
+	register
+	(
+		schema: XmpSchema
+	)
+	-> Result<(), std::io::Error>
+	{
+		let (prefix, uri) = schema.namespace;
+		if !is_valid_name(prefix) || uri.is_empty() || !schema.fields.iter().all(|(name, _)| is_valid_name(name))
+		{
+			return io_error!(InvalidInput, "Invalid prefix, URI or field name of XMP schema!");
+		}
+
+		let mut registered = REGISTERED_SCHEMAS.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+		let conflicts = builtin_schemas().iter().chain(registered.iter())
+			.any(|known| (known.namespace.0 == prefix) != (known.namespace.1 == uri));
+		if conflicts
+		{
+			return io_error!(InvalidInput, format!("Prefix '{}' or URI '{}' is already used for another XMP namespace!", prefix, uri));
+		}
+
+		registered.retain(|known| known.namespace != schema.namespace);
+		registered.push(schema);
+		return Ok(());
+	}
+
+	/// Gets the schemas registered using `register`
+	pub fn
+	registered
+	()
+	-> Vec<XmpSchema>
+	{
+		REGISTERED_SCHEMAS.read()
+			.unwrap_or_else(|poisoned| poisoned.into_inner())
+			.clone()
+	}
+
+	/// Gets the schema for the given prefix, either a registered or a
+	/// built-in one. Returns `None` if there is none.
+	pub fn
+	lookup
+	(
+		prefix: &str
+	)
+	-> Option<XmpSchema>
+	{
+		XmpSchema::registered().into_iter()
+			.chain(builtin_schemas())
+			.find(|schema| schema.namespace.0 == prefix)
+	}
+}
+
+/// Splits the qualified name of a property into its schema and field name
+fn
+resolve
+(
+	property: &str
+)
+-> Result<(XmpSchema, &str), std::io::Error>
+{
+	let (prefix, name) = match property.split_once(':')
+	{
+		Some((prefix, name)) if is_valid_name(name) => (prefix, name),
+		_                                           => return io_error!(InvalidInput, format!("Invalid XMP property name '{}'!", property)),
+	};
+	match XmpSchema::lookup(prefix)
+	{
+		Some(schema) => Ok((schema, name)),
+		None         => io_error!(InvalidInput, format!("Unknown XMP namespace prefix '{}'!", prefix)),
+	}
+}
+
+/// Checks whether the value fits the type of the field
+fn
+fits
+(
+	value:      &XmpValue,
+	field_type: XmpFieldType
+)
+-> bool
+{
+	match (field_type, value)
+	{
+		(XmpFieldType::Text,    XmpValue::Simple(_))     => true,
+		(XmpFieldType::Integer, XmpValue::Simple(value)) => value.trim().parse::<i64>().is_ok(),
+		(XmpFieldType::Real,    XmpValue::Simple(value)) => value.trim().parse::<f64>().is_ok_and(f64::is_finite),
+		(XmpFieldType::Boolean, XmpValue::Simple(value)) => value == "True" || value == "False",
+		(XmpFieldType::Date,    XmpValue::Simple(value)) => {
+			// Dates may be reduced to the year or the year and month
+			let digits = |part: &str, length: usize| part.len() == length && part.bytes().all(|byte| byte.is_ascii_digit());
+			match value.split('-').collect::<Vec<&str>>()[..]
+			{
+				[year]        => digits(year, 4),
+				[year, month] => digits(year, 4) && digits(month, 2) && (1..=12).contains(&month.parse::<u8>().unwrap_or(0)),
+				_             => ExifDate::parse(value, DateCoercion::Lenient).is_some(),
+			}
+		},
+		(XmpFieldType::Bag,     XmpValue::Bag(_))        => true,
+		(XmpFieldType::Seq,     XmpValue::Seq(_))        => true,
+		(XmpFieldType::Alt,     XmpValue::Alt(_))        => true,
+		(XmpFieldType::LangAlt, XmpValue::LangAlt(_))    => true,
+		_                                                => false,
+	}
+}
+
+/// Gets the value of the property with the given qualified name (e.g.
+/// `dc:title`), see the module documentation. Returns `None` if the packet
+/// doesn't contain it.
+/// - If the namespace of the property is unknown, an error of kind
+///   `InvalidInput` gets returned
+pub fn
+get_field
+(
+	packet:   &str,
+	property: &str
+)
+-> Result<Option<XmpValue>, std::io::Error>
+{
+	let (schema, name) = resolve(property)?;
+	let value = match xmp::get_value(packet, schema.namespace, name)
+	{
+		Some(value) => value,
+		None        => return Ok(None),
+	};
+
+	// Simple values written by tools that don't know the type of the field
+	return Ok(Some(match (schema.field_type(name), value)
+	{
+		(Some(XmpFieldType::Bag),     XmpValue::Simple(value)) => XmpValue::Bag(vec![value]),
+		(Some(XmpFieldType::Seq),     XmpValue::Simple(value)) => XmpValue::Seq(vec![value]),
+		(Some(XmpFieldType::Alt),     XmpValue::Simple(value)) => XmpValue::Alt(vec![value]),
+		(Some(XmpFieldType::LangAlt), XmpValue::Simple(value)) => XmpValue::LangAlt(LangAlt::new().with(DEFAULT_LANGUAGE, &value)),
+		(_,                           value)                   => value,
+	}));
+}
+
+/// Sets the values of the properties with the given qualified names (e.g.
+/// `dc:title`) in the packet, or in a new one if there is none, and returns
+/// the resulting packet. Properties with a value of `None` are removed. See
+/// `xmp::set_values` for how the values are written.
+/// - If the namespace of a property is unknown or a value doesn't fit the
+///   type of its field, an error of kind `InvalidInput` gets returned
+pub fn
+set_fields
+(
+	packet: Option<&str>,
+	fields: &[(&str, Option<XmpValue>)]
+)
+-> Result<String, std::io::Error>
+{
+	// Check all fields before changing anything
+	let mut namespaces: Vec<NamespaceValues> = Vec::new();
+	for (property, value) in fields
+	{
+		let (schema, name) = resolve(property)?;
+		if let (Some(field_type), Some(value)) = (schema.field_type(name), value)
+		{
+			if !fits(value, field_type)
+			{
+				return io_error!(InvalidInput, format!("Value of XMP property '{}' doesn't fit its type {:?}!", property, field_type));
+			}
+		}
+
+		match namespaces.iter_mut().find(|(namespace, _)| *namespace == schema.namespace)
+		{
+			Some((_, values)) => values.push((name, value.clone())),
+			None              => namespaces.push((schema.namespace, vec![(name, value.clone())])),
+		}
+	}
+
+	let mut packet = packet.unwrap_or(xmp::EMPTY_PACKET).to_string();
+	for (namespace, values) in namespaces
+	{
+		packet = xmp::set_values(Some(&packet), namespace, &values)?;
+	}
+	return Ok(packet);
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::xmp::LangAlt;
+	use crate::xmp::XmpValue;
+	use crate::xmp_schema::*;
+
+	#[test]
+	fn
+	builtin_and_custom_fields()
+	-> Result<(), std::io::Error>
+	{
+		let title = LangAlt::new().with(DEFAULT_LANGUAGE, "Lighthouse").with("de", "Leuchtturm");
+		let packet = set_fields(None, &[
+			("dc:title",    Some(XmpValue::LangAlt(title.clone()))),
+			("xmp:Rating",  Some(XmpValue::Simple("4".to_string()))),
+			("dc:coverage", Some(XmpValue::Simple("Graz".to_string()))),
+		])?;
+		assert_eq!(get_field(&packet, "dc:title")?, Some(XmpValue::LangAlt(title)));
+		assert_eq!(get_field(&packet, "dc:coverage")?, Some(XmpValue::Simple("Graz".to_string())));
+		assert_eq!(get_field(&packet, "dc:subject")?, None);
+
+		// Types are checked, namespaces have to be known
+		assert!(set_fields(None, &[("dc:title",      Some(XmpValue::Simple("Lighthouse".to_string())))]).is_err());
+		assert!(set_fields(None, &[("xmp:Rating",    Some(XmpValue::Simple("many".to_string())))]).is_err());
+		assert!(set_fields(None, &[("xmp:CreateDate", Some(XmpValue::Simple("2024-13".to_string())))]).is_err());
+		assert!(set_fields(None, &[("xmp:CreateDate", Some(XmpValue::Simple("2024-05".to_string())))]).is_ok());
+		assert!(set_fields(None, &[("unknown:Field", Some(XmpValue::Simple("1".to_string())))]).is_err());
+		assert!(get_field(&packet, "unknown:Field").is_err());
+
+		// Custom schemas work the same way
+		XmpSchema::register(XmpSchema::new("testacme", "https://acme.example/ns/test/1.0/")
+			.field("AssetId",  XmpFieldType::Integer)
+			.field("Projects", XmpFieldType::Bag)
+			.field("Approved", XmpFieldType::Boolean)
+		)?;
+		let packet = set_fields(Some(&packet), &[
+			("testacme:AssetId",  Some(XmpValue::Simple("4711".to_string()))),
+			("testacme:Projects", Some(XmpValue::Bag(vec!["Spring".to_string(), "Summer".to_string()]))),
+		])?;
+		assert!(packet.contains("xmlns:testacme=\"https://acme.example/ns/test/1.0/\""));
+		assert_eq!(get_field(&packet, "testacme:AssetId")?, Some(XmpValue::Simple("4711".to_string())));
+		assert_eq!(get_field(&packet, "testacme:Projects")?.unwrap().items(), vec!["Spring".to_string(), "Summer".to_string()]);
+		assert_eq!(get_field(&packet, "xmp:Rating")?, Some(XmpValue::Simple("4".to_string())));
+		assert!(set_fields(None, &[("testacme:Approved", Some(XmpValue::Simple("yes".to_string())))]).is_err());
+
+		// Simple values of array fields are read as arrays
+		let packet = set_fields(Some(&packet), &[("testacme:Projects", None)])?;
+		let packet = xmp::set_properties(Some(&packet), ("testacme", "https://acme.example/ns/test/1.0/"), &[("Projects", Some("Winter".to_string()))])?;
+		assert_eq!(get_field(&packet, "testacme:Projects")?, Some(XmpValue::Bag(vec!["Winter".to_string()])));
+		Ok(())
+	}
+
+	#[test]
+	fn
+	conflicting_registrations()
+	{
+		assert!(XmpSchema::register(XmpSchema::new("dc", "https://example.com/not-dublin-core/")).is_err());
+		assert!(XmpSchema::register(XmpSchema::new("dublin", "http://purl.org/dc/elements/1.1/")).is_err());
+		assert!(XmpSchema::register(XmpSchema::new("1st", "https://example.com/first/")).is_err());
+		assert!(XmpSchema::register(XmpSchema::new("testfirst", "https://example.com/first/").field("a:b", XmpFieldType::Text)).is_err());
+
+		// Extending a built-in schema
+		XmpSchema::register(XmpSchema::new("photoshop", "http://ns.adobe.com/photoshop/1.0/").field("Urgency", XmpFieldType::Integer)).unwrap();
+		assert_eq!(XmpSchema::lookup("photoshop").unwrap().field_type("Urgency"), Some(XmpFieldType::Integer));
+		assert!(XmpSchema::registered().iter().any(|schema| schema.namespace().0 == "photoshop"));
+	}
+}
@@ -0,0 +1,422 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Deterministic fuzzing of the parsers: Inputs are derived from seed images
+//! by mutations chosen by a seeded random number generator, so that every
+//! run with the same seed visits the same inputs. Inputs that make a parser
+//! panic get minimized and are kept as regression fixtures in
+//! `tests/regressions`, which are checked by the test suite from then on.
+//!
+//! Shared by the tests and the `fuzz` example, which finds, minimizes and
+//! stores new fixtures, see `examples/fuzz.rs`.
+
+#![allow(dead_code)]
+
+use std::panic::AssertUnwindSafe;
+use std::path::Path;
+use std::path::PathBuf;
+
+use little_exif::bplist;
+use little_exif::exif_tag::ExifTag;
+use little_exif::filetype::FileExtension;
+use little_exif::generated_image;
+use little_exif::metadata::Metadata;
+use little_exif::xmp;
+
+pub const REGRESSIONS_DIRECTORY: &str = "tests/regressions";
+pub const FIXTURE_EXTENSION:     &str = "bin";
+
+/// Values of `Make` selecting the decoder of every maker note vendor
+const MAKER_NOTE_MAKES: [&str; 6] = ["NIKON", "PENTAX", "SONY", "Apple", "OLYMPUS", "Canon"];
+
+/// Runs the input through every parser, all of which must handle it without
+/// panicking - returning an error is fine
+pub fn
+exercise
+(
+	data: &[u8]
+)
+{
+	if let Ok(metadata) = Metadata::decode(data)
+	{
+		exercise_maker_note(&metadata);
+	}
+
+	let file_buffer = data.to_vec();
+	let _ = xmp::read_xmp_from_vec(&file_buffer);
+	let _ = generated_image::read_generation_payloads(&file_buffer);
+	let _ = bplist::decode(&file_buffer);
+
+	// The input as the maker notes of every vendor
+	for make in MAKER_NOTE_MAKES
+	{
+		let mut metadata = Metadata::new();
+		metadata.set_tag(ExifTag::Make(make.to_string()));
+		metadata.set_tag(ExifTag::MakerNote(file_buffer.clone()));
+		exercise_maker_note(&metadata);
+	}
+
+	for file_type in [FileExtension::JPEG, FileExtension::PNG { as_zTXt_chunk: true }, FileExtension::WEBP, FileExtension::TIFF, FileExtension::HEIF, FileExtension::AVIF, FileExtension::GIF, FileExtension::CR2, FileExtension::CR3, FileExtension::ORF, FileExtension::RW2, FileExtension::RAF, FileExtension::AVI]
+	{
+		if let Ok(metadata) = Metadata::new_from_vec(&file_buffer, file_type)
+		{
+			exercise_maker_note(&metadata);
+			let _ = metadata.encode();
+			let _ = metadata.write_to_vec(&mut file_buffer.clone(), file_type);
+		}
+	}
+}
+
+/// Decodes the maker notes of the metadata, if any, and calls every accessor
+fn
+exercise_maker_note
+(
+	metadata: &Metadata
+)
+{
+	if let Some(maker_note) = metadata.maker_note()
+	{
+		let _ = maker_note.shutter_count();
+		let _ = maker_note.sensor_temperature();
+		let _ = maker_note.sequence_number();
+		let _ = maker_note.bracket();
+		let _ = maker_note.apple_content_identifier();
+		let _ = maker_note.apple_hdr_gain();
+		let _ = maker_note.apple_hdr_headroom();
+		let _ = maker_note.apple_acceleration_vector();
+		let _ = maker_note.apple_run_time();
+	}
+}
+
+thread_local!
+{
+	static RECORDING:  std::cell::Cell<bool>            = const { std::cell::Cell::new(false) };
+	static LAST_PANIC: std::cell::RefCell<Option<String>> = const { std::cell::RefCell::new(None) };
+}
+
+/// Installs a panic hook that records the panics caught by `find_panic`
+/// instead of printing them. Other panics (e.g. failing tests running at the
+/// same time) are passed on to the previous hook.
+fn
+install_panic_hook()
+{
+	static INSTALL: std::sync::Once = std::sync::Once::new();
+	INSTALL.call_once(|| {
+		let previous_hook = std::panic::take_hook();
+		std::panic::set_hook(Box::new(move |info| {
+			if !RECORDING.with(|recording| recording.get())
+			{
+				return previous_hook(info);
+			}
+			let location = info.location().map(|location| location.to_string()).unwrap_or_default();
+			let message = info.payload().downcast_ref::<String>().cloned()
+				.or_else(|| info.payload().downcast_ref::<&str>().map(|message| message.to_string()))
+				.unwrap_or_default();
+			LAST_PANIC.with(|last_panic| *last_panic.borrow_mut() = Some(format!("{} {}", location, message)));
+		}));
+	});
+}
+
+/// Gets the location (e.g. `src/png.rs:123:45`) and message of the panic
+/// caused by `exercise`, if any
+pub fn
+find_panic
+(
+	data: &[u8]
+)
+-> Option<String>
+{
+	install_panic_hook();
+	RECORDING.with(|recording| recording.set(true));
+	let result = std::panic::catch_unwind(AssertUnwindSafe(|| exercise(data)));
+	RECORDING.with(|recording| recording.set(false));
+
+	result.err()?;
+	return Some(LAST_PANIC.with(|last_panic| last_panic.borrow_mut().take()).unwrap_or_default());
+}
+
+/// Gets the location part of a panic found by `find_panic`, which - unlike
+/// the message - doesn't change while minimizing the input
+pub fn
+panic_location
+(
+	panic: &str
+)
+-> &str
+{
+	panic.split(' ').next().unwrap_or_default()
+}
+
+/// xorshift64* - small, fast and the same on every platform
+pub struct
+Rng(u64);
+
+impl
+Rng
+{
+	pub fn
+	new
+	(
+		seed: u64
+	)
+	-> Rng
+	{
+		Rng(seed.max(1))
+	}
+
+	pub fn
+	next
+	(
+		&mut self
+	)
+	-> u64
+	{
+		self.0 ^= self.0 >> 12;
+		self.0 ^= self.0 << 25;
+		self.0 ^= self.0 >> 27;
+		return self.0.wrapping_mul(0x2545_f491_4f6c_dd1d);
+	}
+
+	/// A number in the range `0..bound`, which must not be 0
+	pub fn
+	below
+	(
+		&mut self,
+		bound: usize
+	)
+	-> usize
+	{
+		(self.next() % bound as u64) as usize
+	}
+}
+
+/// Values likely to hit edge cases when written into length and offset fields
+const INTERESTING_VALUES: [&[u8]; 8] = [
+	&[0x00], &[0xff], &[0x7f], &[0x80],
+	&[0x00, 0x00, 0x00, 0x00], &[0xff, 0xff, 0xff, 0xff], &[0x7f, 0xff, 0xff, 0xff], &[0x00, 0x00, 0x00, 0x01],
+];
+
+/// Derives a new input from the given one by one to four random mutations
+pub fn
+mutate
+(
+	data: &[u8],
+	rng:  &mut Rng
+)
+-> Vec<u8>
+{
+	let mut mutated = data.to_vec();
+	for _ in 0..=rng.below(4)
+	{
+		if mutated.is_empty()
+		{
+			mutated.push(rng.next() as u8);
+			continue;
+		}
+
+		let position = rng.below(mutated.len());
+		match rng.below(6)
+		{
+			0 => mutated[position] ^= 1 << rng.below(8),
+			1 => mutated[position] = rng.next() as u8,
+			2 => {
+				let value = INTERESTING_VALUES[rng.below(INTERESTING_VALUES.len())];
+				let end = (position + value.len()).min(mutated.len());
+				mutated[position..end].copy_from_slice(&value[..end - position]);
+			},
+			3 => mutated.truncate(position),
+			4 => {
+				let end = (position + 1 + rng.below(16)).min(mutated.len());
+				mutated.drain(position..end);
+			},
+			_ => {
+				let length = 1 + rng.below(16).min(mutated.len() - position - 1);
+				let copy = mutated[position..position + length].to_vec();
+				let target = rng.below(mutated.len());
+				mutated.splice(target..target, copy);
+			},
+		}
+	}
+	return mutated;
+}
+
+/// Shrinks the input as long as the given predicate (e.g. "panics at the
+/// same location") still holds, by removing ever smaller parts of it and
+/// finally zeroing single bytes. The result is deterministic.
+pub fn
+minimize
+(
+	data:      &[u8],
+	predicate: impl Fn(&[u8]) -> bool
+)
+-> Vec<u8>
+{
+	let mut minimized = data.to_vec();
+
+	let mut chunk_size = minimized.len().div_ceil(2).max(1);
+	loop
+	{
+		let mut start = 0;
+		while start < minimized.len()
+		{
+			let end = (start + chunk_size).min(minimized.len());
+			let candidate = [&minimized[..start], &minimized[end..]].concat();
+			if predicate(&candidate)
+			{
+				minimized = candidate;
+			}
+			else
+			{
+				start += chunk_size;
+			}
+		}
+
+		if chunk_size == 1
+		{
+			break;
+		}
+		chunk_size = chunk_size.div_ceil(2);
+	}
+
+	for position in 0..minimized.len()
+	{
+		if minimized[position] != 0
+		{
+			let mut candidate = minimized.clone();
+			candidate[position] = 0;
+			if predicate(&candidate)
+			{
+				minimized = candidate;
+			}
+		}
+	}
+
+	return minimized;
+}
+
+/// Names a fixture after the FNV-1a hash of its content, so that the same
+/// input is never stored twice
+pub fn
+fixture_path
+(
+	data: &[u8]
+)
+-> PathBuf
+{
+	let hash = data.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3));
+	return Path::new(REGRESSIONS_DIRECTORY).join(format!("{:016x}.{}", hash, FIXTURE_EXTENSION));
+}
+
+/// Gets the stored regression fixtures, sorted by their path
+pub fn
+fixtures()
+-> Vec<(PathBuf, Vec<u8>)>
+{
+	let mut paths = std::fs::read_dir(REGRESSIONS_DIRECTORY)
+		.map(|entries| entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect::<Vec<PathBuf>>())
+		.unwrap_or_default();
+	paths.retain(|path| path.extension().is_some_and(|extension| extension == FIXTURE_EXTENSION));
+	paths.sort();
+
+	return paths.into_iter()
+		.map(|path| { let data = std::fs::read(&path).unwrap(); (path, data) })
+		.collect();
+}
+
+/// Small images of every supported type with EXIF data as well as a binary
+/// property list and maker notes, from which the inputs are derived
+pub fn
+seeds()
+-> Vec<Vec<u8>>
+{
+	let mut metadata = Metadata::new();
+	metadata.set_tag(ExifTag::ImageDescription("Seed".to_string()));
+	metadata.set_tag(ExifTag::ISO(vec![100]));
+	metadata.set_tag(ExifTag::ExposureTime(vec![1, 250]));
+	metadata.set_tag(ExifTag::GPSLatitude(vec![48, 1, 12, 1, 3456, 100]));
+
+	let mut jpg = vec![0xff, 0xd8, 0xff, 0xda, 0x00, 0x02, 0x00, 0xff, 0xd9];
+	let mut png = std::fs::read("tests/png_parse_test_image.png").unwrap();
+	let mut webp = std::fs::read("tests/read_sample.webp").unwrap();
+	let mut tiff = std::fs::read("tests/sample.tif").unwrap();
+	let mut heif = std::fs::read("tests/sample.heic").unwrap();
+	let mut avif = std::fs::read("tests/sample.avif").unwrap();
+	let mut gif = std::fs::read("tests/sample.gif").unwrap();
+	let cr2 = std::fs::read("tests/sample.cr2").unwrap();
+	let cr3 = std::fs::read("tests/sample.cr3").unwrap();
+	let orf = std::fs::read("tests/sample.orf").unwrap();
+	let rw2 = std::fs::read("tests/sample.rw2").unwrap();
+	let raf = std::fs::read("tests/sample.raf").unwrap();
+	let mut avi = std::fs::read("tests/sample.avi").unwrap();
+	metadata.write_to_vec(&mut jpg, FileExtension::JPEG).unwrap();
+	metadata.write_to_vec(&mut png, FileExtension::PNG { as_zTXt_chunk: false }).unwrap();
+	metadata.write_to_vec(&mut webp, FileExtension::WEBP).unwrap();
+	metadata.write_to_vec(&mut tiff, FileExtension::TIFF).unwrap();
+	metadata.write_to_vec(&mut heif, FileExtension::HEIF).unwrap();
+	metadata.write_to_vec(&mut avif, FileExtension::AVIF).unwrap();
+	metadata.write_to_vec(&mut gif, FileExtension::GIF).unwrap();
+	metadata.write_to_vec(&mut avi, FileExtension::AVI).unwrap();
+
+	// {"a": 42}, also stored as Apple maker notes, and Nikon maker notes
+	// with a shutter count
+	let mut plist = b"bplist00".to_vec();
+	plist.extend([0xd1, 0x01, 0x02, 0x51, b'a', 0x10, 0x2a]);
+	plist.extend([0x08, 0x0b, 0x0d]);
+	plist.extend([0u8; 6]);
+	plist.extend([1, 1]);
+	plist.extend(3u64.to_be_bytes());
+	plist.extend(0u64.to_be_bytes());
+	plist.extend(15u64.to_be_bytes());
+
+	let mut apple_maker_note = b"Apple iOS\0\0\x01MM\0\x01\0\x03\0\x07".to_vec();
+	apple_maker_note.extend((plist.len() as u32).to_be_bytes());
+	apple_maker_note.extend(32u32.to_be_bytes());
+	apple_maker_note.extend([0u8; 4]);
+	apple_maker_note.extend(&plist);
+	let mut apple_metadata = Metadata::new();
+	apple_metadata.set_tag(ExifTag::Make("Apple".to_string()));
+	apple_metadata.set_tag(ExifTag::MakerNote(apple_maker_note));
+
+	let nikon_maker_note = b"Nikon\0\x02\x10\0\0MM\0\x2a\0\0\0\x08\0\x01\0\xa7\0\x04\0\0\0\x01\0\0\x30\x39\0\0\0\0".to_vec();
+	let mut nikon_metadata = Metadata::new();
+	nikon_metadata.set_tag(ExifTag::Make("NIKON CORPORATION".to_string()));
+	nikon_metadata.set_tag(ExifTag::MakerNote(nikon_maker_note));
+
+	return vec![metadata.encode(), jpg, png, webp, tiff, heif, avif, gif, cr2, cr3, orf, rw2, raf, avi, plist, apple_metadata.encode(), nikon_metadata.encode()];
+}
+
+/// Exercises the given number of inputs derived from the seeds, returning
+/// the minimized inputs that caused panics, one for every panic location
+/// together with the panic. Every input is passed to the given function
+/// before being exercised, e.g. for keeping it in case the process aborts
+/// (like on a stack overflow, which can't be caught).
+pub fn
+fuzz
+(
+	iterations: usize,
+	seed:       u64,
+	on_input:   &mut dyn FnMut(&[u8])
+)
+-> Vec<(String, Vec<u8>)>
+{
+	let seeds = seeds();
+	let mut rng = Rng::new(seed);
+	let mut crashes: Vec<(String, Vec<u8>)> = Vec::new();
+
+	for _ in 0..iterations
+	{
+		let input = mutate(&seeds[rng.below(seeds.len())], &mut rng);
+		on_input(&input);
+		if let Some(panic) = find_panic(&input)
+		{
+			let location = panic_location(&panic).to_string();
+			if crashes.iter().all(|(known, _)| panic_location(known) != location)
+			{
+				let minimized = minimize(&input, |candidate| find_panic(candidate).is_some_and(|panic| panic_location(&panic) == location));
+				crashes.push((find_panic(&minimized).unwrap_or(panic), minimized));
+			}
+		}
+	}
+
+	return crashes;
+}
@@ -0,0 +1,136 @@
+// Copyright © 2024 Tobias J. Prisching <tobias.prisching@icloud.com> and CONTRIBUTORS
+// See https://github.com/TechnikTobi/little_exif#license for licensing details
+
+//! Round trips checked by reference decoders: After writing, replacing and
+//! clearing metadata (and XMP packets, where supported), the images have to
+//! decode to the same pixels as before using the decoders of the image crate
+//! (png, jpeg-decoder/zune-jpeg, image-webp, tiff and gif). This catches
+//! container corruption that comparing bytes misses, e.g. a wrong RIFF size
+//! or a chunk CRC that no byte level assertion looks at.
+//!
+//! Requires the `interop` feature: `cargo test --features interop`. HEIF and
+//! AVIF are not covered, as the image crate has no pure Rust decoders for
+//! them.
+
+use image::ImageFormat;
+
+use little_exif::exif_tag::ExifTag;
+use little_exif::filetype::FileExtension;
+use little_exif::metadata::Metadata;
+use little_exif::write_options::PngExifChunk;
+use little_exif::write_options::WriteOptions;
+use little_exif::xmp::read_xmp_from_vec;
+use little_exif::xmp::write_xmp_to_vec;
+
+const SAMPLES: [(&str, FileExtension, ImageFormat); 11] = [
+	("tests/sample2.jpg",                  FileExtension::JPEG,                         ImageFormat::Jpeg),
+	("tests/sample2.png",                  FileExtension::PNG { as_zTXt_chunk: true },  ImageFormat::Png),
+	("tests/png_parse_test_image.png",     FileExtension::PNG { as_zTXt_chunk: false }, ImageFormat::Png),
+	("tests/sample2_simple_lossy.webp",    FileExtension::WEBP,                         ImageFormat::WebP),
+	("tests/sample2_simple_loseless.webp", FileExtension::WEBP,                         ImageFormat::WebP),
+	("tests/sample2_extended.webp",        FileExtension::WEBP,                         ImageFormat::WebP),
+	("tests/read_sample.webp",             FileExtension::WEBP,                         ImageFormat::WebP),
+	("tests/sample.tif",                   FileExtension::TIFF,                         ImageFormat::Tiff),
+	("tests/sample_big_endian.tiff",       FileExtension::TIFF,                         ImageFormat::Tiff),
+	("tests/sample_pyramid.tif",           FileExtension::TIFF,                         ImageFormat::Tiff),
+	("tests/sample.gif",                   FileExtension::GIF,                          ImageFormat::Gif),
+];
+
+/// Decodes the image, returning its dimensions and pixels
+fn
+decode
+(
+	file_buffer: &[u8],
+	format:      ImageFormat,
+	context:     &str
+)
+-> (u32, u32, Vec<u8>)
+{
+	match image::load_from_memory_with_format(file_buffer, format)
+	{
+		Ok(image)  => (image.width(), image.height(), image.into_bytes()),
+		Err(error) => panic!("{}: {}", context, error),
+	}
+}
+
+/// Metadata large enough to require several PNG zTXt blocks, WebP padding
+/// and a value outside of the IFD entries
+fn
+interop_metadata()
+-> Metadata
+{
+	let mut metadata = Metadata::new();
+	metadata.set_tag(ExifTag::ImageDescription("Interop round trip".to_string()));
+	metadata.set_tag(ExifTag::Artist("Jane Doe".to_string()));
+	metadata.set_tag(ExifTag::ISO(vec![400]));
+	metadata.set_tag(ExifTag::ExposureTime(vec![1, 125]));
+	metadata.set_tag(ExifTag::GPSLatitude(vec![48, 1, 12, 1, 3456, 100]));
+	metadata.set_tag(ExifTag::UserComment([b"ASCII\0\0\0".as_slice(), &[b'x'; 301]].concat()));
+	return metadata;
+}
+
+#[test]
+fn
+decodable_after_writing()
+-> Result<(), std::io::Error>
+{
+	for (path, file_type, format) in SAMPLES
+	{
+		let original = std::fs::read(path)?;
+		let original_image = decode(&original, format, path);
+
+		let mut file_buffer = original.clone();
+		interop_metadata().write_to_vec(&mut file_buffer, file_type)?;
+		assert_eq!(decode(&file_buffer, format, &format!("{} after writing", path)), original_image);
+
+		// Replacing the metadata by a smaller version of itself
+		let mut metadata = Metadata::new_from_vec(&file_buffer, file_type)?;
+		metadata.set_tag(ExifTag::UserComment(b"ASCII\0\0\0Short".to_vec()));
+		metadata.write_to_vec(&mut file_buffer, file_type)?;
+		assert_eq!(decode(&file_buffer, format, &format!("{} after replacing", path)), original_image);
+
+		Metadata::clear_metadata(&mut file_buffer, file_type)?;
+		assert_eq!(decode(&file_buffer, format, &format!("{} after clearing", path)), original_image);
+	}
+	Ok(())
+}
+
+#[test]
+fn
+decodable_after_writing_png_exif_chunk()
+-> Result<(), std::io::Error>
+{
+	let original = std::fs::read("tests/sample2.png")?;
+	let original_image = decode(&original, ImageFormat::Png, "tests/sample2.png");
+
+	let mut file_buffer = original.clone();
+	let options = WriteOptions::new().png_exif_chunk(PngExifChunk::eXIf);
+	interop_metadata().write_to_vec_with_options(&mut file_buffer, FileExtension::PNG { as_zTXt_chunk: false }, &options)?;
+	assert_eq!(decode(&file_buffer, ImageFormat::Png, "eXIf chunk"), original_image);
+	Ok(())
+}
+
+#[test]
+fn
+decodable_after_writing_xmp()
+-> Result<(), std::io::Error>
+{
+	for (path, file_type, format) in SAMPLES.into_iter().filter(|(_, file_type, _)| file_type.capabilities().xmp)
+	{
+		let original = std::fs::read(path)?;
+		let original_image = decode(&original, format, path);
+
+		let mut file_buffer = original.clone();
+		interop_metadata().write_to_vec(&mut file_buffer, file_type)?;
+		let packet = read_xmp_from_vec(&file_buffer)?.unwrap_or_default();
+		let packet = match packet.is_empty()
+		{
+			true  => "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"/>".to_string(),
+			false => packet.replace("</rdf:RDF>", "</rdf:RDF>\n"),
+		};
+		write_xmp_to_vec(&mut file_buffer, &packet)?;
+		assert_eq!(read_xmp_from_vec(&file_buffer)?, Some(packet));
+		assert_eq!(decode(&file_buffer, format, &format!("{} after writing XMP", path)), original_image);
+	}
+	Ok(())
+}
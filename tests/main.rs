@@ -4,10 +4,82 @@
 use std::fs::copy;
 use std::fs::remove_file;
 use std::path::Path;
+use std::str::FromStr;
 
 extern crate little_exif;
+use little_exif::astro;
+use little_exif::astro::AcquisitionKeywords;
+use little_exif::astro::KeywordValue;
+use little_exif::chunk_types::RiffChunkType;
+use little_exif::config::Config;
+use little_exif::date_inference::DEFAULT_FILENAME_DATE_PATTERNS;
+use little_exif::dng_opcode::Opcode;
+use little_exif::dng_opcode::OpcodeList;
+use little_exif::document::DocumentPage;
+use little_exif::endian::Endian;
+use little_exif::environment::AccelerationUnit;
+use little_exif::environment::LengthUnit;
+use little_exif::environment::PressureUnit;
+use little_exif::environment::TemperatureUnit;
+use little_exif::errors::MetadataTooLarge;
+use little_exif::errors::NoEmbeddedMetadata;
+use little_exif::errors::ParseErrorContext;
+use little_exif::errors::ReadOnlyTarget;
 use little_exif::metadata::Metadata;
+use little_exif::object_store;
+use little_exif::object_store::FileSystemStore;
+use little_exif::object_store::ObjectStore;
+use little_exif::panorama::PanoInfo;
+use little_exif::panorama::write_pano_to_vec;
+#[cfg(feature = "policy")]
+use little_exif::policy::Policy;
+use little_exif::range_reader::RangeReader;
+use little_exif::range_reader::RangeSource;
+use little_exif::read_options::EmptyValues;
+use little_exif::read_options::ReadOptions;
+use little_exif::scan::Film;
+use little_exif::scan::FilmType;
+use little_exif::scan::Oecf;
+use little_exif::scan::ScanProfile;
+use little_exif::scan::read_film_from_vec;
+use little_exif::shell_properties::encode_xp_string;
+use little_exif::shell_properties::shows_metadata;
+use little_exif::shell_properties::FileManager;
+use little_exif::shell_properties::ShellProperties;
+use little_exif::timelapse::stamp_timelapse;
 use little_exif::exif_tag::ExifTag;
+use little_exif::exif_tag::ExifTagGroup;
+use little_exif::exif_version::ExifVersion;
+use little_exif::filetype::capabilities;
+use little_exif::filetype::FileExtension;
+use little_exif::focus_stack::read_stack_from_vec;
+use little_exif::focus_stack::stamp_focus_stacks;
+use little_exif::geocoding::geocode_files;
+use little_exif::geocoding::read_location_from_vec;
+use little_exif::geocoding::Location;
+use little_exif::gps::GpsAltitudeRef;
+use little_exif::hdr::detect_exposure_brackets;
+use little_exif::gps::GpsDirectionRef;
+use little_exif::vfs::MemoryFileSystem;
+use little_exif::write_options::LITTLE_EXIF_SOFTWARE;
+use little_exif::write_options::PngExifChunk;
+use little_exif::write_options::SidecarFormat;
+use little_exif::write_options::SoftwareStamp;
+use little_exif::write_options::TagHookAction;
+use little_exif::write_options::TempFileLocation;
+use little_exif::write_options::Truncation;
+use little_exif::write_options::WriteDestination;
+use little_exif::write_options::WriteOptions;
+use little_exif::xmp::get_value;
+use little_exif::xmp::read_xmp_from_vec;
+use little_exif::xmp::write_xmp_to_vec;
+use little_exif::xmp::LangAlt;
+use little_exif::xmp::XmpValue;
+use little_exif::xmp::DEFAULT_LANGUAGE;
+
+mod fuzz;
+#[cfg(feature = "interop")]
+mod interop;
 
 #[test]
 fn
@@ -57,9 +129,9 @@ from_u8_vec_to_u32_le
 -> u32
 {
 	let mut result = 0;
-	for i in 0..std::cmp::min(4, data.len())
+	for (i, byte) in data.iter().take(4).enumerate()
 	{
-		result = result + (data[i] as u32) * 256u32.pow(i as u32);
+		result += (*byte as u32) * 256u32.pow(i as u32);
 	}
 	return result;
 }
@@ -126,7 +198,6 @@ as_u8_vec_png()
 			.as_u8_vec(little_exif::filetype::FileExtension::PNG { as_zTXt_chunk: false })
 			.iter()
 			.map(|char_value| *char_value as char)
-			.into_iter()
 			.collect::<String>()
 	);
 }
@@ -143,7 +214,6 @@ as_u8_vec_png_zTXt()
 			.as_u8_vec(little_exif::filetype::FileExtension::PNG { as_zTXt_chunk: true })
 			.iter()
 			.map(|char_value| *char_value as char)
-			.into_iter()
 			.collect::<String>()
 	);
 }
@@ -192,14 +262,11 @@ write_to_file_png()
 	Ok(())
 }
 
-#[ignore]
 #[test]
 fn 
 write_to_file_webp_simple_lossy() 
 -> Result<(), std::io::Error>
 {
-	// Currently not active as the "VP8 " converter does not exist yet!
-
 	// Remove file from previous run and replace it with fresh copy
 	if let Err(error) = remove_file("tests/sample2_simple_lossy_copy.webp")
 	{
@@ -257,4 +324,3240 @@ write_to_file_webp_extended()
 	metadata.write_to_file(Path::new("tests/sample2_extended_copy.webp"))?;
 
 	Ok(())
-}
\ No newline at end of file
+}
+
+
+// The following tests make sure that writing metadata never touches the
+// actual image data, i.e. the compressed bitstream has to be bit-identical
+// before and after any metadata operation
+
+fn
+jpg_scan_data
+(
+	data: &Vec<u8>
+)
+-> Vec<u8>
+{
+	// Walk the segments until the first SOS marker, everything from there on
+	// is the entropy coded image data (plus any further scans)
+	let mut index = 2;
+	while data[index+1] != 0xda
+	{
+		index += 2 + (data[index+2] as usize) * 256 + data[index+3] as usize;
+	}
+	return data[index..].to_vec();
+}
+
+fn
+png_image_data
+(
+	data: &Vec<u8>
+)
+-> Vec<u8>
+{
+	let mut image_data = Vec::new();
+	let mut index = 8;
+	while index < data.len()
+	{
+		let length = u32::from_be_bytes(data[index..index+4].try_into().unwrap()) as usize;
+		if &data[index+4..index+8] == b"IDAT"
+		{
+			image_data.extend(&data[index+8..index+8+length]);
+		}
+		index += 12 + length;
+	}
+	return image_data;
+}
+
+fn
+webp_image_data
+(
+	data: &Vec<u8>
+)
+-> Vec<u8>
+{
+	let mut image_data = Vec::new();
+	let mut index = 12;
+	while index + 8 <= data.len()
+	{
+		let length = u32::from_le_bytes(data[index+4..index+8].try_into().unwrap()) as usize;
+		match &data[index..index+4]
+		{
+			b"VP8 " | b"VP8L" | b"ALPH" | b"ANMF"
+				=> image_data.extend(&data[index+8..index+8+length]),
+			_   => (),
+		}
+		index += 8 + length + length % 2;
+	}
+	return image_data;
+}
+
+fn
+tiff_image_data
+(
+	data: &Vec<u8>
+)
+-> Vec<u8>
+{
+	// The strips referenced by IFD0, which has to contain the offsets of the
+	// two strips of the samples
+	let read = |position: usize, length: usize| {
+		let bytes = data[position..position+length].iter();
+		match &data[0..2]
+		{
+			b"II" => bytes.rev().fold(0, |value, byte| value << 8 | *byte as usize),
+			_     => bytes.fold(0, |value, byte| value << 8 | *byte as usize),
+		}
+	};
+	let ifd0_offset = read(4, 4);
+
+	let mut image_data = Vec::new();
+	for entry_start in (0..read(ifd0_offset, 2)).map(|index| ifd0_offset + 2 + index * 12)
+	{
+		if read(entry_start, 2) == 0x0111
+		{
+			for offset_start in [read(entry_start + 8, 4), read(entry_start + 8, 4) + 4]
+			{
+				let strip_offset = read(offset_start, 4);
+				image_data.extend(&data[strip_offset..strip_offset+12]);
+			}
+		}
+	}
+	return image_data;
+}
+
+fn
+tiff_layers
+(
+	data: &Vec<u8>
+)
+-> Vec<Vec<u8>>
+{
+	// The single strip of every IFD in the chain starting at IFD0 and of the
+	// SubIFDs of these, as found in the little endian pyramid sample
+	let read = |position: usize, length: usize| data[position..position+length].iter()
+		.rev()
+		.fold(0, |value, byte| value << 8 | *byte as usize);
+
+	let mut layers = Vec::new();
+	let mut ifd_offsets = vec![read(4, 4)];
+	while let Some(ifd_offset) = ifd_offsets.pop()
+	{
+		let entry_count = read(ifd_offset, 2);
+		let value = |tag: usize| (0..entry_count)
+			.map(|index| ifd_offset + 2 + index * 12)
+			.find(|entry_start| read(*entry_start, 2) == tag)
+			.map(|entry_start| read(entry_start + 8, 4));
+
+		let strip_offset = value(0x0111).unwrap();
+		layers.push(data[strip_offset..strip_offset + value(0x0117).unwrap()].to_vec());
+		ifd_offsets.extend(value(0x014a));
+		ifd_offsets.extend(Some(read(ifd_offset + 2 + entry_count * 12, 4)).filter(|next| *next != 0));
+	}
+	return layers;
+}
+
+fn
+heif_image_data
+(
+	data: &Vec<u8>
+)
+-> Vec<u8>
+{
+	// The data of the first item in the iloc box, which has to be the image
+	// and - as in the sample - be given by a single extent using version 1
+	// and 4 byte offsets and lengths
+	let read = |position: usize| u32::from_be_bytes(data[position..position+4].try_into().unwrap()) as usize;
+	let iloc = data.windows(4).position(|window| window == b"iloc").unwrap() - 4;
+	assert_eq!(&data[iloc+8..iloc+12], &[0x01, 0x00, 0x00, 0x00]);
+	assert_eq!(&data[iloc+12..iloc+14], &[0x44, 0x00]);
+
+	let first_item = iloc + 16;
+	let (offset, length) = (read(first_item + 8), read(first_item + 12));
+	return data[offset..offset+length].to_vec();
+}
+
+fn
+gif_image_data
+(
+	data: &Vec<u8>
+)
+-> Vec<u8>
+{
+	// Everything starting with the graphic control extension of the image,
+	// which is the first one in the sample
+	let graphic_control = data.windows(3).position(|window| window == [0x21, 0xf9, 0x04]).unwrap();
+	return data[graphic_control..].to_vec();
+}
+
+fn
+assert_image_data_unchanged
+(
+	source:       &str,
+	copy_name:    &str,
+	extract_data: fn(&Vec<u8>) -> Vec<u8>
+)
+-> Result<(), std::io::Error>
+{
+	// Remove file from previous run and replace it with fresh copy
+	if let Err(error) = remove_file(copy_name)
+	{
+		println!("{}", error);
+	}
+	copy(source, copy_name)?;
+
+	let path = Path::new(copy_name);
+	let original_image_data = extract_data(&std::fs::read(path)?);
+	assert!(!original_image_data.is_empty());
+
+	// Write to the file without any previous metadata...
+	get_test_metadata()?.write_to_file(path)?;
+	assert_eq!(extract_data(&std::fs::read(path)?), original_image_data);
+
+	// ...replace the metadata with an extended version of itself...
+	let mut metadata = Metadata::new_from_path(path)?;
+	metadata.set_tag(ExifTag::Artist("Someone".to_string()));
+	metadata.write_to_file(path)?;
+	assert_eq!(extract_data(&std::fs::read(path)?), original_image_data);
+
+	// ...and with nothing at all...
+	Metadata::new().write_to_file(path)?;
+	assert_eq!(extract_data(&std::fs::read(path)?), original_image_data);
+
+	// ...before removing the metadata completely
+	get_test_metadata()?.write_to_file(path)?;
+	Metadata::file_clear_metadata(path)?;
+	assert_eq!(extract_data(&std::fs::read(path)?), original_image_data);
+
+	Ok(())
+}
+
+#[test]
+fn
+image_data_unchanged_jpg()
+-> Result<(), std::io::Error>
+{
+	assert_image_data_unchanged("tests/sample2.jpg", "tests/sample2_image_data_copy.jpg", jpg_scan_data)
+}
+
+#[test]
+fn
+image_data_unchanged_png()
+-> Result<(), std::io::Error>
+{
+	assert_image_data_unchanged("tests/sample2.png", "tests/sample2_image_data_copy.png", png_image_data)
+}
+
+#[test]
+fn
+image_data_unchanged_webp_simple_lossless()
+-> Result<(), std::io::Error>
+{
+	assert_image_data_unchanged("tests/sample2_simple_loseless.webp", "tests/sample2_simple_loseless_image_data_copy.webp", webp_image_data)
+}
+
+#[test]
+fn
+image_data_unchanged_webp_simple_lossy()
+-> Result<(), std::io::Error>
+{
+	assert_image_data_unchanged("tests/sample2_simple_lossy.webp", "tests/sample2_simple_lossy_image_data_copy.webp", webp_image_data)
+}
+
+#[test]
+fn
+image_data_unchanged_webp_extended()
+-> Result<(), std::io::Error>
+{
+	assert_image_data_unchanged("tests/sample2_extended.webp", "tests/sample2_extended_image_data_copy.webp", webp_image_data)
+}
+
+#[test]
+fn
+image_data_unchanged_tiff()
+-> Result<(), std::io::Error>
+{
+	assert_image_data_unchanged("tests/sample.tif", "tests/sample_image_data_copy.tif", tiff_image_data)?;
+	assert_image_data_unchanged("tests/sample_big_endian.tiff", "tests/sample_big_endian_image_data_copy.tiff", tiff_image_data)
+}
+
+#[test]
+fn
+image_data_unchanged_heif()
+-> Result<(), std::io::Error>
+{
+	assert_image_data_unchanged("tests/sample.heic", "tests/sample_image_data_copy.heic", heif_image_data)?;
+	assert_image_data_unchanged("tests/sample.avif", "tests/sample_image_data_copy.avif", heif_image_data)
+}
+
+#[test]
+fn
+image_data_unchanged_gif()
+-> Result<(), std::io::Error>
+{
+	assert_image_data_unchanged("tests/sample.gif", "tests/sample_image_data_copy.gif", gif_image_data)
+}
+
+#[test]
+fn
+write_to_file_png_chunk_types()
+-> Result<(), std::io::Error>
+{
+	// Remove file from previous run and replace it with fresh copy
+	if let Err(error) = remove_file("tests/sample2_chunk_types_copy.png")
+	{
+		println!("{}", error);
+	}
+	copy("tests/sample2.png", "tests/sample2_chunk_types_copy.png")?;
+	let path = Path::new("tests/sample2_chunk_types_copy.png");
+
+	for png_exif_chunk in [
+		PngExifChunk::eXIf,
+		PngExifChunk::tEXt,
+		PngExifChunk::zTXt(0),
+		PngExifChunk::zTXt(10),
+	]
+	{
+		get_test_metadata()?.write_to_file_with_options(
+			path,
+			&WriteOptions::new().png_exif_chunk(png_exif_chunk)
+		)?;
+
+		// Previously written chunks of other types have to be gone
+		let file_content = std::fs::read(path)?;
+		let chunk_type_count = [&b"eXIf"[..], &b"tEXt"[..], &b"zTXt"[..]].iter()
+			.filter(|chunk_type| file_content.windows(4).any(|window| window == **chunk_type))
+			.count();
+		assert_eq!(chunk_type_count, 1);
+
+		let metadata = Metadata::new_from_path(path)?;
+		assert_eq!(
+			metadata.get_tag(&ExifTag::Model(String::new())),
+			Some(&ExifTag::Model("Testcam(1)".to_string()))
+		);
+	}
+
+	Ok(())
+}
+
+#[test]
+fn
+has_exif_and_has_gps()
+-> Result<(), std::io::Error>
+{
+	// Remove file from previous run and replace it with fresh copy
+	if let Err(error) = remove_file("tests/sample2_probe_copy.png")
+	{
+		println!("{}", error);
+	}
+	copy("tests/png_parse_test_image.png", "tests/sample2_probe_copy.png")?;
+	let path = Path::new("tests/sample2_probe_copy.png");
+
+	assert!(!Metadata::has_exif(path)?);
+	assert!(!Metadata::has_gps(path)?);
+
+	let mut metadata = get_test_metadata()?;
+	metadata.write_to_file(path)?;
+	assert!(Metadata::has_exif(path)?);
+	assert!(!Metadata::has_gps(path)?);
+
+	// The pointer to the GPS IFD is written as soon as there are GPS tags
+	metadata.set_tag(ExifTag::GPSImgDirection(vec![90, 1]));
+	metadata.write_to_file(path)?;
+	assert!(Metadata::has_gps(path)?);
+
+	assert!(Metadata::has_exif(Path::new("tests/does_not_exist.png")).is_err());
+	assert!(Metadata::has_gps(Path::new("tests/sample1.txt")).is_err());
+
+	// EXIF data that can't be probed must not pass as no EXIF data, here
+	// IFD0 claiming far more entries than there are
+	let corrupt_path = Path::new("tests/sample2_probe_corrupt.jpg");
+	copy("tests/sample2.jpg", corrupt_path)?;
+	metadata.write_to_file(corrupt_path)?;
+	let mut file_buffer = std::fs::read(corrupt_path)?;
+	let tiff_start = file_buffer.windows(6).position(|window| window == b"Exif\0\0").unwrap() + 6;
+	let ifd0_offset = match &file_buffer[tiff_start..tiff_start + 2]
+	{
+		b"II" => u32::from_le_bytes(file_buffer[tiff_start + 4..tiff_start + 8].try_into().unwrap()),
+		_     => u32::from_be_bytes(file_buffer[tiff_start + 4..tiff_start + 8].try_into().unwrap()),
+	} as usize;
+	file_buffer[tiff_start + ifd0_offset..tiff_start + ifd0_offset + 2].copy_from_slice(&[0xff, 0xff]);
+	std::fs::write(corrupt_path, &file_buffer)?;
+	let has_exif = Metadata::has_exif(corrupt_path);
+	let has_gps = Metadata::has_gps(corrupt_path);
+	remove_file(corrupt_path)?;
+	assert!(has_exif.is_err());
+	assert!(has_gps.is_err());
+
+	// Files without any EXIF container
+	for path in ["tests/sample2.png", "tests/read_sample_no_exif.webp", "tests/sample2_simple_lossy.webp"]
+	{
+		assert!(!Metadata::has_exif(Path::new(path))?, "{}", path);
+	}
+	assert!(Metadata::has_exif(Path::new("tests/sample.avi"))?);
+
+	Ok(())
+}
+
+#[test]
+fn
+write_to_vec_and_clear_metadata()
+-> Result<(), std::io::Error>
+{
+	let metadata = get_test_metadata()?;
+
+	for (path, file_type) in [
+		("tests/sample2.jpg",                FileExtension::JPEG),
+		("tests/sample2.png",                FileExtension::PNG { as_zTXt_chunk: true }),
+		("tests/sample2_simple_lossy.webp",  FileExtension::WEBP),
+	]
+	{
+		let original = std::fs::read(path)?;
+
+		// Writing to and reading from memory
+		let mut file_buffer = original.clone();
+		metadata.write_to_vec(&mut file_buffer, file_type)?;
+		let read_back = Metadata::new_from_vec(&file_buffer, file_type)?;
+		assert_eq!(
+			read_back.get_tag(&ExifTag::ImageDescription(String::new())),
+			metadata.get_tag(&ExifTag::ImageDescription(String::new()))
+		);
+
+		// Clearing leaves an image without any EXIF data
+		Metadata::clear_metadata(&mut file_buffer, file_type)?;
+		assert!(Metadata::new_from_vec(&file_buffer, file_type)?.data().is_empty());
+
+		// Invalid data is rejected and left untouched
+		let mut garbage = vec![0u8; 64];
+		assert!(metadata.write_to_vec(&mut garbage, file_type).is_err());
+		assert!(Metadata::clear_metadata(&mut garbage, file_type).is_err());
+		assert_eq!(garbage, vec![0u8; 64]);
+		assert!(Metadata::new_from_vec(&garbage, file_type).is_err());
+	}
+
+	Ok(())
+}
+
+#[test]
+fn
+write_to_read_only_file()
+-> Result<(), std::io::Error>
+{
+	let path = Path::new("tests/sample2_read_only_copy.jpg");
+	copy("tests/sample2.jpg", path)?;
+	let original = std::fs::read(path)?;
+
+	let mut permissions = std::fs::metadata(path)?.permissions();
+	permissions.set_readonly(true);
+	std::fs::set_permissions(path, permissions.clone())?;
+
+	let metadata = get_test_metadata()?;
+	let error = metadata.write_to_file(path).unwrap_err();
+	let destination = metadata.write_to_file_or_sidecar(path, Path::new("tests"));
+	let unchanged = std::fs::read(path)? == original;
+
+	#[allow(clippy::permissions_set_readonly_false)]
+	permissions.set_readonly(false);
+	std::fs::set_permissions(path, permissions)?;
+	remove_file(path)?;
+
+	assert_eq!(ReadOnlyTarget::from_io_error(&error).map(|target| target.path()), Some(path));
+	assert!(unchanged);
+
+	let sidecar_path = Path::new("tests/sample2_read_only_copy.jpg.exif");
+	assert_eq!(destination?, WriteDestination::Sidecar(sidecar_path.to_path_buf()));
+	let sidecar = std::fs::read(sidecar_path)?;
+	remove_file(sidecar_path)?;
+	assert!(sidecar.starts_with(&[0x49, 0x49, 0x2a, 0x00]));
+
+	Ok(())
+}
+
+#[test]
+#[cfg(windows)]
+fn
+write_to_file_in_use()
+-> Result<(), std::io::Error>
+{
+	use std::os::windows::fs::OpenOptionsExt;
+	use little_exif::errors::SharingViolation;
+
+	let path = Path::new("tests/sample2_in_use_copy.jpg");
+	copy("tests/sample2.jpg", path)?;
+	let original = std::fs::read(path)?;
+
+	// Opened without sharing, like some viewers do
+	let locked = std::fs::OpenOptions::new().read(true).share_mode(0).open(path)?;
+	let metadata = get_test_metadata()?;
+	let error = metadata.write_to_file(path).unwrap_err();
+	let destination = metadata.write_to_file_or_sidecar(path, Path::new("tests"));
+	drop(locked);
+
+	let unchanged = std::fs::read(path)? == original;
+	remove_file(path)?;
+
+	assert_eq!(error.kind(), std::io::ErrorKind::ResourceBusy);
+	assert_eq!(SharingViolation::from_io_error(&error).map(|violation| violation.path()), Some(path));
+	assert!(unchanged);
+
+	let sidecar_path = Path::new("tests/sample2_in_use_copy.jpg.exif");
+	assert_eq!(destination?, WriteDestination::Sidecar(sidecar_path.to_path_buf()));
+	remove_file(sidecar_path)?;
+
+	Ok(())
+}
+
+#[test]
+fn
+bmp_without_embedded_metadata()
+-> Result<(), std::io::Error>
+{
+	// A 1x1 pixel BMP image
+	let path = Path::new("tests/tmp_sidecar.BMP");
+	let mut bmp = b"BM\x3a\0\0\0\0\0\0\0\x36\0\0\0\x28\0\0\0\x01\0\0\0\x01\0\0\0\x01\0\x18\0".to_vec();
+	bmp.extend([0u8; 28]);
+	std::fs::write(path, &bmp)?;
+
+	let metadata = get_test_metadata()?;
+	let read_error = Metadata::new_from_path(path).err().unwrap();
+	let write_error = metadata.write_to_file(path).unwrap_err();
+	let exif_destination = metadata.write_to_file_or_sidecar(path, Path::new("tests"));
+	let xmp_destination = metadata.write_to_file_or_sidecar_with_format(path, Path::new("tests"), SidecarFormat::Xmp);
+	let unchanged = std::fs::read(path)? == bmp;
+	remove_file(path)?;
+
+	assert_eq!(read_error.kind(), std::io::ErrorKind::Unsupported);
+	assert_eq!(NoEmbeddedMetadata::from_io_error(&read_error).map(|error| error.format()), Some("BMP"));
+	assert_eq!(NoEmbeddedMetadata::from_io_error(&write_error).map(|error| error.path()), Some(path));
+	assert!(unchanged);
+
+	let exif_path = Path::new("tests/tmp_sidecar.BMP.exif");
+	assert_eq!(exif_destination?, WriteDestination::Sidecar(exif_path.to_path_buf()));
+	let sidecar = std::fs::read(exif_path)?;
+	remove_file(exif_path)?;
+	assert!(sidecar.starts_with(&[0x49, 0x49, 0x2a, 0x00]));
+
+	let xmp_path = Path::new("tests/tmp_sidecar.BMP.xmp");
+	assert_eq!(xmp_destination?, WriteDestination::Sidecar(xmp_path.to_path_buf()));
+	let packet = std::fs::read_to_string(xmp_path)?;
+	remove_file(xmp_path)?;
+	let tiff = ("tiff", "http://ns.adobe.com/tiff/1.0/");
+	let exif = ("exif", "http://ns.adobe.com/exif/1.0/");
+	assert_eq!(get_value(&packet, tiff, "ImageDescription"), Some(XmpValue::LangAlt(LangAlt::new().with(DEFAULT_LANGUAGE, "Hello World!"))));
+	assert_eq!(get_value(&packet, tiff, "Model"), Some(XmpValue::Simple("Testcam(1)".to_string())));
+	assert_eq!(get_value(&packet, exif, "ISO"), Some(XmpValue::Simple("2706".to_string())));
+
+	// Other unsupported types still get the generic error
+	let other_error = metadata.write_to_file(Path::new("tests/sample1.txt")).unwrap_err();
+	assert!(NoEmbeddedMetadata::from_io_error(&other_error).is_none());
+	Ok(())
+}
+
+#[test]
+fn
+memory_file_system()
+-> Result<(), std::io::Error>
+{
+	// The paths of the sample files are used within the in-memory file
+	// system, which must not touch the files on disk
+	let metadata = get_test_metadata()?;
+	let description = ExifTag::ImageDescription(String::new());
+	for (path, file_type) in [
+		("tests/sample2.jpg",      FileExtension::JPEG),
+		("tests/sample2.png",      FileExtension::PNG { as_zTXt_chunk: true }),
+		("tests/read_sample.webp", FileExtension::WEBP),
+		("tests/sample.tif",       FileExtension::TIFF),
+		("tests/sample.heic",      FileExtension::HEIF),
+		("tests/sample.gif",       FileExtension::GIF),
+	]
+	{
+		let path = Path::new(path);
+		let original = std::fs::read(path)?;
+		let file_system = MemoryFileSystem::new();
+		file_system.insert(path, original.clone());
+
+		metadata.write_to_file_in(&file_system, path)?;
+		let written = file_system.get(path).unwrap();
+		assert_ne!(written, original);
+		assert_eq!(Metadata::new_from_path_in(&file_system, path)?.get_tag(&description), metadata.get_tag(&description));
+
+		Metadata::file_clear_metadata_in(&file_system, path)?;
+		assert!(Metadata::new_from_vec(&file_system.get(path).unwrap(), file_type)?.get_tag(&description).is_none());
+		assert_eq!(std::fs::read(path)?, original);
+	}
+
+	// Files on disk don't exist in the in-memory file system
+	let file_system = MemoryFileSystem::new();
+	assert!(Metadata::new_from_path_in(&file_system, Path::new("tests/sample2.jpg")).is_err());
+	assert!(metadata.write_to_file_in(&file_system, Path::new("tests/sample2.jpg")).is_err());
+	assert!(file_system.get(Path::new("tests/sample2.jpg")).is_none());
+
+	Ok(())
+}
+
+#[test]
+fn
+safe_write()
+-> Result<(), std::io::Error>
+{
+	let metadata = get_test_metadata()?;
+	let options = WriteOptions::new().safe_write(true);
+
+	for (source, copy_name) in [
+		("tests/sample2.jpg",               "tests/sample2_safe_write_copy.jpg"),
+		("tests/sample2.png",               "tests/sample2_safe_write_copy.png"),
+		("tests/sample2_simple_lossy.webp", "tests/sample2_safe_write_copy.webp"),
+	]
+	{
+		let path = Path::new(copy_name);
+		copy(source, path)?;
+		let permissions = std::fs::metadata(path)?.permissions();
+
+		// A temporary file left over by an interrupted write must not be
+		// overwritten
+		let file_name = path.file_name().unwrap().to_str().unwrap();
+		let leftover_path = path.with_file_name(format!(".{}.little_exif-0.tmp", file_name));
+		std::fs::write(&leftover_path, b"leftover")?;
+
+		metadata.write_to_file_with_options(path, &options)?;
+		let safely_written = std::fs::read(path)?;
+		let permissions_kept = std::fs::metadata(path)?.permissions() == permissions;
+		let leftover = std::fs::read(&leftover_path)?;
+		let no_temp_file = !path.with_file_name(format!(".{}.little_exif-1.tmp", file_name)).exists();
+
+		copy(source, path)?;
+		metadata.write_to_file(path)?;
+		let written_in_place = std::fs::read(path)?;
+
+		remove_file(path)?;
+		remove_file(&leftover_path)?;
+
+		assert_eq!(safely_written, written_in_place, "{}", source);
+		assert!(permissions_kept, "{}", source);
+		assert_eq!(leftover, b"leftover");
+		assert!(no_temp_file, "{}", source);
+	}
+
+	// Nothing gets written if the file can't be written in place either
+	assert!(metadata.write_to_file_with_options(Path::new("tests/does_not_exist.jpg"), &options).is_err());
+	assert!(!Path::new("tests/.does_not_exist.jpg.little_exif-0.tmp").exists());
+
+	Ok(())
+}
+
+#[test]
+fn
+safe_write_temp_file_location()
+-> Result<(), std::io::Error>
+{
+	let path = Path::new("tests/sample2_temp_location_copy.jpg");
+	let metadata = get_test_metadata()?;
+	copy("tests/sample2.jpg", path)?;
+	metadata.write_to_file(path)?;
+	let written_in_place = std::fs::read(path)?;
+
+	// On Linux, /dev/shm usually is a separate file system, so the temporary
+	// file has to be copied next to the file before it can be renamed
+	let directory = Path::new("tests/temp_location");
+	std::fs::create_dir_all(directory)?;
+	let mut locations = vec![TempFileLocation::SystemTemp, TempFileLocation::Directory(directory.to_path_buf())];
+	if Path::new("/dev/shm").is_dir()
+	{
+		locations.push(TempFileLocation::Directory(Path::new("/dev/shm").to_path_buf()));
+	}
+
+	let leftovers = |directory: &Path| std::fs::read_dir(directory).unwrap()
+		.filter_map(|entry| entry.ok())
+		.filter(|entry| entry.file_name().to_string_lossy().starts_with(".sample2_temp_location_copy.jpg.little_exif-"))
+		.count();
+
+	for location in locations
+	{
+		copy("tests/sample2.jpg", path)?;
+		let options = WriteOptions::new().safe_write(true).temp_file_location(location.clone());
+		metadata.write_to_file_with_options(path, &options)?;
+		assert_eq!(std::fs::read(path)?, written_in_place, "{:?}", location);
+
+		let temp_directory = match &location
+		{
+			TempFileLocation::Directory(directory) => directory.clone(),
+			_                                      => std::env::temp_dir(),
+		};
+		assert_eq!(leftovers(&temp_directory), 0, "{:?}", location);
+	}
+	assert_eq!(leftovers(Path::new("tests")), 0);
+
+	remove_file(path)?;
+	std::fs::remove_dir(directory)?;
+	Ok(())
+}
+
+#[test]
+fn
+backup_and_restore()
+-> Result<(), std::io::Error>
+{
+	let path = Path::new("tests/sample2_backup_copy.png");
+	let backup_path = Path::new("tests/sample2_backup_copy.png_original");
+	copy("tests/sample2.png", path)?;
+	let original = std::fs::read(path)?;
+
+	let options = WriteOptions::new().backup(true);
+	let metadata = get_test_metadata()?;
+	metadata.write_to_file_with_options(path, &options)?;
+	let written = std::fs::read(path)?;
+	let backup_after_first_write = std::fs::read(backup_path)?;
+
+	// An existing backup is kept when writing again
+	let mut second_metadata = Metadata::new();
+	second_metadata.set_tag(ExifTag::ImageDescription("Second write".to_string()));
+	second_metadata.write_to_file_with_options(path, &options)?;
+	let backup_after_second_write = std::fs::read(backup_path)?;
+
+	Metadata::restore_backup(path)?;
+	let restored = std::fs::read(path)?;
+	let backup_removed = !backup_path.exists();
+	let second_restore = Metadata::restore_backup(path);
+
+	// No backup remains if the write fails
+	std::fs::write(path, b"not a png")?;
+	let failed_write = metadata.write_to_file_with_options(path, &options);
+	let no_backup_after_failure = !backup_path.exists();
+	remove_file(path)?;
+
+	assert_ne!(written, original);
+	assert_eq!(backup_after_first_write, original);
+	assert_eq!(backup_after_second_write, original);
+	assert_eq!(restored, original);
+	assert!(backup_removed);
+	assert_eq!(second_restore.err().map(|error| error.kind()), Some(std::io::ErrorKind::NotFound));
+	assert!(failed_write.is_err());
+	assert!(no_backup_after_failure);
+
+	Ok(())
+}
+
+#[test]
+fn
+clear_with_undo()
+-> Result<(), std::io::Error>
+{
+	let metadata = get_test_metadata()?;
+
+	for (source, file_type) in [
+		("tests/sample2.jpg",               FileExtension::JPEG),
+		("tests/sample2.png",               FileExtension::PNG { as_zTXt_chunk: true }),
+		("tests/sample2_simple_lossy.webp", FileExtension::WEBP),
+	]
+	{
+		let mut file_buffer = std::fs::read(source)?;
+		metadata.write_to_vec(&mut file_buffer, file_type)?;
+		let encoded = Metadata::new_from_vec(&file_buffer, file_type)?.encode();
+
+		let undo_token = Metadata::clear_metadata_with_undo(&mut file_buffer, file_type)?;
+		assert_eq!(Metadata::new_from_vec(&file_buffer, file_type)?.data().len(), 0, "{}", source);
+		assert_eq!(undo_token.removed_metadata()?.encode(), encoded, "{}", source);
+
+		// Clearing again removes nothing, so undoing that does nothing
+		let mut cleared = file_buffer.clone();
+		let empty_undo_token = Metadata::clear_metadata_with_undo(&mut cleared, file_type)?;
+		assert_eq!(empty_undo_token.removed_data(), None);
+		empty_undo_token.apply_to_vec(&mut cleared)?;
+		assert_eq!(cleared, file_buffer);
+
+		undo_token.apply_to_vec(&mut file_buffer)?;
+		assert_eq!(Metadata::new_from_vec(&file_buffer, file_type)?.encode(), encoded, "{}", source);
+	}
+
+	// Undoing via the file
+	let path = Path::new("tests/sample2_undo_copy.jpg");
+	copy("tests/sample2.jpg", path)?;
+	metadata.write_to_file(path)?;
+	let written = std::fs::read(path)?;
+
+	let undo_token = Metadata::file_clear_metadata_with_undo(path)?;
+	let cleared_tag_count = Metadata::new_from_path(path)?.data().len();
+	undo_token.apply_to_file(path)?;
+	let restored = std::fs::read(path)?;
+	remove_file(path)?;
+
+	assert_eq!(cleared_tag_count, 0);
+	assert_eq!(restored, written);
+
+	Ok(())
+}
+
+#[test]
+fn
+read_and_write_streams()
+-> Result<(), std::io::Error>
+{
+	let metadata = get_test_metadata()?;
+	let file_type = FileExtension::PNG { as_zTXt_chunk: true };
+	let file_buffer = std::fs::read("tests/sample2.png")?;
+
+	let mut expected = file_buffer.clone();
+	metadata.write_to_vec(&mut expected, file_type)?;
+
+	// A reader that is not seekable and returns the data in small pieces
+	let input = std::io::Read::chain(&file_buffer[..1000], &file_buffer[1000..]);
+	let mut output = Vec::new();
+	metadata.write_to_stream(input, &mut output, file_type, &WriteOptions::new())?;
+	assert_eq!(output, expected);
+
+	let read_back = Metadata::new_from_reader(&output[..], file_type, &ReadOptions::new())?;
+	assert_eq!(read_back.encode(), Metadata::new_from_vec(&expected, file_type)?.encode());
+
+	// Streams exceeding the limit are rejected without writing anything
+	let mut rejected_output = Vec::new();
+	let too_large = metadata.write_to_stream(&file_buffer[..], &mut rejected_output, file_type, &WriteOptions::new().max_stream_size(1000));
+	assert_eq!(too_large.err().map(|error| error.kind()), Some(std::io::ErrorKind::FileTooLarge));
+	assert!(rejected_output.is_empty());
+
+	let limit = output.len() as u64;
+	assert!(Metadata::new_from_reader(&output[..], file_type, &ReadOptions::new().max_stream_size(limit)).is_ok());
+	assert!(Metadata::new_from_reader(&output[..], file_type, &ReadOptions::new().max_stream_size(limit - 1)).is_err());
+
+	Ok(())
+}
+
+/// A `RangeSource` over data in memory, as a stand-in for a remote file
+struct
+MemoryRangeSource
+{
+	data: Vec<u8>,
+}
+
+impl
+RangeSource
+for
+MemoryRangeSource
+{
+	fn
+	size
+	(
+		&mut self
+	)
+	-> Result<u64, std::io::Error>
+	{
+		Ok(self.data.len() as u64)
+	}
+
+	fn
+	fetch
+	(
+		&mut self,
+		offset: u64,
+		length: u64
+	)
+	-> Result<Vec<u8>, std::io::Error>
+	{
+		Ok(self.data[offset as usize..(offset + length) as usize].to_vec())
+	}
+}
+
+#[test]
+fn
+read_via_range_requests()
+-> Result<(), std::io::Error>
+{
+	let metadata = get_test_metadata()?;
+
+	for (source, file_type) in [
+		("tests/sample2.jpg",               FileExtension::JPEG),
+		("tests/sample2.png",               FileExtension::PNG { as_zTXt_chunk: true }),
+		("tests/sample2_simple_lossy.webp", FileExtension::WEBP),
+	]
+	{
+		let mut file_buffer = std::fs::read(source)?;
+		metadata.write_to_vec(&mut file_buffer, file_type)?;
+		let expected = Metadata::new_from_vec(&file_buffer, file_type)?.encode();
+
+		let mut reader = RangeReader::new(MemoryRangeSource { data: file_buffer.clone() }).chunk_size(4096);
+		let read = Metadata::new_from_seekable_reader(&mut reader, file_type, &ReadOptions::new())?;
+		assert_eq!(read.encode(), expected, "{}", source);
+
+		// The EXIF data of the JPEG file is at its start
+		if file_type == FileExtension::JPEG
+		{
+			assert!(reader.fetched_bytes() * 4 < file_buffer.len() as u64, "{} of {}", reader.fetched_bytes(), file_buffer.len());
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn
+read_via_http_range_requests()
+-> Result<(), std::io::Error>
+{
+	use std::io::Read;
+	use std::io::Write;
+	use little_exif::range_reader::HttpRangeSource;
+
+	let mut file_buffer = std::fs::read("tests/sample2.jpg")?;
+	get_test_metadata()?.write_to_vec(&mut file_buffer, FileExtension::JPEG)?;
+	let expected = Metadata::new_from_vec(&file_buffer, FileExtension::JPEG)?.encode();
+
+	// A minimal server answering every request with the requested range
+	let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+	let address = listener.local_addr()?;
+	let served = file_buffer.clone();
+	std::thread::spawn(move || {
+		for stream in listener.incoming()
+		{
+			let mut stream = stream.unwrap();
+			let mut request = Vec::new();
+			let mut byte = [0u8; 1];
+			while !request.ends_with(b"\r\n\r\n") && stream.read(&mut byte).unwrap() == 1
+			{
+				request.push(byte[0]);
+			}
+
+			let request = String::from_utf8_lossy(&request).to_string();
+			let range = request.lines()
+				.find_map(|line| line.strip_prefix("Range: bytes="))
+				.and_then(|range| range.split_once('-'))
+				.map(|(first, last)| (first.parse::<usize>().unwrap(), last.parse::<usize>().unwrap()))
+				.unwrap();
+			let last = std::cmp::min(range.1, served.len() - 1);
+
+			let _ = write!(stream, "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {}-{}/{}\r\nContent-Length: {}\r\n\r\n", range.0, last, served.len(), last + 1 - range.0);
+			let _ = stream.write_all(&served[range.0..=last]);
+		}
+	});
+
+	let source = HttpRangeSource::new(&format!("http://{}/sample2.jpg", address))?;
+	let mut reader = RangeReader::new(source);
+	let read = Metadata::new_from_seekable_reader(&mut reader, FileExtension::JPEG, &ReadOptions::new())?;
+
+	assert_eq!(read.encode(), expected);
+	assert!(reader.fetched_bytes() < file_buffer.len() as u64);
+	assert!(HttpRangeSource::new("https://example.com/image.jpg").is_err());
+
+	Ok(())
+}
+
+#[test]
+fn
+object_store_round_trip()
+-> Result<(), std::io::Error>
+{
+	let root = Path::new("tests/object_store");
+	let store = FileSystemStore::new(root);
+	let metadata = get_test_metadata()?;
+
+	let result = (|| {
+		store.put("nested/sample2.webp", &std::fs::read("tests/sample2_simple_lossy.webp")?)?;
+		object_store::write_metadata(&store, "nested/sample2.webp", &metadata, &WriteOptions::new())?;
+		let read = object_store::read_metadata(&store, "nested/sample2.webp", &ReadOptions::new())?;
+		let written = Metadata::new_from_path(&root.join("nested/sample2.webp"))?;
+		assert_eq!(read.encode(), written.encode());
+		assert_eq!(read.get_tag(&ExifTag::ImageDescription(String::new())), metadata.get_tag(&ExifTag::ImageDescription(String::new())));
+
+		object_store::clear_metadata(&store, "nested/sample2.webp")?;
+		let cleared = object_store::read_metadata(&store, "nested/sample2.webp", &ReadOptions::new())?;
+		assert_eq!(cleared.data().len(), 0);
+
+		// Keys must stay within the store and name a supported file type
+		assert!(store.put("../escaped.jpg", b"").is_err());
+		assert!(store.size("/etc/passwd").is_err());
+		assert!(object_store::read_metadata(&store, "nested/sample2.gif", &ReadOptions::new()).is_err());
+		Ok(())
+	})();
+
+	std::fs::remove_dir_all(root)?;
+	result
+}
+
+#[test]
+fn
+deterministic_output()
+-> Result<(), std::io::Error>
+{
+	let tags = [
+		ExifTag::Model("Testcam(1)".to_string()),
+		ExifTag::ISO(vec![200]),
+		ExifTag::Artist("Jane Doe".to_string()),
+		ExifTag::ExposureProgram(vec![1]),
+		ExifTag::ImageDescription("Hello World!".to_string()),
+	];
+
+	let mut forward = Metadata::new();
+	tags.iter().cloned().for_each(|tag| forward.set_tag(tag));
+	let mut backward = Metadata::new();
+	tags.iter().rev().cloned().for_each(|tag| backward.set_tag(tag));
+
+	let deterministic = WriteOptions::new()
+		.auto_stamp_software(SoftwareStamp::LittleExif)
+		.deterministic(true);
+	let encoded = forward.encode_with_options(&deterministic)?;
+	assert_eq!(encoded, backward.encode_with_options(&deterministic)?);
+	assert_ne!(forward.encode(), backward.encode());
+
+	// Only the software gets stamped and the tags are ordered by their IDs
+	let decoded = Metadata::decode(&encoded)?;
+	assert!(decoded.get_tag(&ExifTag::ModifyDate(String::new())).is_none());
+	assert!(decoded.get_tag(&ExifTag::Software(String::new())).is_some());
+	for group in decoded.data().chunk_by(|a, b| a.get_group() == b.get_group())
+	{
+		assert!(group.windows(2).all(|pair| pair[0].as_u16() < pair[1].as_u16()));
+	}
+
+	let file_buffer = std::fs::read("tests/sample2.png")?;
+	let mut first  = file_buffer.clone();
+	let mut second = file_buffer.clone();
+	forward.write_to_vec_with_options(&mut first, FileExtension::PNG { as_zTXt_chunk: true }, &deterministic)?;
+	backward.write_to_vec_with_options(&mut second, FileExtension::PNG { as_zTXt_chunk: true }, &deterministic)?;
+	assert_eq!(first, second);
+
+	Ok(())
+}
+
+#[test]
+fn
+metadata_size_budget()
+-> Result<(), std::io::Error>
+{
+	let mut metadata = get_test_metadata()?;
+	metadata.set_tag(ExifTag::Copyright("Jane Doe".to_string()));
+	metadata.set_tag(ExifTag::MakerNote(vec![0xab; 500]));
+	metadata.set_tag(ExifTag::UserComment(vec![0x00; 100]));
+	let size = metadata.encode().len();
+
+	// Within the budget, nothing changes
+	assert_eq!(metadata.encode_with_options(&WriteOptions::new().max_metadata_bytes(size))?, metadata.encode());
+
+	let error = metadata.encode_with_options(&WriteOptions::new().max_metadata_bytes(size - 1)).unwrap_err();
+	let too_large = MetadataTooLarge::from_io_error(&error).unwrap();
+	assert_eq!((too_large.size(), too_large.limit()), (size, size - 1));
+
+	// The maker note goes first, the copyright last
+	let trimmed = Metadata::decode(&metadata.encode_with_options(&WriteOptions::new().max_metadata_bytes(size - 1).trim_to_budget(true))?)?;
+	assert!(trimmed.get_tag(&ExifTag::MakerNote(Vec::new())).is_none());
+	assert!(trimmed.get_tag(&ExifTag::UserComment(Vec::new())).is_some());
+
+	let trimmed = metadata.encode_with_options(&WriteOptions::new().max_metadata_bytes(100).trim_to_budget(true))?;
+	assert!(trimmed.len() <= 100);
+	let trimmed = Metadata::decode(&trimmed)?;
+	assert!(trimmed.get_tag(&ExifTag::Copyright(String::new())).is_some());
+	assert!(trimmed.get_tag(&ExifTag::UserComment(Vec::new())).is_none());
+
+	// Writing to a file fails without modifying it
+	let mut file_buffer = std::fs::read("tests/sample2.jpg")?;
+	let original = file_buffer.clone();
+	assert!(metadata.write_to_vec_with_options(&mut file_buffer, FileExtension::JPEG, &WriteOptions::new().max_metadata_bytes(8)).is_err());
+	assert_eq!(file_buffer, original);
+	assert!(metadata.encode_with_options(&WriteOptions::new().max_metadata_bytes(8).trim_to_budget(true)).is_err());
+
+	Ok(())
+}
+
+#[test]
+fn
+redaction_report()
+-> Result<(), std::io::Error>
+{
+	let mut metadata = get_test_metadata()?;
+	metadata.set_tag(ExifTag::SerialNumber("0123456789".to_string()));
+
+	let mut file_buffer = std::fs::read("tests/sample2.jpg")?;
+	metadata.write_to_vec(&mut file_buffer, FileExtension::JPEG)?;
+
+	let report = Metadata::clear_metadata_with_undo(&mut file_buffer, FileExtension::JPEG)?.redaction_report();
+	assert_eq!(report.removed_data_bytes(), metadata.encode().len());
+
+	let names = report.removed_tags().iter().map(|removed_tag| removed_tag.name()).collect::<Vec<_>>();
+	assert_eq!(names, ["ImageDescription", "Model", "ExposureProgram", "ISO", "SerialNumber"]);
+
+	let serial_number = &report.removed_tags()[4];
+	assert_eq!((serial_number.tag_id(), serial_number.summary(), serial_number.byte_count()), (0xa431, "0123456789", 11));
+	assert!(report.to_json().contains(r#"{"name":"SerialNumber","tag_id":42033,"group":"ExifIFD","summary":"0123456789","byte_count":11}"#));
+
+	// Stripping again removes nothing
+	let report = Metadata::clear_metadata_with_undo(&mut file_buffer, FileExtension::JPEG)?.redaction_report();
+	assert!(report.is_empty());
+	assert_eq!(report.to_json(), r#"{"file_type":"JPEG","removed_data_bytes":0,"removed_tags":[]}"#);
+
+	Ok(())
+}
+
+#[cfg(feature = "policy")]
+#[test]
+fn
+apply_policy()
+-> Result<(), std::io::Error>
+{
+	let mut metadata = get_test_metadata()?;
+	metadata.set_tag(ExifTag::SerialNumber("0123456789".to_string()));
+	metadata.set_tag(ExifTag::Artist("Jane Doe".to_string()));
+
+	let policy_path = Path::new("tests/privacy_policy.toml");
+	std::fs::write(policy_path, "remove = [\"Artist\"]\nhash = [\"SerialNumber\"]\nkeep = [\"Model\", \"ISO\", \"Artist\"]\n")?;
+	let policy = Policy::from_path(policy_path);
+	remove_file(policy_path)?;
+	metadata.apply_policy(&policy?);
+
+	let mut file_buffer = std::fs::read("tests/sample2.jpg")?;
+	metadata.write_to_vec(&mut file_buffer, FileExtension::JPEG)?;
+	let read_back = Metadata::new_from_vec(&file_buffer, FileExtension::JPEG)?;
+
+	let names = read_back.data().iter().filter(|tag| tag.is_writable()).map(|tag| tag.name()).collect::<Vec<_>>();
+	assert_eq!(names, ["Model", "ISO", "SerialNumber"]);
+	assert_eq!(
+		read_back.get_tag(&ExifTag::SerialNumber(String::new())),
+		Some(&ExifTag::SerialNumber("sha256:84d89877f0d4041efb6bf91a16f0248f2fd573e6af05c19f96bedb9f882f7882".to_string()))
+	);
+
+	// Unknown tag names are rejected
+	assert!(Policy::from_json(r#"{"remove": ["Artist", "Artists"]}"#).is_err());
+
+	Ok(())
+}
+
+#[cfg(feature = "policy")]
+#[test]
+fn
+pseudonymize_identifying_tags()
+-> Result<(), std::io::Error>
+{
+	let pseudonymize = |serial_number: &str, key: &[u8]| -> Result<ExifTag, std::io::Error> {
+		let mut metadata = get_test_metadata()?;
+		metadata.set_tag(ExifTag::SerialNumber(serial_number.to_string()));
+		metadata.set_tag(ExifTag::OwnerName("Jane Doe".to_string()));
+		metadata.apply_policy(&Policy::new().pseudonymize(key));
+
+		let read_back = Metadata::decode(&metadata.encode())?;
+		assert!(read_back.get_tag(&ExifTag::Model(String::new())).is_some());
+		match read_back.get_tag(&ExifTag::OwnerName(String::new()))
+		{
+			Some(ExifTag::OwnerName(owner_name)) => assert!(owner_name.starts_with("hmac-sha256:") && !owner_name.contains("Jane")),
+			other                                => panic!("{:?}", other),
+		}
+		return Ok(read_back.get_tag(&ExifTag::SerialNumber(String::new())).unwrap().clone());
+	};
+
+	// Same camera and key, same pseudonym
+	let pseudonym = pseudonymize("0123456789", b"secret key")?;
+	assert_eq!(pseudonymize("0123456789", b"secret key")?, pseudonym);
+	assert_ne!(pseudonymize("0123456780", b"secret key")?, pseudonym);
+	assert_ne!(pseudonymize("0123456789", b"other key")?,  pseudonym);
+
+	Ok(())
+}
+
+#[test]
+fn
+empty_values()
+-> Result<(), std::io::Error>
+{
+	let mut metadata = Metadata::new();
+	metadata.set_tag(ExifTag::Artist("Jane Doe".to_string()));
+	metadata.set_tag(ExifTag::ImageDescription(String::new()));
+	metadata.set_tag(ExifTag::Copyright(" \t ".to_string()));
+	metadata.set_tag(ExifTag::UnknownINT16U(Vec::new(), 0xabcd, ExifTagGroup::ExifIFD));
+
+	assert!(metadata.get_tag(&ExifTag::ImageDescription(String::new())).unwrap().is_empty_value());
+	assert!(!metadata.get_tag(&ExifTag::Artist(String::new())).unwrap().is_empty_value());
+
+	// Written as they are by default
+	let mut jpg = std::fs::read("tests/sample2.jpg")?;
+	metadata.write_to_vec(&mut jpg, FileExtension::JPEG)?;
+	assert_eq!(Metadata::new_from_vec(&jpg, FileExtension::JPEG)?.data().len(), 4);
+
+	// Reading: Kept, dropped or reported
+	let read = |empty_values: EmptyValues, lenient: bool| Metadata::new_from_seekable_reader(
+		&mut std::io::Cursor::new(&jpg),
+		FileExtension::JPEG,
+		&ReadOptions::new().empty_values(empty_values).lenient(lenient)
+	);
+	assert_eq!(read(EmptyValues::Keep, true)?.warnings().len(), 0);
+	assert_eq!(read(EmptyValues::Skip, false)?.data().iter().map(|tag| tag.name()).collect::<Vec<_>>(), ["Artist"]);
+	let reported = read(EmptyValues::Reject, true)?;
+	assert_eq!(reported.warnings().len(), 3);
+	assert_eq!(reported.data().len(), 4);
+	let error = read(EmptyValues::Reject, false).err().unwrap();
+	assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+	assert!(ParseErrorContext::from_io_error(&error).unwrap().message().contains("ImageDescription"));
+
+	// Writing: Dropped or refused, unless a hook fixes the value first
+	let mut skipped = std::fs::read("tests/sample2.jpg")?;
+	metadata.write_to_vec_with_options(&mut skipped, FileExtension::JPEG, &WriteOptions::new().empty_values(EmptyValues::Skip))?;
+	assert_eq!(Metadata::new_from_vec(&skipped, FileExtension::JPEG)?.data().len(), 1);
+
+	let mut rejected = std::fs::read("tests/sample2.jpg")?;
+	let error = metadata.write_to_vec_with_options(&mut rejected, FileExtension::JPEG, &WriteOptions::new().empty_values(EmptyValues::Reject)).err().unwrap();
+	assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+	assert_eq!(rejected, std::fs::read("tests/sample2.jpg")?);
+
+	let options = WriteOptions::new()
+		.empty_values(EmptyValues::Reject)
+		.hook(|tag| match tag
+		{
+			ExifTag::ImageDescription(_) => TagHookAction::Replace(ExifTag::ImageDescription("Untitled".to_string())),
+			_ if tag.is_empty_value()    => TagHookAction::Skip,
+			_                            => TagHookAction::Keep,
+		});
+	metadata.write_to_vec_with_options(&mut rejected, FileExtension::JPEG, &options)?;
+	let read_back = Metadata::new_from_vec(&rejected, FileExtension::JPEG)?;
+	assert_eq!(read_back.data().iter().map(|tag| tag.name()).collect::<Vec<_>>(), ["Artist", "ImageDescription"]);
+
+	Ok(())
+}
+
+#[test]
+fn
+max_length()
+-> Result<(), std::io::Error>
+{
+	let description = "A lighthouse on a cliff at dawn, seen from the beach";
+	let comment = [b"ASCII\0\0\0".to_vec(), b"Taken with a tripod & a remote".to_vec()].concat();
+	let mut metadata = Metadata::new();
+	metadata.set_tag(ExifTag::ImageDescription(description.to_string()));
+	metadata.set_tag(ExifTag::UserComment(comment.clone()));
+	metadata.set_tag(ExifTag::Artist("Jane Doe".to_string()));
+
+	let options = WriteOptions::new()
+		.max_length(ExifTag::ImageDescription(String::new()), 20, Truncation::Ellipsis)
+		.max_length(ExifTag::UserComment(Vec::new()),         10, Truncation::MoveToXmp)
+		.max_length(ExifTag::Artist(String::new()),           4,  Truncation::Cut);
+
+	// The complete comment ends up in XMP, the metadata itself is unchanged
+	let mut jpg = std::fs::read("tests/sample2.jpg")?;
+	metadata.write_to_vec_with_options(&mut jpg, FileExtension::JPEG, &options)?;
+	let read_back = Metadata::new_from_vec(&jpg, FileExtension::JPEG)?;
+	assert_eq!(read_back.get_tag(&ExifTag::ImageDescription(String::new())), Some(&ExifTag::ImageDescription("A lighthouse on a...".to_string())));
+	assert_eq!(read_back.get_tag(&ExifTag::UserComment(Vec::new())),         Some(&ExifTag::UserComment(b"ASCII\0\0\0Taken with".to_vec())));
+	assert_eq!(read_back.get_tag(&ExifTag::Artist(String::new())),           Some(&ExifTag::Artist("Jane".to_string())));
+	let packet = read_xmp_from_vec(&jpg)?.unwrap();
+	let moved_comment = LangAlt::new().with(DEFAULT_LANGUAGE, "Taken with a tripod & a remote");
+	assert_eq!(get_value(&packet, ("exif", "http://ns.adobe.com/exif/1.0/"), "UserComment"), Some(XmpValue::LangAlt(moved_comment)));
+	assert!(!packet.contains("ImageDescription"));
+	assert_eq!(metadata.get_tag(&ExifTag::UserComment(Vec::new())), Some(&ExifTag::UserComment(comment)));
+
+	// Files are written via the XMP packet as well
+	let path = Path::new("tests/tmp_max_length.png");
+	copy("tests/png_parse_test_image.png", path)?;
+	metadata.write_to_file_with_options(path, &options)?;
+	let png = std::fs::read(path)?;
+	remove_file(path)?;
+	assert!(read_xmp_from_vec(&png)?.unwrap().contains("<exif:UserComment>"));
+	assert_eq!(Metadata::new_from_vec(&png, FileExtension::PNG { as_zTXt_chunk: true })?.data(), read_back.data());
+
+	// Moving to XMP fails for files without XMP support, unless nothing has
+	// to be moved
+	let original = std::fs::read("tests/sample2_simple_lossy.webp")?;
+	let mut webp = original.clone();
+	let error = metadata.write_to_vec_with_options(&mut webp, FileExtension::WEBP, &options).err().unwrap();
+	assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+	assert_eq!(webp, original);
+	let options = options.max_length(ExifTag::UserComment(Vec::new()), 100, Truncation::MoveToXmp);
+	metadata.write_to_vec_with_options(&mut webp, FileExtension::WEBP, &options)?;
+	assert_eq!(Metadata::new_from_vec(&webp, FileExtension::WEBP)?.data().len(), 3);
+
+	Ok(())
+}
+
+#[test]
+fn
+config_defaults()
+-> Result<(), std::io::Error>
+{
+	let is_big_endian = |metadata: &Metadata| metadata.encode().windows(4).any(|window| window == [0x4d, 0x4d, 0x00, 0x2a]);
+
+	// Defaults as long as nothing has been configured
+	assert_eq!(Config::current(), Config::new());
+	assert!(!is_big_endian(&Metadata::new()));
+
+	// A config for the current thread only
+	Config::set_thread(Config::new()
+		.endian(Endian::Big)
+		.lenient(false)
+		.backup(true)
+		.software_stamp(SoftwareStamp::LittleExif)
+	);
+	let mut metadata = Metadata::new();
+	metadata.set_tag(ExifTag::Artist("Jane Doe".to_string()));
+	assert!(is_big_endian(&metadata));
+	assert!(format!("{:?}", WriteOptions::new()).contains("backup: true"));
+	assert!(format!("{:?}", WriteOptions::new()).contains("software_stamp: LittleExif"));
+
+	// Strict reading turns warnings into errors, unless changed per call
+	let mut jpg = std::fs::read("tests/sample2.jpg")?;
+	metadata.write_to_vec(&mut jpg, FileExtension::JPEG)?;
+	let app1_length = u16::from_be_bytes([jpg[4], jpg[5]]) as usize;
+	let app1 = jpg[2..4+app1_length].to_vec();
+	jpg.splice(2..2, app1);
+
+	let error = Metadata::new_from_vec(&jpg, FileExtension::JPEG).err().unwrap();
+	assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+	assert_eq!(ParseErrorContext::from_io_error(&error).and_then(|context| context.chunk_name()), Some("APP1"));
+	let read_back = Metadata::new_from_seekable_reader(&mut std::io::Cursor::new(&jpg), FileExtension::JPEG, &ReadOptions::new().lenient(true))?;
+	assert_eq!(read_back.warnings().len(), 1);
+
+	// Configs for single calls and other threads are not affected
+	assert!(!is_big_endian(&Metadata::new_with_config(&Config::new())));
+	assert!(format!("{:?}", WriteOptions::from_config(&Config::new())).contains("backup: false"));
+	assert_eq!(std::thread::spawn(Config::current).join().unwrap(), Config::global());
+
+	Config::clear_thread();
+	assert!(!is_big_endian(&Metadata::new()));
+
+	Ok(())
+}
+
+#[test]
+fn
+gps_destination_and_track()
+-> Result<(), std::io::Error>
+{
+	let mut metadata = get_test_metadata()?;
+	metadata.set_gps_destination(48.858222, -2.2945)?;
+	metadata.set_gps_img_direction(-90.0, GpsDirectionRef::MagneticNorth)?;
+	metadata.set_gps_track(123.456, GpsDirectionRef::TrueNorth)?;
+	metadata.set_gps_dop(1.75)?;
+
+	// Same ID as GPSDOP, but in IFD0 (ProcessingSoftware)
+	metadata.set_tag(ExifTag::UnknownSTRING("little_exif".to_string(), 0x000b, ExifTagGroup::IFD0));
+
+	assert!(metadata.set_gps_destination(91.0, 0.0).is_err());
+	assert!(metadata.set_gps_dop(f64::NAN).is_err());
+
+	let mut file_buffer = std::fs::read("tests/sample2.jpg")?;
+	metadata.write_to_vec(&mut file_buffer, FileExtension::JPEG)?;
+	let read_back = Metadata::new_from_vec(&file_buffer, FileExtension::JPEG)?;
+
+	let (latitude, longitude) = read_back.gps_destination().unwrap();
+	assert!((latitude - 48.858222).abs() < 1e-6 && (longitude + 2.2945).abs() < 1e-6);
+	assert_eq!(read_back.gps_img_direction(), Some((270.0, GpsDirectionRef::MagneticNorth)));
+	assert_eq!(read_back.gps_track(), Some((123.46, GpsDirectionRef::TrueNorth)));
+	assert_eq!(read_back.gps_dop(), Some(1.75));
+
+	assert_eq!(read_back.get_tag(&ExifTag::GPSDOP(Vec::new())).map(|tag| tag.get_group()), Some(ExifTagGroup::GPSIFD));
+	assert_eq!(
+		read_back.get_tag(&ExifTag::UnknownSTRING(String::new(), 0x000b, ExifTagGroup::IFD0)),
+		Some(&ExifTag::UnknownSTRING("little_exif".to_string(), 0x000b, ExifTagGroup::IFD0))
+	);
+	assert!(read_back.get_tag(&ExifTag::ImageDescription(String::new())).is_some());
+
+	Ok(())
+}
+
+#[test]
+fn
+gps_position_and_altitude()
+-> Result<(), std::io::Error>
+{
+	let mut metadata = get_test_metadata()?;
+	metadata.set_gps_position(-33.856784, 151.215297)?;
+	metadata.set_gps_altitude(-12.3456, GpsAltitudeRef::SeaLevel)?;
+	assert!(metadata.set_gps_altitude(f64::INFINITY, GpsAltitudeRef::SeaLevel).is_err());
+
+	let mut file_buffer = std::fs::read("tests/sample2.jpg")?;
+	metadata.write_to_vec(&mut file_buffer, FileExtension::JPEG)?;
+	let mut read_back = Metadata::new_from_vec(&file_buffer, FileExtension::JPEG)?;
+
+	let (latitude, longitude) = read_back.gps_position().unwrap();
+	assert!((latitude + 33.856784).abs() < 1e-6 && (longitude - 151.215297).abs() < 1e-6);
+	assert_eq!(read_back.gps_altitude(), Some((-12.346, GpsAltitudeRef::SeaLevel)));
+	assert_eq!(read_back.get_tag(&ExifTag::GPSAltitudeRef(Vec::new())), Some(&ExifTag::GPSAltitudeRef(vec![1])));
+
+	// The Interop IFD uses the same ID as GPSLatitudeRef
+	assert_eq!(read_back.get_tag(&ExifTag::GPSLatitudeRef(String::new())), Some(&ExifTag::GPSLatitudeRef("S".to_string())));
+	assert_eq!(ExifTag::from_name("GPSLatitudeRef").map(|tag| tag.get_group()), Ok(ExifTagGroup::GPSIFD));
+
+	read_back.set_gps_altitude(104.5, GpsAltitudeRef::Ellipsoid)?;
+	assert_eq!(read_back.gps_altitude(), Some((104.5, GpsAltitudeRef::Ellipsoid)));
+	assert_eq!(read_back.get_tag(&ExifTag::GPSAltitudeRef(Vec::new())), Some(&ExifTag::GPSAltitudeRef(vec![2])));
+
+	#[cfg(feature = "geoid")]
+	{
+		let undulation = |_latitude: f64, longitude: f64| if longitude > 0.0 { Some(22.25) } else { None };
+		read_back.convert_gps_altitude(GpsAltitudeRef::SeaLevel, &undulation)?;
+		assert_eq!(read_back.gps_altitude(), Some((82.25, GpsAltitudeRef::SeaLevel)));
+
+		read_back.set_gps_position(0.0, -1.0)?;
+		assert!(read_back.convert_gps_altitude(GpsAltitudeRef::Ellipsoid, &undulation).is_err());
+		assert!(Metadata::new().convert_gps_altitude(GpsAltitudeRef::Ellipsoid, &undulation).is_err());
+	}
+
+	Ok(())
+}
+
+#[test]
+fn
+environment_tags()
+-> Result<(), std::io::Error>
+{
+	let mut metadata = get_test_metadata()?;
+	metadata.set_ambient_temperature(57.2, TemperatureUnit::Fahrenheit)?;
+	metadata.set_humidity(100.0)?;
+	metadata.set_pressure(29.92, PressureUnit::InchesOfMercury)?;
+	metadata.set_water_depth(60.0, LengthUnit::Feet)?;
+	metadata.set_acceleration(1.0, AccelerationUnit::StandardGravity)?;
+	metadata.set_camera_elevation_angle(-12.5)?;
+
+	assert!(metadata.set_ambient_temperature(-300.0, TemperatureUnit::Celsius).is_err());
+	assert!(metadata.set_humidity(100.5).is_err());
+	assert!(metadata.set_pressure(-1.0, PressureUnit::Hectopascal).is_err());
+	assert!(metadata.set_water_depth(f64::NAN, LengthUnit::Meters).is_err());
+	assert!(metadata.set_camera_elevation_angle(181.0).is_err());
+
+	let mut file_buffer = std::fs::read("tests/sample2.jpg")?;
+	metadata.write_to_vec(&mut file_buffer, FileExtension::JPEG)?;
+	let mut read_back = Metadata::new_from_vec(&file_buffer, FileExtension::JPEG)?;
+
+	assert_eq!(read_back.ambient_temperature(TemperatureUnit::Celsius), Some(14.0));
+	assert!((read_back.ambient_temperature(TemperatureUnit::Fahrenheit).unwrap() - 57.2).abs() < 1e-9);
+	assert_eq!(read_back.humidity(), Some(100.0));
+	assert_eq!(read_back.pressure(PressureUnit::Hectopascal), Some(1013.2));
+	assert_eq!(read_back.water_depth(LengthUnit::Meters), Some(18.288));
+	assert_eq!(read_back.acceleration(AccelerationUnit::Milligal), Some(980665.0));
+	assert_eq!(read_back.camera_elevation_angle(), Some(-12.5));
+
+	// Values marked as unknown
+	read_back.set_tag(ExifTag::AmbientTemperature(vec![-1, -1]));
+	read_back.set_tag(ExifTag::Humidity(vec![u32::MAX, 1]));
+	assert_eq!(read_back.ambient_temperature(TemperatureUnit::Celsius), None);
+	assert_eq!(read_back.humidity(), None);
+	assert_eq!(Metadata::new().water_depth(LengthUnit::Feet), None);
+
+	Ok(())
+}
+
+#[test]
+fn
+panorama_metadata()
+-> Result<(), std::io::Error>
+{
+	let mut file_buffer = std::fs::read("tests/sample2.jpg")?;
+	get_test_metadata()?.write_to_vec(&mut file_buffer, FileExtension::JPEG)?;
+
+	let info = PanoInfo::new(8000, 4000)
+		.cropped_area(0, 500, 8000, 3000)
+		.initial_view(90.0, -10.0)
+		.stitching_software("PanoStitch 3.1");
+	write_pano_to_vec(&mut file_buffer, &info)?;
+
+	let packet = read_xmp_from_vec(&file_buffer)?.unwrap();
+	assert!(packet.contains("GPano:ProjectionType=\"equirectangular\""));
+	assert!(packet.contains("GPano:CroppedAreaTopPixels=\"500\""));
+	assert!(packet.contains("GPano:InitialViewPitchDegrees=\"-10\""));
+	assert!(packet.contains("GPano:StitchingSoftware=\"PanoStitch 3.1\""));
+
+	// Writing again replaces all properties and keeps the EXIF data
+	write_pano_to_vec(&mut file_buffer, &PanoInfo::new(8000, 4000).use_panorama_viewer(false))?;
+	let packet = read_xmp_from_vec(&file_buffer)?.unwrap();
+	assert_eq!(packet.matches("GPano:FullPanoWidthPixels=").count(), 1);
+	assert!(packet.contains("GPano:UsePanoramaViewer=\"False\""));
+	assert!(!packet.contains("GPano:StitchingSoftware"));
+	assert_eq!(
+		Metadata::new_from_vec(&file_buffer, FileExtension::JPEG)?.get_tag(&ExifTag::ImageDescription(String::new())),
+		get_test_metadata()?.get_tag(&ExifTag::ImageDescription(String::new()))
+	);
+
+	assert!(write_pano_to_vec(&mut std::fs::read("tests/read_sample.webp")?, &info).is_err());
+	Ok(())
+}
+
+#[test]
+fn
+geocode_batch()
+-> Result<(), std::io::Error>
+{
+	let with_position    = Path::new("tests/sample2_geocode_copy.jpg");
+	let without_position = Path::new("tests/sample2_geocode_no_gps_copy.jpg");
+	copy("tests/sample2.jpg", with_position)?;
+	copy("tests/sample2.jpg", without_position)?;
+
+	let mut metadata = get_test_metadata()?;
+	metadata.set_gps_position(47.070714, 15.439504)?;
+	metadata.write_to_file(with_position)?;
+
+	let geocoder = |latitude: f64, longitude: f64| {
+		if (latitude - 47.07).abs() < 0.01 && (longitude - 15.44).abs() < 0.01
+		{
+			return Some(Location::new().city("Graz").province_state("Steiermark").country_code("AUT").country("Österreich"));
+		}
+		return None;
+	};
+	let results = geocode_files(&[with_position, without_position, Path::new("tests/no_such_file.jpg")], &geocoder);
+
+	let located   = std::fs::read(with_position)?;
+	let untouched = std::fs::read(without_position)?;
+	remove_file(with_position)?;
+	remove_file(without_position)?;
+
+	assert_eq!(results.len(), 3);
+	assert_eq!(results[0].as_ref().ok(), Some(&geocoder(47.070714, 15.439504)));
+	assert_eq!(results[1].as_ref().ok(), Some(&None));
+	assert!(results[2].is_err());
+
+	assert_eq!(read_location_from_vec(&located)?.and_then(|location| location.city), Some("Graz".to_string()));
+	assert!(Metadata::new_from_vec(&located, FileExtension::JPEG)?.gps_position().is_some());
+	assert_eq!(untouched, std::fs::read("tests/sample2.jpg")?);
+
+	Ok(())
+}
+
+/// Copies the sample JPG into the given directory, writes and reads metadata
+/// via the path and removes the directory again
+fn
+write_and_read_in_directory
+(
+	directory: &Path
+)
+-> Result<(), std::io::Error>
+{
+	std::fs::create_dir_all(directory)?;
+	let path = directory.join("Фото 写真 ü.jpg");
+	copy("tests/sample2.jpg", &path)?;
+
+	let result = (|| {
+		let metadata = get_test_metadata()?;
+		metadata.write_to_file(&path)?;
+		let read_back = Metadata::new_from_path(&path)?;
+		assert_eq!(
+			read_back.get_tag(&ExifTag::ImageDescription(String::new())),
+			metadata.get_tag(&ExifTag::ImageDescription(String::new()))
+		);
+		assert!(Metadata::has_exif(&path)?);
+		Metadata::file_clear_metadata(&path)
+	})();
+
+	std::fs::remove_dir_all(directory)?;
+	return result;
+}
+
+#[test]
+fn
+unicode_paths()
+-> Result<(), std::io::Error>
+{
+	write_and_read_in_directory(Path::new("tests/tmp_ünïcödé_路径"))
+}
+
+#[test]
+#[cfg(windows)]
+fn
+windows_long_paths()
+-> Result<(), std::io::Error>
+{
+	// Exceeds MAX_PATH (260 characters) by far
+	let mut directory = std::env::current_dir()?.join("tests").join("tmp_long_path");
+	for index in 0..12
+	{
+		directory.push(format!("Verzeichnis mit langem Namen Nr. {}", index));
+	}
+	assert!(directory.as_os_str().len() > 260);
+	write_and_read_in_directory(&directory)?;
+
+	// Explicitly verbatim paths work as well
+	let mut verbatim_directory = std::ffi::OsString::from(r"\\?\");
+	verbatim_directory.push(std::env::current_dir()?.join(r"tests\tmp_verbatim_ü").as_os_str());
+	write_and_read_in_directory(Path::new(&verbatim_directory))?;
+
+	std::fs::remove_dir_all("tests/tmp_long_path").or(Ok(()))
+}
+
+#[test]
+fn
+infer_dates()
+-> Result<(), std::io::Error>
+{
+	let path = Path::new("tests/IMG-20240131-WA0003_copy.png");
+	copy("tests/png_parse_test_image.png", path)?;
+
+	let mut metadata = Metadata::new_from_path(path)?;
+	let inferred_from_mtime = Metadata::new().infer_date_from_modification_time(path);
+	assert!(metadata.infer_date_from_filename(path, &DEFAULT_FILENAME_DATE_PATTERNS));
+	metadata.write_to_file(path)?;
+
+	let mut read_back = Metadata::new_from_path(path)?;
+	remove_file(path)?;
+
+	assert!(inferred_from_mtime?);
+	assert_eq!(
+		read_back.get_tag(&ExifTag::DateTimeOriginal(String::new())),
+		Some(&ExifTag::DateTimeOriginal("2024:01:31 00:00:00".to_string()))
+	);
+
+	// Existing dates are kept
+	assert!(!read_back.infer_date_from_filename(Path::new("IMG_20200101_120000.jpg"), &DEFAULT_FILENAME_DATE_PATTERNS));
+	assert!(!Metadata::new().infer_date_from_filename(Path::new("holiday.jpg"), &DEFAULT_FILENAME_DATE_PATTERNS));
+
+	Ok(())
+}
+
+#[test]
+fn
+maker_note_shutter_count()
+-> Result<(), std::io::Error>
+{
+	// Nikon maker notes with a single entry: ShutterCount (0x00a7) = 12345
+	let mut maker_note = b"Nikon\0\x02\x10\0\0MM\0\x2a\0\0\0\x08".to_vec();
+	maker_note.extend([0x00, 0x01]);
+	maker_note.extend([0x00, 0xa7, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x30, 0x39]);
+	maker_note.extend([0x00, 0x00, 0x00, 0x00]);
+
+	let mut metadata = Metadata::new();
+	metadata.set_tag(ExifTag::Make("NIKON CORPORATION".to_string()));
+	metadata.set_tag(ExifTag::MakerNote(maker_note.clone()));
+	metadata.set_tag(ExifTag::DateTimeOriginal("2024:01:31 18:05:00".to_string()));
+	assert_eq!(metadata.shutter_count(), Some(12345));
+
+	// The maker notes are kept as they are when writing
+	let mut file_buffer = std::fs::read("tests/sample2.jpg")?;
+	metadata.write_to_vec(&mut file_buffer, FileExtension::JPEG)?;
+	let read_back = Metadata::new_from_vec(&file_buffer, FileExtension::JPEG)?;
+
+	assert_eq!(read_back.get_tag(&ExifTag::MakerNote(Vec::new())), Some(&ExifTag::MakerNote(maker_note)));
+	assert_eq!(read_back.shutter_count(), Some(12345));
+	assert_eq!(Metadata::new().shutter_count(), None);
+
+	Ok(())
+}
+
+#[test]
+fn
+opcode_lists_are_preserved()
+-> Result<(), std::io::Error>
+{
+	let opcode_list = OpcodeList::new(vec![
+		Opcode { id: 3, dng_version: [1, 3, 0, 0], flags: 1, parameters: vec![0x3f; 56] },
+	]);
+
+	let mut file_buffer = std::fs::read("tests/sample2.jpg")?;
+	let mut metadata = Metadata::new();
+	metadata.set_tag(ExifTag::OpcodeList3(opcode_list.encode()));
+	metadata.set_tag(ExifTag::DateTimeOriginal("2024:01:31 18:05:00".to_string()));
+	metadata.write_to_vec(&mut file_buffer, FileExtension::JPEG)?;
+
+	// Edit some other tag and write the metadata back
+	let mut edited = Metadata::new_from_vec(&file_buffer, FileExtension::JPEG)?;
+	edited.set_tag(ExifTag::Artist("Jane Doe".to_string()));
+	edited.write_to_vec(&mut file_buffer, FileExtension::JPEG)?;
+
+	let read_back = Metadata::new_from_vec(&file_buffer, FileExtension::JPEG)?;
+	match read_back.get_tag(&ExifTag::OpcodeList3(Vec::new()))
+	{
+		Some(ExifTag::OpcodeList3(raw_data)) => {
+			let decoded = OpcodeList::decode(raw_data)?;
+			assert_eq!(decoded, opcode_list);
+			assert_eq!(decoded.lens_corrections()[0].name(), Some("FixVignetteRadial"));
+		},
+		_ => panic!("OpcodeList3 got lost!"),
+	}
+
+	Ok(())
+}
+
+#[test]
+fn
+write_hooks()
+-> Result<(), std::io::Error>
+{
+	let mut metadata = Metadata::new();
+	metadata.set_tag(ExifTag::Artist("jane doe".to_string()));
+	metadata.set_tag(ExifTag::Software("Editor".to_string()));
+	metadata.set_tag(ExifTag::DateTimeOriginal("2024:01:31 18:05:00".to_string()));
+
+	// Normalize the artist and never write the software
+	let options = WriteOptions::new()
+		.hook(|tag| match tag
+		{
+			ExifTag::Artist(artist) => TagHookAction::Replace(ExifTag::Artist(artist.to_uppercase())),
+			_                       => TagHookAction::Keep,
+		})
+		.hook(|tag| match tag
+		{
+			ExifTag::Software(_) => TagHookAction::Skip,
+			_                    => TagHookAction::Keep,
+		});
+
+	let original_buffer = std::fs::read("tests/sample2.jpg")?;
+	let mut file_buffer = original_buffer.clone();
+	metadata.write_to_vec_with_options(&mut file_buffer, FileExtension::JPEG, &options)?;
+
+	let read_back = Metadata::new_from_vec(&file_buffer, FileExtension::JPEG)?;
+	assert_eq!(read_back.get_tag(&ExifTag::Artist(String::new())), Some(&ExifTag::Artist("JANE DOE".to_string())));
+	assert!(read_back.get_tag(&ExifTag::Software(String::new())).is_none());
+	assert!(read_back.get_tag(&ExifTag::DateTimeOriginal(String::new())).is_some());
+
+	// The metadata itself is unchanged
+	assert_eq!(metadata.get_tag(&ExifTag::Artist(String::new())), Some(&ExifTag::Artist("jane doe".to_string())));
+
+	// Vetoing leaves the data untouched
+	let mut file_buffer = original_buffer.clone();
+	let vetoing = options.hook(|tag| match tag
+	{
+		ExifTag::DateTimeOriginal(_) => TagHookAction::Abort("No dates allowed".to_string()),
+		_                            => TagHookAction::Keep,
+	});
+	assert!(metadata.write_to_vec_with_options(&mut file_buffer, FileExtension::JPEG, &vetoing).is_err());
+	assert_eq!(file_buffer, original_buffer);
+
+	Ok(())
+}
+
+#[test]
+fn
+auto_stamp_software()
+-> Result<(), std::io::Error>
+{
+	let mut metadata = Metadata::new();
+	metadata.set_tag(ExifTag::Software("Editor".to_string()));
+	metadata.set_tag(ExifTag::DateTimeOriginal("2024:01:31 18:05:00".to_string()));
+
+	let original_buffer = std::fs::read("tests/sample2.jpg")?;
+	let stamping = WriteOptions::new().auto_stamp_software(SoftwareStamp::LittleExif);
+
+	// Stamping twice doesn't append the stamp twice
+	let mut file_buffer = original_buffer.clone();
+	metadata.write_to_vec_with_options(&mut file_buffer, FileExtension::JPEG, &stamping)?;
+	let stamped = Metadata::new_from_vec(&file_buffer, FileExtension::JPEG)?;
+	stamped.write_to_vec_with_options(&mut file_buffer, FileExtension::JPEG, &stamping)?;
+	let stamped = Metadata::new_from_vec(&file_buffer, FileExtension::JPEG)?;
+
+	assert_eq!(
+		stamped.get_tag(&ExifTag::Software(String::new())),
+		Some(&ExifTag::Software(format!("Editor; {}", LITTLE_EXIF_SOFTWARE)))
+	);
+	assert!(stamped.get_tag(&ExifTag::ModifyDate(String::new())).is_some());
+	assert!(LITTLE_EXIF_SOFTWARE.starts_with("little_exif "));
+
+	// Custom stamps and switching it off
+	let mut file_buffer = original_buffer.clone();
+	metadata.write_to_vec_with_options(&mut file_buffer, FileExtension::JPEG, &WriteOptions::new().auto_stamp_software(SoftwareStamp::Custom("Sorter 2.1".to_string())))?;
+	let stamped = Metadata::new_from_vec(&file_buffer, FileExtension::JPEG)?;
+	assert_eq!(stamped.get_tag(&ExifTag::Software(String::new())), Some(&ExifTag::Software("Editor; Sorter 2.1".to_string())));
+
+	let mut file_buffer = original_buffer.clone();
+	let mut unstamped_buffer = original_buffer.clone();
+	metadata.write_to_vec_with_options(&mut file_buffer, FileExtension::JPEG, &stamping.auto_stamp_software(SoftwareStamp::Off))?;
+	metadata.write_to_vec(&mut unstamped_buffer, FileExtension::JPEG)?;
+	assert_eq!(file_buffer, unstamped_buffer);
+
+	Ok(())
+}
+
+#[test]
+fn
+write_for_exif_version()
+-> Result<(), std::io::Error>
+{
+	let mut metadata = Metadata::new();
+	metadata.set_tag(ExifTag::DateTimeOriginal("2024:01:31 18:05:00".to_string()));
+	metadata.set_tag(ExifTag::OffsetTimeOriginal("+01:00".to_string()));
+	metadata.set_tag(ExifTag::LensModel("50mm F1.8".to_string()));
+	metadata.set_tag(ExifTag::ExifVersion(b"0232".to_vec()));
+
+	let mut file_buffer = std::fs::read("tests/sample2.jpg")?;
+	metadata.write_to_vec_with_options(&mut file_buffer, FileExtension::JPEG, &WriteOptions::new().exif_version(ExifVersion::V220))?;
+	let read_back = Metadata::new_from_vec(&file_buffer, FileExtension::JPEG)?;
+
+	assert!(read_back.get_tag(&ExifTag::DateTimeOriginal(String::new())).is_some());
+	assert!(read_back.get_tag(&ExifTag::OffsetTimeOriginal(String::new())).is_none());
+	assert!(read_back.get_tag(&ExifTag::LensModel(String::new())).is_none());
+	assert_eq!(read_back.get_tag(&ExifTag::ExifVersion(Vec::new())), Some(&ExifTag::ExifVersion(b"0220".to_vec())));
+
+	Ok(())
+}
+
+#[test]
+fn
+write_from_scratch()
+-> Result<(), std::io::Error>
+{
+	// Start with images that have no metadata container at all: A JPEG
+	// without APP1 segment, a PNG without any text chunks and WebP files in
+	// the simple format, i.e. without VP8X chunk
+	let mut jpg_buffer = std::fs::read("tests/sample2.jpg")?;
+	Metadata::clear_metadata(&mut jpg_buffer, FileExtension::JPEG)?;
+	assert!(!jpg_buffer.windows(6).any(|window| window == b"Exif\0\0"));
+
+	let images = [
+		(jpg_buffer,                                          FileExtension::JPEG),
+		(std::fs::read("tests/sample2.png")?,                 FileExtension::PNG { as_zTXt_chunk: true }),
+		(std::fs::read("tests/sample2_simple_lossy.webp")?,   FileExtension::WEBP),
+		(std::fs::read("tests/sample2_simple_loseless.webp")?, FileExtension::WEBP),
+	];
+
+	// Only IFD0 tags (no ExifIFD), only ExifIFD tags, both and none at all
+	let mut ifd0_only = Metadata::new();
+	ifd0_only.set_tag(ExifTag::Artist("Jane Doe".to_string()));
+	let mut exififd_only = Metadata::new();
+	exififd_only.set_tag(ExifTag::LensModel("50mm F1.8".to_string()));
+	let mut both = Metadata::new();
+	both.set_tag(ExifTag::Artist("Jane Doe".to_string()));
+	both.set_tag(ExifTag::LensModel("50mm F1.8".to_string()));
+
+	for (original_buffer, file_type) in images
+	{
+		assert!(Metadata::new_from_vec(&original_buffer, file_type)?.data().is_empty());
+
+		for metadata in [&ifd0_only, &exififd_only, &both, &Metadata::new()]
+		{
+			let mut file_buffer = original_buffer.clone();
+			metadata.write_to_vec(&mut file_buffer, file_type)?;
+			let read_back = Metadata::new_from_vec(&file_buffer, file_type)?;
+
+			assert_eq!(read_back.data(), metadata.data(), "{:?}", file_type);
+		}
+	}
+
+	Ok(())
+}
+
+#[test]
+fn
+encode_and_decode()
+-> Result<(), std::io::Error>
+{
+	let metadata = Metadata::decode(&get_test_metadata()?.encode())?;
+	assert_eq!(metadata.get_tag(&ExifTag::Model(String::new())), Some(&ExifTag::Model("Testcam(1)".to_string())));
+
+	let encoded = metadata.encode();
+	assert!(encoded.starts_with(b"II") || encoded.starts_with(b"MM"));
+	assert_eq!(Metadata::decode(&encoded)?.data(), metadata.data());
+
+	// With the prefix used by JPEG APP1 segments
+	let mut prefixed = b"Exif\0\0".to_vec();
+	prefixed.extend(&encoded);
+	assert_eq!(Metadata::decode(&prefixed)?.data(), metadata.data());
+
+	// With the offset used by HEIF Exif items
+	let mut heif_prefixed = vec![0x00, 0x00, 0x00, 0x06];
+	heif_prefixed.extend(&prefixed);
+	assert_eq!(Metadata::decode(&heif_prefixed)?.data(), metadata.data());
+
+	// Options affecting the tags are applied
+	let options = WriteOptions::new().hook(|tag| match tag
+	{
+		ExifTag::Model(_) => TagHookAction::Skip,
+		_                 => TagHookAction::Keep,
+	});
+	let decoded = Metadata::decode(&metadata.encode_with_options(&options)?)?;
+	assert!(decoded.get_tag(&ExifTag::Model(String::new())).is_none());
+	assert!(decoded.get_tag(&ExifTag::ImageDescription(String::new())).is_some());
+
+	assert!(Metadata::decode(b"garbage").is_err());
+
+	Ok(())
+}
+
+#[test]
+fn
+write_repeatedly()
+-> Result<(), std::io::Error>
+{
+	// Writing the same metadata over and over again must neither accumulate
+	// chunks or padding nor change the container size information. The
+	// metadata is encoded once with an even and once with an odd length, the
+	// latter requiring a padding byte in WebP files
+	let even_metadata = get_test_metadata()?;
+	let mut odd_metadata = get_test_metadata()?;
+	odd_metadata.set_tag(ExifTag::Artist(match even_metadata.encode().len() % 2
+	{
+		0 => "Jane Doe".to_string(),
+		_ => "Jane Doe2".to_string(),
+	}));
+	assert_eq!(odd_metadata.encode().len() % 2, 1);
+
+	let images = [
+		("tests/sample2.jpg",                  FileExtension::JPEG,                           WriteOptions::new()),
+		("tests/sample2.png",                  FileExtension::PNG { as_zTXt_chunk: true },    WriteOptions::new()),
+		("tests/sample2.png",                  FileExtension::PNG { as_zTXt_chunk: false },   WriteOptions::new().png_exif_chunk(PngExifChunk::eXIf)),
+		("tests/sample2_simple_lossy.webp",    FileExtension::WEBP,                           WriteOptions::new()),
+		("tests/sample2_simple_loseless.webp", FileExtension::WEBP,                           WriteOptions::new()),
+		("tests/sample2_extended.webp",        FileExtension::WEBP,                           WriteOptions::new()),
+		("tests/sample.tif",                   FileExtension::TIFF,                           WriteOptions::new()),
+		("tests/sample_big_endian.tiff",       FileExtension::TIFF,                           WriteOptions::new()),
+		("tests/sample.heic",                  FileExtension::HEIF,                           WriteOptions::new()),
+		("tests/sample.avif",                  FileExtension::AVIF,                           WriteOptions::new()),
+		("tests/sample.gif",                   FileExtension::GIF,                            WriteOptions::new()),
+	];
+
+	for ((path, file_type, options), metadata) in images.iter().flat_map(|image| [(image, &even_metadata), (image, &odd_metadata)])
+	{
+		let mut file_buffer = std::fs::read(path)?;
+		metadata.write_to_vec_with_options(&mut file_buffer, *file_type, options)?;
+		let first_write = file_buffer.clone();
+
+		for _ in 1..100
+		{
+			metadata.write_to_vec_with_options(&mut file_buffer, *file_type, options)?;
+			assert_eq!(file_buffer, first_write, "{}", path);
+		}
+
+		if *file_type == FileExtension::WEBP
+		{
+			let riff_size = u32::from_le_bytes(file_buffer[4..8].try_into().unwrap());
+			assert_eq!(riff_size as usize, file_buffer.len() - 8);
+		}
+		assert_eq!(Metadata::new_from_vec(&file_buffer, *file_type)?.data(), Metadata::new_from_vec(&first_write, *file_type)?.data());
+	}
+
+	Ok(())
+}
+
+#[test]
+fn
+format_capabilities()
+{
+	let all_capabilities = capabilities();
+	assert_eq!(all_capabilities.len(), 13);
+
+	for format_capabilities in all_capabilities
+	{
+		// Raw formats are read-only
+		let raw = matches!(format_capabilities.file_type, FileExtension::CR2 | FileExtension::CR3 | FileExtension::ORF | FileExtension::RW2 | FileExtension::RAF);
+		assert!(format_capabilities.read);
+		assert_eq!(format_capabilities.write, !raw);
+		assert_eq!(format_capabilities.clear, !raw);
+		for extension in format_capabilities.extensions
+		{
+			let file_type = FileExtension::from_str(extension).unwrap();
+			assert_eq!(file_type.capabilities(), format_capabilities);
+		}
+	}
+
+	assert!(FileExtension::JPEG.capabilities().xmp);
+	assert!(FileExtension::PNG { as_zTXt_chunk: false }.capabilities().xmp);
+	assert!(!FileExtension::WEBP.capabilities().xmp);
+	assert!(!FileExtension::TIFF.capabilities().xmp);
+	assert!(!FileExtension::HEIF.capabilities().xmp);
+	assert!(!FileExtension::AVIF.capabilities().xmp);
+	assert!(FileExtension::GIF.capabilities().xmp);
+	assert!(!FileExtension::CR2.capabilities().xmp);
+	assert!(!FileExtension::CR3.capabilities().xmp);
+	assert!(!FileExtension::ORF.capabilities().xmp);
+	assert!(!FileExtension::RW2.capabilities().xmp);
+	assert!(!FileExtension::RAF.capabilities().xmp);
+	assert!(!FileExtension::AVI.capabilities().xmp);
+}
+
+#[test]
+fn
+parse_error_context()
+-> Result<(), std::io::Error>
+{
+	// Invalid format of the second IFD0 entry
+	let mut metadata = Metadata::new();
+	metadata.set_tag(ExifTag::Artist("Jane Doe".to_string()));
+	metadata.set_tag(ExifTag::ImageDescription("Hello World!".to_string()));
+	let mut encoded = metadata.encode();
+	let format_position = 8 + 2 + 12 + 2;
+	encoded[format_position..format_position+2].copy_from_slice(&[0xff, 0xff]);
+
+	let error = Metadata::decode(&encoded).err().unwrap();
+	let context = ParseErrorContext::from_io_error(&error).unwrap();
+	assert_eq!(context.message(), "Illegal format value!");
+	assert_eq!(context.ifd_name(), Some("IFD0"));
+	assert_eq!(context.entry_number(), Some(1));
+	assert!(error.to_string().contains("IFD IFD0, entry 1"));
+
+	// Corrupted CRC of the zTXt chunk, which is placed right after IHDR
+	let mut png = std::fs::read("tests/sample2.png")?;
+	metadata.write_to_vec(&mut png, FileExtension::PNG { as_zTXt_chunk: true })?;
+	let ihdr_end = 8 + 25;
+	let ztxt_length = u32::from_be_bytes(png[ihdr_end..ihdr_end+4].try_into().unwrap()) as usize;
+	png[ihdr_end + 8 + ztxt_length] ^= 0xff;
+
+	let error = Metadata::new_from_vec(&png, FileExtension::PNG { as_zTXt_chunk: true }).err().unwrap();
+	let context = ParseErrorContext::from_io_error(&error).unwrap();
+	assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+	assert_eq!(context.chunk_name(), Some("zTXt"));
+	assert_eq!(context.offset_in_file(), Some(ihdr_end as u64));
+
+	// Truncated JPEG
+	let jpg = std::fs::read("tests/sample2.jpg")?;
+	let error = Metadata::clear_metadata(&mut jpg[..100].to_vec(), FileExtension::JPEG).unwrap_err();
+	assert!(ParseErrorContext::from_io_error(&error).unwrap().offset_in_file().is_some());
+
+	Ok(())
+}
+
+#[test]
+fn
+read_warnings()
+-> Result<(), std::io::Error>
+{
+	let mut metadata = Metadata::new();
+	metadata.set_tag(ExifTag::Artist("Jane Doe".to_string()));
+
+	// EXIF chunk in a WebP file without the VP8X flag being set
+	let mut webp = std::fs::read("tests/sample2_extended.webp")?;
+	metadata.write_to_vec(&mut webp, FileExtension::WEBP)?;
+	assert!(Metadata::new_from_vec(&webp, FileExtension::WEBP)?.warnings().is_empty());
+	webp[20] &= !0x08;
+
+	let read_back = Metadata::new_from_vec(&webp, FileExtension::WEBP)?;
+	assert_eq!(read_back.get_tag(&ExifTag::Artist(String::new())), Some(&ExifTag::Artist("Jane Doe".to_string())));
+	assert_eq!(read_back.warnings().len(), 1);
+	assert_eq!(read_back.warnings()[0].chunk_name(), Some("EXIF"));
+
+	// Two APP1 segments with EXIF data in a JPEG file
+	let mut jpg = std::fs::read("tests/sample2.jpg")?;
+	metadata.write_to_vec(&mut jpg, FileExtension::JPEG)?;
+	let app1_length = u16::from_be_bytes([jpg[4], jpg[5]]) as usize;
+	let app1 = jpg[2..4+app1_length].to_vec();
+	jpg.splice(2..2, app1);
+
+	let read_back = Metadata::new_from_vec(&jpg, FileExtension::JPEG)?;
+	assert_eq!(read_back.warnings().len(), 1);
+	assert_eq!(read_back.warnings()[0].chunk_name(), Some("APP1"));
+	assert_eq!(read_back.warnings()[0].offset_in_file(), Some(4 + app1_length as u64));
+
+	// EXIF data that can't be decoded due to an invalid format of the first
+	// IFD0 entry, resulting in an empty object
+	let mut jpg = std::fs::read("tests/sample2.jpg")?;
+	metadata.write_to_vec(&mut jpg, FileExtension::JPEG)?;
+	let format_position = 2 + 4 + 6 + 8 + 2 + 2;
+	jpg[format_position..format_position+2].copy_from_slice(&[0xff, 0xff]);
+
+	let read_back = Metadata::new_from_vec(&jpg, FileExtension::JPEG)?;
+	assert!(read_back.data().is_empty());
+	assert_eq!(read_back.warnings().len(), 1);
+	assert_eq!(read_back.warnings()[0].ifd_name(), Some("IFD0"));
+	assert_eq!(read_back.warnings()[0].entry_number(), Some(0));
+
+	// A JPEG file ending within the APP1 segment can't be read at all, while
+	// a file without EXIF data is no issue
+	let mut jpg = std::fs::read("tests/sample2.jpg")?;
+	metadata.write_to_vec(&mut jpg, FileExtension::JPEG)?;
+	let app1_length = u16::from_be_bytes([jpg[4], jpg[5]]) as usize;
+	jpg.truncate(2 + app1_length);
+
+	let read_back = Metadata::new_from_vec(&jpg, FileExtension::JPEG)?;
+	assert!(read_back.data().is_empty());
+	assert_eq!(read_back.warnings().len(), 1);
+	assert!(Metadata::new_from_seekable_reader(&mut std::io::Cursor::new(&jpg), FileExtension::JPEG, &ReadOptions::new().lenient(false)).is_err());
+
+	let mut jpg = std::fs::read("tests/sample2.jpg")?;
+	Metadata::clear_metadata(&mut jpg, FileExtension::JPEG)?;
+	assert!(Metadata::new_from_seekable_reader(&mut std::io::Cursor::new(&jpg), FileExtension::JPEG, &ReadOptions::new().lenient(false))?.warnings().is_empty());
+
+	Ok(())
+}
+
+#[test]
+fn
+get_tag_by_name()
+-> Result<(), std::io::Error>
+{
+	let mut metadata = Metadata::new();
+	metadata.set_tag(ExifTag::FNumber(vec![18, 10]));
+
+	assert_eq!(metadata.get_tag_by_name("FNumber"), metadata.get_tag(&ExifTag::FNumber(Vec::new())));
+	assert!(metadata.get_tag_by_name("f number").is_some());
+	assert!(metadata.get_tag_by_name("Aperture").is_some());
+	assert!(metadata.get_tag_by_name("ExposureTime").is_none());
+	assert!(metadata.get_tag_by_name("Blub").is_none());
+
+	Ok(())
+}
+
+#[test]
+fn
+timelapse_numbering()
+-> Result<(), std::io::Error>
+{
+	let paths = [
+		Path::new("tests/sample2_timelapse_0_copy.jpg"),
+		Path::new("tests/sample2_timelapse_1_copy.jpg"),
+		Path::new("tests/sample2_timelapse_2_copy.jpg"),
+	];
+	let capture_times = [("2024:01:31 18:05:10", "0"), ("2024:01:31 18:05:00", "5"), ("2024:01:31 18:05:00", "0")];
+	for (path, (date, sub_seconds)) in paths.iter().zip(capture_times)
+	{
+		copy("tests/sample2.jpg", path)?;
+		let mut metadata = Metadata::new();
+		metadata.set_tag(ExifTag::DateTimeOriginal(date.to_string()));
+		metadata.set_tag(ExifTag::SubSecTimeOriginal(sub_seconds.to_string()));
+		metadata.write_to_file(path)?;
+	}
+
+	let numbers = stamp_timelapse(&paths, 100);
+	let image_numbers = paths.iter()
+		.map(|path| Metadata::new_from_path(path).ok()?.get_tag(&ExifTag::ImageNumber(Vec::new())).cloned())
+		.collect::<Vec<Option<ExifTag>>>();
+
+	// A frame without a timestamp
+	Metadata::new().write_to_file(paths[0])?;
+	let error = stamp_timelapse(&paths, 1).err().unwrap();
+	let unchanged = Metadata::new_from_path(paths[1])?.get_tag(&ExifTag::ImageNumber(Vec::new())).cloned();
+
+	for path in paths
+	{
+		remove_file(path)?;
+	}
+
+	assert_eq!(numbers?, vec![102, 101, 100]);
+	assert_eq!(image_numbers, vec![
+		Some(ExifTag::ImageNumber(vec![102])),
+		Some(ExifTag::ImageNumber(vec![101])),
+		Some(ExifTag::ImageNumber(vec![100])),
+	]);
+	assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+	assert_eq!(unchanged, Some(ExifTag::ImageNumber(vec![101])));
+
+	Ok(())
+}
+
+/// Builds Olympus maker notes with the drive mode of a shot taken using
+/// focus bracketing
+fn
+olympus_focus_bracket_maker_note
+(
+	shot_number: u16
+)
+-> Vec<u8>
+{
+	let mut maker_note = b"OLYMPUS\0II\x03\0".to_vec();
+	for value in [1u16, 0x2020, 0x000d]
+	{
+		maker_note.extend(value.to_le_bytes());
+	}
+	maker_note.extend(1u32.to_le_bytes());
+	maker_note.extend(30u32.to_le_bytes());                                     // Camera settings sub-IFD
+	maker_note.extend([0x00; 4]);
+
+	for value in [1u16, 0x0600, 0x0003]
+	{
+		maker_note.extend(value.to_le_bytes());
+	}
+	maker_note.extend(3u32.to_le_bytes());
+	maker_note.extend(48u32.to_le_bytes());
+	maker_note.extend([0x00; 4]);
+	for value in [5u16, shot_number, 0x0040]
+	{
+		maker_note.extend(value.to_le_bytes());
+	}
+	return maker_note;
+}
+
+#[test]
+fn
+focus_stack_grouping()
+-> Result<(), std::io::Error>
+{
+	let paths = [
+		Path::new("tests/sample2_stack_0_copy.jpg"),
+		Path::new("tests/sample2_stack_1_copy.jpg"),
+		Path::new("tests/sample2_stack_2_copy.jpg"),
+		Path::new("tests/sample2_stack_3_copy.jpg"),
+	];
+	let shots = [(Some(2), "2024:01:31 18:05:01"), (Some(1), "2024:01:31 18:05:00"), (None, "2024:01:31 18:05:02"), (Some(1), "2024:01:31 18:06:00")];
+	for (path, (shot_number, date)) in paths.iter().zip(shots)
+	{
+		copy("tests/sample2.jpg", path)?;
+		let mut metadata = Metadata::new();
+		metadata.set_tag(ExifTag::Make("OLYMPUS CORPORATION".to_string()));
+		metadata.set_tag(ExifTag::DateTimeOriginal(date.to_string()));
+		if let Some(shot_number) = shot_number
+		{
+			metadata.set_tag(ExifTag::MakerNote(olympus_focus_bracket_maker_note(shot_number)));
+		}
+		metadata.write_to_file(path)?;
+	}
+
+	let memberships = stamp_focus_stacks(&paths);
+	let stored = paths.iter()
+		.map(|path| read_stack_from_vec(&std::fs::read(path)?))
+		.collect::<Result<Vec<_>, std::io::Error>>();
+	let stamped_again = stamp_focus_stacks(&paths);
+
+	for path in paths
+	{
+		remove_file(path)?;
+	}
+
+	let memberships = memberships?;
+	assert_eq!(stored?, memberships);
+	assert_eq!(stamped_again?, memberships);
+
+	let first = memberships[1].clone().unwrap();
+	let second = memberships[0].clone().unwrap();
+	assert_eq!((first.index, first.count, second.index, second.count), (1, 2, 2, 2));
+	assert_eq!(first.id, second.id);
+	assert_eq!(memberships[2], None);
+	assert_eq!(memberships[3].as_ref().map(|membership| membership.count), Some(1));
+	assert_ne!(memberships[3].as_ref().map(|membership| &membership.id), Some(&first.id));
+
+	Ok(())
+}
+
+#[test]
+fn
+exposure_bracket_detection()
+-> Result<(), std::io::Error>
+{
+	let paths = [
+		Path::new("tests/sample2_bracket_0_copy.jpg"),
+		Path::new("tests/sample2_bracket_1_copy.jpg"),
+		Path::new("tests/sample2_bracket_2_copy.jpg"),
+		Path::new("tests/sample2_bracket_3_copy.jpg"),
+	];
+	let shots = [("2024:01:31 18:05:00", "30", -2), ("2024:01:31 18:05:00", "0", 0), ("2024:01:31 18:05:00", "60", 2), ("2024:01:31 18:07:00", "0", 0)];
+	for (path, (date, sub_seconds, exposure_bias)) in paths.iter().zip(shots)
+	{
+		copy("tests/sample2.jpg", path)?;
+		let mut metadata = Metadata::new();
+		metadata.set_tag(ExifTag::DateTimeOriginal(date.to_string()));
+		metadata.set_tag(ExifTag::SubSecTimeOriginal(sub_seconds.to_string()));
+		metadata.set_tag(ExifTag::ExposureCompensation(vec![exposure_bias, 1]));
+		metadata.write_to_file(path)?;
+	}
+
+	let brackets = detect_exposure_brackets(&paths, std::time::Duration::from_secs(1));
+	for path in paths
+	{
+		remove_file(path)?;
+	}
+
+	let brackets = brackets?;
+	assert_eq!(brackets.len(), 1);
+	assert_eq!(brackets[0].frames, vec![1, 0, 2]);
+	assert_eq!(brackets[0].exposure_biases, vec![Some(0.0), Some(-2.0), Some(2.0)]);
+	assert_eq!(brackets[0].base_frame(), 1);
+
+	Ok(())
+}
+
+#[test]
+fn
+astro_acquisition_keywords()
+-> Result<(), std::io::Error>
+{
+	let keywords = AcquisitionKeywords::new()
+		.keyword(astro::SENSOR_TEMPERATURE, KeywordValue::Float(-10.5))
+		.keyword(astro::GAIN,               KeywordValue::Integer(120))
+		.keyword(astro::FRAME_COUNT,        KeywordValue::Integer(64))
+		.keyword(astro::OBJECT,             KeywordValue::Text("M31".to_string()));
+
+	// UserComment
+	let mut file_buffer = std::fs::read("tests/sample2.jpg")?;
+	let mut metadata = get_test_metadata()?;
+	metadata.set_acquisition_keywords(&keywords)?;
+	metadata.write_to_vec(&mut file_buffer, FileExtension::JPEG)?;
+	let read_back = Metadata::new_from_vec(&file_buffer, FileExtension::JPEG)?;
+	assert_eq!(read_back.acquisition_keywords(), Some(keywords.clone()));
+	assert_eq!(read_back.sensor_temperature(), None);
+
+	let invalid = AcquisitionKeywords::new().keyword("TOO-LONG-NAME", KeywordValue::Logical(true));
+	assert!(metadata.set_acquisition_keywords(&invalid).is_err());
+	assert_eq!(metadata.acquisition_keywords(), Some(keywords.clone()));
+
+	// XMP
+	astro::write_keywords_to_vec(&mut file_buffer, &keywords)?;
+	assert_eq!(astro::read_keywords_from_vec(&file_buffer)?, Some(keywords));
+	assert_eq!(astro::read_keywords_from_vec(&std::fs::read("tests/sample2.jpg")?)?, None);
+
+	Ok(())
+}
+
+#[test]
+fn
+scan_profile()
+-> Result<(), std::io::Error>
+{
+	let oecf = Oecf
+	{
+		column_names: vec!["Log exposure".to_string(), "Y".to_string()],
+		rows:         vec![vec![(-3, 1), (12, 1)], vec![(0, 1), (4095, 1)]],
+	};
+	let film = Film::new(FilmType::BlackAndWhiteNegative).stock("Ilford HP5 Plus");
+	let profile = ScanProfile::archive("City Archive")
+		.scanner("Nikon", "LS-5000")
+		.document_name("Roll 12")
+		.page(3, 36)
+		.oecf(oecf.clone())
+		.film(film.clone());
+
+	let jpg_path = Path::new("tests/sample2_scan_copy.jpg");
+	let webp_path = Path::new("tests/sample2_scan_copy.webp");
+	copy("tests/sample2.jpg", jpg_path)?;
+	copy("tests/read_sample.webp", webp_path)?;
+	let result = profile.write_to_file(jpg_path);
+	let file_buffer = std::fs::read(jpg_path)?;
+	let webp_result = profile.write_to_file(webp_path);
+	let webp_unchanged = std::fs::read(webp_path)? == std::fs::read("tests/read_sample.webp")?;
+	remove_file(jpg_path)?;
+	remove_file(webp_path)?;
+
+	result?;
+	let metadata = Metadata::new_from_vec(&file_buffer, FileExtension::JPEG)?;
+	assert_eq!(metadata.get_tag(&ExifTag::Model(String::new())),        Some(&ExifTag::Model("LS-5000".to_string())));
+	assert_eq!(metadata.get_tag(&ExifTag::DocumentName(String::new())), Some(&ExifTag::DocumentName("Roll 12".to_string())));
+	assert_eq!(metadata.get_tag(&ExifTag::PageNumber(Vec::new())),      Some(&ExifTag::PageNumber(vec![3, 36])));
+	assert_eq!(metadata.get_tag(&ExifTag::XResolution(Vec::new())),     Some(&ExifTag::XResolution(vec![4000, 1])));
+	assert_eq!(metadata.oecf(), Some(oecf));
+	assert_eq!(read_film_from_vec(&file_buffer)?, Some(film));
+
+	// Film is only supported for JPEG and PNG files
+	assert_eq!(webp_result.err().unwrap().kind(), std::io::ErrorKind::Unsupported);
+	assert!(webp_unchanged);
+
+	Ok(())
+}
+
+#[test]
+fn
+document_page_fields()
+-> Result<(), std::io::Error>
+{
+	let page = DocumentPage::new()
+		.document_name("Annual Report 1987")
+		.page_name("Balance sheet")
+		.page_number(11, 40)
+		.page_label("12")
+		.volume("3");
+
+	// PNG, keeping the existing EXIF data
+	let mut file_buffer = std::fs::read("tests/sample2.png")?;
+	page.write_to_vec(&mut file_buffer)?;
+	assert_eq!(DocumentPage::read_from_vec(&file_buffer)?, page);
+	let metadata = Metadata::new_from_vec(&file_buffer, FileExtension::PNG { as_zTXt_chunk: true })?;
+	assert_eq!(
+		metadata.get_tag(&ExifTag::ImageDescription(String::new())),
+		Metadata::new_from_path(Path::new("tests/sample2.png"))?.get_tag(&ExifTag::ImageDescription(String::new()))
+	);
+
+	// Fields that are not set are kept
+	DocumentPage::new().page_number(12, 40).page_label("13").write_to_vec(&mut file_buffer)?;
+	let next_page = DocumentPage::read_from_vec(&file_buffer)?;
+	assert_eq!(next_page.document_name, page.document_name);
+	assert_eq!(next_page.page_number,   Some((12, 40)));
+	assert_eq!(next_page.page_label,    Some("13".to_string()));
+
+	// JPEG
+	let mut file_buffer = std::fs::read("tests/sample2.jpg")?;
+	page.write_to_vec(&mut file_buffer)?;
+	assert_eq!(DocumentPage::read_from_vec(&file_buffer)?, page);
+
+	let mut webp_buffer = std::fs::read("tests/read_sample.webp")?;
+	assert_eq!(page.write_to_vec(&mut webp_buffer).err().unwrap().kind(), std::io::ErrorKind::Unsupported);
+	assert!(DocumentPage::new().page_number(40, 40).write_to_vec(&mut file_buffer).is_err());
+	assert_eq!(DocumentPage::read_from_vec(&file_buffer)?, page);
+
+	Ok(())
+}
+
+#[test]
+fn
+shell_properties_visible()
+-> Result<(), std::io::Error>
+{
+	// Every writable format, as seen by both file managers
+	let properties = ShellProperties::new()
+		.title("Sunset at the lake")
+		.author("Jane Doe")
+		.keywords(&["sunset", "lake"])
+		.comment("Taken from the pier")
+		.copyright("CC BY 4.0")
+		.rating(4)
+		.date_taken("2024:05:06 07:08:09");
+
+	let images = [
+		("tests/sample2.jpg",            FileExtension::JPEG),
+		("tests/sample2.png",            FileExtension::PNG { as_zTXt_chunk: true }),
+		("tests/sample2_extended.webp",  FileExtension::WEBP),
+		("tests/sample.tif",             FileExtension::TIFF),
+		("tests/sample_big_endian.tiff", FileExtension::TIFF),
+		("tests/sample.heic",            FileExtension::HEIF),
+		("tests/sample.avif",            FileExtension::AVIF),
+		("tests/sample.gif",             FileExtension::GIF),
+	];
+	for (path, file_type) in images
+	{
+		let mut file_buffer = std::fs::read(path)?;
+		properties.write_to_vec(&mut file_buffer)?;
+
+		for file_manager in [FileManager::Finder, FileManager::Explorer]
+		{
+			let expected = match (shows_metadata(file_manager, file_type), file_manager)
+			{
+				(false, _)                    => ShellProperties::new(),
+				(true, FileManager::Explorer) => properties.clone(),
+				(true, FileManager::Finder)   => ShellProperties
+				{
+					keywords: Vec::new(),
+					comment:  None,
+					rating:   properties.rating.filter(|_| file_type.capabilities().xmp),
+					..properties.clone()
+				},
+			};
+			assert_eq!(ShellProperties::read_from_vec(&file_buffer, file_manager)?, expected, "{} in {:?}", path, file_manager);
+		}
+	}
+
+	// Explorer falls back to XPTitle, but neither file manager reads the
+	// zTXt chunk little_exif writes to PNG files by default
+	let mut metadata = Metadata::new();
+	metadata.set_tag(ExifTag::XPTitle(encode_xp_string("Only in XPTitle")));
+	let mut png = std::fs::read("tests/sample2.png")?;
+	metadata.write_to_vec(&mut png, FileExtension::PNG { as_zTXt_chunk: true })?;
+	assert_eq!(ShellProperties::read_from_vec(&png, FileManager::Explorer)?.title, None);
+	metadata.write_to_vec_with_options(&mut png, FileExtension::PNG { as_zTXt_chunk: false }, &WriteOptions::new().png_exif_chunk(PngExifChunk::eXIf))?;
+	assert_eq!(ShellProperties::read_from_vec(&png, FileManager::Explorer)?.title.as_deref(), Some("Only in XPTitle"));
+
+	// Values the file managers can't show are rejected
+	let mut file_buffer = std::fs::read("tests/sample2.jpg")?;
+	let original = file_buffer.clone();
+	for invalid in [ShellProperties::new().rating(6), ShellProperties::new().date_taken("2024-05-06 07:08"), ShellProperties::new().keywords(&["sunset;lake"])]
+	{
+		assert_eq!(invalid.write_to_vec(&mut file_buffer).err().unwrap().kind(), std::io::ErrorKind::InvalidInput);
+	}
+	assert_eq!(file_buffer, original);
+
+	Ok(())
+}
+
+#[test]
+fn
+normalize_orientation_batch()
+-> Result<(), std::io::Error>
+{
+	use little_exif::orientation::normalize_orientation;
+	use little_exif::orientation::OrientationFix;
+
+	let jpg_path = Path::new("tests/sample2_orientation_copy.jpg");
+	let png_path = Path::new("tests/sample2_orientation_copy.png");
+	copy("tests/sample2.jpg", jpg_path)?;
+	copy("tests/sample2.png", png_path)?;
+
+	// Invalid value in the JPEG, a rotation of the square PNG by 90°
+	let mut metadata = Metadata::new_from_path(jpg_path)?;
+	metadata.set_tag(ExifTag::Orientation(vec![0]));
+	metadata.write_to_file(jpg_path)?;
+
+	let mut metadata = Metadata::new_from_path(png_path)?;
+	metadata.set_tag(ExifTag::Orientation(vec![6]));
+	metadata.set_tag(ExifTag::ExifImageWidth(vec![1000]));
+	metadata.set_tag(ExifTag::ExifImageHeight(vec![1000]));
+	metadata.write_to_file(png_path)?;
+	let png_before = std::fs::read(png_path)?;
+
+	let results = normalize_orientation(&[jpg_path, png_path, Path::new("tests/sample1.txt")]);
+	assert_eq!(results[0].as_ref().ok(), Some(&OrientationFix::Normalized));
+	assert_eq!(results[1].as_ref().ok(), Some(&OrientationFix::NeedsPixelRotation(6)));
+	assert!(results[2].is_err());
+
+	assert_eq!(Metadata::new_from_path(jpg_path)?.get_tag(&ExifTag::Orientation(Vec::new())), Some(&ExifTag::Orientation(vec![1])));
+	assert_eq!(std::fs::read(png_path)?, png_before);
+
+	// Nothing left to do
+	let results = normalize_orientation(&[jpg_path]);
+	assert_eq!(results[0].as_ref().ok(), Some(&OrientationFix::Unchanged));
+
+	remove_file(jpg_path)?;
+	remove_file(png_path)?;
+	Ok(())
+}
+
+#[cfg(feature = "fits")]
+#[test]
+fn
+fits_header_bridge()
+-> Result<(), std::io::Error>
+{
+	use little_exif::fits::fits_to_metadata;
+	use little_exif::fits::metadata_to_fits;
+	use little_exif::fits::parse_fits_header;
+
+	let cards = [
+		"SIMPLE  =                    T",
+		"BITPIX  =                   16",
+		"NAXIS   =                    2",
+		"NAXIS1  =                 4144",
+		"DATE-OBS= '2024-01-31T18:05:00.250' / UTC",
+		"EXPTIME =                300.0 / [s]",
+		"INSTRUME= 'ZWO ASI294MC Pro'",
+		"CCD-TEMP=                -10.0",
+		"GAIN    =                  120",
+		"END",
+	];
+	let mut fits = cards.iter().flat_map(|card| format!("{:<80}", card).into_bytes()).collect::<Vec<u8>>();
+	fits.resize(2880, b' ');
+
+	let keywords = parse_fits_header(&fits)?;
+	let mut metadata = Metadata::new();
+	fits_to_metadata(&keywords, &mut metadata)?;
+
+	assert_eq!(metadata.get_tag(&ExifTag::DateTimeOriginal(String::new())), Some(&ExifTag::DateTimeOriginal("2024:01:31 18:05:00".to_string())));
+	assert_eq!(metadata.get_tag(&ExifTag::ExposureTime(Vec::new())),        Some(&ExifTag::ExposureTime(vec![300, 1])));
+	assert_eq!(metadata.get_tag(&ExifTag::Model(String::new())),            Some(&ExifTag::Model("ZWO ASI294MC Pro".to_string())));
+
+	let acquisition_keywords = metadata.acquisition_keywords().unwrap();
+	assert_eq!(acquisition_keywords.keywords().iter().map(|(name, _)| name.as_str()).collect::<Vec<&str>>(), vec!["CCD-TEMP", "GAIN"]);
+
+	// Back to FITS, with the capture time in a different time zone
+	metadata.set_tag(ExifTag::DateTimeOriginal("2024:01:31 19:05:00".to_string()));
+	metadata.set_tag(ExifTag::OffsetTimeOriginal("+01:00".to_string()));
+	let round_trip = metadata_to_fits(&metadata);
+	for name in ["DATE-OBS", "EXPTIME", "INSTRUME", "CCD-TEMP", "GAIN"]
+	{
+		assert_eq!(round_trip.get(name), keywords.get(name), "{}", name);
+	}
+	assert_eq!(round_trip.get("NAXIS1"), None);
+
+	assert!(parse_fits_header(&fits[..720]).is_err());
+	assert!(parse_fits_header(b"GIF89a").is_err());
+
+	Ok(())
+}
+
+#[test]
+fn
+concurrent_readers_and_writer()
+-> Result<(), std::io::Error>
+{
+	fn assert_send_sync<T: Send + Sync>() {}
+	assert_send_sync::<Metadata>();
+	assert_send_sync::<WriteOptions>();
+
+	let path = Path::new("tests/sample2_concurrent_read_copy.jpg");
+	copy("tests/sample2.jpg", path)?;
+	let options = WriteOptions::new().safe_write(true);
+
+	let mut metadata = Metadata::new_from_path(path)?;
+	metadata.set_tag(ExifTag::ImageDescription("Version 0".to_string()));
+	metadata.write_to_file_with_options(path, &options)?;
+
+	let writer_finished = std::sync::atomic::AtomicBool::new(false);
+	std::thread::scope(|scope| {
+		let writer = scope.spawn(|| {
+			let result = (1..=50).try_for_each(|version| {
+				metadata.set_tag(ExifTag::ImageDescription(format!("Version {}", version)));
+				metadata.write_to_file_with_options(path, &options)
+			});
+			writer_finished.store(true, std::sync::atomic::Ordering::Relaxed);
+			result
+		});
+
+		let readers = (0..8).map(|_| scope.spawn(|| {
+			while !writer_finished.load(std::sync::atomic::Ordering::Relaxed)
+			{
+				// Each read sees a complete version of the file
+				let description = Metadata::new_from_path(path)
+					.map(|metadata| metadata.get_tag(&ExifTag::ImageDescription(String::new())).cloned());
+				match description
+				{
+					Ok(Some(ExifTag::ImageDescription(description))) => assert!(description.starts_with("Version "), "{}", description),
+					Ok(tag)                                          => panic!("Unexpected tag {:?}", tag),
+					Err(error)                                       => panic!("Read failed: {}", error),
+				}
+			}
+		})).collect::<Vec<_>>();
+
+		for reader in readers
+		{
+			reader.join().unwrap();
+		}
+		writer.join().unwrap()
+	})?;
+
+	assert_eq!(
+		Metadata::new_from_path(path)?.get_tag(&ExifTag::ImageDescription(String::new())),
+		Some(&ExifTag::ImageDescription("Version 50".to_string()))
+	);
+
+	remove_file(path)?;
+	Ok(())
+}
+
+#[test]
+fn
+concurrent_writers_last_wins()
+-> Result<(), std::io::Error>
+{
+	let path = Path::new("tests/sample2_concurrent_write_copy.png");
+	let backup_path = Path::new("tests/sample2_concurrent_write_copy.png_original");
+	copy("tests/sample2.png", path)?;
+	let _ = remove_file(backup_path);
+	let original = std::fs::read(path)?;
+
+	let options = WriteOptions::new().safe_write(true).backup(true);
+	let descriptions = (0..6).map(|writer| format!("Writer {}", writer)).collect::<Vec<String>>();
+
+	std::thread::scope(|scope| {
+		let writers = descriptions.iter().map(|description| scope.spawn(|| {
+			let mut metadata = Metadata::new();
+			metadata.set_tag(ExifTag::ImageDescription(description.clone()));
+			for _ in 0..10
+			{
+				metadata.write_to_file_with_options(path, &options)?;
+			}
+			Ok::<(), std::io::Error>(())
+		})).collect::<Vec<_>>();
+
+		writers.into_iter().try_for_each(|writer| writer.join().unwrap())
+	})?;
+
+	// One of the writes, neither mixed nor lost entirely
+	match Metadata::new_from_path(path)?.get_tag(&ExifTag::ImageDescription(String::new()))
+	{
+		Some(ExifTag::ImageDescription(description)) => assert!(descriptions.contains(description)),
+		tag                                          => panic!("Unexpected tag {:?}", tag),
+	}
+
+	// The backup holds the file before any of the writes
+	assert_eq!(std::fs::read(backup_path)?, original);
+
+	// No temporary files are left behind
+	let leftovers = std::fs::read_dir("tests")?
+		.filter_map(|entry| entry.ok())
+		.filter(|entry| entry.file_name().to_string_lossy().starts_with(".sample2_concurrent_write_copy.png.little_exif-"))
+		.count();
+	assert_eq!(leftovers, 0);
+
+	remove_file(path)?;
+	remove_file(backup_path)?;
+	Ok(())
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn
+tracing_spans()
+-> Result<(), std::io::Error>
+{
+	use std::sync::Arc;
+	use std::sync::Mutex;
+
+	// Collects the names of the spans and the recorded fields
+	struct
+	Recorder
+	{
+		lines: Arc<Mutex<Vec<String>>>,
+	}
+
+	struct
+	FieldVisitor<'a>
+	{
+		line: &'a mut String,
+	}
+
+	impl
+	tracing::field::Visit
+	for
+	FieldVisitor<'_>
+	{
+		fn
+		record_debug
+		(
+			&mut self,
+			field: &tracing::field::Field,
+			value: &dyn std::fmt::Debug
+		)
+		{
+			self.line.push_str(&format!(" {}={:?}", field.name(), value));
+		}
+	}
+
+	impl
+	tracing::Subscriber
+	for
+	Recorder
+	{
+		fn enabled(&self, _: &tracing::Metadata<'_>) -> bool { true }
+		fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+		fn event(&self, _: &tracing::Event<'_>) {}
+		fn enter(&self, _: &tracing::span::Id) {}
+		fn exit(&self, _: &tracing::span::Id) {}
+
+		fn
+		new_span
+		(
+			&self,
+			attributes: &tracing::span::Attributes<'_>
+		)
+		-> tracing::span::Id
+		{
+			let mut line = attributes.metadata().name().to_string();
+			attributes.record(&mut FieldVisitor { line: &mut line });
+			let mut lines = self.lines.lock().unwrap();
+			lines.push(line);
+			return tracing::span::Id::from_u64(lines.len() as u64);
+		}
+
+		fn
+		record
+		(
+			&self,
+			span:   &tracing::span::Id,
+			values: &tracing::span::Record<'_>
+		)
+		{
+			let mut lines = self.lines.lock().unwrap();
+			values.record(&mut FieldVisitor { line: &mut lines[span.into_u64() as usize - 1] });
+		}
+	}
+
+	let lines = Arc::new(Mutex::new(Vec::new()));
+	let recorder = Recorder { lines: lines.clone() };
+	tracing::subscriber::with_default(recorder, || {
+		let mut file_buffer = std::fs::read("tests/sample2.jpg")?;
+		let mut metadata = Metadata::new_from_vec(&file_buffer, FileExtension::JPEG)?;
+		metadata.set_tag(ExifTag::Artist("Jane Doe".to_string()));
+		metadata.write_to_vec(&mut file_buffer, FileExtension::JPEG)
+	})?;
+
+	let lines = lines.lock().unwrap();
+	let find = |prefix: &str| lines.iter().find(|line| line.starts_with(prefix)).cloned().unwrap_or_default();
+	let file_size = std::fs::read("tests/sample2.jpg")?.len();
+	assert!(find("read").contains("format=\"JPEG\""),                     "{:?}", lines);
+	assert!(find("parse").contains("segments="),                          "{:?}", lines);
+	assert!(find("decode").contains("tags="),                             "{:?}", lines);
+	assert!(find("encode").contains("size="),                             "{:?}", lines);
+	assert!(find("write").contains(&format!("file_size={}", file_size)),  "{:?}", lines);
+
+	Ok(())
+}
+
+#[test]
+fn
+fuzz_regressions()
+{
+	for (path, data) in fuzz::fixtures()
+	{
+		assert_eq!(fuzz::find_panic(&data), None, "{}", path.display());
+	}
+}
+
+#[test]
+fn
+fuzz_smoke()
+{
+	// Deterministic, so a failure here can be reproduced and turned into a
+	// fixture with `cargo run --example fuzz -- 300 1`
+	assert_eq!(fuzz::fuzz(300, 1, &mut |_| ()), Vec::new());
+
+	// The seeds reach the maker note accessors, including the plist decoder
+	let maker_notes = fuzz::seeds().iter()
+		.filter_map(|seed| Metadata::decode(seed).ok()?.maker_note())
+		.collect::<Vec<_>>();
+	assert!(maker_notes.iter().any(|maker_note| maker_note.shutter_count() == Some(12345)));
+	assert!(maker_notes.iter().any(|maker_note| maker_note.apple_run_time().is_some()));
+
+	// Minimizing keeps what the predicate depends on, in order
+	let minimized = fuzz::minimize(b"little_exif fuzzing", |candidate| {
+		candidate.windows(2).any(|window| window == b"ex") && candidate.contains(&b'z')
+	});
+	assert_eq!(minimized, b"exz");
+}
+
+#[test]
+fn
+encode_values_exceeding_component_number()
+{
+	// Fuzzing found values longer than the predefined number of components
+	// of their tag making the encoder run out of memory
+	let mut metadata = Metadata::new();
+	metadata.set_tag(ExifTag::GPSLatitudeRef("North".to_string()));
+	metadata.set_tag(ExifTag::ISOSpeedLatitudezzz(vec![1, 2, 3]));
+
+	let decoded = Metadata::decode(&metadata.encode()).unwrap();
+	assert_eq!(decoded.get_tag(&ExifTag::GPSLatitudeRef(String::new())), Some(&ExifTag::GPSLatitudeRef("N".to_string())));
+	assert_eq!(decoded.get_tag(&ExifTag::ISOSpeedLatitudezzz(Vec::new())), Some(&ExifTag::ISOSpeedLatitudezzz(vec![1])));
+}
+
+#[test]
+fn
+webp_fourccs_are_case_sensitive()
+-> Result<(), std::io::Error>
+{
+	// Besides the usual chunks, the fixture has an "exif" chunk with data
+	// that isn't EXIF and an "xmp " chunk, followed by the actual EXIF chunk
+	let chunk_types = |file_buffer: &[u8]| {
+		let mut chunk_types = Vec::new();
+		let mut position = 12;
+		while position + 8 <= file_buffer.len()
+		{
+			let size = u32::from_le_bytes(file_buffer[position+4..position+8].try_into().unwrap()) as usize;
+			chunk_types.push(RiffChunkType::from_bytes(file_buffer[position..position+4].try_into().unwrap()));
+			position += 8 + size + size % 2;
+		}
+		chunk_types
+	};
+
+	let mut file_buffer = std::fs::read("tests/lowercase_chunks.webp")?;
+	let metadata = Metadata::new_from_vec(&file_buffer, FileExtension::WEBP)?;
+	assert_eq!(metadata.encode(), Metadata::new_from_path(Path::new("tests/read_sample.webp"))?.encode());
+
+	// Only the EXIF chunk gets replaced, the lowercase ones are unknown
+	// chunks that are kept as they are
+	let mut metadata = Metadata::new();
+	metadata.set_tag(ExifTag::ImageDescription("case sensitive".to_string()));
+	metadata.write_to_vec(&mut file_buffer, FileExtension::WEBP)?;
+	assert_eq!(chunk_types(&file_buffer), vec![
+		RiffChunkType::VP8X,
+		RiffChunkType::VP8,
+		RiffChunkType::EXIF,
+		RiffChunkType::Unknown(*b"exif"),
+		RiffChunkType::Unknown(*b"xmp "),
+	]);
+	assert!(file_buffer.windows(13).any(|window| window == b"not EXIF data"));
+	assert_eq!(
+		Metadata::new_from_vec(&file_buffer, FileExtension::WEBP)?.get_tag(&ExifTag::ImageDescription(String::new())),
+		Some(&ExifTag::ImageDescription("case sensitive".to_string()))
+	);
+
+	Ok(())
+}
+
+#[test]
+fn
+webp_exif_before_image_data()
+-> Result<(), std::io::Error>
+{
+	// The fixture is read_sample.webp with the EXIF chunk in front of the VP8
+	// chunk instead of after it
+	let mut file_buffer = std::fs::read("tests/exif_before_image_data.webp")?;
+	let metadata = Metadata::new_from_vec(&file_buffer, FileExtension::WEBP)?;
+	assert_eq!(metadata.encode(), Metadata::new_from_path(Path::new("tests/read_sample.webp"))?.encode());
+	assert_eq!(metadata.warnings().len(), 1);
+	assert!(metadata.warnings()[0].message().contains("before the image data"));
+
+	// Moving the chunk doesn't touch it
+	assert!(Metadata::normalize_metadata_position(&mut file_buffer, FileExtension::WEBP)?);
+	assert_eq!(file_buffer, std::fs::read("tests/read_sample.webp")?);
+	assert!(!Metadata::normalize_metadata_position(&mut file_buffer, FileExtension::WEBP)?);
+	assert!(Metadata::new_from_vec(&file_buffer, FileExtension::WEBP)?.warnings().is_empty());
+
+	let path = Path::new("tests/exif_before_image_data_copy.webp");
+	copy("tests/exif_before_image_data.webp", path)?;
+	let changed = Metadata::file_normalize_metadata_position(path);
+	let normalized = std::fs::read(path)?;
+	remove_file(path)?;
+	assert!(changed?);
+	assert_eq!(normalized, file_buffer);
+
+	let mut jpg_buffer = std::fs::read("tests/sample2.jpg")?;
+	let error = Metadata::normalize_metadata_position(&mut jpg_buffer, FileExtension::JPEG).err().unwrap();
+	assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+
+	Ok(())
+}
+
+#[test]
+fn
+read_and_write_tiff()
+-> Result<(), std::io::Error>
+{
+	// Both samples contain the same metadata, one with IFD0 in front of the
+	// image data and in little endian, the other one with IFD0 at the end
+	// and in big endian. The image structure (e.g. StripOffsets) is hidden
+	for path in ["tests/sample.tif", "tests/sample_big_endian.tiff"]
+	{
+		let metadata = Metadata::new_from_path(Path::new(path))?;
+		assert_eq!(metadata.data(), &vec![
+			ExifTag::ImageDescription("TIFF sample".to_string()),
+			ExifTag::XResolution(vec![72, 1]),
+			ExifTag::YResolution(vec![72, 1]),
+			ExifTag::ResolutionUnit(vec![2]),
+			ExifTag::Artist("Jane Doe".to_string()),
+			ExifTag::ExposureTime(vec![1, 250]),
+			ExifTag::ISO(vec![200]),
+		], "{}", path);
+	}
+
+	// Metadata in the other byte order gets converted, while the image
+	// structure of the file takes precedence over the one of the metadata
+	let mut file_buffer = std::fs::read("tests/sample_big_endian.tiff")?;
+	let mut metadata = Metadata::new_with_config(&Config::new().endian(Endian::Little));
+	metadata.set_tag(ExifTag::ImageWidth(vec![1234]));
+	metadata.set_tag(ExifTag::Artist("John Doe".to_string()));
+	metadata.set_tag(ExifTag::GPSLatitude(vec![48, 1, 12, 1, 3456, 100]));
+	metadata.write_to_vec(&mut file_buffer, FileExtension::TIFF)?;
+	assert_eq!(&file_buffer[0..2], b"MM");
+	assert_eq!(Metadata::new_from_vec(&file_buffer, FileExtension::TIFF)?.data(), &vec![
+		ExifTag::Artist("John Doe".to_string()),
+		ExifTag::GPSLatitude(vec![48, 1, 12, 1, 3456, 100]),
+	]);
+	assert_eq!(tiff_image_data(&file_buffer), tiff_image_data(&std::fs::read("tests/sample_big_endian.tiff")?));
+	assert!(Metadata::has_gps(Path::new("tests/sample.tif")).is_ok_and(|has_gps| !has_gps));
+
+	// Clearing keeps the image, which is left untouched if there's nothing
+	// to remove
+	Metadata::clear_metadata(&mut file_buffer, FileExtension::TIFF)?;
+	let cleared = file_buffer.clone();
+	assert!(Metadata::new_from_vec(&file_buffer, FileExtension::TIFF)?.data().is_empty());
+	Metadata::clear_metadata(&mut file_buffer, FileExtension::TIFF)?;
+	assert_eq!(file_buffer, cleared);
+	assert_eq!(tiff_image_data(&file_buffer), tiff_image_data(&std::fs::read("tests/sample_big_endian.tiff")?));
+
+	let error = Metadata::new_from_vec(&std::fs::read("tests/sample2.jpg")?, FileExtension::TIFF).err().unwrap();
+	assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+	Ok(())
+}
+
+#[test]
+fn
+read_and_write_pyramid_tiff()
+-> Result<(), std::io::Error>
+{
+	// IFD0 of the sample is a reduced-resolution overview, followed by the
+	// base layer with the metadata and a SubIFD holding a lower layer, and
+	// a label image
+	let original = std::fs::read("tests/sample_pyramid.tif")?;
+	assert_eq!(Metadata::new_from_vec(&original, FileExtension::TIFF)?.data(), &vec![
+		ExifTag::ImageDescription("Pyramid base".to_string()),
+		ExifTag::Artist("Jane Doe".to_string()),
+		ExifTag::ISO(vec![200]),
+	]);
+	assert_eq!(tiff_layers(&original).iter().map(|layer| layer.len()).collect::<Vec<_>>(), [4, 48, 6, 12]);
+
+	// Writing targets the base layer, the other layers stay where they are
+	let mut file_buffer = original.clone();
+	let mut metadata = Metadata::new();
+	metadata.set_tag(ExifTag::Artist("John Doe".to_string()));
+	metadata.set_tag(ExifTag::GPSLatitude(vec![48, 1, 12, 1, 3456, 100]));
+	metadata.write_to_vec(&mut file_buffer, FileExtension::TIFF)?;
+	assert_eq!(Metadata::new_from_vec(&file_buffer, FileExtension::TIFF)?.data(), metadata.data());
+	assert_eq!(file_buffer[..8], original[..8]);
+	assert_eq!(tiff_layers(&file_buffer), tiff_layers(&original));
+
+	// Clearing as well
+	Metadata::clear_metadata(&mut file_buffer, FileExtension::TIFF)?;
+	assert!(Metadata::new_from_vec(&file_buffer, FileExtension::TIFF)?.data().is_empty());
+	assert_eq!(tiff_layers(&file_buffer), tiff_layers(&original));
+
+	Ok(())
+}
+
+#[test]
+fn
+read_and_write_heif()
+-> Result<(), std::io::Error>
+{
+	// The EXIF item of the HEIF sample is located in the mdat box right after
+	// the image data, the one of the AVIF sample in the idat box within the
+	// meta box. Apart from that, the samples are the same
+	for (path, file_type) in [("tests/sample.heic", FileExtension::HEIF), ("tests/sample.avif", FileExtension::AVIF)]
+	{
+		let original = std::fs::read(path)?;
+		assert_eq!(Metadata::new_from_path(Path::new(path))?.data(), &vec![
+			ExifTag::Make("Phone".to_string()),
+			ExifTag::Model("X1".to_string()),
+			ExifTag::Orientation(vec![6]),
+			ExifTag::ISO(vec![100]),
+		], "{}", path);
+
+		// The previous EXIF data gets overwritten, the new one appended
+		let mut file_buffer = original.clone();
+		let mut metadata = Metadata::new();
+		metadata.set_tag(ExifTag::Artist("Jane Doe".to_string()));
+		metadata.set_tag(ExifTag::GPSLatitude(vec![48, 1, 12, 1, 3456, 100]));
+		metadata.write_to_vec(&mut file_buffer, file_type)?;
+		assert_eq!(Metadata::new_from_vec(&file_buffer, file_type)?.data(), metadata.data());
+		assert!(!file_buffer.windows(5).any(|window| window == b"Phone"));
+		assert_eq!(heif_image_data(&file_buffer), heif_image_data(&original));
+
+		// Clearing removes the item and its data, which is left untouched if
+		// there's nothing to remove
+		Metadata::clear_metadata(&mut file_buffer, file_type)?;
+		let cleared = file_buffer.clone();
+		assert!(cleared.len() < original.len());
+		assert!(!cleared.windows(4).any(|window| window == b"Exif" || window == b"cdsc"));
+		assert!(Metadata::new_from_vec(&cleared, file_type)?.data().is_empty());
+		Metadata::clear_metadata(&mut file_buffer, file_type)?;
+		assert_eq!(file_buffer, cleared);
+		assert_eq!(heif_image_data(&file_buffer), heif_image_data(&original));
+
+		// A new EXIF item gets added to files without one
+		metadata.write_to_vec(&mut file_buffer, file_type)?;
+		assert_eq!(Metadata::new_from_vec(&file_buffer, file_type)?.data(), metadata.data());
+		assert!(file_buffer.windows(4).any(|window| window == b"cdsc"));
+		assert_eq!(heif_image_data(&file_buffer), heif_image_data(&original));
+	}
+
+	let error = Metadata::new_from_vec(&std::fs::read("tests/sample2.jpg")?, FileExtension::HEIF).err().unwrap();
+	assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+	Ok(())
+}
+
+#[test]
+fn
+read_and_write_gif()
+-> Result<(), std::io::Error>
+{
+	// The sample contains a NETSCAPE2.0 extension for looping, followed by
+	// the EXIF and the XMP application extensions
+	let original = std::fs::read("tests/sample.gif")?;
+	assert_eq!(Metadata::new_from_path(Path::new("tests/sample.gif"))?.data(), &vec![
+		ExifTag::ImageDescription("GIF sample".to_string()),
+		ExifTag::Artist("Jane Doe".to_string()),
+	]);
+	assert!(read_xmp_from_vec(&original)?.unwrap().contains("dc:format=\"image/gif\""));
+
+	// Writing replaces the EXIF extension, keeping the XMP packet
+	let mut file_buffer = original.clone();
+	let mut metadata = Metadata::new();
+	metadata.set_tag(ExifTag::Artist("John Doe".to_string()));
+	metadata.set_tag(ExifTag::UserComment(vec![0x42; 600]));
+	metadata.write_to_vec(&mut file_buffer, FileExtension::GIF)?;
+	assert_eq!(Metadata::new_from_vec(&file_buffer, FileExtension::GIF)?.data(), metadata.data());
+	assert_eq!(file_buffer.windows(11).filter(|window| *window == b"EXIFDATAEXF").count(), 1);
+	assert!(file_buffer.windows(11).any(|window| window == b"NETSCAPE2.0"));
+	assert_eq!(read_xmp_from_vec(&file_buffer)?, read_xmp_from_vec(&original)?);
+	assert_eq!(gif_image_data(&file_buffer), gif_image_data(&original));
+
+	// Writing XMP keeps the EXIF data
+	let packet = read_xmp_from_vec(&file_buffer)?.unwrap().replace("image/gif", "image/x-gif");
+	write_xmp_to_vec(&mut file_buffer, &packet)?;
+	assert_eq!(read_xmp_from_vec(&file_buffer)?, Some(packet));
+	assert_eq!(Metadata::new_from_vec(&file_buffer, FileExtension::GIF)?.data(), metadata.data());
+	assert_eq!(gif_image_data(&file_buffer), gif_image_data(&original));
+
+	// Clearing only removes the EXIF data
+	Metadata::clear_metadata(&mut file_buffer, FileExtension::GIF)?;
+	assert!(Metadata::new_from_vec(&file_buffer, FileExtension::GIF)?.data().is_empty());
+	assert!(read_xmp_from_vec(&file_buffer)?.is_some());
+	assert_eq!(gif_image_data(&file_buffer), gif_image_data(&original));
+
+	// Files of version 87a get upgraded, as they don't support extensions
+	let mut gif87a = [b"GIF87a".as_slice(), &gif_image_data(&original)].concat();
+	gif87a.splice(6..6, original[6..19].to_vec());
+	metadata.write_to_vec(&mut gif87a, FileExtension::GIF)?;
+	assert!(gif87a.starts_with(b"GIF89a"));
+	assert_eq!(Metadata::new_from_vec(&gif87a, FileExtension::GIF)?.data(), metadata.data());
+
+	Ok(())
+}
+
+#[test]
+fn
+read_cr2()
+-> Result<(), std::io::Error>
+{
+	// IFD0 of the sample describes the preview and holds the metadata, IFD3
+	// the raw data
+	let path = Path::new("tests/sample.cr2");
+	let original = std::fs::read(path)?;
+	let metadata = Metadata::new_from_path(path)?;
+	assert_eq!(metadata.get_tag(&ExifTag::Make(String::new())), Some(&ExifTag::Make("Canon".to_string())));
+	assert_eq!(metadata.get_tag(&ExifTag::DateTimeOriginal(String::new())), Some(&ExifTag::DateTimeOriginal("2024:05:06 07:08:09".to_string())));
+	assert_eq!(metadata.get_tag(&ExifTag::ExposureTime(Vec::new())), Some(&ExifTag::ExposureTime(vec![1, 250])));
+	assert_eq!(metadata.get_tag(&ExifTag::SerialNumber(String::new())), Some(&ExifTag::SerialNumber("012345678901".to_string())));
+	assert!(metadata.get_tag(&ExifTag::StripOffsets(Vec::new())).is_none());
+
+	// Detected by its signature, not as TIFF
+	assert_eq!(Metadata::new_from_vec(&original, FileExtension::CR2)?.data(), metadata.data());
+	assert!(Metadata::new_from_vec(&std::fs::read("tests/sample.tif")?, FileExtension::CR2).is_err());
+
+	// Writing and clearing are not supported and leave the file untouched
+	let mut file_buffer = original.clone();
+	let error = metadata.write_to_vec(&mut file_buffer, FileExtension::CR2).err().unwrap();
+	assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+	let error = Metadata::clear_metadata(&mut file_buffer, FileExtension::CR2).err().unwrap();
+	assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+	assert_eq!(metadata.write_to_file(path).err().unwrap().kind(), std::io::ErrorKind::Unsupported);
+	assert_eq!(file_buffer, original);
+	assert_eq!(std::fs::read(path)?, original);
+
+	Ok(())
+}
+
+#[test]
+fn
+read_cr3()
+-> Result<(), std::io::Error>
+{
+	// The IFDs of the sample are stored in the CMT1 (IFD0), CMT2 (ExifIFD),
+	// CMT3 (maker notes) and CMT4 (GPS) boxes
+	let path = Path::new("tests/sample.cr3");
+	let original = std::fs::read(path)?;
+	let metadata = Metadata::new_from_path(path)?;
+	assert_eq!(metadata.get_tag(&ExifTag::Make(String::new())), Some(&ExifTag::Make("Canon".to_string())));
+	assert_eq!(metadata.get_tag(&ExifTag::Model(String::new())), Some(&ExifTag::Model("Canon EOS R5".to_string())));
+	assert_eq!(metadata.get_tag(&ExifTag::ExposureTime(Vec::new())), Some(&ExifTag::ExposureTime(vec![1, 250])));
+	assert_eq!(metadata.get_tag(&ExifTag::ISO(Vec::new())), Some(&ExifTag::ISO(vec![200])));
+	assert_eq!(metadata.get_tag(&ExifTag::SerialNumber(String::new())), Some(&ExifTag::SerialNumber("012345678901".to_string())));
+	assert_eq!(metadata.get_tag(&ExifTag::GPSLatitude(Vec::new())), Some(&ExifTag::GPSLatitude(vec![48, 1, 12, 1, 3456, 100])));
+
+	// The HEIF sample lacks the brand and the Canon boxes
+	assert_eq!(Metadata::new_from_vec(&original, FileExtension::CR3)?.data(), metadata.data());
+	assert!(Metadata::new_from_vec(&std::fs::read("tests/sample.heic")?, FileExtension::CR3).is_err());
+
+	// Writing and clearing are not supported and leave the file untouched
+	let mut file_buffer = original.clone();
+	let error = metadata.write_to_vec(&mut file_buffer, FileExtension::CR3).err().unwrap();
+	assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+	let error = Metadata::clear_metadata(&mut file_buffer, FileExtension::CR3).err().unwrap();
+	assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+	assert_eq!(file_buffer, original);
+
+	Ok(())
+}
+
+#[test]
+fn
+read_orf()
+-> Result<(), std::io::Error>
+{
+	// IFD0 of the sample describes the raw data and holds the metadata, the
+	// header starts with "IIRO" instead of "II*\0"
+	let path = Path::new("tests/sample.orf");
+	let original = std::fs::read(path)?;
+	let metadata = Metadata::new_from_path(path)?;
+	assert_eq!(metadata.get_tag(&ExifTag::Make(String::new())), Some(&ExifTag::Make("OLYMPUS CORPORATION".to_string())));
+	assert_eq!(metadata.get_tag(&ExifTag::DateTimeOriginal(String::new())), Some(&ExifTag::DateTimeOriginal("2024:05:06 07:08:09".to_string())));
+	assert_eq!(metadata.get_tag(&ExifTag::FNumber(Vec::new())), Some(&ExifTag::FNumber(vec![28, 10])));
+	assert!(metadata.get_tag(&ExifTag::StripOffsets(Vec::new())).is_none());
+
+	// Detected by its signature, not as TIFF
+	assert_eq!(Metadata::new_from_vec(&original, FileExtension::ORF)?.data(), metadata.data());
+	assert!(Metadata::new_from_vec(&std::fs::read("tests/sample.tif")?, FileExtension::ORF).is_err());
+
+	// Writing and clearing are not supported and leave the file untouched
+	let mut file_buffer = original.clone();
+	let error = metadata.write_to_vec(&mut file_buffer, FileExtension::ORF).err().unwrap();
+	assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+	let error = Metadata::clear_metadata(&mut file_buffer, FileExtension::ORF).err().unwrap();
+	assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+	assert_eq!(file_buffer, original);
+
+	Ok(())
+}
+
+#[test]
+fn
+read_rw2()
+-> Result<(), std::io::Error>
+{
+	// IFD0 of the sample starts with Panasonic's own tags, the header with
+	// "IIU\0" instead of "II*\0"
+	let path = Path::new("tests/sample.rw2");
+	let original = std::fs::read(path)?;
+	let metadata = Metadata::new_from_path(path)?;
+	assert_eq!(metadata.get_tag(&ExifTag::Make(String::new())), Some(&ExifTag::Make("Panasonic".to_string())));
+	assert_eq!(metadata.get_tag(&ExifTag::Model(String::new())), Some(&ExifTag::Model("DC-GH5".to_string())));
+	assert_eq!(metadata.get_tag(&ExifTag::ISO(Vec::new())), Some(&ExifTag::ISO(vec![400])));
+	assert_eq!(metadata.get_tag(&ExifTag::ExposureTime(Vec::new())), Some(&ExifTag::ExposureTime(vec![1, 60])));
+	assert_eq!(metadata.get_tag_by_hex(0x0017).map(|tag| tag.get_group()), Some(ExifTagGroup::IFD0));
+	assert!(metadata.get_tag(&ExifTag::StripOffsets(Vec::new())).is_none());
+
+	// Detected by its signature, not as TIFF
+	assert_eq!(Metadata::new_from_vec(&original, FileExtension::RW2)?.data(), metadata.data());
+	assert!(Metadata::new_from_vec(&std::fs::read("tests/sample.tif")?, FileExtension::RW2).is_err());
+	assert!(Metadata::new_from_vec(&std::fs::read("tests/sample.orf")?, FileExtension::RW2).is_err());
+
+	// Writing and clearing are not supported and leave the file untouched
+	let mut file_buffer = original.clone();
+	let error = metadata.write_to_vec(&mut file_buffer, FileExtension::RW2).err().unwrap();
+	assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+	let error = Metadata::clear_metadata(&mut file_buffer, FileExtension::RW2).err().unwrap();
+	assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+	assert_eq!(file_buffer, original);
+
+	Ok(())
+}
+
+#[test]
+fn
+read_raf()
+-> Result<(), std::io::Error>
+{
+	// The EXIF data of the sample is stored in the JPEG preview, whose
+	// offset and length are given by the RAF header
+	let path = Path::new("tests/sample.raf");
+	let original = std::fs::read(path)?;
+	let metadata = Metadata::new_from_path(path)?;
+	assert_eq!(metadata.get_tag(&ExifTag::Make(String::new())), Some(&ExifTag::Make("FUJIFILM".to_string())));
+	assert_eq!(metadata.get_tag(&ExifTag::Model(String::new())), Some(&ExifTag::Model("X-T4".to_string())));
+	assert_eq!(metadata.get_tag(&ExifTag::ISO(Vec::new())), Some(&ExifTag::ISO(vec![200])));
+	assert_eq!(metadata.get_tag(&ExifTag::FNumber(Vec::new())), Some(&ExifTag::FNumber(vec![56, 10])));
+
+	// Detected by its magic, not as JPEG
+	assert_eq!(Metadata::new_from_vec(&original, FileExtension::RAF)?.data(), metadata.data());
+	assert!(Metadata::new_from_vec(&std::fs::read("tests/sample2.jpg")?, FileExtension::RAF).is_err());
+
+	// A preview reaching past the end of the file is rejected
+	let mut truncated = original.clone();
+	truncated.truncate(0x100);
+	let error = Metadata::new_from_vec(&truncated, FileExtension::RAF).err().unwrap();
+	assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+	// Writing and clearing are not supported and leave the file untouched
+	let mut file_buffer = original.clone();
+	let error = metadata.write_to_vec(&mut file_buffer, FileExtension::RAF).err().unwrap();
+	assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+	let error = Metadata::clear_metadata(&mut file_buffer, FileExtension::RAF).err().unwrap();
+	assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+	assert_eq!(file_buffer, original);
+
+	Ok(())
+}
+
+#[test]
+fn
+read_and_write_avi()
+-> Result<(), std::io::Error>
+{
+	// The sample has the recording time in IDIT, the camera in LIST exif
+	// and the firmware in LIST INFO, followed by JUNK padding
+	let path = Path::new("tests/sample.avi");
+	let original = std::fs::read(path)?;
+	let metadata = Metadata::new_from_path(path)?;
+	assert_eq!(metadata.get_tag(&ExifTag::Make(String::new())), Some(&ExifTag::Make("FUJIFILM".to_string())));
+	assert_eq!(metadata.get_tag(&ExifTag::Model(String::new())), Some(&ExifTag::Model("FinePix F10".to_string())));
+	assert_eq!(metadata.get_tag(&ExifTag::Software(String::new())), Some(&ExifTag::Software("FinePix F10 Ver1.00".to_string())));
+	assert_eq!(metadata.get_tag(&ExifTag::DateTimeOriginal(String::new())), Some(&ExifTag::DateTimeOriginal("2006:10:26 16:46:04".to_string())));
+	assert_eq!(Metadata::new_from_vec(&original, FileExtension::AVI)?.data(), metadata.data());
+	assert!(Metadata::new_from_vec(&std::fs::read("tests/read_sample.webp")?, FileExtension::AVI).is_err());
+
+	// The JUNK chunk absorbs the additional data, IDIT keeps its format and
+	// the sub-chunks that are not mapped to tags are kept
+	let movie_position = |data: &[u8]| data.windows(4).position(|window| window == b"movi").unwrap();
+	let mut changed = Metadata::new();
+	for tag in metadata.data().iter().filter(|tag| !matches!(tag, ExifTag::Software(_)))
+	{
+		changed.set_tag(tag.clone());
+	}
+	let mut metadata = changed;
+	metadata.set_tag(ExifTag::DateTimeOriginal("2007:01:02 03:04:05".to_string()));
+	metadata.set_tag(ExifTag::Artist("Jane Doe".to_string()));
+	let mut file_buffer = original.clone();
+	metadata.write_to_vec(&mut file_buffer, FileExtension::AVI)?;
+	assert_eq!(file_buffer.len(), original.len());
+	assert_eq!(movie_position(&file_buffer), movie_position(&original));
+	assert!(file_buffer.windows(24).any(|window| window == b"TUE JAN 02 03:04:05 2007"));
+	assert!(file_buffer.windows(4).any(|window| window == b"INAM"));
+
+	let written = Metadata::new_from_vec(&file_buffer, FileExtension::AVI)?;
+	assert_eq!(written.get_tag(&ExifTag::DateTimeOriginal(String::new())), Some(&ExifTag::DateTimeOriginal("2007:01:02 03:04:05".to_string())));
+	assert_eq!(written.get_tag(&ExifTag::Artist(String::new())), Some(&ExifTag::Artist("Jane Doe".to_string())));
+	assert_eq!(written.get_tag(&ExifTag::Make(String::new())), Some(&ExifTag::Make("FUJIFILM".to_string())));
+	assert!(written.get_tag(&ExifTag::Software(String::new())).is_none());
+
+	// More data than fits moves the movie data, unless the index holds
+	// absolute offsets
+	metadata.set_tag(ExifTag::ImageDescription("A".repeat(300)));
+	let mut file_buffer = original.clone();
+	metadata.write_to_vec(&mut file_buffer, FileExtension::AVI)?;
+	assert!(movie_position(&file_buffer) > movie_position(&original));
+	assert_eq!(Metadata::new_from_vec(&file_buffer, FileExtension::AVI)?.get_tag(&ExifTag::ImageDescription(String::new())), Some(&ExifTag::ImageDescription("A".repeat(300))));
+
+	let mut absolute = original.clone();
+	let index_offset = absolute.len() - 8;
+	absolute[index_offset..index_offset + 4].copy_from_slice(&(movie_position(&original) as u32 + 4).to_le_bytes());
+	let mut file_buffer = absolute.clone();
+	let error = metadata.write_to_vec(&mut file_buffer, FileExtension::AVI).err().unwrap();
+	assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+	assert_eq!(file_buffer, absolute);
+
+	// Clearing removes the mapped sub-chunks and the recording time
+	let mut file_buffer = original.clone();
+	Metadata::clear_metadata(&mut file_buffer, FileExtension::AVI)?;
+	assert_eq!(file_buffer.len(), original.len());
+	assert!(Metadata::new_from_vec(&file_buffer, FileExtension::AVI)?.data().is_empty());
+	assert!(file_buffer.windows(4).any(|window| window == b"INAM"));
+
+	Ok(())
+}